@@ -9,11 +9,22 @@ extern "C" {
 #[derive(Clone, Debug, serde::Deserialize)]
 pub(crate) struct Config {
     pub(crate) username: Option<String>,
-    pub(crate) public_address: String,
     pub(crate) allowed_login_methods:
         std::collections::HashSet<crate::protocol::AuthType>,
     pub(crate) oauth_login_urls:
         std::collections::HashMap<crate::protocol::AuthType, String>,
+    #[serde(default)]
+    pub(crate) jitter_buffer_ms: f64,
+    #[serde(default)]
+    pub(crate) recordings: bool,
+    #[serde(default)]
+    pub(crate) embed: Option<String>,
+    #[serde(default)]
+    pub(crate) list_username: Option<String>,
+    #[serde(default)]
+    pub(crate) list_room: Option<String>,
+    #[serde(default)]
+    pub(crate) base: String,
 }
 
 impl Config {
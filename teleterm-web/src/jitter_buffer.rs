@@ -0,0 +1,72 @@
+// this mirrors teleterm's jitter buffer, but works in milliseconds since
+// std::time::Instant isn't available here
+
+// gaps longer than this are the streamer pausing rather than the network
+// being uneven, so they shouldn't change how we pace things
+const IDLE_GAP: f64 = 1000.0;
+// how much each new arrival moves the running estimates
+const SMOOTHING: f64 = 1.0 / 16.0;
+// how many multiples of the measured jitter to hold messages back for
+const JITTER_FACTOR: f64 = 2.0;
+
+pub(crate) struct JitterBuffer<T> {
+    max_delay: f64,
+    items: std::collections::VecDeque<(f64, T)>,
+    last_arrival: Option<f64>,
+    last_due: Option<f64>,
+    gap: f64,
+    jitter: f64,
+}
+
+impl<T> JitterBuffer<T> {
+    pub(crate) fn new(max_delay: f64) -> Self {
+        Self {
+            max_delay,
+            items: std::collections::VecDeque::new(),
+            last_arrival: None,
+            last_due: None,
+            gap: 0.0,
+            jitter: 0.0,
+        }
+    }
+
+    // returns how many milliseconds from now the item should be played
+    pub(crate) fn push(&mut self, item: T) -> f64 {
+        let now = js_sys::Date::now();
+
+        if let Some(last_arrival) = self.last_arrival {
+            let gap = now - last_arrival;
+            if gap < IDLE_GAP {
+                self.jitter = smooth(self.jitter, (gap - self.gap).abs());
+                self.gap = smooth(self.gap, gap);
+            }
+        }
+        self.last_arrival = Some(now);
+
+        let mut due = now + (self.jitter * JITTER_FACTOR).min(self.max_delay);
+        if let Some(last_due) = self.last_due {
+            let paced = (last_due + self.gap).min(now + self.max_delay);
+            if paced > due {
+                due = paced;
+            }
+        }
+        self.last_due = Some(due);
+
+        self.items.push_back((due, item));
+        due - now
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        let now = js_sys::Date::now();
+        if let Some((due, _)) = self.items.front() {
+            if *due <= now {
+                return self.items.pop_front().map(|(_, item)| item);
+            }
+        }
+        None
+    }
+}
+
+fn smooth(average: f64, sample: f64) -> f64 {
+    (sample - average).mul_add(SMOOTHING, average)
+}
@@ -1,4 +1,5 @@
 mod config;
+mod jitter_buffer;
 mod model;
 mod prelude;
 mod protocol;
@@ -14,9 +15,16 @@ enum Msg {
     LoggedIn(seed::fetch::ResponseDataResult<crate::protocol::LoginResponse>),
     Refresh,
     List(seed::fetch::ResponseDataResult<Vec<crate::protocol::Session>>),
+    ChooseRoom(Option<String>),
     StartWatching(String),
     Watch(String, crate::ws::WebSocketEvent),
+    PlayBuffered,
     StopWatching,
+    ListRecordings,
+    Recordings(
+        seed::fetch::ResponseDataResult<Vec<crate::protocol::Recording>>,
+    ),
+    StartPlaying(String),
     Logout,
     LoggedOut(seed::fetch::FetchObject<()>),
 }
@@ -1,17 +1,33 @@
 use crate::prelude::*;
 
 struct WatchConn {
+    // unset when playing back a recording
+    id: Option<String>,
     ws: WebSocket,
     term: vt100::Parser,
+    terminal: crate::views::terminal::Terminal,
     received_data: bool,
+    // the last notice from the server
+    notice: Option<String>,
+    jitter_buffer:
+        Option<crate::jitter_buffer::JitterBuffer<crate::protocol::Message>>,
 }
 
 impl WatchConn {
-    fn new(ws: WebSocket) -> Self {
+    fn new(id: Option<&str>, ws: WebSocket, jitter_buffer_ms: f64) -> Self {
+        let jitter_buffer = if jitter_buffer_ms > 0.0 {
+            Some(crate::jitter_buffer::JitterBuffer::new(jitter_buffer_ms))
+        } else {
+            None
+        };
         Self {
+            id: id.map(std::string::ToString::to_string),
             ws,
             term: vt100::Parser::default(),
+            terminal: crate::views::terminal::Terminal::default(),
             received_data: false,
+            notice: None,
+            jitter_buffer,
         }
     }
 }
@@ -26,12 +42,15 @@ impl Drop for WatchConn {
 enum State {
     Login,
     List(Vec<crate::protocol::Session>),
+    Recordings(Vec<crate::protocol::Recording>),
     Watch(WatchConn),
 }
 
 pub(crate) struct Model {
     config: crate::config::Config,
     state: State,
+    // the room whose streams are being listed, if one has been chosen
+    room: Option<String>,
 }
 
 impl Model {
@@ -40,11 +59,15 @@ impl Model {
         orders: &mut impl Orders<crate::Msg>,
     ) -> Self {
         let logged_in = config.username.is_some();
-        let self_ = Self {
+        let embed = config.embed.clone();
+        let mut self_ = Self {
+            room: config.list_room.clone(),
             config,
             state: State::Login,
         };
-        if logged_in {
+        if let Some(id) = embed {
+            self_.embed(&id, orders);
+        } else if logged_in {
             self_.list(orders);
         }
         self_
@@ -83,6 +106,11 @@ impl Model {
                     log::error!("error getting sessions: {:?}", e);
                 }
             },
+            crate::Msg::ChooseRoom(room) => {
+                log::debug!("choosing room {:?}", room);
+                self.room = room;
+                self.list(orders);
+            }
             crate::Msg::StartWatching(id) => {
                 log::debug!("watching {}", id);
                 self.watch(&id, orders);
@@ -99,26 +127,55 @@ impl Model {
                     let json = msg.data().as_string().unwrap();
                     let msg: crate::protocol::Message =
                         serde_json::from_str(&json).unwrap();
-                    match msg {
-                        crate::protocol::Message::TerminalOutput { data } => {
-                            self.process(&data);
-                        }
-                        crate::protocol::Message::Disconnected => {
-                            self.list(orders);
-                        }
-                        crate::protocol::Message::Resize { size } => {
-                            self.set_size(size.rows, size.cols);
-                        }
+                    if let State::Watch(WatchConn {
+                        jitter_buffer: Some(jitter_buffer),
+                        ..
+                    }) = &mut self.state
+                    {
+                        let delay = jitter_buffer.push(msg);
+                        play_buffered_after(delay, orders);
+                    } else {
+                        self.server_message(msg, orders);
                     }
                 }
                 crate::ws::WebSocketEvent::Error(e) => {
                     log::error!("{}: error: {:?}", id, e);
                 }
             },
+            crate::Msg::PlayBuffered => {
+                while let State::Watch(WatchConn {
+                    jitter_buffer: Some(jitter_buffer),
+                    ..
+                }) = &mut self.state
+                {
+                    if let Some(msg) = jitter_buffer.pop() {
+                        self.server_message(msg, orders);
+                    } else {
+                        break;
+                    }
+                }
+            }
             crate::Msg::StopWatching => {
                 log::debug!("stop watching");
                 self.list(orders);
             }
+            crate::Msg::ListRecordings => {
+                log::debug!("listing recordings");
+                self.list_recordings(orders);
+            }
+            crate::Msg::Recordings(recordings) => match recordings {
+                Ok(recordings) => {
+                    log::debug!("got recordings");
+                    self.state = State::Recordings(recordings);
+                }
+                Err(e) => {
+                    log::error!("error getting recordings: {:?}", e);
+                }
+            },
+            crate::Msg::StartPlaying(id) => {
+                log::debug!("playing {}", id);
+                self.play(&id, orders);
+            }
             crate::Msg::Logout => {
                 log::debug!("logout");
                 self.logout(orders);
@@ -147,6 +204,14 @@ impl Model {
         }
     }
 
+    pub(crate) fn browsing_recordings(&self) -> bool {
+        if let State::Recordings(..) = self.state {
+            true
+        } else {
+            false
+        }
+    }
+
     pub(crate) fn watching(&self) -> bool {
         if let State::Watch(..) = self.state {
             true
@@ -155,6 +220,18 @@ impl Model {
         }
     }
 
+    pub(crate) fn embedded(&self) -> bool {
+        self.config.embed.is_some()
+    }
+
+    pub(crate) fn watch_id(&self) -> Option<&str> {
+        if let State::Watch(conn) = &self.state {
+            conn.id.as_ref().map(|s| s.as_str())
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn username(&self) -> Option<&str> {
         self.config.username.as_ref().map(|s| s.as_str())
     }
@@ -167,9 +244,37 @@ impl Model {
         }
     }
 
-    pub(crate) fn screen(&self) -> Option<&vt100::Screen> {
+    pub(crate) fn room(&self) -> Option<&str> {
+        self.room.as_ref().map(|s| s.as_str())
+    }
+
+    // true on the pages for a single user or room, which don't allow
+    // browsing away from them
+    pub(crate) fn list_is_fixed(&self) -> bool {
+        self.config.list_username.is_some() || self.config.list_room.is_some()
+    }
+
+    pub(crate) fn base(&self) -> &str {
+        &self.config.base
+    }
+
+    pub(crate) fn recordings(&self) -> &[crate::protocol::Recording] {
+        if let State::Recordings(recordings) = &self.state {
+            recordings
+        } else {
+            &[]
+        }
+    }
+
+    pub(crate) fn recordings_enabled(&self) -> bool {
+        self.config.recordings
+    }
+
+    pub(crate) fn terminal(
+        &self,
+    ) -> Option<&crate::views::terminal::Terminal> {
         if let State::Watch(conn) = &self.state {
-            Some(conn.term.screen())
+            Some(&conn.terminal)
         } else {
             None
         }
@@ -183,6 +288,14 @@ impl Model {
         }
     }
 
+    pub(crate) fn notice(&self) -> Option<&str> {
+        if let State::Watch(conn) = &self.state {
+            conn.notice.as_ref().map(|s| s.as_str())
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn allowed_login_method(
         &self,
         ty: crate::protocol::AuthType,
@@ -197,40 +310,112 @@ impl Model {
         self.config.oauth_login_urls.get(&ty).map(|s| s.as_str())
     }
 
+    // pages other than the main one aren't at the root, but everything
+    // else is
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.base, path)
+    }
+
     fn login(&self, username: &str, orders: &mut impl Orders<crate::Msg>) {
-        let url = format!(
-            "http://{}/login?username={}",
-            self.config.public_address, username
-        );
+        let url = self.url(&format!("login?username={}", username));
         orders.perform_cmd(
             seed::Request::new(url).fetch_json_data(crate::Msg::LoggedIn),
         );
     }
 
     fn list(&self, orders: &mut impl Orders<crate::Msg>) {
-        let url = format!("http://{}/list", self.config.public_address);
+        let mut params = vec![];
+        if let Some(room) = &self.room {
+            params.push(format!("room={}", encode(room)));
+        }
+        if let Some(username) = &self.config.list_username {
+            params.push(format!("username={}", encode(username)));
+        }
+        let url = if params.is_empty() {
+            self.url("list")
+        } else {
+            self.url(&format!("list?{}", params.join("&")))
+        };
         orders.perform_cmd(
             seed::Request::new(url).fetch_json_data(crate::Msg::List),
         );
     }
 
     fn watch(&mut self, id: &str, orders: &mut impl Orders<crate::Msg>) {
-        let url =
-            format!("ws://{}/watch?id={}", self.config.public_address, id);
+        let url = crate::ws::url(&self.url(&format!("watch?id={}", id)));
+        let ws = crate::ws::connect(&url, id, crate::Msg::Watch, orders);
+        self.state = State::Watch(WatchConn::new(
+            Some(id),
+            ws,
+            self.config.jitter_buffer_ms,
+        ));
+    }
+
+    // embedded pages watch the stream through the page's own url, which
+    // doesn't require being logged in
+    fn embed(&mut self, id: &str, orders: &mut impl Orders<crate::Msg>) {
+        let url = crate::ws::url(id);
+        let ws = crate::ws::connect(&url, id, crate::Msg::Watch, orders);
+        self.state = State::Watch(WatchConn::new(
+            Some(id),
+            ws,
+            self.config.jitter_buffer_ms,
+        ));
+    }
+
+    fn list_recordings(&self, orders: &mut impl Orders<crate::Msg>) {
+        let url = self.url("recordings");
+        orders.perform_cmd(
+            seed::Request::new(url).fetch_json_data(crate::Msg::Recordings),
+        );
+    }
+
+    // recordings are sent with their original timing already, so there is
+    // no need to buffer them
+    fn play(&mut self, id: &str, orders: &mut impl Orders<crate::Msg>) {
+        let url = crate::ws::url(&self.url(&format!("recordings/{}", id)));
         let ws = crate::ws::connect(&url, id, crate::Msg::Watch, orders);
-        self.state = State::Watch(WatchConn::new(ws));
+        self.state = State::Watch(WatchConn::new(None, ws, 0.0));
     }
 
     fn logout(&self, orders: &mut impl Orders<crate::Msg>) {
-        let url = format!("http://{}/logout", self.config.public_address);
+        let url = self.url("logout");
         orders.perform_cmd(
             seed::Request::new(url).fetch(crate::Msg::LoggedOut),
         );
     }
 
+    fn server_message(
+        &mut self,
+        msg: crate::protocol::Message,
+        orders: &mut impl Orders<crate::Msg>,
+    ) {
+        match msg {
+            crate::protocol::Message::TerminalOutput { data } => {
+                self.process(&data);
+            }
+            // embedded pages have nothing else to show, so they keep the
+            // last screen of the stream up
+            crate::protocol::Message::Disconnected => {
+                if !self.embedded() {
+                    self.list(orders);
+                }
+            }
+            crate::protocol::Message::Resize { size } => {
+                self.set_size(size.rows, size.cols);
+            }
+            crate::protocol::Message::Notice { text } => {
+                if let State::Watch(conn) = &mut self.state {
+                    conn.notice = Some(text);
+                }
+            }
+        }
+    }
+
     fn process(&mut self, bytes: &[u8]) {
         if let State::Watch(conn) = &mut self.state {
             conn.term.process(bytes);
+            conn.terminal.update(conn.term.screen());
             conn.received_data = true;
         }
     }
@@ -238,6 +423,24 @@ impl Model {
     fn set_size(&mut self, rows: u16, cols: u16) {
         if let State::Watch(conn) = &mut self.state {
             conn.term.set_size(rows, cols);
+            conn.terminal.update(conn.term.screen());
         }
     }
 }
+
+// each buffered message gets its own timeout, so every one of them is
+// played once it is due
+fn play_buffered_after(delay: f64, orders: &mut impl Orders<crate::Msg>) {
+    let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+    let delay = delay.ceil() as i32;
+    seed::set_timeout(
+        Box::new(move || {
+            app.update(msg_mapper(crate::Msg::PlayBuffered));
+        }),
+        delay,
+    );
+}
+
+fn encode(s: &str) -> String {
+    js_sys::encode_uri_component(s).into()
+}
@@ -6,6 +6,8 @@
 pub enum AuthType {
     Plain,
     RecurseCenter,
+    Github,
+    Google,
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -13,6 +15,7 @@ pub(crate) enum Message {
     TerminalOutput { data: Vec<u8> },
     Disconnected,
     Resize { size: Size },
+    Notice { text: String },
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -23,7 +26,19 @@ pub(crate) struct Session {
     pub size: Size,
     pub idle_time: u32,
     pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub room: String,
+    pub paused: bool,
     pub watchers: u32,
+    pub start_time: u32,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub(crate) struct Recording {
+    pub id: String,
+    pub size: u64,
+    pub modified: u32,
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
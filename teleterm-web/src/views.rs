@@ -1,6 +1,7 @@
 pub(crate) mod list;
 pub(crate) mod login;
 pub(crate) mod page;
+pub(crate) mod recordings;
 pub(crate) mod sessions;
 pub(crate) mod terminal;
 pub(crate) mod watch;
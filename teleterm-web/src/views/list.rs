@@ -1,8 +1,92 @@
 use crate::prelude::*;
 
 pub(crate) fn render(model: &crate::model::Model) -> Vec<Node<crate::Msg>> {
-    vec![
-        crate::views::sessions::render(model.sessions()),
-        seed::button![simple_ev(Ev::Click, crate::Msg::Refresh), "refresh"],
+    let sessions = model.sessions();
+    // the page for a single user lists their streams from every room
+    let mut view = if model.room().is_none()
+        && !model.list_is_fixed()
+        && sessions.iter().any(|session| !session.room.is_empty())
+    {
+        vec![rooms(sessions, model.base())]
+    } else {
+        vec![crate::views::sessions::render(sessions, model.base())]
+    };
+    view.push(seed::button![
+        simple_ev(Ev::Click, crate::Msg::Refresh),
+        "refresh"
+    ]);
+    if model.room().is_some() && !model.list_is_fixed() {
+        view.push(seed::button![
+            simple_ev(Ev::Click, crate::Msg::ChooseRoom(None)),
+            "rooms"
+        ]);
+    }
+    if model.recordings_enabled() {
+        view.push(seed::button![
+            simple_ev(Ev::Click, crate::Msg::ListRecordings),
+            "recordings"
+        ]);
+    }
+    view
+}
+
+// the same grouping that the session list in the terminal uses, with the
+// streams which aren't in a room last
+fn rooms(
+    sessions: &[crate::protocol::Session],
+    base: &str,
+) -> Node<crate::Msg> {
+    let mut rooms: Vec<(&str, usize, u32)> = vec![];
+    for session in sessions {
+        if let Some(room) =
+            rooms.iter_mut().find(|(name, ..)| *name == session.room)
+        {
+            room.1 += 1;
+            room.2 += session.watchers;
+        } else {
+            rooms.push((&session.room, 1, session.watchers));
+        }
+    }
+    rooms.sort_by_key(|(name, ..)| (name.is_empty(), *name));
+
+    let rows: Vec<_> = rooms
+        .into_iter()
+        .map(|(name, streams, watchers)| {
+            let choose = || {
+                simple_ev(
+                    Ev::Click,
+                    crate::Msg::ChooseRoom(Some(name.to_string())),
+                )
+            };
+            // streams outside of a room don't have a page of their own
+            let link = if name.is_empty() {
+                seed::a![
+                    seed::attrs! {At::Href => "#"},
+                    choose(),
+                    "(no room)"
+                ]
+            } else {
+                let room_url = format!(
+                    "{}r/{}",
+                    base,
+                    String::from(js_sys::encode_uri_component(name))
+                );
+                seed::a![seed::attrs! {At::Href => room_url}, name]
+            };
+            seed::tr![
+                seed::td![link],
+                seed::td![choose(), format!("{}", streams)],
+                seed::td![choose(), format!("{}", watchers)],
+            ]
+        })
+        .collect();
+    seed::table![
+        seed::attrs! { At::Class => "list" },
+        seed::tr![
+            seed::th!["room"],
+            seed::th!["streams"],
+            seed::th!["watchers"],
+        ],
+        rows
     ]
 }
@@ -1,6 +1,10 @@
 use crate::prelude::*;
 
 pub(crate) fn render(model: &crate::model::Model) -> Vec<Node<crate::Msg>> {
+    if model.embedded() {
+        return super::watch::render(model);
+    }
+
     let mut view = vec![seed::h1!["teleterm"]];
 
     if let Some(username) = model.username() {
@@ -21,6 +25,8 @@ pub(crate) fn render(model: &crate::model::Model) -> Vec<Node<crate::Msg>> {
         }
     } else if model.choosing() {
         view.extend(super::list::render(model))
+    } else if model.browsing_recordings() {
+        view.extend(super::recordings::render(model))
     } else if model.watching() {
         view.extend(super::watch::render(model))
     } else {
@@ -0,0 +1,52 @@
+use crate::prelude::*;
+
+pub(crate) fn render(model: &crate::model::Model) -> Vec<Node<crate::Msg>> {
+    let rows: Vec<_> = model.recordings().iter().map(row).collect();
+    vec![
+        seed::table![
+            seed::attrs! { At::Class => "list" },
+            seed::tr![
+                seed::th!["name"],
+                seed::th!["size"],
+                seed::th!["modified"],
+                seed::th![],
+            ],
+            rows
+        ],
+        seed::button![simple_ev(Ev::Click, crate::Msg::Refresh), "back"],
+    ]
+}
+
+fn row(recording: &crate::protocol::Recording) -> Node<crate::Msg> {
+    seed::tr![
+        seed::td![seed::a![
+            simple_ev(
+                Ev::Click,
+                crate::Msg::StartPlaying(recording.id.clone())
+            ),
+            seed::attrs! {At::Href => "#"},
+            recording.id
+        ]],
+        seed::td![format_size(recording.size)],
+        seed::td![crate::views::sessions::format_local_time(
+            recording.modified
+        )],
+        seed::td![seed::a![
+            seed::attrs! {
+                At::Href => format!("recordings/{}", recording.id),
+                At::Download => recording.id,
+            },
+            "download"
+        ]],
+    ]
+}
+
+fn format_size(size: u64) -> String {
+    if size < 1024 {
+        format!("{}B", size)
+    } else if size < 1024 * 1024 {
+        format!("{}KiB", size / 1024)
+    } else {
+        format!("{}MiB", size / 1024 / 1024)
+    }
+}
@@ -1,9 +1,15 @@
 use crate::prelude::*;
 
+// streams which have produced output more recently than this (in seconds)
+// are highlighted
+const ACTIVE_IDLE_TIME: u32 = 5;
+
 pub(crate) fn render(
     sessions: &[crate::protocol::Session],
+    base: &str,
 ) -> Node<crate::Msg> {
-    let rows: Vec<_> = sessions.iter().map(row).collect();
+    let rows: Vec<_> =
+        sessions.iter().map(|session| row(session, base)).collect();
     seed::table![
         seed::attrs! { At::Class => "list" },
         seed::tr![
@@ -11,23 +17,74 @@ pub(crate) fn render(
             seed::th!["size"],
             seed::th!["idle"],
             seed::th!["watchers"],
+            seed::th!["started"],
             seed::th!["title"],
+            seed::th!["description"],
         ],
         rows
     ]
 }
 
-fn row(session: &crate::protocol::Session) -> Node<crate::Msg> {
+// the username links to the page listing only that user's streams, and
+// clicking anywhere else in the row watches the stream
+fn row(session: &crate::protocol::Session, base: &str) -> Node<crate::Msg> {
+    let class = if session.idle_time < ACTIVE_IDLE_TIME {
+        "active"
+    } else {
+        ""
+    };
+    let watch = || {
+        simple_ev(Ev::Click, crate::Msg::StartWatching(session.id.clone()))
+    };
+    let user_url = format!(
+        "{}u/{}",
+        base,
+        String::from(js_sys::encode_uri_component(&session.username))
+    );
     seed::tr![
-        simple_ev(Ev::Click, crate::Msg::StartWatching(session.id.clone())),
-        seed::td![seed::a![seed::attrs! {At::Href => "#"}, session.username]],
-        seed::td![format!("{}x{}", session.size.cols, session.size.rows)],
-        seed::td![format_time(session.idle_time)],
-        seed::td![format!("{}", session.watchers)],
-        seed::td![session.title],
+        seed::attrs! { At::Class => class },
+        seed::td![seed::a![
+            seed::attrs! {At::Href => user_url},
+            session.username
+        ]],
+        seed::td![
+            watch(),
+            format!("{}x{}", session.size.cols, session.size.rows)
+        ],
+        seed::td![watch(), format_time(session.idle_time)],
+        seed::td![watch(), format!("{}", session.watchers)],
+        seed::td![watch(), format_local_time(session.start_time)],
+        seed::td![
+            watch(),
+            seed::a![seed::attrs! {At::Href => "#"}, title(session)]
+        ],
+        seed::td![watch(), session.description],
     ]
 }
 
+// the same format that the session list in the terminal uses
+fn title(session: &crate::protocol::Session) -> String {
+    let title = if session.tags.is_empty() {
+        session.title.clone()
+    } else {
+        format!("{} [{}]", session.title, session.tags.join(", "))
+    };
+    if session.paused {
+        format!("(paused) {}", title)
+    } else {
+        title
+    }
+}
+
+// formatted by the browser, so it uses the viewer's time zone
+pub(crate) fn format_local_time(time: u32) -> String {
+    let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(
+        f64::from(time) * 1000.0,
+    ));
+    date.to_locale_string("default", &wasm_bindgen::JsValue::UNDEFINED)
+        .into()
+}
+
 // XXX copied from teleterm
 fn format_time(dur: u32) -> String {
     let secs = dur % 60;
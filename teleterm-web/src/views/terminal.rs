@@ -1,41 +1,86 @@
 use crate::prelude::*;
 use unicode_width::UnicodeWidthStr as _;
 
-pub(crate) fn render(screen: &vt100::Screen) -> Node<crate::Msg> {
-    let (rows, cols) = screen.size();
-    let (cursor_row, cursor_col) = screen.cursor_position();
+// keeps the rendered rows of the screen around between updates, so that
+// each new message only has to rebuild the rows that it actually changed.
+// vt100 has already applied things like scroll regions to the cell grid, so
+// all we need to do is draw it.
+#[derive(Default)]
+pub(crate) struct Terminal {
+    rows: Vec<Row>,
+}
+
+struct Row {
+    cells: Vec<vt100::Cell>,
+    cursor: Option<u16>,
+    node: Node<crate::Msg>,
+}
 
-    let mut grid = vec![];
-    for row_idx in 0..rows {
-        let mut row = vec![];
-        for col_idx in 0..cols {
-            let cell = screen.cell(row_idx, col_idx).unwrap();
+impl Terminal {
+    pub(crate) fn update(&mut self, screen: &vt100::Screen) {
+        let (rows, cols) = screen.size();
+        let (cursor_row, cursor_col) = screen.cursor_position();
 
-            let mut contents = cell.contents();
-            // if we don't use a non-breaking space for cells with no
-            // foreground contents, the table layout may just collapse those
-            // cells. we can't just set a fixed height because there's no way
-            // (that i'm aware of) in css to set a box to have a fixed height
-            // that is the same as the line height of the current font.
-            if contents.trim().is_empty() || contents.width() == 0 {
-                contents = "\u{00a0}".to_string();
+        self.rows.truncate(usize::from(rows));
+        for row_idx in 0..rows {
+            let cells: Vec<_> = (0..cols)
+                .map(|col_idx| screen.cell(row_idx, col_idx).unwrap().clone())
+                .collect();
+            let cursor = if cursor_row == row_idx && !screen.hide_cursor() {
+                Some(cursor_col)
+            } else {
+                None
+            };
+
+            if let Some(row) = self.rows.get(usize::from(row_idx)) {
+                if row.cells == cells && row.cursor == cursor {
+                    continue;
+                }
             }
 
-            row.push(seed::td![
-                seed::attrs! { At::Class => "cell" },
-                style_for_cell(
-                    cell,
-                    cursor_row == row_idx
-                        && cursor_col == col_idx
-                        && !screen.hide_cursor()
-                ),
-                contents
-            ])
+            let row = Row {
+                node: render_row(&cells, cursor),
+                cells,
+                cursor,
+            };
+            if let Some(old) = self.rows.get_mut(usize::from(row_idx)) {
+                *old = row;
+            } else {
+                self.rows.push(row);
+            }
         }
-        grid.push(seed::tr![seed::attrs! { At::Class => "row" }, row]);
     }
 
-    seed::table![seed::attrs! { At::Class => "grid" }, grid]
+    pub(crate) fn render(&self) -> Node<crate::Msg> {
+        let grid: Vec<_> =
+            self.rows.iter().map(|row| row.node.clone()).collect();
+        seed::table![seed::attrs! { At::Class => "grid" }, grid]
+    }
+}
+
+fn render_row(
+    cells: &[vt100::Cell],
+    cursor: Option<u16>,
+) -> Node<crate::Msg> {
+    let mut row = vec![];
+    for (col_idx, cell) in cells.iter().enumerate() {
+        let mut contents = cell.contents();
+        // if we don't use a non-breaking space for cells with no
+        // foreground contents, the table layout may just collapse those
+        // cells. we can't just set a fixed height because there's no way
+        // (that i'm aware of) in css to set a box to have a fixed height
+        // that is the same as the line height of the current font.
+        if contents.trim().is_empty() || contents.width() == 0 {
+            contents = "\u{00a0}".to_string();
+        }
+
+        row.push(seed::td![
+            seed::attrs! { At::Class => "cell" },
+            style_for_cell(cell, cursor.map(usize::from) == Some(col_idx)),
+            contents
+        ])
+    }
+    seed::tr![seed::attrs! { At::Class => "row" }, row]
 }
 
 fn style_for_cell(
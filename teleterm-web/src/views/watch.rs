@@ -1,16 +1,41 @@
 use crate::prelude::*;
 
 pub(crate) fn render(model: &crate::model::Model) -> Vec<Node<crate::Msg>> {
-    vec![
-        if let Some(screen) = model.screen() {
-            if model.received_data() {
-                crate::views::terminal::render(screen)
-            } else {
-                seed::empty![]
-            }
+    let mut view = vec![if let Some(terminal) = model.terminal() {
+        if model.received_data() {
+            terminal.render()
         } else {
             seed::empty![]
-        },
-        seed::button![simple_ev(Ev::Click, crate::Msg::StopWatching), "back"],
-    ]
+        }
+    } else {
+        seed::empty![]
+    }];
+    if model.embedded() {
+        return view;
+    }
+
+    if let Some(notice) = model.notice() {
+        view.insert(
+            0,
+            seed::div![
+                seed::attrs! { At::Class => "notice" },
+                format!("server notice: {}", notice)
+            ],
+        );
+    }
+    view.push(seed::button![
+        simple_ev(Ev::Click, crate::Msg::StopWatching),
+        "back"
+    ]);
+    // a page showing just this stream, for putting in an iframe
+    if let Some(id) = model.watch_id() {
+        view.push(seed::a![
+            seed::attrs! {
+                At::Href => format!("embed/{}", id),
+                At::Target => "_blank",
+            },
+            "embed"
+        ]);
+    }
+    view
 }
@@ -53,6 +53,21 @@ pub(crate) fn connect(
     ws
 }
 
+// websocket urls can't be relative, so resolve them against the page we
+// were loaded from. this keeps working behind a proxy which serves us from a
+// different host or under a path prefix, and picks up whether that page was
+// served over tls.
+pub(crate) fn url(path: &str) -> String {
+    let base = seed::window().location().href().unwrap();
+    let url = web_sys::Url::new_with_base(path, &base).unwrap();
+    if url.protocol() == "https:" {
+        url.set_protocol("wss:");
+    } else {
+        url.set_protocol("ws:");
+    }
+    url.href()
+}
+
 fn register_ws_handler<T, F>(
     id: &str,
     ws_cb_setter: fn(&WebSocket, Option<&js_sys::Function>),
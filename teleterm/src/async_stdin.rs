@@ -1,88 +1,71 @@
-struct EventedStdin;
-
-const STDIN: i32 = 0;
-
-impl std::io::Read for EventedStdin {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let stdin = std::io::stdin();
-        let mut stdin = stdin.lock();
-        stdin.read(buf)
-    }
-}
-
-impl mio::Evented for EventedStdin {
-    fn register(
-        &self,
-        poll: &mio::Poll,
-        token: mio::Token,
-        interest: mio::Ready,
-        opts: mio::PollOpt,
-    ) -> std::io::Result<()> {
-        let fd = STDIN as std::os::unix::io::RawFd;
-        let eventedfd = mio::unix::EventedFd(&fd);
-        eventedfd.register(poll, token, interest, opts)
-    }
-
-    fn reregister(
-        &self,
-        poll: &mio::Poll,
-        token: mio::Token,
-        interest: mio::Ready,
-        opts: mio::PollOpt,
-    ) -> std::io::Result<()> {
-        let fd = STDIN as std::os::unix::io::RawFd;
-        let eventedfd = mio::unix::EventedFd(&fd);
-        eventedfd.reregister(poll, token, interest, opts)
-    }
-
-    fn deregister(&self, poll: &mio::Poll) -> std::io::Result<()> {
-        let fd = STDIN as std::os::unix::io::RawFd;
-        let eventedfd = mio::unix::EventedFd(&fd);
-        eventedfd.deregister(poll)
-    }
-}
+use crate::prelude::*;
 
+// stdin is read in a background thread, since there is no reliable way to
+// read it without blocking. it can't always be registered with the reactor
+// (it isn't allowed for regular files, for instance, and some platforms
+// like android don't support it for their terminals either), and even when
+// it can be, a single read isn't guaranteed to consume everything that is
+// waiting, so clearing the read readiness afterwards can leave input stuck
+// until more arrives.
 pub struct Stdin {
-    input: tokio::reactor::PollEvented2<EventedStdin>,
+    // the thread isn't started until the first poll, so that creating this
+    // doesn't start consuming input that something else may want
+    rx: Option<tokio::sync::mpsc::UnboundedReceiver<Vec<u8>>>,
 }
 
 impl Stdin {
     pub fn new() -> Self {
-        Self {
-            input: tokio::reactor::PollEvented2::new(EventedStdin),
-        }
+        Self { rx: None }
     }
 }
 
-impl std::io::Read for Stdin {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.input.read(buf)
-    }
-}
+#[must_use = "streams do nothing unless polled"]
+impl futures::Stream for Stdin {
+    type Item = Vec<u8>;
+    type Error = Error;
 
-impl tokio::io::AsyncRead for Stdin {
-    fn poll_read(
-        &mut self,
-        buf: &mut [u8],
-    ) -> std::result::Result<futures::Async<usize>, tokio::io::Error> {
-        // XXX this is why i had to do the EventedFd thing - poll_read on its
-        // own will block reading from stdin, so i need a way to explicitly
-        // check readiness before doing the read
-        let ready = mio::Ready::readable();
-        match self.input.poll_read_ready(ready)? {
-            futures::Async::Ready(_) => {
-                let res = self.input.poll_read(buf);
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        if self.rx.is_none() {
+            self.rx = Some(
+                read_in_thread()
+                    .context(crate::error::TerminalInputReadingThread)?,
+            );
+        }
 
-                // XXX i'm pretty sure this is wrong (if the single poll_read
-                // call didn't return all waiting data, clearing read ready
-                // state means that we won't get the rest until some more data
-                // beyond that appears), but i don't know that there's a way
-                // to do it correctly given that poll_read blocks
-                self.input.clear_read_ready(ready)?;
+        self.rx
+            .as_mut()
+            .unwrap()
+            .poll()
+            .context(crate::error::ReadChannel)
+    }
+}
 
-                res
+fn read_in_thread(
+) -> std::io::Result<tokio::sync::mpsc::UnboundedReceiver<Vec<u8>>> {
+    let (mut tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::Builder::new().spawn(move || {
+        let stdin = std::io::stdin();
+        let mut stdin = stdin.lock();
+        let mut buf = [0; 4096];
+        loop {
+            match std::io::Read::read(&mut stdin, &mut buf) {
+                // dropping the sender is how the reader sees eof
+                Ok(0) => break,
+                Ok(n) => {
+                    // the receiver has gone away, so nobody is listening
+                    if tx.try_send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+                // signals like SIGWINCH can interrupt the read
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                }
+                Err(e) => {
+                    log::warn!("failed to read from stdin: {}", e);
+                    break;
+                }
             }
-            futures::Async::NotReady => Ok(futures::Async::NotReady),
         }
-    }
+    })?;
+    Ok(rx)
 }
@@ -1 +1,23 @@
+use crate::prelude::*;
+
+pub mod github;
+pub mod google;
 pub mod recurse_center;
+
+pub fn get_username(
+    ty: crate::protocol::AuthType,
+    access_token: &str,
+) -> Box<dyn futures::Future<Item = String, Error = Error> + Send> {
+    match ty {
+        crate::protocol::AuthType::RecurseCenter => {
+            crate::auth::recurse_center::get_username(access_token)
+        }
+        crate::protocol::AuthType::Github => {
+            crate::auth::github::get_username(access_token)
+        }
+        crate::protocol::AuthType::Google => {
+            crate::auth::google::get_username(access_token)
+        }
+        crate::protocol::AuthType::Plain => unreachable!(),
+    }
+}
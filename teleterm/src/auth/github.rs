@@ -0,0 +1,37 @@
+use crate::prelude::*;
+
+pub fn oauth_config(
+    client_id: &str,
+    client_secret: &str,
+    redirect_url: &url::Url,
+) -> crate::oauth::Config {
+    crate::oauth::Config::new(
+        client_id.to_string(),
+        client_secret.to_string(),
+        url::Url::parse("https://github.com/login/oauth/authorize").unwrap(),
+        url::Url::parse("https://github.com/login/oauth/access_token")
+            .unwrap(),
+        redirect_url.clone(),
+    )
+}
+
+pub fn get_username(
+    access_token: &str,
+) -> Box<dyn futures::Future<Item = String, Error = Error> + Send> {
+    let fut = reqwest::r#async::Client::new()
+        .get("https://api.github.com/user")
+        // the github api rejects requests without a user agent
+        .header(reqwest::header::USER_AGENT, "teleterm")
+        .bearer_auth(access_token)
+        .send()
+        .and_then(reqwest::r#async::Response::error_for_status)
+        .context(crate::error::GetGithubProfile)
+        .and_then(|mut res| res.json().context(crate::error::ParseJson))
+        .map(|user: User| user.login);
+    Box::new(fut)
+}
+
+#[derive(serde::Deserialize)]
+struct User {
+    login: String,
+}
@@ -0,0 +1,40 @@
+use crate::prelude::*;
+
+pub fn oauth_config(
+    client_id: &str,
+    client_secret: &str,
+    redirect_url: &url::Url,
+) -> crate::oauth::Config {
+    let mut config = crate::oauth::Config::new(
+        client_id.to_string(),
+        client_secret.to_string(),
+        url::Url::parse("https://accounts.google.com/o/oauth2/v2/auth")
+            .unwrap(),
+        url::Url::parse("https://oauth2.googleapis.com/token").unwrap(),
+        redirect_url.clone(),
+    );
+    config.add_scope("email");
+    // google only hands out refresh tokens when asked for them
+    config.add_auth_param("access_type", "offline");
+    config.add_auth_param("prompt", "consent");
+    config
+}
+
+pub fn get_username(
+    access_token: &str,
+) -> Box<dyn futures::Future<Item = String, Error = Error> + Send> {
+    let fut = reqwest::r#async::Client::new()
+        .get("https://openidconnect.googleapis.com/v1/userinfo")
+        .bearer_auth(access_token)
+        .send()
+        .and_then(reqwest::r#async::Response::error_for_status)
+        .context(crate::error::GetGoogleProfile)
+        .and_then(|mut res| res.json().context(crate::error::ParseJson))
+        .map(|user: User| user.email);
+    Box::new(fut)
+}
+
+#[derive(serde::Deserialize)]
+struct User {
+    email: String,
+}
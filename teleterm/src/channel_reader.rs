@@ -0,0 +1,56 @@
+use crate::prelude::*;
+
+// an AsyncRead implementation which reads data sent over a channel, to allow
+// multiple sources of input (local keyboard input, input from watchers who
+// have been granted control, etc) to be combined into a single input stream
+// for a subprocess
+pub struct ChannelReader {
+    rx: tokio::sync::mpsc::UnboundedReceiver<Vec<u8>>,
+    buf: std::collections::VecDeque<u8>,
+}
+
+impl ChannelReader {
+    pub fn new() -> (tokio::sync::mpsc::UnboundedSender<Vec<u8>>, Self) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (
+            tx,
+            Self {
+                rx,
+                buf: std::collections::VecDeque::new(),
+            },
+        )
+    }
+}
+
+impl std::io::Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.buf.is_empty() {
+            match self.rx.poll() {
+                Ok(futures::Async::Ready(Some(data))) => {
+                    self.buf.extend(data);
+                }
+                // all senders have gone away, so treat it as eof
+                Ok(futures::Async::Ready(None)) => return Ok(0),
+                Ok(futures::Async::NotReady) => {
+                    return Err(std::io::Error::from(
+                        std::io::ErrorKind::WouldBlock,
+                    ));
+                }
+                Err(e) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        e,
+                    ));
+                }
+            }
+        }
+
+        let n = buf.len().min(self.buf.len());
+        for (dst, src) in buf.iter_mut().zip(self.buf.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl tokio::io::AsyncRead for ChannelReader {}
@@ -1,7 +1,8 @@
 use crate::prelude::*;
 use rand::Rng as _;
-use std::io::Read as _;
+use std::convert::TryFrom as _;
 
+// used until the server tells us how often it wants to hear from us
 const HEARTBEAT_DURATION: std::time::Duration =
     std::time::Duration::from_secs(30);
 const RECONNECT_BACKOFF_BASE: std::time::Duration =
@@ -10,6 +11,15 @@ const RECONNECT_BACKOFF_FACTOR: f32 = 2.0;
 const RECONNECT_BACKOFF_MAX: std::time::Duration =
     std::time::Duration::from_secs(60);
 
+// how many messages can be waiting to be sent before we start dropping
+// terminal output
+const DEFAULT_SEND_QUEUE_SIZE: usize = 1024;
+
+// how long to wait for the server to answer a request before assuming that
+// it is stuck
+const DEFAULT_OPERATION_TIMEOUT: std::time::Duration =
+    std::time::Duration::from_secs(30);
+
 const OAUTH_LISTEN_ADDRESS: &str = "127.0.0.1:44141";
 const OAUTH_BROWSER_SUCCESS_MESSAGE: &str = "authenticated successfully! now close this page and return to your terminal.";
 
@@ -64,6 +74,81 @@ pub enum Event {
     ServerMessage(crate::protocol::Message),
     Disconnect,
     Connect,
+    // terminal output was dropped because the send queue was full, so the
+    // screen on the server side is no longer accurate. `queued` is the
+    // number of messages still waiting to be sent.
+    SendQueueFull { dropped: usize, queued: usize },
+}
+
+// what to do when more messages are queued than the server can accept.
+// messages other than terminal output are never dropped, since losing
+// them would leave the connection in an inconsistent state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendQueuePolicy {
+    DropOldest,
+    Reconnect,
+}
+
+impl std::convert::TryFrom<&str> for SendQueuePolicy {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        match s {
+            "drop-oldest" => Ok(Self::DropOldest),
+            "reconnect" => Ok(Self::Reconnect),
+            _ => Err(Error::InvalidSendQueuePolicy {
+                policy: s.to_string(),
+            }),
+        }
+    }
+}
+
+// requests which the server should answer promptly. heartbeats only tell us
+// that the server is still there, not that it is going to get around to
+// answering us.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Login,
+    List,
+    Watch,
+}
+
+impl Operation {
+    fn from_message(msg: &crate::protocol::Message) -> Option<Self> {
+        match msg {
+            crate::protocol::Message::ListSessions => Some(Self::List),
+            crate::protocol::Message::StartWatching { .. } => {
+                Some(Self::Watch)
+            }
+            _ => None,
+        }
+    }
+
+    fn answered_by(self, msg: &crate::protocol::Message) -> bool {
+        match (self, msg) {
+            // an error means the server did get around to our request
+            (_, crate::protocol::Message::Error { .. })
+            | (Self::Login, crate::protocol::Message::LoggedIn { .. })
+            // logging in through the browser takes as long as the user
+            // takes, so stop timing once the server sends us there
+            | (Self::Login, crate::protocol::Message::OauthCliRequest { .. })
+            | (Self::List, crate::protocol::Message::Sessions { .. })
+            // the first thing sent to new watchers is the terminal size
+            | (Self::Watch, crate::protocol::Message::Resize { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Self::Login => "logging in",
+            Self::List => "listing sessions",
+            Self::Watch => "starting to watch",
+        };
+        write!(f, "{}", name)
+    }
 }
 
 pub type Connector<S> = Box<
@@ -78,23 +163,61 @@ pub struct Client<
     connect: Connector<S>,
     auth: crate::protocol::Auth,
     auth_client: crate::protocol::AuthClient,
+    // web clients run the oauth flow themselves, and just hand the server
+    // the access token that it produced
+    oauth_access_token: Option<String>,
 
     term_type: String,
+    // the version the server picked in its Welcome, which decides how
+    // messages are laid out on the wire in both directions
+    proto_version: u8,
+    server_capabilities: Vec<String>,
 
+    heartbeat_duration: std::time::Duration,
     heartbeat_timer: tokio::timer::Interval,
     reconnect_timer: Option<tokio::timer::Delay>,
     reconnect_backoff_amount: std::time::Duration,
     last_server_time: std::time::Instant,
+    // the server answers each heartbeat with one of its own, which gives us
+    // a rough idea of the round trip time
+    heartbeat_sent: Option<std::time::Instant>,
+    latency: Option<std::time::Duration>,
 
     rsock: ReadSocket<S>,
     wsock: WriteSocket<S>,
 
-    // `raw` means to just connect and send Login, then forward all messages
+    operation_timeout: Option<std::time::Duration>,
+    // requests the server hasn't answered yet, and when to give up on them
+    pending: Vec<(Operation, tokio::timer::Delay)>,
+
+    // `raw` means to just connect and log in, then forward all messages
     // as ServerMessage events rather than handling connection messages
     // internally. Connect and Disconnect events will not be sent.
     raw: bool,
     on_login: Vec<crate::protocol::Message>,
+    // identifies our stream to the server across reconnects, so that
+    // watchers aren't kicked off when our connection drops briefly
+    resume_token: Option<String>,
+    // the room our stream is listed in, if any
+    room: Option<String>,
+    // watchers want to know when the title or tags of the stream change
+    session_changes: bool,
+    // streamers want to be warned before the server ends their stream
+    time_limit: bool,
+    // and to know how many people are watching
+    watcher_count: bool,
+    // the session list shows the server's limits, when it has any
+    server_limits: bool,
+    // raw clients leave it up to their caller to decide what to subscribe
+    // to, but everything else can show notices from the server
+    notices: bool,
     to_send: std::collections::VecDeque<crate::protocol::Message>,
+    output_batch: crate::output_batch::OutputBatch,
+    send_queue_size: usize,
+    send_queue_policy: SendQueuePolicy,
+    dropped: usize,
+    // everything written to the server so far, across reconnects
+    bytes_sent: u64,
 
     last_error: Option<String>,
 }
@@ -107,15 +230,24 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         connect: Connector<S>,
         auth: &crate::protocol::Auth,
         auth_client: crate::protocol::AuthClient,
+        allowed_watchers: &[String],
+        description: &str,
     ) -> Self {
-        Self::new(
+        let mut client = Self::new(
             term_type,
             connect,
             auth,
             auth_client,
-            &[crate::protocol::Message::start_streaming()],
+            &[crate::protocol::Message::start_streaming(
+                allowed_watchers,
+                description,
+            )],
             false,
-        )
+        );
+        client.resume_token = Some(format!("{}", uuid::Uuid::new_v4()));
+        client.time_limit = true;
+        client.watcher_count = true;
+        client
     }
 
     pub fn watch(
@@ -124,15 +256,18 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         auth: &crate::protocol::Auth,
         auth_client: crate::protocol::AuthClient,
         id: &str,
+        offset: u32,
     ) -> Self {
-        Self::new(
+        let mut client = Self::new(
             term_type,
             connect,
             auth,
             auth_client,
-            &[crate::protocol::Message::start_watching(id)],
+            &[crate::protocol::Message::start_watching(id, offset)],
             false,
-        )
+        );
+        client.session_changes = true;
+        client
     }
 
     pub fn list(
@@ -141,7 +276,10 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         auth: &crate::protocol::Auth,
         auth_client: crate::protocol::AuthClient,
     ) -> Self {
-        Self::new(term_type, connect, auth, auth_client, &[], false)
+        let mut client =
+            Self::new(term_type, connect, auth, auth_client, &[], false);
+        client.server_limits = true;
+        client
     }
 
     pub fn raw(
@@ -168,38 +306,179 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             connect,
             auth: auth.clone(),
             auth_client,
+            oauth_access_token: None,
 
             term_type: term_type.to_string(),
+            proto_version: crate::protocol::PROTO_VERSION,
+            server_capabilities: vec![],
 
+            heartbeat_duration: HEARTBEAT_DURATION,
             heartbeat_timer,
             reconnect_timer: None,
             reconnect_backoff_amount: RECONNECT_BACKOFF_BASE,
             last_server_time: std::time::Instant::now(),
+            heartbeat_sent: None,
+            latency: None,
 
             rsock: ReadSocket::NotConnected,
             wsock: WriteSocket::NotConnected,
 
+            operation_timeout: Some(DEFAULT_OPERATION_TIMEOUT),
+            pending: vec![],
+
             raw,
             on_login: on_login.to_vec(),
+            resume_token: None,
+            room: None,
+            session_changes: false,
+            time_limit: false,
+            watcher_count: false,
+            server_limits: false,
+            notices: !raw,
             to_send: std::collections::VecDeque::new(),
+            output_batch: crate::output_batch::OutputBatch::new(),
+            send_queue_size: DEFAULT_SEND_QUEUE_SIZE,
+            send_queue_policy: SendQueuePolicy::DropOldest,
+            dropped: 0,
+            bytes_sent: 0,
 
             last_error: None,
         }
     }
 
+    pub fn set_send_queue(&mut self, size: usize, policy: SendQueuePolicy) {
+        self.send_queue_size = size;
+        self.send_queue_policy = policy;
+    }
+
+    // None means to wait for the server as long as it takes
+    pub fn set_operation_timeout(
+        &mut self,
+        timeout: Option<std::time::Duration>,
+    ) {
+        self.operation_timeout = timeout;
+    }
+
+    pub fn set_oauth_access_token(&mut self, access_token: &str) {
+        self.oauth_access_token = Some(access_token.to_string());
+    }
+
+    pub fn set_room(&mut self, room: &str) {
+        self.room = Some(room.to_string());
+    }
+
     pub fn send_message(&mut self, msg: crate::protocol::Message) {
-        self.to_send.push_back(msg);
+        self.output_batch.push(&mut self.to_send, msg);
+        if self.to_send.len() > self.send_queue_size {
+            self.drop_queued_output();
+        }
     }
 
     pub fn reconnect(&mut self) {
         self.rsock = ReadSocket::NotConnected;
         self.wsock = WriteSocket::NotConnected;
+        self.pending.clear();
+    }
+
+    pub fn latency(&self) -> Option<std::time::Duration> {
+        self.latency
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    // how many messages are waiting to be sent, and how many can wait
+    // before terminal output starts being dropped
+    pub fn send_queue_len(&self) -> (usize, usize) {
+        (self.to_send.len(), self.send_queue_size)
     }
 
     pub fn last_error(&self) -> Option<&str> {
         self.last_error.as_ref().map(std::string::String::as_str)
     }
 
+    // whether the server we are connected to supports an optional feature
+    // (see crate::protocol::CAPABILITIES)
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.server_capabilities.iter().any(|c| c == capability)
+    }
+
+    fn queue_login_messages(&mut self) {
+        if let Some(token) = &self.resume_token {
+            if self.has_capability("resume") {
+                self.to_send.push_back(
+                    crate::protocol::Message::resume_streaming(token),
+                );
+            }
+        }
+        if self.session_changes && self.has_capability("session-changes") {
+            self.to_send.push_back(
+                crate::protocol::Message::subscribe_session_changes(),
+            );
+        }
+        if self.time_limit && self.has_capability("time-limit") {
+            self.to_send
+                .push_back(crate::protocol::Message::subscribe_time_limit());
+        }
+        if self.watcher_count && self.has_capability("watcher-count") {
+            self.to_send.push_back(
+                crate::protocol::Message::subscribe_watcher_count(),
+            );
+        }
+        if self.server_limits && self.has_capability("server-limits") {
+            self.to_send.push_back(
+                crate::protocol::Message::subscribe_server_limits(),
+            );
+        }
+        if self.notices && self.has_capability("notice") {
+            self.to_send
+                .push_back(crate::protocol::Message::subscribe_notices());
+        }
+        for msg in &self.on_login {
+            self.to_send.push_back(msg.clone());
+        }
+        // this has to come after we have started streaming
+        if let Some(room) = &self.room {
+            if self.has_capability("rooms") {
+                self.to_send
+                    .push_back(crate::protocol::Message::join_room(room));
+            }
+        }
+    }
+
+    fn drop_queued_output(&mut self) {
+        match self.send_queue_policy {
+            SendQueuePolicy::DropOldest => {
+                let idx = self.to_send.iter().position(|msg| match msg {
+                    crate::protocol::Message::TerminalOutput { .. } => true,
+                    _ => false,
+                });
+                // if nothing can be dropped, the queue is allowed to grow
+                // past its limit
+                if let Some(idx) = idx {
+                    self.to_send.remove(idx);
+                    self.dropped += 1;
+                }
+            }
+            SendQueuePolicy::Reconnect => {
+                // everything queued will be thrown away when the new
+                // connection is established anyway
+                self.dropped += self.to_send.len();
+                self.to_send.clear();
+            }
+        }
+    }
+
+    fn start_operation(&mut self, operation: Operation) {
+        if let Some(timeout) = self.operation_timeout {
+            self.pending.push((
+                operation,
+                tokio::timer::Delay::new(std::time::Instant::now() + timeout),
+            ));
+        }
+    }
+
     fn set_reconnect_timer(&mut self) {
         let delay = rand::thread_rng().gen_range(
             self.reconnect_backoff_amount / 2,
@@ -220,10 +499,22 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         self.reconnect_backoff_amount = RECONNECT_BACKOFF_BASE;
     }
 
+    fn set_heartbeat_duration(&mut self, duration: std::time::Duration) {
+        if duration != self.heartbeat_duration {
+            log::info!(
+                "server requested heartbeats every {}s",
+                duration.as_secs()
+            );
+            self.heartbeat_duration = duration;
+            self.heartbeat_timer =
+                tokio::timer::Interval::new_interval(duration);
+        }
+    }
+
     fn has_seen_server_recently(&self) -> bool {
         let since_last_server =
             std::time::Instant::now().duration_since(self.last_server_time);
-        if since_last_server > HEARTBEAT_DURATION * 2 {
+        if since_last_server > self.heartbeat_duration * 2 {
             return false;
         }
 
@@ -232,6 +523,7 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
 
     fn handle_successful_connection(&mut self, s: S) -> Result<()> {
         self.last_server_time = std::time::Instant::now();
+        self.heartbeat_sent = None;
 
         log::info!("connected to server");
 
@@ -242,6 +534,8 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             WriteSocket::Connected(crate::protocol::FramedWriter::new(ws));
 
         self.to_send.clear();
+        self.proto_version = crate::protocol::PROTO_VERSION;
+        self.send_message(crate::protocol::Message::hello());
         self.send_message(crate::protocol::Message::login(
             &self.auth,
             self.auth_client,
@@ -267,6 +561,50 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         >,
     )> {
         log::debug!("recv_message({})", msg.format_log());
+        self.pending
+            .retain(|(operation, _)| !operation.answered_by(&msg));
+
+        // this is part of setting up the connection, so raw clients need it
+        // handled too
+        if let crate::protocol::Message::Welcome {
+            proto_version,
+            heartbeat_interval,
+            capabilities,
+        } = msg
+        {
+            if proto_version < crate::protocol::MIN_PROTO_VERSION {
+                return Err(Error::ServerProtocolTooOld {
+                    version: proto_version,
+                    min: crate::protocol::MIN_PROTO_VERSION,
+                });
+            }
+            // the server should have fallen back to our version if it
+            // speaks a newer one
+            if proto_version > crate::protocol::PROTO_VERSION {
+                return Err(Error::ProtocolVersionMismatch {
+                    version: proto_version,
+                    expected: crate::protocol::PROTO_VERSION,
+                });
+            }
+            self.proto_version = proto_version;
+            self.server_capabilities = capabilities;
+            self.set_heartbeat_duration(std::time::Duration::from_secs(
+                u64::from(heartbeat_interval.max(1)),
+            ));
+            return Ok((component_future::Async::DidWork, None));
+        }
+
+        // web clients are raw clients, so this needs to be handled here too
+        if let crate::protocol::Message::OauthWebRequest { .. } = msg {
+            let access_token = self
+                .oauth_access_token
+                .clone()
+                .context(crate::error::MissingOauthAccessToken)?;
+            self.send_message(crate::protocol::Message::oauth_web_response(
+                &access_token,
+            ));
+            return Ok((component_future::Async::DidWork, None));
+        }
 
         if !self.raw {
             match msg {
@@ -293,9 +631,7 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                         username
                     );
                     self.reset_reconnect_timer();
-                    for msg in &self.on_login {
-                        self.to_send.push_back(msg.clone());
-                    }
+                    self.queue_login_messages();
                     self.last_error = None;
                     return Ok((
                         component_future::Async::Ready(Some(Event::Connect)),
@@ -303,8 +639,14 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                     ));
                 }
                 crate::protocol::Message::Heartbeat => {
+                    if let Some(sent) = self.heartbeat_sent.take() {
+                        self.latency = Some(sent.elapsed());
+                    }
                     return Ok((component_future::Async::DidWork, None));
                 }
+                crate::protocol::Message::ServerShutdown { reason } => {
+                    return Err(Error::ServerShutdown { reason });
+                }
                 _ => {}
             }
         }
@@ -389,7 +731,8 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                     ))
                 })
                 .and_then(move |(msg, sock)| {
-                    save_client_auth_id(auth_type, &id).map(|_| (msg, sock))
+                    crate::credentials::save(auth_type, &id)
+                        .map(|_| (msg, sock))
                 })
                 .and_then(|(msg, sock)| {
                     let response = format!(
@@ -417,9 +760,11 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             Error,
         >] = &[
         &Self::poll_reconnect_server,
+        &Self::poll_send_queue,
         &Self::poll_read_server,
         &Self::poll_write_server,
         &Self::poll_heartbeat,
+        &Self::poll_operation_timeout,
     ];
 
     fn poll_reconnect_server(
@@ -435,6 +780,9 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
 
                 self.set_reconnect_timer();
                 self.wsock = WriteSocket::Connecting((self.connect)());
+                // includes the time it takes to connect, since connecting
+                // to an unresponsive host can also hang
+                self.start_operation(Operation::Login);
             }
             WriteSocket::Connecting(ref mut fut) => match fut.poll() {
                 Ok(futures::Async::Ready(s)) => {
@@ -476,6 +824,45 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         Ok(component_future::Async::DidWork)
     }
 
+    fn poll_send_queue(
+        &mut self,
+    ) -> component_future::Poll<Option<Event>, Error> {
+        if self.dropped == 0 {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        let dropped = self.dropped;
+        self.dropped = 0;
+        let queued = self.to_send.len();
+
+        match self.send_queue_policy {
+            SendQueuePolicy::DropOldest => {
+                log::warn!(
+                    "send queue full, dropped {} terminal output messages",
+                    dropped
+                );
+                if self.raw {
+                    return Ok(component_future::Async::DidWork);
+                }
+                Ok(component_future::Async::Ready(Some(
+                    Event::SendQueueFull { dropped, queued },
+                )))
+            }
+            SendQueuePolicy::Reconnect => {
+                if self.raw {
+                    return Err(Error::SendQueueFull {
+                        size: self.send_queue_size,
+                    });
+                }
+
+                log::warn!("send queue full, reconnecting");
+                self.reconnect();
+                self.last_error = Some("send queue full".to_string());
+                Ok(component_future::Async::Ready(Some(Event::Disconnect)))
+            }
+        }
+    }
+
     fn poll_read_server(
         &mut self,
     ) -> component_future::Poll<Option<Event>, Error> {
@@ -484,10 +871,11 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                 Ok(component_future::Async::NothingToDo)
             }
             ReadSocket::Connected(..) => {
-                if let ReadSocket::Connected(s) = std::mem::replace(
+                if let ReadSocket::Connected(mut s) = std::mem::replace(
                     &mut self.rsock,
                     ReadSocket::NotConnected,
                 ) {
+                    s.set_proto_version(self.proto_version);
                     let fut = crate::protocol::Message::read_async(s);
                     self.rsock = ReadSocket::Reading(Box::new(fut));
                 } else {
@@ -586,13 +974,22 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                 if self.to_send.is_empty() {
                     return Ok(component_future::Async::NothingToDo);
                 }
+                component_future::try_ready!(self
+                    .output_batch
+                    .poll_ready(&self.to_send));
 
-                if let WriteSocket::Connected(s) = std::mem::replace(
+                if let WriteSocket::Connected(mut s) = std::mem::replace(
                     &mut self.wsock,
                     WriteSocket::NotConnected,
                 ) {
+                    s.set_proto_version(self.proto_version);
                     let msg = self.to_send.pop_front().unwrap();
                     log::debug!("send_message({})", msg.format_log());
+                    if let Some(operation) = Operation::from_message(&msg) {
+                        self.start_operation(operation);
+                    }
+                    self.bytes_sent += u64::try_from(msg.encoded_len())
+                        .unwrap_or(std::u64::MAX);
                     let fut = msg.write_async(s);
                     self.wsock = WriteSocket::Writing(Box::new(fut));
                 } else {
@@ -633,8 +1030,53 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             .poll()
             .context(crate::error::TimerHeartbeat));
         self.send_message(crate::protocol::Message::heartbeat());
+        // if the previous heartbeat hasn't been answered yet, keep timing
+        // from that one
+        if self.heartbeat_sent.is_none() {
+            self.heartbeat_sent = Some(std::time::Instant::now());
+        }
         Ok(component_future::Async::DidWork)
     }
+
+    fn poll_operation_timeout(
+        &mut self,
+    ) -> component_future::Poll<Option<Event>, Error> {
+        if self.pending.is_empty() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        let mut timed_out = None;
+        for (operation, timer) in &mut self.pending {
+            if timer
+                .poll()
+                .context(crate::error::TimerOperation)?
+                .is_ready()
+            {
+                timed_out = Some(*operation);
+                break;
+            }
+        }
+        let operation = if let Some(operation) = timed_out {
+            operation
+        } else {
+            return Ok(component_future::Async::NotReady);
+        };
+
+        let e = Error::OperationTimeout {
+            operation: operation.to_string(),
+            secs: self.operation_timeout.map_or(0, |t| t.as_secs()),
+        };
+        if self.raw {
+            return Err(e);
+        }
+
+        // the connection may well be half open at this point, so throw it
+        // away and start over rather than waiting on it any longer
+        log::warn!("{}, reconnecting", e);
+        self.reconnect();
+        self.last_error = Some(format!("{}", e));
+        Ok(component_future::Async::Ready(Some(Event::Disconnect)))
+    }
 }
 
 #[must_use = "streams do nothing unless polled"]
@@ -648,40 +1090,3 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         component_future::poll_stream(self, Self::POLL_FNS)
     }
 }
-
-pub fn load_client_auth_id(
-    auth: crate::protocol::AuthType,
-) -> Option<String> {
-    client_id_file(auth, true).and_then(|id_file| {
-        std::fs::File::open(id_file).ok().and_then(|mut file| {
-            let mut id = vec![];
-            file.read_to_end(&mut id).ok().map(|_| {
-                std::string::String::from_utf8_lossy(&id).to_string()
-            })
-        })
-    })
-}
-
-fn save_client_auth_id(
-    auth: crate::protocol::AuthType,
-    id: &str,
-) -> impl futures::Future<Item = (), Error = Error> {
-    let id_file = client_id_file(auth, false).unwrap();
-    let id = id.to_string();
-    tokio::fs::File::create(id_file.clone())
-        .with_context(move || crate::error::CreateFile {
-            filename: id_file.to_string_lossy().to_string(),
-        })
-        .and_then(|file| {
-            tokio::io::write_all(file, id).context(crate::error::WriteFile)
-        })
-        .map(|_| ())
-}
-
-fn client_id_file(
-    auth: crate::protocol::AuthType,
-    must_exist: bool,
-) -> Option<std::path::PathBuf> {
-    let filename = format!("client-oauth-{}", auth.name());
-    crate::dirs::Dirs::new().data_file(&filename, must_exist)
-}
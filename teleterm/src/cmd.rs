@@ -1,5 +1,8 @@
 use crate::prelude::*;
 
+mod admin;
+mod diff;
+mod logout;
 mod play;
 mod record;
 mod server;
@@ -53,6 +56,24 @@ const COMMANDS: &[Command] = &[
         config: &play::config,
         log_level: "error",
     },
+    Command {
+        name: "diff",
+        cmd: &diff::cmd,
+        config: &diff::config,
+        log_level: "error",
+    },
+    Command {
+        name: "logout",
+        cmd: &logout::cmd,
+        config: &logout::config,
+        log_level: "error",
+    },
+    Command {
+        name: "admin",
+        cmd: &admin::cmd,
+        config: &admin::config,
+        log_level: "error",
+    },
 ];
 
 pub fn parse<'a>() -> Result<clap::ArgMatches<'a>> {
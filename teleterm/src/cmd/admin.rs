@@ -0,0 +1,334 @@
+use crate::prelude::*;
+use std::convert::TryFrom as _;
+
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(default)]
+    client: crate::config::Client,
+
+    // comes only from the command line
+    #[serde(skip)]
+    request: Option<crate::protocol::Message>,
+}
+
+impl crate::config::Config for Config {
+    fn merge_args<'a>(
+        &mut self,
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        self.client.merge_args(matches)?;
+        self.request = Some(match matches.subcommand() {
+            ("list", _) => crate::protocol::Message::admin_list_connections(),
+            ("kick", Some(matches)) => crate::protocol::Message::admin_kick(
+                matches.value_of("id").unwrap(),
+                matches.value_of("reason").unwrap_or(""),
+            ),
+            ("notice", Some(matches)) => {
+                let text: Vec<_> =
+                    matches.values_of("text").unwrap().collect();
+                crate::protocol::Message::admin_notice(&text.join(" "))
+            }
+            ("kick-matching", Some(matches)) => {
+                let filter = matches.value_of("filter").unwrap();
+                crate::protocol::AdminFilter::try_from(filter)?;
+                crate::protocol::Message::admin_kick_matching(
+                    filter,
+                    matches.value_of("reason").unwrap_or(""),
+                    matches.is_present("ban"),
+                    matches.is_present("dry-run"),
+                )
+            }
+            ("stats", _) => crate::protocol::Message::admin_get_stats(),
+            ("ban", Some(matches)) => crate::protocol::Message::admin_ban(
+                matches.value_of("entry").unwrap(),
+            ),
+            _ => unreachable!(),
+        });
+        Ok(())
+    }
+
+    fn run(
+        &self,
+    ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
+        let auth = match self.client.auth {
+            crate::protocol::AuthType::Plain => {
+                let username = self
+                    .client
+                    .username
+                    .clone()
+                    .context(crate::error::CouldntFindUsername);
+                match username {
+                    Ok(username) => crate::protocol::Auth::plain(&username),
+                    Err(e) => return Box::new(futures::future::err(e)),
+                }
+            }
+            crate::protocol::AuthType::RecurseCenter => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::recurse_center(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
+            crate::protocol::AuthType::Github => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::github(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
+            crate::protocol::AuthType::Google => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::google(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
+        };
+        let request = self.request.clone().unwrap();
+
+        let connector = match self.client.connector() {
+            Ok(connector) => connector,
+            Err(e) => return Box::new(futures::future::err(e)),
+        };
+        let connect: crate::client::Connector<_> =
+            Box::new(move || connector.connect());
+        Box::new(Admin::new(connect, &auth, self.client.timeout, request))
+    }
+}
+
+pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+    crate::config::Client::cmd(
+        app.about("Manage a teleterm server you are an admin of")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::SubCommand::with_name("list")
+                    .about("List everyone connected to the server"),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("kick")
+                    .about("Disconnect a connection from the server")
+                    .arg(
+                        clap::Arg::with_name("id")
+                            .required(true)
+                            .help("Id of the connection, from tt admin list"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("reason")
+                            .help("Reason to show to the kicked client"),
+                    ),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("kick-matching")
+                    .about("Disconnect everything matching a filter at once")
+                    .arg(
+                        clap::Arg::with_name("filter").required(true).help(
+                            "user:USERNAME, tag:TAG, or address:ADDRESS",
+                        ),
+                    )
+                    .arg(
+                        clap::Arg::with_name("reason")
+                            .help("Reason to show to the kicked clients"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("ban")
+                            .long("ban")
+                            .help("Also ban the matching users or address"),
+                    )
+                    .arg(
+                        clap::Arg::with_name("dry-run")
+                            .long("dry-run")
+                            .help("Only list what would be disconnected"),
+                    ),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("notice")
+                    .about("Send a notice to everyone streaming or watching")
+                    .arg(
+                        clap::Arg::with_name("text")
+                            .required(true)
+                            .multiple(true)
+                            .help("Text of the notice"),
+                    ),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("stats")
+                    .about("Show statistics about the server"),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("ban")
+                    .about("Ban a user or address from the server")
+                    .arg(
+                        clap::Arg::with_name("entry")
+                            .required(true)
+                            .help("Username or IP address to ban"),
+                    ),
+            ),
+    )
+}
+
+pub fn config(
+    config: Option<config::Config>,
+) -> Result<Box<dyn crate::config::Config>> {
+    let config: Config = if let Some(config) = config {
+        config
+            .try_into()
+            .context(crate::error::CouldntParseConfig)?
+    } else {
+        Config::default()
+    };
+    Ok(Box::new(config))
+}
+
+struct Admin<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+{
+    client: crate::client::Client<S>,
+    request: crate::protocol::Message,
+}
+
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    Admin<S>
+{
+    fn new(
+        connector: crate::client::Connector<S>,
+        auth: &crate::protocol::Auth,
+        timeout: Option<std::time::Duration>,
+        request: crate::protocol::Message,
+    ) -> Self {
+        let term_type =
+            std::env::var("TERM").unwrap_or_else(|_| "".to_string());
+        let mut client = crate::client::Client::list(
+            &term_type,
+            connector,
+            auth,
+            crate::protocol::AuthClient::Cli,
+        );
+        client.set_operation_timeout(timeout);
+        Self { client, request }
+    }
+
+    fn print_done(&self, count: u32) {
+        match &self.request {
+            crate::protocol::Message::AdminKick { id, .. } => {
+                println!("kicked {}", id);
+            }
+            crate::protocol::Message::AdminNotice { .. } => {
+                println!("sent notice to {} connections", count);
+            }
+            crate::protocol::Message::AdminBan { entry } => {
+                println!("banned {} ({} connections kicked)", entry, count);
+            }
+            crate::protocol::Message::AdminKickMatching {
+                filter,
+                ban,
+                ..
+            } => {
+                if *ban {
+                    println!(
+                        "banned {} ({} connections kicked)",
+                        filter, count
+                    );
+                } else {
+                    println!("kicked {} connections", count);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    Admin<S>
+{
+    const POLL_FNS:
+        &'static [&'static dyn for<'a> Fn(
+            &'a mut Self,
+        )
+            -> component_future::Poll<
+            (),
+            Error,
+        >] = &[&Self::poll_client];
+
+    fn poll_client(&mut self) -> component_future::Poll<(), Error> {
+        match component_future::try_ready!(self.client.poll()).unwrap() {
+            crate::client::Event::Disconnect => {}
+            crate::client::Event::Connect => {
+                if !self.client.has_capability("admin") {
+                    return Err(Error::ServerMissingCapability {
+                        feature: "the admin interface".to_string(),
+                    });
+                }
+                if let crate::protocol::Message::AdminBan { .. } =
+                    self.request
+                {
+                    if !self.client.has_capability("ban") {
+                        return Err(Error::ServerMissingCapability {
+                            feature: "banning".to_string(),
+                        });
+                    }
+                }
+                if let crate::protocol::Message::AdminKickMatching {
+                    ..
+                } = self.request
+                {
+                    if !self.client.has_capability("admin-matching") {
+                        return Err(Error::ServerMissingCapability {
+                            feature: "kicking by filter".to_string(),
+                        });
+                    }
+                }
+                self.client.send_message(self.request.clone());
+            }
+            crate::client::Event::SendQueueFull { .. } => {}
+            crate::client::Event::ServerMessage(msg) => match msg {
+                crate::protocol::Message::AdminConnections {
+                    connections,
+                } => {
+                    // the answer to a dry run
+                    if let crate::protocol::Message::AdminKickMatching {
+                        ..
+                    } = self.request
+                    {
+                        println!(
+                            "would kick {} connections:",
+                            connections.len()
+                        );
+                    }
+                    for line in crate::ui::admin_connections(&connections) {
+                        println!("{}", line);
+                    }
+                    return Ok(component_future::Async::Ready(()));
+                }
+                crate::protocol::Message::AdminStats { stats } => {
+                    for line in crate::ui::admin_stats(&stats) {
+                        println!("{}", line);
+                    }
+                    return Ok(component_future::Async::Ready(()));
+                }
+                crate::protocol::Message::AdminDone { count } => {
+                    self.print_done(count);
+                    return Ok(component_future::Async::Ready(()));
+                }
+                crate::protocol::Message::Error { msg } => {
+                    return Err(Error::Server { message: msg });
+                }
+                // including the notice we may have just sent ourselves
+                crate::protocol::Message::Notice { .. } => {}
+                msg => {
+                    return Err(crate::error::Error::UnexpectedMessage {
+                        message: msg,
+                    });
+                }
+            },
+        }
+        Ok(component_future::Async::DidWork)
+    }
+}
+
+#[must_use = "futures do nothing unless polled"]
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    futures::Future for Admin<S>
+{
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> futures::Poll<Self::Item, Self::Error> {
+        component_future::poll_future(self, Self::POLL_FNS)
+    }
+}
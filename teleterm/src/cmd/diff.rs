@@ -0,0 +1,321 @@
+use crate::prelude::*;
+
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(default)]
+    diff: crate::config::Diff,
+}
+
+impl crate::config::Config for Config {
+    fn merge_args<'a>(
+        &mut self,
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        self.diff.merge_args(matches)
+    }
+
+    fn run(
+        &self,
+    ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
+        Box::new(futures::future::result(diff(
+            &self.diff.old_filename,
+            &self.diff.new_filename,
+            self.diff.size,
+        )))
+    }
+}
+
+pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+    crate::config::Diff::cmd(
+        app.about("Compare the screens drawn by two recordings"),
+    )
+}
+
+pub fn config(
+    config: Option<config::Config>,
+) -> Result<Box<dyn crate::config::Config>> {
+    let config: Config = if let Some(config) = config {
+        config
+            .try_into()
+            .context(crate::error::CouldntParseConfig)?
+    } else {
+        Config::default()
+    };
+    Ok(Box::new(config))
+}
+
+// the first point where the two recordings draw different screens, along
+// with the last frame of each recording which was played to get there
+#[derive(Debug, PartialEq, Eq)]
+struct Divergence {
+    old_frame: Option<usize>,
+    new_frame: Option<usize>,
+    regions: Vec<Region>,
+}
+
+// the columns of a single row which differ between the two screens
+#[derive(Debug, PartialEq, Eq)]
+struct Region {
+    row: u16,
+    start_col: u16,
+    end_col: u16,
+    old: String,
+    new: String,
+}
+
+// a recording being played back into a terminal, a byte range at a time
+struct Playback<'a> {
+    frames: &'a [ttyrec::Frame],
+    parser: vt100::Parser,
+    frame: usize,
+    pos: usize,
+    played: usize,
+    last_frame: Option<usize>,
+}
+
+impl<'a> Playback<'a> {
+    fn new(frames: &'a [ttyrec::Frame], size: crate::term::Size) -> Self {
+        Self {
+            frames,
+            parser: vt100::Parser::new(size.rows, size.cols, 0),
+            frame: 0,
+            pos: 0,
+            played: 0,
+            last_frame: None,
+        }
+    }
+
+    // the number of bytes of output which will have been played once the
+    // current frame is finished
+    fn next_boundary(&mut self) -> Option<usize> {
+        while let Some(frame) = self.frames.get(self.frame) {
+            if self.pos < frame.data.len() {
+                return Some(self.played + frame.data.len() - self.pos);
+            }
+            self.frame += 1;
+            self.pos = 0;
+        }
+        None
+    }
+
+    fn play_until(&mut self, boundary: usize) {
+        while self.played < boundary {
+            let frame = if let Some(frame) = self.frames.get(self.frame) {
+                frame
+            } else {
+                break;
+            };
+            let len =
+                (frame.data.len() - self.pos).min(boundary - self.played);
+            if len > 0 {
+                self.parser.process(&frame.data[self.pos..self.pos + len]);
+                self.last_frame = Some(self.frame);
+            }
+            self.pos += len;
+            self.played += len;
+            if self.pos >= frame.data.len() {
+                self.frame += 1;
+                self.pos = 0;
+            }
+        }
+    }
+}
+
+fn diff(
+    old_filename: &str,
+    new_filename: &str,
+    size: crate::term::Size,
+) -> Result<()> {
+    let old_frames = read_frames(old_filename)?;
+    let new_frames = read_frames(new_filename)?;
+
+    let divergence = if let Some(divergence) =
+        find_divergence(&old_frames, &new_frames, size)
+    {
+        divergence
+    } else {
+        println!(
+            "screens match ({} and {} frames)",
+            old_frames.len(),
+            new_frames.len()
+        );
+        return Ok(());
+    };
+
+    println!(
+        "screens differ at {} of {} and {} of {}:",
+        format_frame(&old_frames, divergence.old_frame),
+        old_filename,
+        format_frame(&new_frames, divergence.new_frame),
+        new_filename,
+    );
+    for region in &divergence.regions {
+        println!(
+            "row {}, columns {}-{}:",
+            region.row + 1,
+            region.start_col + 1,
+            region.end_col + 1
+        );
+        println!("- {}", region.old);
+        println!("+ {}", region.new);
+    }
+
+    // like diff(1), so that this can be used to check recordings in scripts
+    std::process::exit(1);
+}
+
+// frame times are returned relative to the start of the recording
+fn read_frames(filename: &str) -> Result<Vec<ttyrec::Frame>> {
+    let data =
+        std::fs::read(filename).context(crate::error::OpenFileSync {
+            filename: filename.to_string(),
+        })?;
+    let mut parser = ttyrec::Parser::new();
+    parser.add_bytes(&data);
+    let mut frames = vec![];
+    while let Some(mut frame) = parser.next_frame() {
+        if let Some(offset) = parser.offset() {
+            frame.time -= offset;
+        }
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+// the same output can be split into frames differently from one run to the
+// next, so rather than comparing frame by frame, the screens are compared
+// each time either recording reaches the end of a frame, after the same
+// amount of output has been played from both. once the shorter recording
+// runs out, its final screen is compared against the rest of the longer
+// one.
+fn find_divergence(
+    old_frames: &[ttyrec::Frame],
+    new_frames: &[ttyrec::Frame],
+    size: crate::term::Size,
+) -> Option<Divergence> {
+    let mut old = Playback::new(old_frames, size);
+    let mut new = Playback::new(new_frames, size);
+    loop {
+        let boundary = match (old.next_boundary(), new.next_boundary()) {
+            (Some(old_boundary), Some(new_boundary)) => {
+                old_boundary.min(new_boundary)
+            }
+            (Some(boundary), None) | (None, Some(boundary)) => boundary,
+            (None, None) => return None,
+        };
+        old.play_until(boundary);
+        new.play_until(boundary);
+
+        let regions =
+            diff_screens(old.parser.screen(), new.parser.screen(), size);
+        if !regions.is_empty() {
+            return Some(Divergence {
+                old_frame: old.last_frame,
+                new_frame: new.last_frame,
+                regions,
+            });
+        }
+    }
+}
+
+fn diff_screens(
+    old: &vt100::Screen,
+    new: &vt100::Screen,
+    size: crate::term::Size,
+) -> Vec<Region> {
+    let mut regions = vec![];
+    for row in 0..size.rows {
+        let differing: Vec<_> = (0..size.cols)
+            .filter(|&col| old.cell(row, col) != new.cell(row, col))
+            .collect();
+        if let (Some(&start_col), Some(&end_col)) =
+            (differing.first(), differing.last())
+        {
+            let width = end_col - start_col + 1;
+            let contents = |screen: &vt100::Screen| {
+                screen
+                    .rows(start_col, width)
+                    .nth(usize::from(row))
+                    .unwrap_or_default()
+            };
+            regions.push(Region {
+                row,
+                start_col,
+                end_col,
+                old: contents(old),
+                new: contents(new),
+            });
+        }
+    }
+    regions
+}
+
+fn format_frame(frames: &[ttyrec::Frame], idx: Option<usize>) -> String {
+    idx.map_or_else(
+        || "the start".to_string(),
+        |idx| {
+            format!(
+                "frame {} ({:.3}s)",
+                idx + 1,
+                frames[idx].time.as_secs_f64()
+            )
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frames(data: &[&[u8]]) -> Vec<ttyrec::Frame> {
+        data.iter()
+            .zip(0..)
+            .map(|(data, secs)| ttyrec::Frame {
+                time: std::time::Duration::from_secs(secs),
+                data: data.to_vec(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_divergence() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+
+        // how the output happened to be split into frames doesn't matter
+        let old = frames(&[b"foo\r\n", b"bar", b"\x1b[31mbaz"]);
+        let new = frames(&[b"fo", b"o\r\nbar\x1b[3", b"1mbaz"]);
+        assert_eq!(find_divergence(&old, &new, size), None);
+
+        let old = frames(&[b"foo\r\n", b"bar baz", b"\x1b[31mquux"]);
+        let new = frames(&[b"foo\r\n", b"bar bzz", b"\x1b[31mquux"]);
+        assert_eq!(
+            find_divergence(&old, &new, size),
+            Some(Divergence {
+                old_frame: Some(1),
+                new_frame: Some(1),
+                regions: vec![Region {
+                    row: 1,
+                    start_col: 5,
+                    end_col: 5,
+                    old: "a".to_string(),
+                    new: "z".to_string(),
+                }],
+            })
+        );
+
+        // differences in color are differences too
+        let old = frames(&[b"\x1b[31mfoo"]);
+        let new = frames(&[b"\x1b[32mfoo"]);
+        let divergence = find_divergence(&old, &new, size).unwrap();
+        assert_eq!(divergence.regions.len(), 1);
+        assert_eq!(divergence.regions[0].old, "foo");
+        assert_eq!(divergence.regions[0].new, "foo");
+
+        let old = frames(&[b"foo"]);
+        let new = frames(&[b"foo", b"", b"\r\nbar"]);
+        let divergence = find_divergence(&old, &new, size).unwrap();
+        assert_eq!(divergence.old_frame, Some(0));
+        assert_eq!(divergence.new_frame, Some(2));
+        assert_eq!(divergence.regions[0].row, 1);
+    }
+}
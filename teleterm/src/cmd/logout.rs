@@ -0,0 +1,50 @@
+use crate::prelude::*;
+
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct Config {}
+
+impl crate::config::Config for Config {
+    fn merge_args<'a>(
+        &mut self,
+        _matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn run(
+        &self,
+    ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
+        Box::new(futures::future::result(logout()))
+    }
+}
+
+pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+    app.about("Forget the cached logins used by tt stream and tt watch")
+}
+
+pub fn config(
+    config: Option<config::Config>,
+) -> Result<Box<dyn crate::config::Config>> {
+    let config: Config = if let Some(config) = config {
+        config
+            .try_into()
+            .context(crate::error::CouldntParseConfig)?
+    } else {
+        Config::default()
+    };
+    Ok(Box::new(config))
+}
+
+fn logout() -> Result<()> {
+    let mut logged_in = false;
+    for ty in crate::protocol::AuthType::iter().filter(|ty| ty.is_oauth()) {
+        if crate::credentials::clear(ty)? {
+            println!("logged out of {}", ty.name());
+            logged_in = true;
+        }
+    }
+    if !logged_in {
+        println!("not logged in");
+    }
+    Ok(())
+}
@@ -2,14 +2,24 @@ use crate::prelude::*;
 use std::io::Write as _;
 
 const PLAYBACK_RATIO_INCR: f32 = 1.5;
+const SEEK_AMOUNT: std::time::Duration = std::time::Duration::from_secs(10);
+// used when idle skipping is turned on during playback without --max-idle
+const DEFAULT_MAX_IDLE: std::time::Duration =
+    std::time::Duration::from_secs(2);
 
 #[derive(serde::Deserialize, Debug, Default)]
 pub struct Config {
+    #[serde(default)]
+    client: crate::config::Client,
+
     #[serde(default)]
     ttyrec: crate::config::Ttyrec,
 
     #[serde(default)]
     play: crate::config::Play,
+
+    #[serde(default)]
+    display: crate::config::Display,
 }
 
 impl crate::config::Config for Config {
@@ -17,26 +27,100 @@ impl crate::config::Config for Config {
         &mut self,
         matches: &clap::ArgMatches<'a>,
     ) -> Result<()> {
+        self.client.merge_args(matches)?;
         self.ttyrec.merge_args(matches)?;
         self.play.merge_args(matches)?;
+        self.display.merge_args(matches)?;
         Ok(())
     }
 
     fn run(
         &self,
     ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
+        if self.play.exporting() {
+            return Box::new(futures::future::result(export(
+                &self.ttyrec,
+                &self.play,
+            )));
+        }
+        if !self.play.stream {
+            return Box::new(PlaySession::<tokio::net::tcp::TcpStream>::new(
+                &self.ttyrec.filename,
+                self.ttyrec.format,
+                self.play.play_at_start,
+                self.play.playback_ratio,
+                self.play.max_frame_length,
+                self.display.caps(),
+                None,
+            ));
+        }
+
+        let auth = match self.client.auth {
+            crate::protocol::AuthType::Plain => {
+                let username = self
+                    .client
+                    .username
+                    .clone()
+                    .context(crate::error::CouldntFindUsername);
+                match username {
+                    Ok(username) => crate::protocol::Auth::plain(&username),
+                    Err(e) => return Box::new(futures::future::err(e)),
+                }
+            }
+            crate::protocol::AuthType::RecurseCenter => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::recurse_center(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
+            crate::protocol::AuthType::Github => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::github(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
+            crate::protocol::AuthType::Google => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::google(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
+        };
+        let term_type =
+            std::env::var("TERM").unwrap_or_else(|_| "".to_string());
+
+        let connector = match self.client.connector() {
+            Ok(connector) => connector,
+            Err(e) => return Box::new(futures::future::err(e)),
+        };
+        let connect: crate::client::Connector<_> =
+            Box::new(move || connector.connect());
+        let mut client = crate::client::Client::stream(
+            &term_type,
+            connect,
+            &auth,
+            crate::protocol::AuthClient::Cli,
+            &[],
+            &format!("playback of {}", self.ttyrec.filename),
+        );
+        client.set_operation_timeout(self.client.timeout);
         Box::new(PlaySession::new(
             &self.ttyrec.filename,
+            self.ttyrec.format,
             self.play.play_at_start,
             self.play.playback_ratio,
             self.play.max_frame_length,
+            self.display.caps(),
+            Some(client),
         ))
     }
 }
 
 pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
-    crate::config::Ttyrec::cmd(crate::config::Play::cmd(
-        app.about("Play recorded terminal sessions"),
+    crate::config::Client::cmd(crate::config::Ttyrec::cmd(
+        crate::config::Play::cmd(crate::config::Display::cmd(
+            app.about("Play recorded terminal sessions"),
+        )),
     ))
 }
 
@@ -53,8 +137,61 @@ pub fn config(
     Ok(Box::new(config))
 }
 
+// plays the whole recording into a terminal at once, and writes out what
+// ended up on it
+fn export(
+    ttyrec: &crate::config::Ttyrec,
+    play: &crate::config::Play,
+) -> Result<()> {
+    let filename = &ttyrec.filename;
+    let data =
+        std::fs::read(filename).context(crate::error::OpenFileSync {
+            filename: filename.to_string(),
+        })?;
+    let mut reader = crate::recording::Parser::new(ttyrec.format);
+    reader.add_bytes(&data);
+    reader.finish();
+    let scrollback = if play.export_scrollback {
+        crate::export::SCROLLBACK_LINES
+    } else {
+        0
+    };
+    let mut parser = vt100::Parser::new(
+        play.export_size.rows,
+        play.export_size.cols,
+        scrollback,
+    );
+    while let Some(frame) = reader.next_frame()? {
+        parser.process(&frame.data);
+    }
+
+    if let Some(text_filename) = &play.export_text {
+        let text = crate::export::text(&mut parser, play.export_scrollback);
+        std::fs::write(text_filename, text).context(
+            crate::error::CreateFileSync {
+                filename: text_filename.to_string(),
+            },
+        )?;
+    }
+    if let Some(html_filename) = &play.export_html {
+        let html = crate::export::html(
+            &mut parser,
+            play.export_scrollback,
+            filename,
+        );
+        std::fs::write(html_filename, html).context(
+            crate::error::CreateFileSync {
+                filename: html_filename.to_string(),
+            },
+        )?;
+    }
+    Ok(())
+}
+
 struct Frame {
     dur: std::time::Duration,
+    // time since the start of the recording
+    time: std::time::Duration,
     full: Vec<u8>,
     diff: Vec<u8>,
 }
@@ -126,6 +263,15 @@ impl Ttyrec {
         self.matches_from(idx, re).count()
     }
 
+    // returns the index of the frame that was on the screen at the given
+    // time since the start of the recording
+    fn frame_idx_at(&self, time: std::time::Duration) -> usize {
+        match self.frames.binary_search_by(|frame| frame.time.cmp(&time)) {
+            Ok(idx) => idx,
+            Err(idx) => idx.saturating_sub(1),
+        }
+    }
+
     fn len(&self) -> usize {
         self.frames.len()
     }
@@ -140,7 +286,8 @@ struct SearchState {
 
 struct Player {
     playback_ratio: f32,
-    max_frame_length: Option<std::time::Duration>,
+    max_idle: std::time::Duration,
+    skip_idle: bool,
     ttyrec: Ttyrec,
     idx: usize,
     timer: Option<tokio::timer::Delay>,
@@ -159,7 +306,8 @@ impl Player {
         let now = std::time::Instant::now();
         Self {
             playback_ratio,
-            max_frame_length,
+            max_idle: max_frame_length.unwrap_or(DEFAULT_MAX_IDLE),
+            skip_idle: max_frame_length.is_some(),
             ttyrec: Ttyrec::new(),
             idx: 0,
             timer: None,
@@ -209,6 +357,23 @@ impl Player {
         self.set_timer();
     }
 
+    fn toggle_skip_idle(&mut self) {
+        self.skip_idle = !self.skip_idle;
+        self.set_timer();
+    }
+
+    fn skip_idle(&self) -> bool {
+        self.skip_idle
+    }
+
+    fn max_frame_length(&self) -> Option<std::time::Duration> {
+        if self.skip_idle {
+            Some(self.max_idle)
+        } else {
+            None
+        }
+    }
+
     fn back(&mut self) {
         self.idx = self.idx.saturating_sub(1);
         self.recalculate_times();
@@ -226,6 +391,30 @@ impl Player {
         self.clear_match_idx();
     }
 
+    fn seek_back(&mut self) {
+        let time = self
+            .current_frame()
+            .map_or_else(std::time::Duration::default, |frame| frame.time);
+        self.seek(time.checked_sub(SEEK_AMOUNT).unwrap_or_default());
+    }
+
+    fn seek_forward(&mut self) {
+        let time = self
+            .current_frame()
+            .map_or_else(std::time::Duration::default, |frame| frame.time);
+        self.seek(time + SEEK_AMOUNT);
+    }
+
+    fn seek(&mut self, time: std::time::Duration) {
+        if self.ttyrec.len() == 0 {
+            return;
+        }
+        self.idx = self.ttyrec.frame_idx_at(time);
+        self.recalculate_times();
+        self.set_timer();
+        self.clear_match_idx();
+    }
+
     fn first(&mut self) {
         self.idx = 0;
         self.recalculate_times();
@@ -349,7 +538,7 @@ impl Player {
                     + self.played_amount
                     + frame.adjusted_dur(
                         self.playback_ratio,
-                        self.max_frame_length,
+                        self.max_frame_length(),
                     ),
             ));
         } else {
@@ -388,10 +577,11 @@ impl Player {
 
         futures::try_ready!(timer.poll().context(crate::error::Sleep));
         let ret = frame.diff.clone();
+        let dur =
+            frame.adjusted_dur(self.playback_ratio, self.max_frame_length());
 
         self.idx += 1;
-        self.played_amount +=
-            frame.adjusted_dur(self.playback_ratio, self.max_frame_length);
+        self.played_amount += dur;
         self.set_timer();
         self.clear_match_idx();
 
@@ -409,7 +599,7 @@ enum FileState {
         fut: tokio::fs::file::OpenFuture<String>,
     },
     Open {
-        reader: ttyrec::Reader<tokio::fs::File>,
+        reader: crate::recording::Reader<tokio::fs::File>,
         parser: vt100::Parser,
     },
     Eof,
@@ -420,8 +610,11 @@ enum InputState {
     Search { query: String },
 }
 
-struct PlaySession {
+struct PlaySession<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+> {
     file: FileState,
+    format: Option<crate::recording::Format>,
     player: Player,
     raw_screen: Option<crossterm::screen::RawScreen>,
     alternate_screen: Option<crossterm::screen::AlternateScreen>,
@@ -430,19 +623,38 @@ struct PlaySession {
     last_frame_screen: Option<vt100::Screen>,
     input_state: InputState,
     hide_ui: bool,
+    caps: crate::term::Caps,
+
+    // when streaming, the recording (but not the player ui) is also sent to
+    // the server, so that everyone watching sees the same thing that the
+    // person running the player sees, including pauses and seeks
+    client: Option<crate::client::Client<S>>,
+    connected: bool,
+    term: vt100::Parser,
+    last_screen: vt100::Screen,
+    needs_screen_update: bool,
 }
 
-impl PlaySession {
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    PlaySession<S>
+{
     fn new(
         filename: &str,
+        format: Option<crate::recording::Format>,
         play_at_start: bool,
         playback_ratio: f32,
         max_frame_length: Option<std::time::Duration>,
+        caps: crate::term::Caps,
+        client: Option<crate::client::Client<S>>,
     ) -> Self {
+        let term = vt100::Parser::default();
+        let screen = term.screen().clone();
+
         Self {
             file: FileState::Closed {
                 filename: filename.to_string(),
             },
+            format,
             player: Player::new(
                 play_at_start,
                 playback_ratio,
@@ -455,6 +667,13 @@ impl PlaySession {
             last_frame_screen: None,
             input_state: InputState::Normal,
             hide_ui: false,
+            caps,
+
+            client,
+            connected: false,
+            term,
+            last_screen: screen,
+            needs_screen_update: false,
         }
     }
 
@@ -493,6 +712,11 @@ impl PlaySession {
             ) => {
                 self.player.playback_ratio_reset();
             }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('i'),
+            ) => {
+                self.player.toggle_skip_idle();
+            }
             crossterm::input::InputEvent::Keyboard(
                 crossterm::input::KeyEvent::Char('<'),
             ) => {
@@ -503,6 +727,16 @@ impl PlaySession {
             ) => {
                 self.player.forward();
             }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Left,
+            ) => {
+                self.player.seek_back();
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Right,
+            ) => {
+                self.player.seek_forward();
+            }
             crossterm::input::InputEvent::Keyboard(
                 crossterm::input::KeyEvent::Char('0'),
             ) => {
@@ -587,17 +821,25 @@ impl PlaySession {
         }
     }
 
-    fn redraw(&self) -> Result<()> {
-        let frame = if let Some(frame) = self.player.current_frame() {
-            frame
+    fn redraw(&mut self) -> Result<()> {
+        let full = if let Some(frame) = self.player.current_frame() {
+            frame.full.clone()
         } else {
             return Ok(());
         };
-        self.write(&frame.full)?;
+        self.write_frame(&full)?;
         self.draw_ui()?;
         Ok(())
     }
 
+    fn write_frame(&mut self, data: &[u8]) -> Result<()> {
+        if self.client.is_some() {
+            self.term.process(data);
+            self.needs_screen_update = true;
+        }
+        self.write(data)
+    }
+
     fn write(&self, data: &[u8]) -> Result<()> {
         // TODO async
         let stdout = std::io::stdout();
@@ -611,7 +853,10 @@ impl PlaySession {
         let size = crate::term::Size::get()?;
 
         if self.player.paused() && !self.hide_ui {
-            self.write(b"\x1b7\x1b[37;44m\x1b[?25l")?;
+            self.write(
+                format!("\x1b7{}\x1b[?25l", self.caps.overlay_style())
+                    .as_bytes(),
+            )?;
 
             self.draw_status()?;
             self.draw_help(size)?;
@@ -626,87 +871,81 @@ impl PlaySession {
 
     fn draw_status(&self) -> Result<()> {
         let msg = format!(
-            "paused (frame {}/{})",
+            "paused (frame {}/{}{})",
             self.player.current_frame_idx() + 1,
-            self.player.num_frames()
+            self.player.num_frames(),
+            if self.player.skip_idle() {
+                ", skipping idle"
+            } else {
+                ""
+            }
         );
+        let border = self.caps.border();
 
         self.write(b"\x1b[2;2H")?;
-        self.write("╭".as_bytes())?;
-        self.write("─".repeat(2 + msg.len()).as_bytes())?;
-        self.write("╮".as_bytes())?;
+        self.write(border.top(2 + msg.len()).as_bytes())?;
 
         self.write(b"\x1b[3;2H")?;
-        self.write(format!("│ {} │", msg).as_bytes())?;
+        self.write(border.line(&msg, msg.len()).as_bytes())?;
 
         self.write(b"\x1b[4;2H")?;
-        self.write("╰".as_bytes())?;
-        self.write("─".repeat(2 + msg.len()).as_bytes())?;
-        self.write("╯".as_bytes())?;
+        self.write(border.bottom(2 + msg.len()).as_bytes())?;
 
         Ok(())
     }
 
     fn draw_help(&self, size: crate::term::Size) -> Result<()> {
-        self.write(
-            format!("\x1b[{};{}H", size.rows - 12, size.cols - 32).as_bytes(),
-        )?;
-        self.write("╭".as_bytes())?;
-        self.write("─".repeat(30).as_bytes())?;
-        self.write("╮".as_bytes())?;
+        let border = self.caps.border();
+        let seek = if self.caps.unicode {
+            "←/→: seek 10s back/forward"
+        } else {
+            "left/right: seek -10s/+10s"
+        };
+        let lines = [
+            "q: quit",
+            "Space: pause/unpause",
+            "Backspace: hide/show ui",
+            "</>: previous/next frame",
+            seek,
+            "0/$: first/last frame",
+            "+/-: increase/decrease speed",
+            "=: normal speed",
+            "i: toggle idle skipping",
+            "/: search",
+            "n/p: next/previous match",
+        ];
 
         self.write(
-            format!("\x1b[{};{}H", size.rows - 11, size.cols - 32).as_bytes(),
-        )?;
-        self.write("│             Keys             │".as_bytes())?;
-        self.write(
-            format!("\x1b[{};{}H", size.rows - 10, size.cols - 32).as_bytes(),
-        )?;
-        self.write("│ q: quit                      │".as_bytes())?;
-        self.write(
-            format!("\x1b[{};{}H", size.rows - 9, size.cols - 32).as_bytes(),
-        )?;
-        self.write("│ Space: pause/unpause         │".as_bytes())?;
-        self.write(
-            format!("\x1b[{};{}H", size.rows - 8, size.cols - 32).as_bytes(),
-        )?;
-        self.write("│ Backspace: hide/show ui      │".as_bytes())?;
-        self.write(
-            format!("\x1b[{};{}H", size.rows - 7, size.cols - 32).as_bytes(),
-        )?;
-        self.write("│ </>: previous/next frame     │".as_bytes())?;
-        self.write(
-            format!("\x1b[{};{}H", size.rows - 6, size.cols - 32).as_bytes(),
-        )?;
-        self.write("│ 0/$: first/last frame        │".as_bytes())?;
-        self.write(
-            format!("\x1b[{};{}H", size.rows - 5, size.cols - 32).as_bytes(),
-        )?;
-        self.write("│ +/-: increase/decrease speed │".as_bytes())?;
-        self.write(
-            format!("\x1b[{};{}H", size.rows - 4, size.cols - 32).as_bytes(),
-        )?;
-        self.write("│ =: normal speed              │".as_bytes())?;
-        self.write(
-            format!("\x1b[{};{}H", size.rows - 3, size.cols - 32).as_bytes(),
+            format!("\x1b[{};{}H", size.rows - 14, size.cols - 32).as_bytes(),
         )?;
-        self.write("│ /: search                    │".as_bytes())?;
+        self.write(border.top(30).as_bytes())?;
+
         self.write(
-            format!("\x1b[{};{}H", size.rows - 2, size.cols - 32).as_bytes(),
+            format!("\x1b[{};{}H", size.rows - 13, size.cols - 32).as_bytes(),
         )?;
-        self.write("│ n/p: next/previous match     │".as_bytes())?;
+        self.write(border.line(&format!("{:^28}", "Keys"), 28).as_bytes())?;
+        for (i, line) in lines.iter().enumerate() {
+            self.write(
+                format!(
+                    "\x1b[{};{}H",
+                    usize::from(size.rows) - 12 + i,
+                    size.cols - 32
+                )
+                .as_bytes(),
+            )?;
+            self.write(border.line(line, 28).as_bytes())?;
+        }
 
         self.write(
             format!("\x1b[{};{}H", size.rows - 1, size.cols - 32).as_bytes(),
         )?;
-        self.write("╰".as_bytes())?;
-        self.write("─".repeat(30).as_bytes())?;
-        self.write("╯".as_bytes())?;
+        self.write(border.bottom(30).as_bytes())?;
 
         Ok(())
     }
 
     fn draw_search(&self, size: crate::term::Size) -> Result<()> {
+        let border = self.caps.border();
         match &self.input_state {
             InputState::Normal => {
                 if !self.player.paused() {
@@ -714,88 +953,79 @@ impl PlaySession {
                 }
 
                 if let Some(state) = &self.player.search_state {
-                    self.write(b"\x1b7\x1b[37;44m")?;
+                    self.write(
+                        format!("\x1b7{}", self.caps.overlay_style())
+                            .as_bytes(),
+                    )?;
                     self.write(
                         format!("\x1b[{};{}H", 2, size.cols - 32).as_bytes(),
                     )?;
-                    self.write("╭".as_bytes())?;
-                    self.write("─".repeat(30).as_bytes())?;
-                    self.write("╮".as_bytes())?;
+                    self.write(border.top(30).as_bytes())?;
 
                     let msg = if let Some(idx) = state.idx {
-                        format!("match ({}/{})", idx + 1, state.count)
+                        format!("match ({}/{}):", idx + 1, state.count)
                     } else {
-                        format!("match (-/{})", state.count)
+                        format!("match (-/{}):", state.count)
                     };
                     self.write(
                         format!("\x1b[{};{}H", 3, size.cols - 32).as_bytes(),
                     )?;
-                    self.write(
-                        format!("│ {}:{} │", msg, " ".repeat(27 - msg.len()))
-                            .as_bytes(),
-                    )?;
+                    self.write(border.line(&msg, 28).as_bytes())?;
 
                     self.write(
                         format!("\x1b[{};{}H", 4, size.cols - 32).as_bytes(),
                     )?;
                     let query_str = state.query.as_str();
                     if query_str.len() > 26 {
-                        self.write(
-                            format!("│ /{:24}... │", &query_str[0..24])
-                                .as_bytes(),
-                        )?;
+                        let truncated = format!("/{}...", &query_str[0..24]);
+                        self.write(border.line(&truncated, 28).as_bytes())?;
                     } else {
                         let regex_str = format!("/{}/", query_str);
-                        self.write(
-                            format!("│ {:28} │", regex_str).as_bytes(),
-                        )?;
+                        self.write(border.line(&regex_str, 28).as_bytes())?;
                     }
 
                     self.write(
                         format!("\x1b[{};{}H", 5, size.cols - 32).as_bytes(),
                     )?;
-                    self.write("╰".as_bytes())?;
-                    self.write("─".repeat(30).as_bytes())?;
-                    self.write("╯".as_bytes())?;
+                    self.write(border.bottom(30).as_bytes())?;
 
                     self.write(b"\x1b8")?;
                 }
             }
             InputState::Search { query } => {
-                self.write(b"\x1b7\x1b[37;44m")?;
+                self.write(
+                    format!("\x1b7{}", self.caps.overlay_style()).as_bytes(),
+                )?;
                 self.write(
                     format!("\x1b[{};{}H", 2, size.cols - 32).as_bytes(),
                 )?;
-                self.write("╭".as_bytes())?;
-                self.write("─".repeat(30).as_bytes())?;
-                self.write("╮".as_bytes())?;
+                self.write(border.top(30).as_bytes())?;
 
                 self.write(
                     format!("\x1b[{};{}H", 3, size.cols - 32).as_bytes(),
                 )?;
-                self.write("│ search:                      │".as_bytes())?;
+                self.write(border.line("search:", 28).as_bytes())?;
 
                 self.write(
                     format!("\x1b[{};{}H", 4, size.cols - 32).as_bytes(),
                 )?;
                 self.write(
-                    format!(
-                        "│ {:28} │",
-                        if query.len() > 28 {
-                            &query[query.len() - 28..]
-                        } else {
-                            query
-                        }
-                    )
-                    .as_bytes(),
+                    border
+                        .line(
+                            if query.len() > 28 {
+                                &query[query.len() - 28..]
+                            } else {
+                                query
+                            },
+                            28,
+                        )
+                        .as_bytes(),
                 )?;
 
                 self.write(
                     format!("\x1b[{};{}H", 5, size.cols - 32).as_bytes(),
                 )?;
-                self.write("╰".as_bytes())?;
-                self.write("─".repeat(30).as_bytes())?;
-                self.write("╯".as_bytes())?;
+                self.write(border.bottom(30).as_bytes())?;
 
                 self.write(
                     format!(
@@ -811,7 +1041,9 @@ impl PlaySession {
     }
 }
 
-impl PlaySession {
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    PlaySession<S>
+{
     const POLL_FNS:
         &'static [&'static dyn for<'a> Fn(
             &'a mut Self,
@@ -824,6 +1056,8 @@ impl PlaySession {
         &Self::poll_read_file,
         &Self::poll_input,
         &Self::poll_write_terminal,
+        &Self::poll_read_client,
+        &Self::poll_write_server,
     ];
 
     fn poll_open_file(&mut self) -> component_future::Poll<(), Error> {
@@ -844,8 +1078,9 @@ impl PlaySession {
                         }
                     }));
                 let size = crate::term::Size::get()?;
-                let reader = ttyrec::Reader::new(file);
+                let reader = crate::recording::Reader::new(file, self.format);
                 let parser = vt100::Parser::new(size.rows, size.cols, 0);
+                self.term.set_size(size.rows, size.cols);
                 self.file = FileState::Open { reader, parser };
                 Ok(component_future::Async::DidWork)
             }
@@ -855,13 +1090,12 @@ impl PlaySession {
 
     fn poll_read_file(&mut self) -> component_future::Poll<(), Error> {
         if let FileState::Open { reader, parser } = &mut self.file {
-            if let Some(frame) = component_future::try_ready!(reader
-                .poll_read()
-                .context(crate::error::ReadTtyrec))
+            if let Some(frame) =
+                component_future::try_ready!(reader.poll_read())
             {
                 parser.process(&frame.data);
 
-                let frame_time = frame.time - reader.offset().unwrap();
+                let frame_time = frame.time;
                 let frame_dur = frame_time - self.last_frame_time;
                 self.last_frame_time = frame_time;
 
@@ -877,6 +1111,7 @@ impl PlaySession {
                 self.last_frame_screen = Some(parser.screen().clone());
                 self.player.add_frame(Frame {
                     dur: frame_dur,
+                    time: frame_time,
                     full,
                     diff,
                 });
@@ -906,8 +1141,23 @@ impl PlaySession {
             );
         }
 
-        let e = component_future::try_ready!(self.key_reader.poll()).unwrap();
-        let quit = self.keypress(&e)?;
+        let quit = match component_future::try_ready!(self.key_reader.poll())
+            .unwrap()
+        {
+            crate::key_reader::Event::Input(e) => self.keypress(&e)?,
+            // pasting is only useful for entering a search query, and
+            // shouldn't be treated as a series of commands otherwise
+            crate::key_reader::Event::Paste(data) => {
+                if let InputState::Search { query } = &mut self.input_state {
+                    query.extend(
+                        String::from_utf8_lossy(&data)
+                            .chars()
+                            .filter(|c| !c.is_control()),
+                    );
+                }
+                false
+            }
+        };
         if quit {
             self.write(b"\x1b[?25h")?;
             Ok(component_future::Async::Ready(()))
@@ -923,7 +1173,7 @@ impl PlaySession {
         }
 
         if let Some(data) = component_future::try_ready!(self.player.poll()) {
-            self.write(&data)?;
+            self.write_frame(&data)?;
             self.draw_ui()?;
             Ok(component_future::Async::DidWork)
         } else if let FileState::Eof = self.file {
@@ -932,10 +1182,116 @@ impl PlaySession {
             Ok(component_future::Async::NothingToDo)
         }
     }
+
+    // this should never return Err, because we don't want server
+    // communication issues to ever interrupt playback
+    fn poll_read_client(&mut self) -> component_future::Poll<(), Error> {
+        let client = if let Some(client) = &mut self.client {
+            client
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        match client.poll() {
+            Ok(futures::Async::Ready(Some(e))) => match e {
+                crate::client::Event::Disconnect => {
+                    self.connected = false;
+                    Ok(component_future::Async::DidWork)
+                }
+                crate::client::Event::Connect => {
+                    self.connected = true;
+                    client.send_message(
+                        crate::protocol::Message::terminal_output(
+                            &self.last_screen.contents_formatted(),
+                        ),
+                    );
+                    Ok(component_future::Async::DidWork)
+                }
+                crate::client::Event::SendQueueFull { .. } => {
+                    // redraw the whole screen, since some of the diffs
+                    // never made it to the server
+                    client.send_message(
+                        crate::protocol::Message::terminal_output(
+                            &self.last_screen.contents_formatted(),
+                        ),
+                    );
+                    Ok(component_future::Async::DidWork)
+                }
+                // there is nowhere to show these during playback
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::Chat { .. },
+                )
+                | crate::client::Event::ServerMessage(
+                    crate::protocol::Message::Notice { .. },
+                )
+                | crate::client::Event::ServerMessage(
+                    crate::protocol::Message::WatcherCount { .. },
+                ) => Ok(component_future::Async::DidWork),
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::RequestControl { username },
+                ) => {
+                    // there is no terminal to control during playback
+                    client.send_message(
+                        crate::protocol::Message::revoke_control(&username),
+                    );
+                    Ok(component_future::Async::DidWork)
+                }
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::TimeLimit { remaining },
+                ) => {
+                    // playback continues locally once the server ends the
+                    // stream, but reconnecting would just start a new one
+                    if remaining == 0 {
+                        self.client = None;
+                        self.connected = false;
+                    }
+                    Ok(component_future::Async::DidWork)
+                }
+                crate::client::Event::ServerMessage(..) => {
+                    client.reconnect();
+                    Ok(component_future::Async::DidWork)
+                }
+            },
+            Ok(futures::Async::Ready(None)) => {
+                // the client should never exit on its own
+                unreachable!()
+            }
+            Ok(futures::Async::NotReady) => {
+                Ok(component_future::Async::NotReady)
+            }
+            Err(..) => {
+                client.reconnect();
+                Ok(component_future::Async::DidWork)
+            }
+        }
+    }
+
+    fn poll_write_server(&mut self) -> component_future::Poll<(), Error> {
+        let client = if let Some(client) = &mut self.client {
+            client
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        if !self.connected || !self.needs_screen_update {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        let screen = self.term.screen().clone();
+        client.send_message(crate::protocol::Message::terminal_output(
+            &screen.contents_diff(&self.last_screen),
+        ));
+        self.last_screen = screen;
+        self.needs_screen_update = false;
+
+        Ok(component_future::Async::DidWork)
+    }
 }
 
 #[must_use = "futures do nothing unless polled"]
-impl futures::Future for PlaySession {
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    futures::Future for PlaySession<S>
+{
     type Item = ();
     type Error = Error;
 
@@ -8,6 +8,9 @@ pub struct Config {
 
     #[serde(default)]
     ttyrec: crate::config::Ttyrec,
+
+    #[serde(default)]
+    record: crate::config::Record,
 }
 
 impl crate::config::Config for Config {
@@ -17,23 +20,23 @@ impl crate::config::Config for Config {
     ) -> Result<()> {
         self.command.merge_args(matches)?;
         self.ttyrec.merge_args(matches)?;
+        self.record.merge_args(matches)?;
         Ok(())
     }
 
     fn run(
         &self,
     ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
-        Box::new(RecordSession::new(
-            &self.ttyrec.filename,
-            &self.command.command,
-            &self.command.args,
-        ))
+        let (cmd, args) = self.command.process_command();
+        Box::new(RecordSession::new(&self.ttyrec, &self.record, &cmd, &args))
     }
 }
 
 pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
     crate::config::Command::cmd(crate::config::Ttyrec::cmd(
-        app.about("Record a terminal session to a file"),
+        crate::config::Record::cmd(
+            app.about("Record a terminal session to a file"),
+        ),
     ))
 }
 
@@ -50,51 +53,66 @@ pub fn config(
     Ok(Box::new(config))
 }
 
-#[allow(clippy::large_enum_variant)]
-enum FileState {
-    Closed {
-        filename: String,
-    },
-    Opening {
-        filename: String,
-        fut: tokio::fs::file::CreateFuture<String>,
-    },
-    Open {
-        writer: ttyrec::Writer<tokio::fs::File>,
-    },
-}
-
 struct RecordSession {
-    file: FileState,
-    frame_data: Vec<u8>,
+    file: crate::recorder::Recorder,
 
-    process:
-        tokio_pty_process_stream::ResizingProcess<crate::async_stdin::Stdin>,
+    // typed input is recorded as a separate ttyrec file, with timestamps
+    // that line up with the output file
+    input_file: Option<crate::recorder::Recorder>,
+
+    process: tokio_pty_process_stream::ResizingProcess<
+        crate::channel_reader::ChannelReader,
+    >,
     raw_screen: Option<crossterm::screen::RawScreen>,
     done: bool,
 
+    stdin: crate::async_stdin::Stdin,
+    stdin_done: bool,
+    input: Option<tokio::sync::mpsc::UnboundedSender<Vec<u8>>>,
+
     stdout: tokio::io::Stdout,
     to_write_stdout: std::collections::VecDeque<u8>,
     needs_flush: bool,
 }
 
 impl RecordSession {
-    fn new(filename: &str, cmd: &str, args: &[String]) -> Self {
-        let input = crate::async_stdin::Stdin::new();
+    fn new(
+        ttyrec: &crate::config::Ttyrec,
+        record: &crate::config::Record,
+        cmd: &str,
+        args: &[String],
+    ) -> Self {
+        let format = ttyrec.format.unwrap_or_default();
+        let mut file = crate::recorder::Recorder::new(&ttyrec.filename);
+        file.set_format(format);
+        if let Some(max_file_size) = record.max_file_size {
+            file.set_max_size(max_file_size, record.rotate, record.gzip);
+        }
+        let input_file = record.input_filename.as_ref().map(|filename| {
+            let mut input_file = crate::recorder::Recorder::new(filename);
+            input_file.set_format(format);
+            input_file
+        });
+
+        let stdin = crate::async_stdin::Stdin::new();
+        let (input_tx, input) = crate::channel_reader::ChannelReader::new();
         let process = tokio_pty_process_stream::ResizingProcess::new(
             tokio_pty_process_stream::Process::new(cmd, args, input),
         );
 
         Self {
-            file: FileState::Closed {
-                filename: filename.to_string(),
-            },
-            frame_data: vec![],
+            file,
+
+            input_file,
 
             process,
             raw_screen: None,
             done: false,
 
+            stdin,
+            stdin_done: false,
+            input: Some(input_tx),
+
             stdout: tokio::io::stdout(),
             to_write_stdout: std::collections::VecDeque::new(),
             needs_flush: false,
@@ -102,7 +120,7 @@ impl RecordSession {
     }
 
     fn record_bytes(&mut self, buf: &[u8]) {
-        self.frame_data.extend(buf);
+        self.file.frame(buf);
         self.to_write_stdout.extend(buf);
     }
 }
@@ -117,6 +135,7 @@ impl RecordSession {
             Error,
         >] = &[
         &Self::poll_open_file,
+        &Self::poll_read_stdin,
         &Self::poll_read_process,
         &Self::poll_write_terminal,
         &Self::poll_flush_terminal,
@@ -124,31 +143,34 @@ impl RecordSession {
     ];
 
     fn poll_open_file(&mut self) -> component_future::Poll<(), Error> {
-        match &mut self.file {
-            FileState::Closed { filename } => {
-                self.file = FileState::Opening {
-                    filename: filename.to_string(),
-                    fut: tokio::fs::File::create(filename.to_string()),
-                };
-                Ok(component_future::Async::DidWork)
-            }
-            FileState::Opening { filename, fut } => {
-                let file = component_future::try_ready!(fut
-                    .poll()
-                    .with_context(|| {
-                        crate::error::OpenFile {
-                            filename: filename.clone(),
-                        }
-                    }));
-                self.file = FileState::Open {
-                    writer: ttyrec::Writer::new(file),
-                };
-                Ok(component_future::Async::DidWork)
+        let input = if let Some(input_file) = &mut self.input_file {
+            input_file.poll_open()?
+        } else {
+            component_future::Async::NothingToDo
+        };
+        let output = self.file.poll_open()?;
+        Ok(either(input, output))
+    }
+
+    fn poll_read_stdin(&mut self) -> component_future::Poll<(), Error> {
+        if self.stdin_done {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        if let Some(data) = component_future::try_ready!(self.stdin.poll()) {
+            if let Some(input_file) = &mut self.input_file {
+                input_file.frame(&data);
             }
-            FileState::Open { .. } => {
-                Ok(component_future::Async::NothingToDo)
+            if let Some(input) = &mut self.input {
+                // if this fails, the process has already gone away
+                let _ = input.try_send(data);
             }
+        } else {
+            // dropping the sender is how the process sees eof
+            self.stdin_done = true;
+            self.input = None;
         }
+        Ok(component_future::Async::DidWork)
     }
 
     fn poll_read_process(&mut self) -> component_future::Poll<(), Error> {
@@ -217,33 +239,47 @@ impl RecordSession {
     }
 
     fn poll_write_file(&mut self) -> component_future::Poll<(), Error> {
-        let writer = match &mut self.file {
-            FileState::Open { writer } => writer,
-            _ => {
-                return Ok(component_future::Async::NothingToDo);
-            }
+        let input = if let Some(input_file) = &mut self.input_file {
+            input_file.poll_write()?
+        } else {
+            component_future::Async::NothingToDo
         };
-
-        if !self.frame_data.is_empty() {
-            writer
-                .frame(&self.frame_data)
-                .context(crate::error::WriteTtyrec)?;
-            self.frame_data.clear();
+        let output = self.file.poll_write()?;
+        match either(input, output) {
+            component_future::Async::NothingToDo => {}
+            res => return Ok(res),
         }
 
-        if writer.needs_write() {
-            component_future::try_ready!(writer
-                .poll_write()
-                .context(crate::error::WriteTtyrec));
-            Ok(component_future::Async::DidWork)
+        // finish writing to the file before actually ending
+        let flushed = self.file.is_flushed()
+            && self
+                .input_file
+                .as_ref()
+                .map_or(true, crate::recorder::Recorder::is_flushed);
+        if self.done && flushed {
+            Ok(component_future::Async::Ready(()))
         } else {
-            // finish writing to the file before actually ending
-            if self.done {
-                Ok(component_future::Async::Ready(()))
-            } else {
-                Ok(component_future::Async::NothingToDo)
-            }
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
+}
+
+// the input and output files are polled independently, so that one of them
+// being slow to open or write doesn't hold up the other
+fn either(
+    a: component_future::Async<()>,
+    b: component_future::Async<()>,
+) -> component_future::Async<()> {
+    match (a, b) {
+        (component_future::Async::DidWork, _)
+        | (_, component_future::Async::DidWork) => {
+            component_future::Async::DidWork
+        }
+        (component_future::Async::NotReady, _)
+        | (_, component_future::Async::NotReady) => {
+            component_future::Async::NotReady
         }
+        _ => component_future::Async::NothingToDo,
     }
 }
 
@@ -1,5 +1,17 @@
 use crate::prelude::*;
 use std::io::Read as _;
+use std::os::unix::io::AsRawFd as _;
+
+type Acceptor = Box<
+    dyn futures::Stream<
+            Item = (
+                tokio::net::TcpStream,
+                std::net::SocketAddr,
+                crate::server::ListenerRoles,
+            ),
+            Error = Error,
+        > + Send,
+>;
 
 #[derive(serde::Deserialize, Debug, Default)]
 pub struct Config {
@@ -40,24 +52,92 @@ impl crate::config::Config for Config {
                     .map(|config| (*ty, config.clone()))
             })
             .collect();
-        if let Some(tls_identity_file) = &self.server.tls_identity_file {
+        // the tls and host keys need to be read before dropping privileges,
+        // since they are typically only readable by root
+        let tls_acceptor = match self
+            .server
+            .tls_identity_file
+            .as_ref()
+            .map(std::string::String::as_str)
+            .map(accept_tls)
+            .transpose()
+        {
+            Ok(tls_acceptor) => tls_acceptor,
+            Err(e) => return Box::new(futures::future::err(e)),
+        };
+        let host_key = match self
+            .server
+            .host_key_file
+            .as_ref()
+            .map(std::string::String::as_str)
+            .map(crate::host_key::HostKey::load_or_generate)
+            .transpose()
+        {
+            Ok(host_key) => host_key,
+            Err(e) => return Box::new(futures::future::err(e)),
+        };
+        let bans = match crate::server::access::Bans::load(
+            self.server
+                .ban_file
+                .as_ref()
+                .map(std::string::String::as_str),
+        ) {
+            Ok(bans) => bans,
+            Err(e) => return Box::new(futures::future::err(e)),
+        };
+        let listeners = listeners(&self.server);
+        let acceptor =
+            match listen(&listeners, self.server.uid, self.server.gid) {
+                Ok(acceptor) => acceptor,
+                Err(e) => return Box::new(futures::future::err(e)),
+            };
+        // detach only after binding, so that errors in the configuration are
+        // still reported to the terminal that started the server
+        if let Err(e) = detach(&self.server) {
+            return Box::new(futures::future::err(e));
+        }
+        if let Some(tls_acceptor) = tls_acceptor {
             create_server_tls(
-                self.server.listen_address,
+                acceptor,
+                tls_acceptor,
                 self.server.read_timeout,
-                tls_identity_file,
+                self.server.history_window,
+                self.server.quarantine_duration,
+                self.server.trusted_proxies.iter().copied().collect(),
+                self.server.disconnect_grace,
+                self.server.max_stream_duration,
+                self.server.stream_warnings.clone(),
+                self.server.max_connections,
+                self.server.max_watchers_per_session,
+                self.server.send_queue_size,
+                self.server.send_queue_policy,
                 self.server.allowed_login_methods.clone(),
+                self.server.admins.iter().cloned().collect(),
+                self.server.trust_local_plain_admins,
+                bans,
                 oauth_configs,
-                self.server.uid,
-                self.server.gid,
+                host_key,
             )
         } else {
             create_server(
-                self.server.listen_address,
+                acceptor,
                 self.server.read_timeout,
+                self.server.history_window,
+                self.server.quarantine_duration,
+                self.server.trusted_proxies.iter().copied().collect(),
+                self.server.disconnect_grace,
+                self.server.max_stream_duration,
+                self.server.stream_warnings.clone(),
+                self.server.max_connections,
+                self.server.max_watchers_per_session,
+                self.server.send_queue_size,
+                self.server.send_queue_policy,
                 self.server.allowed_login_methods.clone(),
+                self.server.admins.iter().cloned().collect(),
+                self.server.trust_local_plain_admins,
+                bans,
                 oauth_configs,
-                self.server.uid,
-                self.server.gid,
+                host_key,
             )
         }
     }
@@ -81,82 +161,173 @@ pub fn config(
 }
 
 fn create_server(
-    address: std::net::SocketAddr,
+    acceptor: Acceptor,
     read_timeout: std::time::Duration,
+    history_window: std::time::Duration,
+    quarantine_duration: Option<std::time::Duration>,
+    trusted_proxies: std::collections::HashSet<std::net::IpAddr>,
+    disconnect_grace: Option<std::time::Duration>,
+    max_stream_duration: Option<std::time::Duration>,
+    stream_warnings: Vec<std::time::Duration>,
+    max_connections: Option<u32>,
+    max_watchers_per_session: Option<u32>,
+    send_queue_size: usize,
+    send_queue_policy: crate::server::SendQueuePolicy,
     allowed_login_methods: std::collections::HashSet<
         crate::protocol::AuthType,
     >,
+    admins: std::collections::HashSet<(crate::protocol::AuthType, String)>,
+    trust_local_plain_admins: bool,
+    bans: crate::server::access::Bans,
     oauth_configs: std::collections::HashMap<
         crate::protocol::AuthType,
         crate::oauth::Config,
     >,
-    uid: Option<users::uid_t>,
-    gid: Option<users::gid_t>,
+    host_key: Option<crate::host_key::HostKey>,
 ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
-    let listener = match listen(address, uid, gid) {
-        Ok(listener) => listener,
-        Err(e) => return Box::new(futures::future::err(e)),
-    };
-
-    let acceptor = listener.incoming().context(crate::error::Acceptor);
     let server = crate::server::Server::new(
-        Box::new(acceptor),
+        acceptor,
         read_timeout,
+        history_window,
+        quarantine_duration,
+        trusted_proxies,
+        disconnect_grace,
+        max_stream_duration,
+        stream_warnings,
+        max_connections,
+        max_watchers_per_session,
+        send_queue_size,
+        send_queue_policy,
         allowed_login_methods,
+        admins,
+        trust_local_plain_admins,
+        bans,
         oauth_configs,
+        host_key,
     );
 
     Box::new(server)
 }
 
 fn create_server_tls(
-    address: std::net::SocketAddr,
+    acceptor: Acceptor,
+    tls_acceptor: tokio_tls::TlsAcceptor,
     read_timeout: std::time::Duration,
-    tls_identity_file: &str,
+    history_window: std::time::Duration,
+    quarantine_duration: Option<std::time::Duration>,
+    trusted_proxies: std::collections::HashSet<std::net::IpAddr>,
+    disconnect_grace: Option<std::time::Duration>,
+    max_stream_duration: Option<std::time::Duration>,
+    stream_warnings: Vec<std::time::Duration>,
+    max_connections: Option<u32>,
+    max_watchers_per_session: Option<u32>,
+    send_queue_size: usize,
+    send_queue_policy: crate::server::SendQueuePolicy,
     allowed_login_methods: std::collections::HashSet<
         crate::protocol::AuthType,
     >,
+    admins: std::collections::HashSet<(crate::protocol::AuthType, String)>,
+    trust_local_plain_admins: bool,
+    bans: crate::server::access::Bans,
     oauth_configs: std::collections::HashMap<
         crate::protocol::AuthType,
         crate::oauth::Config,
     >,
-    uid: Option<users::uid_t>,
-    gid: Option<users::gid_t>,
+    host_key: Option<crate::host_key::HostKey>,
 ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
-    let tls_acceptor = match accept_tls(tls_identity_file) {
-        Ok(acceptor) => acceptor,
-        Err(e) => return Box::new(futures::future::err(e)),
-    };
-
-    let listener = match listen(address, uid, gid) {
-        Ok(listener) => listener,
-        Err(e) => return Box::new(futures::future::err(e)),
-    };
-
-    let acceptor = listener
-        .incoming()
-        .context(crate::error::Acceptor)
-        .map(move |sock| tls_acceptor.accept(sock));
+    let acceptor = acceptor.map(move |(sock, address, roles)| {
+        (tls_acceptor.accept(sock), address, roles)
+    });
     let server = crate::server::tls::Server::new(
         Box::new(acceptor),
         read_timeout,
+        history_window,
+        quarantine_duration,
+        trusted_proxies,
+        disconnect_grace,
+        max_stream_duration,
+        stream_warnings,
+        max_connections,
+        max_watchers_per_session,
+        send_queue_size,
+        send_queue_policy,
         allowed_login_methods,
+        admins,
+        trust_local_plain_admins,
+        bans,
         oauth_configs,
+        host_key,
     );
 
     Box::new(server)
 }
 
+// dedicated streaming or watching listeners take that role away from the
+// main listener
+fn listeners(
+    server: &crate::config::Server,
+) -> Vec<(std::net::SocketAddr, crate::server::ListenerRoles)> {
+    let mut listeners = vec![(
+        server.listen_address,
+        crate::server::ListenerRoles {
+            stream: server.stream_listen_address.is_none(),
+            watch: server.watch_listen_address.is_none(),
+        },
+    )];
+    if let Some(address) = server.stream_listen_address {
+        listeners.push((
+            address,
+            crate::server::ListenerRoles {
+                stream: true,
+                watch: false,
+            },
+        ));
+    }
+    if let Some(address) = server.watch_listen_address {
+        listeners.push((
+            address,
+            crate::server::ListenerRoles {
+                stream: false,
+                watch: true,
+            },
+        ));
+    }
+    listeners
+}
+
 fn listen(
-    address: std::net::SocketAddr,
+    listeners: &[(std::net::SocketAddr, crate::server::ListenerRoles)],
     uid: Option<users::uid_t>,
     gid: Option<users::gid_t>,
-) -> Result<tokio::net::TcpListener> {
-    let listener = tokio::net::TcpListener::bind(&address)
-        .context(crate::error::Bind { address })?;
+) -> Result<Acceptor> {
+    let mut acceptor: Acceptor = Box::new(futures::stream::empty());
+    for &(address, roles) in listeners {
+        let listener = tokio::net::TcpListener::bind(&address)
+            .context(crate::error::Bind { address })?;
+        log::info!("Listening on {}", address);
+        let incoming = listener
+            .incoming()
+            .context(crate::error::Acceptor)
+            .filter_map(move |sock| {
+                peer_addr(&sock).map(|peer| (sock, peer, roles))
+            });
+        acceptor = Box::new(acceptor.select(incoming));
+    }
+    // all of the listeners need to be bound before dropping privileges, in
+    // case any of them are on privileged ports
     drop_privs(uid, gid)?;
-    log::info!("Listening on {}", address);
-    Ok(listener)
+    Ok(acceptor)
+}
+
+// the server tracks client addresses so that it can quarantine misbehaving
+// clients, so there's nothing useful to do with a socket whose address we
+// can't determine
+fn peer_addr(sock: &tokio::net::TcpStream) -> Option<std::net::SocketAddr> {
+    sock.peer_addr()
+        .map_err(|e| {
+            log::warn!("failed to get address of new connection: {}", e);
+        })
+        .ok()
 }
 
 fn accept_tls(tls_identity_file: &str) -> Result<tokio_tls::TlsAcceptor> {
@@ -176,6 +347,87 @@ fn accept_tls(tls_identity_file: &str) -> Result<tokio_tls::TlsAcceptor> {
     Ok(tokio_tls::TlsAcceptor::from(acceptor))
 }
 
+fn detach(server: &crate::config::Server) -> Result<()> {
+    // open the log file before daemonizing, since daemonizing changes the
+    // working directory
+    let log_file = server
+        .log_file
+        .as_ref()
+        .map(|filename| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(filename)
+                .context(crate::error::OpenFileSync { filename })
+        })
+        .transpose()?;
+
+    if server.daemonize {
+        daemonize()?;
+        let null = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/null")
+            .context(crate::error::OpenFileSync {
+                filename: "/dev/null",
+            })?;
+        redirect_fd(&null, libc::STDIN_FILENO)?;
+        if log_file.is_none() {
+            redirect_fd(&null, libc::STDOUT_FILENO)?;
+            redirect_fd(&null, libc::STDERR_FILENO)?;
+        }
+    }
+
+    if let Some(log_file) = &log_file {
+        redirect_fd(log_file, libc::STDOUT_FILENO)?;
+        redirect_fd(log_file, libc::STDERR_FILENO)?;
+    }
+
+    if let Some(pid_file) = &server.pid_file {
+        std::fs::write(pid_file, format!("{}\n", std::process::id()))
+            .context(crate::error::WritePidFile { filename: pid_file })?;
+    }
+
+    Ok(())
+}
+
+// the usual double fork, so that the server is not a session leader and can
+// never reacquire a controlling terminal
+fn daemonize() -> Result<()> {
+    fork()?;
+    if unsafe { libc::setsid() } == -1 {
+        return Err(Error::Setsid {
+            source: std::io::Error::last_os_error(),
+        });
+    }
+    fork()?;
+    std::env::set_current_dir("/").context(crate::error::Chdir)?;
+    Ok(())
+}
+
+// returns in the child process, and exits in the parent
+fn fork() -> Result<()> {
+    match unsafe { libc::fork() } {
+        -1 => Err(Error::Fork {
+            source: std::io::Error::last_os_error(),
+        }),
+        0 => Ok(()),
+        _ => std::process::exit(0),
+    }
+}
+
+fn redirect_fd(
+    file: &std::fs::File,
+    fd: std::os::unix::io::RawFd,
+) -> Result<()> {
+    if unsafe { libc::dup2(file.as_raw_fd(), fd) } == -1 {
+        return Err(Error::RedirectFd {
+            source: std::io::Error::last_os_error(),
+        });
+    }
+    Ok(())
+}
+
 fn drop_privs(
     uid: Option<users::uid_t>,
     gid: Option<users::gid_t>,
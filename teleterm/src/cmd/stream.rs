@@ -1,6 +1,9 @@
 use crate::prelude::*;
 use tokio::io::AsyncWrite as _;
 
+// how often the throughput to each server is measured
+const STATS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
 #[derive(serde::Deserialize, Debug, Default)]
 pub struct Config {
     #[serde(default)]
@@ -8,6 +11,45 @@ pub struct Config {
 
     #[serde(default)]
     command: crate::config::Command,
+
+    #[serde(default)]
+    stream: crate::config::Stream,
+
+    #[serde(default)]
+    serial: crate::config::Serial,
+}
+
+impl Config {
+    // streaming a recording, a file, or a serial console doesn't spawn
+    // anything, so only the command itself needs to be checked
+    fn check_allowed_command(&self) -> Result<()> {
+        if self.stream.allowed_commands.is_empty()
+            || self.stream.from_file.is_some()
+            || self.stream.tail.is_some()
+            || self.serial.device.is_some()
+        {
+            return Ok(());
+        }
+
+        let command = &self.command.command;
+        if self.stream.allowed_commands.contains(command) {
+            // things like LD_PRELOAD would allow running anything at all
+            if self.command.env.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::CommandEnvNotAllowed)
+            }
+        } else {
+            log::warn!(
+                "refusing to run {:?} with args {:?}: not an allowed command",
+                command,
+                self.command.args
+            );
+            Err(Error::CommandNotAllowed {
+                command: command.to_string(),
+            })
+        }
+    }
 }
 
 impl crate::config::Config for Config {
@@ -17,12 +59,29 @@ impl crate::config::Config for Config {
     ) -> Result<()> {
         self.client.merge_args(matches)?;
         self.command.merge_args(matches)?;
+        self.stream.merge_args(matches)?;
+        self.serial.merge_args(matches)?;
         Ok(())
     }
 
     fn run(
         &self,
     ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
+        if let Err(e) = self.check_allowed_command() {
+            return Box::new(futures::future::err(e));
+        }
+
+        // nothing needs to log in to a server, so the type of connection
+        // that isn't going to be made doesn't matter
+        if self.stream.no_remote {
+            return Box::new(StreamSession::<tokio::net::TcpStream>::new(
+                &self.command,
+                &self.serial,
+                &self.stream,
+                vec![],
+            ));
+        }
+
         let auth = match self.client.auth {
             crate::protocol::AuthType::Plain => {
                 let username = self
@@ -36,63 +95,58 @@ impl crate::config::Config for Config {
                 }
             }
             crate::protocol::AuthType::RecurseCenter => {
-                let id = crate::client::load_client_auth_id(self.client.auth);
+                let id = crate::credentials::load(self.client.auth);
                 crate::protocol::Auth::recurse_center(
                     id.as_ref().map(std::string::String::as_str),
                 )
             }
+            crate::protocol::AuthType::Github => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::github(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
+            crate::protocol::AuthType::Google => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::google(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
         };
 
-        let host = self.client.host().to_string();
-        let address = *self.client.addr();
-        if self.client.tls {
-            let connector = match native_tls::TlsConnector::new()
-                .context(crate::error::CreateConnector)
-            {
+        // each server gets its own connection, named the same way as its
+        // host key is stored
+        let mut remotes = vec![];
+        for (host, address) in self.client.connect_addresses() {
+            let name = crate::config::server_name(&host, &address);
+            let connector = match self.client.connector_to(&host, address) {
                 Ok(connector) => connector,
                 Err(e) => return Box::new(futures::future::err(e)),
             };
-            let connect: crate::client::Connector<_> = Box::new(move || {
-                let host = host.clone();
-                let connector = connector.clone();
-                let connector = tokio_tls::TlsConnector::from(connector);
-                let stream = tokio::net::tcp::TcpStream::connect(&address);
-                Box::new(
-                    stream
-                        .context(crate::error::Connect { address })
-                        .and_then(move |stream| {
-                            connector
-                                .connect(&host, stream)
-                                .context(crate::error::ConnectTls { host })
-                        }),
-                )
-            });
-            Box::new(StreamSession::new(
-                &self.command.command,
-                &self.command.args,
-                connect,
-                &auth,
-            ))
-        } else {
-            let connect: crate::client::Connector<_> = Box::new(move || {
-                Box::new(
-                    tokio::net::tcp::TcpStream::connect(&address)
-                        .context(crate::error::Connect { address }),
-                )
-            });
-            Box::new(StreamSession::new(
-                &self.command.command,
-                &self.command.args,
+            let connect: crate::client::Connector<_> =
+                Box::new(move || connector.connect());
+            remotes.push(Remote::new(
+                &name,
                 connect,
                 &auth,
-            ))
+                &self.stream,
+                self.client.timeout,
+            ));
         }
+        Box::new(StreamSession::new(
+            &self.command,
+            &self.serial,
+            &self.stream,
+            remotes,
+        ))
     }
 }
 
 pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
     crate::config::Client::cmd(crate::config::Command::cmd(
-        app.about("Stream your terminal"),
+        crate::config::Stream::cmd(crate::config::Serial::cmd(
+            app.about("Stream your terminal"),
+        )),
     ))
 }
 
@@ -112,78 +166,517 @@ pub fn config(
     Ok(Box::new(config))
 }
 
-struct StreamSession<
+// one of the servers being streamed to. each one connects and reconnects
+// on its own, and keeps track of what it has already been sent.
+struct Remote<
     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
 > {
+    name: String,
     client: crate::client::Client<S>,
     connected: bool,
-
-    process:
-        tokio_pty_process_stream::ResizingProcess<crate::async_stdin::Stdin>,
-    raw_screen: Option<crossterm::screen::RawScreen>,
-    done: bool,
-
-    term: vt100::Parser,
+    // set once the server ends the stream for running too long, after
+    // which the process keeps running but is no longer streamed there
+    time_limited: bool,
+    // as last reported by the server
+    watchers: u32,
     last_screen: vt100::Screen,
-    needs_screen_update: bool,
-
-    stdout: tokio::io::Stdout,
-    to_print: std::collections::VecDeque<u8>,
-    needs_flush: bool,
+    // in bytes per second, as of the last time the stats were updated
+    throughput: u64,
+    last_bytes_sent: u64,
 }
 
 impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
-    StreamSession<S>
+    Remote<S>
 {
     fn new(
-        cmd: &str,
-        args: &[String],
+        name: &str,
         connect: crate::client::Connector<S>,
         auth: &crate::protocol::Auth,
+        stream: &crate::config::Stream,
+        timeout: Option<std::time::Duration>,
     ) -> Self {
         let term_type =
             std::env::var("TERM").unwrap_or_else(|_| "".to_string());
-        let client = crate::client::Client::stream(
+        let mut client = crate::client::Client::stream(
             &term_type,
             connect,
             auth,
             crate::protocol::AuthClient::Cli,
+            &stream.allowed_watchers,
+            &stream.description,
         );
+        client
+            .set_send_queue(stream.send_queue_size, stream.send_queue_policy);
+        client.set_operation_timeout(timeout);
+        if let Some(room) = &stream.room {
+            client.set_room(room);
+        }
 
-        // TODO: tokio::io::stdin is broken (it's blocking)
+        Self {
+            name: name.to_string(),
+            client,
+            connected: false,
+            time_limited: false,
+            watchers: 0,
+            // the same as the (empty) screen that streaming starts from
+            last_screen: vt100::Parser::default().screen().clone(),
+            throughput: 0,
+            last_bytes_sent: 0,
+        }
+    }
+}
+
+struct StreamSession<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+> {
+    remotes: Vec<Remote<S>>,
+
+    process: Box<
+        dyn futures::Stream<
+                Item = tokio_pty_process_stream::Event,
+                Error = Error,
+            > + Send,
+    >,
+    raw_screen: Option<crossterm::screen::RawScreen>,
+    done: bool,
+    // there is no process to send input to when streaming a recording or a
+    // file, so we need to provide a way to stop it. this describes what is
+    // being streamed in that case.
+    replaying: Option<&'static str>,
+
+    stdin: crate::async_stdin::Stdin,
+    stdin_done: bool,
+    input: Option<tokio::sync::mpsc::UnboundedSender<Vec<u8>>>,
+    prefix_key: u8,
+    prefix_pressed: bool,
+    // the chat message being typed, if any. this is kept as bytes since
+    // characters can be split across reads.
+    chat_input: Option<Vec<u8>>,
+
+    // along with the index of the remote that the watcher is on
+    allow_control: bool,
+    control_request: Option<(usize, String)>,
+    controller: Option<(usize, String)>,
+
+    term: vt100::Parser,
+    needs_screen_update: bool,
+    // while paused, the process keeps running locally but none of its
+    // output is sent to the servers
+    paused: bool,
+
+    stdout: tokio::io::Stdout,
+    to_print: std::collections::VecDeque<u8>,
+    needs_flush: bool,
+    // whether the terminal's original title has been saved, and so needs
+    // to be restored before exiting
+    title_saved: bool,
+
+    recorder: Option<crate::recorder::Recorder>,
+
+    stats_timer: tokio::timer::Interval,
+
+    naming: Option<crate::naming::Naming>,
+    metadata: Option<crate::naming::Metadata>,
+    // from --title and --tags, and used for whatever the naming command
+    // leaves empty
+    default_metadata: crate::naming::Metadata,
+
+    status_line: crate::status_line::StatusLine,
+}
+
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    StreamSession<S>
+{
+    fn new(
+        command: &crate::config::Command,
+        serial: &crate::config::Serial,
+        stream: &crate::config::Stream,
+        remotes: Vec<Remote<S>>,
+    ) -> Self {
+        // tokio::io::stdin is blocking, so we use our own
         // see https://github.com/tokio-rs/tokio/issues/589
-        // let input = tokio::io::stdin();
-        let input = crate::async_stdin::Stdin::new();
+        let stdin = crate::async_stdin::Stdin::new();
 
-        let process = tokio_pty_process_stream::ResizingProcess::new(
-            tokio_pty_process_stream::Process::new(cmd, args, input),
-        );
+        // we read stdin ourselves rather than handing it directly to the
+        // process, so that we can intercept our own command keys and mix in
+        // input from watchers who have been granted control
+        let (input_tx, input) = crate::channel_reader::ChannelReader::new();
+
+        let process: Box<dyn futures::Stream<Item = _, Error = _> + Send> =
+            if let Some(filename) = &stream.from_file {
+                Box::new(crate::replay::Replay::new(
+                    filename,
+                    stream.playback_ratio,
+                ))
+            } else if let Some(filename) = &stream.tail {
+                Box::new(crate::tail::Tail::new(
+                    filename,
+                    &stream.tail_colors,
+                ))
+            } else if let Some(device) = &serial.device {
+                Box::new(crate::serial::Serial::new(
+                    device,
+                    serial.settings(),
+                    input,
+                ))
+            } else {
+                let (cmd, args) = command.process_command();
+                Box::new(
+                    tokio_pty_process_stream::ResizingProcess::new(
+                        tokio_pty_process_stream::Process::new(
+                            &cmd, &args, input,
+                        ),
+                    )
+                    .context(crate::error::Subprocess),
+                )
+            };
 
         let term = vt100::Parser::default();
-        let screen = term.screen().clone();
+
+        let default_metadata = crate::naming::Metadata {
+            title: stream.title.clone(),
+            tags: stream.tags.clone(),
+        };
+        let metadata = if stream.title.is_empty() && stream.tags.is_empty() {
+            None
+        } else {
+            Some(default_metadata.clone())
+        };
 
         Self {
-            client,
-            connected: false,
+            remotes,
 
             process,
             raw_screen: None,
             done: false,
+            replaying: if stream.from_file.is_some() {
+                Some("recording")
+            } else if stream.tail.is_some() {
+                Some("file")
+            } else {
+                None
+            },
+
+            stdin,
+            stdin_done: false,
+            input: Some(input_tx),
+            prefix_key: stream.prefix_key,
+            prefix_pressed: false,
+            chat_input: None,
+
+            allow_control: stream.allow_control,
+            control_request: None,
+            controller: None,
 
             term,
-            last_screen: screen,
             needs_screen_update: false,
+            paused: false,
 
             stdout: tokio::io::stdout(),
             to_print: std::collections::VecDeque::new(),
             needs_flush: false,
+            title_saved: false,
+
+            recorder: stream
+                .record_filename
+                .as_ref()
+                .map(std::string::String::as_str)
+                .map(crate::recorder::Recorder::new),
+
+            stats_timer: tokio::timer::Interval::new_interval(STATS_INTERVAL),
+
+            naming: stream.naming_command.as_ref().map(|naming_command| {
+                crate::naming::Naming::new(
+                    naming_command,
+                    &command.command,
+                    &command.args,
+                )
+            }),
+            metadata,
+            default_metadata,
+
+            status_line: crate::status_line::StatusLine::new(),
         }
     }
 
     fn record_bytes(&mut self, buf: &[u8]) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.frame(buf);
+        }
         self.to_print.extend(buf);
         self.term.process(buf);
         self.needs_screen_update = true;
+        self.draw_status_line();
+    }
+
+    fn draw_status_line(&mut self) {
+        if self.status_line.is_visible() {
+            let (_, cols) = self.term.screen().size();
+            self.to_print.extend(self.status_line.draw(cols));
+        }
+    }
+
+    fn hide_status_line(&mut self) {
+        if self.status_line.is_visible() {
+            self.status_line.hide();
+            self.to_print
+                .extend(self.term.screen().contents_formatted());
+        }
+    }
+
+    // shows where we are streaming to in the terminal's title, using the
+    // xterm title stack to save the original title the first time
+    fn update_title(&mut self) {
+        let remotes: Vec<_> = self
+            .remotes
+            .iter()
+            .filter(|remote| !remote.time_limited)
+            .collect();
+        if remotes.is_empty() {
+            self.restore_title();
+            return;
+        }
+
+        let names: Vec<_> =
+            remotes.iter().map(|remote| remote.name.as_str()).collect();
+        let watchers = if remotes.iter().any(|remote| remote.connected) {
+            Some(remotes.iter().map(|remote| remote.watchers).sum())
+        } else {
+            None
+        };
+        let title = crate::ui::streaming_title(&names, watchers);
+
+        if !self.title_saved {
+            self.to_print.extend(b"\x1b[22;0t");
+            self.title_saved = true;
+        }
+        self.to_print
+            .extend(format!("\x1b]0;{}\x07", title).bytes());
+    }
+
+    fn restore_title(&mut self) {
+        if self.title_saved {
+            self.to_print.extend(b"\x1b[23;0t");
+            self.title_saved = false;
+        }
+    }
+
+    // describes which server something happened on, which is only worth
+    // mentioning when streaming to more than one
+    fn remote_suffix(&self, i: usize) -> String {
+        if self.remotes.len() > 1 {
+            format!(" ({})", self.remotes[i].name)
+        } else {
+            "".to_string()
+        }
+    }
+
+    fn toggle_paused(&mut self) {
+        self.paused = !self.paused;
+        for remote in &mut self.remotes {
+            if remote.connected && remote.client.has_capability("pause") {
+                remote.client.send_message(
+                    crate::protocol::Message::pause_streaming(self.paused),
+                );
+            }
+        }
+        if self.paused {
+            let prefix = crate::config::prefix_key_name(self.prefix_key);
+            self.status_line
+                .set(&format!("stream paused ({} p to resume)", prefix));
+        } else {
+            // the servers haven't seen anything since we paused, so start
+            // them off with the whole screen again
+            let screen = self.term.screen().clone();
+            for remote in &mut self.remotes {
+                if remote.connected {
+                    remote.client.send_message(
+                        crate::protocol::Message::terminal_output(
+                            &screen.contents_formatted(),
+                        ),
+                    );
+                    remote.last_screen = screen.clone();
+                }
+            }
+            self.needs_screen_update = false;
+            self.status_line.flash("stream resumed");
+        }
+        self.draw_status_line();
+    }
+
+    fn show_stats(&mut self) {
+        let stats: Vec<_> = self
+            .remotes
+            .iter()
+            .map(|remote| {
+                let (queued, queue_size) = remote.client.send_queue_len();
+                let stats = crate::ui::stream_stats(
+                    remote.client.bytes_sent(),
+                    remote.throughput,
+                    queued,
+                    queue_size,
+                );
+                if self.remotes.len() > 1 {
+                    format!("{}: {}", remote.name, stats)
+                } else {
+                    stats
+                }
+            })
+            .collect();
+        self.status_line.flash(&stats.join("; "));
+        self.draw_status_line();
+    }
+
+    fn draw_chat_input(&mut self) {
+        if let Some(text) = &self.chat_input {
+            let text = String::from_utf8_lossy(text);
+            self.status_line.set(&crate::ui::chat_input(&text));
+            self.draw_status_line();
+        }
+    }
+
+    fn send_chat(&mut self) {
+        let text = self.chat_input.take().unwrap();
+        let text = String::from_utf8_lossy(&text);
+        if !text.is_empty() {
+            for remote in &mut self.remotes {
+                if remote.connected && remote.client.has_capability("chat") {
+                    remote.client.send_message(
+                        crate::protocol::Message::chat("", &text),
+                    );
+                }
+            }
+        }
+        self.hide_status_line();
+    }
+
+    // returns false if the rest of the input should be ignored
+    fn handle_chat_key(&mut self, c: u8) -> bool {
+        let text = self.chat_input.as_mut().unwrap();
+        match c {
+            b'\r' | b'\n' => self.send_chat(),
+            b'\x1b' => {
+                self.chat_input = None;
+                self.hide_status_line();
+                // the rest of an escape sequence (from an arrow key, for
+                // instance) shouldn't end up in the terminal either
+                return false;
+            }
+            b'\x7f' | b'\x08' => {
+                // remove a whole utf8 character, not just its last byte
+                while let Some(c) = text.pop() {
+                    if c & 0b1100_0000 != 0b1000_0000 {
+                        break;
+                    }
+                }
+                self.draw_chat_input();
+            }
+            c if c.is_ascii_control() => {}
+            c => {
+                text.push(c);
+                self.draw_chat_input();
+            }
+        }
+        true
+    }
+
+    fn write_process(&mut self, data: &[u8]) {
+        if let Some(input) = &mut self.input {
+            // if this fails, the process has already gone away
+            let _ = input.try_send(data.to_vec());
+        }
+    }
+
+    // commands are given by pressing the prefix key followed by a command
+    // key, similar to screen or tmux. the prefix key itself can be sent to
+    // the process by pressing it twice.
+    fn handle_input(&mut self, data: &[u8]) {
+        let mut to_process = vec![];
+        for &c in data {
+            if self.chat_input.is_some() {
+                if self.handle_chat_key(c) {
+                    continue;
+                } else {
+                    break;
+                }
+            }
+            if self.prefix_pressed {
+                self.prefix_pressed = false;
+                if c == self.prefix_key {
+                    to_process.push(c);
+                } else if !self.handle_command_key(c) {
+                    to_process.push(self.prefix_key);
+                    to_process.push(c);
+                }
+            } else if c == self.prefix_key {
+                self.prefix_pressed = true;
+            } else {
+                to_process.push(c);
+            }
+        }
+        if !to_process.is_empty() {
+            self.write_process(&to_process);
+        }
+    }
+
+    // returns false if the key wasn't a command, so that it can be passed
+    // through to the process
+    fn handle_command_key(&mut self, c: u8) -> bool {
+        let prefix = crate::config::prefix_key_name(self.prefix_key);
+        match c {
+            b'y' if self.allow_control => {
+                if let Some((i, username)) = self.control_request.take() {
+                    self.remotes[i].client.send_message(
+                        crate::protocol::Message::grant_control(&username),
+                    );
+                    self.status_line.flash(&format!(
+                        "{} has control ({} r to revoke)",
+                        username, prefix
+                    ));
+                    self.draw_status_line();
+                    self.controller = Some((i, username));
+                }
+                true
+            }
+            b'n' if self.allow_control => {
+                if let Some((i, username)) = self.control_request.take() {
+                    self.remotes[i].client.send_message(
+                        crate::protocol::Message::revoke_control(&username),
+                    );
+                    self.hide_status_line();
+                }
+                true
+            }
+            b'r' if self.allow_control => {
+                if let Some((i, username)) = self.controller.take() {
+                    self.remotes[i].client.send_message(
+                        crate::protocol::Message::revoke_control(&username),
+                    );
+                    self.status_line
+                        .flash(&format!("revoked control from {}", username));
+                    self.draw_status_line();
+                }
+                true
+            }
+            b'p' => {
+                self.toggle_paused();
+                true
+            }
+            b's' if !self.remotes.is_empty() => {
+                self.show_stats();
+                true
+            }
+            b'c' if !self.remotes.is_empty() => {
+                self.chat_input = Some(vec![]);
+                self.draw_chat_input();
+                true
+            }
+            b'q' if self.replaying.is_some() => {
+                self.done = true;
+                true
+            }
+            _ => false,
+        }
     }
 }
 
@@ -199,58 +692,328 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             Error,
         >] = &[
         &Self::poll_read_client,
+        &Self::poll_read_stdin,
         &Self::poll_read_process,
+        &Self::poll_naming,
+        &Self::poll_stats,
+        &Self::poll_status_line,
         &Self::poll_write_terminal,
         &Self::poll_flush_terminal,
+        &Self::poll_open_file,
+        &Self::poll_write_file,
         &Self::poll_write_server,
     ];
 
     // this should never return Err, because we don't want server
     // communication issues to ever interrupt a running process
     fn poll_read_client(&mut self) -> component_future::Poll<(), Error> {
-        match self.client.poll() {
+        let mut did_work = false;
+        let mut not_ready = false;
+
+        for i in 0..self.remotes.len() {
+            match self.poll_read_remote(i) {
+                component_future::Async::DidWork => {
+                    did_work = true;
+                }
+                component_future::Async::NotReady => {
+                    not_ready = true;
+                }
+                _ => {}
+            }
+        }
+
+        if did_work {
+            Ok(component_future::Async::DidWork)
+        } else if not_ready {
+            Ok(component_future::Async::NotReady)
+        } else {
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
+
+    fn poll_read_remote(&mut self, i: usize) -> component_future::Async<()> {
+        if self.remotes[i].time_limited {
+            return component_future::Async::NothingToDo;
+        }
+
+        match self.remotes[i].client.poll() {
             Ok(futures::Async::Ready(Some(e))) => match e {
                 crate::client::Event::Disconnect => {
-                    self.connected = false;
-                    Ok(component_future::Async::DidWork)
+                    self.remotes[i].connected = false;
+                    self.remotes[i].watchers = 0;
+                    self.update_title();
+                    // the server forgets who is in control when we
+                    // disconnect
+                    if self.control_request.as_ref().map(|(j, _)| *j)
+                        == Some(i)
+                    {
+                        self.control_request = None;
+                    }
+                    if self.controller.as_ref().map(|(j, _)| *j) == Some(i) {
+                        self.controller = None;
+                    }
+                    if let Some(err) = self.remotes[i].client.last_error() {
+                        self.status_line.flash(&format!(
+                            "disconnected{}: {}",
+                            self.remote_suffix(i),
+                            err
+                        ));
+                        self.draw_status_line();
+                    }
+                    component_future::Async::DidWork
                 }
                 crate::client::Event::Connect => {
-                    self.connected = true;
-                    self.client.send_message(
+                    let remote = &mut self.remotes[i];
+                    remote.connected = true;
+                    // a resumed stream may still be paused on the server
+                    // from before we disconnected
+                    if remote.client.has_capability("pause") {
+                        remote.client.send_message(
+                            crate::protocol::Message::pause_streaming(
+                                self.paused,
+                            ),
+                        );
+                    }
+                    // otherwise, the whole screen will be sent once we
+                    // resume
+                    if !self.paused {
+                        // the server has nothing yet, so start it off with
+                        // the whole screen
+                        let screen = self.term.screen().clone();
+                        remote.client.send_message(
+                            crate::protocol::Message::terminal_output(
+                                &screen.contents_formatted(),
+                            ),
+                        );
+                        remote.last_screen = screen;
+                    }
+                    if let Some(metadata) = &self.metadata {
+                        remote.client.send_message(
+                            crate::protocol::Message::update_metadata(
+                                &metadata.title,
+                                &metadata.tags,
+                            ),
+                        );
+                    }
+                    self.update_title();
+                    component_future::Async::DidWork
+                }
+                crate::client::Event::SendQueueFull { dropped, queued } => {
+                    // the diffs that were dropped are no longer in the
+                    // watchers' screens, so send the whole thing again
+                    let remote = &mut self.remotes[i];
+                    remote.client.send_message(
                         crate::protocol::Message::terminal_output(
-                            &self.last_screen.contents_formatted(),
+                            &remote.last_screen.contents_formatted(),
                         ),
                     );
-                    Ok(component_future::Async::DidWork)
+                    self.status_line.flash(&format!(
+                        "server{} is behind, dropped {} updates ({} queued)",
+                        self.remote_suffix(i),
+                        dropped,
+                        queued
+                    ));
+                    self.draw_status_line();
+                    component_future::Async::DidWork
+                }
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::Chat { from, text },
+                ) => {
+                    // don't cover up the message being composed
+                    if self.chat_input.is_none() {
+                        self.status_line
+                            .flash(&format!("{}: {}", from, text));
+                        self.draw_status_line();
+                    }
+                    component_future::Async::DidWork
+                }
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::Notice { text },
+                ) => {
+                    self.status_line.flash(&format!(
+                        "{}{}",
+                        crate::ui::notice(&text),
+                        self.remote_suffix(i)
+                    ));
+                    self.draw_status_line();
+                    component_future::Async::DidWork
+                }
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::RequestControl { username },
+                ) => {
+                    if self.allow_control {
+                        let prefix =
+                            crate::config::prefix_key_name(self.prefix_key);
+                        self.status_line.set(&format!(
+                            "{} wants control ({} y to allow, {} n to deny)",
+                            username, prefix, prefix
+                        ));
+                        self.draw_status_line();
+                        self.control_request = Some((i, username));
+                    } else {
+                        self.remotes[i].client.send_message(
+                            crate::protocol::Message::revoke_control(
+                                &username,
+                            ),
+                        );
+                    }
+                    component_future::Async::DidWork
+                }
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::RevokeControl { username },
+                ) => {
+                    // the watcher gave control back
+                    if self.controller == Some((i, username.clone())) {
+                        self.controller = None;
+                        self.status_line.flash(&format!(
+                            "{} gave control back",
+                            username
+                        ));
+                        self.draw_status_line();
+                    }
+                    component_future::Async::DidWork
+                }
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::TerminalInput { data },
+                ) => {
+                    // the server already checks this, but input could still
+                    // be in flight after we revoke control. a watcher with
+                    // the same name on another server doesn't count.
+                    if self.controller.as_ref().map(|(j, _)| *j) == Some(i) {
+                        self.write_process(&data);
+                    }
+                    component_future::Async::DidWork
+                }
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::TimeLimit { remaining },
+                ) => {
+                    if remaining == 0 {
+                        // the server is about to close the connection, and
+                        // reconnecting would just start a new stream
+                        let remote = &mut self.remotes[i];
+                        remote.time_limited = true;
+                        remote.connected = false;
+                        remote.watchers = 0;
+                        remote.client.reconnect();
+                        self.update_title();
+                        self.status_line.flash(&format!(
+                            "stream ended{} (server time limit hit)",
+                            self.remote_suffix(i)
+                        ));
+                    } else {
+                        self.status_line.flash(&format!(
+                            "stream will end in {}{} (server time limit)",
+                            crate::ui::format_time(remaining),
+                            self.remote_suffix(i)
+                        ));
+                    }
+                    self.draw_status_line();
+                    component_future::Async::DidWork
+                }
+                crate::client::Event::ServerMessage(
+                    crate::protocol::Message::WatcherCount { count },
+                ) => {
+                    self.remotes[i].watchers = count;
+                    self.update_title();
+                    component_future::Async::DidWork
                 }
                 crate::client::Event::ServerMessage(..) => {
-                    // we don't expect to ever see a server message once we
-                    // start streaming, so if one comes through, assume
-                    // something is messed up and try again
-                    self.client.reconnect();
-                    Ok(component_future::Async::DidWork)
+                    // we don't expect to ever see any other server
+                    // messages once we start streaming, so if one comes
+                    // through, assume something is messed up and try again
+                    self.remotes[i].client.reconnect();
+                    component_future::Async::DidWork
                 }
             },
             Ok(futures::Async::Ready(None)) => {
                 // the client should never exit on its own
                 unreachable!()
             }
+            Ok(futures::Async::NotReady) => component_future::Async::NotReady,
+            Err(..) => {
+                self.remotes[i].client.reconnect();
+                component_future::Async::DidWork
+            }
+        }
+    }
+
+    // like the server connection, a broken naming command shouldn't
+    // interrupt the stream
+    fn poll_naming(&mut self) -> component_future::Poll<(), Error> {
+        let naming = if let Some(naming) = &mut self.naming {
+            naming
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        match naming.poll() {
+            Ok(futures::Async::Ready(Some(mut metadata))) => {
+                if metadata.title.is_empty() {
+                    metadata.title = self.default_metadata.title.clone();
+                }
+                if metadata.tags.is_empty() {
+                    metadata.tags = self.default_metadata.tags.clone();
+                }
+                // otherwise, it will be sent once we connect
+                for remote in &mut self.remotes {
+                    if remote.connected {
+                        remote.client.send_message(
+                            crate::protocol::Message::update_metadata(
+                                &metadata.title,
+                                &metadata.tags,
+                            ),
+                        );
+                    }
+                }
+                self.metadata = Some(metadata);
+            }
+            Ok(futures::Async::Ready(None)) => unreachable!(),
             Ok(futures::Async::NotReady) => {
-                Ok(component_future::Async::NotReady)
+                return Ok(component_future::Async::NotReady);
             }
-            Err(..) => {
-                self.client.reconnect();
-                Ok(component_future::Async::DidWork)
+            Err(e) => {
+                log::warn!("disabling naming command: {}", e);
+                self.naming = None;
             }
         }
+        Ok(component_future::Async::DidWork)
     }
 
-    fn poll_read_process(&mut self) -> component_future::Poll<(), Error> {
-        match component_future::try_ready!(self
-            .process
+    fn poll_stats(&mut self) -> component_future::Poll<(), Error> {
+        if self.remotes.is_empty() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        component_future::try_ready!(self
+            .stats_timer
             .poll()
-            .context(crate::error::Subprocess))
-        {
+            .context(crate::error::TimerStats));
+        for remote in &mut self.remotes {
+            let bytes_sent = remote.client.bytes_sent();
+            remote.throughput = (bytes_sent - remote.last_bytes_sent)
+                / STATS_INTERVAL.as_secs();
+            remote.last_bytes_sent = bytes_sent;
+        }
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_read_stdin(&mut self) -> component_future::Poll<(), Error> {
+        if self.stdin_done {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        if let Some(data) = component_future::try_ready!(self.stdin.poll()) {
+            self.handle_input(&data);
+        } else {
+            // dropping the sender is how the process sees eof
+            self.stdin_done = true;
+            self.input = None;
+        }
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_read_process(&mut self) -> component_future::Poll<(), Error> {
+        match component_future::try_ready!(self.process.poll()) {
             Some(tokio_pty_process_stream::Event::CommandStart {
                 ..
             }) => {
@@ -259,6 +1022,16 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                         crossterm::screen::RawScreen::into_raw_mode()
                             .context(crate::error::ToRawMode)?,
                     );
+                    self.update_title();
+                }
+                if let Some(what) = self.replaying {
+                    let prefix =
+                        crate::config::prefix_key_name(self.prefix_key);
+                    self.status_line.flash(&format!(
+                        "streaming {} ({} q to stop)",
+                        what, prefix
+                    ));
+                    self.draw_status_line();
                 }
             }
             Some(tokio_pty_process_stream::Event::CommandExit { .. }) => {
@@ -271,9 +1044,18 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                 size: (rows, cols),
             }) => {
                 self.term.set_size(rows, cols);
-                self.client.send_message(crate::protocol::Message::resize(
-                    crate::term::Size { rows, cols },
-                ));
+                for remote in &mut self.remotes {
+                    remote.client.send_message(
+                        crate::protocol::Message::resize(crate::term::Size {
+                            rows,
+                            cols,
+                        }),
+                    );
+                }
+                // the status line is padded to the width of the terminal,
+                // so it needs to be redrawn at the new width rather than
+                // waiting for the process to produce more output
+                self.draw_status_line();
             }
             None => {
                 if !self.done {
@@ -286,6 +1068,18 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         Ok(component_future::Async::DidWork)
     }
 
+    fn poll_status_line(&mut self) -> component_future::Poll<(), Error> {
+        if !self.status_line.expires() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        component_future::try_ready!(self.status_line.poll_expire());
+        // redraw the screen to get rid of the status line
+        self.to_print
+            .extend(self.term.screen().contents_formatted());
+        Ok(component_future::Async::DidWork)
+    }
+
     fn poll_write_terminal(&mut self) -> component_future::Poll<(), Error> {
         if self.to_print.is_empty() {
             return Ok(component_future::Async::NothingToDo);
@@ -317,22 +1111,61 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         Ok(component_future::Async::DidWork)
     }
 
+    fn poll_open_file(&mut self) -> component_future::Poll<(), Error> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.poll_open()
+        } else {
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
+
+    fn poll_write_file(&mut self) -> component_future::Poll<(), Error> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.poll_write()
+        } else {
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
+
     fn poll_write_server(&mut self) -> component_future::Poll<(), Error> {
-        if !self.connected || !self.needs_screen_update {
-            // ship all data to the server before actually ending
-            if self.done {
+        let connected = self.remotes.iter().any(|remote| remote.connected);
+        if !connected || !self.needs_screen_update || self.paused {
+            // ship all data to the server (and the recording) before
+            // actually ending
+            let recorded = self
+                .recorder
+                .as_ref()
+                .map_or(true, crate::recorder::Recorder::is_flushed);
+            if self.done && recorded {
+                // the original title has to make it to the terminal
+                // before we exit
+                if self.title_saved {
+                    self.restore_title();
+                    return Ok(component_future::Async::DidWork);
+                }
+                if !self.to_print.is_empty() || self.needs_flush {
+                    return Ok(component_future::Async::NothingToDo);
+                }
                 return Ok(component_future::Async::Ready(()));
             } else {
                 return Ok(component_future::Async::NothingToDo);
             }
         }
 
+        // remotes which aren't connected will be sent the whole screen
+        // once they are
         let screen = self.term.screen().clone();
-        self.client
-            .send_message(crate::protocol::Message::terminal_output(
-                &screen.contents_diff(&self.last_screen),
-            ));
-        self.last_screen = screen;
+        for remote in &mut self.remotes {
+            if !remote.connected {
+                continue;
+            }
+            remote.client.send_message(
+                crate::protocol::Message::terminal_output(
+                    &screen.contents_diff(&remote.last_screen),
+                ),
+            );
+            remote.last_screen = screen.clone();
+        }
         self.needs_screen_update = false;
 
         Ok(component_future::Async::DidWork)
@@ -1,13 +1,26 @@
-// here it's mostly crlf which is made less clear by using the ln forms
-#![allow(clippy::print_with_newline)]
-
 use crate::prelude::*;
+
+use std::convert::TryFrom as _;
 use std::io::Write as _;
 
+const REWIND_STEP: u32 = 30;
+// how many lines which have scrolled off the top of the watched terminal are
+// kept around to scroll back through
+const SCROLLBACK_LINES: usize = 1000;
+// how often the idle times and uptimes in the menu are updated
+const MENU_CLOCK_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(1);
+
 #[derive(serde::Deserialize, Debug, Default)]
 pub struct Config {
     #[serde(default)]
     client: crate::config::Client,
+
+    #[serde(default)]
+    watch: crate::config::Watch,
+
+    #[serde(default)]
+    display: crate::config::Display,
 }
 
 impl crate::config::Config for Config {
@@ -15,12 +28,20 @@ impl crate::config::Config for Config {
         &mut self,
         matches: &clap::ArgMatches<'a>,
     ) -> Result<()> {
-        self.client.merge_args(matches)
+        self.client.merge_args(matches)?;
+        self.watch.merge_args(matches)?;
+        self.display.merge_args(matches)?;
+        Ok(())
     }
 
     fn run(
         &self,
     ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
+        // times are formatted with localtime_r, which reads this
+        if let Some(time_zone) = &self.watch.time_zone {
+            std::env::set_var("TZ", time_zone);
+        }
+
         let auth = match self.client.auth {
             crate::protocol::AuthType::Plain => {
                 let username = self
@@ -34,63 +55,66 @@ impl crate::config::Config for Config {
                 }
             }
             crate::protocol::AuthType::RecurseCenter => {
-                let id = crate::client::load_client_auth_id(self.client.auth);
+                let id = crate::credentials::load(self.client.auth);
                 crate::protocol::Auth::recurse_center(
                     id.as_ref().map(std::string::String::as_str),
                 )
             }
+            crate::protocol::AuthType::Github => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::github(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
+            crate::protocol::AuthType::Google => {
+                let id = crate::credentials::load(self.client.auth);
+                crate::protocol::Auth::google(
+                    id.as_ref().map(std::string::String::as_str),
+                )
+            }
         };
 
-        let host = self.client.host().to_string();
-        let address = *self.client.addr();
-        if self.client.tls {
-            let connector = match native_tls::TlsConnector::new()
-                .context(crate::error::CreateConnector)
-            {
-                Ok(connector) => connector,
-                Err(e) => return Box::new(futures::future::err(e)),
-            };
-            let make_connector: Box<
-                dyn Fn() -> crate::client::Connector<_> + Send,
-            > = Box::new(move || {
-                let host = host.clone();
-                let connector = connector.clone();
-                Box::new(move || {
-                    let host = host.clone();
-                    let connector = connector.clone();
-                    let connector = tokio_tls::TlsConnector::from(connector);
-                    let stream =
-                        tokio::net::tcp::TcpStream::connect(&address);
-                    Box::new(
-                        stream
-                            .context(crate::error::Connect { address })
-                            .and_then(move |stream| {
-                                connector.connect(&host, stream).context(
-                                    crate::error::ConnectTls { host },
-                                )
-                            }),
-                    )
-                })
-            });
-            Box::new(WatchSession::new(make_connector, &auth))
-        } else {
-            let make_connector: Box<
-                dyn Fn() -> crate::client::Connector<_> + Send,
-            > = Box::new(move || {
-                Box::new(move || {
-                    Box::new(
-                        tokio::net::tcp::TcpStream::connect(&address)
-                            .context(crate::error::Connect { address }),
-                    )
-                })
-            });
-            Box::new(WatchSession::new(make_connector, &auth))
+        let connector = match self.client.connector() {
+            Ok(connector) => connector,
+            Err(e) => return Box::new(futures::future::err(e)),
+        };
+        let make_connector: Box<
+            dyn Fn() -> crate::client::Connector<_> + Send,
+        > = Box::new(move || {
+            let connector = connector.clone();
+            Box::new(move || connector.connect())
+        });
+        if self.watch.history {
+            Box::new(HistoryList::new(
+                make_connector(),
+                &auth,
+                self.client.timeout,
+            ))
+        } else {
+            Box::new(WatchSession::new(
+                make_connector,
+                &auth,
+                self.client.timeout,
+                &self.watch.columns,
+                self.watch.jitter_buffer,
+                self.watch.refresh_interval,
+                self.watch.follow.as_ref().map(std::string::String::as_str),
+                self.watch.tag.as_ref().map(std::string::String::as_str),
+                self.watch.room.as_ref().map(std::string::String::as_str),
+                self.watch.render,
+                self.display.caps(),
+                self.watch.interactive,
+                &self.watch.escape,
+                &self.watch.detach_key,
+            ))
         }
     }
 }
 
 pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
-    crate::config::Client::cmd(app.about("Watch teleterm streams"))
+    crate::config::Client::cmd(crate::config::Watch::cmd(
+        crate::config::Display::cmd(app.about("Watch teleterm streams")),
+    ))
 }
 
 pub fn config(
@@ -119,9 +143,29 @@ enum State<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static> {
     Choosing {
         sessions: crate::session_list::SessionList,
         alternate_screen: crossterm::screen::AlternateScreen,
+        // sessions can be chosen by clicking on them
+        mouse: crate::key_reader::MouseCapture,
     },
     Watching {
         client: Box<crate::client::Client<S>>,
+        term: vt100::Parser,
+        id: String,
+        offset: u32,
+        started: std::time::Instant,
+        keyframe_saved: std::time::Instant,
+        // true when the screen is showing cached or disconnected output
+        // rather than the current state of the stream
+        stale: bool,
+
+        jitter_buffer: Option<
+            crate::jitter_buffer::JitterBuffer<crate::protocol::Message>,
+        >,
+    },
+    Tiling {
+        panes: Vec<Pane<S>>,
+        focused: usize,
+        // showing just the focused pane, using the whole terminal
+        zoomed: bool,
     },
 }
 
@@ -159,37 +203,110 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             Self::LoggingIn { alternate_screen } => Self::Choosing {
                 alternate_screen,
                 sessions,
+                mouse: crate::key_reader::MouseCapture::new()?,
             },
             Self::Choosing {
-                alternate_screen, ..
+                alternate_screen,
+                mouse,
+                ..
             } => Self::Choosing {
                 alternate_screen,
                 sessions,
+                mouse,
             },
             _ => Self::Choosing {
                 alternate_screen: new_alternate_screen()?,
                 sessions,
+                mouse: crate::key_reader::MouseCapture::new()?,
             },
         };
         Ok(())
     }
 
-    fn watching(&mut self, client: crate::client::Client<S>) {
+    fn watching(
+        &mut self,
+        client: crate::client::Client<S>,
+        id: &str,
+        offset: u32,
+        size: crate::term::Size,
+        jitter_buffer: std::time::Duration,
+    ) {
         if let Self::Temporary = self {
             unreachable!()
         }
+        // a length of zero means playing output as soon as it arrives
+        let jitter_buffer = if jitter_buffer > std::time::Duration::default()
+        {
+            Some(crate::jitter_buffer::JitterBuffer::new(jitter_buffer))
+        } else {
+            None
+        };
         *self = Self::Watching {
             client: Box::new(client),
+            term: vt100::Parser::new(size.rows, size.cols, SCROLLBACK_LINES),
+            id: id.to_string(),
+            offset,
+            started: std::time::Instant::now(),
+            keyframe_saved: std::time::Instant::now(),
+            stale: false,
+            jitter_buffer,
+        }
+    }
+
+    fn tiling(&mut self, panes: Vec<Pane<S>>) {
+        if let Self::Temporary = self {
+            unreachable!()
+        }
+        *self = Self::Tiling {
+            panes,
+            focused: 0,
+            zoomed: false,
         }
     }
 }
 
+// one of several streams being watched side by side
+struct Pane<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+{
+    client: crate::client::Client<S>,
+    term: vt100::Parser,
+    username: String,
+    title: String,
+    reconnecting: bool,
+    // the stream is gone, but its last screen is left up until we go back
+    // to the list
+    ended: bool,
+}
+
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    Pane<S>
+{
+    fn label(&self) -> String {
+        let state = if self.ended {
+            " (ended)"
+        } else if self.reconnecting {
+            " (reconnecting)"
+        } else {
+            ""
+        };
+        format!("{}: {}{}", self.username, self.title, state)
+    }
+}
+
+struct Bookmark {
+    id: String,
+    // the screen as of when we last switched away from this session, so that
+    // switching back to it can show something right away
+    keyframe: Option<crate::keyframe_cache::Keyframe>,
+}
+
 struct WatchSession<
     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
 > {
     term_type: String,
     make_connector: Box<dyn Fn() -> crate::client::Connector<S> + Send>,
     auth: crate::protocol::Auth,
+    timeout: Option<std::time::Duration>,
 
     key_reader: crate::key_reader::KeyReader,
     list_client: crate::client::Client<S>,
@@ -200,6 +317,65 @@ struct WatchSession<
     state: State<S>,
     raw_screen: Option<crossterm::screen::RawScreen>,
     needs_redraw: bool,
+    render: Box<dyn crate::render::Render>,
+    caps: crate::term::Caps,
+
+    columns: Vec<crate::session_list::Column>,
+    sort: crate::session_list::SortOrder,
+    refresh_interval: std::time::Duration,
+    refresh_timer: Option<tokio::timer::Delay>,
+    // when the times in the menu were last brought up to date
+    menu_clock: Option<(tokio::timer::Delay, std::time::Instant)>,
+    // true when the list being requested is a periodic refresh, rather
+    // than one the user asked for
+    background_refresh: bool,
+    // the user to start watching as soon as they show up in the list
+    follow: Option<String>,
+    // only streams with this tag are listed
+    tag: Option<String>,
+    // the room being browsed, if the user has chosen one
+    room: Option<String>,
+    // true when the server is pushing changes to the list to us, so it
+    // doesn't need to be refreshed
+    subscribed: bool,
+    // the server sends these before the list itself, so they are kept here
+    // until there is a list to show them with
+    server_limits: Option<crate::session_list::Limits>,
+    // the last notice from the server, which stays on the menu
+    notice: Option<String>,
+    // set after tab is pressed in the menu, until the key for the session
+    // to mark is pressed
+    marking: bool,
+    // the sessions marked to be watched side by side, kept across
+    // refreshes of the list
+    marked: Vec<String>,
+
+    status_line: crate::status_line::StatusLine,
+    chat_input: Option<String>,
+    in_control: bool,
+    // ask for control whenever we start watching, and go back to the list
+    // when the escape keys are pressed, rather than just giving control back
+    interactive: bool,
+    escape: crate::key_reader::Escape,
+    escape_name: String,
+    // set after the escape keys are pressed in interactive mode, until the
+    // server confirms that we gave control back
+    detaching: bool,
+    // leaves the stream being watched. this isn't a single letter by
+    // default, since it's easy to type one of those while meaning to type
+    // into a streamer's terminal.
+    detach: crate::key_reader::Escape,
+    detach_name: String,
+    show_help: bool,
+    // what the server last told us about the stream being watched (and
+    // when), for the help overlay
+    watched_session: Option<(crate::protocol::Session, std::time::Instant)>,
+    bookmarks: Vec<Bookmark>,
+
+    timeline: crate::timeline::Timeline,
+    show_timeline: bool,
+
+    jitter_buffer: std::time::Duration,
 }
 
 impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
@@ -208,20 +384,34 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     fn new(
         make_connector: Box<dyn Fn() -> crate::client::Connector<S> + Send>,
         auth: &crate::protocol::Auth,
+        timeout: Option<std::time::Duration>,
+        columns: &[crate::session_list::Column],
+        jitter_buffer: std::time::Duration,
+        refresh_interval: std::time::Duration,
+        follow: Option<&str>,
+        tag: Option<&str>,
+        room: Option<&str>,
+        render: crate::render::Mode,
+        caps: crate::term::Caps,
+        interactive: bool,
+        escape: &[u8],
+        detach_key: &[u8],
     ) -> Self {
         let term_type =
             std::env::var("TERM").unwrap_or_else(|_| "".to_string());
-        let list_client = crate::client::Client::list(
+        let mut list_client = crate::client::Client::list(
             &term_type,
             make_connector(),
             auth,
             crate::protocol::AuthClient::Cli,
         );
+        list_client.set_operation_timeout(timeout);
 
         Self {
             term_type,
             make_connector,
             auth: auth.clone(),
+            timeout,
 
             key_reader: crate::key_reader::KeyReader::new(),
             list_client,
@@ -233,12 +423,56 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             state: State::new(),
             raw_screen: None,
             needs_redraw: true,
+            render: render.renderer(),
+            caps,
+
+            columns: columns.to_vec(),
+            sort: crate::session_list::SortOrder::default(),
+            refresh_interval,
+            refresh_timer: None,
+            menu_clock: None,
+            background_refresh: false,
+            follow: follow.map(std::string::ToString::to_string),
+            tag: tag.map(std::string::ToString::to_string),
+            room: room.map(std::string::ToString::to_string),
+            subscribed: false,
+            server_limits: None,
+            notice: None,
+            marking: false,
+            marked: vec![],
+
+            status_line: crate::status_line::StatusLine::new(),
+            chat_input: None,
+            in_control: false,
+            interactive,
+            escape: crate::key_reader::Escape::new(escape),
+            escape_name: crate::config::escape_name(escape),
+            detaching: false,
+            detach: crate::key_reader::Escape::new(detach_key),
+            detach_name: crate::config::escape_name(detach_key),
+            show_help: false,
+            watched_session: None,
+            bookmarks: vec![],
+
+            timeline: crate::timeline::Timeline::new(),
+            show_timeline: false,
+
+            jitter_buffer,
         }
     }
 
     fn reconnect(&mut self, hard: bool) -> Result<()> {
+        self.save_keyframe();
         self.state.logging_in()?;
         self.needs_redraw = true;
+        self.status_line.hide();
+        self.chat_input = None;
+        self.in_control = false;
+        self.detaching = false;
+        self.detach.reset();
+        self.show_help = false;
+        self.show_timeline = false;
+        self.background_refresh = false;
         if hard {
             self.list_client.reconnect();
         } else {
@@ -269,14 +503,110 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     ) -> Result<()> {
         match msg {
             crate::protocol::Message::Sessions { sessions } => {
-                self.state.choosing(
-                    crate::session_list::SessionList::new(
-                        sessions,
-                        crate::term::Size::get()?,
-                    ),
-                )?;
+                let sessions: Vec<_> = sessions
+                    .into_iter()
+                    .filter(|session| self.wanted_session(session))
+                    .collect();
+                let watching = match self.state {
+                    State::Watching { .. } | State::Tiling { .. } => true,
+                    _ => false,
+                };
+                if !watching && self.follow_session(&sessions)? {
+                    return Ok(());
+                }
+                for session in &sessions {
+                    self.update_watched_session(session)?;
+                }
+
+                match &mut self.state {
+                    State::Choosing { sessions: list, .. }
+                        if self.background_refresh =>
+                    {
+                        list.update(sessions);
+                    }
+                    // the user has already moved on from the menu
+                    _ if self.background_refresh => {}
+                    _ => {
+                        let mut list = crate::session_list::SessionList::new(
+                            sessions,
+                            crate::term::Size::get()?,
+                            self.sort,
+                        );
+                        list.set_limits(self.server_limits);
+                        list.set_notice(self.notice.clone());
+                        list.set_marked(self.marked.clone());
+                        list.set_room(self.room.clone());
+                        self.state.choosing(list)?;
+                    }
+                }
+                self.background_refresh = false;
+                // a zero interval means only refreshing on request
+                if !self.subscribed
+                    && self.refresh_interval > std::time::Duration::default()
+                {
+                    self.refresh_timer = Some(tokio::timer::Delay::new(
+                        std::time::Instant::now() + self.refresh_interval,
+                    ));
+                }
                 self.needs_redraw = true;
             }
+            crate::protocol::Message::SessionAdded { session } => {
+                self.update_watched_session(&session)?;
+                if !self.wanted_session(&session) {
+                    return Ok(());
+                }
+                if let State::Choosing { sessions, .. } = &mut self.state {
+                    sessions.add(session.clone());
+                    self.needs_redraw = true;
+                    self.follow_session(&[session])?;
+                }
+            }
+            crate::protocol::Message::SessionRemoved { id } => {
+                if let State::Choosing { sessions, .. } = &mut self.state {
+                    sessions.remove(&id);
+                    self.needs_redraw = true;
+                }
+            }
+            crate::protocol::Message::SessionUpdated { session } => {
+                self.update_watched_session(&session)?;
+                let wanted = self.wanted_session(&session);
+                if let State::Choosing { sessions, .. } = &mut self.state {
+                    // the tags of a stream can change while it is running
+                    if !wanted {
+                        sessions.remove(&session.id);
+                    } else if sessions.session(&session.id).is_some() {
+                        sessions.replace(session);
+                    } else {
+                        sessions.add(session);
+                    }
+                    self.needs_redraw = true;
+                }
+            }
+            crate::protocol::Message::ServerLimits {
+                connections,
+                max_connections,
+                max_watchers,
+            } => {
+                // zero means that there is no limit
+                let limit = |max| if max == 0 { None } else { Some(max) };
+                self.server_limits = Some(crate::session_list::Limits {
+                    connections,
+                    max_connections: limit(max_connections),
+                    max_watchers: limit(max_watchers),
+                });
+                if let State::Choosing { sessions, .. } = &mut self.state {
+                    sessions.set_limits(self.server_limits);
+                    self.needs_redraw = true;
+                }
+            }
+            // the watch connection shows notices itself while watching
+            crate::protocol::Message::Notice { text } => {
+                self.notice = Some(text);
+                if let State::Choosing { sessions, .. } = &mut self.state {
+                    sessions.set_notice(self.notice.clone());
+                    self.needs_redraw = true;
+                }
+            }
             crate::protocol::Message::Disconnected => {
                 self.reconnect(true)?;
             }
@@ -292,6 +622,59 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         Ok(())
     }
 
+    fn update_watched_session(
+        &mut self,
+        session: &crate::protocol::Session,
+    ) -> Result<()> {
+        if let State::Watching { id, .. } = &self.state {
+            if *id == session.id {
+                self.watched_session =
+                    Some((session.clone(), std::time::Instant::now()));
+                if self.show_help {
+                    self.draw_overlays()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // starts watching the followed user if they are in sessions, returning
+    // whether we did
+    fn follow_session(
+        &mut self,
+        sessions: &[crate::protocol::Session],
+    ) -> Result<bool> {
+        if let Some(id) = self.followed_session(sessions) {
+            // only once, so that going back to the menu doesn't
+            // immediately start watching them again
+            self.follow = None;
+            self.background_refresh = false;
+            write(b"\x07")?;
+            self.watch(&id, 0)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn wanted_session(&self, session: &crate::protocol::Session) -> bool {
+        self.tag
+            .as_ref()
+            .map_or(true, |tag| session.tags.iter().any(|t| t == tag))
+    }
+
+    // returns the id of the followed user's most recently active stream
+    fn followed_session(
+        &self,
+        sessions: &[crate::protocol::Session],
+    ) -> Option<String> {
+        let username = self.follow.as_ref()?;
+        sessions
+            .iter()
+            .filter(|session| &session.username == username)
+            .min_by_key(|session| session.idle_time)
+            .map(|session| session.id.clone())
+    }
+
     fn list_keypress(
         &mut self,
         e: &crossterm::input::InputEvent,
@@ -303,18 +686,114 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                 unreachable!()
             };
 
+        // clicking on a row is the same as pressing its key
+        let clicked;
+        let e = if let crossterm::input::InputEvent::Mouse(
+            crossterm::input::MouseEvent::Press(
+                crossterm::input::MouseButton::Left,
+                _,
+                row,
+            ),
+        ) = e
+        {
+            if let Some(c) = crate::ui::choice_at(sessions, *row) {
+                clicked = crossterm::input::InputEvent::Keyboard(
+                    crossterm::input::KeyEvent::Char(c),
+                );
+                &clicked
+            } else {
+                return Ok(false);
+            }
+        } else {
+            e
+        };
+
+        if self.marking {
+            self.marking = false;
+            if let crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char(c),
+            ) = e
+            {
+                if sessions.toggle_mark(*c) {
+                    self.marked = sessions.marked_ids().to_vec();
+                    self.needs_redraw = true;
+                }
+            }
+            return Ok(false);
+        }
+
+        // the room list only has refreshing, paging, and choosing a room
+        if sessions.browsing_rooms() {
+            if let crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char(c),
+            ) = e
+            {
+                if let Some(room) = sessions.room_for(*c) {
+                    sessions.set_room(Some(room.name.clone()));
+                    self.room = Some(room.name);
+                    self.needs_redraw = true;
+                    return Ok(false);
+                }
+            }
+            if let crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Tab,
+            )
+            | crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Enter,
+            )
+            | crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('s'),
+            ) = e
+            {
+                return Ok(false);
+            }
+        }
+
         match e {
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Esc,
+            ) => {
+                if sessions.room().is_some() {
+                    sessions.set_room(None);
+                    self.room = None;
+                    self.needs_redraw = true;
+                }
+            }
             crossterm::input::InputEvent::Keyboard(
                 crossterm::input::KeyEvent::Char(' '),
             ) => {
                 self.list_client
                     .send_message(crate::protocol::Message::list_sessions());
             }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Tab,
+            ) => {
+                self.marking = true;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Enter,
+            ) => {
+                let marked: Vec<_> =
+                    sessions.marked().into_iter().cloned().collect();
+                match marked.len() {
+                    0 => {}
+                    1 => self.watch(&marked[0].id, 0)?,
+                    _ => self.tile(&marked)?,
+                }
+            }
             crossterm::input::InputEvent::Keyboard(
                 crossterm::input::KeyEvent::Char('q'),
             ) => {
                 return Ok(true);
             }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('s'),
+            ) => {
+                sessions.set_sort(sessions.sort().next());
+                // keep the new order when the list is refreshed
+                self.sort = sessions.sort();
+                self.needs_redraw = true;
+            }
             crossterm::input::InputEvent::Keyboard(
                 crossterm::input::KeyEvent::Char('<'),
             ) => {
@@ -330,16 +809,17 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             crossterm::input::InputEvent::Keyboard(
                 crossterm::input::KeyEvent::Char(c),
             ) => {
-                if let Some(id) = sessions.id_for(*c) {
-                    let client = crate::client::Client::watch(
-                        &self.term_type,
-                        (self.make_connector)(),
-                        &self.auth,
-                        crate::protocol::AuthClient::Cli,
-                        id,
-                    );
-                    self.state.watching(client);
-                    clear()?;
+                if let Some(session) = sessions.session_for(*c) {
+                    let id = session.id.clone();
+                    let description = session.description.clone();
+                    self.watch(&id, 0)?;
+                    // don't hide the notice about showing a cached screen
+                    if !description.is_empty()
+                        && !self.status_line.is_visible()
+                    {
+                        self.status_line.flash(&description);
+                        self.draw_status_line()?;
+                    }
                 }
             }
             _ => {}
@@ -351,22 +831,149 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         &mut self,
         msg: crate::protocol::Message,
     ) -> Result<()> {
+        let (term, id, keyframe_saved, stale) = if let State::Watching {
+            term,
+            id,
+            keyframe_saved,
+            stale,
+            ..
+        } = &mut self.state
+        {
+            (term, id, keyframe_saved, stale)
+        } else {
+            unreachable!()
+        };
+
+        self.timeline.record_message(&msg);
         match msg {
             crate::protocol::Message::TerminalOutput { data } => {
-                // TODO async
-                let stdout = std::io::stdout();
-                let mut stdout = stdout.lock();
-                stdout.write(&data).context(crate::error::WriteTerminal)?;
-                stdout.flush().context(crate::error::FlushTerminal)?;
+                if *stale {
+                    // the server always starts by sending the full screen,
+                    // which will overwrite the cached one
+                    *stale = false;
+                    self.status_line.hide();
+                }
+                term.process(&data);
+                // the screen stays paused while scrolled back, and is
+                // redrawn when we go back to the live stream
+                if term.screen().scrollback() > 0 {
+                    return Ok(());
+                }
+                write(&self.render.output(&data, term.screen()))?;
+                let save = keyframe_saved.elapsed()
+                    >= crate::keyframe_cache::KEYFRAME_INTERVAL;
+                self.draw_status_line()?;
+                self.draw_overlays()?;
+                if save {
+                    self.save_keyframe();
+                }
             }
             crate::protocol::Message::Disconnected => {
                 self.reconnect(false)?;
             }
             crate::protocol::Message::Error { msg } => {
+                // if we were reconnecting (or switching to a bookmark), the
+                // stream may have ended while we were gone. any other error
+                // (like no longer being allowed to watch it) still needs to
+                // be reported.
+                let ended = Error::InvalidWatchId { id: id.clone() };
+                if *stale && msg == ended.to_string() {
+                    self.bookmarks.retain(|bookmark| bookmark.id != *id);
+                    return self.reconnect(false);
+                }
                 return Err(Error::Server { message: msg });
             }
-            crate::protocol::Message::Resize { .. } => {
-                // do nothing
+            crate::protocol::Message::Resize { size } => {
+                term.set_size(size.rows, size.cols);
+                if let Some((session, _)) = &mut self.watched_session {
+                    session.size = size;
+                }
+                self.draw_overlays()?;
+            }
+            crate::protocol::Message::Chat { from, text } => {
+                // don't cover up the message being composed
+                if self.chat_input.is_none() {
+                    // messages from the server itself have no sender
+                    if from.is_empty() {
+                        self.status_line.flash(&text);
+                    } else {
+                        self.status_line
+                            .flash(&format!("{}: {}", from, text));
+                    }
+                    self.draw_status_line()?;
+                }
+                self.draw_overlays()?;
+            }
+            crate::protocol::Message::Notice { text } => {
+                // don't cover up the message being composed
+                if self.chat_input.is_none() {
+                    self.status_line.flash(&crate::ui::notice(&text));
+                    self.draw_status_line()?;
+                }
+                self.draw_overlays()?;
+            }
+            crate::protocol::Message::GrantControl { .. } => {
+                self.in_control = true;
+                self.chat_input = None;
+                self.escape.reset();
+                let action = if self.interactive {
+                    "stop"
+                } else {
+                    "give it back"
+                };
+                self.status_line.flash(&format!(
+                    "you have control (press {} to {})",
+                    self.escape_name, action
+                ));
+                self.draw_status_line()?;
+                self.draw_overlays()?;
+            }
+            crate::protocol::Message::RevokeControl { .. } => {
+                if self.detaching {
+                    return self.reconnect(false);
+                }
+                if self.in_control {
+                    self.in_control = false;
+                    self.status_line.flash("you no longer have control");
+                } else {
+                    self.status_line.flash("control request was denied");
+                }
+                self.draw_status_line()?;
+                self.draw_overlays()?;
+            }
+            crate::protocol::Message::SessionChanged {
+                title,
+                tags,
+                paused,
+            } => {
+                if let Some((session, _)) = &mut self.watched_session {
+                    if let Some(title) = &title {
+                        session.title = title.clone();
+                    }
+                    if let Some(tags) = &tags {
+                        session.tags = tags.clone();
+                    }
+                    if let Some(paused) = paused {
+                        session.paused = paused;
+                    }
+                }
+                // don't cover up the message being composed
+                if self.chat_input.is_none() {
+                    let text = crate::ui::session_changed(
+                        title.as_ref().map(std::string::String::as_str),
+                        tags.as_ref().map(std::vec::Vec::as_slice),
+                        paused,
+                    );
+                    // the screen stays frozen for as long as the stream is
+                    // paused, so keep saying why
+                    if paused == Some(true) {
+                        self.status_line.set(&text);
+                    } else {
+                        self.status_line.flash(&text);
+                    }
+                    self.draw_status_line()?;
+                }
+                self.draw_overlays()?;
             }
             msg => {
                 return Err(crate::error::Error::UnexpectedMessage {
@@ -381,199 +988,894 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         &mut self,
         e: &crossterm::input::InputEvent,
     ) -> Result<bool> {
+        if self.in_control {
+            return self.control_keypress(e);
+        }
+        if self.chat_input.is_some() {
+            return self.chat_keypress(e);
+        }
+        if self.show_help {
+            self.show_help = false;
+            self.redraw_watched_screen()?;
+            return Ok(false);
+        }
+
+        if let Some(data) = crate::key_reader::key_bytes(e) {
+            match self.detach.push(&data) {
+                // might be the start of the detach keys
+                Some(data) if data.is_empty() => return Ok(false),
+                Some(_) => {}
+                None => {
+                    self.reconnect(false)?;
+                    return Ok(false);
+                }
+            }
+        }
+
         match e {
             crossterm::input::InputEvent::Keyboard(
-                crossterm::input::KeyEvent::Char('q'),
+                crossterm::input::KeyEvent::Char('?'),
             ) => {
-                self.reconnect(false)?;
+                self.show_help = true;
+                // get up to date watcher counts and such for the overlay,
+                // without leaving the stream
+                self.background_refresh = true;
+                self.list_client
+                    .send_message(crate::protocol::Message::list_sessions());
+                self.draw_overlays()?;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('c'),
+            ) => {
+                if self.server_supports("chat")? {
+                    self.chat_input = Some(String::new());
+                    self.draw_chat_input()?;
+                }
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('r'),
+            ) => {
+                if self.server_supports("control")? {
+                    self.request_control()?;
+                }
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('t'),
+            ) => {
+                self.show_timeline = !self.show_timeline;
+                if self.show_timeline {
+                    self.draw_overlays()?;
+                } else {
+                    self.redraw_watched_screen()?;
+                }
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('['),
+            ) => {
+                if self.server_supports("rewind")? {
+                    let (id, lag) = self.watch_position();
+                    self.watch(&id, lag + REWIND_STEP)?;
+                }
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char(']'),
+            ) => {
+                if self.scrolled_back() > 0 {
+                    self.scroll_back(None)?;
+                    return Ok(false);
+                }
+                let (id, lag) = self.watch_position();
+                if lag > 0 {
+                    self.watch(&id, 0)?;
+                }
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::PageUp,
+            ) => {
+                self.scroll_back(Some(true))?;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::PageDown,
+            ) => {
+                self.scroll_back(Some(false))?;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('b'),
+            ) => {
+                self.toggle_bookmark()?;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('n'),
+            ) => {
+                self.switch_bookmark(true)?;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('p'),
+            ) => {
+                self.switch_bookmark(false)?;
             }
             _ => {}
         }
         Ok(false)
     }
 
-    fn resize(&mut self, size: crate::term::Size) -> Result<()> {
-        if let State::Choosing { sessions, .. } = &mut self.state {
-            sessions.resize(size);
-            self.needs_redraw = true;
+    fn toggle_bookmark(&mut self) -> Result<()> {
+        let (id, _) = self.watch_position();
+        if let Some(idx) =
+            self.bookmarks.iter().position(|bookmark| bookmark.id == id)
+        {
+            self.bookmarks.remove(idx);
+            self.status_line.flash("removed bookmark");
+        } else {
+            self.bookmarks.push(Bookmark { id, keyframe: None });
+            self.status_line.flash(&format!(
+                "bookmarked ({} total, press n or p to switch between them)",
+                self.bookmarks.len()
+            ));
         }
-        Ok(())
+        self.draw_status_line()
     }
 
-    fn redraw(&self) -> Result<()> {
-        match &self.state {
-            State::Temporary => unreachable!(),
-            State::LoggingIn { .. } => {
-                self.display_loading_screen()?;
-            }
-            State::Choosing { .. } => {
-                self.display_choosing_screen()?;
-            }
-            State::Watching { .. } => {}
+    // cycles through the bookmarked sessions, still with only one watch
+    // connection at a time. the screen we are leaving is kept so that
+    // switching back to it is instant.
+    fn switch_bookmark(&mut self, forward: bool) -> Result<()> {
+        let (id, _) = self.watch_position();
+        let len = self.bookmarks.len();
+        let current =
+            self.bookmarks.iter().position(|bookmark| bookmark.id == id);
+        let next = match (current, forward) {
+            _ if len == 0 => None,
+            (Some(idx), true) => Some((idx + 1) % len),
+            (Some(idx), false) => Some((idx + len - 1) % len),
+            (None, true) => Some(0),
+            (None, false) => Some(len - 1),
+        };
+        let next = if let Some(next) = next.filter(|&n| Some(n) != current) {
+            next
+        } else {
+            self.status_line
+                .flash("no other bookmarks (press b to bookmark a stream)");
+            return self.draw_status_line();
+        };
+
+        if let Some(idx) = current {
+            self.bookmarks[idx].keyframe = self.current_keyframe();
+        }
+        self.save_keyframe();
+        let next_id = self.bookmarks[next].id.clone();
+        self.watch(&next_id, 0)?;
+        // the session may have ended since we last saw it
+        if let State::Watching { stale, .. } = &mut self.state {
+            *stale = true;
         }
         Ok(())
     }
 
-    fn display_loading_screen(&self) -> Result<()> {
-        clear()?;
-
-        print!("loading...\r\n");
-        if let Some(err) = self.list_client.last_error() {
-            print!("error: {}\r\n", err);
+    // lets the user know if the server is too old to support the feature
+    // they asked for
+    fn server_supports(&mut self, capability: &str) -> Result<bool> {
+        let supported = if let State::Watching { client, .. } = &self.state {
+            client.has_capability(capability)
+        } else {
+            false
+        };
+        if !supported {
+            self.status_line
+                .flash(&format!("the server doesn't support {}", capability));
+            self.draw_status_line()?;
         }
-        print!("q: quit --> ");
-
-        std::io::stdout()
-            .flush()
-            .context(crate::error::FlushTerminal)?;
+        Ok(supported)
+    }
 
-        Ok(())
+    fn request_control(&mut self) -> Result<()> {
+        if let State::Watching { client, .. } = &mut self.state {
+            client
+                .send_message(crate::protocol::Message::request_control(""));
+        }
+        self.status_line.flash("requested control");
+        self.draw_status_line()
     }
 
-    fn display_choosing_screen(&self) -> Result<()> {
-        let sessions = if let State::Choosing { sessions, .. } = &self.state {
-            sessions
+    // while in control, all keys are sent to the streamer's terminal except
+    // for the escape keys, which give control back
+    fn control_keypress(
+        &mut self,
+        e: &crossterm::input::InputEvent,
+    ) -> Result<bool> {
+        let client = if let State::Watching { client, .. } = &mut self.state {
+            client
         } else {
             unreachable!()
         };
 
-        let char_width = 2;
-
-        let max_name_width = (sessions.size().cols / 3) as usize;
-        let name_width = sessions
-            .visible_sessions()
-            .iter()
-            .map(|s| s.username.len())
-            .max()
-            .unwrap_or(4);
-        // XXX unstable
-        // let name_width = name_width.clamp(4, max_name_width);
-        let name_width = if name_width < 4 {
-            4
-        } else if name_width > max_name_width {
-            max_name_width
-        } else {
-            name_width
+        let data = if let Some(data) = crate::key_reader::key_bytes(e) {
+            data
+        } else {
+            return Ok(false);
         };
+        if let Some(data) = self.escape.push(&data) {
+            if !data.is_empty() {
+                client.send_message(
+                    crate::protocol::Message::terminal_input(&data),
+                );
+            }
+        } else {
+            // we'll get a RevokeControl message back from the server, which
+            // will update the status line
+            client.send_message(crate::protocol::Message::revoke_control(""));
+            self.detaching = self.interactive;
+        }
+        Ok(false)
+    }
 
-        let size_width = 7;
-
-        let max_idle_time = sessions
-            .visible_sessions()
-            .iter()
-            .map(|s| s.idle_time)
-            .max()
-            .unwrap_or(4);
-        let idle_width = format_time(max_idle_time).len();
-        let idle_width = if idle_width < 4 { 4 } else { idle_width };
-
-        let watch_width = 5;
-
-        let max_title_width = (sessions.size().cols as usize)
-            - char_width
-            - 3
-            - name_width
-            - 3
-            - size_width
-            - 3
-            - idle_width
-            - 3
-            - watch_width
-            - 3;
-
-        clear()?;
-        print!("welcome to teleterm\r\n");
-        print!("available sessions:\r\n");
-        print!("\r\n");
-        print!(
-            "{:5$} | {:6$} | {:7$} | {:8$} | {:9$} | title\r\n",
-            "",
-            "name",
-            "size",
-            "idle",
-            "watch",
-            char_width,
-            name_width,
-            size_width,
-            idle_width,
-            watch_width,
-        );
-        print!(
-            "{}+{}+{}+{}+{}+{}\r\n",
-            "-".repeat(char_width + 1),
-            "-".repeat(name_width + 2),
-            "-".repeat(size_width + 2),
-            "-".repeat(idle_width + 2),
-            "-".repeat(watch_width + 2),
-            "-".repeat(max_title_width + 1)
-        );
-
-        let mut prev_name: Option<&str> = None;
-        for (c, session) in sessions.visible_sessions_with_chars() {
-            let first = if let Some(name) = prev_name {
-                name != session.username
-            } else {
-                true
-            };
-
-            let display_char = format!("{})", c);
-            let display_name = if first {
-                truncate(&session.username, max_name_width)
-            } else {
-                "".to_string()
-            };
-            let display_size_plain = format!("{}", &session.size);
-            let display_size_full = if session.size == sessions.size() {
-                // XXX i should be able to use crossterm::style here, but
-                // it has bugs
-                format!("\x1b[32m{}\x1b[m", display_size_plain)
-            } else if session.size.fits_in(sessions.size()) {
-                display_size_plain.clone()
-            } else {
-                // XXX i should be able to use crossterm::style here, but
-                // it has bugs
-                format!("\x1b[31m{}\x1b[m", display_size_plain)
-            };
-            let display_idle = format_time(session.idle_time);
-            let display_title = truncate(&session.title, max_title_width);
-            let display_watch = session.watchers;
-
-            print!(
-                "{:6$} | {:7$} | {:8$} | {:9$} | {:10$} | {}\r\n",
-                display_char,
-                display_name,
-                display_size_full,
-                display_idle,
-                display_watch,
-                display_title,
-                char_width,
-                name_width,
-                size_width
-                    + (display_size_full.len() - display_size_plain.len()),
-                idle_width,
-                watch_width,
+    // pasted text only goes to the streamer's terminal or to the chat
+    // input, and is never treated as commands
+    fn paste(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(text) = &mut self.chat_input {
+            text.extend(
+                String::from_utf8_lossy(data)
+                    .chars()
+                    .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
+                    .filter(|c| !c.is_control()),
             );
-
-            prev_name = Some(&session.username);
+            return self.draw_chat_input();
+        }
+        if !self.in_control {
+            return Ok(());
         }
-        print!(
-            "({}/{}) space: refresh, q: quit, <: prev page, >: next page --> ",
-            sessions.current_page(),
-            sessions.total_pages(),
-        );
-        std::io::stdout()
-            .flush()
-            .context(crate::error::FlushTerminal)?;
 
+        if let State::Watching { client, term, .. } = &mut self.state {
+            let mut input = self.escape.take();
+            // only let the streamer's program know that this was pasted if
+            // it asked to be told
+            if term.screen().bracketed_paste() {
+                input.extend_from_slice(b"\x1b[200~");
+                input.extend_from_slice(data);
+                input.extend_from_slice(b"\x1b[201~");
+            } else {
+                input.extend_from_slice(data);
+            }
+            client.send_message(crate::protocol::Message::terminal_input(
+                &input,
+            ));
+        }
         Ok(())
     }
-}
 
-impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
-    WatchSession<S>
-{
-    const POLL_FNS:
-        &'static [&'static dyn for<'a> Fn(
+    fn chat_keypress(
+        &mut self,
+        e: &crossterm::input::InputEvent,
+    ) -> Result<bool> {
+        match e {
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Esc,
+            ) => {
+                self.chat_input = None;
+                self.status_line.hide();
+                self.redraw_watched_screen()?;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char(c),
+            ) => {
+                self.chat_input.as_mut().unwrap().push(*c);
+                self.draw_chat_input()?;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Backspace,
+            ) => {
+                self.chat_input.as_mut().unwrap().pop();
+                self.draw_chat_input()?;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Enter,
+            ) => {
+                let text = self.chat_input.take().unwrap();
+                if !text.is_empty() {
+                    if let State::Watching { client, .. } = &mut self.state {
+                        client.send_message(crate::protocol::Message::chat(
+                            "", &text,
+                        ));
+                    }
+                }
+                self.status_line.hide();
+                self.redraw_watched_screen()?;
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    fn watch(&mut self, id: &str, offset: u32) -> Result<()> {
+        // rewinding or skipping ahead in the same session keeps its history
+        let same_session =
+            if let State::Watching { id: watching, .. } = &self.state {
+                watching == id
+            } else {
+                false
+            };
+        if !same_session {
+            self.watched_session =
+                if let State::Choosing { sessions, .. } = &self.state {
+                    sessions.session(id).map(|session| {
+                        (session.clone(), std::time::Instant::now())
+                    })
+                } else {
+                    None
+                };
+            self.timeline = crate::timeline::Timeline::new();
+            self.timeline.record("started watching");
+        } else if offset > 0 {
+            self.timeline.record(&format!(
+                "rewound {}",
+                crate::ui::format_time(offset)
+            ));
+        } else {
+            self.timeline.record("skipped to live");
+        }
+
+        let mut client = crate::client::Client::watch(
+            &self.term_type,
+            (self.make_connector)(),
+            &self.auth,
+            crate::protocol::AuthClient::Cli,
+            id,
+            offset,
+        );
+        client.set_operation_timeout(self.timeout);
+        self.state.watching(
+            client,
+            id,
+            offset,
+            crate::term::Size::get()?,
+            self.jitter_buffer,
+        );
+        self.status_line.hide();
+        self.chat_input = None;
+        self.in_control = false;
+        self.detaching = false;
+        clear()?;
+        self.render.reset();
+        self.render.resize(crate::term::Size::get()?);
+        self.draw_overlays()?;
+        if offset == 0 {
+            let keyframe = self
+                .bookmarks
+                .iter_mut()
+                .find(|bookmark| bookmark.id == id)
+                .and_then(|bookmark| bookmark.keyframe.take())
+                .or_else(|| crate::keyframe_cache::Keyframe::load(id));
+            if let Some(keyframe) = keyframe {
+                if let State::Watching { term, stale, .. } = &mut self.state {
+                    term.set_size(keyframe.size.rows, keyframe.size.cols);
+                    term.process(&keyframe.data);
+                    *stale = true;
+                    write(
+                        &self.render.output(&keyframe.data, term.screen()),
+                    )?;
+                }
+                self.status_line
+                    .set("showing cached screen, waiting for server...");
+                self.draw_status_line()?;
+            }
+        }
+        if offset > 0 {
+            self.status_line.flash(&format!(
+                "rewound {} (press ] to skip to live)",
+                crate::ui::format_time(offset)
+            ));
+            self.draw_status_line()?;
+        }
+        Ok(())
+    }
+
+    // watches each of the given sessions side by side
+    fn tile(&mut self, sessions: &[crate::protocol::Session]) -> Result<()> {
+        let mut panes = vec![];
+        for session in sessions {
+            let mut client = crate::client::Client::watch(
+                &self.term_type,
+                (self.make_connector)(),
+                &self.auth,
+                crate::protocol::AuthClient::Cli,
+                &session.id,
+                0,
+            );
+            client.set_operation_timeout(self.timeout);
+            panes.push(Pane {
+                client,
+                term: vt100::Parser::new(
+                    session.size.rows,
+                    session.size.cols,
+                    0,
+                ),
+                username: session.username.clone(),
+                title: session.title.clone(),
+                reconnecting: false,
+                ended: false,
+            });
+        }
+        self.state.tiling(panes);
+        self.watched_session = None;
+        self.status_line.hide();
+        self.chat_input = None;
+        self.in_control = false;
+        self.detaching = false;
+        self.detach.reset();
+        self.render.reset();
+        self.render.resize(crate::term::Size::get()?);
+        self.status_line.flash(&format!(
+            "tab: next stream, z: zoom, {}: back to the list",
+            self.detach_name
+        ));
+        self.draw_tiles()
+    }
+
+    fn tiling_keypress(
+        &mut self,
+        e: &crossterm::input::InputEvent,
+    ) -> Result<bool> {
+        if let Some(data) = crate::key_reader::key_bytes(e) {
+            match self.detach.push(&data) {
+                // might be the start of the detach keys
+                Some(data) if data.is_empty() => return Ok(false),
+                Some(_) => {}
+                None => {
+                    self.reconnect(false)?;
+                    return Ok(false);
+                }
+            }
+        }
+
+        let (count, focused, zoomed) = if let State::Tiling {
+            panes,
+            focused,
+            zoomed,
+        } = &mut self.state
+        {
+            (panes.len(), focused, zoomed)
+        } else {
+            unreachable!()
+        };
+        match e {
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Tab,
+            ) => {
+                *focused = (*focused + 1) % count;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::BackTab,
+            ) => {
+                *focused = (*focused + count - 1) % count;
+            }
+            crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Char('z'),
+            ) => {
+                *zoomed = !*zoomed;
+            }
+            _ => return Ok(false),
+        }
+        self.render.reset();
+        self.draw_tiles()?;
+        Ok(false)
+    }
+
+    fn tile_server_message(
+        &mut self,
+        i: usize,
+        msg: crate::protocol::Message,
+    ) -> Result<()> {
+        let pane = if let State::Tiling { panes, .. } = &mut self.state {
+            &mut panes[i]
+        } else {
+            unreachable!()
+        };
+
+        match msg {
+            crate::protocol::Message::TerminalOutput { data } => {
+                pane.term.process(&data);
+                if self.showing_tile(i) {
+                    return self.draw_tile(i, &data);
+                }
+            }
+            crate::protocol::Message::Resize { size } => {
+                pane.term.set_size(size.rows, size.cols);
+                if self.showing_tile(i) {
+                    return self.draw_tiles();
+                }
+            }
+            crate::protocol::Message::SessionChanged { title, .. } => {
+                if let Some(title) = title {
+                    pane.title = title;
+                    if self.showing_tile(i) {
+                        return self.draw_tile(i, b"");
+                    }
+                }
+            }
+            // the stream ended, so keep its last screen up
+            crate::protocol::Message::Disconnected
+            | crate::protocol::Message::Error { .. } => {
+                pane.ended = true;
+                if self.showing_tile(i) {
+                    return self.draw_tile(i, b"");
+                }
+            }
+            crate::protocol::Message::Chat { from, text } => {
+                // messages from the server itself have no sender
+                if from.is_empty() {
+                    self.status_line.flash(&text);
+                } else {
+                    self.status_line.flash(&format!("{}: {}", from, text));
+                }
+                self.draw_status_line()?;
+            }
+            crate::protocol::Message::Notice { text } => {
+                self.status_line.flash(&crate::ui::notice(&text));
+                self.draw_status_line()?;
+            }
+            msg => {
+                return Err(crate::error::Error::UnexpectedMessage {
+                    message: msg,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // whether the given pane is currently on the screen at all
+    fn showing_tile(&self, i: usize) -> bool {
+        if let State::Tiling {
+            focused, zoomed, ..
+        } = &self.state
+        {
+            i == *focused || !self.zoomed_tiles(*zoomed)
+        } else {
+            false
+        }
+    }
+
+    // renderers which don't draw to a fixed place on the screen can only
+    // show one stream at a time
+    fn zoomed_tiles(&self, zoomed: bool) -> bool {
+        zoomed || !self.render.overlays()
+    }
+
+    // draws the given pane after data was written to it, either in its tile
+    // or as the whole screen
+    fn draw_tile(&mut self, i: usize, data: &[u8]) -> Result<()> {
+        let (panes, focused, zoomed) = if let State::Tiling {
+            panes,
+            focused,
+            zoomed,
+        } = &self.state
+        {
+            (panes, *focused, *zoomed)
+        } else {
+            unreachable!()
+        };
+
+        if self.zoomed_tiles(zoomed) {
+            write(&self.render.output(data, panes[i].term.screen()))?;
+        } else {
+            let layout = crate::ui::tile_layout(
+                panes.len(),
+                crate::term::Size::get()?,
+            );
+            write(&crate::ui::tile(
+                &panes[i].label(),
+                panes[i].term.screen(),
+                layout[i],
+                i == focused,
+            ))?;
+        }
+        self.draw_status_line()
+    }
+
+    // draws every pane, or only the focused one when zoomed in
+    fn draw_tiles(&mut self) -> Result<()> {
+        let (panes, focused, zoomed) = if let State::Tiling {
+            panes,
+            focused,
+            zoomed,
+        } = &self.state
+        {
+            (panes, *focused, *zoomed)
+        } else {
+            unreachable!()
+        };
+
+        if self.zoomed_tiles(zoomed) {
+            if self.render.overlays() {
+                clear()?;
+            }
+            write(&self.render.redraw(panes[focused].term.screen()))?;
+        } else {
+            clear()?;
+            let layout = crate::ui::tile_layout(
+                panes.len(),
+                crate::term::Size::get()?,
+            );
+            for (i, (pane, place)) in panes.iter().zip(layout).enumerate() {
+                write(&crate::ui::tile(
+                    &pane.label(),
+                    pane.term.screen(),
+                    place,
+                    i == focused,
+                ))?;
+            }
+        }
+        self.draw_status_line()
+    }
+
+    // how many lines back from the live screen we are showing
+    fn scrolled_back(&self) -> usize {
+        if let State::Watching { term, .. } = &self.state {
+            term.screen().scrollback()
+        } else {
+            0
+        }
+    }
+
+    // moves a page up or down through the lines which have scrolled off the
+    // top of the watched terminal, or straight back to the live screen when
+    // up is None
+    fn scroll_back(&mut self, up: Option<bool>) -> Result<()> {
+        let term = if let State::Watching { term, .. } = &mut self.state {
+            term
+        } else {
+            unreachable!()
+        };
+        let (rows, _) = term.screen().size();
+        let page = usize::from(rows.saturating_sub(1)).max(1);
+        let current = term.screen().scrollback();
+        term.set_scrollback(match up {
+            Some(true) => current + page,
+            Some(false) => current.saturating_sub(page),
+            None => 0,
+        });
+        // this is limited to the lines which are actually there
+        let scrollback = term.screen().scrollback();
+        if scrollback == current {
+            return Ok(());
+        }
+
+        if scrollback == 0 {
+            self.status_line.hide();
+        } else {
+            self.status_line.set(&crate::ui::scrolled_back(scrollback));
+        }
+        self.redraw_watched_screen()
+    }
+
+    // returns the id of the session being watched, and an estimate of how
+    // many seconds behind the live stream we are
+    fn watch_position(&self) -> (String, u32) {
+        if let State::Watching {
+            id,
+            offset,
+            started,
+            ..
+        } = &self.state
+        {
+            // the server sends missed output at CATCHUP_SPEED, so we gain
+            // CATCHUP_SPEED - 1 seconds on the live stream every second
+            let caught_up = started.elapsed().as_secs()
+                * u64::from(crate::protocol::CATCHUP_SPEED - 1);
+            let lag = u64::from(*offset).saturating_sub(caught_up);
+            // lag is at most offset, which is a u32
+            #[allow(clippy::cast_possible_truncation)]
+            (id.clone(), lag as u32)
+        } else {
+            unreachable!()
+        }
+    }
+
+    // the keyframe cache is only an optimization, so failing to write it
+    // isn't fatal
+    fn current_keyframe(&self) -> Option<crate::keyframe_cache::Keyframe> {
+        if let State::Watching {
+            term, id, stale, ..
+        } = &self.state
+        {
+            // the keyframe should be the live screen, not the part of the
+            // scrollback we are looking at
+            if !*stale && term.screen().scrollback() == 0 {
+                return Some(crate::keyframe_cache::Keyframe::new(
+                    id,
+                    term.screen(),
+                ));
+            }
+        }
+        None
+    }
+
+    fn save_keyframe(&mut self) {
+        if let State::Watching {
+            term,
+            id,
+            keyframe_saved,
+            stale,
+            ..
+        } = &mut self.state
+        {
+            if *stale || term.screen().scrollback() > 0 {
+                return;
+            }
+            let keyframe =
+                crate::keyframe_cache::Keyframe::new(id, term.screen());
+            tokio::spawn(keyframe.save().map_err(|e| {
+                log::warn!("failed to save keyframe: {}", e);
+            }));
+            *keyframe_saved = std::time::Instant::now();
+        }
+    }
+
+    fn draw_chat_input(&mut self) -> Result<()> {
+        let text = self.chat_input.as_ref().unwrap();
+        self.status_line.set(&crate::ui::chat_input(text));
+        self.draw_status_line()
+    }
+
+    fn draw_status_line(&mut self) -> Result<()> {
+        if let Some(text) = self.status_line.text() {
+            if self.render.overlays() {
+                let size = crate::term::Size::get()?;
+                write(&self.status_line.draw(size.cols))?;
+            } else {
+                write(&self.render.status(text))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_overlays(&self) -> Result<()> {
+        if !self.render.overlays() {
+            return Ok(());
+        }
+        let size = crate::term::Size::get()?;
+        if self.show_timeline {
+            write(&self.timeline.draw(size))?;
+        }
+        if self.show_help {
+            write(&self.help_overlay(size))?;
+        }
+        Ok(())
+    }
+
+    fn help_overlay(&self, size: crate::term::Size) -> Vec<u8> {
+        let latency = if let State::Watching { client, .. } = &self.state {
+            client.latency()
+        } else {
+            None
+        };
+        let (session, uptime) =
+            if let Some((session, updated)) = &self.watched_session {
+                let elapsed = u32::try_from(updated.elapsed().as_secs())
+                    .unwrap_or(std::u32::MAX);
+                (Some(session), session.uptime.saturating_add(elapsed))
+            } else {
+                (None, 0)
+            };
+        crate::ui::help(
+            &crate::ui::session_info(session, uptime, latency),
+            &self.help_bindings(),
+            size,
+        )
+    }
+
+    fn help_bindings(&self) -> Vec<(String, &'static str)> {
+        let mut bindings = vec![
+            (self.detach_name.clone(), "go back to the list of streams"),
+            ("c".to_string(), "send a chat message"),
+            ("r".to_string(), "ask for control of the terminal"),
+            (self.escape_name.clone(), "give control back"),
+            ("t".to_string(), "show or hide the timeline"),
+            ("[".to_string(), "rewind by 30 seconds"),
+            ("]".to_string(), "skip to the live stream"),
+            ("pgup".to_string(), "scroll back through earlier output"),
+            ("pgdn".to_string(), "scroll forward again"),
+            ("b".to_string(), "bookmark this stream"),
+        ];
+        if !self.bookmarks.is_empty() {
+            bindings.push(("n".to_string(), "next bookmarked stream"));
+            bindings.push(("p".to_string(), "previous bookmarked stream"));
+        }
+        bindings.push(("?".to_string(), "show this help"));
+        bindings
+    }
+
+    fn redraw_watched_screen(&mut self) -> Result<()> {
+        if let State::Tiling { .. } = &self.state {
+            return self.draw_tiles();
+        }
+        // chat messages and such can cover up the indicator for a while
+        let scrollback = self.scrolled_back();
+        if scrollback > 0 && !self.status_line.is_visible() {
+            self.status_line.set(&crate::ui::scrolled_back(scrollback));
+        }
+        if let State::Watching { term, .. } = &self.state {
+            if !self.render.overlays() {
+                return write(&self.render.redraw(term.screen()));
+            }
+            let timeline = if self.show_timeline {
+                Some(&self.timeline)
+            } else {
+                None
+            };
+            let size = crate::term::Size::get()?;
+            let screen = self.render.redraw(term.screen());
+            write(&crate::ui::watching(
+                &screen,
+                &self.status_line,
+                timeline,
+                size,
+            ))?;
+            if self.show_help {
+                write(&self.help_overlay(size))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn resize(&mut self, size: crate::term::Size) -> Result<()> {
+        match &mut self.state {
+            State::Choosing { sessions, .. } => {
+                sessions.resize(size);
+                self.needs_redraw = true;
+            }
+            State::Watching { .. } => {
+                self.render.resize(size);
+                self.redraw_watched_screen()?;
+            }
+            State::Tiling { .. } => {
+                self.render.resize(size);
+                self.draw_tiles()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn redraw(&self) -> Result<()> {
+        match &self.state {
+            State::Temporary => unreachable!(),
+            State::LoggingIn { .. } => {
+                self.display_loading_screen()?;
+            }
+            State::Choosing { .. } => {
+                self.display_choosing_screen()?;
+            }
+            State::Watching { .. } | State::Tiling { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn display_loading_screen(&self) -> Result<()> {
+        clear()?;
+        write_lines(&crate::ui::loading(self.list_client.last_error()))
+    }
+
+    fn display_choosing_screen(&self) -> Result<()> {
+        let sessions = if let State::Choosing { sessions, .. } = &self.state {
+            sessions
+        } else {
+            unreachable!()
+        };
+
+        clear()?;
+        write_lines(&crate::ui::choosing(
+            sessions,
+            &self.columns,
+            self.caps.color,
+        ))
+    }
+}
+
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    WatchSession<S>
+{
+    const POLL_FNS:
+        &'static [&'static dyn for<'a> Fn(
             &'a mut Self,
         )
             -> component_future::Poll<
@@ -583,7 +1885,12 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         &Self::poll_resizer,
         &Self::poll_input,
         &Self::poll_list_client,
+        &Self::poll_refresh_timer,
+        &Self::poll_menu_clock,
         &Self::poll_watch_client,
+        &Self::poll_jitter_buffer,
+        &Self::poll_tile_clients,
+        &Self::poll_status_line,
     ];
 
     fn poll_resizer(&mut self) -> component_future::Poll<(), Error> {
@@ -603,12 +1910,21 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             }
         }
 
-        let e = component_future::try_ready!(self.key_reader.poll()).unwrap();
+        let e = match component_future::try_ready!(self.key_reader.poll())
+            .unwrap()
+        {
+            crate::key_reader::Event::Input(e) => e,
+            crate::key_reader::Event::Paste(data) => {
+                self.paste(&data)?;
+                return Ok(component_future::Async::DidWork);
+            }
+        };
         let quit = match &mut self.state {
             State::Temporary => unreachable!(),
             State::LoggingIn { .. } => self.loading_keypress(&e)?,
             State::Choosing { .. } => self.list_keypress(&e)?,
             State::Watching { .. } => self.watch_keypress(&e)?,
+            State::Tiling { .. } => self.tiling_keypress(&e)?,
         };
         if quit {
             Ok(component_future::Async::Ready(()))
@@ -618,14 +1934,34 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     }
 
     fn poll_list_client(&mut self) -> component_future::Poll<(), Error> {
+        let watching = match self.state {
+            State::Watching { .. } | State::Tiling { .. } => true,
+            _ => false,
+        };
         match component_future::try_ready!(self.list_client.poll()).unwrap() {
+            // the list client reconnects on its own, so don't interrupt the
+            // session being watched - we'll ask for a new list when we're
+            // done watching
             crate::client::Event::Disconnect => {
-                self.reconnect(true)?;
+                self.subscribed = false;
+                if !watching {
+                    self.reconnect(true)?;
+                }
             }
             crate::client::Event::Connect => {
-                self.list_client
-                    .send_message(crate::protocol::Message::list_sessions());
+                if !watching {
+                    // servers which push changes to the list will also
+                    // send the initial list when we subscribe
+                    self.subscribed =
+                        self.list_client.has_capability("session-updates");
+                    self.list_client.send_message(if self.subscribed {
+                        crate::protocol::Message::subscribe_session_list()
+                    } else {
+                        crate::protocol::Message::list_sessions()
+                    });
+                }
             }
+            crate::client::Event::SendQueueFull { .. } => {}
             crate::client::Event::ServerMessage(msg) => {
                 self.list_server_message(msg)?;
             }
@@ -633,24 +1969,209 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         Ok(component_future::Async::DidWork)
     }
 
+    fn poll_refresh_timer(&mut self) -> component_future::Poll<(), Error> {
+        let choosing = if let State::Choosing { .. } = self.state {
+            true
+        } else {
+            false
+        };
+        // a new list is requested whenever we go back to the menu, which
+        // will restart the timer
+        if !choosing {
+            self.refresh_timer = None;
+        }
+        let timer = if let Some(timer) = &mut self.refresh_timer {
+            timer
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        component_future::try_ready!(timer
+            .poll()
+            .context(crate::error::TimerRefresh));
+        self.refresh_timer = None;
+        self.background_refresh = true;
+        self.list_client
+            .send_message(crate::protocol::Message::list_sessions());
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_menu_clock(&mut self) -> component_future::Poll<(), Error> {
+        let sessions =
+            if let State::Choosing { sessions, .. } = &mut self.state {
+                sessions
+            } else {
+                self.menu_clock = None;
+                return Ok(component_future::Async::NothingToDo);
+            };
+
+        let (timer, last_tick) = self.menu_clock.get_or_insert_with(|| {
+            let now = std::time::Instant::now();
+            (tokio::timer::Delay::new(now + MENU_CLOCK_INTERVAL), now)
+        });
+        component_future::try_ready!(timer
+            .poll()
+            .context(crate::error::TimerRefresh));
+
+        // only whole seconds are counted, so that the remainder isn't lost
+        let secs = last_tick.elapsed().as_secs();
+        *last_tick += std::time::Duration::from_secs(secs);
+        timer.reset(*last_tick + MENU_CLOCK_INTERVAL);
+        sessions.tick(u32::try_from(secs).unwrap_or(std::u32::MAX));
+        self.needs_redraw = true;
+        Ok(component_future::Async::DidWork)
+    }
+
     fn poll_watch_client(&mut self) -> component_future::Poll<(), Error> {
-        let client = if let State::Watching { client } = &mut self.state {
+        let client = if let State::Watching { client, .. } = &mut self.state {
             client
         } else {
             return Ok(component_future::Async::NothingToDo);
         };
 
         match component_future::try_ready!(client.poll()).unwrap() {
+            // keep showing the last known screen while the client
+            // reconnects, since it will start watching the same session
+            // again once it does
             crate::client::Event::Disconnect => {
-                self.reconnect(true)?;
+                // the server won't be confirming that we gave control back
+                if self.detaching {
+                    self.reconnect(false)?;
+                    return Ok(component_future::Async::DidWork);
+                }
+                self.save_keyframe();
+                if let State::Watching { stale, .. } = &mut self.state {
+                    *stale = true;
+                }
+                self.in_control = false;
+                self.chat_input = None;
+                self.timeline.record("connection lost");
+                self.status_line
+                    .set("connection lost, reconnecting (screen is stale)");
+                self.draw_status_line()?;
+                self.draw_overlays()?;
+            }
+            crate::client::Event::Connect => {
+                self.timeline.record("connected to server");
+                self.draw_overlays()?;
+                if self.interactive && self.server_supports("control")? {
+                    self.request_control()?;
+                }
+            }
+            // only terminal output is ever dropped, and watchers don't send
+            // any
+            crate::client::Event::SendQueueFull { .. } => {}
+            crate::client::Event::ServerMessage(msg) => {
+                if let State::Watching {
+                    jitter_buffer: Some(jitter_buffer),
+                    ..
+                } = &mut self.state
+                {
+                    jitter_buffer.push(msg);
+                } else {
+                    self.watch_server_message(msg)?;
+                }
+            }
+        }
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_jitter_buffer(&mut self) -> component_future::Poll<(), Error> {
+        let jitter_buffer = if let State::Watching {
+            jitter_buffer: Some(jitter_buffer),
+            ..
+        } = &mut self.state
+        {
+            jitter_buffer
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+        if jitter_buffer.is_empty() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        let msg = component_future::try_ready!(jitter_buffer.poll()).unwrap();
+        self.watch_server_message(msg)?;
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_tile_clients(&mut self) -> component_future::Poll<(), Error> {
+        let count = if let State::Tiling { panes, .. } = &self.state {
+            panes.len()
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        let mut did_work = false;
+        let mut not_ready = false;
+        for i in 0..count {
+            match self.poll_tile_client(i)? {
+                component_future::Async::DidWork => {
+                    did_work = true;
+                }
+                component_future::Async::NotReady => {
+                    not_ready = true;
+                }
+                _ => {}
+            }
+        }
+
+        if did_work {
+            Ok(component_future::Async::DidWork)
+        } else if not_ready {
+            Ok(component_future::Async::NotReady)
+        } else {
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
+
+    fn poll_tile_client(
+        &mut self,
+        i: usize,
+    ) -> component_future::Poll<(), Error> {
+        let pane = if let State::Tiling { panes, .. } = &mut self.state {
+            &mut panes[i]
+        } else {
+            unreachable!()
+        };
+        // nothing more is coming from streams which have ended
+        if pane.ended {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        match component_future::try_ready!(pane.client.poll()).unwrap() {
+            // like when watching a single stream, the client reconnects on
+            // its own and starts watching the same session again
+            crate::client::Event::Disconnect => {
+                pane.reconnecting = true;
+                if self.showing_tile(i) {
+                    self.draw_tile(i, b"")?;
+                }
+            }
+            crate::client::Event::Connect => {
+                pane.reconnecting = false;
+                if self.showing_tile(i) {
+                    self.draw_tile(i, b"")?;
+                }
             }
-            crate::client::Event::Connect => {}
+            crate::client::Event::SendQueueFull { .. } => {}
             crate::client::Event::ServerMessage(msg) => {
-                self.watch_server_message(msg)?;
+                self.tile_server_message(i, msg)?;
             }
         }
         Ok(component_future::Async::DidWork)
     }
+
+    fn poll_status_line(&mut self) -> component_future::Poll<(), Error> {
+        if !self.status_line.expires() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        component_future::try_ready!(self.status_line.poll_expire());
+        // redraw the screen to get rid of the status line
+        self.redraw_watched_screen()?;
+        Ok(component_future::Async::DidWork)
+    }
 }
 
 #[must_use = "futures do nothing unless polled"]
@@ -673,6 +2194,86 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     }
 }
 
+// prints the streams which have recently ended, and then exits
+struct HistoryList<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+> {
+    client: crate::client::Client<S>,
+}
+
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    HistoryList<S>
+{
+    fn new(
+        connector: crate::client::Connector<S>,
+        auth: &crate::protocol::Auth,
+        timeout: Option<std::time::Duration>,
+    ) -> Self {
+        let term_type =
+            std::env::var("TERM").unwrap_or_else(|_| "".to_string());
+        let mut client = crate::client::Client::list(
+            &term_type,
+            connector,
+            auth,
+            crate::protocol::AuthClient::Cli,
+        );
+        client.set_operation_timeout(timeout);
+        Self { client }
+    }
+}
+
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    HistoryList<S>
+{
+    const POLL_FNS:
+        &'static [&'static dyn for<'a> Fn(
+            &'a mut Self,
+        )
+            -> component_future::Poll<
+            (),
+            Error,
+        >] = &[&Self::poll_client];
+
+    fn poll_client(&mut self) -> component_future::Poll<(), Error> {
+        match component_future::try_ready!(self.client.poll()).unwrap() {
+            crate::client::Event::Disconnect => {}
+            crate::client::Event::Connect => {
+                self.client
+                    .send_message(crate::protocol::Message::list_history());
+            }
+            crate::client::Event::SendQueueFull { .. } => {}
+            crate::client::Event::ServerMessage(msg) => match msg {
+                crate::protocol::Message::History { sessions } => {
+                    print_history(&sessions);
+                    return Ok(component_future::Async::Ready(()));
+                }
+                crate::protocol::Message::Error { msg } => {
+                    return Err(Error::Server { message: msg });
+                }
+                crate::protocol::Message::Notice { .. } => {}
+                msg => {
+                    return Err(crate::error::Error::UnexpectedMessage {
+                        message: msg,
+                    });
+                }
+            },
+        }
+        Ok(component_future::Async::DidWork)
+    }
+}
+
+#[must_use = "futures do nothing unless polled"]
+impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
+    futures::Future for HistoryList<S>
+{
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> futures::Poll<Self::Item, Self::Error> {
+        component_future::poll_future(self, Self::POLL_FNS)
+    }
+}
+
 fn new_raw_screen() -> Result<crossterm::screen::RawScreen> {
     crossterm::screen::RawScreen::into_raw_mode()
         .context(crate::error::ToRawMode)
@@ -683,35 +2284,26 @@ fn new_alternate_screen() -> Result<crossterm::screen::AlternateScreen> {
         .context(crate::error::ToAlternateScreen)
 }
 
-fn format_time(dur: u32) -> String {
-    let secs = dur % 60;
-    let dur = dur / 60;
-    if dur == 0 {
-        return format!("{}s", secs);
-    }
-
-    let mins = dur % 60;
-    let dur = dur / 60;
-    if dur == 0 {
-        return format!("{}m{:02}s", mins, secs);
-    }
-
-    let hours = dur % 24;
-    let dur = dur / 24;
-    if dur == 0 {
-        return format!("{}h{:02}m{:02}s", hours, mins, secs);
+fn print_history(sessions: &[crate::protocol::PastSession]) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    for line in crate::ui::history(sessions, now) {
+        println!("{}", line);
     }
+}
 
-    let days = dur;
-    format!("{}d{:02}h{:02}m{:02}s", days, hours, mins, secs)
+fn write_lines(lines: &[String]) -> Result<()> {
+    write(lines.join("\r\n").as_bytes())
 }
 
-fn truncate(s: &str, len: usize) -> String {
-    if s.len() <= len {
-        s.to_string()
-    } else {
-        format!("{}...", &s[..(len - 3)])
-    }
+fn write(data: &[u8]) -> Result<()> {
+    // TODO async
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    stdout.write(data).context(crate::error::WriteTerminal)?;
+    stdout.flush().context(crate::error::FlushTerminal)?;
+    Ok(())
 }
 
 fn clear() -> Result<()> {
@@ -722,58 +2314,3 @@ fn clear() -> Result<()> {
     )
     .context(crate::error::WriteTerminalCrossterm)
 }
-
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    #[test]
-    fn test_truncate() {
-        assert_eq!(truncate("abcdefghij", 12), "abcdefghij");
-        assert_eq!(truncate("abcdefghij", 11), "abcdefghij");
-        assert_eq!(truncate("abcdefghij", 10), "abcdefghij");
-        assert_eq!(truncate("abcdefghij", 9), "abcdef...");
-        assert_eq!(truncate("abcdefghij", 8), "abcde...");
-        assert_eq!(truncate("abcdefghij", 7), "abcd...");
-
-        assert_eq!(truncate("", 7), "");
-        assert_eq!(truncate("a", 7), "a");
-        assert_eq!(truncate("ab", 7), "ab");
-        assert_eq!(truncate("abc", 7), "abc");
-        assert_eq!(truncate("abcd", 7), "abcd");
-        assert_eq!(truncate("abcde", 7), "abcde");
-        assert_eq!(truncate("abcdef", 7), "abcdef");
-        assert_eq!(truncate("abcdefg", 7), "abcdefg");
-        assert_eq!(truncate("abcdefgh", 7), "abcd...");
-        assert_eq!(truncate("abcdefghi", 7), "abcd...");
-        assert_eq!(truncate("abcdefghij", 7), "abcd...");
-    }
-
-    #[test]
-    fn test_format_time() {
-        assert_eq!(format_time(0), "0s");
-        assert_eq!(format_time(5), "5s");
-        assert_eq!(format_time(10), "10s");
-        assert_eq!(format_time(60), "1m00s");
-        assert_eq!(format_time(61), "1m01s");
-        assert_eq!(format_time(601), "10m01s");
-        assert_eq!(format_time(610), "10m10s");
-        assert_eq!(format_time(3599), "59m59s");
-        assert_eq!(format_time(3600), "1h00m00s");
-        assert_eq!(format_time(3601), "1h00m01s");
-        assert_eq!(format_time(3610), "1h00m10s");
-        assert_eq!(format_time(3660), "1h01m00s");
-        assert_eq!(format_time(3661), "1h01m01s");
-        assert_eq!(format_time(3670), "1h01m10s");
-        assert_eq!(format_time(4200), "1h10m00s");
-        assert_eq!(format_time(4201), "1h10m01s");
-        assert_eq!(format_time(4210), "1h10m10s");
-        assert_eq!(format_time(36000), "10h00m00s");
-        assert_eq!(format_time(86399), "23h59m59s");
-        assert_eq!(format_time(86400), "1d00h00m00s");
-        assert_eq!(format_time(86401), "1d00h00m01s");
-        assert_eq!(format_time(864_000), "10d00h00m00s");
-        assert_eq!(format_time(8_640_000), "100d00h00m00s");
-        assert_eq!(format_time(86_400_000), "1000d00h00m00s");
-    }
-}
@@ -32,7 +32,6 @@ impl crate::config::Config for Config {
     ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
         Box::new(crate::web::Server::new(
             self.web.listen_address,
-            self.web.public_address.clone(),
             self.web.server_address.clone(),
             self.web.allowed_login_methods.clone(),
             self.oauth_configs
@@ -41,10 +40,9 @@ impl crate::config::Config for Config {
                     configs.get(&crate::protocol::AuthClient::Web).map(
                         |config| {
                             let mut config = config.clone();
-                            // TODO: tls
                             let url = url::Url::parse(&format!(
-                                "http://{}/oauth/{}",
-                                self.web.public_address,
+                                "{}/oauth/{}",
+                                self.web.public_url(),
                                 ty.name()
                             ))
                             .unwrap();
@@ -54,6 +52,12 @@ impl crate::config::Config for Config {
                     )
                 })
                 .collect(),
+            self.web.jitter_buffer,
+            self.web.web_root.as_ref().map(std::path::PathBuf::from),
+            self.web
+                .recordings_dir
+                .as_ref()
+                .map(std::path::PathBuf::from),
         ))
     }
 }
@@ -7,32 +7,146 @@ pub mod wizard;
 
 const CONFIG_FILENAME: &str = "config.toml";
 
+const ADMIN_OPTION: &str = "admin";
+const ALLOW_OPTION: &str = "allow";
+const ALLOW_CONTROL_OPTION: &str = "allow-control";
 const ALLOWED_LOGIN_METHODS_OPTION: &str = "allowed-login-methods";
 const ARGS_OPTION: &str = "args";
+const ASCII_OPTION: &str = "ascii";
+const BAN_FILE_OPTION: &str = "ban-file";
+const BAUD_OPTION: &str = "baud";
+const BIND_ADDRESS_OPTION: &str = "bind-address";
+const CLEAR_ENV_OPTION: &str = "clear-env";
+const COLUMNS_OPTION: &str = "columns";
 const COMMAND_OPTION: &str = "command";
 const CONNECT_ADDRESS_OPTION: &str = "connect-address";
+const CONNECT_TIMEOUT_OPTION: &str = "connect-timeout-secs";
+const CWD_OPTION: &str = "cwd";
+const DAEMONIZE_OPTION: &str = "daemonize";
+const DESCRIPTION_OPTION: &str = "description";
+const DETACH_KEY_OPTION: &str = "detach-key";
+const DISCONNECT_GRACE_OPTION: &str = "disconnect-grace-secs";
+const ENV_OPTION: &str = "env";
+const ESCAPE_OPTION: &str = "escape";
+const EXPORT_HTML_OPTION: &str = "export-html";
+const EXPORT_SCROLLBACK_OPTION: &str = "export-scrollback";
+const EXPORT_SIZE_OPTION: &str = "export-size";
+const EXPORT_TEXT_OPTION: &str = "export-text";
 const FILENAME_OPTION: &str = "filename";
+const FLOW_CONTROL_OPTION: &str = "flow-control";
+const FOLLOW_OPTION: &str = "follow";
+const FORMAT_OPTION: &str = "format";
+const FROM_FILE_OPTION: &str = "from-file";
+const GZIP_OPTION: &str = "gzip";
+const HISTORY_OPTION: &str = "history-secs";
+const HOST_KEY_FILE_OPTION: &str = "host-key-file";
+const INTERACTIVE_OPTION: &str = "interactive";
+const INTERFACE_OPTION: &str = "interface";
+const JITTER_BUFFER_OPTION: &str = "jitter-buffer-ms";
 const LISTEN_ADDRESS_OPTION: &str = "listen-address";
+const LOG_FILE_OPTION: &str = "log-file";
+const LOGIN_GITHUB_OPTION: &str = "login-github";
+const LOGIN_GOOGLE_OPTION: &str = "login-google";
 const LOGIN_PLAIN_OPTION: &str = "login-plain";
 const LOGIN_RECURSE_CENTER_OPTION: &str = "login-recurse-center";
+const MAX_CONNECTIONS_OPTION: &str = "max-connections";
+const MAX_FILE_SIZE_OPTION: &str = "max-file-size";
 const MAX_FRAME_LENGTH_OPTION: &str = "max-frame-length";
+const MAX_IDLE_OPTION: &str = "max-idle";
+const MAX_STREAM_OPTION: &str = "max-stream-secs";
+const MAX_WATCHERS_OPTION: &str = "max-watchers-per-session";
+const NAMING_COMMAND_OPTION: &str = "naming-command";
+const NEW_FILE_OPTION: &str = "new-file";
+const NO_COLOR_OPTION: &str = "no-color";
+const NO_REMOTE_OPTION: &str = "no-remote";
+const OLD_FILE_OPTION: &str = "old-file";
+const PARITY_OPTION: &str = "parity";
+const PID_FILE_OPTION: &str = "pid-file";
 const PLAY_AT_START_OPTION: &str = "play-at-start";
 const PLAYBACK_RATIO_OPTION: &str = "playback-ratio";
+const PREFIX_KEY_OPTION: &str = "prefix-key";
+const PROFILE_OPTION: &str = "profile";
 const PUBLIC_ADDRESS_OPTION: &str = "public-address";
+const QUARANTINE_OPTION: &str = "quarantine-secs";
 const READ_TIMEOUT_OPTION: &str = "read-timeout-secs";
+const RECORD_INPUT_OPTION: &str = "record-input";
+const RECORDINGS_DIR_OPTION: &str = "recordings-dir";
+const REFRESH_INTERVAL_OPTION: &str = "refresh-interval";
+const RECORD_OPTION: &str = "record";
+const RENDER_OPTION: &str = "render";
+const ROOM_OPTION: &str = "room";
+const ROTATE_OPTION: &str = "rotate";
+const SEND_QUEUE_POLICY_OPTION: &str = "send-queue-policy";
+const SEND_QUEUE_SIZE_OPTION: &str = "send-queue-size";
+const SERIAL_OPTION: &str = "serial";
 const SERVER_ADDRESS_OPTION: &str = "server-address";
+const SHOW_HISTORY_OPTION: &str = "history";
+const SIZE_OPTION: &str = "size";
+const SPEED_OPTION: &str = "speed";
+const STREAM_LISTEN_ADDRESS_OPTION: &str = "stream-listen-address";
+const STREAM_OPTION: &str = "stream";
+const STREAM_WARNING_OPTION: &str = "stream-warning-secs";
+const TAG_OPTION: &str = "tag";
+const TAGS_OPTION: &str = "tags";
+const TAIL_COLOR_OPTION: &str = "tail-color";
+const TAIL_OPTION: &str = "tail";
+const TIME_ZONE_OPTION: &str = "time-zone";
+const TIMEOUT_OPTION: &str = "timeout-secs";
+const TITLE_OPTION: &str = "title";
+const TLS_CA_OPTION: &str = "tls-ca";
 const TLS_IDENTITY_FILE_OPTION: &str = "tls-identity-file";
+const TLS_INSECURE_OPTION: &str = "tls-insecure";
 const TLS_OPTION: &str = "tls";
+const TRUST_LOCAL_PLAIN_ADMINS_OPTION: &str = "trust-local-plain-admins";
+const TRUSTED_PROXY_OPTION: &str = "trusted-proxy";
+const WATCH_LISTEN_ADDRESS_OPTION: &str = "watch-listen-address";
+const WEB_ROOT_OPTION: &str = "web-root";
 
 const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:4144";
 const DEFAULT_CONNECT_ADDRESS: &str = "127.0.0.1:4144";
 const DEFAULT_WEB_LISTEN_ADDRESS: &str = "127.0.0.1:4145";
 const DEFAULT_READ_TIMEOUT: std::time::Duration =
     std::time::Duration::from_secs(120);
+const DEFAULT_HISTORY_WINDOW: std::time::Duration =
+    std::time::Duration::from_secs(300);
+const DEFAULT_REFRESH_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(5);
+const DEFAULT_HARDENED_QUARANTINE: std::time::Duration =
+    std::time::Duration::from_secs(3600);
+const DEFAULT_TIMEOUT: std::time::Duration =
+    std::time::Duration::from_secs(30);
+const DEFAULT_CONNECT_TIMEOUT: std::time::Duration =
+    std::time::Duration::from_secs(10);
+const DEFAULT_STREAM_WARNINGS: &[std::time::Duration] = &[
+    std::time::Duration::from_secs(600),
+    std::time::Duration::from_secs(60),
+];
+const DEFAULT_BAUD: u32 = 115_200;
+const DEFAULT_PREFIX_KEY: u8 = b'\x1c';
+const DEFAULT_ESCAPE: &[u8] = b"\x1c";
+const DEFAULT_DETACH_KEY: &[u8] = b"\x1c";
+const DEFAULT_SEND_QUEUE_POLICY: crate::client::SendQueuePolicy =
+    crate::client::SendQueuePolicy::DropOldest;
+const DEFAULT_SEND_QUEUE_SIZE: usize = 1024;
+const DEFAULT_SERVER_SEND_QUEUE_POLICY: crate::server::SendQueuePolicy =
+    crate::server::SendQueuePolicy::DropOldest;
+const DEFAULT_SESSION_LIST_COLUMNS: &[crate::session_list::Column] = &[
+    crate::session_list::Column::Name,
+    crate::session_list::Column::Size,
+    crate::session_list::Column::Idle,
+    crate::session_list::Column::Watchers,
+    crate::session_list::Column::Uptime,
+];
+const DEFAULT_FLOW_CONTROL: tokio_serial::FlowControl =
+    tokio_serial::FlowControl::None;
+const DEFAULT_PARITY: tokio_serial::Parity = tokio_serial::Parity::None;
 const DEFAULT_AUTH_TYPE: crate::protocol::AuthType =
     crate::protocol::AuthType::Plain;
+const DEFAULT_TERM: &str = "xterm-256color";
 const DEFAULT_TLS: bool = false;
 const DEFAULT_TTYREC_FILENAME: &str = "teleterm.ttyrec";
+const DEFAULT_DIFF_SIZE: crate::term::Size =
+    crate::term::Size { rows: 24, cols: 80 };
 
 pub trait Config: std::fmt::Debug {
     fn merge_args<'a>(
@@ -86,8 +200,37 @@ pub struct Client {
     )]
     pub connect_address: (String, std::net::SocketAddr),
 
+    #[serde(deserialize_with = "connect_addresses", default)]
+    pub extra_connect_addresses: Vec<(String, std::net::SocketAddr)>,
+
     #[serde(default = "default_tls")]
     pub tls: bool,
+
+    #[serde(default)]
+    pub tls_ca: Option<String>,
+
+    #[serde(default)]
+    pub tls_insecure: bool,
+
+    #[serde(default)]
+    pub bind_address: Option<std::net::IpAddr>,
+
+    #[serde(default)]
+    pub interface: Option<String>,
+
+    #[serde(
+        rename = "timeout_secs",
+        deserialize_with = "timeout",
+        default = "default_timeout"
+    )]
+    pub timeout: Option<std::time::Duration>,
+
+    #[serde(
+        rename = "connect_timeout_secs",
+        deserialize_with = "timeout",
+        default = "default_connect_timeout"
+    )]
+    pub connect_timeout: Option<std::time::Duration>,
 }
 
 impl Client {
@@ -99,13 +242,90 @@ impl Client {
         &self.connect_address.1
     }
 
+    // the name that the server's host key is stored under
+    pub fn server_name(&self) -> String {
+        server_name(self.host(), self.addr())
+    }
+
+    // tt stream streams to all of these at once, but everything else only
+    // connects to the first one
+    pub fn connect_addresses(&self) -> Vec<(String, std::net::SocketAddr)> {
+        std::iter::once(self.connect_address.clone())
+            .chain(self.extra_connect_addresses.iter().cloned())
+            .collect()
+    }
+
+    pub fn source(&self) -> crate::connect::Source {
+        if let Some(address) = self.bind_address {
+            crate::connect::Source::Address(address)
+        } else if let Some(interface) = &self.interface {
+            crate::connect::Source::Interface(interface.clone())
+        } else {
+            crate::connect::Source::Any
+        }
+    }
+
+    pub fn connector(&self) -> Result<crate::connect::Connector> {
+        self.connector_to(self.host(), *self.addr())
+    }
+
+    pub fn connector_to(
+        &self,
+        host: &str,
+        address: std::net::SocketAddr,
+    ) -> Result<crate::connect::Connector> {
+        let tls = if self.tls {
+            Some(self.tls_connector()?)
+        } else {
+            None
+        };
+        Ok(crate::connect::Connector::new(
+            host,
+            address,
+            self.source(),
+            self.connect_timeout,
+            tls,
+        ))
+    }
+
+    // certificates from private CAs (or self-signed ones) aren't in the
+    // system's certificate store, so they have to be trusted explicitly
+    fn tls_connector(&self) -> Result<native_tls::TlsConnector> {
+        let mut builder = native_tls::TlsConnector::builder();
+        if let Some(filename) = &self.tls_ca {
+            let ca = std::fs::read(filename).context(
+                crate::error::OpenFileSync {
+                    filename: filename.clone(),
+                },
+            )?;
+            let ca = native_tls::Certificate::from_pem(&ca)
+                .or_else(|_| native_tls::Certificate::from_der(&ca))
+                .context(crate::error::ParseCertificate {
+                    filename: filename.clone(),
+                })?;
+            builder.add_root_certificate(ca);
+        }
+        if self.tls_insecure {
+            builder.danger_accept_invalid_certs(true);
+            builder.danger_accept_invalid_hostnames(true);
+        }
+        builder.build().context(crate::error::CreateConnector)
+    }
+
     pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
         let login_plain_help = "Use the 'plain' authentication method (default), with username USERNAME (defaults to $USER)";
         let login_recurse_center_help =
             "Use the 'recurse_center' authentication method";
-        let connect_address_help =
-            "Host and port to connect to (defaults to localhost:4144)";
+        let login_github_help = "Use the 'github' authentication method";
+        let login_google_help = "Use the 'google' authentication method";
+        let connect_address_help = "Host and port to connect to (defaults to localhost:4144, can be given multiple times to stream to more than one server)";
         let tls_help = "Connect to the server using TLS";
+        let tls_ca_help = "Trust server certificates signed by the CA certificate in FILE (in pem or der format), for servers using a private CA or a self-signed certificate (implies --tls)";
+        let tls_insecure_help = "Don't verify the server's TLS certificate at all. This allows anyone between you and the server to read and modify the connection, so only use it for testing (implies --tls)";
+        let bind_address_help = "Local address to connect to the server from, for hosts on more than one network";
+        let interface_help = "Connect to the server from the address of the network interface INTERFACE (looked up again on every reconnect)";
+        let timeout_help = "Number of seconds to wait for the server to respond when logging in, listing sessions, or starting to watch before reconnecting, or 0 to wait forever (defaults to 30)";
+        let connect_timeout_help = "Number of seconds to wait for each connection to the server to be established before trying again, or 0 to wait forever (defaults to 10)";
 
         app.arg(
             clap::Arg::with_name(LOGIN_PLAIN_OPTION)
@@ -120,10 +340,31 @@ impl Client {
                 .conflicts_with(LOGIN_PLAIN_OPTION)
                 .help(login_recurse_center_help),
         )
+        .arg(
+            clap::Arg::with_name(LOGIN_GITHUB_OPTION)
+                .long(LOGIN_GITHUB_OPTION)
+                .conflicts_with_all(&[
+                    LOGIN_PLAIN_OPTION,
+                    LOGIN_RECURSE_CENTER_OPTION,
+                ])
+                .help(login_github_help),
+        )
+        .arg(
+            clap::Arg::with_name(LOGIN_GOOGLE_OPTION)
+                .long(LOGIN_GOOGLE_OPTION)
+                .conflicts_with_all(&[
+                    LOGIN_PLAIN_OPTION,
+                    LOGIN_RECURSE_CENTER_OPTION,
+                    LOGIN_GITHUB_OPTION,
+                ])
+                .help(login_google_help),
+        )
         .arg(
             clap::Arg::with_name(CONNECT_ADDRESS_OPTION)
                 .long(CONNECT_ADDRESS_OPTION)
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
                 .value_name("HOST:PORT")
                 .help(connect_address_help),
         )
@@ -132,6 +373,47 @@ impl Client {
                 .long(TLS_OPTION)
                 .help(tls_help),
         )
+        .arg(
+            clap::Arg::with_name(TLS_CA_OPTION)
+                .long(TLS_CA_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .help(tls_ca_help),
+        )
+        .arg(
+            clap::Arg::with_name(TLS_INSECURE_OPTION)
+                .long(TLS_INSECURE_OPTION)
+                .help(tls_insecure_help),
+        )
+        .arg(
+            clap::Arg::with_name(BIND_ADDRESS_OPTION)
+                .long(BIND_ADDRESS_OPTION)
+                .takes_value(true)
+                .value_name("ADDRESS")
+                .help(bind_address_help),
+        )
+        .arg(
+            clap::Arg::with_name(INTERFACE_OPTION)
+                .long(INTERFACE_OPTION)
+                .conflicts_with(BIND_ADDRESS_OPTION)
+                .takes_value(true)
+                .value_name("INTERFACE")
+                .help(interface_help),
+        )
+        .arg(
+            clap::Arg::with_name(TIMEOUT_OPTION)
+                .long(TIMEOUT_OPTION)
+                .takes_value(true)
+                .value_name("SECS")
+                .help(timeout_help),
+        )
+        .arg(
+            clap::Arg::with_name(CONNECT_TIMEOUT_OPTION)
+                .long(CONNECT_TIMEOUT_OPTION)
+                .takes_value(true)
+                .value_name("SECS")
+                .help(connect_timeout_help),
+        )
     }
 
     pub fn merge_args<'a>(
@@ -141,6 +423,12 @@ impl Client {
         if matches.is_present(LOGIN_RECURSE_CENTER_OPTION) {
             self.auth = crate::protocol::AuthType::RecurseCenter;
         }
+        if matches.is_present(LOGIN_GITHUB_OPTION) {
+            self.auth = crate::protocol::AuthType::Github;
+        }
+        if matches.is_present(LOGIN_GOOGLE_OPTION) {
+            self.auth = crate::protocol::AuthType::Google;
+        }
         if matches.is_present(LOGIN_PLAIN_OPTION) {
             let username = matches
                 .value_of(LOGIN_PLAIN_OPTION)
@@ -149,12 +437,65 @@ impl Client {
             self.username = username;
         }
         if matches.is_present(CONNECT_ADDRESS_OPTION) {
-            let address = matches.value_of(CONNECT_ADDRESS_OPTION).unwrap();
-            self.connect_address = to_connect_address(address)?;
+            let mut addresses = matches
+                .values_of(CONNECT_ADDRESS_OPTION)
+                .unwrap()
+                .map(to_connect_address)
+                .collect::<Result<Vec<_>>>()?;
+            // addresses given on the command line replace all of the ones
+            // from the config file
+            self.connect_address = addresses.remove(0);
+            self.extra_connect_addresses = addresses;
         }
         if matches.is_present(TLS_OPTION) {
             self.tls = true;
         }
+        if matches.is_present(TLS_CA_OPTION) {
+            self.tls_ca =
+                Some(matches.value_of(TLS_CA_OPTION).unwrap().to_string());
+            self.tls = true;
+        }
+        if matches.is_present(TLS_INSECURE_OPTION) {
+            self.tls_insecure = true;
+            self.tls = true;
+        }
+        // these are alternatives, so one given on the command line should
+        // replace either of them from the config file
+        if matches.is_present(BIND_ADDRESS_OPTION) {
+            self.bind_address = Some(
+                matches
+                    .value_of(BIND_ADDRESS_OPTION)
+                    .unwrap()
+                    .parse()
+                    .context(crate::error::ParseAddr)?,
+            );
+            self.interface = None;
+        }
+        if matches.is_present(INTERFACE_OPTION) {
+            self.interface =
+                Some(matches.value_of(INTERFACE_OPTION).unwrap().to_string());
+            self.bind_address = None;
+        }
+        if matches.is_present(TIMEOUT_OPTION) {
+            let s = matches.value_of(TIMEOUT_OPTION).unwrap();
+            let secs =
+                s.parse().context(crate::error::ParseTimeout { input: s })?;
+            self.timeout = if secs == 0 {
+                None
+            } else {
+                Some(std::time::Duration::from_secs(secs))
+            };
+        }
+        if matches.is_present(CONNECT_TIMEOUT_OPTION) {
+            let s = matches.value_of(CONNECT_TIMEOUT_OPTION).unwrap();
+            let secs =
+                s.parse().context(crate::error::ParseTimeout { input: s })?;
+            self.connect_timeout = if secs == 0 {
+                None
+            } else {
+                Some(std::time::Duration::from_secs(secs))
+            };
+        }
         Ok(())
     }
 }
@@ -165,7 +506,14 @@ impl Default for Client {
             auth: default_auth_type(),
             username: default_username(),
             connect_address: default_connect_address(),
+            extra_connect_addresses: vec![],
             tls: default_tls(),
+            tls_ca: None,
+            tls_insecure: false,
+            bind_address: None,
+            interface: None,
+            timeout: default_timeout(),
+            connect_timeout: default_connect_timeout(),
         }
     }
 }
@@ -187,7 +535,10 @@ fn default_auth_type() -> crate::protocol::AuthType {
 }
 
 fn default_username() -> Option<String> {
-    std::env::var("USER").ok()
+    // $USER isn't set everywhere (termux, for instance)
+    std::env::var("USER").ok().or_else(|| {
+        users::get_current_username().and_then(|name| name.into_string().ok())
+    })
 }
 
 fn connect_address<'a, D>(
@@ -204,7 +555,26 @@ fn default_connect_address() -> (String, std::net::SocketAddr) {
     to_connect_address(DEFAULT_CONNECT_ADDRESS).unwrap()
 }
 
-// XXX this does a blocking dns lookup - should try to find an async version
+fn connect_addresses<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<(String, std::net::SocketAddr)>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    <Vec<String>>::deserialize(deserializer)?
+        .iter()
+        .map(|s| to_connect_address(s).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+pub fn server_name(host: &str, addr: &std::net::SocketAddr) -> String {
+    format!("{}:{}", host, addr.port())
+}
+
+// this does a blocking dns lookup, which is fine since configuration is
+// read before anything starts running on the reactor. connections look the
+// host up again (see crate::connect::tcp), so this address is only used if
+// that lookup fails.
 fn to_connect_address(
     address: &str,
 ) -> Result<(String, std::net::SocketAddr)> {
@@ -227,14 +597,45 @@ fn default_tls() -> bool {
     DEFAULT_TLS
 }
 
+fn timeout<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Option<std::time::Duration>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    let secs = u64::deserialize(deserializer)?;
+    Ok(if secs == 0 {
+        None
+    } else {
+        Some(std::time::Duration::from_secs(secs))
+    })
+}
+
+fn default_timeout() -> Option<std::time::Duration> {
+    Some(DEFAULT_TIMEOUT)
+}
+
+fn default_connect_timeout() -> Option<std::time::Duration> {
+    Some(DEFAULT_CONNECT_TIMEOUT)
+}
+
 #[derive(serde::Deserialize, Debug)]
 pub struct Server {
+    #[serde(deserialize_with = "server_profile", default)]
+    pub profile: ServerProfile,
+
     #[serde(
         deserialize_with = "listen_address",
         default = "default_listen_address"
     )]
     pub listen_address: std::net::SocketAddr,
 
+    #[serde(deserialize_with = "optional_listen_address", default)]
+    pub stream_listen_address: Option<std::net::SocketAddr>,
+
+    #[serde(deserialize_with = "optional_listen_address", default)]
+    pub watch_listen_address: Option<std::net::SocketAddr>,
+
     #[serde(
         rename = "read_timeout_secs",
         deserialize_with = "read_timeout",
@@ -242,8 +643,63 @@ pub struct Server {
     )]
     pub read_timeout: std::time::Duration,
 
+    #[serde(
+        rename = "history_secs",
+        deserialize_with = "history_window",
+        default = "default_history_window"
+    )]
+    pub history_window: std::time::Duration,
+
+    #[serde(
+        rename = "quarantine_secs",
+        deserialize_with = "quarantine_duration",
+        default
+    )]
+    pub quarantine_duration: Option<std::time::Duration>,
+
+    #[serde(default)]
+    pub trusted_proxies: Vec<std::net::IpAddr>,
+
+    #[serde(
+        rename = "disconnect_grace_secs",
+        deserialize_with = "disconnect_grace",
+        default
+    )]
+    pub disconnect_grace: Option<std::time::Duration>,
+
+    #[serde(
+        rename = "max_stream_secs",
+        deserialize_with = "max_stream_duration",
+        default
+    )]
+    pub max_stream_duration: Option<std::time::Duration>,
+
+    #[serde(
+        rename = "stream_warning_secs",
+        deserialize_with = "stream_warnings",
+        default = "default_stream_warnings"
+    )]
+    pub stream_warnings: Vec<std::time::Duration>,
+
+    #[serde(deserialize_with = "max_connections", default)]
+    pub max_connections: Option<u32>,
+
+    #[serde(deserialize_with = "max_watchers_per_session", default)]
+    pub max_watchers_per_session: Option<u32>,
+
+    #[serde(default = "default_send_queue_size")]
+    pub send_queue_size: usize,
+
+    #[serde(
+        deserialize_with = "server_send_queue_policy",
+        default = "default_server_send_queue_policy"
+    )]
+    pub send_queue_policy: crate::server::SendQueuePolicy,
+
     pub tls_identity_file: Option<String>,
 
+    pub host_key_file: Option<String>,
+
     #[serde(
         deserialize_with = "allowed_login_methods",
         default = "default_allowed_login_methods"
@@ -251,27 +707,84 @@ pub struct Server {
     pub allowed_login_methods:
         std::collections::HashSet<crate::protocol::AuthType>,
 
+    #[serde(deserialize_with = "admins", default)]
+    pub admins: Vec<(crate::protocol::AuthType, String)>,
+
+    #[serde(default)]
+    pub trust_local_plain_admins: bool,
+
+    pub ban_file: Option<String>,
+
     #[serde(deserialize_with = "uid", default)]
     pub uid: Option<users::uid_t>,
 
     #[serde(deserialize_with = "gid", default)]
     pub gid: Option<users::gid_t>,
+
+    #[serde(default)]
+    pub daemonize: bool,
+
+    pub log_file: Option<String>,
+
+    pub pid_file: Option<String>,
 }
 
 impl Server {
     pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+        let profile_help = "Set of defaults to start from, one of default or hardened. The hardened profile requires TLS and a login method other than plain, and quarantines clients which break the protocol (defaults to default)";
         let listen_address_help =
             "Host and port to listen on (defaults to localhost:4144)";
-        let read_timeout_help = "Number of idle seconds to wait before disconnecting a client (defaults to 30)";
+        let stream_listen_address_help = "Separate host and port to accept streaming clients on. If set, streaming clients will not be accepted on the main listen address";
+        let watch_listen_address_help = "Separate host and port to accept watching clients on. If set, watching clients will not be accepted on the main listen address";
+        let read_timeout_help = "Number of idle seconds to wait before disconnecting a client (defaults to 120). Clients are asked to send heartbeats three times within this period";
+        let history_help = "Number of seconds of stream output to keep, so that watchers can rewind (defaults to 300)";
+        let quarantine_help = "Strictly enforce the protocol: clients which send invalid or unexpected messages are logged in detail and their address is refused for SECS seconds";
+        let trusted_proxy_help = "IP address of a proxy which connects on behalf of many clients, like tt web, whose address is never refused by --quarantine-secs (can be given multiple times)";
+        let disconnect_grace_help = "Number of seconds to keep a stream going after its streamer's connection drops, so that watchers aren't disconnected if the streamer reconnects within that time (by default, watchers are disconnected right away)";
+        let max_stream_help = "Number of seconds a stream can run for before the server ends it, so that forgotten streams don't run forever (by default, there is no limit)";
+        let max_connections_help = "Number of clients which can be connected to the server at once. Clients beyond this are refused with an error message (by default, there is no limit)";
+        let max_watchers_help = "Number of watchers each stream can have at once. Watchers beyond this are refused with an error message (by default, there is no limit)";
+        let stream_warning_help = "Comma separated list of how many seconds before the end of a stream (see --max-stream-secs) to warn the streamer that it will be ended (defaults to 600,60)";
+        let send_queue_size_help = "Number of messages that can be waiting to be sent to a client before applying the --send-queue-policy (defaults to 1024)";
+        let send_queue_policy_help = "What to do when a client can't keep up with the messages being sent to it, one of drop-oldest (drop the queued terminal output and send the current screen instead), coalesce (merge the queued terminal output into fewer messages), or disconnect (defaults to drop-oldest)";
         let tls_identity_file_help = "File containing the TLS certificate and private key to use for accepting TLS connections. Must be in pfx format. The server will only allow connections over TLS if this option is set.";
-        let allowed_login_methods_help = "Comma separated list containing the auth methods this server should allow. Allows everything by default, valid values are plain, recurse_center";
+        let host_key_file_help = "File containing a key which clients that aren't using TLS remember, so that they notice if a different server starts answering at this address (this doesn't make unencrypted connections secure). Will be generated if it doesn't exist";
+        let allowed_login_methods_help = "Comma separated list containing the auth methods this server should allow. Allows everything by default, valid values are plain, recurse_center, github, google";
+        let admin_help = "Allow the user logged in as USERNAME with AUTH_METHOD (for instance, github:doy) to use tt admin to list and kick connections, send notices, and see server stats (can be given multiple times). Admins using the plain login method are never trusted unless --trust-local-plain-admins is given";
+        let trust_local_plain_admins_help = "Trust admins using the plain login method when they connect from localhost. Anyone who can connect from the server's machine can claim to be them";
+        let ban_file_help = "File listing usernames and IP addresses (one per line) which are not allowed to connect. Reloaded automatically when it changes, and tt admin ban adds to it";
+        let daemonize_help =
+            "Detach from the terminal and run in the background";
+        let log_file_help = "File to write log output to";
+        let pid_file_help = "File to write the process id of the server to";
         app.arg(
+            clap::Arg::with_name(PROFILE_OPTION)
+                .long(PROFILE_OPTION)
+                .takes_value(true)
+                .value_name("PROFILE")
+                .help(profile_help),
+        )
+        .arg(
             clap::Arg::with_name(LISTEN_ADDRESS_OPTION)
                 .long(LISTEN_ADDRESS_OPTION)
                 .takes_value(true)
                 .value_name("HOST:PORT")
                 .help(listen_address_help),
         )
+        .arg(
+            clap::Arg::with_name(STREAM_LISTEN_ADDRESS_OPTION)
+                .long(STREAM_LISTEN_ADDRESS_OPTION)
+                .takes_value(true)
+                .value_name("HOST:PORT")
+                .help(stream_listen_address_help),
+        )
+        .arg(
+            clap::Arg::with_name(WATCH_LISTEN_ADDRESS_OPTION)
+                .long(WATCH_LISTEN_ADDRESS_OPTION)
+                .takes_value(true)
+                .value_name("HOST:PORT")
+                .help(watch_listen_address_help),
+        )
         .arg(
             clap::Arg::with_name(READ_TIMEOUT_OPTION)
                 .long(READ_TIMEOUT_OPTION)
@@ -279,6 +792,79 @@ impl Server {
                 .value_name("SECS")
                 .help(read_timeout_help),
         )
+        .arg(
+            clap::Arg::with_name(HISTORY_OPTION)
+                .long(HISTORY_OPTION)
+                .takes_value(true)
+                .value_name("SECS")
+                .help(history_help),
+        )
+        .arg(
+            clap::Arg::with_name(QUARANTINE_OPTION)
+                .long(QUARANTINE_OPTION)
+                .takes_value(true)
+                .value_name("SECS")
+                .help(quarantine_help),
+        )
+        .arg(
+            clap::Arg::with_name(TRUSTED_PROXY_OPTION)
+                .long(TRUSTED_PROXY_OPTION)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("ADDRESS")
+                .help(trusted_proxy_help),
+        )
+        .arg(
+            clap::Arg::with_name(DISCONNECT_GRACE_OPTION)
+                .long(DISCONNECT_GRACE_OPTION)
+                .takes_value(true)
+                .value_name("SECS")
+                .help(disconnect_grace_help),
+        )
+        .arg(
+            clap::Arg::with_name(MAX_STREAM_OPTION)
+                .long(MAX_STREAM_OPTION)
+                .takes_value(true)
+                .value_name("SECS")
+                .help(max_stream_help),
+        )
+        .arg(
+            clap::Arg::with_name(STREAM_WARNING_OPTION)
+                .long(STREAM_WARNING_OPTION)
+                .use_delimiter(true)
+                .takes_value(true)
+                .value_name("SECS")
+                .help(stream_warning_help),
+        )
+        .arg(
+            clap::Arg::with_name(MAX_CONNECTIONS_OPTION)
+                .long(MAX_CONNECTIONS_OPTION)
+                .takes_value(true)
+                .value_name("COUNT")
+                .help(max_connections_help),
+        )
+        .arg(
+            clap::Arg::with_name(MAX_WATCHERS_OPTION)
+                .long(MAX_WATCHERS_OPTION)
+                .takes_value(true)
+                .value_name("COUNT")
+                .help(max_watchers_help),
+        )
+        .arg(
+            clap::Arg::with_name(SEND_QUEUE_SIZE_OPTION)
+                .long(SEND_QUEUE_SIZE_OPTION)
+                .takes_value(true)
+                .value_name("COUNT")
+                .help(send_queue_size_help),
+        )
+        .arg(
+            clap::Arg::with_name(SEND_QUEUE_POLICY_OPTION)
+                .long(SEND_QUEUE_POLICY_OPTION)
+                .takes_value(true)
+                .value_name("POLICY")
+                .help(send_queue_policy_help),
+        )
         .arg(
             clap::Arg::with_name(TLS_IDENTITY_FILE_OPTION)
                 .long(TLS_IDENTITY_FILE_OPTION)
@@ -286,6 +872,13 @@ impl Server {
                 .value_name("FILE")
                 .help(tls_identity_file_help),
         )
+        .arg(
+            clap::Arg::with_name(HOST_KEY_FILE_OPTION)
+                .long(HOST_KEY_FILE_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .help(host_key_file_help),
+        )
         .arg(
             clap::Arg::with_name(ALLOWED_LOGIN_METHODS_OPTION)
                 .long(ALLOWED_LOGIN_METHODS_OPTION)
@@ -294,12 +887,57 @@ impl Server {
                 .value_name("AUTH_METHODS")
                 .help(allowed_login_methods_help),
         )
+        .arg(
+            clap::Arg::with_name(ADMIN_OPTION)
+                .long(ADMIN_OPTION)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("AUTH_METHOD:USERNAME")
+                .help(admin_help),
+        )
+        .arg(
+            clap::Arg::with_name(TRUST_LOCAL_PLAIN_ADMINS_OPTION)
+                .long(TRUST_LOCAL_PLAIN_ADMINS_OPTION)
+                .help(trust_local_plain_admins_help),
+        )
+        .arg(
+            clap::Arg::with_name(BAN_FILE_OPTION)
+                .long(BAN_FILE_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .help(ban_file_help),
+        )
+        .arg(
+            clap::Arg::with_name(DAEMONIZE_OPTION)
+                .long(DAEMONIZE_OPTION)
+                .help(daemonize_help),
+        )
+        .arg(
+            clap::Arg::with_name(LOG_FILE_OPTION)
+                .long(LOG_FILE_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .help(log_file_help),
+        )
+        .arg(
+            clap::Arg::with_name(PID_FILE_OPTION)
+                .long(PID_FILE_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .help(pid_file_help),
+        )
     }
 
     pub fn merge_args<'a>(
         &mut self,
         matches: &clap::ArgMatches<'a>,
     ) -> Result<()> {
+        if matches.is_present(PROFILE_OPTION) {
+            self.profile = ServerProfile::try_from(
+                matches.value_of(PROFILE_OPTION).unwrap(),
+            )?;
+        }
         if matches.is_present(LISTEN_ADDRESS_OPTION) {
             self.listen_address = matches
                 .value_of(LISTEN_ADDRESS_OPTION)
@@ -307,6 +945,24 @@ impl Server {
                 .parse()
                 .context(crate::error::ParseAddr)?;
         }
+        if matches.is_present(STREAM_LISTEN_ADDRESS_OPTION) {
+            self.stream_listen_address = Some(
+                matches
+                    .value_of(STREAM_LISTEN_ADDRESS_OPTION)
+                    .unwrap()
+                    .parse()
+                    .context(crate::error::ParseAddr)?,
+            );
+        }
+        if matches.is_present(WATCH_LISTEN_ADDRESS_OPTION) {
+            self.watch_listen_address = Some(
+                matches
+                    .value_of(WATCH_LISTEN_ADDRESS_OPTION)
+                    .unwrap()
+                    .parse()
+                    .context(crate::error::ParseAddr)?,
+            );
+        }
         if matches.is_present(READ_TIMEOUT_OPTION) {
             let s = matches.value_of(READ_TIMEOUT_OPTION).unwrap();
             self.read_timeout = s
@@ -314,6 +970,90 @@ impl Server {
                 .map(std::time::Duration::from_secs)
                 .context(crate::error::ParseReadTimeout { input: s })?;
         }
+        if matches.is_present(HISTORY_OPTION) {
+            let s = matches.value_of(HISTORY_OPTION).unwrap();
+            self.history_window = s
+                .parse()
+                .map(std::time::Duration::from_secs)
+                .context(crate::error::ParseHistory { input: s })?;
+        }
+        if matches.is_present(QUARANTINE_OPTION) {
+            let s = matches.value_of(QUARANTINE_OPTION).unwrap();
+            self.quarantine_duration = Some(
+                s.parse()
+                    .map(std::time::Duration::from_secs)
+                    .context(crate::error::ParseQuarantine { input: s })?,
+            );
+        }
+        if matches.is_present(TRUSTED_PROXY_OPTION) {
+            self.trusted_proxies = matches
+                .values_of(TRUSTED_PROXY_OPTION)
+                .unwrap()
+                .map(|address| {
+                    address.parse().context(crate::error::ParseAddr)
+                })
+                .collect::<Result<_>>()?;
+        }
+        if matches.is_present(DISCONNECT_GRACE_OPTION) {
+            let s = matches.value_of(DISCONNECT_GRACE_OPTION).unwrap();
+            let secs = s
+                .parse()
+                .context(crate::error::ParseDisconnectGrace { input: s })?;
+            self.disconnect_grace = if secs == 0 {
+                None
+            } else {
+                Some(std::time::Duration::from_secs(secs))
+            };
+        }
+        if matches.is_present(MAX_STREAM_OPTION) {
+            let s = matches.value_of(MAX_STREAM_OPTION).unwrap();
+            let secs = s
+                .parse()
+                .context(crate::error::ParseMaxStream { input: s })?;
+            self.max_stream_duration = if secs == 0 {
+                None
+            } else {
+                Some(std::time::Duration::from_secs(secs))
+            };
+        }
+        if matches.is_present(STREAM_WARNING_OPTION) {
+            self.stream_warnings = matches
+                .values_of(STREAM_WARNING_OPTION)
+                .unwrap()
+                .map(|s| {
+                    s.parse().map(std::time::Duration::from_secs).context(
+                        crate::error::ParseStreamWarning { input: s },
+                    )
+                })
+                .collect::<Result<_>>()?;
+        }
+        if matches.is_present(MAX_CONNECTIONS_OPTION) {
+            let s = matches.value_of(MAX_CONNECTIONS_OPTION).unwrap();
+            let max = s
+                .parse()
+                .context(crate::error::ParseMaxConnections { input: s })?;
+            self.max_connections = if max == 0 { None } else { Some(max) };
+        }
+        if matches.is_present(MAX_WATCHERS_OPTION) {
+            let s = matches.value_of(MAX_WATCHERS_OPTION).unwrap();
+            let max = s
+                .parse()
+                .context(crate::error::ParseMaxWatchers { input: s })?;
+            self.max_watchers_per_session =
+                if max == 0 { None } else { Some(max) };
+        }
+        if matches.is_present(SEND_QUEUE_SIZE_OPTION) {
+            let s = matches.value_of(SEND_QUEUE_SIZE_OPTION).unwrap();
+            self.send_queue_size = s
+                .parse()
+                .context(crate::error::ParseSendQueueSize { input: s })?;
+        }
+        if matches.is_present(SEND_QUEUE_POLICY_OPTION) {
+            self.send_queue_policy =
+                crate::server::SendQueuePolicy::try_from(
+                    matches.value_of(SEND_QUEUE_POLICY_OPTION).unwrap(),
+                )?;
+        }
         if matches.is_present(TLS_IDENTITY_FILE_OPTION) {
             self.tls_identity_file = Some(
                 matches
@@ -322,6 +1062,11 @@ impl Server {
                     .to_string(),
             );
         }
+        if matches.is_present(HOST_KEY_FILE_OPTION) {
+            self.host_key_file = Some(
+                matches.value_of(HOST_KEY_FILE_OPTION).unwrap().to_string(),
+            );
+        }
         if matches.is_present(ALLOWED_LOGIN_METHODS_OPTION) {
             self.allowed_login_methods = matches
                 .values_of(ALLOWED_LOGIN_METHODS_OPTION)
@@ -331,6 +1076,61 @@ impl Server {
                     std::collections::HashSet<crate::protocol::AuthType>,
                 >>()?;
         }
+        if matches.is_present(ADMIN_OPTION) {
+            self.admins = matches
+                .values_of(ADMIN_OPTION)
+                .unwrap()
+                .map(to_admin)
+                .collect::<Result<_>>()?;
+        }
+        if matches.is_present(TRUST_LOCAL_PLAIN_ADMINS_OPTION) {
+            self.trust_local_plain_admins = true;
+        }
+        if matches.is_present(BAN_FILE_OPTION) {
+            self.ban_file =
+                Some(matches.value_of(BAN_FILE_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(DAEMONIZE_OPTION) {
+            self.daemonize = true;
+        }
+        if matches.is_present(LOG_FILE_OPTION) {
+            self.log_file =
+                Some(matches.value_of(LOG_FILE_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(PID_FILE_OPTION) {
+            self.pid_file =
+                Some(matches.value_of(PID_FILE_OPTION).unwrap().to_string());
+        }
+        self.apply_profile()
+    }
+
+    // this runs after everything else has been merged, so that the profile
+    // only fills in settings which weren't given explicitly, and can check
+    // the ones which were
+    fn apply_profile(&mut self) -> Result<()> {
+        match self.profile {
+            ServerProfile::Default => {}
+            ServerProfile::Hardened => {
+                if self.tls_identity_file.is_none() {
+                    return Err(Error::HardenedProfile {
+                        requirement: "a tls identity file".to_string(),
+                    });
+                }
+                // plain logins let anyone claim to be anyone
+                self.allowed_login_methods
+                    .remove(&crate::protocol::AuthType::Plain);
+                if self.allowed_login_methods.is_empty() {
+                    return Err(Error::HardenedProfile {
+                        requirement: "a login method other than plain"
+                            .to_string(),
+                    });
+                }
+                if self.quarantine_duration.is_none() {
+                    self.quarantine_duration =
+                        Some(DEFAULT_HARDENED_QUARANTINE);
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -338,22 +1138,80 @@ impl Server {
 impl Default for Server {
     fn default() -> Self {
         Self {
+            profile: ServerProfile::default(),
             listen_address: default_listen_address(),
+            stream_listen_address: None,
+            watch_listen_address: None,
             read_timeout: default_read_timeout(),
+            history_window: default_history_window(),
+            quarantine_duration: None,
+            trusted_proxies: vec![],
+            disconnect_grace: None,
+            max_stream_duration: None,
+            stream_warnings: default_stream_warnings(),
+            max_connections: None,
+            max_watchers_per_session: None,
+            send_queue_size: default_send_queue_size(),
+            send_queue_policy: default_server_send_queue_policy(),
             tls_identity_file: None,
+            host_key_file: None,
             allowed_login_methods: default_allowed_login_methods(),
+            admins: vec![],
+            trust_local_plain_admins: false,
+            ban_file: None,
             uid: None,
             gid: None,
+            daemonize: false,
+            log_file: None,
+            pid_file: None,
         }
     }
 }
 
-fn listen_address<'a, D>(
-    deserializer: D,
-) -> std::result::Result<std::net::SocketAddr, D::Error>
-where
-    D: serde::de::Deserializer<'a>,
-{
+// presets for server settings, for operators who want a safe baseline
+// without needing to go through every option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerProfile {
+    Default,
+    Hardened,
+}
+
+impl Default for ServerProfile {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+impl std::convert::TryFrom<&str> for ServerProfile {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        match s {
+            "default" => Ok(Self::Default),
+            "hardened" => Ok(Self::Hardened),
+            _ => Err(Error::InvalidProfile {
+                profile: s.to_string(),
+            }),
+        }
+    }
+}
+
+fn server_profile<'a, D>(
+    deserializer: D,
+) -> std::result::Result<ServerProfile, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    ServerProfile::try_from(<String>::deserialize(deserializer)?.as_ref())
+        .map_err(serde::de::Error::custom)
+}
+
+fn listen_address<'a, D>(
+    deserializer: D,
+) -> std::result::Result<std::net::SocketAddr, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
     to_listen_address(&<String>::deserialize(deserializer)?)
         .map_err(serde::de::Error::custom)
 }
@@ -362,6 +1220,15 @@ fn default_listen_address() -> std::net::SocketAddr {
     to_listen_address(DEFAULT_LISTEN_ADDRESS).unwrap()
 }
 
+fn optional_listen_address<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Option<std::net::SocketAddr>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    Ok(Some(listen_address(deserializer)?))
+}
+
 fn to_listen_address(address: &str) -> Result<std::net::SocketAddr> {
     address.parse().context(crate::error::ParseAddr)
 }
@@ -381,6 +1248,112 @@ fn default_read_timeout() -> std::time::Duration {
     DEFAULT_READ_TIMEOUT
 }
 
+fn history_window<'a, D>(
+    deserializer: D,
+) -> std::result::Result<std::time::Duration, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    Ok(std::time::Duration::from_secs(u64::deserialize(
+        deserializer,
+    )?))
+}
+
+fn default_history_window() -> std::time::Duration {
+    DEFAULT_HISTORY_WINDOW
+}
+
+fn quarantine_duration<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Option<std::time::Duration>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    Ok(Some(std::time::Duration::from_secs(u64::deserialize(
+        deserializer,
+    )?)))
+}
+
+fn disconnect_grace<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Option<std::time::Duration>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    let secs = u64::deserialize(deserializer)?;
+    Ok(if secs == 0 {
+        None
+    } else {
+        Some(std::time::Duration::from_secs(secs))
+    })
+}
+
+fn max_stream_duration<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Option<std::time::Duration>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    let secs = u64::deserialize(deserializer)?;
+    Ok(if secs == 0 {
+        None
+    } else {
+        Some(std::time::Duration::from_secs(secs))
+    })
+}
+
+fn stream_warnings<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<std::time::Duration>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    Ok(<Vec<u64>>::deserialize(deserializer)?
+        .into_iter()
+        .map(std::time::Duration::from_secs)
+        .collect())
+}
+
+fn default_stream_warnings() -> Vec<std::time::Duration> {
+    DEFAULT_STREAM_WARNINGS.to_vec()
+}
+
+fn max_connections<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Option<u32>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    let max = u32::deserialize(deserializer)?;
+    Ok(if max == 0 { None } else { Some(max) })
+}
+
+fn max_watchers_per_session<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Option<u32>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    let max = u32::deserialize(deserializer)?;
+    Ok(if max == 0 { None } else { Some(max) })
+}
+
+fn server_send_queue_policy<'a, D>(
+    deserializer: D,
+) -> std::result::Result<crate::server::SendQueuePolicy, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    crate::server::SendQueuePolicy::try_from(
+        <String>::deserialize(deserializer)?.as_ref(),
+    )
+    .map_err(serde::de::Error::custom)
+}
+
+fn default_server_send_queue_policy() -> crate::server::SendQueuePolicy {
+    DEFAULT_SERVER_SEND_QUEUE_POLICY
+}
+
 fn allowed_login_methods<'a, D>(
     deserializer: D,
 ) -> std::result::Result<
@@ -443,6 +1416,31 @@ fn default_allowed_login_methods(
     crate::protocol::AuthType::iter().collect()
 }
 
+fn admins<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<(crate::protocol::AuthType, String)>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    <Vec<String>>::deserialize(deserializer)?
+        .iter()
+        .map(|admin| to_admin(admin).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+// admins are given as the auth method they log in with and their username
+// there, like github:doy
+fn to_admin(admin: &str) -> Result<(crate::protocol::AuthType, String)> {
+    let mut parts = admin.splitn(2, ':');
+    let ty = parts.next().unwrap();
+    let username = parts
+        .next()
+        .filter(|username| !username.is_empty())
+        .context(crate::error::InvalidAdmin { admin })?;
+    let ty = crate::protocol::AuthType::try_from(ty)?;
+    Ok((ty, username.to_string()))
+}
+
 fn uid<'a, D>(
     deserializer: D,
 ) -> std::result::Result<Option<users::uid_t>, D::Error>
@@ -577,6 +1575,19 @@ pub struct Web {
     )]
     pub allowed_login_methods:
         std::collections::HashSet<crate::protocol::AuthType>,
+
+    #[serde(
+        rename = "jitter_buffer_ms",
+        deserialize_with = "jitter_buffer",
+        default
+    )]
+    pub jitter_buffer: std::time::Duration,
+
+    #[serde(default)]
+    pub web_root: Option<String>,
+
+    #[serde(default)]
+    pub recordings_dir: Option<String>,
 }
 
 impl Web {
@@ -584,10 +1595,13 @@ impl Web {
         let listen_address_help =
             "Host and port to listen on (defaults to localhost:4145)";
         let public_address_help =
-            "Host and port that the web server will be publicly available on (defaults to the listen address)";
+            "Host and port (or full url, including any path prefix) that the web server will be publicly available on (defaults to the listen address)";
         let server_address_help =
             "Host and port of the teleterm server (defaults to localhost:4144)";
-        let allowed_login_methods_help = "Comma separated list containing the auth methods this server should allow. Allows everything by default, valid values are plain, recurse_center";
+        let allowed_login_methods_help = "Comma separated list containing the auth methods this server should allow. Allows everything by default, valid values are plain, recurse_center, github, google";
+        let jitter_buffer_help = "Hold back output in the browser by up to MS milliseconds to smooth out network jitter. Higher values are smoother, lower values stay closer to live (defaults to 0, which disables the buffer)";
+        let web_root_help = "Serve the files for the web interface from DIR instead of using the copies built into tt";
+        let recordings_dir_help = "Allow logged in users to download and play back the ttyrec files in DIR";
         app.arg(
             clap::Arg::with_name(LISTEN_ADDRESS_OPTION)
                 .long(LISTEN_ADDRESS_OPTION)
@@ -599,7 +1613,7 @@ impl Web {
             clap::Arg::with_name(PUBLIC_ADDRESS_OPTION)
                 .long(PUBLIC_ADDRESS_OPTION)
                 .takes_value(true)
-                .value_name("HOST:PORT")
+                .value_name("ADDRESS")
                 .help(public_address_help),
         )
         .arg(
@@ -617,6 +1631,27 @@ impl Web {
                 .value_name("AUTH_METHODS")
                 .help(allowed_login_methods_help),
         )
+        .arg(
+            clap::Arg::with_name(JITTER_BUFFER_OPTION)
+                .long(JITTER_BUFFER_OPTION)
+                .takes_value(true)
+                .value_name("MS")
+                .help(jitter_buffer_help),
+        )
+        .arg(
+            clap::Arg::with_name(WEB_ROOT_OPTION)
+                .long(WEB_ROOT_OPTION)
+                .takes_value(true)
+                .value_name("DIR")
+                .help(web_root_help),
+        )
+        .arg(
+            clap::Arg::with_name(RECORDINGS_DIR_OPTION)
+                .long(RECORDINGS_DIR_OPTION)
+                .takes_value(true)
+                .value_name("DIR")
+                .help(recordings_dir_help),
+        )
     }
 
     pub fn merge_args<'a>(
@@ -650,8 +1685,33 @@ impl Web {
                     std::collections::HashSet<crate::protocol::AuthType>,
                 >>()?;
         }
+        if matches.is_present(JITTER_BUFFER_OPTION) {
+            self.jitter_buffer = parse_jitter_buffer(
+                matches.value_of(JITTER_BUFFER_OPTION).unwrap(),
+            )?;
+        }
+        if matches.is_present(WEB_ROOT_OPTION) {
+            self.web_root =
+                Some(matches.value_of(WEB_ROOT_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(RECORDINGS_DIR_OPTION) {
+            self.recordings_dir = Some(
+                matches.value_of(RECORDINGS_DIR_OPTION).unwrap().to_string(),
+            );
+        }
         Ok(())
     }
+
+    // the public address can also be a full url, to give the scheme (when
+    // a proxy in front of us handles tls) and a path prefix
+    pub fn public_url(&self) -> String {
+        let address = self.public_address.trim_end_matches('/');
+        if address.contains("://") {
+            address.to_string()
+        } else {
+            format!("http://{}", address)
+        }
+    }
 }
 
 impl Default for Web {
@@ -661,6 +1721,9 @@ impl Default for Web {
             public_address: default_web_public_address(),
             server_address: default_connect_address(),
             allowed_login_methods: default_allowed_login_methods(),
+            jitter_buffer: std::time::Duration::default(),
+            web_root: None,
+            recordings_dir: None,
         }
     }
 }
@@ -673,6 +1736,67 @@ fn default_web_public_address() -> String {
     DEFAULT_WEB_LISTEN_ADDRESS.to_string()
 }
 
+#[derive(serde::Deserialize, Debug)]
+pub struct Display {
+    #[serde(default)]
+    pub ascii: bool,
+
+    #[serde(default)]
+    pub no_color: bool,
+}
+
+impl Display {
+    // what we detect from the environment can only be turned off here, not
+    // forced on
+    pub fn caps(&self) -> crate::term::Caps {
+        let mut caps = crate::term::Caps::detect();
+        if self.ascii {
+            caps.unicode = false;
+        }
+        if self.no_color {
+            caps.color = false;
+        }
+        caps
+    }
+
+    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+        let ascii_help = "Only use ASCII characters when drawing the interface (detected automatically from TERM and the locale)";
+        let no_color_help = "Don't use colors when drawing the interface (detected automatically from TERM and NO_COLOR)";
+        app.arg(
+            clap::Arg::with_name(ASCII_OPTION)
+                .long(ASCII_OPTION)
+                .help(ascii_help),
+        )
+        .arg(
+            clap::Arg::with_name(NO_COLOR_OPTION)
+                .long(NO_COLOR_OPTION)
+                .help(no_color_help),
+        )
+    }
+
+    pub fn merge_args<'a>(
+        &mut self,
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        if matches.is_present(ASCII_OPTION) {
+            self.ascii = true;
+        }
+        if matches.is_present(NO_COLOR_OPTION) {
+            self.no_color = true;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self {
+            ascii: false,
+            no_color: false,
+        }
+    }
+}
+
 #[derive(serde::Deserialize, Debug)]
 pub struct Command {
     #[serde(default = "default_command")]
@@ -680,12 +1804,24 @@ pub struct Command {
 
     #[serde(default = "default_args")]
     pub args: Vec<String>,
+
+    #[serde(default)]
+    pub env: std::collections::BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub clear_env: bool,
+
+    #[serde(default)]
+    pub cwd: Option<String>,
 }
 
 impl Command {
     pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
         let command_help = "Command to run";
         let args_help = "Arguments for the command";
+        let env_help = "Set the environment variable KEY to VALUE for the command (can be given multiple times)";
+        let clear_env_help = "Run the command with an empty environment, other than TERM and any variables given by --env";
+        let cwd_help = "Directory to run the command in";
 
         app.arg(
             clap::Arg::with_name(COMMAND_OPTION)
@@ -698,7 +1834,29 @@ impl Command {
                 .multiple(true)
                 .help(args_help),
         )
+        .arg(
+            clap::Arg::with_name(ENV_OPTION)
+                .long(ENV_OPTION)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("KEY=VALUE")
+                .help(env_help),
+        )
+        .arg(
+            clap::Arg::with_name(CLEAR_ENV_OPTION)
+                .long(CLEAR_ENV_OPTION)
+                .help(clear_env_help),
+        )
+        .arg(
+            clap::Arg::with_name(CWD_OPTION)
+                .long(CWD_OPTION)
+                .takes_value(true)
+                .value_name("DIR")
+                .help(cwd_help),
+        )
     }
+
     pub fn merge_args<'a>(
         &mut self,
         matches: &clap::ArgMatches<'a>,
@@ -714,8 +1872,61 @@ impl Command {
                 .map(std::string::ToString::to_string)
                 .collect();
         }
+        if matches.is_present(ENV_OPTION) {
+            for env in matches.values_of(ENV_OPTION).unwrap() {
+                let mut parts = env.splitn(2, '=');
+                let key = parts.next().unwrap();
+                let value =
+                    parts.next().context(crate::error::InvalidEnv { env })?;
+                if key.is_empty() {
+                    return Err(Error::InvalidEnv {
+                        env: env.to_string(),
+                    });
+                }
+                self.env.insert(key.to_string(), value.to_string());
+            }
+        }
+        if matches.is_present(CLEAR_ENV_OPTION) {
+            self.clear_env = true;
+        }
+        if matches.is_present(CWD_OPTION) {
+            self.cwd =
+                Some(matches.value_of(CWD_OPTION).unwrap().to_string());
+        }
         Ok(())
     }
+
+    // the pty library can only run a command as is, inheriting our own
+    // environment and working directory, so the command is wrapped in
+    // env(1) (and a shell, to change directories) to set those up.
+    // TELETERM is always set, so that programs can tell that they are
+    // being streamed or recorded.
+    pub fn process_command(&self) -> (String, Vec<String>) {
+        let mut args = vec![];
+        if self.clear_env {
+            args.push("-i".to_string());
+        }
+        let term = std::env::var("TERM").ok().filter(|term| !term.is_empty());
+        if self.clear_env || term.is_none() {
+            let term = term.unwrap_or_else(|| DEFAULT_TERM.to_string());
+            args.push(format!("TERM={}", term));
+        }
+        args.push("TELETERM=1".to_string());
+        args.extend(
+            self.env
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value)),
+        );
+        if let Some(cwd) = &self.cwd {
+            args.push("sh".to_string());
+            args.push("-c".to_string());
+            args.push(r#"cd -- "$0" && exec "$@""#.to_string());
+            args.push(cwd.clone());
+        }
+        args.push(self.command.clone());
+        args.extend(self.args.iter().cloned());
+        ("env".to_string(), args)
+    }
 }
 
 impl Default for Command {
@@ -723,12 +1934,26 @@ impl Default for Command {
         Self {
             command: default_command(),
             args: default_args(),
+            env: std::collections::BTreeMap::new(),
+            clear_env: false,
+            cwd: None,
         }
     }
 }
 
 fn default_command() -> String {
-    std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())
+    if let Ok(shell) = std::env::var("SHELL") {
+        return shell;
+    }
+
+    // termux (on android) has no /bin, and instead installs everything
+    // under $PREFIX
+    let prefix = std::env::var("PREFIX").unwrap_or_else(|_| "".to_string());
+    ["bash", "sh"]
+        .iter()
+        .map(|shell| format!("{}/bin/{}", prefix, shell))
+        .find(|shell| std::path::Path::new(shell).exists())
+        .unwrap_or_else(|| "/bin/sh".to_string())
 }
 
 fn default_args() -> Vec<String> {
@@ -736,75 +1961,833 @@ fn default_args() -> Vec<String> {
 }
 
 #[derive(serde::Deserialize, Debug)]
-pub struct Ttyrec {
-    #[serde(default = "default_ttyrec_filename")]
-    pub filename: String,
-}
+pub struct Stream {
+    // deliberately only settable from the config file, so that someone
+    // sitting at a locked down terminal can't loosen it from the command
+    // line
+    #[serde(default)]
+    pub allowed_commands: Vec<String>,
 
-impl Ttyrec {
-    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
-        let filename_help =
-            "TTYrec file to use (defaults to teleterm.ttyrec)";
-        app.arg(
-            clap::Arg::with_name(FILENAME_OPTION)
-                .long(FILENAME_OPTION)
-                .takes_value(true)
-                .value_name("FILE")
-                .help(filename_help),
-        )
-    }
+    #[serde(default)]
+    pub allowed_watchers: Vec<String>,
 
-    pub fn merge_args<'a>(
-        &mut self,
-        matches: &clap::ArgMatches<'a>,
-    ) -> Result<()> {
-        if matches.is_present(FILENAME_OPTION) {
-            self.filename =
-                matches.value_of(FILENAME_OPTION).unwrap().to_string();
-        }
-        Ok(())
-    }
-}
+    #[serde(default)]
+    pub allow_control: bool,
 
-impl Default for Ttyrec {
-    fn default() -> Self {
-        Self {
-            filename: default_ttyrec_filename(),
-        }
-    }
-}
+    #[serde(default)]
+    pub description: String,
 
-fn default_ttyrec_filename() -> String {
-    DEFAULT_TTYREC_FILENAME.to_string()
-}
+    #[serde(default)]
+    pub from_file: Option<String>,
 
-#[derive(serde::Deserialize, Debug)]
-pub struct Play {
     #[serde(default)]
-    pub play_at_start: bool,
+    pub naming_command: Option<String>,
+
+    #[serde(default)]
+    pub no_remote: bool,
 
     #[serde(default = "default_playback_ratio")]
     pub playback_ratio: f32,
 
-    #[serde(default, deserialize_with = "max_frame_length")]
-    pub max_frame_length: Option<std::time::Duration>,
-}
+    #[serde(default)]
+    pub record_filename: Option<String>,
 
-impl Play {
-    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
-        let play_at_start_help = "Start the player unpaused";
-        let playback_ratio_help =
-            "Speed to play back the ttyrec at (defaults to 1.0)";
-        let max_frame_length_help =
-            "Clamp frame duration at this number of seconds";
-        app.arg(
-            clap::Arg::with_name(PLAY_AT_START_OPTION)
-                .long(PLAY_AT_START_OPTION)
-                .help(play_at_start_help),
+    #[serde(deserialize_with = "prefix_key", default = "default_prefix_key")]
+    pub prefix_key: u8,
+
+    #[serde(default)]
+    pub room: Option<String>,
+
+    #[serde(
+        deserialize_with = "send_queue_policy",
+        default = "default_send_queue_policy"
+    )]
+    pub send_queue_policy: crate::client::SendQueuePolicy,
+
+    #[serde(default = "default_send_queue_size")]
+    pub send_queue_size: usize,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    #[serde(default)]
+    pub tail: Option<String>,
+
+    #[serde(deserialize_with = "tail_colors", default)]
+    pub tail_colors: Vec<TailColor>,
+
+    #[serde(default)]
+    pub title: String,
+}
+
+impl Stream {
+    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+        let allow_help = "Only allow USERNAME to watch this stream (can be given multiple times, defaults to allowing everyone)";
+        let allow_control_help = "Allow watchers to request control of the terminal (requests must still be approved)";
+        let description_help =
+            "Free-form description of this stream, shown to watchers";
+        let from_file_help = "Stream the contents of the TTYrec file FILE instead of running a command";
+        let naming_command_help = "Run COMMAND periodically to choose the title and tags of this stream (see the README for details)";
+        let no_remote_help = "Don't connect to a server, just run (and optionally --record) the command locally";
+        let speed_help =
+            "Speed to stream the --from-file recording at (defaults to 1.0)";
+        let record_help =
+            "Also record the stream to the TTYrec file FILE while streaming";
+        let prefix_key_help = "Key to press before teleterm commands, like ^A or C-a (defaults to ^\\, press it twice to send it to the terminal)";
+        let room_help = "Room to list this stream in, so that watchers can find it along with the other streams in the same room";
+        let send_queue_policy_help = "What to do when the server can't keep up with the terminal output, one of drop-oldest (drop the oldest output and redraw the screen once the server catches up) or reconnect (defaults to drop-oldest)";
+        let send_queue_size_help = "Number of messages that can be waiting to be sent to the server before applying the --send-queue-policy (defaults to 1024)";
+        let tags_help = "Comma separated list of tags for this stream, which watchers can filter the list of streams by";
+        let tail_help = "Stream data as it is appended to FILE (like tail -f) instead of running a command";
+        let tail_color_help = "Color lines of the --tail file matching the regex PATTERN with COLOR, which is one of black, red, green, yellow, blue, magenta, cyan, or white (can be given multiple times, the first matching pattern is used)";
+        let title_help = "Title of this stream, shown to watchers (defaults to the title of the terminal)";
+        app.arg(
+            clap::Arg::with_name(ALLOW_OPTION)
+                .long(ALLOW_OPTION)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("USERNAME")
+                .help(allow_help),
+        )
+        .arg(
+            clap::Arg::with_name(ALLOW_CONTROL_OPTION)
+                .long(ALLOW_CONTROL_OPTION)
+                .help(allow_control_help),
+        )
+        .arg(
+            clap::Arg::with_name(DESCRIPTION_OPTION)
+                .long(DESCRIPTION_OPTION)
+                .takes_value(true)
+                .value_name("TEXT")
+                .help(description_help),
+        )
+        .arg(
+            clap::Arg::with_name(FROM_FILE_OPTION)
+                .long(FROM_FILE_OPTION)
+                .conflicts_with_all(&[
+                    COMMAND_OPTION,
+                    SERIAL_OPTION,
+                    TAIL_OPTION,
+                ])
+                .takes_value(true)
+                .value_name("FILE")
+                .help(from_file_help),
+        )
+        .arg(
+            clap::Arg::with_name(NAMING_COMMAND_OPTION)
+                .long(NAMING_COMMAND_OPTION)
+                .takes_value(true)
+                .value_name("COMMAND")
+                .help(naming_command_help),
+        )
+        .arg(
+            clap::Arg::with_name(NO_REMOTE_OPTION)
+                .long(NO_REMOTE_OPTION)
+                .help(no_remote_help),
+        )
+        .arg(
+            clap::Arg::with_name(SPEED_OPTION)
+                .long(SPEED_OPTION)
+                .takes_value(true)
+                .value_name("RATIO")
+                .help(speed_help),
+        )
+        .arg(
+            clap::Arg::with_name(RECORD_OPTION)
+                .long(RECORD_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .help(record_help),
+        )
+        .arg(
+            clap::Arg::with_name(PREFIX_KEY_OPTION)
+                .long(PREFIX_KEY_OPTION)
+                .takes_value(true)
+                .value_name("KEY")
+                .help(prefix_key_help),
+        )
+        .arg(
+            clap::Arg::with_name(ROOM_OPTION)
+                .long(ROOM_OPTION)
+                .takes_value(true)
+                .value_name("ROOM")
+                .help(room_help),
+        )
+        .arg(
+            clap::Arg::with_name(SEND_QUEUE_POLICY_OPTION)
+                .long(SEND_QUEUE_POLICY_OPTION)
+                .takes_value(true)
+                .value_name("POLICY")
+                .help(send_queue_policy_help),
+        )
+        .arg(
+            clap::Arg::with_name(SEND_QUEUE_SIZE_OPTION)
+                .long(SEND_QUEUE_SIZE_OPTION)
+                .takes_value(true)
+                .value_name("COUNT")
+                .help(send_queue_size_help),
+        )
+        .arg(
+            clap::Arg::with_name(TAGS_OPTION)
+                .long(TAGS_OPTION)
+                .use_delimiter(true)
+                .takes_value(true)
+                .value_name("TAGS")
+                .help(tags_help),
+        )
+        .arg(
+            clap::Arg::with_name(TAIL_OPTION)
+                .long(TAIL_OPTION)
+                .conflicts_with_all(&[COMMAND_OPTION, SERIAL_OPTION])
+                .takes_value(true)
+                .value_name("FILE")
+                .help(tail_help),
+        )
+        .arg(
+            clap::Arg::with_name(TAIL_COLOR_OPTION)
+                .long(TAIL_COLOR_OPTION)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PATTERN=COLOR")
+                .help(tail_color_help),
+        )
+        .arg(
+            clap::Arg::with_name(TITLE_OPTION)
+                .long(TITLE_OPTION)
+                .takes_value(true)
+                .value_name("TITLE")
+                .help(title_help),
+        )
+    }
+
+    pub fn merge_args<'a>(
+        &mut self,
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        if matches.is_present(ALLOW_OPTION) {
+            self.allowed_watchers = matches
+                .values_of(ALLOW_OPTION)
+                .unwrap()
+                .map(std::string::ToString::to_string)
+                .collect();
+        }
+        if matches.is_present(ALLOW_CONTROL_OPTION) {
+            self.allow_control = true;
+        }
+        if matches.is_present(DESCRIPTION_OPTION) {
+            self.description =
+                matches.value_of(DESCRIPTION_OPTION).unwrap().to_string();
+        }
+        if matches.is_present(FROM_FILE_OPTION) {
+            self.from_file =
+                Some(matches.value_of(FROM_FILE_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(NAMING_COMMAND_OPTION) {
+            self.naming_command = Some(
+                matches.value_of(NAMING_COMMAND_OPTION).unwrap().to_string(),
+            );
+        }
+        if matches.is_present(NO_REMOTE_OPTION) {
+            self.no_remote = true;
+        }
+        if matches.is_present(SPEED_OPTION) {
+            self.playback_ratio = matches
+                .value_of(SPEED_OPTION)
+                .unwrap()
+                .to_string()
+                .parse()
+                .context(crate::error::ParseFloat { name: SPEED_OPTION })?;
+        }
+        check_playback_ratio(self.playback_ratio)?;
+        if matches.is_present(RECORD_OPTION) {
+            self.record_filename =
+                Some(matches.value_of(RECORD_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(PREFIX_KEY_OPTION) {
+            self.prefix_key =
+                to_prefix_key(matches.value_of(PREFIX_KEY_OPTION).unwrap())?;
+        }
+        if matches.is_present(ROOM_OPTION) {
+            self.room =
+                Some(matches.value_of(ROOM_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(SEND_QUEUE_POLICY_OPTION) {
+            self.send_queue_policy =
+                crate::client::SendQueuePolicy::try_from(
+                    matches.value_of(SEND_QUEUE_POLICY_OPTION).unwrap(),
+                )?;
+        }
+        if matches.is_present(SEND_QUEUE_SIZE_OPTION) {
+            let s = matches.value_of(SEND_QUEUE_SIZE_OPTION).unwrap();
+            self.send_queue_size = s
+                .parse()
+                .context(crate::error::ParseSendQueueSize { input: s })?;
+        }
+        if matches.is_present(TAGS_OPTION) {
+            self.tags = matches
+                .values_of(TAGS_OPTION)
+                .unwrap()
+                .map(std::string::ToString::to_string)
+                .collect();
+        }
+        if matches.is_present(TAIL_OPTION) {
+            self.tail =
+                Some(matches.value_of(TAIL_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(TAIL_COLOR_OPTION) {
+            self.tail_colors = matches
+                .values_of(TAIL_COLOR_OPTION)
+                .unwrap()
+                .map(to_tail_color)
+                .collect::<Result<_>>()?;
+        }
+        if matches.is_present(TITLE_OPTION) {
+            self.title = matches.value_of(TITLE_OPTION).unwrap().to_string();
+        }
+        Ok(())
+    }
+}
+
+impl Default for Stream {
+    fn default() -> Self {
+        Self {
+            allowed_commands: vec![],
+            allowed_watchers: vec![],
+            allow_control: false,
+            description: String::new(),
+            from_file: None,
+            naming_command: None,
+            no_remote: false,
+            playback_ratio: default_playback_ratio(),
+            record_filename: None,
+            prefix_key: default_prefix_key(),
+            room: None,
+            send_queue_policy: default_send_queue_policy(),
+            send_queue_size: default_send_queue_size(),
+            tags: vec![],
+            tail: None,
+            tail_colors: vec![],
+            title: String::new(),
+        }
+    }
+}
+
+fn prefix_key<'a, D>(deserializer: D) -> std::result::Result<u8, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    to_prefix_key(&<String>::deserialize(deserializer)?)
+        .map_err(serde::de::Error::custom)
+}
+
+fn default_prefix_key() -> u8 {
+    DEFAULT_PREFIX_KEY
+}
+
+// accepts either a single ascii character, or a control key written as ^X
+// or C-x
+fn to_prefix_key(s: &str) -> Result<u8> {
+    let bytes = s.as_bytes();
+    let key = match bytes {
+        [c] if c.is_ascii() => Some(*c),
+        [b'^', b'?'] => Some(b'\x7f'),
+        [b'^', c] | [b'C', b'-', c] | [b'c', b'-', c] => {
+            let c = c.to_ascii_uppercase();
+            if (b'@'..=b'_').contains(&c) {
+                Some(c - b'@')
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    key.context(crate::error::InvalidPrefixKey { key: s })
+}
+
+// the inverse of to_prefix_key, for displaying in help messages
+pub fn prefix_key_name(key: u8) -> String {
+    match key {
+        b'\x7f' => "^?".to_string(),
+        c if c < b' ' => format!("^{}", char::from(c + b'@')),
+        c => char::from(c).to_string(),
+    }
+}
+
+fn escape<'a, D>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    to_escape(&<String>::deserialize(deserializer)?)
+        .map_err(serde::de::Error::custom)
+}
+
+fn default_escape() -> Vec<u8> {
+    DEFAULT_ESCAPE.to_vec()
+}
+
+fn default_detach_key() -> Vec<u8> {
+    DEFAULT_DETACH_KEY.to_vec()
+}
+
+// a sequence of keys in the same format as to_prefix_key, like ~. or ^X^C
+fn to_escape(s: &str) -> Result<Vec<u8>> {
+    let mut escape = vec![];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        let mut key = c.to_string();
+        if c == '^' {
+            if let Some(c) = chars.next() {
+                key.push(c);
+            }
+        }
+        let key = to_prefix_key(&key)
+            .ok()
+            .context(crate::error::InvalidEscape { escape: s })?;
+        escape.push(key);
+    }
+    if escape.is_empty() {
+        return Err(Error::InvalidEscape {
+            escape: s.to_string(),
+        });
+    }
+    Ok(escape)
+}
+
+// the inverse of to_escape, for displaying in help messages
+pub fn escape_name(escape: &[u8]) -> String {
+    escape.iter().copied().map(prefix_key_name).collect()
+}
+
+fn send_queue_policy<'a, D>(
+    deserializer: D,
+) -> std::result::Result<crate::client::SendQueuePolicy, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    crate::client::SendQueuePolicy::try_from(
+        <String>::deserialize(deserializer)?.as_ref(),
+    )
+    .map_err(serde::de::Error::custom)
+}
+
+fn default_send_queue_policy() -> crate::client::SendQueuePolicy {
+    DEFAULT_SEND_QUEUE_POLICY
+}
+
+fn default_send_queue_size() -> usize {
+    DEFAULT_SEND_QUEUE_SIZE
+}
+
+#[derive(Debug, Clone)]
+pub struct TailColor {
+    pub pattern: regex::bytes::Regex,
+    pub color: u8,
+}
+
+fn tail_colors<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<TailColor>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    <Vec<String>>::deserialize(deserializer)?
+        .iter()
+        .map(|s| to_tail_color(s).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+// rules are written as PATTERN=COLOR, and since the pattern is a regex which
+// can contain its own = characters, split on the last one
+fn to_tail_color(s: &str) -> Result<TailColor> {
+    let (pattern, color) = if let Some(i) = s.rfind('=') {
+        (&s[..i], &s[i + 1..])
+    } else {
+        return Err(Error::InvalidTailColor {
+            color: s.to_string(),
+        });
+    };
+    let color = match color {
+        "black" => 30,
+        "red" => 31,
+        "green" => 32,
+        "yellow" => 33,
+        "blue" => 34,
+        "magenta" => 35,
+        "cyan" => 36,
+        "white" => 37,
+        _ => {
+            return Err(Error::InvalidTailColor {
+                color: color.to_string(),
+            })
+        }
+    };
+    let pattern = regex::bytes::Regex::new(pattern).context(
+        crate::error::ParseTailPattern {
+            pattern: pattern.to_string(),
+        },
+    )?;
+    Ok(TailColor { pattern, color })
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Serial {
+    #[serde(default)]
+    pub device: Option<String>,
+
+    #[serde(default = "default_baud")]
+    pub baud: u32,
+
+    #[serde(deserialize_with = "parity", default = "default_parity")]
+    pub parity: tokio_serial::Parity,
+
+    #[serde(
+        deserialize_with = "flow_control",
+        default = "default_flow_control"
+    )]
+    pub flow_control: tokio_serial::FlowControl,
+}
+
+impl Serial {
+    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+        let serial_help =
+            "Stream the console on serial device DEVICE instead of running a command";
+        let baud_help =
+            "Baud rate for the serial device (defaults to 115200)";
+        let parity_help =
+            "Parity for the serial device, one of none, odd, even (defaults to none)";
+        let flow_control_help = "Flow control for the serial device, one of none, software, hardware (defaults to none)";
+        app.arg(
+            clap::Arg::with_name(SERIAL_OPTION)
+                .long(SERIAL_OPTION)
+                .conflicts_with(COMMAND_OPTION)
+                .takes_value(true)
+                .value_name("DEVICE")
+                .help(serial_help),
+        )
+        .arg(
+            clap::Arg::with_name(BAUD_OPTION)
+                .long(BAUD_OPTION)
+                .takes_value(true)
+                .value_name("RATE")
+                .help(baud_help),
+        )
+        .arg(
+            clap::Arg::with_name(PARITY_OPTION)
+                .long(PARITY_OPTION)
+                .takes_value(true)
+                .value_name("PARITY")
+                .help(parity_help),
+        )
+        .arg(
+            clap::Arg::with_name(FLOW_CONTROL_OPTION)
+                .long(FLOW_CONTROL_OPTION)
+                .takes_value(true)
+                .value_name("FLOW_CONTROL")
+                .help(flow_control_help),
+        )
+    }
+
+    pub fn merge_args<'a>(
+        &mut self,
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        if matches.is_present(SERIAL_OPTION) {
+            self.device =
+                Some(matches.value_of(SERIAL_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(BAUD_OPTION) {
+            self.baud = to_baud(matches.value_of(BAUD_OPTION).unwrap())?;
+        }
+        if matches.is_present(PARITY_OPTION) {
+            self.parity =
+                to_parity(matches.value_of(PARITY_OPTION).unwrap())?;
+        }
+        if matches.is_present(FLOW_CONTROL_OPTION) {
+            self.flow_control = to_flow_control(
+                matches.value_of(FLOW_CONTROL_OPTION).unwrap(),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn settings(&self) -> tokio_serial::SerialPortSettings {
+        tokio_serial::SerialPortSettings {
+            baud_rate: self.baud,
+            parity: self.parity,
+            flow_control: self.flow_control,
+            ..tokio_serial::SerialPortSettings::default()
+        }
+    }
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self {
+            device: None,
+            baud: default_baud(),
+            parity: default_parity(),
+            flow_control: default_flow_control(),
+        }
+    }
+}
+
+fn default_baud() -> u32 {
+    DEFAULT_BAUD
+}
+
+fn to_baud(s: &str) -> Result<u32> {
+    s.parse().context(crate::error::ParseBaud { input: s })
+}
+
+fn parity<'a, D>(
+    deserializer: D,
+) -> std::result::Result<tokio_serial::Parity, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    to_parity(&<String>::deserialize(deserializer)?)
+        .map_err(serde::de::Error::custom)
+}
+
+fn default_parity() -> tokio_serial::Parity {
+    DEFAULT_PARITY
+}
+
+fn to_parity(s: &str) -> Result<tokio_serial::Parity> {
+    match s {
+        "none" => Ok(tokio_serial::Parity::None),
+        "odd" => Ok(tokio_serial::Parity::Odd),
+        "even" => Ok(tokio_serial::Parity::Even),
+        _ => Err(Error::InvalidParity {
+            parity: s.to_string(),
+        }),
+    }
+}
+
+fn flow_control<'a, D>(
+    deserializer: D,
+) -> std::result::Result<tokio_serial::FlowControl, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    to_flow_control(&<String>::deserialize(deserializer)?)
+        .map_err(serde::de::Error::custom)
+}
+
+fn default_flow_control() -> tokio_serial::FlowControl {
+    DEFAULT_FLOW_CONTROL
+}
+
+fn to_flow_control(s: &str) -> Result<tokio_serial::FlowControl> {
+    match s {
+        "none" => Ok(tokio_serial::FlowControl::None),
+        "software" => Ok(tokio_serial::FlowControl::Software),
+        "hardware" => Ok(tokio_serial::FlowControl::Hardware),
+        _ => Err(Error::InvalidFlowControl {
+            flow_control: s.to_string(),
+        }),
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Ttyrec {
+    #[serde(default = "default_ttyrec_filename")]
+    pub filename: String,
+
+    #[serde(deserialize_with = "recording_format", default)]
+    pub format: Option<crate::recording::Format>,
+}
+
+impl Ttyrec {
+    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+        let filename_help =
+            "TTYrec file to use (defaults to teleterm.ttyrec)";
+        let format_help = "Format of the recording file. Valid values are ttyrec, native (the same as ttyrec), asciicast (defaults to ttyrec when recording, and to detecting the format from the file when playing)";
+        app.arg(
+            clap::Arg::with_name(FILENAME_OPTION)
+                .long(FILENAME_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .help(filename_help),
+        )
+        .arg(
+            clap::Arg::with_name(FORMAT_OPTION)
+                .long(FORMAT_OPTION)
+                .takes_value(true)
+                .value_name("FORMAT")
+                .help(format_help),
+        )
+    }
+
+    pub fn merge_args<'a>(
+        &mut self,
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        if matches.is_present(FILENAME_OPTION) {
+            self.filename =
+                matches.value_of(FILENAME_OPTION).unwrap().to_string();
+        }
+        if matches.is_present(FORMAT_OPTION) {
+            self.format = Some(crate::recording::Format::try_from(
+                matches.value_of(FORMAT_OPTION).unwrap(),
+            )?);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Ttyrec {
+    fn default() -> Self {
+        Self {
+            filename: default_ttyrec_filename(),
+            format: None,
+        }
+    }
+}
+
+fn default_ttyrec_filename() -> String {
+    DEFAULT_TTYREC_FILENAME.to_string()
+}
+
+fn recording_format<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Option<crate::recording::Format>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    crate::recording::Format::try_from(
+        <String>::deserialize(deserializer)?.as_ref(),
+    )
+    .map(Some)
+    .map_err(serde::de::Error::custom)
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct Record {
+    #[serde(default)]
+    pub input_filename: Option<String>,
+
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+
+    #[serde(default)]
+    pub rotate: bool,
+
+    #[serde(default)]
+    pub gzip: bool,
+}
+
+impl Record {
+    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+        let record_input_help = "Also record typed input to FILE, as a separate TTYrec file with matching timestamps";
+        let max_file_size_help = "Stop recording once the file reaches this many bytes (or start a new file, with --rotate)";
+        let rotate_help = "When the file reaches --max-file-size, continue recording in a new numbered file (teleterm.2.ttyrec, and so on)";
+        let gzip_help =
+            "Compress each file with gzip once --rotate has moved on from it";
+        app.arg(
+            clap::Arg::with_name(RECORD_INPUT_OPTION)
+                .long(RECORD_INPUT_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .help(record_input_help),
+        )
+        .arg(
+            clap::Arg::with_name(MAX_FILE_SIZE_OPTION)
+                .long(MAX_FILE_SIZE_OPTION)
+                .takes_value(true)
+                .value_name("BYTES")
+                .help(max_file_size_help),
+        )
+        .arg(
+            clap::Arg::with_name(ROTATE_OPTION)
+                .long(ROTATE_OPTION)
+                .requires(MAX_FILE_SIZE_OPTION)
+                .help(rotate_help),
+        )
+        .arg(
+            clap::Arg::with_name(GZIP_OPTION)
+                .long(GZIP_OPTION)
+                .requires(ROTATE_OPTION)
+                .help(gzip_help),
+        )
+    }
+
+    pub fn merge_args<'a>(
+        &mut self,
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        if matches.is_present(RECORD_INPUT_OPTION) {
+            self.input_filename = Some(
+                matches.value_of(RECORD_INPUT_OPTION).unwrap().to_string(),
+            );
+        }
+        if matches.is_present(MAX_FILE_SIZE_OPTION) {
+            let s = matches.value_of(MAX_FILE_SIZE_OPTION).unwrap();
+            let max = s
+                .parse()
+                .context(crate::error::ParseMaxFileSize { input: s })?;
+            // zero means that there is no limit
+            self.max_file_size = if max == 0 { None } else { Some(max) };
+        }
+        if matches.is_present(ROTATE_OPTION) {
+            self.rotate = true;
+        }
+        if matches.is_present(GZIP_OPTION) {
+            self.gzip = true;
+        }
+        Ok(())
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Play {
+    #[serde(default)]
+    pub play_at_start: bool,
+
+    #[serde(default = "default_playback_ratio")]
+    pub playback_ratio: f32,
+
+    #[serde(default, deserialize_with = "max_frame_length")]
+    pub max_frame_length: Option<std::time::Duration>,
+
+    #[serde(default)]
+    pub stream: bool,
+
+    #[serde(skip)]
+    pub export_html: Option<String>,
+
+    #[serde(skip)]
+    pub export_text: Option<String>,
+
+    #[serde(default)]
+    pub export_scrollback: bool,
+
+    #[serde(deserialize_with = "size", default = "default_export_size")]
+    pub export_size: crate::term::Size,
+}
+
+impl Play {
+    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+        let play_at_start_help = "Start the player unpaused";
+        let playback_ratio_help =
+            "Speed to play back the ttyrec at (defaults to 1.0)";
+        let max_frame_length_help = "Compress idle periods longer than this number of seconds down to that length (can be toggled with 'i' while playing)";
+        let stream_help = "Also stream the playback to the server, so that others can watch along";
+        let export_html_help = "Write the screen at the end of the recording to FILE as HTML, rather than playing it";
+        let export_text_help = "Write the screen at the end of the recording to FILE as plain text, rather than playing it";
+        let export_scrollback_help = "Also export everything that scrolled off the top of the screen during the recording";
+        let export_size_help = "Size of the terminal to play the recording into when exporting, as COLSxROWS (defaults to 80x24)";
+        app.arg(
+            clap::Arg::with_name(PLAY_AT_START_OPTION)
+                .long(PLAY_AT_START_OPTION)
+                .help(play_at_start_help),
         )
         .arg(
             clap::Arg::with_name(PLAYBACK_RATIO_OPTION)
                 .long(PLAYBACK_RATIO_OPTION)
+                .visible_alias(SPEED_OPTION)
                 .takes_value(true)
                 .value_name("RATIO")
                 .help(playback_ratio_help),
@@ -812,10 +2795,48 @@ impl Play {
         .arg(
             clap::Arg::with_name(MAX_FRAME_LENGTH_OPTION)
                 .long(MAX_FRAME_LENGTH_OPTION)
+                .visible_alias(MAX_IDLE_OPTION)
                 .takes_value(true)
                 .value_name("SECS")
                 .help(max_frame_length_help),
         )
+        .arg(
+            clap::Arg::with_name(STREAM_OPTION)
+                .long(STREAM_OPTION)
+                .help(stream_help),
+        )
+        .arg(
+            clap::Arg::with_name(EXPORT_HTML_OPTION)
+                .long(EXPORT_HTML_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with(STREAM_OPTION)
+                .help(export_html_help),
+        )
+        .arg(
+            clap::Arg::with_name(EXPORT_TEXT_OPTION)
+                .long(EXPORT_TEXT_OPTION)
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with(STREAM_OPTION)
+                .help(export_text_help),
+        )
+        .arg(
+            clap::Arg::with_name(EXPORT_SCROLLBACK_OPTION)
+                .long(EXPORT_SCROLLBACK_OPTION)
+                .help(export_scrollback_help),
+        )
+        .arg(
+            clap::Arg::with_name(EXPORT_SIZE_OPTION)
+                .long(EXPORT_SIZE_OPTION)
+                .takes_value(true)
+                .value_name("COLSxROWS")
+                .help(export_size_help),
+        )
+    }
+
+    pub fn exporting(&self) -> bool {
+        self.export_html.is_some() || self.export_text.is_some()
     }
 
     pub fn merge_args<'a>(
@@ -833,11 +2854,37 @@ impl Play {
                     name: PLAYBACK_RATIO_OPTION,
                 })?;
         }
+        check_playback_ratio(self.playback_ratio)?;
         self.max_frame_length = matches
             .value_of(MAX_FRAME_LENGTH_OPTION)
-            .map(|len| len.parse().map(std::time::Duration::from_secs))
+            .map(|len| {
+                // allow "2s" as well as "2", to match --max-idle 2s
+                len.trim_end_matches('s')
+                    .parse()
+                    .map(std::time::Duration::from_secs)
+            })
             .transpose()
             .context(crate::error::ParseMaxFrameLength)?;
+        if matches.is_present(STREAM_OPTION) {
+            self.stream = true;
+        }
+        if matches.is_present(EXPORT_HTML_OPTION) {
+            self.export_html = Some(
+                matches.value_of(EXPORT_HTML_OPTION).unwrap().to_string(),
+            );
+        }
+        if matches.is_present(EXPORT_TEXT_OPTION) {
+            self.export_text = Some(
+                matches.value_of(EXPORT_TEXT_OPTION).unwrap().to_string(),
+            );
+        }
+        if matches.is_present(EXPORT_SCROLLBACK_OPTION) {
+            self.export_scrollback = true;
+        }
+        if matches.is_present(EXPORT_SIZE_OPTION) {
+            self.export_size =
+                to_size(matches.value_of(EXPORT_SIZE_OPTION).unwrap())?;
+        }
         Ok(())
     }
 }
@@ -848,10 +2895,24 @@ impl Default for Play {
             play_at_start: false,
             playback_ratio: default_playback_ratio(),
             max_frame_length: None,
+            stream: false,
+            export_html: None,
+            export_text: None,
+            export_scrollback: false,
+            export_size: default_export_size(),
         }
     }
 }
 
+// the time between frames is divided by this
+fn check_playback_ratio(ratio: f32) -> Result<()> {
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(())
+    } else {
+        Err(Error::InvalidSpeed { speed: ratio })
+    }
+}
+
 fn default_playback_ratio() -> f32 {
     1.0
 }
@@ -867,6 +2928,395 @@ where
     )?)))
 }
 
+#[derive(serde::Deserialize, Debug)]
+pub struct Watch {
+    #[serde(default)]
+    pub history: bool,
+
+    #[serde(
+        deserialize_with = "session_list_columns",
+        default = "default_session_list_columns"
+    )]
+    pub columns: Vec<crate::session_list::Column>,
+
+    #[serde(
+        rename = "jitter_buffer_ms",
+        deserialize_with = "jitter_buffer",
+        default
+    )]
+    pub jitter_buffer: std::time::Duration,
+
+    #[serde(
+        rename = "refresh_interval_secs",
+        deserialize_with = "refresh_interval",
+        default = "default_refresh_interval"
+    )]
+    pub refresh_interval: std::time::Duration,
+
+    pub follow: Option<String>,
+
+    pub tag: Option<String>,
+
+    pub room: Option<String>,
+
+    pub time_zone: Option<String>,
+
+    #[serde(deserialize_with = "render_mode", default)]
+    pub render: crate::render::Mode,
+
+    #[serde(default)]
+    pub interactive: bool,
+
+    #[serde(deserialize_with = "escape", default = "default_escape")]
+    pub escape: Vec<u8>,
+
+    #[serde(deserialize_with = "escape", default = "default_detach_key")]
+    pub detach_key: Vec<u8>,
+}
+
+impl Watch {
+    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+        let history_help = "List the streams which have recently ended, rather than watching one";
+        let columns_help = "Comma separated list of the columns to show in the list of streams, in order. Valid values are name, size, idle, watch, uptime, started (defaults to name,size,idle,watch,uptime)";
+        let jitter_buffer_help = "Hold back watched output by up to MS milliseconds to smooth out network jitter. Higher values are smoother, lower values stay closer to live (defaults to 0, which disables the buffer)";
+        let refresh_interval_help = "Number of seconds between automatic refreshes of the list of streams (defaults to 5, 0 disables automatic refreshes)";
+        let follow_help = "Wait for USERNAME to start streaming, and then ring the bell and start watching them";
+        let tag_help = "Only list streams which have been tagged with TAG";
+        let room_help = "Only list the streams in ROOM, rather than starting from the list of rooms";
+        let time_zone_help = "Time zone to show times in, in the same format as the TZ environment variable (defaults to the local time zone)";
+        let render_help = "How to show the stream being watched. Valid values are terminal, text-diff (defaults to terminal)";
+        let interactive_help = "Ask the streamer for control as soon as you start watching, and type into their terminal once they grant it. Press the --escape keys to stop and go back to the list of streams";
+        let escape_help = "Keys to press to give control back to the streamer, like ~. or ^] (each key is a single character, or a control key written as ^X). Everything else typed while in control is sent to the streamer's terminal (defaults to ^\\)";
+        let detach_key_help = "Keys to press while watching a stream to stop watching it and go back to the list of streams, in the same format as --escape (defaults to ^\\)";
+        app.arg(
+            clap::Arg::with_name(SHOW_HISTORY_OPTION)
+                .long(SHOW_HISTORY_OPTION)
+                .help(history_help),
+        )
+        .arg(
+            clap::Arg::with_name(COLUMNS_OPTION)
+                .long(COLUMNS_OPTION)
+                .use_delimiter(true)
+                .takes_value(true)
+                .value_name("COLUMNS")
+                .help(columns_help),
+        )
+        .arg(
+            clap::Arg::with_name(JITTER_BUFFER_OPTION)
+                .long(JITTER_BUFFER_OPTION)
+                .takes_value(true)
+                .value_name("MS")
+                .help(jitter_buffer_help),
+        )
+        .arg(
+            clap::Arg::with_name(REFRESH_INTERVAL_OPTION)
+                .long(REFRESH_INTERVAL_OPTION)
+                .takes_value(true)
+                .value_name("SECS")
+                .help(refresh_interval_help),
+        )
+        .arg(
+            clap::Arg::with_name(FOLLOW_OPTION)
+                .long(FOLLOW_OPTION)
+                .takes_value(true)
+                .value_name("USERNAME")
+                .help(follow_help),
+        )
+        .arg(
+            clap::Arg::with_name(TAG_OPTION)
+                .long(TAG_OPTION)
+                .takes_value(true)
+                .value_name("TAG")
+                .help(tag_help),
+        )
+        .arg(
+            clap::Arg::with_name(ROOM_OPTION)
+                .long(ROOM_OPTION)
+                .takes_value(true)
+                .value_name("ROOM")
+                .help(room_help),
+        )
+        .arg(
+            clap::Arg::with_name(TIME_ZONE_OPTION)
+                .long(TIME_ZONE_OPTION)
+                .takes_value(true)
+                .value_name("TZ")
+                .help(time_zone_help),
+        )
+        .arg(
+            clap::Arg::with_name(RENDER_OPTION)
+                .long(RENDER_OPTION)
+                .takes_value(true)
+                .value_name("MODE")
+                .help(render_help),
+        )
+        .arg(
+            clap::Arg::with_name(INTERACTIVE_OPTION)
+                .long(INTERACTIVE_OPTION)
+                .help(interactive_help),
+        )
+        .arg(
+            clap::Arg::with_name(ESCAPE_OPTION)
+                .long(ESCAPE_OPTION)
+                .takes_value(true)
+                .value_name("KEYS")
+                .help(escape_help),
+        )
+        .arg(
+            clap::Arg::with_name(DETACH_KEY_OPTION)
+                .long(DETACH_KEY_OPTION)
+                .takes_value(true)
+                .value_name("KEYS")
+                .help(detach_key_help),
+        )
+    }
+
+    pub fn merge_args<'a>(
+        &mut self,
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        if matches.is_present(SHOW_HISTORY_OPTION) {
+            self.history = true;
+        }
+        if matches.is_present(COLUMNS_OPTION) {
+            self.columns = matches
+                .values_of(COLUMNS_OPTION)
+                .unwrap()
+                .map(crate::session_list::Column::try_from)
+                .collect::<Result<_>>()?;
+        }
+        if matches.is_present(JITTER_BUFFER_OPTION) {
+            self.jitter_buffer = parse_jitter_buffer(
+                matches.value_of(JITTER_BUFFER_OPTION).unwrap(),
+            )?;
+        }
+        if matches.is_present(REFRESH_INTERVAL_OPTION) {
+            let s = matches.value_of(REFRESH_INTERVAL_OPTION).unwrap();
+            self.refresh_interval = s
+                .parse()
+                .map(std::time::Duration::from_secs)
+                .context(crate::error::ParseRefreshInterval { input: s })?;
+        }
+        if matches.is_present(FOLLOW_OPTION) {
+            self.follow =
+                Some(matches.value_of(FOLLOW_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(TAG_OPTION) {
+            self.tag =
+                Some(matches.value_of(TAG_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(ROOM_OPTION) {
+            self.room =
+                Some(matches.value_of(ROOM_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(TIME_ZONE_OPTION) {
+            self.time_zone =
+                Some(matches.value_of(TIME_ZONE_OPTION).unwrap().to_string());
+        }
+        if matches.is_present(RENDER_OPTION) {
+            self.render = crate::render::Mode::try_from(
+                matches.value_of(RENDER_OPTION).unwrap(),
+            )?;
+        }
+        if matches.is_present(INTERACTIVE_OPTION) {
+            self.interactive = true;
+        }
+        if matches.is_present(ESCAPE_OPTION) {
+            self.escape =
+                to_escape(matches.value_of(ESCAPE_OPTION).unwrap())?;
+        }
+        if matches.is_present(DETACH_KEY_OPTION) {
+            self.detach_key =
+                to_escape(matches.value_of(DETACH_KEY_OPTION).unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Watch {
+    fn default() -> Self {
+        Self {
+            history: false,
+            columns: default_session_list_columns(),
+            jitter_buffer: std::time::Duration::default(),
+            refresh_interval: default_refresh_interval(),
+            follow: None,
+            tag: None,
+            room: None,
+            time_zone: None,
+            render: crate::render::Mode::default(),
+            interactive: false,
+            escape: default_escape(),
+            detach_key: default_detach_key(),
+        }
+    }
+}
+
+fn render_mode<'a, D>(
+    deserializer: D,
+) -> std::result::Result<crate::render::Mode, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    crate::render::Mode::try_from(
+        <String>::deserialize(deserializer)?.as_ref(),
+    )
+    .map_err(serde::de::Error::custom)
+}
+
+fn session_list_columns<'a, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<crate::session_list::Column>, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    <Vec<String>>::deserialize(deserializer)?
+        .iter()
+        .map(|s| {
+            crate::session_list::Column::try_from(s.as_str())
+                .map_err(serde::de::Error::custom)
+        })
+        .collect()
+}
+
+fn default_session_list_columns() -> Vec<crate::session_list::Column> {
+    DEFAULT_SESSION_LIST_COLUMNS.to_vec()
+}
+
+fn parse_jitter_buffer(s: &str) -> Result<std::time::Duration> {
+    s.parse()
+        .map(std::time::Duration::from_millis)
+        .context(crate::error::ParseJitterBuffer { input: s })
+}
+
+fn jitter_buffer<'a, D>(
+    deserializer: D,
+) -> std::result::Result<std::time::Duration, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    Ok(std::time::Duration::from_millis(u64::deserialize(
+        deserializer,
+    )?))
+}
+
+fn refresh_interval<'a, D>(
+    deserializer: D,
+) -> std::result::Result<std::time::Duration, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    Ok(std::time::Duration::from_secs(u64::deserialize(
+        deserializer,
+    )?))
+}
+
+fn default_refresh_interval() -> std::time::Duration {
+    DEFAULT_REFRESH_INTERVAL
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Diff {
+    #[serde(skip)]
+    pub old_filename: String,
+
+    #[serde(skip)]
+    pub new_filename: String,
+
+    #[serde(deserialize_with = "size", default = "default_diff_size")]
+    pub size: crate::term::Size,
+}
+
+impl Diff {
+    pub fn cmd<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+        let old_file_help = "TTYrec file with the expected output";
+        let new_file_help = "TTYrec file to compare against it";
+        let size_help = "Size of the terminal to play the recordings back in, as COLSxROWS (defaults to 80x24)";
+        app.arg(
+            clap::Arg::with_name(OLD_FILE_OPTION)
+                .index(1)
+                .required(true)
+                .help(old_file_help),
+        )
+        .arg(
+            clap::Arg::with_name(NEW_FILE_OPTION)
+                .index(2)
+                .required(true)
+                .help(new_file_help),
+        )
+        .arg(
+            clap::Arg::with_name(SIZE_OPTION)
+                .long(SIZE_OPTION)
+                .takes_value(true)
+                .value_name("COLSxROWS")
+                .help(size_help),
+        )
+    }
+
+    pub fn merge_args<'a>(
+        &mut self,
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<()> {
+        if matches.is_present(OLD_FILE_OPTION) {
+            self.old_filename =
+                matches.value_of(OLD_FILE_OPTION).unwrap().to_string();
+        }
+        if matches.is_present(NEW_FILE_OPTION) {
+            self.new_filename =
+                matches.value_of(NEW_FILE_OPTION).unwrap().to_string();
+        }
+        if matches.is_present(SIZE_OPTION) {
+            self.size = to_size(matches.value_of(SIZE_OPTION).unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Diff {
+    fn default() -> Self {
+        Self {
+            old_filename: String::new(),
+            new_filename: String::new(),
+            size: default_diff_size(),
+        }
+    }
+}
+
+fn size<'a, D>(
+    deserializer: D,
+) -> std::result::Result<crate::term::Size, D::Error>
+where
+    D: serde::de::Deserializer<'a>,
+{
+    to_size(&<String>::deserialize(deserializer)?)
+        .map_err(serde::de::Error::custom)
+}
+
+fn default_diff_size() -> crate::term::Size {
+    DEFAULT_DIFF_SIZE
+}
+
+// recordings don't say what size of terminal they were made in, so this is
+// the same guess that tt diff makes
+fn default_export_size() -> crate::term::Size {
+    DEFAULT_DIFF_SIZE
+}
+
+// the same format that sizes are displayed in, like 80x24
+fn to_size(s: &str) -> Result<crate::term::Size> {
+    let mut parts = s.splitn(2, 'x');
+    let cols = parts.next().and_then(|cols| cols.parse::<u16>().ok());
+    let rows = parts.next().and_then(|rows| rows.parse::<u16>().ok());
+    if let (Some(cols), Some(rows)) = (cols, rows) {
+        if cols > 0 && rows > 0 {
+            return Ok(crate::term::Size { rows, cols });
+        }
+    }
+    Err(Error::InvalidSize {
+        size: s.to_string(),
+    })
+}
+
 pub fn oauth_configs<'a, D>(
     deserializer: D,
 ) -> std::result::Result<
@@ -896,7 +3346,9 @@ where
                 crate::protocol::AuthClient::try_from(key.as_str())
                     .map_err(serde::de::Error::custom)?;
             let real_config = match auth_type {
-                crate::protocol::AuthType::RecurseCenter => {
+                crate::protocol::AuthType::RecurseCenter
+                | crate::protocol::AuthType::Github
+                | crate::protocol::AuthType::Google => {
                     let client_id = config
                         .client_id
                         .context(crate::error::OauthMissingConfiguration {
@@ -919,11 +3371,19 @@ where
                     let redirect_url =
                         url::Url::parse(crate::oauth::CLI_REDIRECT_URL)
                             .unwrap();
-                    crate::auth::recurse_center::oauth_config(
-                        &client_id,
-                        &client_secret,
-                        &redirect_url,
-                    )
+                    let oauth_config = match auth_type {
+                        crate::protocol::AuthType::RecurseCenter => {
+                            crate::auth::recurse_center::oauth_config
+                        }
+                        crate::protocol::AuthType::Github => {
+                            crate::auth::github::oauth_config
+                        }
+                        crate::protocol::AuthType::Google => {
+                            crate::auth::google::oauth_config
+                        }
+                        _ => unreachable!(),
+                    };
+                    oauth_config(&client_id, &client_secret, &redirect_url)
                 }
                 ty if !ty.is_oauth() => {
                     return Err(Error::AuthTypeNotOauth { ty: auth_type })
@@ -967,3 +3427,46 @@ where
         .transpose()
         .map_err(serde::de::Error::custom)?)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_baud() {
+        assert_eq!(to_baud("115200").unwrap(), 115_200);
+        assert_eq!(to_baud("9600").unwrap(), 9600);
+        assert!(to_baud("").is_err());
+        assert!(to_baud("fast").is_err());
+        assert!(to_baud("-9600").is_err());
+        assert!(to_baud("99999999999").is_err());
+    }
+
+    #[test]
+    fn test_to_parity() {
+        assert_eq!(to_parity("none").unwrap(), tokio_serial::Parity::None);
+        assert_eq!(to_parity("odd").unwrap(), tokio_serial::Parity::Odd);
+        assert_eq!(to_parity("even").unwrap(), tokio_serial::Parity::Even);
+        assert!(to_parity("").is_err());
+        assert!(to_parity("Odd").is_err());
+        assert!(to_parity("mark").is_err());
+    }
+
+    #[test]
+    fn test_to_flow_control() {
+        assert_eq!(
+            to_flow_control("none").unwrap(),
+            tokio_serial::FlowControl::None
+        );
+        assert_eq!(
+            to_flow_control("software").unwrap(),
+            tokio_serial::FlowControl::Software
+        );
+        assert_eq!(
+            to_flow_control("hardware").unwrap(),
+            tokio_serial::FlowControl::Hardware
+        );
+        assert!(to_flow_control("").is_err());
+        assert!(to_flow_control("xon").is_err());
+    }
+}
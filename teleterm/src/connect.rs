@@ -0,0 +1,284 @@
+use crate::prelude::*;
+use std::io::{Read as _, Write as _};
+use std::net::ToSocketAddrs as _;
+
+// which local address connections to the server should be made from, for
+// hosts on more than one network (a vpn alongside a lan, for instance)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Any,
+    Address(std::net::IpAddr),
+    // looked up again on every connection attempt, since interfaces like
+    // vpn tunnels can change their address when they reconnect
+    Interface(String),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Self::Any
+    }
+}
+
+// everything needed to make a connection to a server, which is cloned into
+// each client that needs its own connection. connections use tls if a
+// connector is given, and otherwise check the server's host key before
+// handing the connection off.
+#[derive(Clone)]
+pub struct Connector {
+    host: String,
+    address: std::net::SocketAddr,
+    source: Source,
+    timeout: Option<std::time::Duration>,
+    tls: Option<native_tls::TlsConnector>,
+}
+
+impl Connector {
+    pub fn new(
+        host: &str,
+        address: std::net::SocketAddr,
+        source: Source,
+        timeout: Option<std::time::Duration>,
+        tls: Option<native_tls::TlsConnector>,
+    ) -> Self {
+        Self {
+            host: host.to_string(),
+            address,
+            source,
+            timeout,
+            tls,
+        }
+    }
+
+    pub fn connect(
+        &self,
+    ) -> Box<dyn futures::Future<Item = Stream, Error = Error> + Send> {
+        let tcp = tcp(&self.host, self.address, &self.source, self.timeout);
+        if let Some(connector) = &self.tls {
+            let connector = tokio_tls::TlsConnector::from(connector.clone());
+            let host = self.host.clone();
+            Box::new(tcp.and_then(move |stream| {
+                connector
+                    .connect(&host, stream)
+                    .context(crate::error::ConnectTls { host })
+                    .map(Stream::Tls)
+            }))
+        } else {
+            let server_name =
+                crate::config::server_name(&self.host, &self.address);
+            Box::new(tcp.and_then(move |stream| {
+                crate::host_key::verify(stream, &server_name).map(Stream::Tcp)
+            }))
+        }
+    }
+}
+
+pub enum Stream {
+    Tcp(tokio::net::tcp::TcpStream),
+    Tls(tokio_tls::TlsStream<tokio::net::tcp::TcpStream>),
+}
+
+impl std::io::Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            Self::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl std::io::Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+impl tokio::io::AsyncRead for Stream {}
+
+impl tokio::io::AsyncWrite for Stream {
+    fn shutdown(&mut self) -> futures::Poll<(), std::io::Error> {
+        match self {
+            Self::Tcp(stream) => tokio::io::AsyncWrite::shutdown(stream),
+            Self::Tls(stream) => tokio::io::AsyncWrite::shutdown(stream),
+        }
+    }
+}
+
+// the host is looked up again for every connection, so that clients which
+// stay running for a long time follow the server to a new address (for dns
+// based failover, for instance). `address` is what it resolved to when the
+// configuration was read, which is used if the lookup fails.
+pub fn tcp(
+    host: &str,
+    address: std::net::SocketAddr,
+    source: &Source,
+    timeout: Option<std::time::Duration>,
+) -> Box<
+    dyn futures::Future<Item = tokio::net::tcp::TcpStream, Error = Error>
+        + Send,
+> {
+    let source = source.clone();
+    let connect = resolve(host, address)
+        .and_then(move |address| connect_from(address, &source));
+    let timeout = if let Some(timeout) = timeout {
+        timeout
+    } else {
+        return Box::new(connect);
+    };
+
+    // a host which drops our packets (or a dns server which never answers)
+    // can otherwise leave us waiting for the operating system to give up,
+    // which can take minutes
+    Box::new(
+        tokio::timer::Timeout::new(connect, timeout).map_err(move |e| {
+            if e.is_elapsed() {
+                Error::ConnectTimeout {
+                    address,
+                    secs: timeout.as_secs(),
+                }
+            } else if e.is_inner() {
+                e.into_inner().unwrap()
+            } else {
+                Error::TimerConnect {
+                    source: e.into_timer().unwrap(),
+                }
+            }
+        }),
+    )
+}
+
+// the system resolver only has a blocking interface, so the lookup is run
+// as a blocking section of the threadpool, which moves the other tasks off
+// of this thread while it waits. if we aren't running on the threadpool,
+// there is nothing to hand the other tasks off to, so just block.
+fn resolve(
+    host: &str,
+    address: std::net::SocketAddr,
+) -> impl futures::Future<Item = std::net::SocketAddr, Error = Error> {
+    let host = host.to_string();
+    futures::future::poll_fn(move || {
+        tokio_threadpool::blocking(|| lookup(&host, address))
+            .or_else(|_| Ok(futures::Async::Ready(lookup(&host, address))))
+    })
+}
+
+fn lookup(host: &str, address: std::net::SocketAddr) -> std::net::SocketAddr {
+    match (host, address.port()).to_socket_addrs() {
+        Ok(mut addrs) => addrs.next().unwrap_or(address),
+        Err(e) => {
+            log::warn!(
+                "failed to resolve {}, using {} instead: {}",
+                host,
+                address,
+                e
+            );
+            address
+        }
+    }
+}
+
+fn connect_from(
+    address: std::net::SocketAddr,
+    source: &Source,
+) -> Box<
+    dyn futures::Future<Item = tokio::net::tcp::TcpStream, Error = Error>
+        + Send,
+> {
+    let local = match source {
+        Source::Any => {
+            return Box::new(
+                tokio::net::tcp::TcpStream::connect(&address)
+                    .context(crate::error::Connect { address }),
+            )
+        }
+        Source::Address(local) => *local,
+        Source::Interface(name) => {
+            match interface_address(name, address.is_ipv4()) {
+                Ok(local) => local,
+                Err(e) => return Box::new(futures::future::err(e)),
+            }
+        }
+    };
+
+    let builder = if address.is_ipv4() {
+        net2::TcpBuilder::new_v4()
+    } else {
+        net2::TcpBuilder::new_v6()
+    };
+    let stream = builder
+        .and_then(|builder| {
+            builder.bind((local, 0))?;
+            builder.to_tcp_stream()
+        })
+        .context(crate::error::BindSourceAddress { address: local });
+    let stream = match stream {
+        Ok(stream) => stream,
+        Err(e) => return Box::new(futures::future::err(e)),
+    };
+    Box::new(
+        tokio::net::tcp::TcpStream::connect_std(
+            stream,
+            &address,
+            &tokio::reactor::Handle::default(),
+        )
+        .context(crate::error::Connect { address }),
+    )
+}
+
+// the sockaddr returned by getifaddrs is really a sockaddr_in or
+// sockaddr_in6 (as indicated by its family), which is correctly aligned
+#[allow(clippy::cast_ptr_alignment)]
+fn interface_address(name: &str, ipv4: bool) -> Result<std::net::IpAddr> {
+    let mut addrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } == -1 {
+        return Err(std::io::Error::last_os_error())
+            .context(crate::error::GetInterfaceAddresses);
+    }
+
+    let mut found = None;
+    let mut cur = addrs;
+    while !cur.is_null() {
+        let ifa = unsafe { &*cur };
+        cur = ifa.ifa_next;
+
+        if ifa.ifa_addr.is_null() {
+            continue;
+        }
+        let ifa_name = unsafe { std::ffi::CStr::from_ptr(ifa.ifa_name) };
+        if ifa_name.to_bytes() != name.as_bytes() {
+            continue;
+        }
+
+        let family = i32::from(unsafe { (*ifa.ifa_addr).sa_family });
+        if ipv4 && family == libc::AF_INET {
+            let addr =
+                unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in) };
+            found = Some(std::net::IpAddr::V4(std::net::Ipv4Addr::from(
+                u32::from_be(addr.sin_addr.s_addr),
+            )));
+            break;
+        } else if !ipv4 && family == libc::AF_INET6 {
+            let addr =
+                unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in6) };
+            found = Some(std::net::IpAddr::V6(std::net::Ipv6Addr::from(
+                addr.sin6_addr.s6_addr,
+            )));
+            break;
+        }
+    }
+    unsafe { libc::freeifaddrs(addrs) };
+
+    found.context(crate::error::InterfaceAddressNotFound {
+        interface: name,
+        family: if ipv4 { "ipv4" } else { "ipv6" },
+    })
+}
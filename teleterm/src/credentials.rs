@@ -0,0 +1,99 @@
+use crate::prelude::*;
+use std::io::{Read as _, Seek as _, Write as _};
+use std::os::unix::fs::{OpenOptionsExt as _, PermissionsExt as _};
+use std::os::unix::io::AsRawFd as _;
+
+// the cached ids are enough to log in as the user they belong to, so they
+// shouldn't be readable by anyone else
+const CREDENTIAL_FILE_MODE: u32 = 0o600;
+
+// returns the id that the server knows our oauth login by, if we have
+// logged in with this auth type before
+pub fn load(auth: crate::protocol::AuthType) -> Option<String> {
+    let filename = credential_file(auth, true)?;
+    let mut file = std::fs::File::open(&filename).ok()?;
+    lock(&file, &filename, libc::LOCK_SH).ok()?;
+    let mut id = vec![];
+    file.read_to_end(&mut id).ok()?;
+    let id = std::string::String::from_utf8_lossy(&id).trim().to_string();
+    if id.is_empty() {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+pub fn save(auth: crate::protocol::AuthType, id: &str) -> Result<()> {
+    let filename = credential_file(auth, false).unwrap();
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .mode(CREDENTIAL_FILE_MODE)
+        .open(&filename)
+        .with_context(|| crate::error::CreateFileSync {
+            filename: filename.to_string_lossy().to_string(),
+        })?;
+    // the mode is only used when the file is created, so fix up files left
+    // behind by older versions
+    file.set_permissions(std::fs::Permissions::from_mode(
+        CREDENTIAL_FILE_MODE,
+    ))
+    .with_context(|| crate::error::SetPermissions {
+        filename: filename.to_string_lossy().to_string(),
+    })?;
+    // don't truncate until we hold the lock, so that a concurrent load
+    // never sees a partially written id
+    lock(&file, &filename, libc::LOCK_EX)?;
+    file.set_len(0).context(crate::error::WriteFileSync)?;
+    file.seek(std::io::SeekFrom::Start(0))
+        .context(crate::error::WriteFileSync)?;
+    file.write_all(id.as_bytes())
+        .context(crate::error::WriteFileSync)?;
+    Ok(())
+}
+
+// forgets the id for this auth type, returning whether there was one
+pub fn clear(auth: crate::protocol::AuthType) -> Result<bool> {
+    let filename = if let Some(filename) = credential_file(auth, true) {
+        filename
+    } else {
+        return Ok(false);
+    };
+    let file = std::fs::File::open(&filename).with_context(|| {
+        crate::error::OpenFileSync {
+            filename: filename.to_string_lossy().to_string(),
+        }
+    })?;
+    // wait for anything currently writing it to finish
+    lock(&file, &filename, libc::LOCK_EX)?;
+    std::fs::remove_file(&filename).with_context(|| {
+        crate::error::RemoveFile {
+            filename: filename.to_string_lossy().to_string(),
+        }
+    })?;
+    Ok(true)
+}
+
+// the lock is released when the file is closed
+fn lock(
+    file: &std::fs::File,
+    filename: &std::path::Path,
+    operation: libc::c_int,
+) -> Result<()> {
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } == -1 {
+        return Err(std::io::Error::last_os_error()).with_context(|| {
+            crate::error::LockFile {
+                filename: filename.to_string_lossy().to_string(),
+            }
+        });
+    }
+    Ok(())
+}
+
+fn credential_file(
+    auth: crate::protocol::AuthType,
+    must_exist: bool,
+) -> Option<std::path::PathBuf> {
+    let filename = format!("client-oauth-{}", auth.name());
+    crate::dirs::Dirs::new().data_file(&filename, must_exist)
+}
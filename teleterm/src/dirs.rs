@@ -1,4 +1,9 @@
 use crate::prelude::*;
+use std::os::unix::fs::{DirBuilderExt as _, PermissionsExt as _};
+
+// the cache holds copies of other people's screens, so it shouldn't be
+// readable by anyone else
+const CACHE_DIR_MODE: u32 = 0o700;
 
 pub struct Dirs {
     project_dirs: Option<directories::ProjectDirs>,
@@ -19,6 +24,24 @@ impl Dirs {
                 }
             })?;
         }
+        if let Some(filename) = self.cache_dir() {
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(CACHE_DIR_MODE)
+                .create(filename)
+                .with_context(|| crate::error::CreateDir {
+                    filename: filename.to_string_lossy(),
+                })?;
+            // the mode is only used when the directory is created, so fix up
+            // directories left behind by older versions
+            std::fs::set_permissions(
+                filename,
+                std::fs::Permissions::from_mode(CACHE_DIR_MODE),
+            )
+            .with_context(|| crate::error::SetPermissions {
+                filename: filename.to_string_lossy(),
+            })?;
+        }
         Ok(())
     }
 
@@ -95,4 +118,38 @@ impl Dirs {
 
         None
     }
+
+    fn global_cache_dir(&self) -> &std::path::Path {
+        std::path::Path::new("/var/cache/teleterm")
+    }
+
+    fn cache_dir(&self) -> Option<&std::path::Path> {
+        if self.has_home() {
+            self.project_dirs
+                .as_ref()
+                .map(directories::ProjectDirs::cache_dir)
+        } else {
+            None
+        }
+    }
+
+    pub fn cache_file(
+        &self,
+        name: &str,
+        must_exist: bool,
+    ) -> Option<std::path::PathBuf> {
+        if let Some(cache_dir) = self.cache_dir() {
+            let file = cache_dir.join(name);
+            if !must_exist || file.exists() {
+                return Some(file);
+            }
+        }
+
+        let file = self.global_cache_dir().join(name);
+        if !must_exist || file.exists() {
+            return Some(file);
+        }
+
+        None
+    }
 }
@@ -16,12 +16,46 @@ pub enum Error {
     #[snafu(display("auth type {:?} does not use oauth", ty))]
     AuthTypeNotOauth { ty: crate::protocol::AuthType },
 
+    #[snafu(display("banned from this server"))]
+    Banned,
+
     #[snafu(display("failed to bind to {}: {}", address, source))]
     Bind {
         address: std::net::SocketAddr,
         source: tokio::io::Error,
     },
 
+    #[snafu(display(
+        "failed to bind to source address {}: {}",
+        address,
+        source
+    ))]
+    BindSourceAddress {
+        address: std::net::IpAddr,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("failed to change directory: {}", source))]
+    Chdir { source: std::io::Error },
+
+    #[snafu(display(
+        "client protocol version {} is too old (need {}), please upgrade",
+        version,
+        min
+    ))]
+    ClientProtocolTooOld { version: u8, min: u8 },
+
+    #[snafu(display(
+        "environment variables can't be set when allowed_commands is set"
+    ))]
+    CommandEnvNotAllowed,
+
+    #[snafu(display(
+        "command {} is not in the allowed_commands list",
+        command
+    ))]
+    CommandNotAllowed { command: String },
+
     #[snafu(display("config file {} doesn't exist", name))]
     ConfigFileDoesntExist { name: String },
 
@@ -32,7 +66,17 @@ pub enum Error {
     },
 
     #[snafu(display(
-        "failed to make tls connection to {}: {}",
+        "timed out connecting to {} after {} seconds",
+        address,
+        secs
+    ))]
+    ConnectTimeout {
+        address: std::net::SocketAddr,
+        secs: u64,
+    },
+
+    #[snafu(display(
+        "failed to make tls connection to {}: {} (see --tls-ca)",
         host,
         source
     ))]
@@ -41,6 +85,9 @@ pub enum Error {
         source: native_tls::Error,
     },
 
+    #[snafu(display("couldn't find a place to store the known servers"))]
+    CouldntFindKnownServersFile,
+
     #[snafu(display("couldn't determine the current username"))]
     CouldntFindUsername,
 
@@ -74,6 +121,9 @@ pub enum Error {
     #[snafu(display("received EOF from server"))]
     EOF,
 
+    #[snafu(display("failed to enable mouse reporting: {}", source))]
+    EnableMouse { source: crossterm::ErrorKind },
+
     #[snafu(display(
         "failed to retrieve access token from authorization code: {:?}",
         msg
@@ -113,6 +163,24 @@ pub enum Error {
     #[snafu(display("failed to write to stdout: {}", source))]
     FlushTerminal { source: tokio::io::Error },
 
+    #[snafu(display("failed to fork: {}", source))]
+    Fork { source: std::io::Error },
+
+    #[snafu(display("message frame too big (at most {} bytes)", max))]
+    FrameTooBig { max: usize },
+
+    #[snafu(display("failed to generate host key"))]
+    GenerateHostKey,
+
+    #[snafu(display("failed to get github profile data: {}", source))]
+    GetGithubProfile { source: reqwest::Error },
+
+    #[snafu(display("failed to get google profile data: {}", source))]
+    GetGoogleProfile { source: reqwest::Error },
+
+    #[snafu(display("failed to list network interfaces: {}", source))]
+    GetInterfaceAddresses { source: std::io::Error },
+
     #[snafu(display(
         "failed to get recurse center profile data: {}",
         source
@@ -122,9 +190,47 @@ pub enum Error {
     #[snafu(display("failed to get terminal size: {}", source))]
     GetTerminalSize { source: crossterm::ErrorKind },
 
+    #[snafu(display("the hardened profile requires {}", requirement))]
+    HardenedProfile { requirement: String },
+
     #[snafu(display("failed to find any resolvable addresses"))]
     HasResolvedAddr,
 
+    #[snafu(display(
+        "host key for {} doesn't match the known servers file (now {})",
+        host,
+        fingerprint
+    ))]
+    HostKeyChanged { host: String, fingerprint: String },
+
+    #[snafu(display(
+        "{} no longer presents the host key in the known servers file",
+        host
+    ))]
+    HostKeyMissing { host: String },
+
+    #[snafu(display(
+        "couldn't find an {} address for network interface {}",
+        family,
+        interface
+    ))]
+    InterfaceAddressNotFound {
+        interface: String,
+        family: &'static str,
+    },
+
+    #[snafu(display(
+        "invalid admin {} (expected AUTH_METHOD:USERNAME)",
+        admin
+    ))]
+    InvalidAdmin { admin: String },
+
+    #[snafu(display(
+        "invalid filter {} (expected user:NAME, tag:TAG, or address:ADDR)",
+        filter
+    ))]
+    InvalidAdminFilter { filter: String },
+
     #[snafu(display("invalid auth client {}", ty))]
     InvalidAuthClient { ty: u8 },
 
@@ -137,12 +243,66 @@ pub enum Error {
     #[snafu(display("invalid auth type {}", ty))]
     InvalidAuthTypeStr { ty: String },
 
+    #[snafu(display(
+        "invalid environment variable {} (expected KEY=VALUE)",
+        env
+    ))]
+    InvalidEnv { env: String },
+
+    #[snafu(display("invalid key sequence {}", escape))]
+    InvalidEscape { escape: String },
+
+    #[snafu(display("invalid flow control setting {}", flow_control))]
+    InvalidFlowControl { flow_control: String },
+
+    #[snafu(display("{} sent an invalid host key signature", host))]
+    InvalidHostKeySignature { host: String },
+
     #[snafu(display("invalid message type {}", ty))]
     InvalidMessageType { ty: u8 },
 
+    #[snafu(display("oauth login did not come from this session"))]
+    InvalidOauthState,
+
+    #[snafu(display("invalid parity setting {}", parity))]
+    InvalidParity { parity: String },
+
+    #[snafu(display("invalid prefix key {}", key))]
+    InvalidPrefixKey { key: String },
+
+    #[snafu(display("invalid profile {}", profile))]
+    InvalidProfile { profile: String },
+
+    #[snafu(display("invalid recording format {}", format))]
+    InvalidRecordingFormat { format: String },
+
+    #[snafu(display("invalid render mode {}", mode))]
+    InvalidRenderMode { mode: String },
+
+    #[snafu(display("invalid send queue policy {}", policy))]
+    InvalidSendQueuePolicy { policy: String },
+
+    #[snafu(display("invalid session list column {}", column))]
+    InvalidSessionListColumn { column: String },
+
+    #[snafu(display("invalid terminal size {}", size))]
+    InvalidSize { size: String },
+
+    #[snafu(display("invalid playback speed {} (must be positive)", speed))]
+    InvalidSpeed { speed: f32 },
+
+    #[snafu(display("invalid color {}", color))]
+    InvalidTailColor { color: String },
+
+    #[snafu(display("invalid connection id {}", id))]
+    InvalidConnectionId { id: String },
+
     #[snafu(display("invalid watch id {}", id))]
     InvalidWatchId { id: String },
 
+    #[snafu(display("kicked by a server administrator: {}", reason))]
+    Kicked { reason: String },
+
     #[snafu(display(
         "packet length must be at least {} bytes (got {})",
         expected,
@@ -157,15 +317,46 @@ pub enum Error {
     ))]
     LenTooBig { len: u32, expected: usize },
 
+    #[snafu(display("failed to lock file {}: {}", filename, source))]
+    LockFile {
+        filename: String,
+        source: std::io::Error,
+    },
+
     #[snafu(display("couldn't find name in argv"))]
     MissingArgv,
 
+    #[snafu(display("no oauth access token to log in with"))]
+    MissingOauthAccessToken,
+
+    #[snafu(display("naming command exited with {}", status))]
+    NamingCommandFailed { status: std::process::ExitStatus },
+
+    #[snafu(display("failed to spawn naming command thread: {}", source))]
+    NamingCommandThread { source: std::io::Error },
+
+    #[snafu(display("naming command thread died"))]
+    NamingCommandThreadDied,
+
+    #[snafu(display("naming command took longer than {} seconds", secs))]
+    NamingCommandTimeout { secs: u64 },
+
     #[snafu(display(
         "detected argv path {} was not a valid filename",
         path
     ))]
     NotAFileName { path: String },
 
+    #[snafu(display("{} is not a server administrator", username))]
+    NotAdmin { username: String },
+
+    #[snafu(display(
+        "{} is not allowed to watch {}'s session",
+        username,
+        streamer
+    ))]
+    NotAllowedToWatch { username: String, streamer: String },
+
     #[snafu(display(
         "missing oauth configuration item {} for section oauth.{}.{}",
         field,
@@ -190,9 +381,18 @@ pub enum Error {
         source: std::io::Error,
     },
 
+    #[snafu(display("failed to open serial device {}: {}", device, source))]
+    OpenSerial {
+        device: String,
+        source: std::io::Error,
+    },
+
     #[snafu(display("failed to open link in browser: {}", source))]
     OpenLink { source: std::io::Error },
 
+    #[snafu(display("timed out {} after {} seconds", operation, secs))]
+    OperationTimeout { operation: String, secs: u64 },
+
     #[snafu(display("failed to parse address"))]
     ParseAddress,
 
@@ -202,15 +402,57 @@ pub enum Error {
     #[snafu(display("{}", source))]
     ParseArgs { source: clap::Error },
 
+    #[snafu(display("failed to parse asciicast file: {}", source))]
+    ParseAsciicast { source: serde_json::Error },
+
+    #[snafu(display("failed to parse baud rate {}: {}", input, source))]
+    ParseBaud {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
     #[snafu(display("failed to parse buffer size {}: {}", input, source))]
     ParseBufferSize {
         input: String,
         source: std::num::ParseIntError,
     },
 
+    #[snafu(display(
+        "failed to parse certificate file {}: {}",
+        filename,
+        source
+    ))]
+    ParseCertificate {
+        filename: String,
+        source: native_tls::Error,
+    },
+
     #[snafu(display("failed to parse config file: {}", source))]
     ParseConfigFile { source: config::ConfigError },
 
+    #[snafu(display(
+        "failed to parse disconnect grace period {}: {}",
+        input,
+        source
+    ))]
+    ParseDisconnectGrace {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display(
+        "failed to parse history window {}: {}",
+        input,
+        source
+    ))]
+    ParseHistory {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display("failed to parse host key in {}", filename))]
+    ParseHostKey { filename: String },
+
     #[snafu(display("failed to parse incoming http request"))]
     ParseHttpRequest,
 
@@ -249,12 +491,65 @@ pub enum Error {
         source: std::num::ParseFloatError,
     },
 
+    #[snafu(display(
+        "failed to parse jitter buffer length {}: {}",
+        input,
+        source
+    ))]
+    ParseJitterBuffer {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
     #[snafu(display("failed to parse response json: {}", source))]
     ParseJson { source: reqwest::Error },
 
+    #[snafu(display(
+        "failed to parse maximum connections {}: {}",
+        input,
+        source
+    ))]
+    ParseMaxConnections {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display(
+        "failed to parse maximum file size {}: {}",
+        input,
+        source
+    ))]
+    ParseMaxFileSize {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
     #[snafu(display("failed to parse max frame length: {}", source))]
     ParseMaxFrameLength { source: std::num::ParseIntError },
 
+    #[snafu(display(
+        "failed to parse maximum stream length {}: {}",
+        input,
+        source
+    ))]
+    ParseMaxStream {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display(
+        "failed to parse maximum watchers per session {}: {}",
+        input,
+        source
+    ))]
+    ParseMaxWatchers {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display("failed to parse naming command output: {}", source))]
+    ParseNamingOutput { source: serde_json::Error },
+
     #[snafu(display(
         "failed to parse port {} from address: {}",
         string,
@@ -265,18 +560,77 @@ pub enum Error {
         source: std::num::ParseIntError,
     },
 
+    #[snafu(display(
+        "failed to parse quarantine duration {}: {}",
+        input,
+        source
+    ))]
+    ParseQuarantine {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
     #[snafu(display("failed to parse read timeout {}: {}", input, source))]
     ParseReadTimeout {
         input: String,
         source: std::num::ParseIntError,
     },
 
+    #[snafu(display(
+        "failed to parse refresh interval {}: {}",
+        input,
+        source
+    ))]
+    ParseRefreshInterval {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display(
+        "failed to parse send queue size {}: {}",
+        input,
+        source
+    ))]
+    ParseSendQueueSize {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display(
+        "failed to parse stream warning time {}: {}",
+        input,
+        source
+    ))]
+    ParseStreamWarning {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
     #[snafu(display("failed to parse string {:?}: {}", string, source))]
     ParseString {
         string: Vec<u8>,
         source: std::string::FromUtf8Error,
     },
 
+    #[snafu(display("failed to parse pattern {}: {}", pattern, source))]
+    ParseTailPattern {
+        pattern: String,
+        source: regex::Error,
+    },
+
+    #[snafu(display("failed to parse timeout {}: {}", input, source))]
+    ParseTimeout {
+        input: String,
+        source: std::num::ParseIntError,
+    },
+
+    #[snafu(display(
+        "incompatible protocol version {} (expected {})",
+        version,
+        expected
+    ))]
+    ProtocolVersionMismatch { version: u8, expected: u8 },
+
     #[snafu(display("rate limit exceeded"))]
     RateLimited,
 
@@ -300,6 +654,9 @@ pub enum Error {
     #[snafu(display("failed to read packet: {}", source))]
     ReadPacket { source: tokio::io::Error },
 
+    #[snafu(display("failed to read from serial device: {}", source))]
+    ReadSerial { source: tokio::io::Error },
+
     #[snafu(display("failed to read from socket: {}", source))]
     ReadSocket { source: tokio::io::Error },
 
@@ -309,11 +666,23 @@ pub enum Error {
     #[snafu(display("failed to read ttyrec: {}", source))]
     ReadTtyrec { source: ttyrec::Error },
 
+    #[snafu(display("failed to redirect output: {}", source))]
+    RedirectFd { source: std::io::Error },
+
+    #[snafu(display("failed to remove file {}: {}", filename, source))]
+    RemoveFile {
+        filename: String,
+        source: std::io::Error,
+    },
+
     #[snafu(display("failed to poll for terminal resizing: {}", source))]
     Resize {
         source: tokio_terminal_resize::Error,
     },
 
+    #[snafu(display("failed to poll for terminal resizing: stream ended"))]
+    ResizeEnded,
+
     #[snafu(display(
         "failed to resolve address {}:{}: {}",
         host,
@@ -326,18 +695,63 @@ pub enum Error {
         source: std::io::Error,
     },
 
+    #[snafu(display(
+        "failed to run naming command {}: {}",
+        command,
+        source
+    ))]
+    RunNamingCommand {
+        command: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("failed to seek in file: {}", source))]
+    SeekFile { source: std::io::Error },
+
+    #[snafu(display("more than {} messages waiting to be sent", size))]
+    SendQueueFull { size: usize },
+
     #[snafu(display("failed to serialize message as json: {}", source))]
     SerializeMessage { source: serde_json::Error },
 
     #[snafu(display("received error from server: {}", message))]
     Server { message: String },
 
+    #[snafu(display(
+        "server protocol version {} is too old (need {}), please upgrade it",
+        version,
+        min
+    ))]
+    ServerProtocolTooOld { version: u8, min: u8 },
+
     #[snafu(display("couldn't connect to server"))]
     ServerDisconnected,
 
+    #[snafu(display("the server doesn't support {}", feature))]
+    ServerMissingCapability { feature: String },
+
+    #[snafu(display("server shut down: {}", reason))]
+    ServerShutdown { reason: String },
+
+    #[snafu(display(
+        "failed to set permissions on file {}: {}",
+        filename,
+        source
+    ))]
+    SetPermissions {
+        filename: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("failed to start a new session: {}", source))]
+    Setsid { source: std::io::Error },
+
     #[snafu(display("SIGWINCH handler failed: {}", source))]
     SigWinchHandler { source: std::io::Error },
 
+    #[snafu(display("failed to listen for signals: {}", source))]
+    Signal { source: std::io::Error },
+
     #[snafu(display("failed to sleep until next frame: {}", source))]
     Sleep { source: tokio::timer::Error },
 
@@ -354,6 +768,16 @@ pub enum Error {
         source: tokio::sync::mpsc::error::RecvError,
     },
 
+    #[snafu(display("stream reached the time limit of {} seconds", secs))]
+    StreamTimeLimit { secs: u64 },
+
+    #[snafu(display("{} too long ({} bytes, at most {})", field, len, max))]
+    StringTooLong {
+        field: String,
+        len: usize,
+        max: usize,
+    },
+
     #[snafu(display("poll subprocess failed: {}", source))]
     Subprocess {
         source: tokio_pty_process_stream::Error,
@@ -380,15 +804,63 @@ pub enum Error {
     #[snafu(display("timeout"))]
     Timeout,
 
+    #[snafu(display("ban file timer failed: {}", source))]
+    TimerBanFile { source: tokio::timer::Error },
+
+    #[snafu(display("catchup timer failed: {}", source))]
+    TimerCatchup { source: tokio::timer::Error },
+
+    #[snafu(display("connect timer failed: {}", source))]
+    TimerConnect { source: tokio::timer::Error },
+
+    #[snafu(display("escape timer failed: {}", source))]
+    TimerEscape { source: tokio::timer::Error },
+
     #[snafu(display("heartbeat timer failed: {}", source))]
     TimerHeartbeat { source: tokio::timer::Error },
 
+    #[snafu(display("jitter buffer timer failed: {}", source))]
+    TimerJitterBuffer { source: tokio::timer::Error },
+
+    #[snafu(display("naming command timer failed: {}", source))]
+    TimerNaming { source: tokio::timer::Error },
+
+    #[snafu(display("operation timeout timer failed: {}", source))]
+    TimerOperation { source: tokio::timer::Error },
+
+    #[snafu(display("output batching timer failed: {}", source))]
+    TimerOutputBatch { source: tokio::timer::Error },
+
     #[snafu(display("read timeout timer failed: {}", source))]
     TimerReadTimeout { source: tokio::timer::Error },
 
     #[snafu(display("reconnect timer failed: {}", source))]
     TimerReconnect { source: tokio::timer::Error },
 
+    #[snafu(display("session list refresh timer failed: {}", source))]
+    TimerRefresh { source: tokio::timer::Error },
+
+    #[snafu(display("replay timer failed: {}", source))]
+    TimerReplay { source: tokio::timer::Error },
+
+    #[snafu(display("session list timer failed: {}", source))]
+    TimerSessionList { source: tokio::timer::Error },
+
+    #[snafu(display("stats timer failed: {}", source))]
+    TimerStats { source: tokio::timer::Error },
+
+    #[snafu(display("status line timer failed: {}", source))]
+    TimerStatusLine { source: tokio::timer::Error },
+
+    #[snafu(display("tail timer failed: {}", source))]
+    TimerTail { source: tokio::timer::Error },
+
+    #[snafu(display("time limit timer failed: {}", source))]
+    TimerTimeLimit { source: tokio::timer::Error },
+
+    #[snafu(display("watcher count timer failed: {}", source))]
+    TimerWatcherCount { source: tokio::timer::Error },
+
     #[snafu(display("failed to switch to alternate screen: {}", source))]
     ToAlternateScreen { source: crossterm::ErrorKind },
 
@@ -398,6 +870,25 @@ pub enum Error {
     ))]
     ToRawMode { source: crossterm::ErrorKind },
 
+    #[snafu(display(
+        "the server is full (it allows {} connections), try again later",
+        max
+    ))]
+    TooManyConnections { max: u32 },
+
+    #[snafu(display("too many {} ({}, at most {})", field, len, max))]
+    TooManyItems {
+        field: String,
+        len: usize,
+        max: usize,
+    },
+
+    #[snafu(display(
+        "this stream already has the maximum of {} watchers, try again later",
+        max
+    ))]
+    TooManyWatchers { max: u32 },
+
     #[snafu(display("unauthenticated message: {:?}", message))]
     UnauthenticatedMessage { message: crate::protocol::Message },
 
@@ -416,6 +907,9 @@ pub enum Error {
     #[snafu(display("failed to find user with username {}", name))]
     UnknownUser { name: String },
 
+    #[snafu(display("unsupported asciicast version {}", version))]
+    UnsupportedAsciicastVersion { version: u32 },
+
     #[snafu(display("failure during websocket stream: {}", source))]
     WebSocket {
         source: tokio_tungstenite::tungstenite::Error,
@@ -439,6 +933,15 @@ pub enum Error {
     #[snafu(display("failed to write packet: {}", source))]
     WritePacket { source: tokio::io::Error },
 
+    #[snafu(display("failed to write pid file {}: {}", filename, source))]
+    WritePidFile {
+        filename: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("failed to write to serial device: {}", source))]
+    WriteSerial { source: tokio::io::Error },
+
     #[snafu(display("failed to write to socket: {}", source))]
     WriteSocket { source: tokio::io::Error },
 
@@ -450,6 +953,9 @@ pub enum Error {
 
     #[snafu(display("failed to write ttyrec: {}", source))]
     WriteTtyrec { source: ttyrec::Error },
+
+    #[snafu(display("{} is not allowed on this address", action))]
+    WrongListener { action: String },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
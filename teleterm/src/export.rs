@@ -0,0 +1,263 @@
+// turns a terminal that a recording was played into into something which
+// can be shared, rather than replayed. this is either just the final screen,
+// or everything which scrolled off the top of it first as well.
+
+// how many lines which scrolled off the top of the screen are kept, when
+// exporting them too
+pub const SCROLLBACK_LINES: usize = 100_000;
+
+const DEFAULT_FGCOLOR: &str = "#d3d3d3";
+const DEFAULT_BGCOLOR: &str = "#000000";
+
+pub fn text(parser: &mut vt100::Parser, scrollback: bool) -> String {
+    let mut out = String::new();
+    for row in rows(parser, scrollback) {
+        let line: String =
+            cell_text(&row).into_iter().map(|(_, text)| text).collect();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+pub fn html(
+    parser: &mut vt100::Parser,
+    scrollback: bool,
+    title: &str,
+) -> String {
+    let mut out = "<!DOCTYPE html>\n<html>\n<head>\n".to_string();
+    out.push_str("<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", escape(title)));
+    out.push_str(&format!(
+        "<style>\nbody {{ background: {}; color: {}; }}\n</style>\n",
+        DEFAULT_BGCOLOR, DEFAULT_FGCOLOR
+    ));
+    out.push_str("</head>\n<body>\n<pre>\n");
+    for row in rows(parser, scrollback) {
+        // cells next to each other with the same attributes are drawn as a
+        // single span
+        let mut spans: Vec<(String, String)> = vec![];
+        for (cell, text) in cell_text(&row) {
+            let style = style(cell);
+            match spans.last_mut() {
+                Some((last_style, last_text)) if *last_style == style => {
+                    last_text.push_str(&text);
+                }
+                _ => spans.push((style, text)),
+            }
+        }
+        if let Some((style, text)) = spans.last_mut() {
+            if style.is_empty() {
+                *text = text.trim_end().to_string();
+            }
+        }
+
+        for (style, text) in spans {
+            if style.is_empty() {
+                out.push_str(&escape(&text));
+            } else {
+                out.push_str(&format!(
+                    "<span style=\"{}\">{}</span>",
+                    style,
+                    escape(&text)
+                ));
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
+
+// the cells of each row, starting with the scrollback if requested, and
+// leaving off blank rows at the end
+fn rows(
+    parser: &mut vt100::Parser,
+    scrollback: bool,
+) -> Vec<Vec<vt100::Cell>> {
+    let (rows, cols) = parser.screen().size();
+    let scrollback_len = if scrollback {
+        // this is limited to the lines which are actually there
+        parser.set_scrollback(std::usize::MAX);
+        parser.screen().scrollback()
+    } else {
+        0
+    };
+
+    // vt100 only shows the scrollback a screen at a time, so this pages
+    // down through it from the top
+    let mut lines: Vec<Vec<vt100::Cell>> = vec![];
+    while lines.len() < scrollback_len + usize::from(rows) {
+        let start = lines.len();
+        let offset = scrollback_len.saturating_sub(start);
+        parser.set_scrollback(offset);
+        // the line that is shown in the top row
+        let first = scrollback_len - offset;
+        let screen = parser.screen();
+        for row in 0..rows {
+            if first + usize::from(row) < start {
+                continue;
+            }
+            lines.push(
+                (0..cols)
+                    .filter_map(|col| screen.cell(row, col).cloned())
+                    .collect(),
+            );
+        }
+    }
+    parser.set_scrollback(0);
+
+    while lines.last().map_or(false, |row| {
+        row.iter().all(|cell| cell.contents().trim().is_empty())
+    }) {
+        lines.pop();
+    }
+    lines
+}
+
+// the text of each cell of the row, leaving out the second half of wide
+// characters
+fn cell_text(row: &[vt100::Cell]) -> Vec<(&vt100::Cell, String)> {
+    let mut out = vec![];
+    let mut skip = false;
+    for cell in row {
+        if skip {
+            skip = false;
+            continue;
+        }
+        skip = cell.is_wide();
+        let contents = cell.contents();
+        if contents.is_empty() {
+            out.push((cell, " ".to_string()));
+        } else {
+            out.push((cell, contents));
+        }
+    }
+    out
+}
+
+fn style(cell: &vt100::Cell) -> String {
+    let mut fgcolor = color(cell.fgcolor(), cell.bold());
+    let mut bgcolor = color(cell.bgcolor(), false);
+    if cell.inverse() {
+        let inverted_fgcolor =
+            bgcolor.unwrap_or_else(|| DEFAULT_BGCOLOR.to_string());
+        let inverted_bgcolor =
+            fgcolor.unwrap_or_else(|| DEFAULT_FGCOLOR.to_string());
+        fgcolor = Some(inverted_fgcolor);
+        bgcolor = Some(inverted_bgcolor);
+    }
+
+    let mut styles = vec![];
+    if let Some(fgcolor) = fgcolor {
+        styles.push(format!("color: {}", fgcolor));
+    }
+    if let Some(bgcolor) = bgcolor {
+        styles.push(format!("background: {}", bgcolor));
+    }
+    if cell.bold() {
+        styles.push("font-weight: bold".to_string());
+    }
+    if cell.italic() {
+        styles.push("font-style: italic".to_string());
+    }
+    if cell.underline() {
+        styles.push("text-decoration: underline".to_string());
+    }
+    styles.join("; ")
+}
+
+fn color(color: vt100::Color, bright: bool) -> Option<String> {
+    match color {
+        vt100::Color::Default => None,
+        vt100::Color::Idx(idx) if idx < 8 && bright => {
+            Some(indexed_color(idx + 8))
+        }
+        vt100::Color::Idx(idx) => Some(indexed_color(idx)),
+        vt100::Color::Rgb(r, g, b) => Some(rgb(r, g, b)),
+    }
+}
+
+// the standard xterm palette
+fn indexed_color(idx: u8) -> String {
+    const BASIC: [&str; 16] = [
+        "#000000", "#800000", "#008000", "#808000", "#000080", "#800080",
+        "#008080", "#c0c0c0", "#808080", "#ff0000", "#00ff00", "#ffff00",
+        "#0000ff", "#ff00ff", "#00ffff", "#ffffff",
+    ];
+    if idx < 16 {
+        return BASIC[usize::from(idx)].to_string();
+    }
+    if idx < 232 {
+        let idx = idx - 16;
+        let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+        return rgb(level(idx / 36), level(idx / 6 % 6), level(idx % 6));
+    }
+    let gray = 8 + (idx - 232) * 10;
+    rgb(gray, gray, gray)
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            // recordings can contain anything
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let mut parser = vt100::Parser::new(3, 10, SCROLLBACK_LINES);
+        parser.process(b"one\r\ntwo\r\nthree\r\nfour\r\n\x1b[31mfive");
+        assert_eq!(text(&mut parser, false), "three\nfour\nfive\n");
+        assert_eq!(text(&mut parser, true), "one\ntwo\nthree\nfour\nfive\n");
+        // exporting doesn't leave the parser scrolled back
+        assert_eq!(parser.screen().scrollback(), 0);
+
+        let mut parser = vt100::Parser::new(24, 80, SCROLLBACK_LINES);
+        parser.process(b"hello   \r\n\r\n");
+        assert_eq!(text(&mut parser, true), "hello\n");
+    }
+
+    #[test]
+    fn test_html() {
+        let mut parser = vt100::Parser::new(24, 80, 0);
+        parser.process(b"<a> & \x1b[1;31mred\x1b[m \x1b[7mrev\x1b[m  ");
+        let html = html(&mut parser, false, "doy's \"session\"");
+        assert!(html.contains("<title>doy's &quot;session&quot;</title>"));
+        let red =
+            "<span style=\"color: #ff0000; font-weight: bold\">red</span>";
+        let rev =
+            "<span style=\"color: #000000; background: #d3d3d3\">rev</span>";
+        assert!(html.contains(&format!(
+            "<pre>\n&lt;a&gt; &amp; {} {}\n</pre>",
+            red, rev
+        )));
+    }
+
+    #[test]
+    fn test_indexed_color() {
+        assert_eq!(indexed_color(1), "#800000");
+        assert_eq!(indexed_color(16), "#000000");
+        assert_eq!(indexed_color(21), "#0000ff");
+        assert_eq!(indexed_color(196), "#ff0000");
+        assert_eq!(indexed_color(244), "#808080");
+        assert_eq!(indexed_color(255), "#eeeeee");
+    }
+}
@@ -0,0 +1,400 @@
+use crate::prelude::*;
+use ring::signature::KeyPair as _;
+use std::io::{Read as _, Write as _};
+use std::os::unix::fs::OpenOptionsExt as _;
+
+const KNOWN_SERVERS_FILE: &str = "known_servers";
+const NONCE_LEN: usize = 32;
+
+// the only responses we read here are Welcome (a list of short capability
+// names) and HostKey (two length-prefixed byte strings), so anything much
+// bigger than this isn't a response we are going to accept anyway
+const MAX_RESPONSE_LEN: usize = 4096;
+
+// keeps the server from being usable as a signing oracle for anything other
+// than this exchange
+const SIGNATURE_CONTEXT: &[u8] = b"teleterm host key verification\0";
+
+pub struct HostKey {
+    key_pair: ring::signature::Ed25519KeyPair,
+}
+
+impl HostKey {
+    // the key is generated the first time the server starts, so that there
+    // is nothing to set up ahead of time
+    pub fn load_or_generate(filename: &str) -> Result<Self> {
+        let pkcs8 = if std::path::Path::new(filename).exists() {
+            let mut file = std::fs::File::open(filename).context(
+                crate::error::OpenFileSync {
+                    filename: filename.to_string(),
+                },
+            )?;
+            let mut pkcs8 = vec![];
+            file.read_to_end(&mut pkcs8)
+                .context(crate::error::ReadFileSync)?;
+            pkcs8
+        } else {
+            log::info!("generating a new host key in {}", filename);
+            let rng = ring::rand::SystemRandom::new();
+            let pkcs8 = ring::signature::Ed25519KeyPair::generate_pkcs8(&rng)
+                .map_err(|_| Error::GenerateHostKey)?;
+            let mut file = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(filename)
+                .context(crate::error::CreateFileSync {
+                    filename: filename.to_string(),
+                })?;
+            file.write_all(pkcs8.as_ref())
+                .context(crate::error::WriteFileSync)?;
+            pkcs8.as_ref().to_vec()
+        };
+
+        let key_pair = ring::signature::Ed25519KeyPair::from_pkcs8(
+            untrusted::Input::from(&pkcs8[..]),
+        )
+        .map_err(|_| Error::ParseHostKey {
+            filename: filename.to_string(),
+        })?;
+        log::info!(
+            "host key fingerprint is {}",
+            fingerprint(key_pair.public_key().as_ref())
+        );
+
+        Ok(Self { key_pair })
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        self.key_pair.public_key().as_ref()
+    }
+
+    pub fn sign(&self, nonce: &[u8]) -> Vec<u8> {
+        self.key_pair.sign(&signed_data(nonce)).as_ref().to_vec()
+    }
+}
+
+// this runs on the raw connection before it is handed off to the client,
+// and checks that whoever answered holds the key we saw last time. only the
+// nonce is signed, though - nothing ties that proof to the rest of the
+// connection, which isn't encrypted. this catches a different server
+// answering at the same address (after a dns change, for instance), but
+// anyone who can relay the connection to the real server passes the check
+// and can then read the login and everything after it.
+pub fn verify<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+>(
+    stream: S,
+    host: &str,
+) -> impl futures::Future<Item = S, Error = Error> + Send {
+    let host = host.to_string();
+
+    // servers which don't know about host keys would reject the request, so
+    // check what the server supports first
+    write_message(stream, &crate::protocol::Message::hello())
+        .and_then(read_message)
+        .and_then(|(stream, msg)| match msg {
+            crate::protocol::Message::Welcome { capabilities, .. } => {
+                Ok((stream, capabilities.iter().any(|c| c == "host-key")))
+            }
+            crate::protocol::Message::Error { msg } => {
+                Err(Error::Server { message: msg })
+            }
+            msg => Err(Error::UnexpectedMessage { message: msg }),
+        })
+        .and_then(move |(stream, supported)| {
+            if supported {
+                futures::future::Either::A(request(stream, host))
+            } else if known_host_key(&host).is_some() {
+                log::error!("server {} no longer supports host keys", host);
+                futures::future::Either::B(futures::future::err(
+                    Error::HostKeyMissing { host },
+                ))
+            } else {
+                futures::future::Either::B(futures::future::ok(stream))
+            }
+        })
+}
+
+fn request<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+>(
+    stream: S,
+    host: String,
+) -> impl futures::Future<Item = S, Error = Error> + Send {
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    write_message(stream, &crate::protocol::Message::host_key_request(&nonce))
+        .and_then(read_message)
+        .and_then(move |(stream, msg)| {
+            let known = known_host_key(&host);
+            let known = known.as_ref().map(std::vec::Vec::as_slice);
+            if let Some(public_key) = check(&host, &nonce, msg, known)? {
+                // the key will just be checked again next time
+                if let Err(e) = add_known_host_key(&host, &public_key) {
+                    log::warn!("failed to save host key for {}: {}", host, e);
+                } else {
+                    log::warn!(
+                    "added host key for {} ({}) to the known servers list",
+                    host,
+                    fingerprint(&public_key)
+                );
+                }
+            }
+            Ok(stream)
+        })
+}
+
+fn write_message<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+>(
+    stream: S,
+    msg: &crate::protocol::Message,
+) -> impl futures::Future<Item = S, Error = Error> + Send {
+    let mut buf = vec![];
+    msg.write(&mut buf).unwrap();
+    tokio::io::write_all(stream, buf)
+        .context(crate::error::WriteSocket)
+        .map(|(stream, _)| stream)
+}
+
+fn read_message<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+>(
+    stream: S,
+) -> Box<
+    dyn futures::Future<Item = (S, crate::protocol::Message), Error = Error>
+        + Send,
+> {
+    let fut = tokio::io::read_exact(stream, [0_u8; 4])
+        .context(crate::error::ReadSocket)
+        .and_then(|(stream, len_buf)| {
+            let len = u32::from_be_bytes(len_buf);
+            let res = if len as usize > MAX_RESPONSE_LEN {
+                Err(Error::LenTooBig {
+                    len,
+                    expected: MAX_RESPONSE_LEN,
+                })
+            } else {
+                Ok(())
+            };
+            futures::future::result(res).and_then(move |_| {
+                tokio::io::read_exact(stream, vec![0_u8; len as usize])
+                    .context(crate::error::ReadSocket)
+                    .map(move |(stream, data)| {
+                        let packet: Vec<u8> = len_buf
+                            .iter()
+                            .chain(data.iter())
+                            .copied()
+                            .collect();
+                        (stream, packet)
+                    })
+            })
+        })
+        .and_then(|(stream, packet)| {
+            let msg = crate::protocol::Message::read(packet.as_slice())?;
+            Ok((stream, msg))
+        });
+    Box::new(fut)
+}
+
+// returns the server's key if it should be added to the known servers file
+fn check(
+    host: &str,
+    nonce: &[u8],
+    msg: crate::protocol::Message,
+    known: Option<&[u8]>,
+) -> Result<Option<Vec<u8>>> {
+    let (public_key, signature) = match msg {
+        crate::protocol::Message::HostKey {
+            public_key,
+            signature,
+        } => (public_key, signature),
+        crate::protocol::Message::Error { msg } => {
+            return Err(Error::Server { message: msg });
+        }
+        msg => return Err(Error::UnexpectedMessage { message: msg }),
+    };
+
+    // the server doesn't have a host key configured
+    if public_key.is_empty() {
+        if known.is_some() {
+            log::error!("server {} no longer presents a host key", host);
+            return Err(Error::HostKeyMissing {
+                host: host.to_string(),
+            });
+        }
+        return Ok(None);
+    }
+
+    ring::signature::verify(
+        &ring::signature::ED25519,
+        untrusted::Input::from(&public_key[..]),
+        untrusted::Input::from(&signed_data(nonce)[..]),
+        untrusted::Input::from(&signature[..]),
+    )
+    .map_err(|_| Error::InvalidHostKeySignature {
+        host: host.to_string(),
+    })?;
+
+    match known {
+        Some(known) => {
+            if known != public_key.as_slice() {
+                log::error!(
+                    "host key for {} has changed, refusing to connect",
+                    host
+                );
+                return Err(Error::HostKeyChanged {
+                    host: host.to_string(),
+                    fingerprint: fingerprint(&public_key),
+                });
+            }
+            Ok(None)
+        }
+        None => Ok(Some(public_key)),
+    }
+}
+
+fn signed_data(nonce: &[u8]) -> Vec<u8> {
+    SIGNATURE_CONTEXT
+        .iter()
+        .chain(nonce.iter())
+        .copied()
+        .collect()
+}
+
+fn fingerprint(public_key: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, public_key);
+    format!(
+        "SHA256:{}",
+        base64::encode_config(digest.as_ref(), base64::STANDARD_NO_PAD)
+    )
+}
+
+// each line of the known servers file is "HOST:PORT KEY", where KEY is the
+// base64 encoded public key of that server
+fn known_host_key(host: &str) -> Option<Vec<u8>> {
+    let filename =
+        crate::dirs::Dirs::new().data_file(KNOWN_SERVERS_FILE, true)?;
+    let contents = std::fs::read_to_string(filename).ok()?;
+    parse_known_servers(&contents, host)
+}
+
+fn parse_known_servers(contents: &str, host: &str) -> Option<Vec<u8>> {
+    contents.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        if parts.next()? == host {
+            base64::decode(parts.next()?).ok()
+        } else {
+            None
+        }
+    })
+}
+
+fn add_known_host_key(host: &str, public_key: &[u8]) -> Result<()> {
+    let filename = crate::dirs::Dirs::new()
+        .data_file(KNOWN_SERVERS_FILE, false)
+        .context(crate::error::CouldntFindKnownServersFile)?;
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&filename)
+        .with_context(|| crate::error::CreateFileSync {
+            filename: filename.to_string_lossy().to_string(),
+        })?;
+    writeln!(file, "{} {}", host, base64::encode(public_key))
+        .context(crate::error::WriteFileSync)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn host_key() -> HostKey {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 =
+            ring::signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        HostKey {
+            key_pair: ring::signature::Ed25519KeyPair::from_pkcs8(
+                untrusted::Input::from(pkcs8.as_ref()),
+            )
+            .unwrap(),
+        }
+    }
+
+    fn response(key: &HostKey, nonce: &[u8]) -> crate::protocol::Message {
+        crate::protocol::Message::HostKey {
+            public_key: key.public_key().to_vec(),
+            signature: key.sign(nonce),
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let key = host_key();
+        let other = host_key();
+        let nonce = [1; NONCE_LEN];
+        let host = "example.com:4144";
+
+        // first connection, the key should be remembered
+        assert_eq!(
+            check(host, &nonce, response(&key, &nonce), None).unwrap(),
+            Some(key.public_key().to_vec())
+        );
+        assert_eq!(
+            check(
+                host,
+                &nonce,
+                response(&key, &nonce),
+                Some(key.public_key())
+            )
+            .unwrap(),
+            None
+        );
+
+        match check(
+            host,
+            &nonce,
+            response(&other, &nonce),
+            Some(key.public_key()),
+        ) {
+            Err(Error::HostKeyChanged { .. }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // a signature over a different nonce (replayed from an earlier
+        // connection, for instance) isn't accepted
+        match check(host, &nonce, response(&key, &[2; NONCE_LEN]), None) {
+            Err(Error::InvalidHostKeySignature { .. }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let no_key = crate::protocol::Message::HostKey {
+            public_key: vec![],
+            signature: vec![],
+        };
+        assert_eq!(check(host, &nonce, no_key.clone(), None).unwrap(), None);
+        match check(host, &nonce, no_key, Some(key.public_key())) {
+            Err(Error::HostKeyMissing { .. }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_parse_known_servers() {
+        let contents = format!(
+            "example.com:4144 {}\nlocalhost:4144 {}\n\nbroken:4144\n",
+            base64::encode(b"foo"),
+            base64::encode(b"bar"),
+        );
+        assert_eq!(
+            parse_known_servers(&contents, "example.com:4144"),
+            Some(b"foo".to_vec())
+        );
+        assert_eq!(
+            parse_known_servers(&contents, "localhost:4144"),
+            Some(b"bar".to_vec())
+        );
+        assert_eq!(parse_known_servers(&contents, "example.com:4145"), None);
+        assert_eq!(parse_known_servers(&contents, "broken:4144"), None);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::prelude::*;
+
+// gaps longer than this are the streamer pausing rather than the network
+// being uneven, so they shouldn't change how we pace things
+const IDLE_GAP: std::time::Duration = std::time::Duration::from_secs(1);
+// how much each new arrival moves the running estimates
+const SMOOTHING: f64 = 1.0 / 16.0;
+// how many multiples of the measured jitter to hold messages back for
+const JITTER_FACTOR: f64 = 2.0;
+
+// holds on to messages for a short (adaptive) amount of time before
+// releasing them, so that output which arrives in bursts because of network
+// jitter is played back at the pace it was originally sent at. the protocol
+// doesn't timestamp messages, so the pacing is estimated from when they
+// arrive. messages are never held for longer than max_delay, so that sets
+// the tradeoff between smoothness and how far behind live we are.
+pub struct JitterBuffer<T> {
+    max_delay: std::time::Duration,
+    items: std::collections::VecDeque<(std::time::Instant, T)>,
+    last_arrival: Option<std::time::Instant>,
+    last_due: Option<std::time::Instant>,
+    // running estimates (in seconds) of the time between messages, and of
+    // how much that time varies
+    gap: f64,
+    jitter: f64,
+    timer: Option<tokio::timer::Delay>,
+}
+
+impl<T> JitterBuffer<T> {
+    pub fn new(max_delay: std::time::Duration) -> Self {
+        Self {
+            max_delay,
+            items: std::collections::VecDeque::new(),
+            last_arrival: None,
+            last_due: None,
+            gap: 0.0,
+            jitter: 0.0,
+            timer: None,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        let due = self.schedule(std::time::Instant::now());
+        self.items.push_back((due, item));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // returns when a message arriving at `now` should be released
+    fn schedule(&mut self, now: std::time::Instant) -> std::time::Instant {
+        if let Some(last_arrival) = self.last_arrival {
+            let gap = now.duration_since(last_arrival);
+            if gap < IDLE_GAP {
+                let gap = gap.as_secs_f64();
+                self.jitter = smooth(self.jitter, (gap - self.gap).abs());
+                self.gap = smooth(self.gap, gap);
+            }
+        }
+        self.last_arrival = Some(now);
+
+        let delay = std::time::Duration::from_secs_f64(
+            (self.jitter * JITTER_FACTOR).min(self.max_delay.as_secs_f64()),
+        );
+        let mut due = now + delay;
+        // messages which arrive together are spread back out, as long as
+        // that doesn't put us too far behind
+        if let Some(last_due) = self.last_due {
+            let paced = (last_due
+                + std::time::Duration::from_secs_f64(self.gap))
+            .min(now + self.max_delay);
+            if paced > due {
+                due = paced;
+            }
+        }
+        self.last_due = Some(due);
+        due
+    }
+}
+
+fn smooth(average: f64, sample: f64) -> f64 {
+    (sample - average).mul_add(SMOOTHING, average)
+}
+
+#[must_use = "streams do nothing unless polled"]
+impl<T> futures::Stream for JitterBuffer<T> {
+    type Item = T;
+    type Error = Error;
+
+    // nothing wakes the task up when a message is pushed, so callers
+    // should check is_empty first
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        let due = if let Some((due, _)) = self.items.front() {
+            *due
+        } else {
+            return Ok(futures::Async::NotReady);
+        };
+        if due > std::time::Instant::now() {
+            let timer = self
+                .timer
+                .get_or_insert_with(|| tokio::timer::Delay::new(due));
+            futures::try_ready!(timer
+                .poll()
+                .context(crate::error::TimerJitterBuffer));
+        }
+        self.timer = None;
+        Ok(futures::Async::Ready(
+            self.items.pop_front().map(|(_, item)| item),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let max_delay = std::time::Duration::from_millis(200);
+        let mut buffer: JitterBuffer<()> = JitterBuffer::new(max_delay);
+        let start = std::time::Instant::now();
+
+        // steady output shouldn't be held back much
+        let mut now = start;
+        let mut due = now;
+        for _ in 0..100 {
+            now += std::time::Duration::from_millis(10);
+            due = buffer.schedule(now);
+        }
+        assert!(due - now < std::time::Duration::from_millis(5));
+
+        // output which arrives all at once is spread back out
+        now += std::time::Duration::from_millis(50);
+        let mut prev_due = buffer.schedule(now);
+        for _ in 0..4 {
+            let due = buffer.schedule(now);
+            assert!(due - prev_due > std::time::Duration::from_millis(5));
+            assert!(due <= now + max_delay);
+            prev_due = due;
+        }
+
+        // but never by more than max_delay
+        for _ in 0..100 {
+            let due = buffer.schedule(now);
+            assert!(due <= now + max_delay);
+            assert!(due >= prev_due);
+            prev_due = due;
+        }
+    }
+}
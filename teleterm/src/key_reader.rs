@@ -1,68 +1,659 @@
 use crate::prelude::*;
+use std::convert::TryFrom as _;
+use std::io::Write as _;
+
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+// the escape key and the start of an escape sequence look the same, so an
+// escape at the end of a read is only treated as the key if nothing else
+// follows it within this long
+const ESCAPE_TIMEOUT: std::time::Duration =
+    std::time::Duration::from_millis(50);
+
+// pasted text beyond this is passed along in pieces rather than all held
+// onto until the paste ends, since nothing guarantees that it will
+const MAX_PASTE_LEN: usize = 64 * 1024;
+// no sequence we understand is anywhere near this long
+const MAX_SEQUENCE_LEN: usize = 256;
+
+// pasted text is kept separate from keypresses, so that it can be passed
+// along as a whole (or ignored) rather than being treated as commands
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Input(crossterm::input::InputEvent),
+    Paste(Vec<u8>),
+}
 
 pub struct KeyReader {
-    events: Option<
-        tokio::sync::mpsc::UnboundedReceiver<crossterm::input::InputEvent>,
-    >,
-    quit: Option<tokio::sync::oneshot::Sender<()>>,
+    stdin: crate::async_stdin::Stdin,
+    parser: Parser,
+    events: std::collections::VecDeque<Event>,
+    escape_timer: Option<tokio::timer::Delay>,
+    // bracketed paste is turned on by the first poll, which is also when
+    // stdin starts being read
+    started: bool,
 }
 
 impl KeyReader {
     pub fn new() -> Self {
         Self {
-            events: None,
-            quit: None,
+            stdin: crate::async_stdin::Stdin::new(),
+            parser: Parser::default(),
+            events: std::collections::VecDeque::new(),
+            escape_timer: None,
+            started: false,
         }
     }
+
+    fn reset_escape_timer(&mut self) {
+        self.escape_timer = if self.parser.holding_escape() {
+            Some(tokio::timer::Delay::new(
+                std::time::Instant::now() + ESCAPE_TIMEOUT,
+            ))
+        } else {
+            None
+        };
+    }
 }
 
 impl futures::Stream for KeyReader {
-    type Item = crossterm::input::InputEvent;
+    type Item = Event;
     type Error = Error;
 
     fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
-        if self.events.is_none() {
-            let task = futures::task::current();
-            let reader = crossterm::input::input().read_sync();
-            let (events_tx, events_rx) =
-                tokio::sync::mpsc::unbounded_channel();
-            let mut events_tx = events_tx.wait();
-            let (quit_tx, mut quit_rx) = tokio::sync::oneshot::channel();
-            // TODO: this is pretty janky - it'd be better to build in more
-            // useful support to crossterm directly
-            std::thread::Builder::new()
-                .spawn(move || {
-                    for event in reader {
-                        // unwrap is unpleasant, but so is figuring out how to
-                        // propagate the error back to the main thread
-                        events_tx.send(event).unwrap();
-                        task.notify();
-                        if quit_rx.try_recv().is_ok() {
-                            break;
-                        }
+        if !self.started {
+            // crossterm drops the markers around pasted text (along with
+            // anything else it doesn't recognize), so the terminal is read
+            // directly instead
+            write_terminal(b"\x1b[?2004h")?;
+            self.started = true;
+        }
+
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(futures::Async::Ready(Some(event)));
+            }
+            if let Some(timer) = &mut self.escape_timer {
+                if timer.poll().context(crate::error::TimerEscape)?.is_ready()
+                {
+                    self.events.extend(self.parser.flush());
+                    self.reset_escape_timer();
+                    continue;
+                }
+            }
+            match futures::try_ready!(self.stdin.poll()) {
+                Some(data) => {
+                    self.events.extend(self.parser.push(&data));
+                    self.reset_escape_timer();
+                }
+                None => {
+                    self.events.extend(self.parser.flush());
+                    if self.events.is_empty() {
+                        return Ok(futures::Async::Ready(None));
                     }
-                })
-                .context(crate::error::TerminalInputReadingThread)?;
+                }
+            }
+        }
+    }
+}
 
-            self.events = Some(events_rx);
-            self.quit = Some(quit_tx);
+impl Drop for KeyReader {
+    fn drop(&mut self) {
+        if self.started {
+            let _ = write_terminal(b"\x1b[?2004l");
         }
+    }
+}
+
+// reports mouse clicks as input events for as long as it is alive. this
+// isn't always on, since it stops the terminal from selecting text.
+pub struct MouseCapture;
 
-        self.events
-            .as_mut()
-            .unwrap()
-            .poll()
-            .context(crate::error::ReadChannel)
+impl MouseCapture {
+    pub fn new() -> Result<Self> {
+        crossterm::input::input()
+            .enable_mouse_mode()
+            .context(crate::error::EnableMouse)?;
+        Ok(Self)
     }
 }
 
-impl Drop for KeyReader {
+impl Drop for MouseCapture {
     fn drop(&mut self) {
-        if let Some(quit_tx) = self.quit.take() {
-            // don't care if it fails to send, this can happen if the thread
-            // terminates due to seeing a newline before the keyreader goes
-            // out of scope
-            let _ = quit_tx.send(());
+        let _ = crossterm::input::input().disable_mouse_mode();
+    }
+}
+
+fn write_terminal(data: &[u8]) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    stdout
+        .write_all(data)
+        .context(crate::error::WriteTerminal)?;
+    stdout.flush().context(crate::error::FlushTerminal)?;
+    Ok(())
+}
+
+// turns the bytes the terminal sends into events, the same way that
+// crossterm does, except for also handling pasted text. sequences which are
+// cut off by the end of a read are held onto until the rest arrives.
+#[derive(Default)]
+struct Parser {
+    buf: Vec<u8>,
+    pasting: bool,
+}
+
+impl Parser {
+    fn push(&mut self, data: &[u8]) -> Vec<Event> {
+        self.buf.extend_from_slice(data);
+        let mut events = vec![];
+        let mut i = 0;
+        while i < self.buf.len() {
+            let rest = &self.buf[i..];
+            if self.pasting {
+                if let Some(end) = find(rest, PASTE_END) {
+                    events.push(Event::Paste(rest[..end].to_vec()));
+                    self.pasting = false;
+                    i += end + PASTE_END.len();
+                    continue;
+                }
+                if rest.len() > MAX_PASTE_LEN {
+                    // hold onto enough to still see the end marker if it
+                    // was cut off
+                    let len = rest.len() - (PASTE_END.len() - 1);
+                    events.push(Event::Paste(rest[..len].to_vec()));
+                    i += len;
+                }
+                break;
+            }
+            if rest.starts_with(PASTE_START) {
+                self.pasting = true;
+                i += PASTE_START.len();
+                continue;
+            }
+            match parse_event(rest) {
+                Some((event, len)) => {
+                    if let Some(event) = event {
+                        events.push(Event::Input(event));
+                    }
+                    i += len;
+                }
+                // whatever this is, it isn't going to end, so skip past
+                // the byte that started it
+                None if rest.len() > MAX_SEQUENCE_LEN => i += 1,
+                None => break,
+            }
+        }
+        self.buf.drain(..i);
+        events
+    }
+
+    fn holding_escape(&self) -> bool {
+        !self.pasting && self.buf.first() == Some(&b'\x1b')
+    }
+
+    // called once nothing has followed an escape for a while, so that it
+    // can be treated as the escape key itself
+    fn flush(&mut self) -> Vec<Event> {
+        if !self.holding_escape() {
+            return vec![];
+        }
+        let rest = self.buf.split_off(1);
+        self.buf.clear();
+        let mut events =
+            vec![Event::Input(crossterm::input::InputEvent::Keyboard(
+                crossterm::input::KeyEvent::Esc,
+            ))];
+        events.extend(self.push(&rest));
+        events
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// returns the event at the start of buf (if it is one we know about) and
+// how many bytes it took up, or None if buf ends partway through it
+fn parse_event(
+    buf: &[u8],
+) -> Option<(Option<crossterm::input::InputEvent>, usize)> {
+    let key = |key| Some(crossterm::input::InputEvent::Keyboard(key));
+    let (event, len) = match buf[0] {
+        b'\x1b' => match buf.get(1) {
+            // wait to see whether this is the start of a sequence or the
+            // escape key itself (see Parser::flush)
+            None => return None,
+            Some(b'[') => return parse_csi(buf),
+            Some(b'O') => match buf.get(2)? {
+                c @ b'P'..=b'S' => {
+                    (key(crossterm::input::KeyEvent::F(1 + c - b'P')), 3)
+                }
+                _ => (None, 3),
+            },
+            Some(b'\x1b') => (key(crossterm::input::KeyEvent::Esc), 2),
+            Some(_) => {
+                let (c, len) = parse_utf8_char(&buf[1..])?;
+                (
+                    c.map(crossterm::input::KeyEvent::Alt).and_then(key),
+                    len + 1,
+                )
+            }
+        },
+        b'\r' | b'\n' => (key(crossterm::input::KeyEvent::Enter), 1),
+        b'\t' => (key(crossterm::input::KeyEvent::Tab), 1),
+        b'\x7f' => (key(crossterm::input::KeyEvent::Backspace), 1),
+        c @ b'\x01'..=b'\x1a' => (
+            key(crossterm::input::KeyEvent::Ctrl((c - 0x1 + b'a') as char)),
+            1,
+        ),
+        c @ b'\x1c'..=b'\x1f' => (
+            key(crossterm::input::KeyEvent::Ctrl((c - 0x1c + b'4') as char)),
+            1,
+        ),
+        b'\0' => (key(crossterm::input::KeyEvent::Null), 1),
+        _ => {
+            let (c, len) = parse_utf8_char(buf)?;
+            (c.map(crossterm::input::KeyEvent::Char).and_then(key), len)
+        }
+    };
+    Some((event, len))
+}
+
+// buf starts with \x1b[
+fn parse_csi(
+    buf: &[u8],
+) -> Option<(Option<crossterm::input::InputEvent>, usize)> {
+    let key = |key| Some(crossterm::input::InputEvent::Keyboard(key));
+    let f_key = |n: u16| {
+        u8::try_from(n)
+            .ok()
+            .map(crossterm::input::KeyEvent::F)
+            .and_then(key)
+    };
+
+    // parameter and intermediate bytes, followed by a single final byte
+    let end = 2 + buf[2..].iter().position(|c| (0x40..=0x7e).contains(c))?;
+    let params = &buf[2..end];
+    let len = end + 1;
+
+    let event = match (params, buf[end]) {
+        (b"", b'A') => key(crossterm::input::KeyEvent::Up),
+        (b"", b'B') => key(crossterm::input::KeyEvent::Down),
+        (b"", b'C') => key(crossterm::input::KeyEvent::Right),
+        (b"", b'D') => key(crossterm::input::KeyEvent::Left),
+        (b"", b'H') => key(crossterm::input::KeyEvent::Home),
+        (b"", b'F') => key(crossterm::input::KeyEvent::End),
+        (b"", b'Z') => key(crossterm::input::KeyEvent::BackTab),
+        // the linux console sends \x1b[[A through \x1b[[E for f1-f5
+        (b"", b'[') => {
+            let event = match buf.get(len)? {
+                c @ b'A'..=b'E' => {
+                    key(crossterm::input::KeyEvent::F(1 + c - b'A'))
+                }
+                _ => None,
+            };
+            return Some((event, len + 1));
+        }
+        // x10 mouse encoding, which is followed by three raw bytes
+        (b"", b'M') => {
+            let bytes = buf.get(len..len + 3)?;
+            let event = mouse_event(
+                u16::from(bytes[0].saturating_sub(32)),
+                u16::from(bytes[1].saturating_sub(32)),
+                u16::from(bytes[2].saturating_sub(32)),
+                true,
+            );
+            return Some((Some(event), len + 3));
+        }
+        // sgr mouse encoding: \x1b[<Cb;Cx;Cy followed by M for presses
+        // and m for releases
+        (params, c @ b'M') | (params, c @ b'm')
+            if params.starts_with(b"<") =>
+        {
+            let nums = parse_params(&params[1..]);
+            match nums.as_slice() {
+                [cb, cx, cy] => Some(mouse_event(*cb, *cx, *cy, c == b'M')),
+                _ => None,
+            }
         }
+        (params, b'~') => match parse_params(params).as_slice() {
+            [1] | [7] => key(crossterm::input::KeyEvent::Home),
+            [2] => key(crossterm::input::KeyEvent::Insert),
+            [3] => key(crossterm::input::KeyEvent::Delete),
+            [4] | [8] => key(crossterm::input::KeyEvent::End),
+            [5] => key(crossterm::input::KeyEvent::PageUp),
+            [6] => key(crossterm::input::KeyEvent::PageDown),
+            [v @ 11..=15] => f_key(v - 10),
+            [v @ 17..=21] => f_key(v - 11),
+            [v @ 23..=24] => f_key(v - 12),
+            _ => None,
+        },
+        (b"1;5", b'A') => key(crossterm::input::KeyEvent::CtrlUp),
+        (b"1;5", b'B') => key(crossterm::input::KeyEvent::CtrlDown),
+        (b"1;5", b'C') => key(crossterm::input::KeyEvent::CtrlRight),
+        (b"1;5", b'D') => key(crossterm::input::KeyEvent::CtrlLeft),
+        (b"1;2", b'A') => key(crossterm::input::KeyEvent::ShiftUp),
+        (b"1;2", b'B') => key(crossterm::input::KeyEvent::ShiftDown),
+        (b"1;2", b'C') => key(crossterm::input::KeyEvent::ShiftRight),
+        (b"1;2", b'D') => key(crossterm::input::KeyEvent::ShiftLeft),
+        _ => None,
+    };
+    Some((event, len))
+}
+
+fn parse_params(params: &[u8]) -> Vec<u16> {
+    params
+        .split(|c| *c == b';')
+        .map(|param| {
+            std::str::from_utf8(param)
+                .ok()
+                .and_then(|param| param.parse().ok())
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+// coordinates start at (1, 1) in the top left, as they do in crossterm
+fn mouse_event(
+    cb: u16,
+    cx: u16,
+    cy: u16,
+    pressed: bool,
+) -> crossterm::input::InputEvent {
+    // ignore which modifier keys were held down
+    let cb = cb & !0b1_1100;
+    let event = match cb {
+        0 | 1 | 2 | 64 | 65 if pressed => {
+            let button = match cb {
+                0 => crossterm::input::MouseButton::Left,
+                1 => crossterm::input::MouseButton::Middle,
+                2 => crossterm::input::MouseButton::Right,
+                64 => crossterm::input::MouseButton::WheelUp,
+                _ => crossterm::input::MouseButton::WheelDown,
+            };
+            crossterm::input::MouseEvent::Press(button, cx, cy)
+        }
+        32..=34 => crossterm::input::MouseEvent::Hold(cx, cy),
+        _ => crossterm::input::MouseEvent::Release(cx, cy),
+    };
+    crossterm::input::InputEvent::Mouse(event)
+}
+
+// invalid utf8 is skipped (returning None for the character), and None
+// overall means that buf ends partway through a character
+fn parse_utf8_char(buf: &[u8]) -> Option<(Option<char>, usize)> {
+    let len = match buf[0] {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Some((None, 1)),
+    };
+    let bytes = buf.get(..len)?;
+    Some(match std::str::from_utf8(bytes) {
+        Ok(s) => (s.chars().next(), len),
+        Err(_) => (None, 1),
+    })
+}
+
+// translates a key event into the bytes that a terminal would send to the
+// program running inside it
+pub fn key_bytes(e: &crossterm::input::InputEvent) -> Option<Vec<u8>> {
+    let key = if let crossterm::input::InputEvent::Keyboard(key) = e {
+        key
+    } else {
+        return None;
+    };
+
+    let bytes: &[u8] = match key {
+        crossterm::input::KeyEvent::Char(c) => {
+            return Some(c.to_string().into_bytes());
+        }
+        crossterm::input::KeyEvent::Alt(c) => {
+            return Some(format!("\x1b{}", c).into_bytes());
+        }
+        crossterm::input::KeyEvent::Ctrl(c) => {
+            if !c.is_ascii() {
+                return None;
+            }
+            return Some(vec![(*c as u8) & 0x1f]);
+        }
+        crossterm::input::KeyEvent::F(n) => match n {
+            1 => b"\x1bOP",
+            2 => b"\x1bOQ",
+            3 => b"\x1bOR",
+            4 => b"\x1bOS",
+            5 => b"\x1b[15~",
+            6 => b"\x1b[17~",
+            7 => b"\x1b[18~",
+            8 => b"\x1b[19~",
+            9 => b"\x1b[20~",
+            10 => b"\x1b[21~",
+            11 => b"\x1b[23~",
+            12 => b"\x1b[24~",
+            _ => return None,
+        },
+        crossterm::input::KeyEvent::Backspace => b"\x7f",
+        crossterm::input::KeyEvent::Enter => b"\r",
+        crossterm::input::KeyEvent::Tab => b"\t",
+        crossterm::input::KeyEvent::BackTab => b"\x1b[Z",
+        crossterm::input::KeyEvent::Esc => b"\x1b",
+        crossterm::input::KeyEvent::Null => b"\x00",
+        crossterm::input::KeyEvent::Up => b"\x1b[A",
+        crossterm::input::KeyEvent::Down => b"\x1b[B",
+        crossterm::input::KeyEvent::Right => b"\x1b[C",
+        crossterm::input::KeyEvent::Left => b"\x1b[D",
+        crossterm::input::KeyEvent::Home => b"\x1b[H",
+        crossterm::input::KeyEvent::End => b"\x1b[F",
+        crossterm::input::KeyEvent::Insert => b"\x1b[2~",
+        crossterm::input::KeyEvent::Delete => b"\x1b[3~",
+        crossterm::input::KeyEvent::PageUp => b"\x1b[5~",
+        crossterm::input::KeyEvent::PageDown => b"\x1b[6~",
+        _ => return None,
+    };
+    Some(bytes.to_vec())
+}
+
+// watches typed keys for a sequence which is reserved for the user to talk to
+// us, rather than to the program the keys are being sent to. keys which might
+// be the start of that sequence are held back until it's clear whether they
+// are.
+pub struct Escape {
+    escape: Vec<u8>,
+    typed: Vec<u8>,
+}
+
+impl Escape {
+    pub fn new(escape: &[u8]) -> Self {
+        Self {
+            escape: escape.to_vec(),
+            typed: vec![],
+        }
+    }
+
+    // returns the bytes which should be passed through, or None once the
+    // whole sequence has been typed
+    pub fn push(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        self.typed.extend_from_slice(data);
+        if self.typed == self.escape {
+            self.typed.clear();
+            return None;
+        }
+        if self.escape.starts_with(&self.typed) {
+            return Some(vec![]);
+        }
+
+        // the latest key may still start a new sequence by itself
+        let mut typed = std::mem::replace(&mut self.typed, vec![]);
+        if data != self.escape.as_slice() && self.escape.starts_with(data) {
+            typed.truncate(typed.len() - data.len());
+            self.typed = data.to_vec();
+        }
+        Some(typed)
+    }
+
+    pub fn reset(&mut self) {
+        self.typed.clear();
+    }
+
+    // returns the part of the sequence which has been typed so far, for
+    // when the next input can't continue it
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::replace(&mut self.typed, vec![])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_key_bytes() {
+        let key = |k| crossterm::input::InputEvent::Keyboard(k);
+        assert_eq!(
+            key_bytes(&key(crossterm::input::KeyEvent::Char('a'))),
+            Some(b"a".to_vec())
+        );
+        assert_eq!(
+            key_bytes(&key(crossterm::input::KeyEvent::Char('é'))),
+            Some("é".as_bytes().to_vec())
+        );
+        assert_eq!(
+            key_bytes(&key(crossterm::input::KeyEvent::Ctrl('c'))),
+            Some(b"\x03".to_vec())
+        );
+        assert_eq!(
+            key_bytes(&key(crossterm::input::KeyEvent::Alt('x'))),
+            Some(b"\x1bx".to_vec())
+        );
+        assert_eq!(
+            key_bytes(&key(crossterm::input::KeyEvent::Up)),
+            Some(b"\x1b[A".to_vec())
+        );
+        assert_eq!(key_bytes(&key(crossterm::input::KeyEvent::F(13))), None);
+    }
+
+    #[test]
+    fn test_escape() {
+        let mut escape = Escape::new(b"~.");
+        assert_eq!(escape.push(b"a"), Some(b"a".to_vec()));
+        assert_eq!(escape.push(b"~"), Some(b"".to_vec()));
+        assert_eq!(escape.push(b"b"), Some(b"~b".to_vec()));
+        assert_eq!(escape.push(b"~"), Some(b"".to_vec()));
+        assert_eq!(escape.push(b"~"), Some(b"~".to_vec()));
+        assert_eq!(escape.push(b"."), None);
+        assert_eq!(escape.push(b"."), Some(b".".to_vec()));
+        assert_eq!(escape.push(b"\x1b[A"), Some(b"\x1b[A".to_vec()));
+
+        let mut escape = Escape::new(b"\x1c");
+        assert_eq!(escape.push(b"\x1c"), None);
+        assert_eq!(escape.push(b"x"), Some(b"x".to_vec()));
+    }
+
+    #[test]
+    fn test_parser() {
+        let key = |k| Event::Input(crossterm::input::InputEvent::Keyboard(k));
+        let mouse = |m| Event::Input(crossterm::input::InputEvent::Mouse(m));
+
+        let mut parser = Parser::default();
+        assert_eq!(
+            parser.push("a\x03\ré".as_bytes()),
+            vec![
+                key(crossterm::input::KeyEvent::Char('a')),
+                key(crossterm::input::KeyEvent::Ctrl('c')),
+                key(crossterm::input::KeyEvent::Enter),
+                key(crossterm::input::KeyEvent::Char('é')),
+            ]
+        );
+        assert_eq!(parser.push(b"\x1b"), vec![]);
+        assert!(parser.holding_escape());
+        assert_eq!(
+            parser.flush(),
+            vec![key(crossterm::input::KeyEvent::Esc)]
+        );
+        assert!(!parser.holding_escape());
+        assert_eq!(parser.push(b"\x1b"), vec![]);
+        assert_eq!(
+            parser.push(b"[B"),
+            vec![key(crossterm::input::KeyEvent::Down)]
+        );
+        assert_eq!(parser.push(b"\x1b["), vec![]);
+        assert_eq!(
+            parser.flush(),
+            vec![
+                key(crossterm::input::KeyEvent::Esc),
+                key(crossterm::input::KeyEvent::Char('[')),
+            ]
+        );
+        assert_eq!(parser.flush(), vec![]);
+        assert_eq!(
+            parser.push(b"\x1b[15~\x1b[24~"),
+            vec![
+                key(crossterm::input::KeyEvent::F(5)),
+                key(crossterm::input::KeyEvent::F(12)),
+            ]
+        );
+        assert_eq!(
+            parser.push(b"\x1bx\x1b[A\x1b[1;5C\x1b[3~\x1bOQ"),
+            vec![
+                key(crossterm::input::KeyEvent::Alt('x')),
+                key(crossterm::input::KeyEvent::Up),
+                key(crossterm::input::KeyEvent::CtrlRight),
+                key(crossterm::input::KeyEvent::Delete),
+                key(crossterm::input::KeyEvent::F(2)),
+            ]
+        );
+
+        assert_eq!(parser.push(b"\x1b[5"), vec![]);
+        assert_eq!(
+            parser.push(b"~"),
+            vec![key(crossterm::input::KeyEvent::PageUp)]
+        );
+
+        assert_eq!(
+            parser.push(b"\x1b[<0;3;7M\x1b[<0;3;7m\x1b[M !\""),
+            vec![
+                mouse(crossterm::input::MouseEvent::Press(
+                    crossterm::input::MouseButton::Left,
+                    3,
+                    7
+                )),
+                mouse(crossterm::input::MouseEvent::Release(3, 7)),
+                mouse(crossterm::input::MouseEvent::Press(
+                    crossterm::input::MouseButton::Left,
+                    1,
+                    2
+                )),
+            ]
+        );
+
+        assert_eq!(
+            parser.push(b"x\x1b[200~q\x1b[A"),
+            vec![key(crossterm::input::KeyEvent::Char('x'))]
+        );
+        assert_eq!(
+            parser.push(b"\r\x1b[201~y"),
+            vec![
+                Event::Paste(b"q\x1b[A\r".to_vec()),
+                key(crossterm::input::KeyEvent::Char('y')),
+            ]
+        );
+
+        // pastes which don't end aren't held onto forever
+        assert_eq!(parser.push(PASTE_START), vec![]);
+        let events = parser.push(&vec![b'x'; MAX_PASTE_LEN + 1]);
+        assert_eq!(events.len(), 1);
+        assert!(parser.buf.len() < PASTE_END.len());
+        assert_eq!(parser.push(PASTE_END).len(), 1);
+        assert!(parser.buf.is_empty());
+
+        // neither are sequences which don't end
+        parser.push(b"\x1b[");
+        parser.push(&vec![b'0'; MAX_SEQUENCE_LEN * 2]);
+        assert!(parser.buf.len() <= MAX_SEQUENCE_LEN);
     }
 }
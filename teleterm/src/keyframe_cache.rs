@@ -0,0 +1,112 @@
+use crate::prelude::*;
+use std::io::Read as _;
+use std::os::unix::fs::OpenOptionsExt as _;
+
+const KEYFRAME_FILENAME: &str = "watch-keyframe";
+
+// how often the screen of the session being watched is saved to disk
+pub const KEYFRAME_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(5);
+
+// the last known screen of the most recently watched session, so that it can
+// be displayed immediately when reattaching to that session rather than
+// staring at a blank screen while waiting for the server
+#[derive(Debug, PartialEq)]
+pub struct Keyframe {
+    pub id: String,
+    pub size: crate::term::Size,
+    pub data: Vec<u8>,
+}
+
+impl Keyframe {
+    pub fn new(id: &str, screen: &vt100::Screen) -> Self {
+        let (rows, cols) = screen.size();
+        Self {
+            id: id.to_string(),
+            size: crate::term::Size { rows, cols },
+            data: screen.contents_formatted(),
+        }
+    }
+
+    pub fn load(id: &str) -> Option<Self> {
+        let filename =
+            crate::dirs::Dirs::new().cache_file(KEYFRAME_FILENAME, true)?;
+        let mut buf = vec![];
+        std::fs::File::open(filename)
+            .ok()?
+            .read_to_end(&mut buf)
+            .ok()?;
+        Self::parse(&buf).filter(|keyframe| keyframe.id == id)
+    }
+
+    // this is saved regularly while watching, so the file is written
+    // asynchronously rather than blocking the reactor
+    pub fn save(
+        &self,
+    ) -> impl futures::Future<Item = (), Error = Error> + Send {
+        let filename = crate::dirs::Dirs::new()
+            .cache_file(KEYFRAME_FILENAME, false)
+            .unwrap();
+        let data = self.serialize();
+        // the screen can contain anything, so it shouldn't be readable by
+        // anyone else. the mode only applies to newly created files, so any
+        // existing file is removed first.
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true).mode(0o600);
+        tokio::fs::remove_file(filename.clone())
+            .then(move |_| {
+                tokio::fs::OpenOptions::from(options)
+                    .open(filename.clone())
+                    .with_context(move || crate::error::CreateFile {
+                        filename: filename.to_string_lossy().to_string(),
+                    })
+            })
+            .and_then(|file| {
+                tokio::io::write_all(file, data)
+                    .context(crate::error::WriteFile)
+            })
+            .map(|_| ())
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf =
+            format!("{}\n{}\n{}\n", self.id, self.size.rows, self.size.cols)
+                .into_bytes();
+        buf.extend_from_slice(&self.data);
+        buf
+    }
+
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let mut parts = buf.splitn(4, |&c| c == b'\n');
+        let id = std::str::from_utf8(parts.next()?).ok()?.to_string();
+        let rows = std::str::from_utf8(parts.next()?).ok()?.parse().ok()?;
+        let cols = std::str::from_utf8(parts.next()?).ok()?.parse().ok()?;
+        let data = parts.next()?.to_vec();
+        Some(Self {
+            id,
+            size: crate::term::Size { rows, cols },
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut parser = vt100::Parser::new(24, 80, 0);
+        parser.process(b"foo\r\n\x1b[31mbar\x1b[m\r\n");
+        let keyframe = Keyframe::new("some-id", parser.screen());
+        assert_eq!(keyframe.size, crate::term::Size { rows: 24, cols: 80 });
+        assert_eq!(Keyframe::parse(&keyframe.serialize()), Some(keyframe));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(Keyframe::parse(b""), None);
+        assert_eq!(Keyframe::parse(b"some-id\n24\n"), None);
+        assert_eq!(Keyframe::parse(b"some-id\nfoo\n80\n"), None);
+    }
+}
@@ -19,17 +19,35 @@ mod prelude;
 
 mod async_stdin;
 mod auth;
+mod channel_reader;
 mod client;
 mod cmd;
 mod config;
+mod connect;
+mod credentials;
 mod dirs;
 mod error;
+mod export;
+mod host_key;
+mod jitter_buffer;
 mod key_reader;
+mod keyframe_cache;
+mod naming;
 mod oauth;
+mod output_batch;
 mod protocol;
+mod recorder;
+mod recording;
+mod render;
+mod replay;
+mod serial;
 mod server;
 mod session_list;
+mod status_line;
+mod tail;
 mod term;
+mod timeline;
+mod ui;
 mod web;
 
 fn main() {
@@ -0,0 +1,313 @@
+use crate::prelude::*;
+use std::io::{Read as _, Write as _};
+
+// the naming command is rerun periodically, so that the title can keep up
+// with what the streamer is working on
+const REFRESH_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(60);
+// a naming command which hangs is killed, so that it doesn't stop later
+// refreshes from happening
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const POLL_INTERVAL: std::time::Duration =
+    std::time::Duration::from_millis(50);
+
+// this is what the naming command receives on stdin, as json
+#[derive(serde::Serialize, Debug)]
+struct Input {
+    cwd: String,
+    command: String,
+    args: Vec<String>,
+    git: Option<GitInfo>,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct GitInfo {
+    root: String,
+    commit: String,
+    branch: String,
+}
+
+// and this is what it is expected to print to stdout, also as json
+#[derive(serde::Deserialize, Debug)]
+struct Output {
+    #[serde(default)]
+    title: String,
+
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub title: String,
+    pub tags: Vec<String>,
+}
+
+// runs a user-provided command to decide what the stream should be called,
+// so that sites can have their own naming conventions (for instance, based
+// on the git repository being worked in) without us needing to know about
+// them
+pub struct Naming {
+    naming_command: String,
+    command: String,
+    args: Vec<String>,
+
+    timer: tokio::timer::Interval,
+    running: Option<tokio::sync::oneshot::Receiver<Result<Metadata>>>,
+}
+
+impl Naming {
+    pub fn new(naming_command: &str, command: &str, args: &[String]) -> Self {
+        Self {
+            naming_command: naming_command.to_string(),
+            command: command.to_string(),
+            args: args.to_vec(),
+
+            timer: tokio::timer::Interval::new_interval(REFRESH_INTERVAL),
+            running: None,
+        }
+    }
+
+    fn spawn(&mut self) -> Result<()> {
+        let naming_command = self.naming_command.clone();
+        let command = self.command.clone();
+        let args = self.args.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        std::thread::Builder::new()
+            .spawn(move || {
+                let res = run(&naming_command, &command, &args);
+                // don't care if this fails, that just means we're shutting
+                // down
+                let _ = tx.send(res);
+            })
+            .context(crate::error::NamingCommandThread)?;
+        self.running = Some(rx);
+        Ok(())
+    }
+}
+
+#[must_use = "streams do nothing unless polled"]
+impl futures::Stream for Naming {
+    type Item = Metadata;
+    type Error = Error;
+
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(running) = &mut self.running {
+                let res = match running.poll() {
+                    Ok(futures::Async::Ready(res)) => res,
+                    Ok(futures::Async::NotReady) => {
+                        return Ok(futures::Async::NotReady)
+                    }
+                    // the thread panicked, which will have been reported
+                    // already
+                    Err(..) => Err(Error::NamingCommandThreadDied),
+                };
+                self.running = None;
+                match res {
+                    Ok(metadata) => {
+                        return Ok(futures::Async::Ready(Some(metadata)))
+                    }
+                    // a broken naming command shouldn't interrupt the
+                    // stream, and it may start working again later
+                    Err(e) => log::warn!("naming command failed: {}", e),
+                }
+            }
+
+            futures::try_ready!(self
+                .timer
+                .poll()
+                .context(crate::error::TimerNaming));
+            self.spawn()?;
+        }
+    }
+}
+
+fn run(
+    naming_command: &str,
+    command: &str,
+    args: &[String],
+) -> Result<Metadata> {
+    let cwd = cwd();
+    let input = Input {
+        git: git_info(&cwd),
+        cwd,
+        command: command.to_string(),
+        args: args.to_vec(),
+    };
+    let input =
+        serde_json::to_vec(&input).context(crate::error::SerializeMessage)?;
+
+    // stderr would be drawn over the terminal being streamed
+    let mut child = std::process::Command::new(naming_command)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .context(crate::error::RunNamingCommand {
+            command: naming_command,
+        })?;
+    // stdin is closed when this is dropped, so the command sees eof
+    let written = child.stdin.take().unwrap().write_all(&input);
+    let status = written.and_then(|_| wait(&mut child)).context(
+        crate::error::RunNamingCommand {
+            command: naming_command,
+        },
+    )?;
+    let status = if let Some(status) = status {
+        status
+    } else {
+        return Err(Error::NamingCommandTimeout {
+            secs: TIMEOUT.as_secs(),
+        });
+    };
+    if !status.success() {
+        return Err(Error::NamingCommandFailed { status });
+    }
+
+    // the command has exited, so this won't block on anything but
+    // grandchildren which kept stdout open, which we don't wait for
+    let mut stdout = vec![];
+    let mut pipe = child.stdout.take().unwrap();
+    let _ = pipe.read_to_end(&mut stdout);
+    let output: Output = serde_json::from_slice(&stdout)
+        .context(crate::error::ParseNamingOutput)?;
+    Ok(Metadata {
+        title: output.title,
+        tags: output.tags,
+    })
+}
+
+// returns None (after killing the command) if it runs for too long
+fn wait(
+    child: &mut std::process::Child,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    let deadline = std::time::Instant::now() + TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if std::time::Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// the fields of /proc/<pid>/stat that we care about
+#[derive(Debug, PartialEq, Eq)]
+struct Stat {
+    ppid: u32,
+    session: u32,
+    // the foreground process group of the process's controlling terminal,
+    // which is what tcgetpgrp would return for it
+    tpgid: Option<u32>,
+}
+
+// the directory the streamer is working in. the streamed command runs in a
+// session of its own on its own pty, and whatever is in the foreground on
+// that pty is what the streamer is working on right now, so we use its
+// directory, or the streamed command's if that can't be read. this can only
+// be found through /proc, so elsewhere (or when there is no command, like
+// when streaming a serial console) this falls back to our own working
+// directory.
+fn cwd() -> String {
+    streamed_command()
+        .and_then(|(pid, stat)| {
+            stat.tpgid.and_then(read_cwd).or_else(|| read_cwd(pid))
+        })
+        .or_else(|| std::env::current_dir().ok())
+        .map(|cwd| cwd.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn read_cwd(pid: u32) -> Option<std::path::PathBuf> {
+    std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+// the pty process is the only child of ours which leads its own session
+// (naming commands and the like stay in ours), so it can't be confused
+// with anything else we have running
+fn streamed_command() -> Option<(u32, Stat)> {
+    let pid = std::process::id();
+    std::fs::read_dir("/proc").ok()?.find_map(|entry| {
+        let child: u32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+        let stat = parse_stat(
+            &std::fs::read_to_string(format!("/proc/{}/stat", child)).ok()?,
+        )?;
+        if stat.ppid == pid && stat.session == child {
+            Some((child, stat))
+        } else {
+            None
+        }
+    })
+}
+
+// the command name in parentheses can contain spaces (and parentheses), so
+// the fields are counted from the end of it
+fn parse_stat(stat: &str) -> Option<Stat> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields: Vec<_> = rest.split_whitespace().collect();
+    let tpgid: i32 = fields.get(5)?.parse().ok()?;
+    Some(Stat {
+        ppid: fields.get(1)?.parse().ok()?,
+        session: fields.get(3)?.parse().ok()?,
+        // this is -1 for processes without a controlling terminal
+        tpgid: std::convert::TryFrom::try_from(tpgid).ok(),
+    })
+}
+
+fn git_info(cwd: &str) -> Option<GitInfo> {
+    let output = std::process::Command::new("git")
+        .args(&[
+            "-C",
+            cwd,
+            "rev-parse",
+            "--show-toplevel",
+            "HEAD",
+            "--abbrev-ref",
+            "HEAD",
+        ])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+    let mut lines = output.lines();
+    Some(GitInfo {
+        root: lines.next()?.to_string(),
+        commit: lines.next()?.to_string(),
+        branch: lines.next()?.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        assert_eq!(
+            parse_stat("1234 (zsh) S 1000 1234 1234 34817 5678 4194304"),
+            Some(Stat {
+                ppid: 1000,
+                session: 1234,
+                tpgid: Some(5678),
+            })
+        );
+        assert_eq!(
+            parse_stat("1234 (my (weird) cmd) R 42 1234 1200 34817 -1 0"),
+            Some(Stat {
+                ppid: 42,
+                session: 1200,
+                tpgid: None,
+            })
+        );
+        assert_eq!(parse_stat("1234 zsh S 1000 1234 1234 34817 5678"), None);
+        assert_eq!(parse_stat("1234 (zsh) S 1000"), None);
+    }
+}
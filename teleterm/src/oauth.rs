@@ -7,19 +7,32 @@ pub const CLI_REDIRECT_URL: &str = "http://localhost:44141/oauth";
 pub struct Oauth {
     client: oauth2::basic::BasicClient,
     user_id: String,
+    scopes: Vec<String>,
+    auth_params: Vec<(String, String)>,
 }
 
 impl Oauth {
     pub fn new(config: Config, user_id: String) -> Self {
+        let scopes = config.scopes.clone();
+        let auth_params = config.auth_params.clone();
         let client = config.into_basic_client();
-        Self { client, user_id }
+        Self {
+            client,
+            user_id,
+            scopes,
+            auth_params,
+        }
     }
 
-    pub fn generate_authorize_url(&self) -> String {
-        let (auth_url, _) = self
-            .client
-            .authorize_url(oauth2::CsrfToken::new_random)
-            .url();
+    pub fn generate_authorize_url(&self, state: oauth2::CsrfToken) -> String {
+        let mut req = self.client.authorize_url(move || state);
+        for scope in &self.scopes {
+            req = req.add_scope(oauth2::Scope::new(scope.clone()));
+        }
+        for (name, value) in &self.auth_params {
+            req = req.add_extra_param(name.clone(), value.clone());
+        }
+        let (auth_url, _) = req.url();
         auth_url.to_string()
     }
 
@@ -41,8 +54,12 @@ impl Oauth {
                 Error::ExchangeCode { msg }
             })
             .and_then(|token| {
-                cache_refresh_token(token_cache_file, &token)
-                    .map(move |_| token.access_token().secret().to_string())
+                let refresh_token = token
+                    .refresh_token()
+                    .map_or("", |token| token.secret().as_str());
+                let access_token = token.access_token().secret().to_string();
+                cache_tokens(token_cache_file, refresh_token, &access_token)
+                    .map(move |_| access_token)
             });
         Box::new(fut)
     }
@@ -51,26 +68,48 @@ impl Oauth {
         self,
     ) -> Box<dyn futures::Future<Item = String, Error = Error> + Send> {
         let token_cache_file = self.server_token_file(false).unwrap();
-        let fut = load_refresh_token(&token_cache_file).and_then(
-            move |refresh_token| {
-                // XXX
-                let refresh_token = refresh_token.unwrap();
-                self.client
-                    .exchange_refresh_token(&oauth2::RefreshToken::new(
-                        refresh_token,
-                    ))
-                    .request_future(oauth2::reqwest::future_http_client)
-                    .map_err(|e| {
-                        let msg = stringify_oauth2_http_error(&e);
-                        Error::ExchangeRefreshToken { msg }
-                    })
-                    .and_then(move |token| {
-                        cache_refresh_token(token_cache_file, &token).map(
-                            move |_| {
-                                token.access_token().secret().to_string()
-                            },
-                        )
-                    })
+        let fut = load_tokens(&token_cache_file).and_then(
+            move |(refresh_token, access_token)| {
+                // some providers (like github) hand out access tokens which
+                // never expire, and so don't give us a refresh token
+                let refresh_token = match (refresh_token, access_token) {
+                    (Some(refresh_token), _) => refresh_token,
+                    (None, Some(access_token)) => {
+                        return futures::future::Either::A(
+                            futures::future::ok(access_token),
+                        );
+                    }
+                    // XXX
+                    (None, None) => unreachable!(),
+                };
+                futures::future::Either::B(
+                    self.client
+                        .exchange_refresh_token(&oauth2::RefreshToken::new(
+                            refresh_token.clone(),
+                        ))
+                        .request_future(oauth2::reqwest::future_http_client)
+                        .map_err(|e| {
+                            let msg = stringify_oauth2_http_error(&e);
+                            Error::ExchangeRefreshToken { msg }
+                        })
+                        .and_then(move |token| {
+                            // the refresh token is allowed to stay the same,
+                            // in which case it won't be sent again
+                            let refresh_token = token
+                                .refresh_token()
+                                .map_or(refresh_token, |token| {
+                                    token.secret().to_string()
+                                });
+                            let access_token =
+                                token.access_token().secret().to_string();
+                            cache_tokens(
+                                token_cache_file,
+                                &refresh_token,
+                                &access_token,
+                            )
+                            .map(move |_| access_token)
+                        }),
+                )
             },
         );
         Box::new(fut)
@@ -85,9 +124,16 @@ impl Oauth {
     }
 }
 
-fn load_refresh_token(
+// returns the cached refresh token and access token. the refresh token is
+// left empty when the provider didn't give us one.
+fn load_tokens(
     token_cache_file: &std::path::Path,
-) -> Box<dyn futures::Future<Item = Option<String>, Error = Error> + Send> {
+) -> Box<
+    dyn futures::Future<
+            Item = (Option<String>, Option<String>),
+            Error = Error,
+        > + Send,
+> {
     let token_cache_file = token_cache_file.to_path_buf();
     Box::new(
         tokio::fs::File::open(token_cache_file.clone())
@@ -96,23 +142,24 @@ fn load_refresh_token(
             })
             .and_then(|file| {
                 tokio::io::lines(std::io::BufReader::new(file))
-                    .into_future()
-                    .map_err(|(e, _)| e)
+                    .collect()
                     .context(crate::error::ReadFile)
             })
-            .map(|(refresh_token, _)| refresh_token),
+            .map(|lines| {
+                let token = |i: usize| {
+                    lines.get(i).filter(|line| !line.is_empty()).cloned()
+                };
+                (token(0), token(1))
+            }),
     )
 }
 
-fn cache_refresh_token(
+fn cache_tokens(
     token_cache_file: std::path::PathBuf,
-    token: &oauth2::basic::BasicTokenResponse,
+    refresh_token: &str,
+    access_token: &str,
 ) -> Box<dyn futures::Future<Item = (), Error = Error> + Send> {
-    let token_data = format!(
-        "{}\n{}\n",
-        token.refresh_token().unwrap().secret(),
-        token.access_token().secret(),
-    );
+    let token_data = format!("{}\n{}\n", refresh_token, access_token);
     let fut = tokio::fs::File::create(token_cache_file.clone())
         .with_context(move || crate::error::CreateFile {
             filename: token_cache_file.to_string_lossy().to_string(),
@@ -132,6 +179,8 @@ pub struct Config {
     auth_url: url::Url,
     token_url: url::Url,
     redirect_url: url::Url,
+    scopes: Vec<String>,
+    auth_params: Vec<(String, String)>,
 }
 
 impl Config {
@@ -148,6 +197,8 @@ impl Config {
             auth_url,
             token_url,
             redirect_url,
+            scopes: vec![],
+            auth_params: vec![],
         }
     }
 
@@ -155,6 +206,15 @@ impl Config {
         self.redirect_url = url;
     }
 
+    pub fn add_scope(&mut self, scope: &str) {
+        self.scopes.push(scope.to_string());
+    }
+
+    // extra query parameters to send to the authorization url
+    pub fn add_auth_param(&mut self, name: &str, value: &str) {
+        self.auth_params.push((name.to_string(), value.to_string()));
+    }
+
     fn into_basic_client(self) -> oauth2::basic::BasicClient {
         oauth2::basic::BasicClient::new(
             oauth2::ClientId::new(self.client_id),
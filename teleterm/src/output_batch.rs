@@ -0,0 +1,121 @@
+use crate::prelude::*;
+
+// how long to hold on to terminal output waiting for more to arrive
+const MAX_DELAY: std::time::Duration = std::time::Duration::from_millis(10);
+// and how much output to collect before sending it regardless
+const MAX_BYTES: usize = 16 * 1024;
+
+// merges terminal output which is queued in quick succession into a single
+// message. programs like `yes` or progress bars can otherwise generate
+// thousands of tiny messages a second, each of which costs a frame and a
+// write of its own.
+pub struct OutputBatch {
+    timer: Option<tokio::timer::Delay>,
+}
+
+impl OutputBatch {
+    pub fn new() -> Self {
+        Self { timer: None }
+    }
+
+    // queues a message, appending it to the previous one instead if they
+    // are both terminal output and there is still room
+    pub fn push(
+        &mut self,
+        queue: &mut std::collections::VecDeque<crate::protocol::Message>,
+        msg: crate::protocol::Message,
+    ) {
+        if let crate::protocol::Message::TerminalOutput { data } = &msg {
+            if let Some(crate::protocol::Message::TerminalOutput {
+                data: batch,
+            }) = queue.back_mut()
+            {
+                if batch.len() + data.len() <= MAX_BYTES {
+                    batch.extend_from_slice(data);
+                    return;
+                }
+            }
+            self.timer = Some(tokio::timer::Delay::new(
+                std::time::Instant::now() + MAX_DELAY,
+            ));
+        }
+        queue.push_back(msg);
+    }
+
+    // resolves when the message at the front of the queue is ready to be
+    // sent. only the last message in the queue can still be collecting
+    // output, since anything queued after it ends the batch.
+    pub fn poll_ready(
+        &mut self,
+        queue: &std::collections::VecDeque<crate::protocol::Message>,
+    ) -> futures::Poll<(), Error> {
+        if queue.len() != 1 {
+            return Ok(futures::Async::Ready(()));
+        }
+        if let Some(crate::protocol::Message::TerminalOutput { data }) =
+            queue.front()
+        {
+            if data.len() < MAX_BYTES {
+                if let Some(timer) = &mut self.timer {
+                    futures::try_ready!(timer
+                        .poll()
+                        .context(crate::error::TimerOutputBatch));
+                    self.timer = None;
+                }
+            }
+        }
+        Ok(futures::Async::Ready(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_push() {
+        let mut batch = OutputBatch::new();
+        let mut queue = std::collections::VecDeque::new();
+
+        batch.push(
+            &mut queue,
+            crate::protocol::Message::terminal_output(b"foo"),
+        );
+        batch.push(
+            &mut queue,
+            crate::protocol::Message::terminal_output(b"bar"),
+        );
+        assert_eq!(
+            queue,
+            vec![crate::protocol::Message::terminal_output(b"foobar")]
+        );
+
+        // other messages end the batch
+        batch.push(&mut queue, crate::protocol::Message::heartbeat());
+        batch.push(
+            &mut queue,
+            crate::protocol::Message::terminal_output(b"baz"),
+        );
+        assert_eq!(
+            queue,
+            vec![
+                crate::protocol::Message::terminal_output(b"foobar"),
+                crate::protocol::Message::heartbeat(),
+                crate::protocol::Message::terminal_output(b"baz"),
+            ]
+        );
+
+        // and so does running out of room
+        let big = vec![b'a'; MAX_BYTES];
+        batch.push(
+            &mut queue,
+            crate::protocol::Message::terminal_output(&big),
+        );
+        assert_eq!(queue.len(), 4);
+        batch.push(
+            &mut queue,
+            crate::protocol::Message::terminal_output(b"quux"),
+        );
+        assert_eq!(queue.len(), 5);
+    }
+}
@@ -12,14 +12,111 @@ pub struct Session {
     pub size: crate::term::Size,
     pub idle_time: u32,
     pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    // empty for streams which haven't joined a room
+    pub room: String,
+    // the streamer has stopped sending output for now
+    pub paused: bool,
     pub watchers: u32,
+    // seconds since the stream started
+    pub uptime: u32,
+    // seconds since the unix epoch
+    pub start_time: u32,
 }
 
+// a stream which has already ended, as kept in the server's history
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PastSession {
+    pub username: String,
+    pub title: String,
+    pub description: String,
+    // both in seconds since the unix epoch
+    pub start_time: u32,
+    pub end_time: u32,
+    pub bytes: u64,
+}
+
+// a connection to the server, as shown to administrators
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct AdminConnection {
+    pub id: String,
+    pub address: String,
+    // empty for connections which haven't logged in yet
+    pub username: String,
+    pub state: String,
+    // empty unless the connection is watching a stream
+    pub watch_id: String,
+    // seconds since the connection was made
+    pub connected: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ServerStats {
+    // seconds since the server started
+    pub uptime: u32,
+    pub connections: u32,
+    pub streams: u32,
+    pub watchers: u32,
+    // counted since the server started
+    pub total_connections: u32,
+    pub total_streams: u32,
+}
+
+// which connections a bulk admin action applies to. these are sent as
+// strings (user:USERNAME, tag:TAG, or address:ADDRESS) so that new kinds of
+// filters don't need a new message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminFilter {
+    // everything logged in as this user
+    User(String),
+    // streams with this tag
+    Tag(String),
+    // everything connected from this address
+    Address(std::net::IpAddr),
+}
+
+impl std::convert::TryFrom<&str> for AdminFilter {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(2, ':');
+        let ty = parts.next().unwrap();
+        let value = parts
+            .next()
+            .filter(|value| !value.is_empty())
+            .context(crate::error::InvalidAdminFilter { filter: s })?;
+        Ok(match ty {
+            "user" => Self::User(value.to_string()),
+            "tag" => Self::Tag(value.to_string()),
+            "address" => {
+                Self::Address(value.parse().ok().context(
+                    crate::error::InvalidAdminFilter { filter: s },
+                )?)
+            }
+            _ => {
+                return Err(Error::InvalidAdminFilter {
+                    filter: s.to_string(),
+                })
+            }
+        })
+    }
+}
+
+impl PastSession {
+    pub fn duration(&self) -> u32 {
+        self.end_time.saturating_sub(self.start_time)
+    }
+}
+
+// the second field is the protocol version that incoming messages are
+// expected to be encoded with
 pub struct FramedReader<T: tokio::io::AsyncRead>(
     tokio::codec::FramedRead<
         T,
         tokio::codec::length_delimited::LengthDelimitedCodec,
     >,
+    u8,
 );
 
 impl<T: tokio::io::AsyncRead> FramedReader<T> {
@@ -27,29 +124,142 @@ impl<T: tokio::io::AsyncRead> FramedReader<T> {
         Self(
             tokio::codec::length_delimited::Builder::new()
                 .length_field_length(4)
+                .max_frame_length(MAX_FRAME_LEN)
                 .new_read(rs),
+            PROTO_VERSION,
         )
     }
+
+    pub fn set_proto_version(&mut self, proto_version: u8) {
+        self.1 = proto_version;
+    }
 }
 
 pub struct FramedWriter<T: tokio::io::AsyncWrite>(
-    tokio::codec::FramedWrite<
-        T,
-        tokio::codec::length_delimited::LengthDelimitedCodec,
-    >,
+    tokio::codec::FramedWrite<T, MessageEncoder>,
 );
 
 impl<T: tokio::io::AsyncWrite> FramedWriter<T> {
     pub fn new(ws: T) -> Self {
-        Self(
-            tokio::codec::length_delimited::Builder::new()
-                .length_field_length(4)
-                .new_write(ws),
-        )
+        Self(tokio::codec::FramedWrite::new(
+            ws,
+            MessageEncoder {
+                proto_version: PROTO_VERSION,
+            },
+        ))
+    }
+
+    pub fn set_proto_version(&mut self, proto_version: u8) {
+        self.0.encoder_mut().proto_version = proto_version;
+    }
+
+    // adds a message to the write buffer without writing it yet, so that
+    // several messages can go out in a single write. hands the message back
+    // if the buffer is too full to take it right now.
+    pub fn queue(&mut self, msg: Message) -> Result<Option<Message>> {
+        match self.0.start_send(msg).context(crate::error::WritePacket)? {
+            futures::AsyncSink::Ready => Ok(None),
+            futures::AsyncSink::NotReady(msg) => Ok(Some(msg)),
+        }
+    }
+
+    pub fn flush(self) -> impl futures::Future<Item = Self, Error = Error> {
+        self.0
+            .flush()
+            .map(FramedWriter)
+            .context(crate::error::WritePacket)
+    }
+}
+
+// encodes messages (along with their length prefix) directly into the
+// writer's buffer, which lives as long as the connection does, so sending a
+// message doesn't need a separately allocated packet which then gets copied
+// into the buffer. messages are encoded in the layout of the protocol
+// version which was negotiated with the peer.
+pub struct MessageEncoder {
+    proto_version: u8,
+}
+
+impl tokio::codec::Encoder for MessageEncoder {
+    type Item = Message;
+    type Error = std::io::Error;
+
+    fn encode(
+        &mut self,
+        msg: Message,
+        dst: &mut bytes::BytesMut,
+    ) -> std::io::Result<()> {
+        let len_size = std::mem::size_of::<u32>();
+        let start = dst.len();
+        dst.extend_from_slice(&[0; std::mem::size_of::<u32>()]);
+        encode_message(&msg, self.proto_version, dst);
+        let len: u32 = (dst.len() - start - len_size).try_into().unwrap();
+        dst[start..start + len_size].copy_from_slice(&len.to_be_bytes());
+        Ok(())
+    }
+}
+
+// version 2 added fields to StartStreaming, StartWatching, Session, and
+// Welcome, along with every message type after OauthWebResponse other than
+// the Hello/Welcome handshake
+pub const PROTO_VERSION: u8 = 2;
+
+// the oldest protocol version that we still know how to speak. peers which
+// speak a newer version than we do are expected to fall back to ours.
+pub const MIN_PROTO_VERSION: u8 = 1;
+
+// picks the version to use with a client. clients which speak a newer
+// version than we do are expected to fall back to ours, but there is
+// nothing we can do for clients which are too old.
+pub fn negotiate_proto_version(proto_version: u8) -> Result<u8> {
+    if proto_version < MIN_PROTO_VERSION {
+        return Err(Error::ClientProtocolTooOld {
+            version: proto_version,
+            min: MIN_PROTO_VERSION,
+        });
     }
+    Ok(proto_version.min(PROTO_VERSION))
 }
 
-pub const PROTO_VERSION: u8 = 1;
+// frames bigger than this are rejected before anything is allocated for
+// them (this is also the default for tokio's length delimited codec)
+pub const MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+// usernames, room names, titles, and messages end up on other people's
+// screens, so there's no reason for them to be anywhere near as big as a
+// frame can be
+pub const MAX_USERNAME_LEN: usize = 256;
+pub const MAX_ROOM_LEN: usize = 256;
+pub const MAX_TITLE_LEN: usize = 1024;
+pub const MAX_TEXT_LEN: usize = 4096;
+pub const MAX_TERM_TYPE_LEN: usize = 256;
+pub const MAX_ALLOWED_WATCHERS: usize = 1024;
+
+// optional features that the server supports, sent to clients in the
+// Welcome message so that they can avoid relying on features which older
+// servers don't have
+pub const CAPABILITIES: &[&str] = &[
+    "admin",
+    "admin-matching",
+    "ban",
+    "chat",
+    "control",
+    "history",
+    "host-key",
+    "notice",
+    "pause",
+    "resume",
+    "rewind",
+    "rooms",
+    "server-limits",
+    "session-changes",
+    "session-updates",
+    "time-limit",
+    "watcher-count",
+];
+
+// watchers who start watching at an offset are sent the missed output this
+// many times faster than it originally happened, until they catch up
+pub const CATCHUP_SPEED: u32 = 4;
 
 #[repr(u8)]
 #[derive(
@@ -116,6 +326,8 @@ impl std::convert::TryFrom<&str> for AuthClient {
 pub enum AuthType {
     Plain = 0,
     RecurseCenter,
+    Github,
+    Google,
 }
 
 impl AuthType {
@@ -123,6 +335,8 @@ impl AuthType {
         match self {
             Self::Plain => "plain",
             Self::RecurseCenter => "recurse_center",
+            Self::Github => "github",
+            Self::Google => "google",
         }
     }
 
@@ -130,6 +344,8 @@ impl AuthType {
         match self {
             Self::Plain => false,
             Self::RecurseCenter => true,
+            Self::Github => true,
+            Self::Google => true,
         }
     }
 
@@ -162,6 +378,8 @@ impl std::convert::TryFrom<u8> for AuthType {
         Ok(match n {
             0 => Self::Plain,
             1 => Self::RecurseCenter,
+            2 => Self::Github,
+            3 => Self::Google,
             _ => return Err(Error::InvalidAuthType { ty: n }),
         })
     }
@@ -174,6 +392,8 @@ impl std::convert::TryFrom<&str> for AuthType {
         Ok(match s {
             s if Self::Plain.name() == s => Self::Plain,
             s if Self::RecurseCenter.name() == s => Self::RecurseCenter,
+            s if Self::Github.name() == s => Self::Github,
+            s if Self::Google.name() == s => Self::Google,
             _ => return Err(Error::InvalidAuthTypeStr { ty: s.to_string() }),
         })
     }
@@ -185,6 +405,8 @@ impl std::convert::TryFrom<&str> for AuthType {
 pub enum Auth {
     Plain { username: String },
     RecurseCenter { id: Option<String> },
+    Github { id: Option<String> },
+    Google { id: Option<String> },
 }
 
 impl Auth {
@@ -200,6 +422,18 @@ impl Auth {
         }
     }
 
+    pub fn github(id: Option<&str>) -> Self {
+        Self::Github {
+            id: id.map(std::string::ToString::to_string),
+        }
+    }
+
+    pub fn google(id: Option<&str>) -> Self {
+        Self::Google {
+            id: id.map(std::string::ToString::to_string),
+        }
+    }
+
     pub fn is_oauth(&self) -> bool {
         self.auth_type().is_oauth()
     }
@@ -213,7 +447,9 @@ impl Auth {
 
     pub fn oauth_id(&self) -> Option<&str> {
         match self {
-            Self::RecurseCenter { id, .. } => {
+            Self::RecurseCenter { id, .. }
+            | Self::Github { id, .. }
+            | Self::Google { id, .. } => {
                 id.as_ref().map(std::string::String::as_str)
             }
             _ => None,
@@ -228,6 +464,8 @@ impl Auth {
         match self {
             Self::Plain { .. } => AuthType::Plain,
             Self::RecurseCenter { .. } => AuthType::RecurseCenter,
+            Self::Github { .. } => AuthType::Github,
+            Self::Google { .. } => AuthType::Google,
         }
     }
 }
@@ -250,6 +488,46 @@ pub enum MessageType {
     OauthCliResponse,
     OauthWebRequest,
     OauthWebResponse,
+    Chat,
+    RequestControl,
+    GrantControl,
+    RevokeControl,
+    TerminalInput,
+    ListHistory,
+    History,
+    ServerShutdown,
+    HostKeyRequest,
+    HostKey,
+    Hello,
+    Welcome,
+    UpdateMetadata,
+    SubscribeSessionList,
+    SessionAdded,
+    SessionRemoved,
+    SessionUpdated,
+    ResumeStreaming,
+    SubscribeSessionChanges,
+    SessionChanged,
+    SubscribeTimeLimit,
+    TimeLimit,
+    SubscribeServerLimits,
+    ServerLimits,
+    AdminListConnections,
+    AdminConnections,
+    AdminKick,
+    AdminNotice,
+    AdminGetStats,
+    AdminStats,
+    AdminDone,
+    SubscribeNotices,
+    Notice,
+    SubscribeWatcherCount,
+    WatcherCount,
+    PauseStreaming,
+    AdminBan,
+    JoinRoom,
+    ListRoomSessions,
+    AdminKickMatching,
 }
 
 impl std::convert::TryFrom<u8> for MessageType {
@@ -272,6 +550,46 @@ impl std::convert::TryFrom<u8> for MessageType {
             12 => Self::OauthCliResponse,
             13 => Self::OauthWebRequest,
             14 => Self::OauthWebResponse,
+            15 => Self::Chat,
+            16 => Self::RequestControl,
+            17 => Self::GrantControl,
+            18 => Self::RevokeControl,
+            19 => Self::TerminalInput,
+            20 => Self::ListHistory,
+            21 => Self::History,
+            22 => Self::ServerShutdown,
+            23 => Self::HostKeyRequest,
+            24 => Self::HostKey,
+            25 => Self::Hello,
+            26 => Self::Welcome,
+            27 => Self::UpdateMetadata,
+            28 => Self::SubscribeSessionList,
+            29 => Self::SessionAdded,
+            30 => Self::SessionRemoved,
+            31 => Self::SessionUpdated,
+            32 => Self::ResumeStreaming,
+            33 => Self::SubscribeSessionChanges,
+            34 => Self::SessionChanged,
+            35 => Self::SubscribeTimeLimit,
+            36 => Self::TimeLimit,
+            37 => Self::SubscribeServerLimits,
+            38 => Self::ServerLimits,
+            39 => Self::AdminListConnections,
+            40 => Self::AdminConnections,
+            41 => Self::AdminKick,
+            42 => Self::AdminNotice,
+            43 => Self::AdminGetStats,
+            44 => Self::AdminStats,
+            45 => Self::AdminDone,
+            46 => Self::SubscribeNotices,
+            47 => Self::Notice,
+            48 => Self::SubscribeWatcherCount,
+            49 => Self::WatcherCount,
+            50 => Self::PauseStreaming,
+            51 => Self::AdminBan,
+            52 => Self::JoinRoom,
+            53 => Self::ListRoomSessions,
+            54 => Self::AdminKickMatching,
             _ => return Err(Error::InvalidMessageType { ty: n }),
         })
     }
@@ -288,9 +606,13 @@ pub enum Message {
         term_type: String,
         size: crate::term::Size,
     },
-    StartStreaming,
+    StartStreaming {
+        allowed_watchers: Vec<String>,
+        description: String,
+    },
     StartWatching {
         id: String,
+        offset: u32,
     },
     Heartbeat,
     TerminalOutput {
@@ -323,6 +645,121 @@ pub enum Message {
     OauthWebResponse {
         access_token: String,
     },
+    Chat {
+        from: String,
+        text: String,
+    },
+    RequestControl {
+        username: String,
+    },
+    GrantControl {
+        username: String,
+    },
+    RevokeControl {
+        username: String,
+    },
+    TerminalInput {
+        data: Vec<u8>,
+    },
+    ListHistory,
+    History {
+        sessions: Vec<PastSession>,
+    },
+    ServerShutdown {
+        reason: String,
+    },
+    HostKeyRequest {
+        nonce: Vec<u8>,
+    },
+    HostKey {
+        public_key: Vec<u8>,
+        signature: Vec<u8>,
+    },
+    Hello {
+        proto_version: u8,
+    },
+    Welcome {
+        proto_version: u8,
+        heartbeat_interval: u32,
+        capabilities: Vec<String>,
+    },
+    UpdateMetadata {
+        title: String,
+        tags: Vec<String>,
+    },
+    SubscribeSessionList,
+    SessionAdded {
+        session: Session,
+    },
+    SessionRemoved {
+        id: String,
+    },
+    SessionUpdated {
+        session: Session,
+    },
+    ResumeStreaming {
+        token: String,
+    },
+    SubscribeSessionChanges,
+    SessionChanged {
+        title: Option<String>,
+        tags: Option<Vec<String>>,
+        paused: Option<bool>,
+    },
+    SubscribeTimeLimit,
+    TimeLimit {
+        remaining: u32,
+    },
+    SubscribeServerLimits,
+    ServerLimits {
+        connections: u32,
+        max_connections: u32,
+        max_watchers: u32,
+    },
+    AdminListConnections,
+    AdminConnections {
+        connections: Vec<AdminConnection>,
+    },
+    AdminKick {
+        id: String,
+        reason: String,
+    },
+    AdminNotice {
+        text: String,
+    },
+    AdminGetStats,
+    AdminStats {
+        stats: ServerStats,
+    },
+    AdminDone {
+        count: u32,
+    },
+    SubscribeNotices,
+    Notice {
+        text: String,
+    },
+    SubscribeWatcherCount,
+    WatcherCount {
+        count: u32,
+    },
+    PauseStreaming {
+        paused: bool,
+    },
+    AdminBan {
+        entry: String,
+    },
+    JoinRoom {
+        room: String,
+    },
+    ListRoomSessions {
+        room: String,
+    },
+    AdminKickMatching {
+        filter: String,
+        reason: String,
+        ban: bool,
+        dry_run: bool,
+    },
 }
 
 impl Message {
@@ -336,17 +773,27 @@ impl Message {
             proto_version: PROTO_VERSION,
             auth: auth.clone(),
             auth_client,
-            term_type: term_type.to_string(),
+            term_type: truncate(term_type, MAX_TERM_TYPE_LEN).to_string(),
             size,
         }
     }
 
-    pub fn start_streaming() -> Self {
-        Self::StartStreaming
+    pub fn start_streaming(
+        allowed_watchers: &[String],
+        description: &str,
+    ) -> Self {
+        Self::StartStreaming {
+            allowed_watchers: allowed_watchers.to_vec(),
+            description: truncate(description, MAX_TEXT_LEN).to_string(),
+        }
     }
 
-    pub fn start_watching(id: &str) -> Self {
-        Self::StartWatching { id: id.to_string() }
+    // offset is the number of seconds in the past to start watching from
+    pub fn start_watching(id: &str, offset: u32) -> Self {
+        Self::StartWatching {
+            id: id.to_string(),
+            offset,
+        }
     }
 
     pub fn heartbeat() -> Self {
@@ -412,277 +859,1020 @@ impl Message {
         }
     }
 
-    pub fn message_type(&self) -> MessageType {
-        match self {
-            Self::Login { .. } => MessageType::Login,
-            Self::StartStreaming { .. } => MessageType::StartStreaming,
-            Self::StartWatching { .. } => MessageType::StartWatching,
-            Self::Heartbeat { .. } => MessageType::Heartbeat,
-            Self::TerminalOutput { .. } => MessageType::TerminalOutput,
-            Self::ListSessions { .. } => MessageType::ListSessions,
-            Self::Sessions { .. } => MessageType::Sessions,
-            Self::Disconnected { .. } => MessageType::Disconnected,
-            Self::Error { .. } => MessageType::Error,
-            Self::Resize { .. } => MessageType::Resize,
-            Self::LoggedIn { .. } => MessageType::LoggedIn,
-            Self::OauthCliRequest { .. } => MessageType::OauthCliRequest,
-            Self::OauthCliResponse { .. } => MessageType::OauthCliResponse,
-            Self::OauthWebRequest { .. } => MessageType::OauthWebRequest,
-            Self::OauthWebResponse { .. } => MessageType::OauthWebResponse,
+    // the from field is ignored when sent by a client - the server will fill
+    // it in with the username of the connection that sent it
+    pub fn chat(from: &str, text: &str) -> Self {
+        Self::Chat {
+            from: from.to_string(),
+            text: truncate(text, MAX_TEXT_LEN).to_string(),
         }
     }
 
-    #[allow(dead_code)]
-    pub fn read<R: std::io::Read>(r: R) -> Result<Self> {
-        Packet::read(r).and_then(Self::try_from)
+    // like chat, the username is ignored when sent by a watcher - the server
+    // fills it in before passing the request along to the streamer
+    pub fn request_control(username: &str) -> Self {
+        Self::RequestControl {
+            username: username.to_string(),
+        }
     }
 
-    pub fn read_async<T: tokio::io::AsyncRead>(
-        r: FramedReader<T>,
-    ) -> impl futures::Future<Item = (Self, FramedReader<T>), Error = Error>
-    {
-        Packet::read_async(r).and_then(|(packet, r)| {
-            Self::try_from(packet).map(|msg| (msg, r))
-        })
+    pub fn grant_control(username: &str) -> Self {
+        Self::GrantControl {
+            username: username.to_string(),
+        }
     }
 
-    #[allow(dead_code)]
-    pub fn write<W: std::io::Write>(&self, w: W) -> Result<()> {
-        Packet::from(self).write(w)
+    pub fn revoke_control(username: &str) -> Self {
+        Self::RevokeControl {
+            username: username.to_string(),
+        }
     }
 
-    pub fn write_async<T: tokio::io::AsyncWrite>(
-        &self,
-        w: FramedWriter<T>,
-    ) -> impl futures::Future<Item = FramedWriter<T>, Error = Error> {
-        Packet::from(self).write_async(w)
+    pub fn terminal_input(data: &[u8]) -> Self {
+        Self::TerminalInput {
+            data: data.to_vec(),
+        }
     }
 
-    // it'd be nice if i could just override the Debug implementation for
-    // specific enum variants, but writing the whole impl Debug by hand just
-    // to make this one change would be super obnoxious
-    pub fn format_log(&self) -> String {
-        match self {
-            Self::TerminalOutput { data } => {
-                format!("TerminalOutput {{ data: ({} bytes) }}", data.len())
-            }
+    pub fn list_history() -> Self {
+        Self::ListHistory
+    }
 
-            // these are security-sensitive, keep them out of logs
-            Self::OauthCliRequest { .. } => {
-                "OauthCliRequest {{ .. }}".to_string()
-            }
-            Self::OauthCliResponse { .. } => {
-                "OauthCliResponse {{ .. }}".to_string()
-            }
-            Self::OauthWebRequest { .. } => {
-                "OauthWebRequest {{ .. }}".to_string()
-            }
-            Self::OauthWebResponse { .. } => {
-                "OauthWebResponse {{ .. }}".to_string()
-            }
+    pub fn history(sessions: &[PastSession]) -> Self {
+        Self::History {
+            sessions: sessions.to_vec(),
+        }
+    }
 
-            _ => format!("{:?}", self),
+    pub fn server_shutdown(reason: &str) -> Self {
+        Self::ServerShutdown {
+            reason: reason.to_string(),
         }
     }
-}
 
-struct Packet {
-    ty: u8,
-    data: Vec<u8>,
-}
+    pub fn host_key_request(nonce: &[u8]) -> Self {
+        Self::HostKeyRequest {
+            nonce: nonce.to_vec(),
+        }
+    }
 
-impl Packet {
-    fn read<R: std::io::Read>(mut r: R) -> Result<Self> {
-        let mut len_buf = [0_u8; std::mem::size_of::<u32>()];
-        r.read_exact(&mut len_buf)
-            .context(crate::error::ReadPacket)?;
-        let len = u32::from_be_bytes(len_buf.try_into().unwrap());
-        if (len as usize) < std::mem::size_of::<u8>() {
-            return Err(Error::LenTooSmall {
-                len,
-                expected: std::mem::size_of::<u8>(),
-            });
+    pub fn host_key(public_key: &[u8], signature: &[u8]) -> Self {
+        Self::HostKey {
+            public_key: public_key.to_vec(),
+            signature: signature.to_vec(),
         }
+    }
 
-        let mut data = vec![0_u8; len as usize];
-        r.read_exact(&mut data).context(crate::error::ReadPacket)?;
-        let (ty_buf, rest) = data.split_at(std::mem::size_of::<u8>());
-        let ty = u8::from_be_bytes(ty_buf.try_into().unwrap());
+    pub fn hello() -> Self {
+        Self::Hello {
+            proto_version: PROTO_VERSION,
+        }
+    }
 
-        Ok(Self {
-            ty,
-            data: rest.to_vec(),
-        })
+    // proto_version is the version that the rest of the connection will
+    // use, and heartbeat_interval is the number of seconds clients should
+    // wait between sending heartbeats. version 1 clients don't know about
+    // capabilities.
+    pub fn welcome(proto_version: u8, heartbeat_interval: u32) -> Self {
+        let capabilities = if proto_version > 1 {
+            CAPABILITIES
+                .iter()
+                .map(std::string::ToString::to_string)
+                .collect()
+        } else {
+            vec![]
+        };
+        Self::Welcome {
+            proto_version,
+            heartbeat_interval,
+            capabilities,
+        }
     }
 
-    fn read_async<T: tokio::io::AsyncRead>(
-        r: FramedReader<T>,
-    ) -> impl futures::Future<Item = (Self, FramedReader<T>), Error = Error>
-    {
-        r.0.into_future()
-            .map_err(|(e, _)| Error::ReadPacket { source: e })
-            .and_then(|(data, r)| match data {
-                Some(data) => Ok((data, r)),
-                None => Err(Error::EOF),
-            })
-            .and_then(|(buf, r)| {
-                if buf.len() < std::mem::size_of::<u8>() {
-                    return Err(Error::LenTooSmall {
-                        len: buf.len().try_into().unwrap(),
-                        expected: std::mem::size_of::<u8>(),
-                    });
-                }
-                let (ty_buf, data_buf) =
-                    buf.split_at(std::mem::size_of::<u8>());
-                let ty = u8::from_be_bytes(ty_buf.try_into().unwrap());
-                let data = data_buf.to_vec();
-                Ok((Self { ty, data }, FramedReader(r)))
-            })
+    // an empty title means to use the title set by the streamed terminal.
+    // anything too long for the server to accept is cut short.
+    pub fn update_metadata(title: &str, tags: &[String]) -> Self {
+        Self::UpdateMetadata {
+            title: truncate(title, MAX_TITLE_LEN).to_string(),
+            tags: tags
+                .iter()
+                .map(|tag| truncate(tag, MAX_TITLE_LEN).to_string())
+                .collect(),
+        }
     }
 
-    fn write<W: std::io::Write>(&self, mut w: W) -> Result<()> {
-        let bytes = self.as_bytes();
-        let len: u32 = bytes.len().try_into().unwrap();
-        let len_buf = len.to_be_bytes();
-        let buf: Vec<u8> =
-            len_buf.iter().chain(bytes.iter()).copied().collect();
-        Ok(w.write_all(&buf).context(crate::error::WritePacket)?)
+    // after the server replies with the full list of sessions, it sends the
+    // changes to that list as they happen instead of needing to be asked
+    // again
+    pub fn subscribe_session_list() -> Self {
+        Self::SubscribeSessionList
     }
 
-    fn write_async<T: tokio::io::AsyncWrite>(
-        &self,
-        w: FramedWriter<T>,
-    ) -> impl futures::Future<Item = FramedWriter<T>, Error = Error> {
-        w.0.send(bytes::Bytes::from(self.as_bytes()))
-            .map(FramedWriter)
-            .context(crate::error::WritePacket)
+    pub fn session_added(session: &Session) -> Self {
+        Self::SessionAdded {
+            session: session.clone(),
+        }
     }
 
-    fn as_bytes(&self) -> Vec<u8> {
-        self.ty
-            .to_be_bytes()
-            .iter()
-            .chain(self.data.iter())
-            .cloned()
-            .collect()
+    pub fn session_removed(id: &str) -> Self {
+        Self::SessionRemoved { id: id.to_string() }
     }
-}
 
-impl From<&Message> for Packet {
-    fn from(msg: &Message) -> Self {
-        fn u32_from_usize(n: usize) -> u32 {
-            n.try_into().unwrap()
+    pub fn session_updated(session: &Session) -> Self {
+        Self::SessionUpdated {
+            session: session.clone(),
         }
-        fn write_u32(val: u32, data: &mut Vec<u8>) {
-            data.extend_from_slice(&val.to_be_bytes());
-        }
-        fn write_u16(val: u16, data: &mut Vec<u8>) {
-            data.extend_from_slice(&val.to_be_bytes());
-        }
-        fn write_u8(val: u8, data: &mut Vec<u8>) {
-            data.extend_from_slice(&val.to_be_bytes());
-        }
-        fn write_bytes(val: &[u8], data: &mut Vec<u8>) {
-            write_u32(u32_from_usize(val.len()), data);
-            data.extend_from_slice(val);
-        }
-        fn write_str(val: &str, data: &mut Vec<u8>) {
-            write_bytes(val.as_bytes(), data);
-        }
-        fn write_size(val: crate::term::Size, data: &mut Vec<u8>) {
-            write_u16(val.rows, data);
-            write_u16(val.cols, data);
-        }
-        fn write_session(val: &Session, data: &mut Vec<u8>) {
-            write_str(&val.id, data);
-            write_str(&val.username, data);
-            write_str(&val.term_type, data);
-            write_size(val.size, data);
-            write_u32(val.idle_time, data);
-            write_str(&val.title, data);
-            write_u32(val.watchers, data);
-        }
-        fn write_sessions(val: &[Session], data: &mut Vec<u8>) {
-            write_u32(u32_from_usize(val.len()), data);
-            for s in val {
-                write_session(s, data);
-            }
-        }
-        fn write_auth(val: &Auth, data: &mut Vec<u8>) {
-            write_u8(val.auth_type() as u8, data);
-            match val {
-                Auth::Plain { username } => {
-                    write_str(username, data);
-                }
-                Auth::RecurseCenter { id } => {
-                    let id = id.as_ref().map_or("", |s| s.as_str());
-                    write_str(id, data);
-                }
-            }
+    }
+
+    // sent before StartStreaming, so that if this connection drops, a new
+    // connection which sends the same token can take over the stream
+    pub fn resume_streaming(token: &str) -> Self {
+        Self::ResumeStreaming {
+            token: token.to_string(),
         }
+    }
 
-        let ty = msg.message_type() as u8;
-        let mut data = vec![];
+    // asks to be sent SessionChanged messages about the session being
+    // watched. changes to the size are already sent as Resize messages.
+    pub fn subscribe_session_changes() -> Self {
+        Self::SubscribeSessionChanges
+    }
 
-        match msg {
-            Message::Login {
-                proto_version,
-                auth,
-                auth_client,
-                term_type,
-                size,
-            } => {
-                write_u8(*proto_version, &mut data);
-                write_auth(auth, &mut data);
-                write_u8(*auth_client as u8, &mut data);
-                write_str(term_type, &mut data);
-                write_size(*size, &mut data);
-            }
-            Message::StartStreaming => {}
-            Message::StartWatching { id } => {
-                write_str(id, &mut data);
-            }
-            Message::Heartbeat => {}
-            Message::TerminalOutput { data: output } => {
-                write_bytes(output, &mut data);
-            }
-            Message::ListSessions => {}
-            Message::Sessions { sessions } => {
-                write_sessions(sessions, &mut data);
-            }
-            Message::Disconnected => {}
-            Message::Error { msg } => {
-                write_str(msg, &mut data);
-            }
-            Message::Resize { size } => {
-                write_size(*size, &mut data);
-            }
-            Message::LoggedIn { username } => {
-                write_str(username, &mut data);
-            }
-            Message::OauthCliRequest { url, id } => {
-                write_str(url, &mut data);
-                write_str(id, &mut data);
-            }
-            Message::OauthCliResponse { code } => {
-                write_str(code, &mut data);
-            }
-            Message::OauthWebRequest { id } => {
-                write_str(id, &mut data);
-            }
-            Message::OauthWebResponse { access_token } => {
-                write_str(access_token, &mut data);
-            }
+    // only the fields which actually changed are included
+    pub fn session_changed(
+        title: Option<&str>,
+        tags: Option<&[String]>,
+        paused: Option<bool>,
+    ) -> Self {
+        Self::SessionChanged {
+            title: title.map(std::string::ToString::to_string),
+            tags: tags.map(<[String]>::to_vec),
+            paused,
         }
+    }
 
-        Self { ty, data }
+    // asks to be warned with TimeLimit messages before the server ends the
+    // stream for running too long
+    pub fn subscribe_time_limit() -> Self {
+        Self::SubscribeTimeLimit
     }
-}
 
-impl std::convert::TryFrom<Packet> for Message {
+    // remaining is the number of seconds left before the stream is ended,
+    // and zero means that it just was
+    pub fn time_limit(remaining: u32) -> Self {
+        Self::TimeLimit { remaining }
+    }
+
+    // asks to be sent ServerLimits messages along with the session list
+    pub fn subscribe_server_limits() -> Self {
+        Self::SubscribeServerLimits
+    }
+
+    // connections is the number of clients currently connected to the
+    // server. a zero max_connections or max_watchers (per session) means
+    // that there is no limit.
+    pub fn server_limits(
+        connections: u32,
+        max_connections: u32,
+        max_watchers: u32,
+    ) -> Self {
+        Self::ServerLimits {
+            connections,
+            max_connections,
+            max_watchers,
+        }
+    }
+
+    // the admin messages are only accepted from the usernames the server
+    // was configured to treat as administrators
+    pub fn admin_list_connections() -> Self {
+        Self::AdminListConnections
+    }
+
+    pub fn admin_connections(connections: &[AdminConnection]) -> Self {
+        Self::AdminConnections {
+            connections: connections.to_vec(),
+        }
+    }
+
+    // id is the id of the connection, as given in AdminConnections
+    pub fn admin_kick(id: &str, reason: &str) -> Self {
+        Self::AdminKick {
+            id: id.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    // sent to everyone connected, as a Notice
+    pub fn admin_notice(text: &str) -> Self {
+        Self::AdminNotice {
+            text: truncate(text, MAX_TEXT_LEN).to_string(),
+        }
+    }
+
+    pub fn admin_get_stats() -> Self {
+        Self::AdminGetStats
+    }
+
+    pub fn admin_stats(stats: &ServerStats) -> Self {
+        Self::AdminStats {
+            stats: stats.clone(),
+        }
+    }
+
+    // the reply to admin requests which don't return anything else, where
+    // count is the number of connections that the request affected
+    pub fn admin_done(count: u32) -> Self {
+        Self::AdminDone { count }
+    }
+
+    // asks to be sent Notice messages, regardless of what the connection is
+    // doing at the time
+    pub fn subscribe_notices() -> Self {
+        Self::SubscribeNotices
+    }
+
+    // an announcement from the server itself (from an administrator, for
+    // instance), rather than from another user
+    pub fn notice(text: &str) -> Self {
+        Self::Notice {
+            text: text.to_string(),
+        }
+    }
+
+    // asks to be sent WatcherCount messages whenever the number of people
+    // watching our stream changes
+    pub fn subscribe_watcher_count() -> Self {
+        Self::SubscribeWatcherCount
+    }
+
+    pub fn watcher_count(count: u32) -> Self {
+        Self::WatcherCount { count }
+    }
+
+    // while paused, the streamer doesn't send any TerminalOutput messages,
+    // and watchers are told that the stream is paused rather than just
+    // seeing it freeze
+    pub fn pause_streaming(paused: bool) -> Self {
+        Self::PauseStreaming { paused }
+    }
+
+    // entry is either a username or an ip address
+    pub fn admin_ban(entry: &str) -> Self {
+        Self::AdminBan {
+            entry: entry.to_string(),
+        }
+    }
+
+    // sent by streamers right after StartStreaming, so that their stream is
+    // listed along with the others in the same room. an empty room leaves
+    // the room it was in.
+    pub fn join_room(room: &str) -> Self {
+        Self::JoinRoom {
+            room: truncate(room, MAX_ROOM_LEN).to_string(),
+        }
+    }
+
+    // like ListSessions, but only the sessions in the given room are sent
+    pub fn list_room_sessions(room: &str) -> Self {
+        Self::ListRoomSessions {
+            room: room.to_string(),
+        }
+    }
+
+    // kicks (and optionally bans) every connection matching the filter (see
+    // AdminFilter) at once. the server answers with AdminDone, or with the
+    // AdminConnections which would have been kicked for a dry run.
+    pub fn admin_kick_matching(
+        filter: &str,
+        reason: &str,
+        ban: bool,
+        dry_run: bool,
+    ) -> Self {
+        Self::AdminKickMatching {
+            filter: filter.to_string(),
+            reason: reason.to_string(),
+            ban,
+            dry_run,
+        }
+    }
+
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            Self::Login { .. } => MessageType::Login,
+            Self::StartStreaming { .. } => MessageType::StartStreaming,
+            Self::StartWatching { .. } => MessageType::StartWatching,
+            Self::Heartbeat { .. } => MessageType::Heartbeat,
+            Self::TerminalOutput { .. } => MessageType::TerminalOutput,
+            Self::ListSessions { .. } => MessageType::ListSessions,
+            Self::Sessions { .. } => MessageType::Sessions,
+            Self::Disconnected { .. } => MessageType::Disconnected,
+            Self::Error { .. } => MessageType::Error,
+            Self::Resize { .. } => MessageType::Resize,
+            Self::LoggedIn { .. } => MessageType::LoggedIn,
+            Self::OauthCliRequest { .. } => MessageType::OauthCliRequest,
+            Self::OauthCliResponse { .. } => MessageType::OauthCliResponse,
+            Self::OauthWebRequest { .. } => MessageType::OauthWebRequest,
+            Self::OauthWebResponse { .. } => MessageType::OauthWebResponse,
+            Self::Chat { .. } => MessageType::Chat,
+            Self::RequestControl { .. } => MessageType::RequestControl,
+            Self::GrantControl { .. } => MessageType::GrantControl,
+            Self::RevokeControl { .. } => MessageType::RevokeControl,
+            Self::TerminalInput { .. } => MessageType::TerminalInput,
+            Self::ListHistory { .. } => MessageType::ListHistory,
+            Self::History { .. } => MessageType::History,
+            Self::ServerShutdown { .. } => MessageType::ServerShutdown,
+            Self::HostKeyRequest { .. } => MessageType::HostKeyRequest,
+            Self::HostKey { .. } => MessageType::HostKey,
+            Self::Hello { .. } => MessageType::Hello,
+            Self::Welcome { .. } => MessageType::Welcome,
+            Self::UpdateMetadata { .. } => MessageType::UpdateMetadata,
+            Self::SubscribeSessionList { .. } => {
+                MessageType::SubscribeSessionList
+            }
+            Self::SessionAdded { .. } => MessageType::SessionAdded,
+            Self::SessionRemoved { .. } => MessageType::SessionRemoved,
+            Self::SessionUpdated { .. } => MessageType::SessionUpdated,
+            Self::ResumeStreaming { .. } => MessageType::ResumeStreaming,
+            Self::SubscribeSessionChanges { .. } => {
+                MessageType::SubscribeSessionChanges
+            }
+            Self::SessionChanged { .. } => MessageType::SessionChanged,
+            Self::SubscribeTimeLimit { .. } => {
+                MessageType::SubscribeTimeLimit
+            }
+            Self::TimeLimit { .. } => MessageType::TimeLimit,
+            Self::SubscribeServerLimits { .. } => {
+                MessageType::SubscribeServerLimits
+            }
+            Self::ServerLimits { .. } => MessageType::ServerLimits,
+            Self::AdminListConnections { .. } => {
+                MessageType::AdminListConnections
+            }
+            Self::AdminConnections { .. } => MessageType::AdminConnections,
+            Self::AdminKick { .. } => MessageType::AdminKick,
+            Self::AdminNotice { .. } => MessageType::AdminNotice,
+            Self::AdminGetStats { .. } => MessageType::AdminGetStats,
+            Self::AdminStats { .. } => MessageType::AdminStats,
+            Self::AdminDone { .. } => MessageType::AdminDone,
+            Self::SubscribeNotices { .. } => MessageType::SubscribeNotices,
+            Self::Notice { .. } => MessageType::Notice,
+            Self::SubscribeWatcherCount { .. } => {
+                MessageType::SubscribeWatcherCount
+            }
+            Self::WatcherCount { .. } => MessageType::WatcherCount,
+            Self::PauseStreaming { .. } => MessageType::PauseStreaming,
+            Self::AdminBan { .. } => MessageType::AdminBan,
+            Self::JoinRoom { .. } => MessageType::JoinRoom,
+            Self::ListRoomSessions { .. } => MessageType::ListRoomSessions,
+            Self::AdminKickMatching { .. } => MessageType::AdminKickMatching,
+        }
+    }
+
+    // parses a single frame (the type byte followed by the message data, as
+    // it appears on the wire after the length prefix). this doesn't do any
+    // io, so it can be handed arbitrary bytes, by a fuzzer for instance.
+    #[allow(dead_code)]
+    pub fn parse(frame: &[u8]) -> Result<Self> {
+        Packet::new(bytes::Bytes::from(frame))
+            .and_then(|packet| Self::try_from((packet, PROTO_VERSION)))
+    }
+
+    // whether a peer speaking the given protocol version knows about this
+    // type of message at all
+    pub fn supported_by(&self, proto_version: u8) -> bool {
+        match self.message_type() {
+            MessageType::Hello | MessageType::Welcome => true,
+            ty => {
+                proto_version > 1
+                    || ty as u8 <= MessageType::OauthWebResponse as u8
+            }
+        }
+    }
+
+    // rejects messages which parsed fine, but which no well-behaved peer
+    // would send
+    fn validate(&self) -> Result<()> {
+        fn check_len(field: &str, s: &str, max: usize) -> Result<()> {
+            if s.len() > max {
+                return Err(Error::StringTooLong {
+                    field: field.to_string(),
+                    len: s.len(),
+                    max,
+                });
+            }
+            Ok(())
+        }
+
+        match self {
+            Self::Login {
+                auth, term_type, ..
+            } => {
+                if let Auth::Plain { username } = auth {
+                    check_len("username", username, MAX_USERNAME_LEN)?;
+                }
+                check_len("term type", term_type, MAX_TERM_TYPE_LEN)
+            }
+            Self::StartStreaming {
+                allowed_watchers,
+                description,
+            } => {
+                if allowed_watchers.len() > MAX_ALLOWED_WATCHERS {
+                    return Err(Error::TooManyItems {
+                        field: "allowed watchers".to_string(),
+                        len: allowed_watchers.len(),
+                        max: MAX_ALLOWED_WATCHERS,
+                    });
+                }
+                for username in allowed_watchers {
+                    check_len("allowed watcher", username, MAX_USERNAME_LEN)?;
+                }
+                check_len("description", description, MAX_TEXT_LEN)
+            }
+            Self::Chat { from, text } => {
+                check_len("username", from, MAX_USERNAME_LEN)?;
+                check_len("chat", text, MAX_TEXT_LEN)
+            }
+            Self::RequestControl { username }
+            | Self::GrantControl { username }
+            | Self::RevokeControl { username } => {
+                check_len("username", username, MAX_USERNAME_LEN)
+            }
+            Self::AdminNotice { text } => {
+                check_len("notice", text, MAX_TEXT_LEN)
+            }
+            Self::UpdateMetadata { title, tags } => {
+                check_len("title", title, MAX_TITLE_LEN)?;
+                for tag in tags {
+                    check_len("tag", tag, MAX_TITLE_LEN)?;
+                }
+                Ok(())
+            }
+            Self::JoinRoom { room } | Self::ListRoomSessions { room } => {
+                check_len("room", room, MAX_ROOM_LEN)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn read<R: std::io::Read>(r: R) -> Result<Self> {
+        Packet::read(r)
+            .and_then(|packet| Self::try_from((packet, PROTO_VERSION)))
+    }
+
+    pub fn read_async<T: tokio::io::AsyncRead>(
+        r: FramedReader<T>,
+    ) -> impl futures::Future<Item = (Self, FramedReader<T>), Error = Error>
+    {
+        Packet::read_async(r).and_then(|(packet, r)| {
+            Self::try_from((packet, r.1)).map(|msg| (msg, r))
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn write<W: std::io::Write>(&self, w: W) -> Result<()> {
+        Packet::from(self).write(w)
+    }
+
+    // the number of bytes this message takes up on the wire, including its
+    // length prefix
+    pub fn encoded_len(&self) -> usize {
+        let mut data = bytes::BytesMut::new();
+        encode_message(self, PROTO_VERSION, &mut data);
+        std::mem::size_of::<u32>() + data.len()
+    }
+
+    pub fn write_async<T: tokio::io::AsyncWrite>(
+        &self,
+        w: FramedWriter<T>,
+    ) -> impl futures::Future<Item = FramedWriter<T>, Error = Error> {
+        w.0.send(self.clone())
+            .map(FramedWriter)
+            .context(crate::error::WritePacket)
+    }
+
+    // it'd be nice if i could just override the Debug implementation for
+    // specific enum variants, but writing the whole impl Debug by hand just
+    // to make this one change would be super obnoxious
+    pub fn format_log(&self) -> String {
+        match self {
+            Self::TerminalOutput { data } => {
+                format!("TerminalOutput {{ data: ({} bytes) }}", data.len())
+            }
+
+            // these are security-sensitive, keep them out of logs
+            Self::OauthCliRequest { .. } => {
+                "OauthCliRequest {{ .. }}".to_string()
+            }
+            Self::OauthCliResponse { .. } => {
+                "OauthCliResponse {{ .. }}".to_string()
+            }
+            Self::OauthWebRequest { .. } => {
+                "OauthWebRequest {{ .. }}".to_string()
+            }
+            Self::OauthWebResponse { .. } => {
+                "OauthWebResponse {{ .. }}".to_string()
+            }
+            Self::ResumeStreaming { .. } => {
+                "ResumeStreaming {{ .. }}".to_string()
+            }
+
+            _ => format!("{:?}", self),
+        }
+    }
+}
+
+// the type byte followed by the message data, which is exactly what goes on
+// the wire after the length prefix. keeping it all in one buffer means that
+// packets can be taken from the codec without being copied.
+struct Packet {
+    frame: bytes::Bytes,
+}
+
+impl Packet {
+    fn new(frame: bytes::Bytes) -> Result<Self> {
+        if frame.len() < std::mem::size_of::<u8>() {
+            return Err(Error::LenTooSmall {
+                len: frame.len().try_into().unwrap_or(std::u32::MAX),
+                expected: std::mem::size_of::<u8>(),
+            });
+        }
+        if frame.len() > MAX_FRAME_LEN {
+            return Err(Error::LenTooBig {
+                len: frame.len().try_into().unwrap_or(std::u32::MAX),
+                expected: MAX_FRAME_LEN,
+            });
+        }
+        Ok(Self { frame })
+    }
+
+    fn ty(&self) -> u8 {
+        self.frame[0]
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.frame[std::mem::size_of::<u8>()..]
+    }
+
+    fn read<R: std::io::Read>(mut r: R) -> Result<Self> {
+        let mut len_buf = [0_u8; std::mem::size_of::<u32>()];
+        r.read_exact(&mut len_buf)
+            .context(crate::error::ReadPacket)?;
+        let len = u32::from_be_bytes(len_buf.try_into().unwrap());
+        if (len as usize) < std::mem::size_of::<u8>() {
+            return Err(Error::LenTooSmall {
+                len,
+                expected: std::mem::size_of::<u8>(),
+            });
+        }
+        if len as usize > MAX_FRAME_LEN {
+            return Err(Error::LenTooBig {
+                len,
+                expected: MAX_FRAME_LEN,
+            });
+        }
+
+        let mut frame = vec![0_u8; len as usize];
+        r.read_exact(&mut frame).context(crate::error::ReadPacket)?;
+        Self::new(frame.into())
+    }
+
+    fn read_async<T: tokio::io::AsyncRead>(
+        r: FramedReader<T>,
+    ) -> impl futures::Future<Item = (Self, FramedReader<T>), Error = Error>
+    {
+        let proto_version = r.1;
+        r.0.into_future()
+            .map_err(|(e, _)| {
+                // the length delimited codec reports frames which are over
+                // its max_frame_length this way
+                if e.kind() == std::io::ErrorKind::InvalidData {
+                    Error::FrameTooBig { max: MAX_FRAME_LEN }
+                } else {
+                    Error::ReadPacket { source: e }
+                }
+            })
+            .and_then(|(data, r)| match data {
+                Some(data) => Ok((data, r)),
+                None => Err(Error::EOF),
+            })
+            .and_then(|(buf, r)| {
+                Ok((Self::new(buf.freeze())?, FramedReader(r, proto_version)))
+            })
+    }
+
+    fn write<W: std::io::Write>(&self, mut w: W) -> Result<()> {
+        let len: u32 = self.frame.len().try_into().unwrap();
+        w.write_all(&len.to_be_bytes())
+            .context(crate::error::WritePacket)?;
+        Ok(w.write_all(&self.frame)
+            .context(crate::error::WritePacket)?)
+    }
+}
+
+impl From<&Message> for Packet {
+    fn from(msg: &Message) -> Self {
+        let mut data = bytes::BytesMut::new();
+        encode_message(msg, PROTO_VERSION, &mut data);
+        Self {
+            frame: data.freeze(),
+        }
+    }
+}
+
+// cuts s down to at most max bytes, without splitting a character
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+// the type byte followed by the message data
+fn encode_message(
+    msg: &Message,
+    proto_version: u8,
+    data: &mut bytes::BytesMut,
+) {
+    fn u32_from_usize(n: usize) -> u32 {
+        n.try_into().unwrap()
+    }
+    fn write_u64(val: u64, data: &mut bytes::BytesMut) {
+        data.extend_from_slice(&val.to_be_bytes());
+    }
+    fn write_u32(val: u32, data: &mut bytes::BytesMut) {
+        data.extend_from_slice(&val.to_be_bytes());
+    }
+    fn write_u16(val: u16, data: &mut bytes::BytesMut) {
+        data.extend_from_slice(&val.to_be_bytes());
+    }
+    fn write_u8(val: u8, data: &mut bytes::BytesMut) {
+        data.extend_from_slice(&val.to_be_bytes());
+    }
+    fn write_bytes(val: &[u8], data: &mut bytes::BytesMut) {
+        write_u32(u32_from_usize(val.len()), data);
+        data.extend_from_slice(val);
+    }
+    fn write_str(val: &str, data: &mut bytes::BytesMut) {
+        write_bytes(val.as_bytes(), data);
+    }
+    fn write_strs(val: &[String], data: &mut bytes::BytesMut) {
+        write_u32(u32_from_usize(val.len()), data);
+        for s in val {
+            write_str(s, data);
+        }
+    }
+    fn write_opt_str(val: Option<&str>, data: &mut bytes::BytesMut) {
+        if let Some(val) = val {
+            write_u8(1, data);
+            write_str(val, data);
+        } else {
+            write_u8(0, data);
+        }
+    }
+    fn write_bool(val: bool, data: &mut bytes::BytesMut) {
+        write_u8(if val { 1 } else { 0 }, data);
+    }
+    fn write_opt_bool(val: Option<bool>, data: &mut bytes::BytesMut) {
+        if let Some(val) = val {
+            write_u8(1, data);
+            write_bool(val, data);
+        } else {
+            write_u8(0, data);
+        }
+    }
+    fn write_opt_strs(val: Option<&[String]>, data: &mut bytes::BytesMut) {
+        if let Some(val) = val {
+            write_u8(1, data);
+            write_strs(val, data);
+        } else {
+            write_u8(0, data);
+        }
+    }
+    fn write_size(val: crate::term::Size, data: &mut bytes::BytesMut) {
+        write_u16(val.rows, data);
+        write_u16(val.cols, data);
+    }
+    fn write_session(
+        val: &Session,
+        proto_version: u8,
+        data: &mut bytes::BytesMut,
+    ) {
+        write_str(&val.id, data);
+        write_str(&val.username, data);
+        write_str(&val.term_type, data);
+        write_size(val.size, data);
+        write_u32(val.idle_time, data);
+        write_str(&val.title, data);
+        if proto_version > 1 {
+            write_str(&val.description, data);
+            write_strs(&val.tags, data);
+            write_str(&val.room, data);
+            write_bool(val.paused, data);
+        }
+        write_u32(val.watchers, data);
+        if proto_version > 1 {
+            write_u32(val.uptime, data);
+            write_u32(val.start_time, data);
+        }
+    }
+    fn write_sessions(
+        val: &[Session],
+        proto_version: u8,
+        data: &mut bytes::BytesMut,
+    ) {
+        write_u32(u32_from_usize(val.len()), data);
+        for s in val {
+            write_session(s, proto_version, data);
+        }
+    }
+    fn write_past_session(val: &PastSession, data: &mut bytes::BytesMut) {
+        write_str(&val.username, data);
+        write_str(&val.title, data);
+        write_str(&val.description, data);
+        write_u32(val.start_time, data);
+        write_u32(val.end_time, data);
+        write_u64(val.bytes, data);
+    }
+    fn write_past_sessions(val: &[PastSession], data: &mut bytes::BytesMut) {
+        write_u32(u32_from_usize(val.len()), data);
+        for s in val {
+            write_past_session(s, data);
+        }
+    }
+    fn write_admin_connection(
+        val: &AdminConnection,
+        data: &mut bytes::BytesMut,
+    ) {
+        write_str(&val.id, data);
+        write_str(&val.address, data);
+        write_str(&val.username, data);
+        write_str(&val.state, data);
+        write_str(&val.watch_id, data);
+        write_u32(val.connected, data);
+    }
+    fn write_admin_connections(
+        val: &[AdminConnection],
+        data: &mut bytes::BytesMut,
+    ) {
+        write_u32(u32_from_usize(val.len()), data);
+        for c in val {
+            write_admin_connection(c, data);
+        }
+    }
+    fn write_server_stats(val: &ServerStats, data: &mut bytes::BytesMut) {
+        write_u32(val.uptime, data);
+        write_u32(val.connections, data);
+        write_u32(val.streams, data);
+        write_u32(val.watchers, data);
+        write_u32(val.total_connections, data);
+        write_u32(val.total_streams, data);
+    }
+    fn write_auth(val: &Auth, data: &mut bytes::BytesMut) {
+        write_u8(val.auth_type() as u8, data);
+        match val {
+            Auth::Plain { username } => {
+                write_str(username, data);
+            }
+            Auth::RecurseCenter { id }
+            | Auth::Github { id }
+            | Auth::Google { id } => {
+                let id = id.as_ref().map_or("", |s| s.as_str());
+                write_str(id, data);
+            }
+        }
+    }
+
+    write_u8(msg.message_type() as u8, data);
+
+    match msg {
+        Message::Login {
+            proto_version,
+            auth,
+            auth_client,
+            term_type,
+            size,
+        } => {
+            write_u8(*proto_version, data);
+            write_auth(auth, data);
+            write_u8(*auth_client as u8, data);
+            write_str(term_type, data);
+            write_size(*size, data);
+        }
+        Message::StartStreaming {
+            allowed_watchers,
+            description,
+        } => {
+            if proto_version > 1 {
+                write_strs(allowed_watchers, data);
+                write_str(description, data);
+            }
+        }
+        Message::StartWatching { id, offset } => {
+            write_str(id, data);
+            if proto_version > 1 {
+                write_u32(*offset, data);
+            }
+        }
+        Message::Heartbeat => {}
+        Message::TerminalOutput { data: output } => {
+            write_bytes(output, data);
+        }
+        Message::ListSessions => {}
+        Message::Sessions { sessions } => {
+            write_sessions(sessions, proto_version, data);
+        }
+        Message::Disconnected => {}
+        Message::Error { msg } => {
+            write_str(msg, data);
+        }
+        Message::Resize { size } => {
+            write_size(*size, data);
+        }
+        Message::LoggedIn { username } => {
+            write_str(username, data);
+        }
+        Message::OauthCliRequest { url, id } => {
+            write_str(url, data);
+            write_str(id, data);
+        }
+        Message::OauthCliResponse { code } => {
+            write_str(code, data);
+        }
+        Message::OauthWebRequest { id } => {
+            write_str(id, data);
+        }
+        Message::OauthWebResponse { access_token } => {
+            write_str(access_token, data);
+        }
+        Message::Chat { from, text } => {
+            write_str(from, data);
+            write_str(text, data);
+        }
+        Message::RequestControl { username } => {
+            write_str(username, data);
+        }
+        Message::GrantControl { username } => {
+            write_str(username, data);
+        }
+        Message::RevokeControl { username } => {
+            write_str(username, data);
+        }
+        Message::TerminalInput { data: input } => {
+            write_bytes(input, data);
+        }
+        Message::ListHistory => {}
+        Message::History { sessions } => {
+            write_past_sessions(sessions, data);
+        }
+        Message::ServerShutdown { reason } => {
+            write_str(reason, data);
+        }
+        Message::HostKeyRequest { nonce } => {
+            write_bytes(nonce, data);
+        }
+        Message::HostKey {
+            public_key,
+            signature,
+        } => {
+            write_bytes(public_key, data);
+            write_bytes(signature, data);
+        }
+        Message::Hello { proto_version } => {
+            write_u8(*proto_version, data);
+        }
+        Message::Welcome {
+            proto_version,
+            heartbeat_interval,
+            capabilities,
+        } => {
+            // this is the negotiated version itself, which the peer needs
+            // to be able to read before it knows which version we picked
+            write_u8(*proto_version, data);
+            write_u32(*heartbeat_interval, data);
+            if *proto_version > 1 {
+                write_strs(capabilities, data);
+            }
+        }
+        Message::UpdateMetadata { title, tags } => {
+            write_str(title, data);
+            write_strs(tags, data);
+        }
+        Message::SubscribeSessionList => {}
+        Message::SessionAdded { session } => {
+            write_session(session, proto_version, data);
+        }
+        Message::SessionRemoved { id } => {
+            write_str(id, data);
+        }
+        Message::SessionUpdated { session } => {
+            write_session(session, proto_version, data);
+        }
+        Message::ResumeStreaming { token } => {
+            write_str(token, data);
+        }
+        Message::SubscribeSessionChanges => {}
+        Message::SessionChanged {
+            title,
+            tags,
+            paused,
+        } => {
+            write_opt_str(
+                title.as_ref().map(std::string::String::as_str),
+                data,
+            );
+            write_opt_strs(tags.as_ref().map(std::vec::Vec::as_slice), data);
+            write_opt_bool(*paused, data);
+        }
+        Message::SubscribeTimeLimit => {}
+        Message::TimeLimit { remaining } => {
+            write_u32(*remaining, data);
+        }
+        Message::SubscribeServerLimits => {}
+        Message::ServerLimits {
+            connections,
+            max_connections,
+            max_watchers,
+        } => {
+            write_u32(*connections, data);
+            write_u32(*max_connections, data);
+            write_u32(*max_watchers, data);
+        }
+        Message::AdminListConnections => {}
+        Message::AdminConnections { connections } => {
+            write_admin_connections(connections, data);
+        }
+        Message::AdminKick { id, reason } => {
+            write_str(id, data);
+            write_str(reason, data);
+        }
+        Message::AdminNotice { text } => {
+            write_str(text, data);
+        }
+        Message::AdminGetStats => {}
+        Message::AdminStats { stats } => {
+            write_server_stats(stats, data);
+        }
+        Message::AdminDone { count } => {
+            write_u32(*count, data);
+        }
+        Message::SubscribeNotices => {}
+        Message::Notice { text } => {
+            write_str(text, data);
+        }
+        Message::SubscribeWatcherCount => {}
+        Message::WatcherCount { count } => {
+            write_u32(*count, data);
+        }
+        Message::PauseStreaming { paused } => {
+            write_bool(*paused, data);
+        }
+        Message::AdminBan { entry } => {
+            write_str(entry, data);
+        }
+        Message::JoinRoom { room } => {
+            write_str(room, data);
+        }
+        Message::ListRoomSessions { room } => {
+            write_str(room, data);
+        }
+        Message::AdminKickMatching {
+            filter,
+            reason,
+            ban,
+            dry_run,
+        } => {
+            write_str(filter, data);
+            write_str(reason, data);
+            write_bool(*ban, data);
+            write_bool(*dry_run, data);
+        }
+    }
+}
+
+// the packet along with the protocol version it was encoded with
+impl std::convert::TryFrom<(Packet, u8)> for Message {
     type Error = Error;
 
-    fn try_from(packet: Packet) -> Result<Self> {
+    fn try_from((packet, proto_version): (Packet, u8)) -> Result<Self> {
+        fn read_u64(data: &[u8]) -> Result<(u64, &[u8])> {
+            if std::mem::size_of::<u64>() > data.len() {
+                return Err(Error::LenTooBig {
+                    len: std::mem::size_of::<u64>().try_into().unwrap(),
+                    expected: data.len(),
+                });
+            }
+            let (buf, rest) = data.split_at(std::mem::size_of::<u64>());
+            let val = u64::from_be_bytes(
+                buf.try_into().context(crate::error::ParseInt { buf })?,
+            );
+            Ok((val, rest))
+        }
         fn read_u32(data: &[u8]) -> Result<(u32, &[u8])> {
             if std::mem::size_of::<u32>() > data.len() {
                 return Err(Error::LenTooBig {
@@ -743,42 +1933,194 @@ impl std::convert::TryFrom<Packet> for Message {
                 })?;
             Ok((val, rest))
         }
-        fn read_size(data: &[u8]) -> Result<(crate::term::Size, &[u8])> {
-            let (rows, data) = read_u16(data)?;
-            let (cols, data) = read_u16(data)?;
-            Ok((crate::term::Size { rows, cols }, data))
+        fn read_strs(data: &[u8]) -> Result<(Vec<String>, &[u8])> {
+            let mut val = vec![];
+            let (len, mut data) = read_u32(data)?;
+            for _ in 0..len {
+                let (subval, subdata) = read_str(data)?;
+                val.push(subval);
+                data = subdata;
+            }
+            Ok((val, data))
+        }
+        fn read_opt_str(data: &[u8]) -> Result<(Option<String>, &[u8])> {
+            let (present, data) = read_u8(data)?;
+            if present == 0 {
+                return Ok((None, data));
+            }
+            let (val, data) = read_str(data)?;
+            Ok((Some(val), data))
+        }
+        fn read_bool(data: &[u8]) -> Result<(bool, &[u8])> {
+            let (val, data) = read_u8(data)?;
+            Ok((val != 0, data))
+        }
+        fn read_opt_bool(data: &[u8]) -> Result<(Option<bool>, &[u8])> {
+            let (present, data) = read_u8(data)?;
+            if present == 0 {
+                return Ok((None, data));
+            }
+            let (val, data) = read_bool(data)?;
+            Ok((Some(val), data))
+        }
+        fn read_opt_strs(
+            data: &[u8],
+        ) -> Result<(Option<Vec<String>>, &[u8])> {
+            let (present, data) = read_u8(data)?;
+            if present == 0 {
+                return Ok((None, data));
+            }
+            let (val, data) = read_strs(data)?;
+            Ok((Some(val), data))
+        }
+        fn read_size(data: &[u8]) -> Result<(crate::term::Size, &[u8])> {
+            let (rows, data) = read_u16(data)?;
+            let (cols, data) = read_u16(data)?;
+            Ok((crate::term::Size { rows, cols }, data))
+        }
+        fn read_session(
+            data: &[u8],
+            proto_version: u8,
+        ) -> Result<(Session, &[u8])> {
+            let (id, data) = read_str(data)?;
+            let (username, data) = read_str(data)?;
+            let (term_type, data) = read_str(data)?;
+            let (size, data) = read_size(data)?;
+            let (idle_time, data) = read_u32(data)?;
+            let (title, data) = read_str(data)?;
+            let (description, tags, room, paused, data) = if proto_version > 1
+            {
+                let (description, data) = read_str(data)?;
+                let (tags, data) = read_strs(data)?;
+                let (room, data) = read_str(data)?;
+                let (paused, data) = read_bool(data)?;
+                (description, tags, room, paused, data)
+            } else {
+                (String::new(), vec![], String::new(), false, data)
+            };
+            let (watchers, data) = read_u32(data)?;
+            let (uptime, start_time, data) = if proto_version > 1 {
+                let (uptime, data) = read_u32(data)?;
+                let (start_time, data) = read_u32(data)?;
+                (uptime, start_time, data)
+            } else {
+                (0, 0, data)
+            };
+            Ok((
+                Session {
+                    id,
+                    username,
+                    term_type,
+                    size,
+                    idle_time,
+                    title,
+                    description,
+                    tags,
+                    room,
+                    paused,
+                    watchers,
+                    uptime,
+                    start_time,
+                },
+                data,
+            ))
+        }
+        fn read_sessions(
+            data: &[u8],
+            proto_version: u8,
+        ) -> Result<(Vec<Session>, &[u8])> {
+            let mut val = vec![];
+            let (len, mut data) = read_u32(data)?;
+            for _ in 0..len {
+                let (subval, subdata) = read_session(data, proto_version)?;
+                val.push(subval);
+                data = subdata;
+            }
+            Ok((val, data))
+        }
+        fn read_past_session(data: &[u8]) -> Result<(PastSession, &[u8])> {
+            let (username, data) = read_str(data)?;
+            let (title, data) = read_str(data)?;
+            let (description, data) = read_str(data)?;
+            let (start_time, data) = read_u32(data)?;
+            let (end_time, data) = read_u32(data)?;
+            let (bytes, data) = read_u64(data)?;
+            Ok((
+                PastSession {
+                    username,
+                    title,
+                    description,
+                    start_time,
+                    end_time,
+                    bytes,
+                },
+                data,
+            ))
+        }
+        fn read_past_sessions(
+            data: &[u8],
+        ) -> Result<(Vec<PastSession>, &[u8])> {
+            let mut val = vec![];
+            let (len, mut data) = read_u32(data)?;
+            for _ in 0..len {
+                let (subval, subdata) = read_past_session(data)?;
+                val.push(subval);
+                data = subdata;
+            }
+            Ok((val, data))
         }
-        fn read_session(data: &[u8]) -> Result<(Session, &[u8])> {
+        fn read_admin_connection(
+            data: &[u8],
+        ) -> Result<(AdminConnection, &[u8])> {
             let (id, data) = read_str(data)?;
+            let (address, data) = read_str(data)?;
             let (username, data) = read_str(data)?;
-            let (term_type, data) = read_str(data)?;
-            let (size, data) = read_size(data)?;
-            let (idle_time, data) = read_u32(data)?;
-            let (title, data) = read_str(data)?;
-            let (watchers, data) = read_u32(data)?;
+            let (state, data) = read_str(data)?;
+            let (watch_id, data) = read_str(data)?;
+            let (connected, data) = read_u32(data)?;
             Ok((
-                Session {
+                AdminConnection {
                     id,
+                    address,
                     username,
-                    term_type,
-                    size,
-                    idle_time,
-                    title,
-                    watchers,
+                    state,
+                    watch_id,
+                    connected,
                 },
                 data,
             ))
         }
-        fn read_sessions(data: &[u8]) -> Result<(Vec<Session>, &[u8])> {
+        fn read_admin_connections(
+            data: &[u8],
+        ) -> Result<(Vec<AdminConnection>, &[u8])> {
             let mut val = vec![];
             let (len, mut data) = read_u32(data)?;
             for _ in 0..len {
-                let (subval, subdata) = read_session(data)?;
+                let (subval, subdata) = read_admin_connection(data)?;
                 val.push(subval);
                 data = subdata;
             }
             Ok((val, data))
         }
+        fn read_server_stats(data: &[u8]) -> Result<(ServerStats, &[u8])> {
+            let (uptime, data) = read_u32(data)?;
+            let (connections, data) = read_u32(data)?;
+            let (streams, data) = read_u32(data)?;
+            let (watchers, data) = read_u32(data)?;
+            let (total_connections, data) = read_u32(data)?;
+            let (total_streams, data) = read_u32(data)?;
+            Ok((
+                ServerStats {
+                    uptime,
+                    connections,
+                    streams,
+                    watchers,
+                    total_connections,
+                    total_streams,
+                },
+                data,
+            ))
+        }
         fn read_auth(data: &[u8]) -> Result<(Auth, &[u8])> {
             let (ty, data) = read_u8(data)?;
             let ty = AuthType::try_from(ty)?;
@@ -794,12 +2136,24 @@ impl std::convert::TryFrom<Packet> for Message {
                     let auth = Auth::RecurseCenter { id };
                     (auth, data)
                 }
+                AuthType::Github => {
+                    let (id, data) = read_str(data)?;
+                    let id = if id == "" { None } else { Some(id) };
+                    let auth = Auth::Github { id };
+                    (auth, data)
+                }
+                AuthType::Google => {
+                    let (id, data) = read_str(data)?;
+                    let id = if id == "" { None } else { Some(id) };
+                    let auth = Auth::Google { id };
+                    (auth, data)
+                }
             };
             Ok((auth, data))
         }
 
-        let ty = MessageType::try_from(packet.ty)?;
-        let data: &[u8] = packet.data.as_ref();
+        let ty = MessageType::try_from(packet.ty())?;
+        let data = packet.data();
         let (msg, rest) = match ty {
             MessageType::Login => {
                 let (proto_version, data) = read_u8(data)?;
@@ -820,11 +2174,33 @@ impl std::convert::TryFrom<Packet> for Message {
                     data,
                 )
             }
-            MessageType::StartStreaming => (Self::StartStreaming, data),
+            MessageType::StartStreaming => {
+                let (allowed_watchers, description, data) =
+                    if proto_version > 1 {
+                        let (allowed_watchers, data) = read_strs(data)?;
+                        let (description, data) = read_str(data)?;
+                        (allowed_watchers, description, data)
+                    } else {
+                        (vec![], String::new(), data)
+                    };
+
+                (
+                    Self::StartStreaming {
+                        allowed_watchers,
+                        description,
+                    },
+                    data,
+                )
+            }
             MessageType::StartWatching => {
                 let (id, data) = read_str(data)?;
+                let (offset, data) = if proto_version > 1 {
+                    read_u32(data)?
+                } else {
+                    (0, data)
+                };
 
-                (Self::StartWatching { id }, data)
+                (Self::StartWatching { id, offset }, data)
             }
             MessageType::Heartbeat => (Self::Heartbeat, data),
             MessageType::TerminalOutput => {
@@ -834,7 +2210,7 @@ impl std::convert::TryFrom<Packet> for Message {
             }
             MessageType::ListSessions => (Self::ListSessions, data),
             MessageType::Sessions => {
-                let (sessions, data) = read_sessions(data)?;
+                let (sessions, data) = read_sessions(data, proto_version)?;
 
                 (Self::Sessions { sessions }, data)
             }
@@ -875,6 +2251,236 @@ impl std::convert::TryFrom<Packet> for Message {
 
                 (Self::OauthWebResponse { access_token }, data)
             }
+            MessageType::Chat => {
+                let (from, data) = read_str(data)?;
+                let (text, data) = read_str(data)?;
+
+                (Self::Chat { from, text }, data)
+            }
+            MessageType::RequestControl => {
+                let (username, data) = read_str(data)?;
+
+                (Self::RequestControl { username }, data)
+            }
+            MessageType::GrantControl => {
+                let (username, data) = read_str(data)?;
+
+                (Self::GrantControl { username }, data)
+            }
+            MessageType::RevokeControl => {
+                let (username, data) = read_str(data)?;
+
+                (Self::RevokeControl { username }, data)
+            }
+            MessageType::TerminalInput => {
+                let (input, data) = read_bytes(data)?;
+
+                (Self::TerminalInput { data: input }, data)
+            }
+            MessageType::ListHistory => (Self::ListHistory, data),
+            MessageType::History => {
+                let (sessions, data) = read_past_sessions(data)?;
+
+                (Self::History { sessions }, data)
+            }
+            MessageType::ServerShutdown => {
+                let (reason, data) = read_str(data)?;
+
+                (Self::ServerShutdown { reason }, data)
+            }
+            MessageType::HostKeyRequest => {
+                let (nonce, data) = read_bytes(data)?;
+
+                (Self::HostKeyRequest { nonce }, data)
+            }
+            MessageType::HostKey => {
+                let (public_key, data) = read_bytes(data)?;
+                let (signature, data) = read_bytes(data)?;
+
+                (
+                    Self::HostKey {
+                        public_key,
+                        signature,
+                    },
+                    data,
+                )
+            }
+            MessageType::Hello => {
+                let (proto_version, data) = read_u8(data)?;
+
+                (Self::Hello { proto_version }, data)
+            }
+            MessageType::Welcome => {
+                // the layout depends on the version the server picked,
+                // rather than on the version we were expecting
+                let (proto_version, data) = read_u8(data)?;
+                let (heartbeat_interval, data) = read_u32(data)?;
+                let (capabilities, data) = if proto_version > 1 {
+                    read_strs(data)?
+                } else {
+                    (vec![], data)
+                };
+
+                (
+                    Self::Welcome {
+                        proto_version,
+                        heartbeat_interval,
+                        capabilities,
+                    },
+                    data,
+                )
+            }
+            MessageType::UpdateMetadata => {
+                let (title, data) = read_str(data)?;
+                let (tags, data) = read_strs(data)?;
+
+                (Self::UpdateMetadata { title, tags }, data)
+            }
+            MessageType::SubscribeSessionList => {
+                (Self::SubscribeSessionList, data)
+            }
+            MessageType::SessionAdded => {
+                let (session, data) = read_session(data, proto_version)?;
+
+                (Self::SessionAdded { session }, data)
+            }
+            MessageType::SessionRemoved => {
+                let (id, data) = read_str(data)?;
+
+                (Self::SessionRemoved { id }, data)
+            }
+            MessageType::SessionUpdated => {
+                let (session, data) = read_session(data, proto_version)?;
+
+                (Self::SessionUpdated { session }, data)
+            }
+            MessageType::ResumeStreaming => {
+                let (token, data) = read_str(data)?;
+
+                (Self::ResumeStreaming { token }, data)
+            }
+            MessageType::SubscribeSessionChanges => {
+                (Self::SubscribeSessionChanges, data)
+            }
+            MessageType::SessionChanged => {
+                let (title, data) = read_opt_str(data)?;
+                let (tags, data) = read_opt_strs(data)?;
+                let (paused, data) = read_opt_bool(data)?;
+
+                (
+                    Self::SessionChanged {
+                        title,
+                        tags,
+                        paused,
+                    },
+                    data,
+                )
+            }
+            MessageType::SubscribeTimeLimit => {
+                (Self::SubscribeTimeLimit, data)
+            }
+            MessageType::TimeLimit => {
+                let (remaining, data) = read_u32(data)?;
+
+                (Self::TimeLimit { remaining }, data)
+            }
+            MessageType::SubscribeServerLimits => {
+                (Self::SubscribeServerLimits, data)
+            }
+            MessageType::ServerLimits => {
+                let (connections, data) = read_u32(data)?;
+                let (max_connections, data) = read_u32(data)?;
+                let (max_watchers, data) = read_u32(data)?;
+
+                (
+                    Self::ServerLimits {
+                        connections,
+                        max_connections,
+                        max_watchers,
+                    },
+                    data,
+                )
+            }
+            MessageType::AdminListConnections => {
+                (Self::AdminListConnections, data)
+            }
+            MessageType::AdminConnections => {
+                let (connections, data) = read_admin_connections(data)?;
+
+                (Self::AdminConnections { connections }, data)
+            }
+            MessageType::AdminKick => {
+                let (id, data) = read_str(data)?;
+                let (reason, data) = read_str(data)?;
+
+                (Self::AdminKick { id, reason }, data)
+            }
+            MessageType::AdminNotice => {
+                let (text, data) = read_str(data)?;
+
+                (Self::AdminNotice { text }, data)
+            }
+            MessageType::AdminGetStats => (Self::AdminGetStats, data),
+            MessageType::AdminStats => {
+                let (stats, data) = read_server_stats(data)?;
+
+                (Self::AdminStats { stats }, data)
+            }
+            MessageType::AdminDone => {
+                let (count, data) = read_u32(data)?;
+
+                (Self::AdminDone { count }, data)
+            }
+            MessageType::SubscribeNotices => (Self::SubscribeNotices, data),
+            MessageType::Notice => {
+                let (text, data) = read_str(data)?;
+
+                (Self::Notice { text }, data)
+            }
+            MessageType::SubscribeWatcherCount => {
+                (Self::SubscribeWatcherCount, data)
+            }
+            MessageType::WatcherCount => {
+                let (count, data) = read_u32(data)?;
+
+                (Self::WatcherCount { count }, data)
+            }
+            MessageType::PauseStreaming => {
+                let (paused, data) = read_bool(data)?;
+
+                (Self::PauseStreaming { paused }, data)
+            }
+            MessageType::AdminBan => {
+                let (entry, data) = read_str(data)?;
+
+                (Self::AdminBan { entry }, data)
+            }
+            MessageType::JoinRoom => {
+                let (room, data) = read_str(data)?;
+
+                (Self::JoinRoom { room }, data)
+            }
+            MessageType::ListRoomSessions => {
+                let (room, data) = read_str(data)?;
+
+                (Self::ListRoomSessions { room }, data)
+            }
+            MessageType::AdminKickMatching => {
+                let (filter, data) = read_str(data)?;
+                let (reason, data) = read_str(data)?;
+                let (ban, data) = read_bool(data)?;
+                let (dry_run, data) = read_bool(data)?;
+
+                (
+                    Self::AdminKickMatching {
+                        filter,
+                        reason,
+                        ban,
+                        dry_run,
+                    },
+                    data,
+                )
+            }
         };
 
         if !rest.is_empty() {
@@ -882,6 +2488,7 @@ impl std::convert::TryFrom<Packet> for Message {
                 data: rest.to_vec(),
             });
         }
+        msg.validate()?;
 
         Ok(msg)
     }
@@ -895,7 +2502,7 @@ mod test {
     fn test_serialize_deserialize() {
         for msg in valid_messages() {
             let packet = Packet::from(&msg);
-            let msg2 = Message::try_from(packet).unwrap();
+            let msg2 = Message::try_from((packet, PROTO_VERSION)).unwrap();
             assert_eq!(msg, msg2);
         }
     }
@@ -969,6 +2576,126 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_parse_malformed() {
+        for msg in valid_messages() {
+            let frame = Packet::from(&msg).frame;
+            assert_eq!(Message::parse(&frame).unwrap(), msg);
+
+            // none of these are expected to parse, but they must not panic
+            for len in 0..frame.len() {
+                let _ = Message::parse(&frame[..len]);
+            }
+            for i in 0..frame.len() {
+                let mut frame = frame.to_vec();
+                frame[i] ^= 0xff;
+                let _ = Message::parse(&frame);
+            }
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let login = Message::login(
+            &Auth::plain(&"x".repeat(MAX_USERNAME_LEN + 1)),
+            AuthClient::Cli,
+            "screen",
+            crate::term::Size { rows: 24, cols: 80 },
+        );
+        match Message::parse(&Packet::from(&login).frame) {
+            Err(Error::StringTooLong { .. }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let update = Message::UpdateMetadata {
+            title: "x".repeat(MAX_TITLE_LEN + 1),
+            tags: vec![],
+        };
+        match Message::parse(&Packet::from(&update).frame) {
+            Err(Error::StringTooLong { .. }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // titles which are too long are cut short rather than rejected
+        let update = Message::update_metadata(
+            &"é".repeat(MAX_TITLE_LEN),
+            &["x".repeat(MAX_TITLE_LEN + 1)],
+        );
+        match Message::parse(&Packet::from(&update).frame).unwrap() {
+            Message::UpdateMetadata { title, tags } => {
+                assert_eq!(title, "é".repeat(MAX_TITLE_LEN / 2));
+                assert_eq!(tags, vec!["x".repeat(MAX_TITLE_LEN)]);
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        }
+
+        let list = Message::list_room_sessions(&"x".repeat(MAX_ROOM_LEN + 1));
+        match Message::parse(&Packet::from(&list).frame) {
+            Err(Error::StringTooLong { .. }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let long_username = "x".repeat(MAX_USERNAME_LEN + 1);
+        let long_text = "x".repeat(MAX_TEXT_LEN + 1);
+        let too_long = vec![
+            Message::Login {
+                proto_version: PROTO_VERSION,
+                auth: Auth::plain("doy"),
+                auth_client: AuthClient::Cli,
+                term_type: "x".repeat(MAX_TERM_TYPE_LEN + 1),
+                size: crate::term::Size { rows: 24, cols: 80 },
+            },
+            Message::StartStreaming {
+                allowed_watchers: vec![],
+                description: long_text.clone(),
+            },
+            Message::start_streaming(&[long_username.clone()], ""),
+            Message::Chat {
+                from: "doy".to_string(),
+                text: long_text.clone(),
+            },
+            Message::chat(&long_username, "hi"),
+            Message::request_control(&long_username),
+            Message::grant_control(&long_username),
+            Message::AdminNotice { text: long_text },
+        ];
+        for msg in too_long {
+            match Message::parse(&Packet::from(&msg).frame) {
+                Err(Error::StringTooLong { .. }) => {}
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+
+        let start = Message::start_streaming(
+            &vec!["doy".to_string(); MAX_ALLOWED_WATCHERS + 1],
+            "",
+        );
+        match Message::parse(&Packet::from(&start).frame) {
+            Err(Error::TooManyItems { .. }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // text which is too long is cut short rather than rejected
+        let chat = Message::chat("doy", &"x".repeat(MAX_TEXT_LEN + 1));
+        match Message::parse(&Packet::from(&chat).frame).unwrap() {
+            Message::Chat { text, .. } => {
+                assert_eq!(text, "x".repeat(MAX_TEXT_LEN));
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        }
+
+        let mut buf = u32::try_from(MAX_FRAME_LEN + 1)
+            .unwrap()
+            .to_be_bytes()
+            .to_vec();
+        buf.push(0x04);
+        match Message::read(buf.as_slice()) {
+            Err(Error::LenTooBig { .. }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(Packet::new(vec![0x04; MAX_FRAME_LEN + 1].into()).is_err());
+    }
+
     #[test]
     fn test_auth_values() {
         let mut set = std::collections::HashSet::new();
@@ -1011,6 +2738,211 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_encoder() {
+        let mut encoder = MessageEncoder {
+            proto_version: PROTO_VERSION,
+        };
+        let mut encoded = bytes::BytesMut::new();
+        let mut written = vec![];
+        for msg in valid_messages() {
+            msg.write(&mut written).unwrap();
+            tokio::codec::Encoder::encode(&mut encoder, msg, &mut encoded)
+                .unwrap();
+        }
+        assert_eq!(&encoded[..], written.as_slice());
+
+        // queued messages are all written out by a single flush
+        let mut w = FramedWriter::new(std::io::Cursor::new(vec![]));
+        for msg in valid_messages() {
+            assert!(w.queue(msg).unwrap().is_none());
+        }
+        let w = w.flush().wait().unwrap();
+        let mut r = std::io::Cursor::new(w.0.into_inner().into_inner());
+        for msg in valid_messages() {
+            assert_eq!(Message::read(&mut r).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn test_admin_filter() {
+        assert_eq!(
+            AdminFilter::try_from("user:doy").unwrap(),
+            AdminFilter::User("doy".to_string())
+        );
+        assert_eq!(
+            AdminFilter::try_from("tag:rust:beginner").unwrap(),
+            AdminFilter::Tag("rust:beginner".to_string())
+        );
+        assert_eq!(
+            AdminFilter::try_from("address:::1").unwrap(),
+            AdminFilter::Address("::1".parse().unwrap())
+        );
+        for filter in &["doy", "user:", "address:doy", "room:rustconf", ""] {
+            assert!(AdminFilter::try_from(*filter).is_err());
+        }
+    }
+
+    #[test]
+    fn test_negotiate_proto_version() {
+        assert_eq!(negotiate_proto_version(1).unwrap(), 1);
+        assert_eq!(
+            negotiate_proto_version(PROTO_VERSION).unwrap(),
+            PROTO_VERSION
+        );
+        assert_eq!(
+            negotiate_proto_version(PROTO_VERSION + 1).unwrap(),
+            PROTO_VERSION
+        );
+        match negotiate_proto_version(MIN_PROTO_VERSION - 1) {
+            Err(Error::ClientProtocolTooOld { version: 0, min: 1 }) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_proto_version_1() {
+        fn encode(msg: &Message, proto_version: u8) -> Vec<u8> {
+            let mut encoder = MessageEncoder { proto_version };
+            let mut buf = bytes::BytesMut::new();
+            tokio::codec::Encoder::encode(
+                &mut encoder,
+                msg.clone(),
+                &mut buf,
+            )
+            .unwrap();
+            buf.to_vec()
+        }
+        fn decode(buf: Vec<u8>, proto_version: u8) -> Result<Message> {
+            let mut r = FramedReader::new(std::io::Cursor::new(buf));
+            r.set_proto_version(proto_version);
+            Message::read_async(r).wait().map(|(msg, _)| msg)
+        }
+
+        // these are the frames that version 1 clients send
+        let start_streaming = vec![0, 0, 0, 1, 1];
+        let start_watching =
+            vec![0, 0, 0, 9, 2, 0, 0, 0, 4, b'a', b'b', b'c', b'd'];
+        assert_eq!(
+            decode(start_streaming.clone(), 1).unwrap(),
+            Message::start_streaming(&[], "")
+        );
+        assert_eq!(
+            decode(start_watching.clone(), 1).unwrap(),
+            Message::start_watching("abcd", 0)
+        );
+        assert!(decode(start_streaming.clone(), PROTO_VERSION).is_err());
+        assert_eq!(
+            encode(&Message::start_streaming(&[], ""), 1),
+            start_streaming
+        );
+        assert_eq!(
+            encode(&Message::start_watching("abcd", 0), 1),
+            start_watching
+        );
+
+        // and these are what they expect to receive
+        let session = Session {
+            id: "id".to_string(),
+            username: "doy".to_string(),
+            term_type: "screen".to_string(),
+            size: crate::term::Size { rows: 24, cols: 80 },
+            idle_time: 12,
+            title: "title".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            room: "".to_string(),
+            paused: false,
+            watchers: 3,
+            uptime: 0,
+            start_time: 0,
+        };
+        let mut sessions = vec![0, 0, 0, 49, 6, 0, 0, 0, 1];
+        sessions.extend_from_slice(&[0, 0, 0, 2]);
+        sessions.extend_from_slice(b"id");
+        sessions.extend_from_slice(&[0, 0, 0, 3]);
+        sessions.extend_from_slice(b"doy");
+        sessions.extend_from_slice(&[0, 0, 0, 6]);
+        sessions.extend_from_slice(b"screen");
+        sessions.extend_from_slice(&[0, 24, 0, 80, 0, 0, 0, 12]);
+        sessions.extend_from_slice(&[0, 0, 0, 5]);
+        sessions.extend_from_slice(b"title");
+        sessions.extend_from_slice(&[0, 0, 0, 3]);
+        let msg = Message::sessions(&[session]);
+        assert_eq!(encode(&msg, 1), sessions);
+        assert_eq!(decode(sessions, 1).unwrap(), msg);
+
+        // the new fields are dropped rather than garbling the message
+        let mut session = match &msg {
+            Message::Sessions { sessions } => sessions[0].clone(),
+            _ => unreachable!(),
+        };
+        session.description = "debugging".to_string();
+        session.uptime = 345;
+        let new = Message::sessions(&[session]);
+        assert_eq!(decode(encode(&new, 1), 1).unwrap(), msg);
+        assert_eq!(decode(encode(&new, 2), 2).unwrap(), new);
+
+        let welcome = Message::welcome(1, 10);
+        assert_eq!(encode(&welcome, 1), vec![0, 0, 0, 6, 26, 1, 0, 0, 0, 10]);
+        assert_eq!(
+            decode(encode(&welcome, 1), PROTO_VERSION).unwrap(),
+            welcome
+        );
+
+        // version 1 clients only know about the message types they started
+        // out with
+        assert!(Message::logged_in("doy").supported_by(1));
+        assert!(Message::welcome(1, 10).supported_by(1));
+        assert!(!Message::chat("doy", "hi").supported_by(1));
+        assert!(Message::chat("doy", "hi").supported_by(PROTO_VERSION));
+    }
+
+    // run with `cargo test --release -- --ignored --nocapture bench_`
+    #[test]
+    #[ignore]
+    fn bench_relay_encoding() {
+        const WATCHERS: u32 = 100;
+        const MESSAGES: u32 = 1_000;
+
+        // each message is 1KiB, and each watcher has its own copy of it in
+        // its queue
+        let msg = Message::terminal_output(&[b'x'; 1024]);
+        let mut bufs: Vec<_> =
+            (0..WATCHERS).map(|_| bytes::BytesMut::new()).collect();
+        let mib = f64::from(MESSAGES) * f64::from(WATCHERS) / 1024.0;
+
+        // building a packet for each watcher and then copying it into the
+        // write buffer, as the length delimited codec required
+        let start = std::time::Instant::now();
+        for _ in 0..MESSAGES {
+            for buf in &mut bufs {
+                let msg = msg.clone();
+                let packet = Packet::from(&msg);
+                let len: u32 = packet.frame.len().try_into().unwrap();
+                buf.extend_from_slice(&len.to_be_bytes());
+                buf.extend_from_slice(&packet.frame);
+                buf.clear();
+            }
+        }
+        let packets = start.elapsed();
+        println!("packets: {:.0} MiB/s", mib / packets.as_secs_f64());
+
+        let mut encoder = MessageEncoder {
+            proto_version: PROTO_VERSION,
+        };
+        let start = std::time::Instant::now();
+        for _ in 0..MESSAGES {
+            for buf in &mut bufs {
+                tokio::codec::Encoder::encode(&mut encoder, msg.clone(), buf)
+                    .unwrap();
+                buf.clear();
+            }
+        }
+        let encoded = start.elapsed();
+        println!("encoder: {:.0} MiB/s", mib / encoded.as_secs_f64());
+    }
+
     fn valid_messages() -> Vec<Message> {
         vec![
             Message::login(
@@ -1035,8 +2967,28 @@ mod test {
                 "screen",
                 crate::term::Size { rows: 24, cols: 80 },
             ),
-            Message::start_streaming(),
-            Message::start_watching("some-session-id"),
+            Message::login(
+                &Auth::Github {
+                    id: Some("some-random-id".to_string()),
+                },
+                AuthClient::Cli,
+                "screen",
+                crate::term::Size { rows: 24, cols: 80 },
+            ),
+            Message::login(
+                &Auth::Google { id: None },
+                AuthClient::Cli,
+                "screen",
+                crate::term::Size { rows: 24, cols: 80 },
+            ),
+            Message::start_streaming(&[], ""),
+            Message::start_streaming(
+                &["doy".to_string(), "sartak".to_string()],
+                "",
+            ),
+            Message::start_streaming(&[], "debugging the build"),
+            Message::start_watching("some-session-id", 0),
+            Message::start_watching("some-session-id", 30),
             Message::heartbeat(),
             Message::terminal_output(b"foobar"),
             Message::terminal_output(b""),
@@ -1049,7 +3001,13 @@ mod test {
                 size: crate::term::Size { rows: 24, cols: 80 },
                 idle_time: 123,
                 title: "it's my terminal title".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                room: "".to_string(),
+                paused: false,
                 watchers: 0,
+                uptime: 4567,
+                start_time: 1_573_000_000,
             }]),
             Message::sessions(&[
                 Session {
@@ -1059,7 +3017,13 @@ mod test {
                     size: crate::term::Size { rows: 24, cols: 80 },
                     idle_time: 123,
                     title: "it's my terminal title".to_string(),
+                    description: "debugging the build".to_string(),
+                    tags: vec!["rust".to_string(), "ci".to_string()],
+                    room: "rustconf".to_string(),
+                    paused: false,
                     watchers: 0,
+                    uptime: 4567,
+                    start_time: 1_573_000_000,
                 },
                 Session {
                     id: "some-other-session-id".to_string(),
@@ -1068,13 +3032,188 @@ mod test {
                     size: crate::term::Size { rows: 24, cols: 80 },
                     idle_time: 68,
                     title: "some other terminal title".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    room: "".to_string(),
+                    paused: false,
                     watchers: 0,
+                    uptime: 89,
+                    start_time: 1_573_004_478,
                 },
             ]),
             Message::disconnected(),
             Message::error("error message"),
             Message::resize(crate::term::Size { rows: 25, cols: 81 }),
             Message::logged_in("doy"),
+            Message::chat("doy", "hello"),
+            Message::chat("", ""),
+            Message::request_control("doy"),
+            Message::request_control(""),
+            Message::grant_control("doy"),
+            Message::revoke_control("doy"),
+            Message::terminal_input(b"ls\r"),
+            Message::terminal_input(b""),
+            Message::list_history(),
+            Message::history(&[]),
+            Message::history(&[
+                PastSession {
+                    username: "doy".to_string(),
+                    title: "it's my terminal title".to_string(),
+                    description: "debugging the build".to_string(),
+                    start_time: 1_574_000_000,
+                    end_time: 1_574_003_600,
+                    bytes: 123_456,
+                },
+                PastSession {
+                    username: "sartak".to_string(),
+                    title: "".to_string(),
+                    description: "".to_string(),
+                    start_time: 1_574_000_000,
+                    end_time: 1_574_000_000,
+                    bytes: 0,
+                },
+            ]),
+            Message::server_shutdown("the server is restarting"),
+            Message::server_shutdown(""),
+            Message::host_key_request(&[0x5a; 32]),
+            Message::host_key_request(b""),
+            Message::host_key(&[0x01; 32], &[0x02; 64]),
+            Message::host_key(b"", b""),
+            Message::hello(),
+            Message::welcome(PROTO_VERSION, 10),
+            Message::welcome(1, 10),
+            Message::welcome(0, 0),
+            Message::update_metadata("deploying", &["ops".to_string()]),
+            Message::update_metadata("", &[]),
+            Message::subscribe_session_list(),
+            Message::session_added(&Session {
+                id: "some-session-id".to_string(),
+                username: "doy".to_string(),
+                term_type: "screen".to_string(),
+                size: crate::term::Size { rows: 24, cols: 80 },
+                idle_time: 0,
+                title: "".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                room: "".to_string(),
+                paused: false,
+                watchers: 0,
+                uptime: 0,
+                start_time: 1_573_004_567,
+            }),
+            Message::session_removed("some-session-id"),
+            Message::session_updated(&Session {
+                id: "some-session-id".to_string(),
+                username: "doy".to_string(),
+                term_type: "screen".to_string(),
+                size: crate::term::Size { rows: 25, cols: 81 },
+                idle_time: 12,
+                title: "it's my terminal title".to_string(),
+                description: "debugging the build".to_string(),
+                tags: vec!["rust".to_string()],
+                room: "".to_string(),
+                paused: true,
+                watchers: 2,
+                uptime: 345,
+                start_time: 1_573_004_222,
+            }),
+            Message::resume_streaming("some-resume-token"),
+            Message::resume_streaming(""),
+            Message::subscribe_session_changes(),
+            Message::session_changed(Some("new title"), None, None),
+            Message::session_changed(None, Some(&["rust".to_string()]), None),
+            Message::session_changed(Some(""), Some(&[]), None),
+            Message::session_changed(None, None, Some(true)),
+            Message::session_changed(None, None, Some(false)),
+            Message::subscribe_time_limit(),
+            Message::time_limit(600),
+            Message::time_limit(0),
+            Message::subscribe_server_limits(),
+            Message::server_limits(12, 100, 10),
+            Message::server_limits(1, 0, 0),
+            Message::admin_list_connections(),
+            Message::admin_connections(&[]),
+            Message::admin_connections(&[
+                AdminConnection {
+                    id: "some-session-id".to_string(),
+                    address: "127.0.0.1:54321".to_string(),
+                    username: "doy".to_string(),
+                    state: "streaming".to_string(),
+                    watch_id: "".to_string(),
+                    connected: 600,
+                },
+                AdminConnection {
+                    id: "some-other-session-id".to_string(),
+                    address: "[::1]:12345".to_string(),
+                    username: "sartak".to_string(),
+                    state: "watching".to_string(),
+                    watch_id: "some-session-id".to_string(),
+                    connected: 30,
+                },
+            ]),
+            Message::admin_kick("some-session-id", "spamming"),
+            Message::admin_kick("some-session-id", ""),
+            Message::admin_notice("restarting in 5 minutes"),
+            Message::admin_get_stats(),
+            Message::admin_stats(&ServerStats {
+                uptime: 86400,
+                connections: 12,
+                streams: 3,
+                watchers: 8,
+                total_connections: 1234,
+                total_streams: 56,
+            }),
+            Message::admin_done(1),
+            Message::subscribe_notices(),
+            Message::notice("restarting in 5 minutes"),
+            Message::subscribe_watcher_count(),
+            Message::watcher_count(3),
+            Message::watcher_count(0),
+            Message::pause_streaming(true),
+            Message::pause_streaming(false),
+            Message::admin_ban("spammer"),
+            Message::admin_ban("10.1.2.3"),
+            Message::join_room("rustconf"),
+            Message::join_room(""),
+            Message::list_room_sessions("rustconf"),
+            Message::admin_kick_matching(
+                "user:spammer",
+                "spamming",
+                true,
+                false,
+            ),
+            Message::admin_kick_matching("tag:offtopic", "", false, true),
+            Message::admin_kick_matching("address:10.1.2.3", "", true, true),
+            Message::admin_kick_matching("address:::1", "", false, false),
+            Message::admin_connections(&[AdminConnection {
+                id: "some-session-id".to_string(),
+                address: "10.1.2.3:4144".to_string(),
+                username: "".to_string(),
+                state: "logging in".to_string(),
+                watch_id: "".to_string(),
+                connected: 0,
+            }]),
+            Message::admin_kick("some-session-id", "ne parlez pas ça ☃"),
+            Message::admin_notice(""),
+            Message::admin_stats(&ServerStats {
+                uptime: 0,
+                connections: 0,
+                streams: 0,
+                watchers: 0,
+                total_connections: 0,
+                total_streams: 0,
+            }),
+            Message::admin_stats(&ServerStats {
+                uptime: std::u32::MAX,
+                connections: std::u32::MAX,
+                streams: std::u32::MAX,
+                watchers: std::u32::MAX,
+                total_connections: std::u32::MAX,
+                total_streams: std::u32::MAX,
+            }),
+            Message::admin_done(0),
+            Message::admin_done(std::u32::MAX),
+            Message::admin_ban("2001:db8::1"),
         ]
     }
 
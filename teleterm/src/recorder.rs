@@ -0,0 +1,246 @@
+use crate::prelude::*;
+
+#[allow(clippy::large_enum_variant)]
+enum FileState {
+    Closed {
+        filename: String,
+    },
+    Opening {
+        filename: String,
+        fut: tokio::fs::file::CreateFuture<String>,
+    },
+    Open {
+        writer: crate::recording::Writer<tokio::fs::File>,
+    },
+}
+
+// writes data to a recording file as it arrives, with each frame timestamped
+// when it is written. data which arrives before the file has been opened is
+// buffered and written as a single frame once it is.
+pub struct Recorder {
+    file: FileState,
+    frame_data: Vec<u8>,
+    format: crate::recording::Format,
+
+    filename: String,
+    // which of the numbered files we are on, when rotating
+    segment: u32,
+    // how much has been written to the current file
+    written: u64,
+    max_size: Option<u64>,
+    rotate: bool,
+    gzip: bool,
+    // set when the file reached its maximum size without rotation, after
+    // which nothing more is recorded
+    full: bool,
+}
+
+impl Recorder {
+    pub fn new(filename: &str) -> Self {
+        Self {
+            file: FileState::Closed {
+                filename: filename.to_string(),
+            },
+            frame_data: vec![],
+            format: crate::recording::Format::default(),
+
+            filename: filename.to_string(),
+            segment: 1,
+            written: 0,
+            max_size: None,
+            rotate: false,
+            gzip: false,
+            full: false,
+        }
+    }
+
+    // limits each file to max_size bytes, after which recording either
+    // stops or moves on to a new numbered file (compressing the finished
+    // one with gzip, if requested)
+    pub fn set_max_size(&mut self, max_size: u64, rotate: bool, gzip: bool) {
+        self.max_size = Some(max_size);
+        self.rotate = rotate;
+        self.gzip = gzip;
+    }
+
+    pub fn set_format(&mut self, format: crate::recording::Format) {
+        self.format = format;
+    }
+
+    pub fn frame(&mut self, data: &[u8]) {
+        if self.full {
+            return;
+        }
+        self.frame_data.extend(data);
+    }
+
+    // true once everything that has been given to the recorder has made it
+    // to the file
+    pub fn is_flushed(&self) -> bool {
+        if let FileState::Open { writer } = &self.file {
+            self.frame_data.is_empty() && !writer.needs_write()
+        } else {
+            false
+        }
+    }
+
+    pub fn poll_open(&mut self) -> component_future::Poll<(), Error> {
+        match &mut self.file {
+            FileState::Closed { filename } => {
+                self.file = FileState::Opening {
+                    filename: filename.to_string(),
+                    fut: tokio::fs::File::create(filename.to_string()),
+                };
+                Ok(component_future::Async::DidWork)
+            }
+            FileState::Opening { filename, fut } => {
+                let file = component_future::try_ready!(fut
+                    .poll()
+                    .with_context(|| crate::error::OpenFile {
+                        filename: filename.clone(),
+                    }));
+                self.file = FileState::Open {
+                    writer: crate::recording::Writer::new(file, self.format),
+                };
+                Ok(component_future::Async::DidWork)
+            }
+            FileState::Open { .. } => {
+                Ok(component_future::Async::NothingToDo)
+            }
+        }
+    }
+
+    pub fn poll_write(&mut self) -> component_future::Poll<(), Error> {
+        let writer = match &mut self.file {
+            FileState::Open { writer } => writer,
+            _ => {
+                return Ok(component_future::Async::NothingToDo);
+            }
+        };
+
+        if !self.frame_data.is_empty() {
+            let len = writer.frame_len(&self.frame_data);
+            // a single frame which is bigger than the limit still has to go
+            // somewhere
+            let written = self.written;
+            let over_limit = self.max_size.map_or(false, |max_size| {
+                written > 0 && written.saturating_add(len) > max_size
+            });
+            if over_limit {
+                // everything already given to this file has to make it
+                // there before moving on
+                if writer.needs_write() {
+                    component_future::try_ready!(writer.poll_write());
+                    return Ok(component_future::Async::DidWork);
+                }
+                self.finish_file();
+                return Ok(component_future::Async::DidWork);
+            }
+
+            writer.frame(&self.frame_data)?;
+            self.written += len;
+            self.frame_data.clear();
+        }
+
+        if writer.needs_write() {
+            component_future::try_ready!(writer.poll_write());
+            Ok(component_future::Async::DidWork)
+        } else {
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
+
+    // called once the current file has reached its maximum size and has
+    // been completely written
+    fn finish_file(&mut self) {
+        let finished = segment_filename(&self.filename, self.segment);
+        if self.rotate {
+            self.segment += 1;
+            self.written = 0;
+            // the file is closed when the writer is dropped here, and the
+            // next one is opened by poll_open
+            self.file = FileState::Closed {
+                filename: segment_filename(&self.filename, self.segment),
+            };
+            if self.gzip {
+                gzip(&finished);
+            }
+        } else {
+            log::warn!(
+                "{} reached its maximum size, no longer recording",
+                finished
+            );
+            self.full = true;
+            self.frame_data.clear();
+        }
+    }
+}
+
+// the first file is the one we were given, and later ones are numbered from
+// 2, before the extension (teleterm.ttyrec, teleterm.2.ttyrec, ...)
+fn segment_filename(filename: &str, segment: u32) -> String {
+    if segment == 1 {
+        return filename.to_string();
+    }
+    let path = std::path::Path::new(filename);
+    let numbered = match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => format!(
+            "{}.{}.{}",
+            stem.to_string_lossy(),
+            segment,
+            extension.to_string_lossy()
+        ),
+        _ => format!(
+            "{}.{}",
+            path.file_name()
+                .map_or_else(|| "".into(), |name| name.to_string_lossy()),
+            segment
+        ),
+    };
+    path.with_file_name(numbered).to_string_lossy().into_owned()
+}
+
+// compressing a large file can take a while, so it happens in the
+// background while recording continues. failing to compress just leaves the
+// file as it was.
+fn gzip(filename: &str) {
+    let filename = filename.to_string();
+    let res = std::thread::Builder::new().spawn(move || {
+        match std::process::Command::new("gzip")
+            .arg("-f")
+            .arg(&filename)
+            .stderr(std::process::Stdio::null())
+            .status()
+        {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                log::warn!("failed to gzip {}: {}", filename, status);
+            }
+            Err(e) => {
+                log::warn!("failed to gzip {}: {}", filename, e);
+            }
+        }
+    });
+    if let Err(e) = res {
+        log::warn!("failed to start gzip thread: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_segment_filename() {
+        assert_eq!(segment_filename("teleterm.ttyrec", 1), "teleterm.ttyrec");
+        assert_eq!(
+            segment_filename("teleterm.ttyrec", 2),
+            "teleterm.2.ttyrec"
+        );
+        assert_eq!(
+            segment_filename("/tmp/rec/session.ttyrec", 12),
+            "/tmp/rec/session.12.ttyrec"
+        );
+        assert_eq!(segment_filename("session", 3), "session.3");
+    }
+}
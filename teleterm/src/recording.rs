@@ -0,0 +1,449 @@
+use crate::prelude::*;
+use std::convert::TryFrom as _;
+use tokio::io::{AsyncRead as _, AsyncWrite as _};
+
+// used for the header of asciicast files when the size of the terminal
+// can't be determined
+const DEFAULT_ASCIICAST_SIZE: crate::term::Size =
+    crate::term::Size { rows: 24, cols: 80 };
+
+// the file formats that recordings can be read from and written to. ttyrec
+// is what teleterm itself uses, and is also what is produced by other tools
+// like ttyrec and termrec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ttyrec,
+    Asciicast,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::Ttyrec
+    }
+}
+
+impl std::convert::TryFrom<&str> for Format {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        match s {
+            "ttyrec" | "native" => Ok(Self::Ttyrec),
+            "asciicast" => Ok(Self::Asciicast),
+            _ => Err(Error::InvalidRecordingFormat {
+                format: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct AsciicastHeader {
+    version: u32,
+}
+
+#[derive(serde::Serialize)]
+struct NewAsciicastHeader {
+    version: u32,
+    width: u16,
+    height: u16,
+    timestamp: u64,
+}
+
+// parses recordings in any of the supported formats into ttyrec frames,
+// with times relative to the start of the recording. if no format is
+// given, it is detected from the start of the data - asciicast files start
+// with a json header line, and anything else is treated as ttyrec.
+pub struct Parser {
+    format: Option<Format>,
+    ttyrec: ttyrec::Parser,
+
+    // asciicast data which hasn't made up a full line yet, or the start of
+    // the data while the format is still being detected
+    buf: Vec<u8>,
+    read_header: bool,
+}
+
+impl Parser {
+    pub fn new(format: Option<Format>) -> Self {
+        Self {
+            format,
+            ttyrec: ttyrec::Parser::new(),
+
+            buf: vec![],
+            read_header: false,
+        }
+    }
+
+    pub fn add_bytes(&mut self, data: &[u8]) {
+        match self.format {
+            Some(Format::Ttyrec) => self.ttyrec.add_bytes(data),
+            Some(Format::Asciicast) => self.buf.extend_from_slice(data),
+            None => {
+                self.buf.extend_from_slice(data);
+                self.detect(false);
+            }
+        }
+    }
+
+    // called once all of the data has been added
+    pub fn finish(&mut self) {
+        if self.format.is_none() {
+            self.detect(true);
+        }
+        if self.format == Some(Format::Asciicast)
+            && !self.buf.is_empty()
+            && !self.buf.ends_with(b"\n")
+        {
+            self.buf.push(b'\n');
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Result<Option<ttyrec::Frame>> {
+        match self.format {
+            Some(Format::Ttyrec) => {
+                Ok(self.ttyrec.next_frame().map(|mut frame| {
+                    if let Some(offset) = self.ttyrec.offset() {
+                        frame.time -= offset;
+                    }
+                    frame
+                }))
+            }
+            Some(Format::Asciicast) => {
+                while let Some(i) = self.buf.iter().position(|&c| c == b'\n')
+                {
+                    let line: Vec<u8> = self.buf.drain(..=i).collect();
+                    if let Some(frame) = self.asciicast_line(&line)? {
+                        return Ok(Some(frame));
+                    }
+                }
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn detect(&mut self, eof: bool) {
+        let format = if self.buf.first().map_or(false, |&c| c != b'{') {
+            Some(Format::Ttyrec)
+        } else if let Some(i) = self.buf.iter().position(|&c| c == b'\n') {
+            Some(detect_line(&self.buf[..i]))
+        } else if eof {
+            Some(detect_line(&self.buf))
+        } else {
+            None
+        };
+
+        if format == Some(Format::Ttyrec) {
+            let buf = std::mem::replace(&mut self.buf, vec![]);
+            self.ttyrec.add_bytes(&buf);
+        }
+        self.format = format;
+    }
+
+    fn asciicast_line(
+        &mut self,
+        line: &[u8],
+    ) -> Result<Option<ttyrec::Frame>> {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            return Ok(None);
+        }
+
+        if !self.read_header {
+            self.read_header = true;
+            let header: AsciicastHeader = serde_json::from_slice(line)
+                .context(crate::error::ParseAsciicast)?;
+            if header.version != 2 {
+                return Err(Error::UnsupportedAsciicastVersion {
+                    version: header.version,
+                });
+            }
+            return Ok(None);
+        }
+
+        let (time, ty, data): (f64, String, String) =
+            serde_json::from_slice(line)
+                .context(crate::error::ParseAsciicast)?;
+        // input and resize events have nothing to play back
+        if ty != "o" {
+            return Ok(None);
+        }
+        Ok(Some(ttyrec::Frame {
+            time: std::time::Duration::from_secs_f64(time.max(0.0)),
+            data: data.into_bytes(),
+        }))
+    }
+}
+
+fn detect_line(line: &[u8]) -> Format {
+    match serde_json::from_slice::<serde_json::Value>(line) {
+        Ok(serde_json::Value::Object(header))
+            if header.contains_key("version") =>
+        {
+            Format::Asciicast
+        }
+        _ => Format::Ttyrec,
+    }
+}
+
+pub struct Reader<R: tokio::io::AsyncRead> {
+    input: R,
+    parser: Parser,
+    eof: bool,
+}
+
+impl<R: tokio::io::AsyncRead> Reader<R> {
+    pub fn new(input: R, format: Option<Format>) -> Self {
+        Self {
+            input,
+            parser: Parser::new(format),
+            eof: false,
+        }
+    }
+
+    pub fn poll_read(
+        &mut self,
+    ) -> futures::Poll<Option<ttyrec::Frame>, Error> {
+        loop {
+            if let Some(frame) = self.parser.next_frame()? {
+                return Ok(futures::Async::Ready(Some(frame)));
+            }
+            if self.eof {
+                return Ok(futures::Async::Ready(None));
+            }
+
+            let mut buf = [0; 4096];
+            let n = futures::try_ready!(self
+                .input
+                .poll_read(&mut buf)
+                .context(crate::error::ReadFile));
+            if n == 0 {
+                self.eof = true;
+                self.parser.finish();
+            } else {
+                self.parser.add_bytes(&buf[..n]);
+            }
+        }
+    }
+}
+
+pub enum Writer<W: tokio::io::AsyncWrite> {
+    Ttyrec(ttyrec::Writer<W>),
+    Asciicast(AsciicastWriter<W>),
+}
+
+impl<W: tokio::io::AsyncWrite> Writer<W> {
+    pub fn new(output: W, format: Format) -> Self {
+        match format {
+            Format::Ttyrec => Self::Ttyrec(ttyrec::Writer::new(output)),
+            Format::Asciicast => {
+                let size = crate::term::Size::get()
+                    .unwrap_or(DEFAULT_ASCIICAST_SIZE);
+                Self::Asciicast(AsciicastWriter::new(output, size))
+            }
+        }
+    }
+
+    pub fn frame(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            Self::Ttyrec(writer) => {
+                writer.frame(data).context(crate::error::WriteTtyrec)
+            }
+            Self::Asciicast(writer) => {
+                writer.frame(data);
+                Ok(())
+            }
+        }
+    }
+
+    // roughly how much writing a frame containing data will add to the file
+    pub fn frame_len(&self, data: &[u8]) -> u64 {
+        match self {
+            // the ttyrec header is three 32-bit integers
+            Self::Ttyrec(_) => {
+                12 + u64::try_from(data.len()).unwrap_or(std::u64::MAX)
+            }
+            // the data is written as a json string, with the time and
+            // event type in front of it
+            Self::Asciicast(_) => {
+                let escaped = serde_json::Value::String(
+                    String::from_utf8_lossy(data).into_owned(),
+                )
+                .to_string();
+                24 + u64::try_from(escaped.len()).unwrap_or(std::u64::MAX)
+            }
+        }
+    }
+
+    pub fn needs_write(&self) -> bool {
+        match self {
+            Self::Ttyrec(writer) => writer.needs_write(),
+            Self::Asciicast(writer) => writer.needs_write(),
+        }
+    }
+
+    pub fn poll_write(&mut self) -> futures::Poll<(), Error> {
+        match self {
+            Self::Ttyrec(writer) => {
+                writer.poll_write().context(crate::error::WriteTtyrec)
+            }
+            Self::Asciicast(writer) => writer.poll_write(),
+        }
+    }
+}
+
+// writes asciicast v2 files, with each frame written as an output event
+// timed from when the writer was created
+pub struct AsciicastWriter<W: tokio::io::AsyncWrite> {
+    output: W,
+    start: std::time::Instant,
+    to_write: Vec<u8>,
+
+    // asciicast events have to contain valid utf8, so a multibyte
+    // character which is split across frames is held back until the rest of
+    // it arrives
+    partial: Vec<u8>,
+}
+
+impl<W: tokio::io::AsyncWrite> AsciicastWriter<W> {
+    fn new(output: W, size: crate::term::Size) -> Self {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        let header = NewAsciicastHeader {
+            version: 2,
+            width: size.cols,
+            height: size.rows,
+            timestamp,
+        };
+        // serializing a struct of integers can't fail
+        let mut to_write = serde_json::to_vec(&header).unwrap();
+        to_write.push(b'\n');
+
+        Self {
+            output,
+            start: std::time::Instant::now(),
+            to_write,
+
+            partial: vec![],
+        }
+    }
+
+    fn frame(&mut self, data: &[u8]) {
+        self.partial.extend_from_slice(data);
+        let len = complete_utf8_len(&self.partial);
+        if len == 0 {
+            return;
+        }
+        let data: Vec<u8> = self.partial.drain(..len).collect();
+
+        let time = self.start.elapsed().as_secs_f64();
+        let event = serde_json::Value::Array(vec![
+            serde_json::Value::from(time),
+            serde_json::Value::from("o"),
+            serde_json::Value::from(String::from_utf8_lossy(&data)),
+        ]);
+        self.to_write
+            .extend_from_slice(event.to_string().as_bytes());
+        self.to_write.push(b'\n');
+    }
+
+    fn needs_write(&self) -> bool {
+        !self.to_write.is_empty()
+    }
+
+    fn poll_write(&mut self) -> futures::Poll<(), Error> {
+        let n = futures::try_ready!(self
+            .output
+            .poll_write(&self.to_write)
+            .context(crate::error::WriteFile));
+        self.to_write.drain(..n);
+        Ok(futures::Async::Ready(()))
+    }
+}
+
+// the length of data without any incomplete utf8 character at the end.
+// invalid utf8 anywhere else is written out (lossily) as it is.
+fn complete_utf8_len(data: &[u8]) -> usize {
+    match std::str::from_utf8(data) {
+        Ok(_) => data.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => data.len(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frames(format: Option<Format>, data: &[u8]) -> Vec<ttyrec::Frame> {
+        let mut parser = Parser::new(format);
+        // one byte at a time, to make sure partial lines and headers are
+        // handled
+        for c in data {
+            parser.add_bytes(&[*c]);
+        }
+        parser.finish();
+        let mut frames = vec![];
+        while let Some(frame) = parser.next_frame().unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn test_asciicast() {
+        let data = [
+            r#"{"version": 2, "width": 80, "height": 24}"#,
+            r#"[0.5, "o", "foo\r\n"]"#,
+            r#"[0.75, "i", "q"]"#,
+            "",
+            r#"[1.25, "o", "\u001b[31mbar"]"#,
+        ]
+        .join("\n");
+        for format in &[None, Some(Format::Asciicast)] {
+            let frames = frames(*format, data.as_bytes());
+            assert_eq!(frames.len(), 2);
+            assert_eq!(frames[0].time, std::time::Duration::from_millis(500));
+            assert_eq!(frames[0].data, b"foo\r\n");
+            assert_eq!(
+                frames[1].time,
+                std::time::Duration::from_millis(1250)
+            );
+            assert_eq!(frames[1].data, b"\x1b[31mbar");
+        }
+
+        let mut parser = Parser::new(None);
+        parser.add_bytes(b"{\"version\": 1, \"stdout\": []}\n");
+        parser.finish();
+        assert!(parser.next_frame().is_err());
+    }
+
+    #[test]
+    fn test_ttyrec() {
+        let mut data = vec![];
+        for (secs, frame) in &[(1000u32, &b"foo"[..]), (1002, b"{bar}")] {
+            data.extend_from_slice(&secs.to_le_bytes());
+            data.extend_from_slice(&0u32.to_le_bytes());
+            let len = u32::try_from(frame.len()).unwrap();
+            data.extend_from_slice(&len.to_le_bytes());
+            data.extend_from_slice(frame);
+        }
+        for format in &[None, Some(Format::Ttyrec)] {
+            let frames = frames(*format, &data);
+            assert_eq!(frames.len(), 2);
+            assert_eq!(frames[0].time, std::time::Duration::from_secs(0));
+            assert_eq!(frames[0].data, b"foo");
+            assert_eq!(frames[1].time, std::time::Duration::from_secs(2));
+            assert_eq!(frames[1].data, b"{bar}");
+        }
+    }
+
+    #[test]
+    fn test_complete_utf8_len() {
+        assert_eq!(complete_utf8_len(b"foo"), 3);
+        assert_eq!(complete_utf8_len("fo\u{e9}".as_bytes()), 4);
+        assert_eq!(complete_utf8_len(b"fo\xc3"), 2);
+        assert_eq!(complete_utf8_len(b"f\xffo"), 3);
+    }
+}
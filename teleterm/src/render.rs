@@ -0,0 +1,421 @@
+use crate::prelude::*;
+
+// how the screen of the stream being watched is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Terminal,
+    TextDiff,
+}
+
+impl Mode {
+    pub fn renderer(self) -> Box<dyn Render> {
+        match self {
+            Self::Terminal => Box::new(Terminal::new()),
+            Self::TextDiff => Box::new(TextDiff::new()),
+        }
+    }
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Self::Terminal
+    }
+}
+
+impl std::convert::TryFrom<&str> for Mode {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        match s {
+            "terminal" => Ok(Self::Terminal),
+            "text-diff" => Ok(Self::TextDiff),
+            _ => Err(Error::InvalidRenderMode {
+                mode: s.to_string(),
+            }),
+        }
+    }
+}
+
+// turns the state of the watched terminal into the bytes that we write to
+// our own terminal
+pub trait Render: Send {
+    // called with output from the stream, after it has already been
+    // processed into screen
+    fn output(&mut self, data: &[u8], screen: &vt100::Screen) -> Vec<u8>;
+
+    // called when the screen needs to be shown again, since something else
+    // was drawn over it
+    fn redraw(&mut self, screen: &vt100::Screen) -> Vec<u8>;
+
+    // called when we start showing a different screen
+    fn reset(&mut self) {}
+
+    // called with the size of our own terminal, whenever it changes
+    fn resize(&mut self, _size: crate::term::Size) {}
+
+    // called with the messages that would otherwise be drawn on the status
+    // line, for renderers which don't draw overlays
+    fn status(&mut self, _text: &str) -> Vec<u8> {
+        vec![]
+    }
+
+    // whether the status line and timeline should be drawn over the screen
+    fn overlays(&self) -> bool {
+        true
+    }
+}
+
+// passes the output through unchanged, so our terminal looks just like the
+// streamer's. when their terminal is bigger than ours, that would garble
+// everything, so the part of their screen around their cursor is drawn from
+// the parsed screen instead.
+pub struct Terminal {
+    size: Option<crate::term::Size>,
+    // the row and column of their screen which is in our top left corner,
+    // while cropping
+    origin: (u16, u16),
+    // what was last drawn on each of our rows, while cropping
+    rows: Vec<Vec<u8>>,
+    cropping: bool,
+}
+
+impl Terminal {
+    pub fn new() -> Self {
+        Self {
+            size: None,
+            origin: (0, 0),
+            rows: vec![],
+            cropping: false,
+        }
+    }
+
+    fn fits(&self, screen: &vt100::Screen) -> bool {
+        let (rows, cols) = screen.size();
+        self.size.map_or(true, |size| {
+            crate::term::Size { rows, cols }.fits_in(size)
+        })
+    }
+
+    // draws the rows of the visible part of the screen which changed since
+    // the last time, or all of them if full is set
+    fn crop(&mut self, screen: &vt100::Screen, full: bool) -> Vec<u8> {
+        let size = self.size.unwrap();
+        let mut out = vec![];
+        if full || !self.cropping {
+            out.extend(b"\x1b[H\x1b[2J");
+            self.rows.clear();
+            self.cropping = true;
+        }
+
+        // scroll as little as possible to keep the cursor visible
+        let (rows, cols) = screen.size();
+        let (row, col) = screen.cursor_position();
+        self.origin = (
+            follow(self.origin.0, row, size.rows, rows),
+            follow(self.origin.1, col, size.cols, cols),
+        );
+
+        let visible_rows = rows.saturating_sub(self.origin.0).min(size.rows);
+        let visible_cols = cols.saturating_sub(self.origin.1).min(size.cols);
+        for i in 0..visible_rows {
+            let line = crop_row(
+                screen,
+                self.origin.0 + i,
+                self.origin.1,
+                visible_cols,
+            );
+            let i = usize::from(i);
+            if self.rows.get(i) == Some(&line) {
+                continue;
+            }
+            out.extend(format!("\x1b[{};1H", i + 1).as_bytes());
+            out.extend(&line);
+            if i < self.rows.len() {
+                self.rows[i] = line;
+            } else {
+                self.rows.push(line);
+            }
+        }
+        out.extend(
+            format!(
+                "\x1b[{};{}H",
+                row.saturating_sub(self.origin.0) + 1,
+                col.saturating_sub(self.origin.1) + 1
+            )
+            .as_bytes(),
+        );
+        out
+    }
+}
+
+impl Render for Terminal {
+    fn output(&mut self, data: &[u8], screen: &vt100::Screen) -> Vec<u8> {
+        if !self.fits(screen) {
+            return self.crop(screen, false);
+        }
+        // their terminal just shrank to fit in ours
+        if self.cropping {
+            return self.redraw(screen);
+        }
+        data.to_vec()
+    }
+
+    fn redraw(&mut self, screen: &vt100::Screen) -> Vec<u8> {
+        if !self.fits(screen) {
+            return self.crop(screen, true);
+        }
+        self.cropping = false;
+        let mut out = b"\x1b[H\x1b[2J".to_vec();
+        out.extend(screen.contents_formatted());
+        out
+    }
+
+    fn reset(&mut self) {
+        self.origin = (0, 0);
+        self.rows.clear();
+        self.cropping = false;
+    }
+
+    fn resize(&mut self, size: crate::term::Size) {
+        self.size = Some(size);
+        self.rows.clear();
+        self.cropping = false;
+    }
+}
+
+// returns where the visible part of a row or column of the screen should
+// start in order to show pos, moving it from origin as little as possible
+fn follow(origin: u16, pos: u16, visible: u16, total: u16) -> u16 {
+    if visible == 0 {
+        return 0;
+    }
+    let origin = if pos < origin {
+        pos
+    } else if pos >= origin + visible {
+        pos + 1 - visible
+    } else {
+        origin
+    };
+    origin.min(total.saturating_sub(visible))
+}
+
+// draws width cells of the given row of the screen, starting at col, with
+// their colors and attributes
+fn crop_row(
+    screen: &vt100::Screen,
+    row: u16,
+    col: u16,
+    width: u16,
+) -> Vec<u8> {
+    let mut out = b"\x1b[m".to_vec();
+    let mut attrs = String::new();
+    let mut skip = false;
+    for i in 0..width {
+        let cell = if let Some(cell) = screen.cell(row, col + i) {
+            cell
+        } else {
+            break;
+        };
+        // the second half of a wide character
+        if skip {
+            skip = false;
+            continue;
+        }
+        let cell_attrs = sgr(cell);
+        if cell_attrs != attrs {
+            out.extend(cell_attrs.as_bytes());
+            attrs = cell_attrs;
+        }
+        let contents = cell.contents();
+        // wide characters which are cut off by the edge of our terminal
+        // would wrap, so they are left out
+        if contents.is_empty() || (cell.is_wide() && i + 1 == width) {
+            out.push(b' ');
+        } else {
+            out.extend(contents.as_bytes());
+            skip = cell.is_wide();
+        }
+    }
+    out.extend(b"\x1b[m\x1b[K");
+    out
+}
+
+fn sgr(cell: &vt100::Cell) -> String {
+    let mut params = vec!["0".to_string()];
+    let color = |color, base: u8| match color {
+        vt100::Color::Default => None,
+        vt100::Color::Idx(i) if i < 8 => Some(format!("{}", base + i)),
+        vt100::Color::Idx(i) => Some(format!("{};5;{}", base + 8, i)),
+        vt100::Color::Rgb(r, g, b) => {
+            Some(format!("{};2;{};{};{}", base + 8, r, g, b))
+        }
+    };
+    params.extend(color(cell.fgcolor(), 30));
+    params.extend(color(cell.bgcolor(), 40));
+    if cell.bold() {
+        params.push("1".to_string());
+    }
+    if cell.italic() {
+        params.push("3".to_string());
+    }
+    if cell.underline() {
+        params.push("4".to_string());
+    }
+    if cell.inverse() {
+        params.push("7".to_string());
+    }
+    format!("\x1b[{}m", params.join(";"))
+}
+
+// prints the rows of the screen which have changed as plain lines of text,
+// without any escape sequences. screen readers and braille displays cope
+// much better with this than with a program redrawing the terminal.
+pub struct TextDiff {
+    rows: Vec<String>,
+    status: Option<String>,
+}
+
+impl TextDiff {
+    pub fn new() -> Self {
+        Self {
+            rows: vec![],
+            status: None,
+        }
+    }
+
+    fn diff(&mut self, screen: &vt100::Screen) -> Vec<u8> {
+        let (_, cols) = screen.size();
+        let rows: Vec<_> = screen
+            .rows(0, cols)
+            .map(|row| row.trim_end().to_string())
+            .collect();
+
+        let mut out = vec![];
+        for (i, row) in rows.iter().enumerate() {
+            // rows which were cleared don't need announcing
+            if row.is_empty() || self.rows.get(i) == Some(row) {
+                continue;
+            }
+            out.extend(row.as_bytes());
+            out.extend(b"\r\n");
+        }
+        self.rows = rows;
+        out
+    }
+}
+
+impl Render for TextDiff {
+    fn output(&mut self, _data: &[u8], screen: &vt100::Screen) -> Vec<u8> {
+        self.diff(screen)
+    }
+
+    // everything that was on the screen has already been printed, so only
+    // the changes are needed here too
+    fn redraw(&mut self, screen: &vt100::Screen) -> Vec<u8> {
+        self.diff(screen)
+    }
+
+    fn reset(&mut self) {
+        self.rows.clear();
+        self.status = None;
+    }
+
+    fn status(&mut self, text: &str) -> Vec<u8> {
+        if self.status.as_ref().map(std::string::String::as_str) == Some(text)
+        {
+            return vec![];
+        }
+        self.status = Some(text.to_string());
+        // the text may have come from another user, so don't let it send
+        // arbitrary escape sequences to our terminal
+        let line: String = text.chars().filter(|c| !c.is_control()).collect();
+        format!("[{}]\r\n", line).into_bytes()
+    }
+
+    fn overlays(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_text_diff() {
+        let mut term = vt100::Parser::new(24, 80, 0);
+        let mut render = TextDiff::new();
+
+        term.process(b"\x1b[1mhello\x1b[m\r\nworld");
+        assert_eq!(
+            render.output(b"", term.screen()),
+            b"hello\r\nworld\r\n".to_vec()
+        );
+
+        // only the rows that changed are printed again
+        term.process(b"\x1b[2;1Hthere");
+        assert_eq!(render.output(b"", term.screen()), b"there\r\n".to_vec());
+        assert_eq!(render.redraw(term.screen()), b"".to_vec());
+
+        render.reset();
+        assert_eq!(
+            render.redraw(term.screen()),
+            b"hello\r\nthere\r\n".to_vec()
+        );
+
+        assert_eq!(render.status("doy: hi\x1b[2J"), b"[doy: hi[2J]\r\n");
+        assert_eq!(render.status("doy: hi\x1b[2J"), b"".to_vec());
+    }
+
+    #[test]
+    fn test_terminal_crop() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let row = |term: &vt100::Parser, i| -> String {
+            term.screen()
+                .rows(0, size.cols)
+                .nth(i)
+                .unwrap()
+                .trim_end()
+                .to_string()
+        };
+        let mut render = Terminal::new();
+        render.resize(size);
+
+        // streams which fit are passed through unchanged
+        let mut term = vt100::Parser::new(24, 80, 0);
+        term.process(b"hello");
+        assert_eq!(render.output(b"hello", term.screen()), b"hello".to_vec());
+
+        let mut term = vt100::Parser::new(30, 100, 0);
+        let mut local = vt100::Parser::new(size.rows, size.cols, 0);
+        term.process(b"hello");
+        local.process(&render.output(b"hello", term.screen()));
+        assert_eq!(row(&local, 0), "hello");
+        assert_eq!(local.screen().cursor_position(), (0, 5));
+
+        // the visible part of the screen follows the cursor
+        term.process(b"\x1b[1;91Hworld");
+        local.process(&render.output(b"", term.screen()));
+        assert_eq!(row(&local, 0), format!("{}world", " ".repeat(74)));
+        assert_eq!(local.screen().cursor_position(), (0, 79));
+        term.process(b"\x1b[2;20H\x1b[31mred");
+        local.process(&render.output(b"", term.screen()));
+        assert_eq!(row(&local, 1), "   red");
+        assert_eq!(
+            local.screen().cell(1, 3).unwrap().fgcolor(),
+            vt100::Color::Idx(1)
+        );
+        assert_eq!(local.screen().cursor_position(), (1, 6));
+
+        term.process(b"\x1b[30;20Hbottom");
+        local.process(&render.output(b"", term.screen()));
+        assert_eq!(row(&local, 23), "   bottom");
+        assert_eq!(local.screen().cursor_position(), (23, 9));
+        assert_eq!(row(&local, 0), "");
+
+        // going back to a size which fits draws the whole screen again
+        term.set_size(24, 80);
+        local.process(&render.output(b"", term.screen()));
+        assert_eq!(row(&local, 0), "hello");
+    }
+}
@@ -0,0 +1,231 @@
+use crate::prelude::*;
+use std::os::unix::process::ExitStatusExt as _;
+
+enum FileState {
+    Closed {
+        filename: String,
+    },
+    Opening {
+        filename: String,
+        fut: tokio::fs::file::OpenFuture<String>,
+    },
+    Open {
+        reader: ttyrec::Reader<tokio::fs::File>,
+    },
+    Eof,
+}
+
+// this produces the same events as tokio_pty_process_stream::Process, so
+// that a recording can be used as a drop-in replacement for the process
+// input source in tt stream. the frames in the recording are emitted with
+// their original timing, scaled by the playback ratio.
+pub struct Replay {
+    filename: String,
+    file: FileState,
+    playback_ratio: f32,
+
+    // unset when there is no terminal to follow the size of
+    resizer: Option<
+        Box<
+            dyn futures::Stream<
+                    Item = (u16, u16),
+                    Error = crate::error::Error,
+                > + Send,
+        >,
+    >,
+
+    start: std::time::Instant,
+    frame: Option<ttyrec::Frame>,
+    timer: Option<tokio::timer::Delay>,
+    exited: bool,
+    needs_exit_event: bool,
+}
+
+impl Replay {
+    pub fn new(filename: &str, playback_ratio: f32) -> Self {
+        Self {
+            filename: filename.to_string(),
+            file: FileState::Closed {
+                filename: filename.to_string(),
+            },
+            playback_ratio,
+
+            resizer: Some(Box::new(
+                tokio_terminal_resize::resizes()
+                    .flatten_stream()
+                    .context(crate::error::Resize),
+            )),
+
+            start: std::time::Instant::now(),
+            frame: None,
+            timer: None,
+            exited: false,
+            needs_exit_event: false,
+        }
+    }
+
+    pub fn ignore_resizes(&mut self) {
+        self.resizer = None;
+    }
+}
+
+impl Replay {
+    const POLL_FNS:
+        &'static [&'static dyn for<'a> Fn(
+            &'a mut Self,
+        )
+            -> component_future::Poll<
+            Option<tokio_pty_process_stream::Event>,
+            Error,
+        >] = &[
+        &Self::poll_open,
+        &Self::poll_exit,
+        &Self::poll_resize,
+        &Self::poll_read_file,
+        &Self::poll_write_frame,
+    ];
+
+    fn poll_open(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        match &mut self.file {
+            FileState::Closed { filename } => {
+                self.file = FileState::Opening {
+                    filename: filename.to_string(),
+                    fut: tokio::fs::File::open(filename.to_string()),
+                };
+                Ok(component_future::Async::DidWork)
+            }
+            FileState::Opening { filename, fut } => {
+                let file = component_future::try_ready!(fut
+                    .poll()
+                    .with_context(|| {
+                        crate::error::OpenFile {
+                            filename: filename.to_string(),
+                        }
+                    }));
+                self.file = FileState::Open {
+                    reader: ttyrec::Reader::new(file),
+                };
+                self.start = std::time::Instant::now();
+                Ok(component_future::Async::Ready(Some(
+                    tokio_pty_process_stream::Event::CommandStart {
+                        cmd: self.filename.clone(),
+                        args: vec![],
+                    },
+                )))
+            }
+            _ => Ok(component_future::Async::NothingToDo),
+        }
+    }
+
+    fn poll_exit(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        if !self.exited {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        if self.needs_exit_event {
+            self.needs_exit_event = false;
+            Ok(component_future::Async::Ready(Some(
+                tokio_pty_process_stream::Event::CommandExit {
+                    status: std::process::ExitStatus::from_raw(0),
+                },
+            )))
+        } else {
+            Ok(component_future::Async::Ready(None))
+        }
+    }
+
+    fn poll_resize(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        let resizer = if let Some(resizer) = &mut self.resizer {
+            resizer
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+        if let Some((rows, cols)) =
+            component_future::try_ready!(resizer.poll())
+        {
+            Ok(component_future::Async::Ready(Some(
+                tokio_pty_process_stream::Event::Resize {
+                    size: (rows, cols),
+                },
+            )))
+        } else {
+            // the terminal went away, so there is nothing left to follow
+            self.resizer = None;
+            Ok(component_future::Async::DidWork)
+        }
+    }
+
+    fn poll_read_file(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        if self.frame.is_some() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+        let reader = if let FileState::Open { reader } = &mut self.file {
+            reader
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        if let Some(frame) = component_future::try_ready!(reader
+            .poll_read()
+            .context(crate::error::ReadTtyrec))
+        {
+            // frames with timestamps from before the first frame are
+            // played immediately
+            let offset = frame
+                .time
+                .checked_sub(reader.offset().unwrap())
+                .unwrap_or_default();
+            self.timer = Some(tokio::timer::Delay::new(
+                self.start + offset.div_f32(self.playback_ratio),
+            ));
+            self.frame = Some(frame);
+        } else {
+            self.file = FileState::Eof;
+            self.exited = true;
+            self.needs_exit_event = true;
+        }
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_write_frame(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        let timer = if let Some(timer) = &mut self.timer {
+            timer
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        component_future::try_ready!(timer
+            .poll()
+            .context(crate::error::TimerReplay));
+        self.timer = None;
+        let frame = self.frame.take().unwrap();
+        Ok(component_future::Async::Ready(Some(
+            tokio_pty_process_stream::Event::Output { data: frame.data },
+        )))
+    }
+}
+
+#[must_use = "streams do nothing unless polled"]
+impl futures::Stream for Replay {
+    type Item = tokio_pty_process_stream::Event;
+    type Error = Error;
+
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        component_future::poll_stream(self, Self::POLL_FNS)
+    }
+}
@@ -0,0 +1,211 @@
+use crate::prelude::*;
+use std::os::unix::process::ExitStatusExt as _;
+use tokio::io::{AsyncRead as _, AsyncWrite as _};
+
+const READ_BUFFER_SIZE: usize = 4 * 1024;
+
+// this produces the same events as tokio_pty_process_stream::Process, so
+// that it can be used as a drop-in replacement for the process input source
+// in tt stream
+pub struct Serial<R: tokio::io::AsyncRead + 'static> {
+    device: String,
+    settings: tokio_serial::SerialPortSettings,
+    port: Option<tokio_serial::Serial>,
+
+    input: R,
+    input_buf: std::collections::VecDeque<u8>,
+    input_done: bool,
+
+    resizer: Box<
+        dyn futures::Stream<Item = (u16, u16), Error = crate::error::Error>
+            + Send,
+    >,
+
+    read_buf: [u8; READ_BUFFER_SIZE],
+    exited: bool,
+    needs_exit_event: bool,
+}
+
+impl<R: tokio::io::AsyncRead + 'static> Serial<R> {
+    pub fn new(
+        device: &str,
+        settings: tokio_serial::SerialPortSettings,
+        input: R,
+    ) -> Self {
+        Self {
+            device: device.to_string(),
+            settings,
+            port: None,
+
+            input,
+            input_buf: std::collections::VecDeque::new(),
+            input_done: false,
+
+            resizer: Box::new(
+                tokio_terminal_resize::resizes()
+                    .flatten_stream()
+                    .context(crate::error::Resize),
+            ),
+
+            read_buf: [0; READ_BUFFER_SIZE],
+            exited: false,
+            needs_exit_event: false,
+        }
+    }
+}
+
+impl<R: tokio::io::AsyncRead + 'static> Serial<R> {
+    const POLL_FNS:
+        &'static [&'static dyn for<'a> Fn(
+            &'a mut Self,
+        )
+            -> component_future::Poll<
+            Option<tokio_pty_process_stream::Event>,
+            Error,
+        >] = &[
+        &Self::poll_open,
+        &Self::poll_exit,
+        &Self::poll_resize,
+        &Self::poll_read_stdin,
+        &Self::poll_write_port,
+        &Self::poll_read_port,
+    ];
+
+    fn poll_open(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        if self.port.is_some() || self.exited {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        let port =
+            tokio_serial::Serial::from_path(&self.device, &self.settings)
+                .context(crate::error::OpenSerial {
+                    device: self.device.clone(),
+                })?;
+        self.port = Some(port);
+
+        Ok(component_future::Async::Ready(Some(
+            tokio_pty_process_stream::Event::CommandStart {
+                cmd: self.device.clone(),
+                args: vec![],
+            },
+        )))
+    }
+
+    fn poll_exit(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        if !self.exited {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        if self.needs_exit_event {
+            self.needs_exit_event = false;
+            // there is no real exit status for a serial device, so just
+            // report success when the device goes away
+            Ok(component_future::Async::Ready(Some(
+                tokio_pty_process_stream::Event::CommandExit {
+                    status: std::process::ExitStatus::from_raw(0),
+                },
+            )))
+        } else {
+            Ok(component_future::Async::Ready(None))
+        }
+    }
+
+    fn poll_resize(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        let (rows, cols) = component_future::try_ready!(self.resizer.poll())
+            .context(crate::error::ResizeEnded)?;
+        Ok(component_future::Async::Ready(Some(
+            tokio_pty_process_stream::Event::Resize { size: (rows, cols) },
+        )))
+    }
+
+    fn poll_read_stdin(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        if self.input_done || !self.input_buf.is_empty() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        let mut buf = [0; READ_BUFFER_SIZE];
+        let n = component_future::try_ready!(self
+            .input
+            .poll_read(&mut buf)
+            .context(crate::error::ReadTerminal));
+        if n > 0 {
+            self.input_buf.extend(buf[..n].iter());
+        } else {
+            self.input_done = true;
+        }
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_write_port(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        if self.input_buf.is_empty() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+        let port = if let Some(port) = &mut self.port {
+            port
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        let (a, b) = self.input_buf.as_slices();
+        let buf = if a.is_empty() { b } else { a };
+        let n = component_future::try_ready!(port
+            .poll_write(buf)
+            .context(crate::error::WriteSerial));
+        for _ in 0..n {
+            self.input_buf.pop_front();
+        }
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_read_port(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        let port = if let Some(port) = &mut self.port {
+            port
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        let n = component_future::try_ready!(port
+            .poll_read(&mut self.read_buf)
+            .context(crate::error::ReadSerial));
+        if n > 0 {
+            Ok(component_future::Async::Ready(Some(
+                tokio_pty_process_stream::Event::Output {
+                    data: self.read_buf[..n].to_vec(),
+                },
+            )))
+        } else {
+            self.port = None;
+            self.exited = true;
+            self.needs_exit_event = true;
+            Ok(component_future::Async::DidWork)
+        }
+    }
+}
+
+#[must_use = "streams do nothing unless polled"]
+impl<R: tokio::io::AsyncRead + 'static> futures::Stream for Serial<R> {
+    type Item = tokio_pty_process_stream::Event;
+    type Error = Error;
+
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        component_future::poll_stream(self, Self::POLL_FNS)
+    }
+}
@@ -1,8 +1,95 @@
 use crate::prelude::*;
+use std::convert::{TryFrom as _, TryInto as _};
 use tokio::util::FutureExt as _;
 
+pub mod access;
+pub mod history;
+pub mod replay_cache;
 pub mod tls;
 
+// number of recent messages from each connection to include in the
+// diagnostic dump when a client is quarantined
+const DIAGNOSTIC_MESSAGES: usize = 20;
+
+// number of finished streams to remember, so that people can see what was
+// streamed recently
+const PAST_SESSIONS: usize = 100;
+
+const SHUTDOWN_REASON: &str = "the server is shutting down";
+
+// sent to watchers as chat messages with no sender, so that they know why
+// the stream stopped and started again
+const RECONNECTING_NOTICE: &str =
+    "the streamer was disconnected, waiting for them to reconnect";
+const RESUMED_NOTICE: &str = "the streamer reconnected";
+const TIME_LIMIT_NOTICE: &str = "the stream reached its time limit";
+
+// clients are asked to send heartbeats often enough that they would have to
+// miss this many in a row before hitting the read timeout
+const MISSED_HEARTBEATS: u32 = 3;
+
+// when coalescing terminal output for a slow connection, give up and resend
+// the whole screen once the merged output gets larger than this
+const MAX_COALESCED_OUTPUT: usize = 1024 * 1024;
+
+// how often the ban file is checked for changes
+const BAN_FILE_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(5);
+
+// how often connections which have subscribed to the list of sessions are
+// sent the changes to it
+const SESSION_LIST_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(1);
+
+// how far (in seconds) the idle times and uptimes that subscribed
+// connections know about can fall behind before they are resent
+const SESSION_LIST_MAX_DRIFT: u32 = 60;
+
+// how often streams are checked against the time limit
+const TIME_LIMIT_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(1);
+
+// how often streamers are sent changes to the number of people watching
+// them
+const WATCHER_COUNT_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(1);
+
+// what connections accepted on a given listener are allowed to do, so that
+// streaming and watching can be exposed on different addresses
+#[derive(Debug, Clone, Copy)]
+pub struct ListenerRoles {
+    pub stream: bool,
+    pub watch: bool,
+}
+
+// what to do when a connection isn't reading messages as fast as we are
+// queueing them, so that one slow client can't use up all of the server's
+// memory. messages other than terminal output are never dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendQueuePolicy {
+    // throw away the queued terminal output and send a fresh copy of the
+    // screen in its place
+    DropOldest,
+    // merge the queued terminal output into as few messages as possible
+    Coalesce,
+    Disconnect,
+}
+
+impl std::convert::TryFrom<&str> for SendQueuePolicy {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        match s {
+            "drop-oldest" => Ok(Self::DropOldest),
+            "coalesce" => Ok(Self::Coalesce),
+            "disconnect" => Ok(Self::Disconnect),
+            _ => Err(Error::InvalidSendQueuePolicy {
+                policy: s.to_string(),
+            }),
+        }
+    }
+}
+
 enum ReadSocket<
     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
 > {
@@ -66,11 +153,32 @@ enum ConnectionState {
         username: String,
         term_info: TerminalInfo,
         term: vt100::Parser,
+        allowed_watchers: std::collections::HashSet<String>,
+        // control belongs to a single watcher's connection (by id), not to
+        // everyone logged in with that name
+        controller: Option<String>,
+        // the connection which most recently asked for control for each
+        // username, since grants from the streamer are made by username
+        control_requests: std::collections::HashMap<String, String>,
+        history: history::History,
+        replay_cache: replay_cache::ReplayCache,
+        description: String,
+        // set by the streamer's naming command, if it has one. an empty
+        // title means to use the title of the terminal.
+        title: String,
+        tags: Vec<String>,
+        // empty until the streamer joins a room
+        room: String,
+        // the streamer has stopped sending output for now
+        paused: bool,
+        started: std::time::SystemTime,
+        bytes: u64,
     },
     Watching {
         username: String,
         term_info: TerminalInfo,
         watch_id: String,
+        catchup: Option<history::Catchup>,
     },
 }
 
@@ -139,6 +247,202 @@ impl ConnectionState {
         }
     }
 
+    fn allowed_watchers(&self) -> Option<&std::collections::HashSet<String>> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming {
+                allowed_watchers, ..
+            } => Some(allowed_watchers),
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn controller(&self) -> Option<&str> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { controller, .. } => {
+                controller.as_ref().map(std::string::String::as_str)
+            }
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn set_controller(&mut self, id: Option<&str>) {
+        if let Self::Streaming { controller, .. } = self {
+            *controller = id.map(std::string::ToString::to_string);
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn add_control_request(&mut self, username: &str, id: &str) {
+        if let Self::Streaming {
+            control_requests, ..
+        } = self
+        {
+            control_requests.insert(username.to_string(), id.to_string());
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn take_control_request(&mut self, username: &str) -> Option<String> {
+        if let Self::Streaming {
+            control_requests, ..
+        } = self
+        {
+            control_requests.remove(username)
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn description(&self) -> Option<&str> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { description, .. } => Some(description),
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn title(&self) -> Option<&str> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { title, term, .. } => {
+                if title.is_empty() {
+                    Some(term.screen().title())
+                } else {
+                    Some(title)
+                }
+            }
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn tags(&self) -> Option<&[String]> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { tags, .. } => Some(tags),
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn room(&self) -> Option<&str> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { room, .. } => Some(room),
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn set_room(&mut self, new_room: &str) {
+        if let Self::Streaming { room, .. } = self {
+            *room = new_room.to_string();
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn paused(&self) -> Option<bool> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { paused, .. } => Some(*paused),
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn set_paused(&mut self, new_paused: bool) {
+        if let Self::Streaming { paused, .. } = self {
+            *paused = new_paused;
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn started(&self) -> Option<std::time::SystemTime> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { started, .. } => Some(*started),
+            Self::Watching { .. } => None,
+        }
+    }
+
+    // the data which brings a new watcher's screen up to date
+    fn replay(&mut self) -> Option<(crate::term::Size, Vec<u8>)> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming {
+                term, replay_cache, ..
+            } => Some(replay_cache.replay(term.screen())),
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn set_metadata(&mut self, new_title: &str, new_tags: &[String]) {
+        if let Self::Streaming { title, tags, .. } = self {
+            *title = new_title.to_string();
+            *tags = new_tags.to_vec();
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn history(&self) -> Option<&history::History> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { history, .. } => Some(history),
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn history_mut(&mut self) -> Option<&mut history::History> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { history, .. } => Some(history),
+            Self::Watching { .. } => None,
+        }
+    }
+
+    fn catchup_mut(&mut self) -> Option<&mut history::Catchup> {
+        match self {
+            Self::Accepted => None,
+            Self::LoggingIn { .. } => None,
+            Self::LoggedIn { .. } => None,
+            Self::Streaming { .. } => None,
+            Self::Watching { catchup, .. } => catchup.as_mut(),
+        }
+    }
+
+    fn finish_catchup(&mut self) {
+        if let Self::Watching { catchup, .. } = self {
+            *catchup = None;
+        } else {
+            unreachable!()
+        }
+    }
+
     fn watch_id(&self) -> Option<&str> {
         match self {
             Self::Accepted => None,
@@ -187,7 +491,12 @@ impl ConnectionState {
         }
     }
 
-    fn stream(&mut self) {
+    fn stream(
+        &mut self,
+        allowed_watchers: &[String],
+        description: &str,
+        history_window: std::time::Duration,
+    ) {
         if let Self::LoggedIn {
             username,
             term_info,
@@ -198,13 +507,25 @@ impl ConnectionState {
                 username,
                 term_info,
                 term: vt100::Parser::new(size.rows, size.cols, 0),
+                allowed_watchers: allowed_watchers.iter().cloned().collect(),
+                controller: None,
+                control_requests: std::collections::HashMap::new(),
+                history: history::History::new(size, history_window),
+                replay_cache: replay_cache::ReplayCache::default(),
+                description: description.to_string(),
+                title: String::new(),
+                tags: vec![],
+                room: String::new(),
+                paused: false,
+                started: std::time::SystemTime::now(),
+                bytes: 0,
             };
         } else {
             unreachable!()
         }
     }
 
-    fn watch(&mut self, id: &str) {
+    fn watch(&mut self, id: &str, catchup: Option<history::Catchup>) {
         if let Self::LoggedIn {
             username,
             term_info,
@@ -214,6 +535,7 @@ impl ConnectionState {
                 username,
                 term_info,
                 watch_id: id.to_string(),
+                catchup,
             };
         } else {
             unreachable!()
@@ -225,36 +547,107 @@ struct Connection<
     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
 > {
     id: String,
+    address: std::net::SocketAddr,
+    roles: ListenerRoles,
     rsock: Option<ReadSocket<S>>,
     wsock: Option<WriteSocket<S>>,
+    // the protocol version negotiated with the client, which decides how
+    // messages are laid out on the wire in both directions
+    proto_version: u8,
     to_send: std::collections::VecDeque<crate::protocol::Message>,
+    output_batch: crate::output_batch::OutputBatch,
+    send_queue_size: usize,
+    send_queue_policy: SendQueuePolicy,
+    needs_resync: bool,
     closed: bool,
     state: ConnectionState,
+    // the state only keeps track of this while logging in, but admin
+    // messages need to know it afterwards too
+    auth_type: Option<crate::protocol::AuthType>,
+    connected_at: std::time::Instant,
+    // the last time the streamer sent terminal output. a stream's idle
+    // time is measured from this, so a stream which is only being watched
+    // or chatted in still counts as idle.
     last_activity: std::time::Instant,
     oauth_client: Option<crate::oauth::Oauth>,
+    // streamers which send a token can pick their stream back up from a new
+    // connection. while waiting for them to do that, the connection is kept
+    // around without its sockets until the deadline.
+    resume_token: Option<String>,
+    reconnect_deadline: Option<std::time::Instant>,
+    recent_messages: std::collections::VecDeque<String>,
+    // the sessions that this connection was last told about, if it has
+    // subscribed to changes to the list
+    session_list:
+        Option<std::collections::HashMap<String, crate::protocol::Session>>,
+    // whether this connection wants to hear about changes to the title and
+    // tags of the stream it is watching
+    session_changes: bool,
+    // whether this connection wants to be warned before its stream hits the
+    // time limit, and how many of those warnings it has been sent
+    time_limit: bool,
+    time_limit_warnings: usize,
+    // whether this connection wants to know how many people are watching
+    // its stream, and the count it was last told about
+    watcher_count: bool,
+    sent_watcher_count: Option<u32>,
+    // whether this connection wants to be told about the server's limits
+    // along with the session list, and the connection count it was last
+    // told about
+    server_limits: bool,
+    sent_connection_count: Option<u32>,
+    // whether this connection knows how to show Notice messages
+    notices: bool,
 }
 
 impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     Connection<S>
 {
-    fn new(s: S) -> Self {
+    fn new(
+        s: S,
+        address: std::net::SocketAddr,
+        roles: ListenerRoles,
+        send_queue_size: usize,
+        send_queue_policy: SendQueuePolicy,
+    ) -> Self {
         let (rs, ws) = s.split();
         let id = format!("{}", uuid::Uuid::new_v4());
-        log::info!("{}: new connection", id);
+        log::info!("{}: new connection from {}", id, address);
 
         Self {
             id,
+            address,
+            roles,
             rsock: Some(ReadSocket::Connected(
                 crate::protocol::FramedReader::new(rs),
             )),
             wsock: Some(WriteSocket::Connected(
                 crate::protocol::FramedWriter::new(ws),
             )),
+            proto_version: crate::protocol::PROTO_VERSION,
             to_send: std::collections::VecDeque::new(),
+            output_batch: crate::output_batch::OutputBatch::new(),
+            send_queue_size,
+            send_queue_policy,
+            needs_resync: false,
             closed: false,
             state: ConnectionState::new(),
+            auth_type: None,
+            connected_at: std::time::Instant::now(),
             last_activity: std::time::Instant::now(),
             oauth_client: None,
+            resume_token: None,
+            reconnect_deadline: None,
+            recent_messages: std::collections::VecDeque::new(),
+            session_list: None,
+            session_changes: false,
+            time_limit: false,
+            time_limit_warnings: 0,
+            watcher_count: false,
+            sent_watcher_count: None,
+            server_limits: false,
+            sent_connection_count: None,
+            notices: false,
         }
     }
 
@@ -277,10 +670,13 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                 ..
             } => (username, term_info),
         };
-        let title = self
-            .state
-            .term()
-            .map_or("", |parser| parser.screen().title());
+        let title = self.state.title().unwrap_or("");
+        let description = self.state.description().unwrap_or("");
+        let tags = self.state.tags().unwrap_or(&[]);
+        // elapsed() fails if the clock has gone backwards since then
+        let uptime = self.state.started().map_or(0, |started| {
+            started.elapsed().map_or(0, |uptime| uptime.as_secs())
+        });
 
         // i don't really care if things break for a connection that has been
         // idle for 136 years
@@ -294,12 +690,163 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                 .duration_since(self.last_activity)
                 .as_secs() as u32,
             title: title.to_string(),
+            description: description.to_string(),
+            tags: tags.to_vec(),
+            room: self.state.room().unwrap_or("").to_string(),
+            paused: self.state.paused().unwrap_or(false),
             watchers,
+            uptime: uptime as u32,
+            start_time: self.state.started().map_or(0, unix_time),
         })
     }
 
+    fn past_session(&self) -> Option<crate::protocol::PastSession> {
+        if let ConnectionState::Streaming {
+            username,
+            description,
+            started,
+            bytes,
+            ..
+        } = &self.state
+        {
+            Some(crate::protocol::PastSession {
+                username: username.clone(),
+                title: self.state.title().unwrap_or("").to_string(),
+                description: description.clone(),
+                start_time: unix_time(*started),
+                end_time: unix_time(std::time::SystemTime::now()),
+                bytes: *bytes,
+            })
+        } else {
+            None
+        }
+    }
+
     fn send_message(&mut self, message: crate::protocol::Message) {
-        self.to_send.push_back(message);
+        // older clients would just disconnect on seeing a message type they
+        // don't know
+        if !message.supported_by(self.proto_version) {
+            log::debug!(
+                "{}: not sending {} to a version {} client",
+                self.id,
+                message.format_log(),
+                self.proto_version
+            );
+            return;
+        }
+        self.output_batch.push(&mut self.to_send, message);
+        if self.to_send.len() > self.send_queue_size {
+            self.handle_full_send_queue();
+        }
+    }
+
+    fn handle_full_send_queue(&mut self) {
+        match self.send_queue_policy {
+            SendQueuePolicy::DropOldest => {
+                // the server will send the current screen in its place (see
+                // Server::resync_connection)
+                if self.drop_queued_output() > 0 {
+                    self.needs_resync = true;
+                }
+            }
+            SendQueuePolicy::Coalesce => {
+                let queued = std::mem::replace(
+                    &mut self.to_send,
+                    std::collections::VecDeque::new(),
+                );
+                for msg in queued {
+                    if let (
+                        crate::protocol::Message::TerminalOutput { data },
+                        Some(crate::protocol::Message::TerminalOutput {
+                            data: prev,
+                        }),
+                    ) = (&msg, self.to_send.back_mut())
+                    {
+                        prev.extend_from_slice(data);
+                        // too much to be worth sending, so send the current
+                        // screen instead (see Server::resync_connection)
+                        if prev.len() > MAX_COALESCED_OUTPUT {
+                            self.needs_resync = true;
+                        }
+                        continue;
+                    }
+                    self.to_send.push_back(msg);
+                }
+            }
+            SendQueuePolicy::Disconnect => {
+                // the connection is going away, so there is no point in
+                // queueing anything else for it
+                if self.closed {
+                    self.to_send.pop_back();
+                    return;
+                }
+
+                log::warn!("{}: send queue full, disconnecting", self.id);
+                let e = Error::SendQueueFull {
+                    size: self.send_queue_size,
+                };
+                self.to_send.clear();
+                self.to_send.push_back(crate::protocol::Message::error(
+                    &format!("{}", e),
+                ));
+                self.closed = true;
+            }
+        }
+    }
+
+    fn handle_full_catchup(&mut self) {
+        match self.send_queue_policy {
+            SendQueuePolicy::Disconnect => {
+                if !self.closed {
+                    self.handle_full_send_queue();
+                }
+            }
+            SendQueuePolicy::DropOldest | SendQueuePolicy::Coalesce => {
+                // Server::resync_connection abandons the catchup and sends
+                // the current screen instead
+                self.needs_resync = true;
+            }
+        }
+    }
+
+    // returns the number of messages dropped
+    fn drop_queued_output(&mut self) -> usize {
+        let len = self.to_send.len();
+        self.to_send.retain(|msg| match msg {
+            crate::protocol::Message::TerminalOutput { .. } => false,
+            _ => true,
+        });
+        len - self.to_send.len()
+    }
+
+    // watchers who are still catching up need to see events in order, so
+    // live events are queued up behind the output they are replaying
+    fn send_stream_event(&mut self, event: history::Event) {
+        if let Some(catchup) = self.state.catchup_mut() {
+            catchup.push(event);
+            // a watcher replaying a busy stream can keep falling further
+            // behind, so the live events it is holding back are limited in
+            // the same way as its send queue
+            if catchup.live_len() > self.send_queue_size {
+                self.handle_full_catchup();
+            }
+        } else {
+            self.send_message(event.message());
+        }
+    }
+
+    fn record_message(&mut self, message: &crate::protocol::Message) {
+        self.recent_messages.push_back(message.format_log());
+        while self.recent_messages.len() > DIAGNOSTIC_MESSAGES {
+            self.recent_messages.pop_front();
+        }
+    }
+
+    // like close, but lets the client know that it shouldn't expect to be
+    // able to reconnect right away
+    fn shutdown(&mut self, reason: &str) {
+        self.send_message(crate::protocol::Message::server_shutdown(reason));
+        self.closed = true;
     }
 
     fn close(&mut self, res: Result<()>) {
@@ -316,43 +863,192 @@ pub struct Server<
     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
 > {
     read_timeout: std::time::Duration,
-    acceptor: Box<dyn futures::Stream<Item = S, Error = Error> + Send>,
+    history_window: std::time::Duration,
+    quarantine_duration: Option<std::time::Duration>,
+    // proxies like tt web make connections on behalf of many different
+    // clients, so their address is never quarantined
+    trusted_proxies: std::collections::HashSet<std::net::IpAddr>,
+    disconnect_grace: Option<std::time::Duration>,
+    max_stream_duration: Option<std::time::Duration>,
+    // in order from the earliest warning to the last
+    stream_warnings: Vec<std::time::Duration>,
+    max_connections: Option<u32>,
+    max_watchers_per_session: Option<u32>,
+    send_queue_size: usize,
+    send_queue_policy: SendQueuePolicy,
+    acceptor: Box<
+        dyn futures::Stream<
+                Item = (S, std::net::SocketAddr, ListenerRoles),
+                Error = Error,
+            > + Send,
+    >,
     connections: std::collections::HashMap<String, Connection<S>>,
+    shutdown:
+        Box<dyn futures::Stream<Item = &'static str, Error = Error> + Send>,
+    shutting_down: bool,
+    // along with the allowed watchers of each, since the history shouldn't
+    // reveal streams to people who weren't allowed to watch them
+    past_sessions: std::collections::VecDeque<(
+        crate::protocol::PastSession,
+        std::collections::HashSet<String>,
+    )>,
+    quarantined:
+        std::collections::HashMap<std::net::IpAddr, std::time::Instant>,
     rate_limiter: ratelimit_meter::KeyedRateLimiter<Option<String>>,
+    bans: access::Bans,
+    ban_file_timer: tokio::timer::Interval,
+    session_list_timer: tokio::timer::Interval,
+    time_limit_timer: tokio::timer::Interval,
+    watcher_count_timer: tokio::timer::Interval,
+    reconnect_timer: Option<tokio::timer::Delay>,
     allowed_auth_types: std::collections::HashSet<crate::protocol::AuthType>,
+    // logins which are allowed to send admin messages, as the auth type
+    // and the username it logged in as
+    admins: std::collections::HashSet<(crate::protocol::AuthType, String)>,
+    // whether plain admins are trusted when connecting from localhost (see
+    // check_admin)
+    trust_local_plain_admins: bool,
     oauth_configs: std::collections::HashMap<
         crate::protocol::AuthType,
         crate::oauth::Config,
     >,
+    host_key: Option<crate::host_key::HostKey>,
+    // for the stats shown to admins
+    started: std::time::Instant,
+    total_connections: u32,
+    total_streams: u32,
 }
 
 impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     Server<S>
 {
     pub fn new(
-        acceptor: Box<dyn futures::Stream<Item = S, Error = Error> + Send>,
+        acceptor: Box<
+            dyn futures::Stream<
+                    Item = (S, std::net::SocketAddr, ListenerRoles),
+                    Error = Error,
+                > + Send,
+        >,
         read_timeout: std::time::Duration,
+        history_window: std::time::Duration,
+        quarantine_duration: Option<std::time::Duration>,
+        trusted_proxies: std::collections::HashSet<std::net::IpAddr>,
+        disconnect_grace: Option<std::time::Duration>,
+        max_stream_duration: Option<std::time::Duration>,
+        mut stream_warnings: Vec<std::time::Duration>,
+        max_connections: Option<u32>,
+        max_watchers_per_session: Option<u32>,
+        send_queue_size: usize,
+        send_queue_policy: SendQueuePolicy,
         allowed_auth_types: std::collections::HashSet<
             crate::protocol::AuthType,
         >,
+        admins: std::collections::HashSet<(
+            crate::protocol::AuthType,
+            String,
+        )>,
+        trust_local_plain_admins: bool,
+        bans: access::Bans,
         oauth_configs: std::collections::HashMap<
             crate::protocol::AuthType,
             crate::oauth::Config,
         >,
+        host_key: Option<crate::host_key::HostKey>,
     ) -> Self {
+        stream_warnings.sort_by(|a, b| b.cmp(a));
+        stream_warnings.dedup();
+
         Self {
             read_timeout,
+            history_window,
+            quarantine_duration,
+            trusted_proxies,
+            disconnect_grace,
+            max_stream_duration,
+            stream_warnings,
+            max_connections,
+            max_watchers_per_session,
+            send_queue_size,
+            send_queue_policy,
             acceptor,
             connections: std::collections::HashMap::new(),
+            shutdown: Box::new(
+                signal(tokio_signal::unix::SIGTERM, "SIGTERM")
+                    .select(signal(tokio_signal::unix::SIGINT, "SIGINT")),
+            ),
+            shutting_down: false,
+            past_sessions: std::collections::VecDeque::new(),
+            quarantined: std::collections::HashMap::new(),
             rate_limiter: ratelimit_meter::KeyedRateLimiter::new(
                 std::num::NonZeroU32::new(300).unwrap(),
                 std::time::Duration::from_secs(60),
             ),
+            bans,
+            ban_file_timer: tokio::timer::Interval::new_interval(
+                BAN_FILE_INTERVAL,
+            ),
+            session_list_timer: tokio::timer::Interval::new_interval(
+                SESSION_LIST_INTERVAL,
+            ),
+            time_limit_timer: tokio::timer::Interval::new_interval(
+                TIME_LIMIT_INTERVAL,
+            ),
+            watcher_count_timer: tokio::timer::Interval::new_interval(
+                WATCHER_COUNT_INTERVAL,
+            ),
+            reconnect_timer: None,
             allowed_auth_types,
+            admins,
+            trust_local_plain_admins,
             oauth_configs,
+            host_key,
+            started: std::time::Instant::now(),
+            total_connections: 0,
+            total_streams: 0,
         }
     }
 
+    // servers without a host key answer with an empty one, so that clients
+    // can tell that apart from a key which has changed
+    fn handle_message_host_key_request(
+        &self,
+        conn: &mut Connection<S>,
+        nonce: &[u8],
+    ) {
+        let msg = self.host_key.as_ref().map_or_else(
+            || crate::protocol::Message::host_key(&[], &[]),
+            |host_key| {
+                crate::protocol::Message::host_key(
+                    host_key.public_key(),
+                    &host_key.sign(nonce),
+                )
+            },
+        );
+        conn.send_message(msg);
+    }
+
+    fn handle_message_hello(
+        &self,
+        conn: &mut Connection<S>,
+        proto_version: u8,
+    ) -> Result<()> {
+        let proto_version =
+            crate::protocol::negotiate_proto_version(proto_version)?;
+        conn.proto_version = proto_version;
+
+        let heartbeat_interval = (self.read_timeout / MISSED_HEARTBEATS)
+            .as_secs()
+            .max(1)
+            .try_into()
+            .unwrap_or(std::u32::MAX);
+        conn.send_message(crate::protocol::Message::welcome(
+            proto_version,
+            heartbeat_interval,
+        ));
+
+        Ok(())
+    }
+
     fn handle_message_login(
         &mut self,
         conn: &mut Connection<S>,
@@ -378,6 +1074,7 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         if !self.allowed_auth_types.contains(&ty) {
             return Err(Error::AuthTypeNotAllowed { ty });
         }
+        conn.auth_type = Some(ty);
 
         match &auth {
             crate::protocol::Auth::Plain { username } => {
@@ -387,6 +1084,10 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                     conn.id,
                     username
                 );
+                if self.bans.is_banned_username(username) {
+                    log::info!("{}: refusing banned user", conn.id);
+                    return Err(Error::Banned);
+                }
                 conn.state.login_plain(username, term_type, size);
                 conn.send_message(crate::protocol::Message::logged_in(
                     username,
@@ -407,7 +1108,9 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                         );
                     }
                     crate::protocol::AuthClient::Web => {
-                        return self.handle_oauth_login_web();
+                        return self.handle_oauth_login_web(
+                            conn, auth, term_type, size,
+                        );
                     }
                 }
             }
@@ -446,13 +1149,8 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             let term_type = term_type.to_string();
             let fut = client
                 .get_access_token_from_refresh_token()
-                .and_then(move |access_token| match ty {
-                    crate::protocol::AuthType::RecurseCenter => {
-                        crate::auth::recurse_center::get_username(
-                            &access_token,
-                        )
-                    }
-                    _ => unreachable!(),
+                .and_then(move |access_token| {
+                    crate::auth::get_username(ty, &access_token)
                 })
                 .map(move |username| {
                     (
@@ -471,7 +1169,8 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             conn.oauth_client = Some(client);
             let client = conn.oauth_client.as_ref().unwrap();
             conn.state.login_oauth_start(ty, term_type, size);
-            let authorize_url = client.generate_authorize_url();
+            let authorize_url = client
+                .generate_authorize_url(oauth2::CsrfToken::new_random());
             let user_id = client.user_id().to_string();
             conn.send_message(crate::protocol::Message::oauth_cli_request(
                 &authorize_url,
@@ -481,8 +1180,15 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         }
     }
 
+    // the web server does the oauth flow itself (since the redirect has to
+    // go back to it), so all we need from it is the access token, which we
+    // check by asking the provider who it belongs to
     fn handle_oauth_login_web(
         &mut self,
+        conn: &mut Connection<S>,
+        auth: &crate::protocol::Auth,
+        term_type: &str,
+        size: crate::term::Size,
     ) -> Result<
         Option<
             Box<
@@ -493,55 +1199,237 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             >,
         >,
     > {
-        unimplemented!()
+        let ty = auth.auth_type();
+        conn.state.login_oauth_start(ty, term_type, size);
+        conn.send_message(crate::protocol::Message::oauth_web_request(
+            auth.oauth_id().unwrap_or(""),
+        ));
+        Ok(None)
     }
 
     fn handle_message_start_streaming(
         &mut self,
         conn: &mut Connection<S>,
+        allowed_watchers: &[String],
+        description: &str,
     ) -> Result<()> {
         let username = conn.state.username().unwrap();
 
-        log::info!("{}: stream({})", conn.id, username);
-        conn.state.stream();
+        if !conn.roles.stream {
+            log::info!("{}: stream({}): wrong listener", conn.id, username);
+            return Err(Error::WrongListener {
+                action: "streaming".to_string(),
+            });
+        }
+
+        if let Some(id) = self.resumable_stream(conn) {
+            return self.resume_stream(
+                conn,
+                &id,
+                allowed_watchers,
+                description,
+            );
+        }
+
+        if allowed_watchers.is_empty() {
+            log::info!("{}: stream({})", conn.id, username);
+        } else {
+            log::info!(
+                "{}: stream({}, allowed: {})",
+                conn.id,
+                username,
+                allowed_watchers.join(", ")
+            );
+        }
+        conn.state
+            .stream(allowed_watchers, description, self.history_window);
+        self.total_streams = self.total_streams.saturating_add(1);
 
         Ok(())
     }
 
-    fn handle_message_start_watching(
+    fn handle_message_resume_streaming(
         &mut self,
         conn: &mut Connection<S>,
-        id: String,
+        token: &str,
     ) -> Result<()> {
-        let username = conn.state.username().unwrap();
+        conn.resume_token = Some(token.to_string());
 
-        if let Some(stream_conn) = self.connections.get(&id) {
-            let term = stream_conn.state.term().ok_or_else(|| {
-                Error::InvalidWatchId { id: id.to_string() }
-            })?;
-            let (rows, cols) = term.screen().size();
-            let data = term.screen().contents_formatted();
-
-            log::info!("{}: watch({}, {})", conn.id, username, id);
-            conn.state.watch(&id);
-            conn.send_message(crate::protocol::Message::resize(
-                crate::term::Size { rows, cols },
-            ));
-            conn.send_message(crate::protocol::Message::terminal_output(
-                &data,
-            ));
+        Ok(())
+    }
 
-            Ok(())
-        } else {
-            Err(Error::InvalidWatchId { id })
-        }
+    // a stream can be picked back up by the same user sending the same
+    // token, whether or not we have noticed that its old connection dropped
+    fn resumable_stream(&self, conn: &Connection<S>) -> Option<String> {
+        let token = conn.resume_token.as_ref()?;
+        let username = conn.state.username()?;
+        self.streamers()
+            .find(|stream_conn| {
+                stream_conn.resume_token.as_ref() == Some(token)
+                    && stream_conn.state.username() == Some(username)
+            })
+            .map(|stream_conn| stream_conn.id.clone())
     }
 
-    fn handle_message_heartbeat(
+    fn resume_stream(
         &mut self,
         conn: &mut Connection<S>,
+        id: &str,
+        new_allowed_watchers: &[String],
+        new_description: &str,
     ) -> Result<()> {
-        conn.send_message(crate::protocol::Message::heartbeat());
+        let old_conn = self.connections.remove(id).unwrap();
+        log::info!(
+            "{}: resume({}, {})",
+            conn.id,
+            conn.state.username().unwrap(),
+            id
+        );
+
+        // watchers refer to the stream by its id, so the new connection
+        // takes over the old one's id along with its state
+        let size = conn.state.term_info().unwrap().size;
+        conn.id = old_conn.id;
+        conn.state = old_conn.state;
+        conn.time_limit_warnings = old_conn.time_limit_warnings;
+        if let ConnectionState::Streaming {
+            allowed_watchers,
+            description,
+            ..
+        } = &mut conn.state
+        {
+            *allowed_watchers =
+                new_allowed_watchers.iter().cloned().collect();
+            *description = new_description.to_string();
+        } else {
+            unreachable!()
+        }
+        if conn.state.term_info().unwrap().size != size {
+            self.handle_message_resize(conn, size)?;
+        }
+
+        let msg = crate::protocol::Message::chat("", RESUMED_NOTICE);
+        for watch_conn in self.watchers_mut() {
+            if watch_conn.state.watch_id().unwrap() == conn.id {
+                watch_conn.send_message(msg.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_message_start_watching(
+        &mut self,
+        conn: &mut Connection<S>,
+        id: String,
+        offset: u32,
+    ) -> Result<()> {
+        let username = conn.state.username().unwrap();
+
+        if !conn.roles.watch {
+            log::info!(
+                "{}: watch({}, {}): wrong listener",
+                conn.id,
+                username,
+                id
+            );
+            return Err(Error::WrongListener {
+                action: "watching".to_string(),
+            });
+        }
+
+        let watchers = self
+            .watchers()
+            .filter(|watcher| watcher.state.watch_id() == Some(id.as_str()))
+            .count();
+        if let Some(stream_conn) = self.connections.get_mut(&id) {
+            if stream_conn.state.term().is_none() {
+                return Err(Error::InvalidWatchId { id });
+            }
+
+            // an empty allow list means that anyone can watch
+            let allowed_watchers =
+                stream_conn.state.allowed_watchers().unwrap();
+            if !allowed_watchers.is_empty()
+                && !allowed_watchers.contains(username)
+            {
+                log::info!(
+                    "{}: watch({}, {}): not allowed",
+                    conn.id,
+                    username,
+                    id
+                );
+                return Err(Error::NotAllowedToWatch {
+                    username: username.to_string(),
+                    streamer: stream_conn
+                        .state
+                        .username()
+                        .unwrap()
+                        .to_string(),
+                });
+            }
+
+            if let Some(max) = self.max_watchers_per_session {
+                if u32::try_from(watchers).unwrap_or(std::u32::MAX) >= max {
+                    log::info!(
+                        "{}: watch({}, {}): too many watchers",
+                        conn.id,
+                        username,
+                        id
+                    );
+                    return Err(Error::TooManyWatchers { max });
+                }
+            }
+
+            let (size, data, catchup) = if offset > 0 {
+                let (size, data, catchup) =
+                    stream_conn.state.history().unwrap().rewind(
+                        std::time::Duration::from_secs(u64::from(offset)),
+                    );
+                (size, data, Some(catchup))
+            } else {
+                let (size, data) = stream_conn.state.replay().unwrap();
+                (size, data, None)
+            };
+
+            if offset > 0 {
+                log::info!(
+                    "{}: watch({}, {}, -{}s)",
+                    conn.id,
+                    username,
+                    id,
+                    offset
+                );
+            } else {
+                log::info!("{}: watch({}, {})", conn.id, username, id);
+            }
+            conn.state.watch(&id, catchup);
+            conn.send_message(crate::protocol::Message::resize(size));
+            conn.send_message(crate::protocol::Message::terminal_output(
+                &data,
+            ));
+            // otherwise it would just look like nothing is happening
+            if conn.session_changes
+                && stream_conn.state.paused() == Some(true)
+            {
+                conn.send_message(crate::protocol::Message::session_changed(
+                    None,
+                    None,
+                    Some(true),
+                ));
+            }
+
+            Ok(())
+        } else {
+            Err(Error::InvalidWatchId { id })
+        }
+    }
+
+    fn handle_message_heartbeat(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        conn.send_message(crate::protocol::Message::heartbeat());
 
         Ok(())
     }
@@ -556,17 +1444,43 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         let screen = parser.screen().clone();
         parser.process(data);
         let diff = parser.screen().contents_diff(&screen);
+        let term_title = if parser.screen().title() == screen.title() {
+            None
+        } else {
+            Some(parser.screen().title().to_string())
+        };
+        if let ConnectionState::Streaming {
+            bytes,
+            replay_cache,
+            ..
+        } = &mut conn.state
+        {
+            *bytes += data.len() as u64;
+            replay_cache.output(data, &diff);
+        }
+        let event = history::Event::Output(diff);
+        conn.state.history_mut().unwrap().push(event.clone());
         for watch_conn in self.watchers_mut() {
             let watch_id = watch_conn.state.watch_id().unwrap();
             if conn.id == watch_id {
-                watch_conn.send_message(
-                    crate::protocol::Message::terminal_output(&diff),
-                );
+                watch_conn.send_stream_event(event.clone());
             }
         }
 
         conn.last_activity = std::time::Instant::now();
 
+        // the terminal's title is only used when no title was set explicitly
+        if let Some(term_title) = term_title {
+            if conn.state.title() == Some(term_title.as_str()) {
+                self.notify_session_changed(
+                    conn,
+                    Some(&term_title),
+                    None,
+                    None,
+                );
+            }
+        }
+
         Ok(())
     }
 
@@ -574,29 +1488,501 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         &mut self,
         conn: &mut Connection<S>,
     ) -> Result<()> {
+        self.send_server_limits(conn);
+        conn.send_message(crate::protocol::Message::sessions(
+            &self.sessions(),
+        ));
+
+        Ok(())
+    }
+
+    fn handle_message_list_room_sessions(
+        &mut self,
+        conn: &mut Connection<S>,
+        room: &str,
+    ) -> Result<()> {
+        let sessions: Vec<_> = self
+            .sessions()
+            .into_iter()
+            .filter(|session| session.room == room)
+            .collect();
+        self.send_server_limits(conn);
+        conn.send_message(crate::protocol::Message::sessions(&sessions));
+
+        Ok(())
+    }
+
+    fn handle_message_subscribe_session_changes(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        log::info!(
+            "{}: subscribe_session_changes({})",
+            conn.id,
+            conn.state.username().unwrap()
+        );
+        conn.session_changes = true;
+
+        Ok(())
+    }
+
+    fn handle_message_subscribe_time_limit(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        log::info!(
+            "{}: subscribe_time_limit({})",
+            conn.id,
+            conn.state.username().unwrap()
+        );
+        conn.time_limit = true;
+
+        Ok(())
+    }
+
+    fn handle_message_subscribe_watcher_count(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        log::info!(
+            "{}: subscribe_watcher_count({})",
+            conn.id,
+            conn.state.username().unwrap()
+        );
+        conn.watcher_count = true;
+
+        Ok(())
+    }
+
+    fn handle_message_subscribe_session_list(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        log::info!(
+            "{}: subscribe_session_list({})",
+            conn.id,
+            conn.state.username().unwrap()
+        );
+
+        self.send_server_limits(conn);
+        let sessions = self.sessions();
+        conn.send_message(crate::protocol::Message::sessions(&sessions));
+        conn.session_list = Some(
+            sessions
+                .into_iter()
+                .map(|session| (session.id.clone(), session))
+                .collect(),
+        );
+
+        Ok(())
+    }
+
+    fn handle_message_subscribe_server_limits(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        log::info!(
+            "{}: subscribe_server_limits({})",
+            conn.id,
+            conn.state.username().unwrap()
+        );
+        conn.server_limits = true;
+
+        Ok(())
+    }
+
+    fn handle_message_subscribe_notices(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        log::info!(
+            "{}: subscribe_notices({})",
+            conn.id,
+            conn.state.username().unwrap()
+        );
+        conn.notices = true;
+
+        Ok(())
+    }
+
+    // the connection being handled has been taken out of the list of
+    // connections, so it is counted separately
+    fn send_server_limits(&self, conn: &mut Connection<S>) {
+        if !conn.server_limits {
+            return;
+        }
+        let connections = self.connection_count().saturating_add(1);
+        conn.send_message(self.server_limits(connections));
+        conn.sent_connection_count = Some(connections);
+    }
+
+    fn server_limits(&self, connections: u32) -> crate::protocol::Message {
+        crate::protocol::Message::server_limits(
+            connections,
+            self.max_connections.unwrap_or(0),
+            self.max_watchers_per_session.unwrap_or(0),
+        )
+    }
+
+    // streams waiting for their streamer to reconnect don't count, since
+    // they aren't holding a socket open, and neither do connections which
+    // are on their way out
+    fn connection_count(&self) -> u32 {
+        let count = self
+            .connections
+            .values()
+            .filter(|conn| conn.rsock.is_some() && !conn.closed)
+            .count();
+        u32::try_from(count).unwrap_or(std::u32::MAX)
+    }
+
+    // the number of watchers of each stream, by the stream's id
+    fn watcher_counts(&self) -> std::collections::HashMap<&str, u32> {
         let mut watcher_counts = std::collections::HashMap::new();
         for watcher in self.watchers() {
-            let watch_id =
-                if let ConnectionState::Watching { watch_id, .. } =
-                    &watcher.state
-                {
-                    watch_id
-                } else {
-                    unreachable!()
-                };
-            watcher_counts.insert(
-                watch_id,
-                *watcher_counts.get(&watch_id).unwrap_or(&0) + 1,
-            );
+            let watch_id = watcher.state.watch_id().unwrap();
+            let count = watcher_counts.entry(watch_id).or_insert(0);
+            *count += 1;
         }
-        let sessions: Vec<_> = self
-            .streamers()
+        watcher_counts
+    }
+
+    fn sessions(&self) -> Vec<crate::protocol::Session> {
+        let watcher_counts = self.watcher_counts();
+        self.streamers()
             .flat_map(|streamer| {
-                streamer
-                    .session(*watcher_counts.get(&streamer.id).unwrap_or(&0))
+                streamer.session(
+                    *watcher_counts.get(streamer.id.as_str()).unwrap_or(&0),
+                )
+            })
+            .collect()
+    }
+
+    // admins are matched on the auth type as well as the username, since
+    // the same name can belong to different people with different auth
+    // providers. plain logins let anyone claim to be anyone, so they are
+    // never trusted unless the server was told to trust them from the
+    // machine it is running on, which still lets anyone with an account
+    // there claim a plain admin.
+    fn check_admin(&self, conn: &Connection<S>, action: &str) -> Result<()> {
+        let username = conn.state.username().unwrap();
+        let admin = conn.auth_type.map_or(false, |ty| {
+            self.admins.contains(&(ty, username.to_string()))
+        });
+        let trusted = conn.auth_type
+            != Some(crate::protocol::AuthType::Plain)
+            || (self.trust_local_plain_admins
+                && conn.address.ip().is_loopback());
+        if !admin || !trusted {
+            log::warn!("{}: {}({}): not an admin", conn.id, action, username);
+            return Err(Error::NotAdmin {
+                username: username.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn handle_message_admin_list_connections(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        self.check_admin(conn, "admin_list_connections")?;
+        log::info!(
+            "{}: admin_list_connections({})",
+            conn.id,
+            conn.state.username().unwrap()
+        );
+
+        // the connection making the request isn't in the list right now
+        let mut connections: Vec<_> = std::iter::once(&*conn)
+            .chain(self.connections.values())
+            .map(admin_connection)
+            .collect();
+        connections.sort_by_key(|conn| std::cmp::Reverse(conn.connected));
+        conn.send_message(crate::protocol::Message::admin_connections(
+            &connections,
+        ));
+
+        Ok(())
+    }
+
+    fn handle_message_admin_kick(
+        &mut self,
+        conn: &mut Connection<S>,
+        id: &str,
+        reason: &str,
+    ) -> Result<()> {
+        self.check_admin(conn, "admin_kick")?;
+        let username = conn.state.username().unwrap();
+
+        let kicked = if let Some(kicked) = self.connections.get_mut(id) {
+            kicked
+        } else {
+            return Err(Error::InvalidConnectionId { id: id.to_string() });
+        };
+        log::info!(
+            "{}: admin_kick({}, {}): {}",
+            conn.id,
+            username,
+            id,
+            reason
+        );
+        let reason = if reason.is_empty() {
+            "no reason given".to_string()
+        } else {
+            reason.to_string()
+        };
+        kicked.close(Err(Error::Kicked { reason }));
+        // streams which are waiting for their streamer to come back have no
+        // socket to write the error to, so they just end
+        kicked.reconnect_deadline =
+            kicked.reconnect_deadline.map(|_| std::time::Instant::now());
+        conn.send_message(crate::protocol::Message::admin_done(1));
+
+        Ok(())
+    }
+
+    fn handle_message_admin_ban(
+        &mut self,
+        conn: &mut Connection<S>,
+        entry: &str,
+    ) -> Result<()> {
+        self.check_admin(conn, "admin_ban")?;
+        log::info!(
+            "{}: admin_ban({}): {}",
+            conn.id,
+            conn.state.username().unwrap(),
+            entry
+        );
+
+        self.bans.ban(entry)?;
+        let count = self.kick_banned();
+        conn.send_message(crate::protocol::Message::admin_done(count));
+
+        Ok(())
+    }
+
+    // everything matching the filter is kicked while handling this one
+    // message, so nothing can connect or change its tags part way through
+    fn handle_message_admin_kick_matching(
+        &mut self,
+        conn: &mut Connection<S>,
+        filter: &str,
+        reason: &str,
+        ban: bool,
+        dry_run: bool,
+    ) -> Result<()> {
+        self.check_admin(conn, "admin_kick_matching")?;
+        log::info!(
+            "{}: admin_kick_matching({}, {}, ban={}, dry_run={}): {}",
+            conn.id,
+            conn.state.username().unwrap(),
+            filter,
+            ban,
+            dry_run,
+            reason
+        );
+        let filter = crate::protocol::AdminFilter::try_from(filter)?;
+
+        // banning a tag bans the streamers using it, which also kicks
+        // anything else they have connected
+        let mut banned_usernames = vec![];
+        if let crate::protocol::AdminFilter::Tag(..) = filter {
+            if ban {
+                banned_usernames = self
+                    .connections
+                    .values()
+                    .filter(|c| admin_filter_matches(&filter, c))
+                    .filter_map(|c| c.state.username())
+                    .map(std::string::ToString::to_string)
+                    .collect();
+                banned_usernames.sort();
+                banned_usernames.dedup();
+            }
+        }
+        let affected: Vec<_> = self
+            .connections
+            .values()
+            .filter(|c| {
+                !c.closed
+                    && (admin_filter_matches(&filter, c)
+                        || c.state.username().map_or(false, |username| {
+                            banned_usernames.iter().any(|u| u == username)
+                        }))
             })
+            .map(|c| c.id.clone())
             .collect();
-        conn.send_message(crate::protocol::Message::sessions(&sessions));
+
+        if dry_run {
+            let mut connections: Vec<_> = affected
+                .iter()
+                .map(|id| admin_connection(&self.connections[id]))
+                .collect();
+            connections.sort_by_key(|conn| std::cmp::Reverse(conn.connected));
+            conn.send_message(crate::protocol::Message::admin_connections(
+                &connections,
+            ));
+            return Ok(());
+        }
+
+        // if this fails, nobody has been kicked yet
+        if ban {
+            let entries = match &filter {
+                crate::protocol::AdminFilter::User(username) => {
+                    vec![username.clone()]
+                }
+                crate::protocol::AdminFilter::Address(address) => {
+                    vec![address.to_string()]
+                }
+                crate::protocol::AdminFilter::Tag(..) => banned_usernames,
+            };
+            for entry in &entries {
+                self.bans.ban(entry)?;
+            }
+        }
+
+        let reason = if reason.is_empty() {
+            "no reason given"
+        } else {
+            reason
+        };
+        for id in &affected {
+            let kicked = self.connections.get_mut(id).unwrap();
+            kicked.close(Err(if ban {
+                Error::Banned
+            } else {
+                Error::Kicked {
+                    reason: reason.to_string(),
+                }
+            }));
+            kicked.reconnect_deadline =
+                kicked.reconnect_deadline.map(|_| std::time::Instant::now());
+        }
+        conn.send_message(crate::protocol::Message::admin_done(
+            u32::try_from(affected.len()).unwrap_or(std::u32::MAX),
+        ));
+
+        Ok(())
+    }
+
+    // disconnects everyone who is now banned, returning how many
+    // connections that was
+    fn kick_banned(&mut self) -> u32 {
+        let bans = &self.bans;
+        let mut count = 0;
+        for conn in self.connections.values_mut() {
+            if conn.closed {
+                continue;
+            }
+            let banned = bans.is_banned_address(conn.address.ip())
+                || conn.state.username().map_or(false, |username| {
+                    bans.is_banned_username(username)
+                });
+            if !banned {
+                continue;
+            }
+            log::info!("{}: disconnecting banned connection", conn.id);
+            conn.close(Err(Error::Banned));
+            conn.reconnect_deadline =
+                conn.reconnect_deadline.map(|_| std::time::Instant::now());
+            count += 1;
+        }
+        count
+    }
+
+    fn handle_message_admin_notice(
+        &mut self,
+        conn: &mut Connection<S>,
+        text: &str,
+    ) -> Result<()> {
+        self.check_admin(conn, "admin_notice")?;
+        log::info!(
+            "{}: admin_notice({}): {}",
+            conn.id,
+            conn.state.username().unwrap(),
+            text
+        );
+
+        let count = self.notice(text);
+        conn.send_message(crate::protocol::Message::admin_done(count));
+
+        Ok(())
+    }
+
+    // sends text to every logged in connection which can show it, returning
+    // how many connections it was sent to
+    fn notice(&mut self, text: &str) -> u32 {
+        let mut count = 0_u32;
+        for conn in self.connections.values_mut() {
+            if conn.state.username().is_none() {
+                continue;
+            }
+            if conn.notices {
+                conn.send_message(crate::protocol::Message::notice(text));
+            } else {
+                // older streamers and watchers can at least show chat
+                // messages, and a chat message with no sender is shown
+                // on its own
+                match conn.state {
+                    ConnectionState::Streaming { .. }
+                    | ConnectionState::Watching { .. } => {
+                        conn.send_message(crate::protocol::Message::chat(
+                            "", text,
+                        ));
+                    }
+                    _ => continue,
+                }
+            }
+            count = count.saturating_add(1);
+        }
+        count
+    }
+
+    fn handle_message_admin_get_stats(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        self.check_admin(conn, "admin_get_stats")?;
+        log::info!(
+            "{}: admin_get_stats({})",
+            conn.id,
+            conn.state.username().unwrap()
+        );
+
+        let count = |n: usize| u32::try_from(n).unwrap_or(std::u32::MAX);
+        let stats = crate::protocol::ServerStats {
+            uptime: u32::try_from(self.started.elapsed().as_secs())
+                .unwrap_or(std::u32::MAX),
+            connections: self.connection_count().saturating_add(1),
+            streams: count(self.streamers().count()),
+            watchers: count(self.watchers().count()),
+            total_connections: self.total_connections,
+            total_streams: self.total_streams,
+        };
+        conn.send_message(crate::protocol::Message::admin_stats(&stats));
+
+        Ok(())
+    }
+
+    fn handle_message_list_history(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        let username = conn.state.username().unwrap();
+
+        // an empty allow list means that anyone can watch
+        let sessions: Vec<_> = self
+            .past_sessions
+            .iter()
+            .rev()
+            .filter(|(session, allowed_watchers)| {
+                allowed_watchers.is_empty()
+                    || allowed_watchers.contains(username)
+                    || session.username == username
+            })
+            .map(|(session, _)| session.clone())
+            .collect();
+        conn.send_message(crate::protocol::Message::history(&sessions));
 
         Ok(())
     }
@@ -612,12 +1998,310 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         if let Some(parser) = conn.state.term_mut() {
             parser.set_size(size.rows, size.cols);
         }
+        if let ConnectionState::Streaming { replay_cache, .. } =
+            &mut conn.state
+        {
+            replay_cache.resize();
+        }
+
+        let event = history::Event::Resize(size);
+        if let Some(history) = conn.state.history_mut() {
+            history.push(event.clone());
+        }
 
         for watch_conn in self.watchers_mut() {
             let watch_id = watch_conn.state.watch_id().unwrap();
             if conn.id == watch_id {
-                watch_conn
-                    .send_message(crate::protocol::Message::resize(size));
+                watch_conn.send_stream_event(event.clone());
+            }
+        }
+
+        if let ConnectionState::Streaming { .. } = conn.state {
+            self.notify_session_changed(conn, None, None, None);
+        }
+
+        Ok(())
+    }
+
+    fn handle_message_chat(
+        &mut self,
+        conn: &mut Connection<S>,
+        text: &str,
+    ) -> Result<()> {
+        let username = conn.state.username().unwrap().to_string();
+        let session_id = match &conn.state {
+            ConnectionState::Streaming { .. } => conn.id.clone(),
+            ConnectionState::Watching { watch_id, .. } => watch_id.clone(),
+            _ => unreachable!(),
+        };
+
+        log::info!("{}: chat({}, {})", conn.id, username, session_id);
+
+        let msg = crate::protocol::Message::chat(&username, text);
+        for other_conn in self.connections.values_mut() {
+            let in_session = match &other_conn.state {
+                ConnectionState::Streaming { .. } => {
+                    other_conn.id == session_id
+                }
+                ConnectionState::Watching { watch_id, .. } => {
+                    watch_id == &session_id
+                }
+                _ => false,
+            };
+            if in_session {
+                other_conn.send_message(msg.clone());
+            }
+        }
+        // the current connection isn't in self.connections while its
+        // messages are being handled
+        conn.send_message(msg);
+
+        Ok(())
+    }
+
+    fn handle_message_update_metadata(
+        &mut self,
+        conn: &mut Connection<S>,
+        title: &str,
+        tags: &[String],
+    ) -> Result<()> {
+        log::info!("{}: update_metadata({:?}, {:?})", conn.id, title, tags);
+        let old_title =
+            conn.state.title().map(std::string::ToString::to_string);
+        let old_tags = conn.state.tags().map(<[String]>::to_vec);
+        conn.state.set_metadata(title, tags);
+
+        let title = conn.state.title().filter(|title| {
+            Some(*title)
+                != old_title.as_ref().map(std::string::String::as_str)
+        });
+        let tags = conn.state.tags().filter(|tags| {
+            Some(*tags) != old_tags.as_ref().map(std::vec::Vec::as_slice)
+        });
+        self.notify_session_changed(conn, title, tags, None);
+        Ok(())
+    }
+
+    fn handle_message_join_room(
+        &mut self,
+        conn: &mut Connection<S>,
+        room: &str,
+    ) -> Result<()> {
+        log::info!("{}: join_room({:?})", conn.id, room);
+        if conn.state.room() == Some(room) {
+            return Ok(());
+        }
+        conn.state.set_room(room);
+        self.notify_session_changed(conn, None, None, None);
+        Ok(())
+    }
+
+    fn handle_message_pause_streaming(
+        &mut self,
+        conn: &mut Connection<S>,
+        paused: bool,
+    ) -> Result<()> {
+        log::info!("{}: pause_streaming({})", conn.id, paused);
+        if conn.state.paused() == Some(paused) {
+            return Ok(());
+        }
+        conn.state.set_paused(paused);
+        self.notify_session_changed(conn, None, None, Some(paused));
+        Ok(())
+    }
+
+    // tells the watchers of a stream who asked for it about changes to its
+    // title, tags, or whether it is paused, and sends connections which are
+    // subscribed to the session list the new version of the session right
+    // away rather than at the next periodic update
+    fn notify_session_changed(
+        &mut self,
+        conn: &Connection<S>,
+        title: Option<&str>,
+        tags: Option<&[String]>,
+        paused: Option<bool>,
+    ) {
+        if title.is_some() || tags.is_some() || paused.is_some() {
+            let msg = crate::protocol::Message::session_changed(
+                title, tags, paused,
+            );
+            for watch_conn in self.watchers_mut() {
+                if watch_conn.session_changes
+                    && watch_conn.state.watch_id() == Some(conn.id.as_str())
+                {
+                    watch_conn.send_message(msg.clone());
+                }
+            }
+        }
+
+        let watchers = self
+            .watchers()
+            .filter(|watch_conn| {
+                watch_conn.state.watch_id() == Some(conn.id.as_str())
+            })
+            .count()
+            .try_into()
+            .unwrap_or(std::u32::MAX);
+        let session = if let Some(session) = conn.session(watchers) {
+            session
+        } else {
+            return;
+        };
+        for other_conn in self.connections.values_mut() {
+            if let Some(known) = &mut other_conn.session_list {
+                if known.contains_key(&session.id) {
+                    known.insert(session.id.clone(), session.clone());
+                    other_conn.send_message(
+                        crate::protocol::Message::session_updated(&session),
+                    );
+                }
+            }
+        }
+    }
+
+    fn handle_message_request_control(
+        &mut self,
+        conn: &mut Connection<S>,
+    ) -> Result<()> {
+        let username = conn.state.username().unwrap();
+        let watch_id = conn.state.watch_id().unwrap();
+
+        log::info!(
+            "{}: request_control({}, {})",
+            conn.id,
+            username,
+            watch_id
+        );
+
+        // the streamer decides whether to actually grant control
+        if let Some(stream_conn) = self.connections.get_mut(watch_id) {
+            stream_conn.state.add_control_request(username, &conn.id);
+            stream_conn.send_message(
+                crate::protocol::Message::request_control(username),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn handle_message_grant_control(
+        &mut self,
+        conn: &mut Connection<S>,
+        username: &str,
+    ) -> Result<()> {
+        log::info!("{}: grant_control({})", conn.id, username);
+
+        // control goes to the connection which asked for it, as long as it
+        // is still watching
+        let id = conn.state.take_control_request(username);
+        let watch_conn = id.and_then(|id| {
+            self.connections.get_mut(&id).filter(|watch_conn| {
+                watch_conn.state.watch_id() == Some(conn.id.as_str())
+            })
+        });
+        let watch_conn = if let Some(watch_conn) = watch_conn {
+            watch_conn
+        } else {
+            log::info!(
+                "{}: grant_control({}): no pending request",
+                conn.id,
+                username
+            );
+            return Ok(());
+        };
+        watch_conn
+            .send_message(crate::protocol::Message::grant_control(username));
+        let id = watch_conn.id.clone();
+
+        // only one watcher can be in control at a time
+        if let Some(prev) = conn.state.controller() {
+            if prev != id {
+                let prev = prev.to_string();
+                self.revoke_control_from(&prev);
+            }
+        }
+        conn.state.set_controller(Some(&id));
+
+        Ok(())
+    }
+
+    fn handle_message_revoke_control(
+        &mut self,
+        conn: &mut Connection<S>,
+        username: &str,
+    ) -> Result<()> {
+        match &conn.state {
+            // the streamer can revoke control from anyone (this is also how
+            // control requests are denied)
+            ConnectionState::Streaming { .. } => {
+                log::info!("{}: revoke_control({})", conn.id, username);
+
+                conn.state.take_control_request(username);
+                if let Some(id) = conn.state.controller() {
+                    let controlling =
+                        self.connections.get(id).and_then(|watch_conn| {
+                            watch_conn.state.username()
+                        }) == Some(username);
+                    if controlling {
+                        conn.state.set_controller(None);
+                    }
+                }
+                let msg = crate::protocol::Message::revoke_control(username);
+                self.send_to_watchers(&conn.id, username, &msg);
+            }
+            // watchers can only give up control that they hold themselves
+            ConnectionState::Watching {
+                username, watch_id, ..
+            } => {
+                log::info!(
+                    "{}: revoke_control({}, {})",
+                    conn.id,
+                    username,
+                    watch_id
+                );
+
+                let msg = crate::protocol::Message::revoke_control(username);
+                if let Some(stream_conn) = self.connections.get_mut(watch_id)
+                {
+                    if stream_conn.state.controller()
+                        == Some(conn.id.as_str())
+                    {
+                        stream_conn.state.set_controller(None);
+                        stream_conn.send_message(msg.clone());
+                    }
+                }
+                // the current connection isn't in self.connections while
+                // its messages are being handled
+                conn.send_message(msg);
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    fn handle_message_terminal_input(
+        &mut self,
+        conn: &mut Connection<S>,
+        data: &[u8],
+    ) -> Result<()> {
+        let username = conn.state.username().unwrap();
+        let watch_id = conn.state.watch_id().unwrap();
+
+        if let Some(stream_conn) = self.connections.get_mut(watch_id) {
+            if stream_conn.state.controller() == Some(conn.id.as_str()) {
+                stream_conn.send_message(
+                    crate::protocol::Message::terminal_input(data),
+                );
+            } else {
+                // this can happen normally if control was revoked while
+                // input was in flight, so just drop it
+                log::info!(
+                    "{}: terminal_input({}, {}): not in control",
+                    conn.id,
+                    username,
+                    watch_id
+                );
             }
         }
 
@@ -648,11 +2332,8 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         let term_info = conn.state.term_info().unwrap().clone();
         let fut = client
             .get_access_token_from_auth_code(code)
-            .and_then(move |access_token| match ty {
-                crate::protocol::AuthType::RecurseCenter => {
-                    crate::auth::recurse_center::get_username(&access_token)
-                }
-                _ => unreachable!(),
+            .and_then(move |access_token| {
+                crate::auth::get_username(ty, &access_token)
             })
             .map(|username| {
                 (
@@ -667,6 +2348,45 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         Ok(Some(Box::new(fut)))
     }
 
+    fn handle_message_oauth_web_response(
+        &mut self,
+        conn: &mut Connection<S>,
+        access_token: &str,
+    ) -> Result<
+        Option<
+            Box<
+                dyn futures::Future<
+                        Item = (ConnectionState, crate::protocol::Message),
+                        Error = Error,
+                    > + Send,
+            >,
+        >,
+    > {
+        // cli logins exchange their code here instead
+        if conn.oauth_client.is_some() {
+            return Err(Error::UnexpectedMessage {
+                message: crate::protocol::Message::oauth_web_response(
+                    access_token,
+                ),
+            });
+        }
+
+        let ty = conn.state.auth_type().unwrap();
+        let term_info = conn.state.term_info().unwrap().clone();
+        let fut =
+            crate::auth::get_username(ty, access_token).map(|username| {
+                (
+                    ConnectionState::LoggedIn {
+                        term_info,
+                        username: username.clone(),
+                    },
+                    crate::protocol::Message::logged_in(&username),
+                )
+            });
+
+        Ok(Some(Box::new(fut)))
+    }
+
     fn handle_accepted_message(
         &mut self,
         conn: &mut Connection<S>,
@@ -683,18 +2403,30 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     > {
         match message {
             crate::protocol::Message::Login {
+                proto_version,
                 auth,
                 auth_client,
                 term_type,
                 size,
-                ..
-            } => self.handle_message_login(
-                conn,
-                &auth,
-                auth_client,
-                &term_type,
-                size,
-            ),
+            } => {
+                // clients which don't send a Hello first negotiate here
+                conn.proto_version =
+                    crate::protocol::negotiate_proto_version(proto_version)?;
+                self.handle_message_login(
+                    conn,
+                    &auth,
+                    auth_client,
+                    &term_type,
+                    size,
+                )
+            }
+            crate::protocol::Message::HostKeyRequest { nonce } => {
+                self.handle_message_host_key_request(conn, &nonce);
+                Ok(None)
+            }
+            crate::protocol::Message::Hello { proto_version } => {
+                self.handle_message_hello(conn, proto_version).map(|_| None)
+            }
             m => Err(Error::UnauthenticatedMessage { message: m }),
         }
     }
@@ -717,6 +2449,9 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             crate::protocol::Message::OauthCliResponse { code } => {
                 self.handle_message_oauth_cli_response(conn, &code)
             }
+            crate::protocol::Message::OauthWebResponse { access_token } => {
+                self.handle_message_oauth_web_response(conn, &access_token)
+            }
             m => Err(Error::UnauthenticatedMessage { message: m }),
         }
     }
@@ -736,11 +2471,66 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             crate::protocol::Message::ListSessions => {
                 self.handle_message_list_sessions(conn)
             }
-            crate::protocol::Message::StartStreaming => {
-                self.handle_message_start_streaming(conn)
+            crate::protocol::Message::ListRoomSessions { room } => {
+                self.handle_message_list_room_sessions(conn, &room)
+            }
+            crate::protocol::Message::SubscribeSessionList => {
+                self.handle_message_subscribe_session_list(conn)
+            }
+            crate::protocol::Message::SubscribeSessionChanges => {
+                self.handle_message_subscribe_session_changes(conn)
+            }
+            crate::protocol::Message::SubscribeTimeLimit => {
+                self.handle_message_subscribe_time_limit(conn)
+            }
+            crate::protocol::Message::SubscribeWatcherCount => {
+                self.handle_message_subscribe_watcher_count(conn)
+            }
+            crate::protocol::Message::SubscribeServerLimits => {
+                self.handle_message_subscribe_server_limits(conn)
+            }
+            crate::protocol::Message::SubscribeNotices => {
+                self.handle_message_subscribe_notices(conn)
+            }
+            crate::protocol::Message::AdminListConnections => {
+                self.handle_message_admin_list_connections(conn)
+            }
+            crate::protocol::Message::AdminKick { id, reason } => {
+                self.handle_message_admin_kick(conn, &id, &reason)
+            }
+            crate::protocol::Message::AdminNotice { text } => {
+                self.handle_message_admin_notice(conn, &text)
+            }
+            crate::protocol::Message::AdminBan { entry } => {
+                self.handle_message_admin_ban(conn, &entry)
+            }
+            crate::protocol::Message::AdminKickMatching {
+                filter,
+                reason,
+                ban,
+                dry_run,
+            } => self.handle_message_admin_kick_matching(
+                conn, &filter, &reason, ban, dry_run,
+            ),
+            crate::protocol::Message::AdminGetStats => {
+                self.handle_message_admin_get_stats(conn)
+            }
+            crate::protocol::Message::ListHistory => {
+                self.handle_message_list_history(conn)
             }
-            crate::protocol::Message::StartWatching { id } => {
-                self.handle_message_start_watching(conn, id)
+            crate::protocol::Message::ResumeStreaming { token } => {
+                self.handle_message_resume_streaming(conn, &token)
+            }
+            crate::protocol::Message::StartStreaming {
+                allowed_watchers,
+                description,
+            } => self.handle_message_start_streaming(
+                conn,
+                &allowed_watchers,
+                &description,
+            ),
+            crate::protocol::Message::StartWatching { id, offset } => {
+                self.handle_message_start_watching(conn, id, offset)
             }
             m => Err(crate::error::Error::UnexpectedMessage { message: m }),
         }
@@ -761,6 +2551,24 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             crate::protocol::Message::TerminalOutput { data } => {
                 self.handle_message_terminal_output(conn, &data)
             }
+            crate::protocol::Message::Chat { text, .. } => {
+                self.handle_message_chat(conn, &text)
+            }
+            crate::protocol::Message::GrantControl { username } => {
+                self.handle_message_grant_control(conn, &username)
+            }
+            crate::protocol::Message::RevokeControl { username } => {
+                self.handle_message_revoke_control(conn, &username)
+            }
+            crate::protocol::Message::UpdateMetadata { title, tags } => {
+                self.handle_message_update_metadata(conn, &title, &tags)
+            }
+            crate::protocol::Message::PauseStreaming { paused } => {
+                self.handle_message_pause_streaming(conn, paused)
+            }
+            crate::protocol::Message::JoinRoom { room } => {
+                self.handle_message_join_room(conn, &room)
+            }
             m => Err(crate::error::Error::UnexpectedMessage { message: m }),
         }
     }
@@ -777,6 +2585,18 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             crate::protocol::Message::Resize { size } => {
                 self.handle_message_resize(conn, size)
             }
+            crate::protocol::Message::Chat { text, .. } => {
+                self.handle_message_chat(conn, &text)
+            }
+            crate::protocol::Message::RequestControl { .. } => {
+                self.handle_message_request_control(conn)
+            }
+            crate::protocol::Message::RevokeControl { username } => {
+                self.handle_message_revoke_control(conn, &username)
+            }
+            crate::protocol::Message::TerminalInput { data } => {
+                self.handle_message_terminal_input(conn, &data)
+            }
             m => Err(crate::error::Error::UnexpectedMessage { message: m }),
         }
     }
@@ -787,15 +2607,80 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         } else {
             log::info!("{}: disconnect", conn.id);
         }
+        self.finish_session(conn);
+
+        // the streamer gets control back when the watcher holding it leaves
+        if let ConnectionState::Watching {
+            username, watch_id, ..
+        } = &conn.state
+        {
+            if let Some(stream_conn) = self.connections.get_mut(watch_id) {
+                if stream_conn.state.controller() == Some(conn.id.as_str()) {
+                    stream_conn.state.set_controller(None);
+                    stream_conn.send_message(
+                        crate::protocol::Message::revoke_control(username),
+                    );
+                }
+            }
+        }
 
         for watch_conn in self.watchers_mut() {
             let watch_id = watch_conn.state.watch_id().unwrap();
-            if conn.id == watch_id {
+            if conn.id == watch_id && !watch_conn.closed {
                 watch_conn.close(Ok(()));
             }
         }
     }
 
+    // instead of ending a stream as soon as its connection drops, streamers
+    // which are able to resume it are given some time to reconnect first.
+    // returns whether the connection is being kept around for that.
+    fn hold_for_reconnect(&mut self, conn: &mut Connection<S>) -> bool {
+        let grace = if let Some(grace) = self.disconnect_grace {
+            grace
+        } else {
+            return false;
+        };
+        // connections we closed ourselves aren't coming back
+        if self.shutting_down || conn.closed || conn.resume_token.is_none() {
+            return false;
+        }
+        let username = if let ConnectionState::Streaming {
+            username, ..
+        } = &conn.state
+        {
+            username
+        } else {
+            return false;
+        };
+
+        log::info!("{}: waiting for {} to reconnect", conn.id, username);
+        conn.rsock = None;
+        conn.wsock = None;
+        conn.to_send.clear();
+        conn.reconnect_deadline = Some(std::time::Instant::now() + grace);
+
+        let msg = crate::protocol::Message::chat("", RECONNECTING_NOTICE);
+        for watch_conn in self.watchers_mut() {
+            if watch_conn.state.watch_id().unwrap() == conn.id {
+                watch_conn.send_message(msg.clone());
+            }
+        }
+
+        true
+    }
+
+    fn finish_session(&mut self, conn: &Connection<S>) {
+        if let Some(session) = conn.past_session() {
+            let allowed_watchers =
+                conn.state.allowed_watchers().unwrap().clone();
+            self.past_sessions.push_back((session, allowed_watchers));
+            while self.past_sessions.len() > PAST_SESSIONS {
+                self.past_sessions.pop_front();
+            }
+        }
+    }
+
     fn handle_message(
         &mut self,
         conn: &mut Connection<S>,
@@ -824,6 +2709,9 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         }
 
         log::debug!("{}: recv({})", conn.id, message.format_log());
+        if self.quarantine_duration.is_some() {
+            conn.record_message(&message);
+        }
 
         match conn.state {
             ConnectionState::Accepted { .. } => {
@@ -850,7 +2738,9 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     ) -> component_future::Poll<(), Error> {
         match &mut conn.rsock {
             Some(ReadSocket::Connected(..)) => {
-                if let Some(ReadSocket::Connected(s)) = conn.rsock.take() {
+                if let Some(ReadSocket::Connected(mut s)) = conn.rsock.take()
+                {
+                    s.set_proto_version(conn.proto_version);
                     let fut = Box::new(
                         crate::protocol::Message::read_async(s)
                             .timeout(self.read_timeout)
@@ -872,8 +2762,11 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                         Ok(None) => {
                             conn.rsock = Some(ReadSocket::Connected(s));
                         }
-                        e @ Err(..) => {
-                            conn.close(e.map(|_| ()));
+                        Err(e) => {
+                            if is_protocol_violation(&e) {
+                                self.quarantine(conn, &e);
+                            }
+                            conn.close(Err(e));
                             conn.rsock = Some(ReadSocket::Connected(s));
                         }
                     }
@@ -882,14 +2775,31 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                 Ok(futures::Async::NotReady) => {
                     Ok(component_future::Async::NotReady)
                 }
-                Err(e) => classify_connection_error(e),
+                Err(e) => {
+                    let res = classify_connection_error(e);
+                    if let Err(e) = &res {
+                        if is_protocol_violation(e) {
+                            self.quarantine(conn, e);
+                        }
+                    }
+                    res
+                }
             },
             Some(ReadSocket::Processing(_, fut)) => {
                 let (state, msg) = component_future::try_ready!(fut.poll());
                 if let Some(ReadSocket::Processing(s, _)) = conn.rsock.take()
                 {
-                    conn.state = state;
-                    conn.send_message(msg);
+                    // oauth logins only find out who the user is here
+                    let banned = state.username().map_or(false, |username| {
+                        self.bans.is_banned_username(username)
+                    });
+                    if banned {
+                        log::info!("{}: refusing banned user", conn.id);
+                        conn.close(Err(Error::Banned));
+                    } else {
+                        conn.state = state;
+                        conn.send_message(msg);
+                    }
                     conn.rsock = Some(ReadSocket::Connected(s));
                 } else {
                     unreachable!()
@@ -906,29 +2816,50 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     ) -> component_future::Poll<(), Error> {
         match &mut conn.wsock {
             Some(WriteSocket::Connected(..)) => {
-                if let Some(msg) = conn.to_send.pop_front() {
-                    if let Some(WriteSocket::Connected(s)) = conn.wsock.take()
-                    {
+                component_future::try_ready!(conn
+                    .output_batch
+                    .poll_ready(&conn.to_send));
+                if conn.to_send.is_empty() {
+                    return if conn.closed {
+                        Ok(component_future::Async::Ready(()))
+                    } else {
+                        Ok(component_future::Async::NothingToDo)
+                    };
+                }
+
+                if let Some(WriteSocket::Connected(mut s)) = conn.wsock.take()
+                {
+                    s.set_proto_version(conn.proto_version);
+                    // a popular stream can queue up lots of small messages
+                    // for each watcher, so send everything which is ready
+                    // with as few writes as possible
+                    while let Some(msg) = conn.to_send.pop_front() {
                         log::debug!(
                             "{}: send({})",
                             conn.id,
                             msg.format_log()
                         );
-                        let fut = msg
-                            .write_async(s)
-                            .timeout(self.read_timeout)
-                            .context(crate::error::WriteMessageWithTimeout);
-                        conn.wsock =
-                            Some(WriteSocket::Writing(Box::new(fut)));
-                    } else {
-                        unreachable!()
+                        if let Some(msg) = s.queue(msg)? {
+                            conn.to_send.push_front(msg);
+                            break;
+                        }
+                        if !conn
+                            .output_batch
+                            .poll_ready(&conn.to_send)?
+                            .is_ready()
+                        {
+                            break;
+                        }
                     }
-                    Ok(component_future::Async::DidWork)
-                } else if conn.closed {
-                    Ok(component_future::Async::Ready(()))
+                    let fut = s
+                        .flush()
+                        .timeout(self.read_timeout)
+                        .context(crate::error::WriteMessageWithTimeout);
+                    conn.wsock = Some(WriteSocket::Writing(Box::new(fut)));
                 } else {
-                    Ok(component_future::Async::NothingToDo)
+                    unreachable!()
                 }
+                Ok(component_future::Async::DidWork)
             }
             Some(WriteSocket::Writing(fut)) => match fut.poll() {
                 Ok(futures::Async::Ready(s)) => {
@@ -944,6 +2875,63 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         }
     }
 
+    // in strict mode, clients which violate the protocol have their address
+    // refused for a while, and what they were doing is logged to help debug
+    // the client
+    fn quarantine(&mut self, conn: &Connection<S>, e: &Error) {
+        let duration = if let Some(duration) = self.quarantine_duration {
+            duration
+        } else {
+            return;
+        };
+
+        let address = conn.address.ip();
+        let mut dump = if self.trusted_proxies.contains(&address) {
+            // the connection is still closed, but the proxy's other
+            // clients shouldn't be refused along with it
+            format!(
+                "{}: not quarantining trusted proxy {} after protocol \
+                 violation: {}\n",
+                conn.id, address, e
+            )
+        } else {
+            let now = std::time::Instant::now();
+            self.quarantined.retain(|_, until| *until > now);
+            self.quarantined.insert(address, now + duration);
+            format!(
+                "{}: quarantining {} for {}s after protocol violation: {}\n",
+                conn.id,
+                address,
+                duration.as_secs(),
+                e
+            )
+        };
+        dump.push_str(&format!(
+            "  user: {}\n",
+            conn.state.username().unwrap_or("(not logged in)")
+        ));
+        dump.push_str(&format!(
+            "  last {} messages received:\n",
+            conn.recent_messages.len()
+        ));
+        for message in &conn.recent_messages {
+            dump.push_str(&format!("    {}\n", message));
+        }
+        log::warn!("{}", dump.trim_end());
+    }
+
+    fn is_quarantined(&mut self, address: std::net::SocketAddr) -> bool {
+        let now = std::time::Instant::now();
+        match self.quarantined.get(&address.ip()) {
+            Some(until) if *until > now => true,
+            Some(_) => {
+                self.quarantined.remove(&address.ip());
+                false
+            }
+            None => false,
+        }
+    }
+
     fn streamers(&self) -> impl Iterator<Item = &Connection<S>> {
         self.connections.values().filter(|conn| match conn.state {
             ConnectionState::Streaming { .. } => true,
@@ -966,6 +2954,66 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                 _ => false,
             })
     }
+
+    // watchers who had terminal output dropped are sent a fresh copy of the
+    // screen instead, since the output they did get no longer applies to
+    // what they are displaying
+    fn resync_connection(&self, conn: &mut Connection<S>) {
+        if !conn.needs_resync {
+            return;
+        }
+        conn.needs_resync = false;
+
+        let term = if let Some(term) = conn
+            .state
+            .watch_id()
+            .and_then(|watch_id| self.connections.get(watch_id))
+            .and_then(|stream_conn| stream_conn.state.term())
+        {
+            term
+        } else {
+            return;
+        };
+
+        log::info!("{}: send queue full, resending screen", conn.id);
+        // anything a rewinding watcher had left to catch up on is relative
+        // to output which is now gone
+        conn.state.finish_catchup();
+        conn.drop_queued_output();
+        // these bypass send_message, since the queue is likely still full
+        let (rows, cols) = term.screen().size();
+        conn.to_send.push_back(crate::protocol::Message::resize(
+            crate::term::Size { rows, cols },
+        ));
+        conn.to_send
+            .push_back(crate::protocol::Message::terminal_output(
+                &term.screen().contents_formatted(),
+            ));
+    }
+
+    fn revoke_control_from(&mut self, id: &str) {
+        if let Some(watch_conn) = self.connections.get_mut(id) {
+            if let Some(username) = watch_conn.state.username() {
+                let msg = crate::protocol::Message::revoke_control(username);
+                watch_conn.send_message(msg);
+            }
+        }
+    }
+
+    fn send_to_watchers(
+        &mut self,
+        watch_id: &str,
+        username: &str,
+        msg: &crate::protocol::Message,
+    ) {
+        for watch_conn in self.watchers_mut() {
+            if watch_conn.state.watch_id().unwrap() == watch_id
+                && watch_conn.state.username().unwrap() == username
+            {
+                watch_conn.send_message(msg.clone());
+            }
+        }
+    }
 }
 
 impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
@@ -978,12 +3026,93 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             -> component_future::Poll<
             (),
             Error,
-        >] = &[&Self::poll_accept, &Self::poll_read, &Self::poll_write];
+        >] = &[
+        &Self::poll_shutdown,
+        &Self::poll_accept,
+        &Self::poll_read,
+        &Self::poll_reconnecting,
+        &Self::poll_catchup,
+        &Self::poll_ban_file,
+        &Self::poll_session_list,
+        &Self::poll_time_limits,
+        &Self::poll_watcher_counts,
+        &Self::poll_write,
+    ];
+
+    // on SIGTERM or SIGINT, stop accepting new connections and tell everyone
+    // who is currently connected that we are going away, and then exit once
+    // all of those messages have been sent
+    fn poll_shutdown(&mut self) -> component_future::Poll<(), Error> {
+        if self.shutting_down {
+            return if self.connections.is_empty() {
+                Ok(component_future::Async::Ready(()))
+            } else {
+                Ok(component_future::Async::NothingToDo)
+            };
+        }
+
+        if let Some(signal) =
+            component_future::try_ready!(self.shutdown.poll())
+        {
+            log::info!(
+                "received {}, disconnecting {} clients",
+                signal,
+                self.connections.len()
+            );
+            self.shutting_down = true;
+            for conn in self.connections.values_mut() {
+                if !conn.closed {
+                    conn.shutdown(SHUTDOWN_REASON);
+                }
+            }
+            Ok(component_future::Async::DidWork)
+        } else {
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
 
     fn poll_accept(&mut self) -> component_future::Poll<(), Error> {
-        if let Some(sock) = component_future::try_ready!(self.acceptor.poll())
+        if self.shutting_down {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        if let Some((sock, address, roles)) =
+            component_future::try_ready!(self.acceptor.poll())
         {
-            let conn = Connection::new(sock);
+            if self.is_quarantined(address) {
+                log::info!(
+                    "refusing connection from quarantined address {}",
+                    address.ip()
+                );
+                return Ok(component_future::Async::DidWork);
+            }
+            let banned = self.bans.is_banned_address(address.ip());
+            let mut conn = Connection::new(
+                sock,
+                address,
+                roles,
+                self.send_queue_size,
+                self.send_queue_policy,
+            );
+            // the connection is still accepted, so that the client can be
+            // told why it is being refused
+            if banned {
+                log::info!(
+                    "{}: refusing connection from banned address {}",
+                    conn.id,
+                    address.ip()
+                );
+                conn.close(Err(Error::Banned));
+            } else if let Some(max) = self.max_connections {
+                if self.connection_count() >= max {
+                    log::info!("{}: too many connections", conn.id);
+                    conn.close(Err(Error::TooManyConnections { max }));
+                }
+            }
+            if !conn.closed {
+                self.total_connections =
+                    self.total_connections.saturating_add(1);
+            }
             self.connections.insert(conn.id.to_string(), conn);
             Ok(component_future::Async::DidWork)
         } else {
@@ -1000,7 +3129,11 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
             let mut conn = self.connections.remove(&key).unwrap();
             match self.poll_read_connection(&mut conn) {
                 Ok(component_future::Async::Ready(())) => {
-                    self.handle_disconnect(&mut conn);
+                    if self.hold_for_reconnect(&mut conn) {
+                        self.connections.insert(key.to_string(), conn);
+                    } else {
+                        self.handle_disconnect(&mut conn);
+                    }
                     continue;
                 }
                 Ok(component_future::Async::DidWork) => {
@@ -1014,11 +3147,115 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                         "error reading from active connection: {}",
                         e
                     );
+                    if self.hold_for_reconnect(&mut conn) {
+                        self.connections.insert(key.to_string(), conn);
+                    } else {
+                        self.finish_session(&conn);
+                    }
                     continue;
                 }
                 _ => {}
             }
-            self.connections.insert(key.to_string(), conn);
+            // resuming a stream gives the connection the stream's id
+            self.connections.insert(conn.id.clone(), conn);
+        }
+
+        if did_work {
+            Ok(component_future::Async::DidWork)
+        } else if not_ready {
+            Ok(component_future::Async::NotReady)
+        } else {
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
+
+    // streams whose streamer didn't reconnect in time (or which can't wait
+    // any longer because the server is shutting down) end the same way as
+    // if their connection had just dropped
+    fn poll_reconnecting(&mut self) -> component_future::Poll<(), Error> {
+        let now = std::time::Instant::now();
+        let shutting_down = self.shutting_down;
+        let expired: Vec<_> = self
+            .connections
+            .values()
+            .filter(|conn| {
+                conn.reconnect_deadline.map_or(false, |deadline| {
+                    shutting_down || deadline <= now
+                })
+            })
+            .map(|conn| conn.id.clone())
+            .collect();
+        if !expired.is_empty() {
+            for id in expired {
+                let mut conn = self.connections.remove(&id).unwrap();
+                log::info!("{}: reconnect grace period expired", conn.id);
+                self.handle_disconnect(&mut conn);
+            }
+            return Ok(component_future::Async::DidWork);
+        }
+
+        let next_deadline = self
+            .connections
+            .values()
+            .filter_map(|conn| conn.reconnect_deadline)
+            .min();
+        let next_deadline = if let Some(deadline) = next_deadline {
+            deadline
+        } else {
+            self.reconnect_timer = None;
+            return Ok(component_future::Async::NothingToDo);
+        };
+        if self
+            .reconnect_timer
+            .as_ref()
+            .map_or(true, |timer| timer.deadline() != next_deadline)
+        {
+            self.reconnect_timer =
+                Some(tokio::timer::Delay::new(next_deadline));
+        }
+
+        component_future::try_ready!(self
+            .reconnect_timer
+            .as_mut()
+            .unwrap()
+            .poll()
+            .context(crate::error::TimerReconnect));
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_catchup(&mut self) -> component_future::Poll<(), Error> {
+        let mut did_work = false;
+        let mut not_ready = false;
+
+        for conn in self.connections.values_mut() {
+            let catchup = if let Some(catchup) = conn.state.catchup_mut() {
+                catchup
+            } else {
+                continue;
+            };
+
+            match catchup.poll() {
+                Ok(futures::Async::Ready(Some(events))) => {
+                    for event in events {
+                        conn.send_message(event.message());
+                    }
+                    did_work = true;
+                }
+                Ok(futures::Async::Ready(None)) => {
+                    log::info!("{}: caught up", conn.id);
+                    conn.state.finish_catchup();
+                    did_work = true;
+                }
+                Ok(futures::Async::NotReady) => {
+                    not_ready = true;
+                }
+                Err(e) => {
+                    log::error!("error catching up connection: {}", e);
+                    conn.state.finish_catchup();
+                    conn.close(Err(e));
+                    did_work = true;
+                }
+            }
         }
 
         if did_work {
@@ -1030,6 +3267,161 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         }
     }
 
+    // errors reading the ban file are logged rather than stopping the
+    // server, since the previous bans are still around to use
+    fn poll_ban_file(&mut self) -> component_future::Poll<(), Error> {
+        component_future::try_ready!(self
+            .ban_file_timer
+            .poll()
+            .context(crate::error::TimerBanFile));
+
+        match self.bans.reload() {
+            Ok(true) => {
+                log::info!("reloaded ban file: {} bans", self.bans.count());
+                self.kick_banned();
+            }
+            Ok(false) => {}
+            Err(e) => {
+                log::warn!("failed to reload ban file: {}", e);
+            }
+        }
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_session_list(&mut self) -> component_future::Poll<(), Error> {
+        component_future::try_ready!(self
+            .session_list_timer
+            .poll()
+            .context(crate::error::TimerSessionList));
+
+        let subscribed = self
+            .connections
+            .values()
+            .any(|conn| conn.session_list.is_some());
+        if !subscribed {
+            return Ok(component_future::Async::DidWork);
+        }
+
+        let sessions = self.sessions();
+        let connections = self.connection_count();
+        let limits = self.server_limits(connections);
+        for conn in self.connections.values_mut() {
+            let mut msgs = if let Some(known) = &mut conn.session_list {
+                session_list_changes(known, &sessions)
+            } else {
+                continue;
+            };
+            if conn.server_limits
+                && conn.sent_connection_count != Some(connections)
+            {
+                msgs.insert(0, limits.clone());
+                conn.sent_connection_count = Some(connections);
+            }
+            for msg in msgs {
+                conn.send_message(msg);
+            }
+        }
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_watcher_counts(&mut self) -> component_future::Poll<(), Error> {
+        component_future::try_ready!(self
+            .watcher_count_timer
+            .poll()
+            .context(crate::error::TimerWatcherCount));
+
+        let watcher_counts: std::collections::HashMap<_, _> = self
+            .watcher_counts()
+            .into_iter()
+            .map(|(id, count)| (id.to_string(), count))
+            .collect();
+        for conn in self.connections.values_mut() {
+            if !conn.watcher_count || conn.state.term().is_none() {
+                continue;
+            }
+            let count = *watcher_counts.get(&conn.id).unwrap_or(&0);
+            if conn.sent_watcher_count != Some(count) {
+                conn.send_message(crate::protocol::Message::watcher_count(
+                    count,
+                ));
+                conn.sent_watcher_count = Some(count);
+            }
+        }
+        Ok(component_future::Async::DidWork)
+    }
+
+    // streams which have run for longer than the maximum stream duration
+    // are ended, after warning their streamers as the time runs out
+    fn poll_time_limits(&mut self) -> component_future::Poll<(), Error> {
+        let max = if let Some(max) = self.max_stream_duration {
+            max
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        component_future::try_ready!(self
+            .time_limit_timer
+            .poll()
+            .context(crate::error::TimerTimeLimit));
+
+        let mut expired = vec![];
+        for conn in self.connections.values_mut() {
+            // streams waiting for their streamer to reconnect are checked
+            // again once they do
+            if conn.closed || conn.reconnect_deadline.is_some() {
+                continue;
+            }
+            let elapsed = if let Some(started) = conn.state.started() {
+                started.elapsed().unwrap_or_default()
+            } else {
+                continue;
+            };
+
+            if elapsed >= max {
+                log::info!("{}: stream reached the time limit", conn.id);
+                if conn.time_limit {
+                    conn.send_message(crate::protocol::Message::time_limit(
+                        0,
+                    ));
+                }
+                conn.close(Err(Error::StreamTimeLimit {
+                    secs: max.as_secs(),
+                }));
+                expired.push(conn.id.clone());
+                continue;
+            }
+
+            // only the most recent warning is sent if several were missed
+            // at once, like after reconnecting
+            let remaining = max - elapsed;
+            let mut warn = false;
+            while let Some(&warning) =
+                self.stream_warnings.get(conn.time_limit_warnings)
+            {
+                if remaining > warning {
+                    break;
+                }
+                conn.time_limit_warnings += 1;
+                warn = true;
+            }
+            if warn && conn.time_limit {
+                let secs = u32::try_from(remaining.as_secs())
+                    .unwrap_or(std::u32::MAX);
+                conn.send_message(crate::protocol::Message::time_limit(secs));
+            }
+        }
+
+        let msg = crate::protocol::Message::chat("", TIME_LIMIT_NOTICE);
+        for watch_conn in self.watchers_mut() {
+            let watch_id = watch_conn.state.watch_id().unwrap();
+            if expired.iter().any(|id| id == watch_id) {
+                watch_conn.send_message(msg.clone());
+            }
+        }
+
+        Ok(component_future::Async::DidWork)
+    }
+
     fn poll_write(&mut self) -> component_future::Poll<(), Error> {
         let mut did_work = false;
         let mut not_ready = false;
@@ -1037,6 +3429,7 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         let keys: Vec<_> = self.connections.keys().cloned().collect();
         for key in keys {
             let mut conn = self.connections.remove(&key).unwrap();
+            self.resync_connection(&mut conn);
             match self.poll_write_connection(&mut conn) {
                 Ok(component_future::Async::Ready(())) => {
                     self.handle_disconnect(&mut conn);
@@ -1050,6 +3443,11 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
                 }
                 Err(e) => {
                     log::error!("error writing to active connection: {}", e);
+                    if self.hold_for_reconnect(&mut conn) {
+                        self.connections.insert(key.to_string(), conn);
+                    } else {
+                        self.finish_session(&conn);
+                    }
                     continue;
                 }
                 _ => {}
@@ -1067,6 +3465,142 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     }
 }
 
+fn signal(
+    signal: std::os::raw::c_int,
+    name: &'static str,
+) -> impl futures::Stream<Item = &'static str, Error = Error> + Send {
+    tokio_signal::unix::Signal::new(signal)
+        .flatten_stream()
+        .map(move |_| name)
+        .context(crate::error::Signal)
+}
+
+// updates the sessions that a subscribed connection knows about, returning
+// the messages which tell it about the differences. idle times and uptimes
+// change constantly, so they only count as a change when a session becomes
+// active again or when they have drifted too far from what was last sent.
+fn session_list_changes(
+    known: &mut std::collections::HashMap<String, crate::protocol::Session>,
+    sessions: &[crate::protocol::Session],
+) -> Vec<crate::protocol::Message> {
+    let mut msgs = vec![];
+
+    let ids: std::collections::HashSet<_> =
+        sessions.iter().map(|session| session.id.as_str()).collect();
+    let removed: Vec<_> = known
+        .keys()
+        .filter(|id| !ids.contains(id.as_str()))
+        .cloned()
+        .collect();
+    for id in removed {
+        known.remove(&id);
+        msgs.push(crate::protocol::Message::session_removed(&id));
+    }
+
+    for session in sessions {
+        if let Some(prev) = known.get(&session.id) {
+            let unchanged = crate::protocol::Session {
+                idle_time: prev.idle_time,
+                uptime: prev.uptime,
+                ..session.clone()
+            } == *prev;
+            let drifted = session.idle_time < prev.idle_time
+                || session.idle_time - prev.idle_time
+                    >= SESSION_LIST_MAX_DRIFT
+                || session.uptime.saturating_sub(prev.uptime)
+                    >= SESSION_LIST_MAX_DRIFT;
+            if unchanged && !drifted {
+                continue;
+            }
+            msgs.push(crate::protocol::Message::session_updated(session));
+        } else {
+            msgs.push(crate::protocol::Message::session_added(session));
+        }
+        known.insert(session.id.clone(), session.clone());
+    }
+
+    msgs
+}
+
+// how a connection is described to admins
+fn admin_connection<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+>(
+    conn: &Connection<S>,
+) -> crate::protocol::AdminConnection {
+    let connected = conn.connected_at.elapsed().as_secs();
+    crate::protocol::AdminConnection {
+        id: conn.id.clone(),
+        address: conn.address.to_string(),
+        username: conn.state.username().unwrap_or("").to_string(),
+        state: admin_connection_state(conn).to_string(),
+        watch_id: conn.state.watch_id().unwrap_or("").to_string(),
+        connected: u32::try_from(connected).unwrap_or(std::u32::MAX),
+    }
+}
+
+fn admin_filter_matches<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+>(
+    filter: &crate::protocol::AdminFilter,
+    conn: &Connection<S>,
+) -> bool {
+    match filter {
+        crate::protocol::AdminFilter::User(username) => {
+            conn.state.username() == Some(username.as_str())
+        }
+        crate::protocol::AdminFilter::Tag(tag) => {
+            conn.state.tags().map_or(false, |tags| tags.contains(tag))
+        }
+        crate::protocol::AdminFilter::Address(address) => {
+            conn.address.ip() == *address
+        }
+    }
+}
+
+fn admin_connection_state<
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+>(
+    conn: &Connection<S>,
+) -> &'static str {
+    if conn.reconnect_deadline.is_some() {
+        return "reconnecting";
+    }
+    match conn.state {
+        ConnectionState::Accepted => "accepted",
+        ConnectionState::LoggingIn { .. } => "logging in",
+        ConnectionState::LoggedIn { .. } => "logged in",
+        ConnectionState::Streaming { .. } => "streaming",
+        ConnectionState::Watching { .. } => "watching",
+    }
+}
+
+// i don't really care if things break in 2106
+#[allow(clippy::cast_possible_truncation)]
+fn unix_time(time: std::time::SystemTime) -> u32 {
+    time.duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as u32)
+}
+
+fn is_protocol_violation(e: &Error) -> bool {
+    match e {
+        Error::UnexpectedMessage { .. }
+        | Error::UnauthenticatedMessage { .. }
+        | Error::ExtraMessageData { .. }
+        | Error::FrameTooBig { .. }
+        | Error::InvalidAuthClient { .. }
+        | Error::InvalidAuthType { .. }
+        | Error::InvalidMessageType { .. }
+        | Error::LenTooBig { .. }
+        | Error::LenTooSmall { .. }
+        | Error::ParseInt { .. }
+        | Error::ParseString { .. }
+        | Error::StringTooLong { .. }
+        | Error::TooManyItems { .. } => true,
+        _ => false,
+    }
+}
+
 fn classify_connection_error(e: Error) -> component_future::Poll<(), Error> {
     let source = match e {
         Error::ReadMessageWithTimeout { source } => source,
@@ -0,0 +1,166 @@
+use crate::prelude::*;
+use std::io::{Read as _, Write as _};
+
+// usernames and addresses which aren't allowed to use the server. these
+// come from the ban file (one entry per line, where anything which parses
+// as an ip address is an address and anything else is a username), which
+// is reread whenever it changes, and from admins running tt admin ban.
+#[derive(Debug, Default)]
+pub struct Bans {
+    filename: Option<String>,
+    modified: Option<std::time::SystemTime>,
+    usernames: std::collections::HashSet<String>,
+    addresses: std::collections::HashSet<std::net::IpAddr>,
+}
+
+impl Bans {
+    pub fn load(filename: Option<&str>) -> Result<Self> {
+        let mut bans = Self {
+            filename: filename.map(std::string::ToString::to_string),
+            ..Self::default()
+        };
+        bans.reload()?;
+        Ok(bans)
+    }
+
+    pub fn is_banned_username(&self, username: &str) -> bool {
+        self.usernames.contains(username)
+    }
+
+    pub fn is_banned_address(&self, address: std::net::IpAddr) -> bool {
+        self.addresses.contains(&address)
+    }
+
+    pub fn count(&self) -> usize {
+        self.usernames.len() + self.addresses.len()
+    }
+
+    // rereads the ban file if it has been modified since it was last read,
+    // returning whether anything was reread
+    pub fn reload(&mut self) -> Result<bool> {
+        let filename = if let Some(filename) = &self.filename {
+            filename
+        } else {
+            return Ok(false);
+        };
+
+        // a ban file which doesn't exist yet just means no one is banned,
+        // since it will be created by the first tt admin ban
+        let modified = match std::fs::metadata(filename) {
+            Ok(metadata) => metadata.modified().ok(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(Error::OpenFileSync {
+                    filename: filename.clone(),
+                    source: e,
+                })
+            }
+        };
+        if modified == self.modified {
+            return Ok(false);
+        }
+
+        let mut contents = String::new();
+        if modified.is_some() {
+            let mut file = std::fs::File::open(filename).context(
+                crate::error::OpenFileSync {
+                    filename: filename.clone(),
+                },
+            )?;
+            file.read_to_string(&mut contents)
+                .context(crate::error::ReadFileSync)?;
+        }
+        let (usernames, addresses) = parse(&contents);
+        self.usernames = usernames;
+        self.addresses = addresses;
+        self.modified = modified;
+        Ok(true)
+    }
+
+    // bans made by admins are also appended to the ban file (if there is
+    // one), so that they survive restarts
+    pub fn ban(&mut self, entry: &str) -> Result<()> {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            return Ok(());
+        }
+        if let Some(filename) = &self.filename {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(filename)
+                .context(crate::error::OpenFileSync {
+                    filename: filename.clone(),
+                })?;
+            writeln!(file, "{}", entry)
+                .context(crate::error::WriteFileSync)?;
+            // the file now matches what we have in memory, so there is no
+            // need to reread it on the next check
+            self.modified = file
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.modified().ok());
+        }
+
+        if let Ok(address) = entry.parse() {
+            self.addresses.insert(address);
+        } else {
+            self.usernames.insert(entry.to_string());
+        }
+        Ok(())
+    }
+}
+
+// blank lines and lines starting with # are ignored
+fn parse(
+    contents: &str,
+) -> (
+    std::collections::HashSet<String>,
+    std::collections::HashSet<std::net::IpAddr>,
+) {
+    let mut usernames = std::collections::HashSet::new();
+    let mut addresses = std::collections::HashSet::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Ok(address) = line.parse() {
+            addresses.insert(address);
+        } else {
+            usernames.insert(line.to_string());
+        }
+    }
+    (usernames, addresses)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let (usernames, addresses) =
+            parse("# spam\nspammer\n\n  troll  \n10.1.2.3\n::1\n#doy\n");
+        let mut usernames: Vec<_> = usernames.into_iter().collect();
+        usernames.sort();
+        assert_eq!(usernames, vec!["spammer", "troll"]);
+        assert_eq!(addresses.len(), 2);
+        assert!(addresses.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(addresses.contains(&"::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_ban() {
+        let mut bans = Bans::load(None).unwrap();
+        assert!(!bans.reload().unwrap());
+        assert!(!bans.is_banned_username("spammer"));
+        bans.ban("spammer").unwrap();
+        bans.ban("192.168.0.1").unwrap();
+        assert!(bans.is_banned_username("spammer"));
+        assert!(!bans.is_banned_username("192.168.0.1"));
+        assert!(bans.is_banned_address("192.168.0.1".parse().unwrap()));
+        assert!(!bans.is_banned_address("192.168.0.2".parse().unwrap()));
+        assert_eq!(bans.count(), 2);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::prelude::*;
+
+// output beyond this is folded into the base screen even if it is still
+// inside the window, so that a stream producing lots of output (running
+// `yes`, for instance) can't use up the server's memory
+const MAX_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Output(Vec<u8>),
+    Resize(crate::term::Size),
+}
+
+impl Event {
+    pub fn message(&self) -> crate::protocol::Message {
+        match self {
+            Self::Output(data) => {
+                crate::protocol::Message::terminal_output(data)
+            }
+            Self::Resize(size) => crate::protocol::Message::resize(*size),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Output(data) => data.len(),
+            Self::Resize(_) => 0,
+        }
+    }
+}
+
+// the recent output of a stream, so that watchers can rewind a bit to see
+// what they missed
+pub struct History {
+    window: std::time::Duration,
+    // the state of the terminal as of the start of the retained window
+    base: vt100::Parser,
+    events: std::collections::VecDeque<(std::time::Instant, Event)>,
+    // total size of the output in events
+    bytes: usize,
+}
+
+impl History {
+    pub fn new(size: crate::term::Size, window: std::time::Duration) -> Self {
+        Self {
+            window,
+            base: vt100::Parser::new(size.rows, size.cols, 0),
+            events: std::collections::VecDeque::new(),
+            bytes: 0,
+        }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        let now = std::time::Instant::now();
+        self.bytes += event.len();
+        self.events.push_back((now, event));
+
+        while let Some((time, _)) = self.events.front() {
+            if now.duration_since(*time) <= self.window
+                && self.bytes <= MAX_BYTES
+            {
+                break;
+            }
+            let (_, event) = self.events.pop_front().unwrap();
+            self.bytes -= event.len();
+            apply(&mut self.base, &event);
+        }
+    }
+
+    // returns the contents of the screen as of offset ago, along with a
+    // catchup stream for the events that have happened since then
+    pub fn rewind(
+        &self,
+        offset: std::time::Duration,
+    ) -> (crate::term::Size, Vec<u8>, Catchup) {
+        let offset = offset.min(self.window);
+        // if the offset goes back further than the clock does, everything
+        // we have is replayed from the base screen
+        let start = std::time::Instant::now()
+            .checked_sub(offset)
+            .or_else(|| self.events.front().map(|(time, _)| *time))
+            .unwrap_or_else(std::time::Instant::now);
+
+        let (rows, cols) = self.base.screen().size();
+        let mut parser = vt100::Parser::new(rows, cols, 0);
+        parser.process(&self.base.screen().contents_formatted());
+
+        // only the events which need to be replayed are copied
+        let skip = self
+            .events
+            .iter()
+            .take_while(|(time, _)| *time < start)
+            .count();
+        for (_, event) in self.events.iter().take(skip) {
+            apply(&mut parser, event);
+        }
+        let events = self.events.iter().skip(skip).cloned().collect();
+
+        let (rows, cols) = parser.screen().size();
+        (
+            crate::term::Size { rows, cols },
+            parser.screen().contents_formatted(),
+            Catchup::new(start, events),
+        )
+    }
+}
+
+fn apply(parser: &mut vt100::Parser, event: &Event) {
+    match event {
+        Event::Output(data) => parser.process(data),
+        Event::Resize(size) => parser.set_size(size.rows, size.cols),
+    }
+}
+
+// replays the events after a rewound screen at an accelerated rate until it
+// catches back up with the live stream
+pub struct Catchup {
+    start_stream: std::time::Instant,
+    start_real: std::time::Instant,
+    events: std::collections::VecDeque<(std::time::Instant, Event)>,
+    // how many events have been pushed since the catchup started
+    live: usize,
+    timer: Option<tokio::timer::Delay>,
+}
+
+impl Catchup {
+    fn new(
+        start: std::time::Instant,
+        events: std::collections::VecDeque<(std::time::Instant, Event)>,
+    ) -> Self {
+        Self {
+            start_stream: start,
+            start_real: std::time::Instant::now(),
+            events,
+            live: 0,
+            timer: None,
+        }
+    }
+
+    // live events which arrive while catching up are queued behind the
+    // replayed ones
+    pub fn push(&mut self, event: Event) {
+        self.events.push_back((std::time::Instant::now(), event));
+        self.live += 1;
+    }
+
+    // the number of live events which haven't been replayed yet. the live
+    // events are all at the end of the queue, so once the queue is shorter
+    // than that, everything left in it is live.
+    pub fn live_len(&self) -> usize {
+        self.live.min(self.events.len())
+    }
+
+    fn due(&self, time: std::time::Instant) -> std::time::Instant {
+        let elapsed = if time > self.start_stream {
+            time - self.start_stream
+        } else {
+            std::time::Duration::from_secs(0)
+        };
+        self.start_real + elapsed / crate::protocol::CATCHUP_SPEED
+    }
+}
+
+#[must_use = "streams do nothing unless polled"]
+impl futures::Stream for Catchup {
+    type Item = Vec<Event>;
+    type Error = Error;
+
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if self.events.is_empty() {
+                return Ok(futures::Async::Ready(None));
+            }
+
+            let now = std::time::Instant::now();
+            let mut ready = vec![];
+            while let Some((time, _)) = self.events.front() {
+                if self.due(*time) > now {
+                    break;
+                }
+                let (_, event) = self.events.pop_front().unwrap();
+                ready.push(event);
+            }
+            if !ready.is_empty() {
+                self.timer = None;
+                return Ok(futures::Async::Ready(Some(ready)));
+            }
+
+            let next = self.due(self.events.front().unwrap().0);
+            let timer = self
+                .timer
+                .get_or_insert_with(|| tokio::timer::Delay::new(next));
+            timer.reset(next);
+            futures::try_ready!(timer
+                .poll()
+                .context(crate::error::TimerCatchup));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_max_bytes() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let mut history =
+            History::new(size, std::time::Duration::from_secs(300));
+        for _ in 0..5 {
+            history.push(Event::Output(vec![b'y'; MAX_BYTES / 4]));
+        }
+        assert_eq!(history.events.len(), 4);
+        assert_eq!(history.bytes, MAX_BYTES);
+        history.push(Event::Resize(size));
+        assert_eq!(history.events.len(), 5);
+    }
+
+    #[test]
+    fn test_rewind_past_boot() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let forever = std::time::Duration::from_secs(std::u64::MAX / 2);
+        let mut history = History::new(size, forever);
+        history.push(Event::Output(b"foo".to_vec()));
+        let (rewound_size, data, catchup) = history.rewind(forever);
+        assert_eq!(rewound_size, size);
+        let mut parser = vt100::Parser::new(size.rows, size.cols, 0);
+        parser.process(&data);
+        assert_eq!(parser.screen().contents(), "");
+        assert_eq!(catchup.events.len(), 1);
+    }
+
+    #[test]
+    fn test_catchup_live_len() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let window = std::time::Duration::from_secs(300);
+        let mut history = History::new(size, window);
+        history.push(Event::Output(b"foo".to_vec()));
+        history.push(Event::Output(b"bar".to_vec()));
+        let (_, _, mut catchup) = history.rewind(window);
+        assert_eq!(catchup.live_len(), 0);
+        catchup.push(Event::Output(b"baz".to_vec()));
+        catchup.push(Event::Resize(size));
+        assert_eq!(catchup.events.len(), 4);
+        assert_eq!(catchup.live_len(), 2);
+        catchup.events.pop_front();
+        catchup.events.pop_front();
+        catchup.events.pop_front();
+        assert_eq!(catchup.live_len(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+// sequences which throw away everything that was on the screen before them
+const SCREEN_CLEARS: &[&[u8]] =
+    &[b"\x1b[2J", b"\x1bc", b"\x1b[?1049h", b"\x1b[?1049l"];
+
+// the screen of a stream as of some point, along with the output which has
+// happened since then. watchers who join are sent both, which is much
+// cheaper than redrawing the whole screen from scratch for each of them when
+// lots of watchers join a popular stream at once.
+#[derive(Default)]
+pub struct ReplayCache {
+    keyframe: Option<Keyframe>,
+}
+
+struct Keyframe {
+    size: crate::term::Size,
+    data: Vec<u8>,
+    deltas: Vec<u8>,
+}
+
+impl ReplayCache {
+    // returns the size of the screen along with the data which draws it
+    pub fn replay(
+        &mut self,
+        screen: &vt100::Screen,
+    ) -> (crate::term::Size, Vec<u8>) {
+        let keyframe = self.keyframe.get_or_insert_with(|| {
+            let (rows, cols) = screen.size();
+            Keyframe {
+                size: crate::term::Size { rows, cols },
+                data: screen.contents_formatted(),
+                deltas: vec![],
+            }
+        });
+        let mut data =
+            Vec::with_capacity(keyframe.data.len() + keyframe.deltas.len());
+        data.extend_from_slice(&keyframe.data);
+        data.extend_from_slice(&keyframe.deltas);
+        (keyframe.size, data)
+    }
+
+    // `diff` is the change to the screen that `data` made
+    pub fn output(&mut self, data: &[u8], diff: &[u8]) {
+        let keyframe = if let Some(keyframe) = &mut self.keyframe {
+            keyframe
+        } else {
+            return;
+        };
+
+        // a new keyframe taken after the screen was cleared will be small,
+        // and once the chain is bigger than the keyframe itself, sending a
+        // new one is cheaper for the watchers too
+        if clears_screen(data)
+            || keyframe.deltas.len() + diff.len() > keyframe.data.len()
+        {
+            self.keyframe = None;
+        } else {
+            keyframe.deltas.extend_from_slice(diff);
+        }
+    }
+
+    pub fn resize(&mut self) {
+        self.keyframe = None;
+    }
+}
+
+fn clears_screen(data: &[u8]) -> bool {
+    SCREEN_CLEARS
+        .iter()
+        .any(|seq| data.windows(seq.len()).any(|window| window == *seq))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn output(
+        term: &mut vt100::Parser,
+        cache: &mut ReplayCache,
+        data: &[u8],
+    ) {
+        let screen = term.screen().clone();
+        term.process(data);
+        let diff = term.screen().contents_diff(&screen);
+        cache.output(data, &diff);
+    }
+
+    fn replayed(cache: &mut ReplayCache, term: &vt100::Parser) -> Vec<u8> {
+        let (size, data) = cache.replay(term.screen());
+        let mut watcher = vt100::Parser::new(size.rows, size.cols, 0);
+        watcher.process(&data);
+        watcher.screen().contents_formatted()
+    }
+
+    #[test]
+    fn test_replay_cache() {
+        let mut term = vt100::Parser::new(24, 80, 0);
+        let mut cache = ReplayCache::default();
+
+        output(&mut term, &mut cache, b"\x1b[31mfoo\x1b[m\r\nbar");
+        assert_eq!(
+            replayed(&mut cache, &term),
+            term.screen().contents_formatted()
+        );
+        assert!(cache.keyframe.is_some());
+
+        // the chain of changes since the keyframe is sent along with it
+        output(&mut term, &mut cache, b"\x1b[1;2Hx");
+        assert_eq!(
+            replayed(&mut cache, &term),
+            term.screen().contents_formatted()
+        );
+        assert!(!cache.keyframe.as_ref().unwrap().deltas.is_empty());
+
+        output(&mut term, &mut cache, b"\x1b[H\x1b[2Jbaz");
+        assert!(cache.keyframe.is_none());
+        assert_eq!(
+            replayed(&mut cache, &term),
+            term.screen().contents_formatted()
+        );
+
+        term.set_size(30, 100);
+        cache.resize();
+        assert!(cache.keyframe.is_none());
+        let (size, _) = cache.replay(term.screen());
+        assert_eq!(
+            size,
+            crate::term::Size {
+                rows: 30,
+                cols: 100
+            }
+        );
+
+        // the chain never grows larger than the keyframe
+        for _ in 0..100 {
+            output(&mut term, &mut cache, b"\x1b[1;1Hquux\x1b[1;1Hbaz ");
+            cache.replay(term.screen());
+            let keyframe = cache.keyframe.as_ref().unwrap();
+            assert!(keyframe.deltas.len() <= keyframe.data.len());
+        }
+        assert_eq!(
+            replayed(&mut cache, &term),
+            term.screen().contents_formatted()
+        );
+    }
+
+    // run with `cargo test --release -- --ignored --nocapture bench_`
+    #[test]
+    #[ignore]
+    fn bench_join_throughput() {
+        const JOINS: u32 = 10_000;
+
+        let mut term = vt100::Parser::new(50, 200, 0);
+        let mut cache = ReplayCache::default();
+        for i in 0..50 {
+            let line = format!("\x1b[3{}m{:0200}\x1b[m", i % 8, i);
+            output(&mut term, &mut cache, line.as_bytes());
+        }
+
+        let start = std::time::Instant::now();
+        let mut bytes = 0_u64;
+        for _ in 0..JOINS {
+            bytes += term.screen().contents_formatted().len() as u64;
+        }
+        let uncached = start.elapsed();
+        println!(
+            "uncached: {:.0} joins/s, {} bytes/join",
+            f64::from(JOINS) / uncached.as_secs_f64(),
+            bytes / u64::from(JOINS),
+        );
+
+        let start = std::time::Instant::now();
+        let mut bytes = 0_u64;
+        for i in 0..JOINS {
+            if i % 100 == 0 {
+                output(&mut term, &mut cache, b"\x1b[1;1Hx");
+            }
+            bytes += cache.replay(term.screen()).1.len() as u64;
+        }
+        let cached = start.elapsed();
+        println!(
+            "cached: {:.0} joins/s, {} bytes/join",
+            f64::from(JOINS) / cached.as_secs_f64(),
+            bytes / u64::from(JOINS),
+        );
+    }
+}
@@ -4,32 +4,63 @@ pub struct Server {
     server: super::Server<tokio_tls::TlsStream<tokio::net::TcpStream>>,
     acceptor: Box<
         dyn futures::Stream<
-                Item = tokio_tls::Accept<tokio::net::TcpStream>,
+                Item = (
+                    tokio_tls::Accept<tokio::net::TcpStream>,
+                    std::net::SocketAddr,
+                    super::ListenerRoles,
+                ),
                 Error = Error,
             > + Send,
     >,
-    sock_w: tokio::sync::mpsc::Sender<
+    sock_w: tokio::sync::mpsc::Sender<(
         tokio_tls::TlsStream<tokio::net::TcpStream>,
-    >,
-    accepting_sockets: Vec<tokio_tls::Accept<tokio::net::TcpStream>>,
+        std::net::SocketAddr,
+        super::ListenerRoles,
+    )>,
+    accepting_sockets: Vec<(
+        tokio_tls::Accept<tokio::net::TcpStream>,
+        std::net::SocketAddr,
+        super::ListenerRoles,
+    )>,
 }
 
 impl Server {
     pub fn new(
         acceptor: Box<
             dyn futures::Stream<
-                    Item = tokio_tls::Accept<tokio::net::TcpStream>,
+                    Item = (
+                        tokio_tls::Accept<tokio::net::TcpStream>,
+                        std::net::SocketAddr,
+                        super::ListenerRoles,
+                    ),
                     Error = Error,
                 > + Send,
         >,
         read_timeout: std::time::Duration,
+        history_window: std::time::Duration,
+        quarantine_duration: Option<std::time::Duration>,
+        trusted_proxies: std::collections::HashSet<std::net::IpAddr>,
+        disconnect_grace: Option<std::time::Duration>,
+        max_stream_duration: Option<std::time::Duration>,
+        stream_warnings: Vec<std::time::Duration>,
+        max_connections: Option<u32>,
+        max_watchers_per_session: Option<u32>,
+        send_queue_size: usize,
+        send_queue_policy: super::SendQueuePolicy,
         allowed_login_methods: std::collections::HashSet<
             crate::protocol::AuthType,
         >,
+        admins: std::collections::HashSet<(
+            crate::protocol::AuthType,
+            String,
+        )>,
+        trust_local_plain_admins: bool,
+        bans: super::access::Bans,
         oauth_configs: std::collections::HashMap<
             crate::protocol::AuthType,
             crate::oauth::Config,
         >,
+        host_key: Option<crate::host_key::HostKey>,
     ) -> Self {
         let (tls_sock_w, tls_sock_r) = tokio::sync::mpsc::channel(100);
         Self {
@@ -38,8 +69,22 @@ impl Server {
                     tls_sock_r.context(crate::error::SocketChannelReceive),
                 ),
                 read_timeout,
+                history_window,
+                quarantine_duration,
+                trusted_proxies,
+                disconnect_grace,
+                max_stream_duration,
+                stream_warnings,
+                max_connections,
+                max_watchers_per_session,
+                send_queue_size,
+                send_queue_policy,
                 allowed_login_methods,
+                admins,
+                trust_local_plain_admins,
+                bans,
                 oauth_configs,
+                host_key,
             ),
             acceptor,
             sock_w: tls_sock_w,
@@ -80,16 +125,21 @@ impl Server {
 
         let mut i = 0;
         while i < self.accepting_sockets.len() {
-            let sock = self.accepting_sockets.get_mut(i).unwrap();
+            let (sock, address, roles) =
+                self.accepting_sockets.get_mut(i).unwrap();
+            let address = *address;
+            let roles = *roles;
             match sock.poll() {
                 Ok(futures::Async::Ready(sock)) => {
                     self.accepting_sockets.swap_remove(i);
-                    self.sock_w.try_send(sock).unwrap_or_else(|e| {
-                        log::warn!(
-                            "failed to send connected tls socket: {}",
-                            e
-                        );
-                    });
+                    self.sock_w
+                        .try_send((sock, address, roles))
+                        .unwrap_or_else(|e| {
+                            log::warn!(
+                                "failed to send connected tls socket: {}",
+                                e
+                            );
+                        });
                     did_work = true;
                     continue;
                 }
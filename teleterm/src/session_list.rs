@@ -1,64 +1,319 @@
+use crate::prelude::*;
+
+// these keys are bound to other commands in the session list, so they
+// can't be used to choose a stream
+const RESERVED_CHARS: &[char] = &['q', 's'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Idle,
+    Username,
+    Watchers,
+    StartTime,
+}
+
+impl SortOrder {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Idle => Self::Username,
+            Self::Username => Self::Watchers,
+            Self::Watchers => Self::StartTime,
+            Self::StartTime => Self::Idle,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Idle => "idle time",
+            Self::Username => "name",
+            Self::Watchers => "watchers",
+            Self::StartTime => "start time",
+        }
+    }
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        Self::Idle
+    }
+}
+
+// the columns which can be shown between the key used to choose a session
+// and its title
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Name,
+    Size,
+    Idle,
+    Watchers,
+    Uptime,
+    Started,
+}
+
+impl Column {
+    pub fn header(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Size => "size",
+            Self::Idle => "idle",
+            Self::Watchers => "watch",
+            Self::Uptime => "uptime",
+            Self::Started => "started",
+        }
+    }
+}
+
+impl std::convert::TryFrom<&str> for Column {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        match s {
+            "name" => Ok(Self::Name),
+            "size" => Ok(Self::Size),
+            "idle" => Ok(Self::Idle),
+            "watch" => Ok(Self::Watchers),
+            "uptime" => Ok(Self::Uptime),
+            "started" => Ok(Self::Started),
+            _ => Err(Error::InvalidSessionListColumn {
+                column: s.to_string(),
+            }),
+        }
+    }
+}
+
+// what the server allows, as of the last time it told us, so that people
+// can see why they might be refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub connections: u32,
+    pub max_connections: Option<u32>,
+    pub max_watchers: Option<u32>,
+}
+
+// a group of streams, as shown in the list of rooms that watchers start
+// from on servers where streamers have joined rooms
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Room {
+    // empty for the streams which haven't joined any room
+    pub name: String,
+    pub sessions: usize,
+    pub watchers: u32,
+}
+
 pub struct SessionList {
     sessions: Vec<crate::protocol::Session>,
+    // only the sessions in this room are listed. if it isn't set and some
+    // of the sessions are in rooms, the list of rooms is shown instead.
+    room: Option<String>,
     offset: usize,
     size: crate::term::Size,
+    sort: SortOrder,
+    limits: Option<Limits>,
+    notice: Option<String>,
+    // ids of the sessions picked to be watched side by side, in the order
+    // they were picked
+    marked: Vec<String>,
 }
 
 impl SessionList {
     pub fn new(
         sessions: Vec<crate::protocol::Session>,
         size: crate::term::Size,
+        sort: SortOrder,
     ) -> Self {
-        let mut by_name = std::collections::HashMap::new();
-        for session in sessions {
-            if !by_name.contains_key(&session.username) {
-                by_name.insert(session.username.clone(), vec![]);
-            }
-            by_name.get_mut(&session.username).unwrap().push(session);
+        Self {
+            sessions: sort_sessions(sessions, sort),
+            room: None,
+            offset: 0,
+            size,
+            sort,
+            limits: None,
+            notice: None,
+            marked: vec![],
         }
-        let mut names: Vec<_> = by_name.keys().cloned().collect();
-        names.sort_by(|a: &String, b: &String| {
-            let a_idle =
-                by_name[a].iter().min_by_key(|session| session.idle_time);
-            let b_idle =
-                by_name[b].iter().min_by_key(|session| session.idle_time);
-            // these unwraps are safe because we know that none of the vecs in
-            // the map can be empty
-            a_idle.unwrap().idle_time.cmp(&b_idle.unwrap().idle_time)
-        });
-        for name in &names {
-            if let Some(sessions) = by_name.get_mut(name) {
-                sessions.sort_by_key(|s| s.idle_time);
-            }
+    }
+
+    pub fn limits(&self) -> Option<Limits> {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Option<Limits>) {
+        self.limits = limits;
+    }
+
+    // the most recent notice from the server, if any
+    pub fn notice(&self) -> Option<&str> {
+        self.notice.as_ref().map(std::string::String::as_str)
+    }
+
+    pub fn set_notice(&mut self, notice: Option<String>) {
+        self.notice = notice;
+    }
+
+    // marks or unmarks the session for the given key, returning whether
+    // there was one
+    pub fn toggle_mark(&mut self, c: char) -> bool {
+        let id = if let Some(id) = self.id_for(c) {
+            id.to_string()
+        } else {
+            return false;
+        };
+        if self.is_marked(&id) {
+            self.marked.retain(|marked| *marked != id);
+        } else {
+            self.marked.push(id);
         }
+        true
+    }
 
-        let mut sorted = vec![];
-        for name in names {
-            let sessions = by_name.remove(&name).unwrap();
-            for session in sessions {
-                sorted.push(session);
+    pub fn is_marked(&self, id: &str) -> bool {
+        self.marked.iter().any(|marked| marked == id)
+    }
+
+    // streams which have ended since being marked are left out
+    pub fn marked(&self) -> Vec<&crate::protocol::Session> {
+        self.marked
+            .iter()
+            .filter_map(|id| self.session(id))
+            .collect()
+    }
+
+    // keeps the marks from a previous list of the same sessions
+    pub fn set_marked(&mut self, marked: Vec<String>) {
+        self.marked = marked;
+    }
+
+    pub fn marked_ids(&self) -> &[String] {
+        &self.marked
+    }
+
+    pub fn room(&self) -> Option<&str> {
+        self.room.as_ref().map(std::string::String::as_str)
+    }
+
+    pub fn set_room(&mut self, room: Option<String>) {
+        self.room = room;
+        self.offset = 0;
+    }
+
+    pub fn browsing_rooms(&self) -> bool {
+        self.room.is_none()
+            && self.sessions.iter().any(|session| !session.room.is_empty())
+    }
+
+    // sorted by name, with the streams which aren't in a room last
+    pub fn rooms(&self) -> Vec<Room> {
+        let mut rooms: Vec<Room> = vec![];
+        for session in &self.sessions {
+            if let Some(room) =
+                rooms.iter_mut().find(|room| room.name == session.room)
+            {
+                room.sessions += 1;
+                room.watchers =
+                    room.watchers.saturating_add(session.watchers);
+            } else {
+                rooms.push(Room {
+                    name: session.room.clone(),
+                    sessions: 1,
+                    watchers: session.watchers,
+                });
             }
         }
+        rooms.sort_by(|a, b| {
+            a.name
+                .is_empty()
+                .cmp(&b.name.is_empty())
+                .then(a.name.cmp(&b.name))
+        });
+        rooms
+    }
+
+    pub fn visible_rooms_with_chars(&self) -> Vec<(char, Room)> {
+        self.rooms()
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit())
+            .enumerate()
+            .map(|(i, room)| (self.idx_to_char(i).unwrap(), room))
+            .collect()
+    }
+
+    pub fn room_for(&self, c: char) -> Option<Room> {
+        self.char_to_idx(c)
+            .and_then(|i| self.rooms().into_iter().nth(i + self.offset))
+    }
+
+    pub fn sort(&self) -> SortOrder {
+        self.sort
+    }
+
+    pub fn set_sort(&mut self, sort: SortOrder) {
+        let sessions = std::mem::replace(&mut self.sessions, vec![]);
+        self.sessions = sort_sessions(sessions, sort);
+        self.sort = sort;
+        self.offset = 0;
+    }
+
+    // replaces the list with a newer one from the server. streams which are
+    // still running keep their place (and so the key used to choose them),
+    // so that the list changing underneath someone who is about to press a
+    // key doesn't start watching the wrong stream. new streams are added at
+    // the end.
+    pub fn update(&mut self, sessions: Vec<crate::protocol::Session>) {
+        let mut by_id: std::collections::HashMap<_, _> = sessions
+            .into_iter()
+            .map(|session| (session.id.clone(), session))
+            .collect();
+        self.sessions = self
+            .sessions
+            .iter()
+            .filter_map(|session| by_id.remove(&session.id))
+            .collect();
+        let added = by_id.into_iter().map(|(_, session)| session).collect();
+        self.sessions.extend(sort_sessions(added, self.sort));
+        self.clamp_offset();
+    }
+
+    // these apply changes pushed by the server, keeping the same ordering
+    // rules as update
+    pub fn add(&mut self, session: crate::protocol::Session) {
+        self.sessions.push(session);
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.sessions.retain(|session| session.id != id);
+        self.clamp_offset();
+    }
+
+    pub fn replace(&mut self, session: crate::protocol::Session) {
+        if let Some(prev) =
+            self.sessions.iter_mut().find(|prev| prev.id == session.id)
+        {
+            *prev = session;
+        }
+    }
 
-        Self {
-            sessions: sorted,
-            offset: 0,
-            size,
+    // idle times and uptimes keep counting up between updates, since the
+    // server only resends them once they have drifted too far
+    pub fn tick(&mut self, secs: u32) {
+        for session in &mut self.sessions {
+            session.idle_time = session.idle_time.saturating_add(secs);
+            session.uptime = session.uptime.saturating_add(secs);
         }
     }
 
-    pub fn visible_sessions(&self) -> &[crate::protocol::Session] {
-        let start = self.offset;
-        let end = self.offset + self.limit();
-        let end = end.min(self.sessions.len());
-        &self.sessions[start..end]
+    pub fn visible_sessions(&self) -> Vec<&crate::protocol::Session> {
+        self.listed_sessions()
+            .skip(self.offset)
+            .take(self.limit())
+            .collect()
     }
 
     pub fn visible_sessions_with_chars(
         &self,
     ) -> impl Iterator<Item = (char, &crate::protocol::Session)> {
         self.visible_sessions()
-            .iter()
+            .into_iter()
             .enumerate()
             .map(move |(i, s)| (self.idx_to_char(i).unwrap(), s))
     }
@@ -71,15 +326,22 @@ impl SessionList {
         self.size = size;
     }
 
+    pub fn session_for(&self, c: char) -> Option<&crate::protocol::Session> {
+        self.char_to_idx(c)
+            .and_then(|i| self.listed_sessions().nth(i + self.offset))
+    }
+
+    pub fn session(&self, id: &str) -> Option<&crate::protocol::Session> {
+        self.sessions.iter().find(|session| session.id == id)
+    }
+
     pub fn id_for(&self, c: char) -> Option<&str> {
-        self.char_to_idx(c).and_then(|i| {
-            self.sessions.get(i + self.offset).map(|s| s.id.as_ref())
-        })
+        self.session_for(c).map(|s| s.id.as_ref())
     }
 
     pub fn next_page(&mut self) {
         let inc = self.limit();
-        if self.offset + inc < self.sessions.len() {
+        if self.offset + inc < self.len() {
             self.offset += inc;
         }
     }
@@ -96,43 +358,42 @@ impl SessionList {
     }
 
     pub fn total_pages(&self) -> usize {
-        if self.sessions.is_empty() {
+        if self.len() == 0 {
             1
         } else {
-            (self.sessions.len() - 1) / self.limit() + 1
+            (self.len() - 1) / self.limit() + 1
         }
     }
 
-    fn idx_to_char(&self, mut i: usize) -> Option<char> {
-        if i >= self.limit() {
-            return None;
-        }
+    // the sessions in the room being browsed
+    fn listed_sessions(
+        &self,
+    ) -> impl Iterator<Item = &crate::protocol::Session> {
+        let room = self.room.as_ref();
+        self.sessions
+            .iter()
+            .filter(move |session| room.map_or(true, |r| session.room == *r))
+    }
 
-        // 'q' shouldn't be a list option, since it is bound to quit
-        if i >= 16 {
-            i += 1;
+    // the number of rows in whichever list is being shown
+    fn len(&self) -> usize {
+        if self.browsing_rooms() {
+            self.rooms().len()
+        } else {
+            self.listed_sessions().count()
         }
-
-        #[allow(clippy::cast_possible_truncation)]
-        Some(std::char::from_u32(('a' as u32) + (i as u32)).unwrap())
     }
 
-    fn char_to_idx(&self, c: char) -> Option<usize> {
-        if c == 'q' {
+    fn idx_to_char(&self, i: usize) -> Option<char> {
+        if i >= self.limit() {
             return None;
         }
 
-        let i = ((c as i32) - ('a' as i32)) as isize;
-        if i < 0 {
-            return None;
-        }
-        #[allow(clippy::cast_sign_loss)]
-        let mut i = i as usize;
+        chars().nth(i)
+    }
 
-        // 'q' shouldn't be a list option, since it is bound to quit
-        if i > 16 {
-            i -= 1;
-        }
+    fn char_to_idx(&self, c: char) -> Option<usize> {
+        let i = chars().position(|choice| choice == c)?;
 
         if i >= self.limit() {
             return None;
@@ -141,19 +402,95 @@ impl SessionList {
         Some(i)
     }
 
+    // the page we were on may not exist anymore
+    fn clamp_offset(&mut self) {
+        while self.offset > 0 && self.offset >= self.len() {
+            self.offset = self.offset.saturating_sub(self.limit());
+        }
+    }
+
     fn limit(&self) -> usize {
         let limit = self.size.rows as usize - 6;
 
-        // enough for a-z except q - if we want to allow more than this, we'll
-        // need to come up with a better way of choosing streams
-        if limit > 25 {
-            25
+        // enough for a-z except the reserved keys - if we want to allow more
+        // than this, we'll need to come up with a better way of choosing
+        // streams
+        let max = 26 - RESERVED_CHARS.len();
+        if limit > max {
+            max
         } else {
             limit
         }
     }
 }
 
+fn chars() -> impl Iterator<Item = char> {
+    (b'a'..=b'z')
+        .map(char::from)
+        .filter(|c| !RESERVED_CHARS.contains(c))
+}
+
+fn sort_sessions(
+    mut sessions: Vec<crate::protocol::Session>,
+    sort: SortOrder,
+) -> Vec<crate::protocol::Session> {
+    match sort {
+        SortOrder::Idle => return sort_sessions_by_idle(sessions),
+        SortOrder::Username => sessions.sort_by(|a, b| {
+            a.username
+                .cmp(&b.username)
+                .then(a.idle_time.cmp(&b.idle_time))
+        }),
+        // most watched first
+        SortOrder::Watchers => sessions.sort_by(|a, b| {
+            b.watchers
+                .cmp(&a.watchers)
+                .then(a.idle_time.cmp(&b.idle_time))
+        }),
+        // newest first
+        SortOrder::StartTime => sessions.sort_by_key(|s| s.uptime),
+    }
+    sessions
+}
+
+// keeps each user's sessions together, ordering users by their most
+// recently active session
+fn sort_sessions_by_idle(
+    sessions: Vec<crate::protocol::Session>,
+) -> Vec<crate::protocol::Session> {
+    let mut by_name = std::collections::HashMap::new();
+    for session in sessions {
+        if !by_name.contains_key(&session.username) {
+            by_name.insert(session.username.clone(), vec![]);
+        }
+        by_name.get_mut(&session.username).unwrap().push(session);
+    }
+    let mut names: Vec<_> = by_name.keys().cloned().collect();
+    names.sort_by(|a: &String, b: &String| {
+        let a_idle =
+            by_name[a].iter().min_by_key(|session| session.idle_time);
+        let b_idle =
+            by_name[b].iter().min_by_key(|session| session.idle_time);
+        // these unwraps are safe because we know that none of the vecs in
+        // the map can be empty
+        a_idle.unwrap().idle_time.cmp(&b_idle.unwrap().idle_time)
+    });
+    for name in &names {
+        if let Some(sessions) = by_name.get_mut(name) {
+            sessions.sort_by_key(|s| s.idle_time);
+        }
+    }
+
+    let mut sorted = vec![];
+    for name in names {
+        let sessions = by_name.remove(&name).unwrap();
+        for session in sessions {
+            sorted.push(session);
+        }
+    }
+    sorted
+}
+
 #[cfg(test)]
 #[allow(clippy::cognitive_complexity)]
 #[allow(clippy::redundant_clone)]
@@ -162,6 +499,15 @@ mod test {
     use super::*;
 
     fn session(username: &str, idle: u32) -> crate::protocol::Session {
+        session_with(username, idle, 0, 0)
+    }
+
+    fn session_with(
+        username: &str,
+        idle: u32,
+        watchers: u32,
+        uptime: u32,
+    ) -> crate::protocol::Session {
         crate::protocol::Session {
             id: format!("{}", uuid::Uuid::new_v4()),
             username: username.to_string(),
@@ -169,7 +515,13 @@ mod test {
             size: crate::term::Size { rows: 24, cols: 80 },
             idle_time: idle,
             title: "title".to_string(),
-            watchers: 0,
+            description: "".to_string(),
+            tags: vec![],
+            room: "".to_string(),
+            paused: false,
+            watchers,
+            uptime,
+            start_time: 1_573_000_000 - uptime,
         }
     }
 
@@ -191,7 +543,8 @@ mod test {
         ];
 
         assert_eq!(
-            SessionList::new(sessions.clone(), size.clone()).sessions,
+            SessionList::new(sessions.clone(), size.clone(), SortOrder::Idle)
+                .sessions,
             vec![
                 session2.clone(),
                 session1.clone(),
@@ -204,7 +557,8 @@ mod test {
         session3.idle_time = 2;
         sessions[2].idle_time = 2;
         assert_eq!(
-            SessionList::new(sessions.clone(), size.clone()).sessions,
+            SessionList::new(sessions.clone(), size.clone(), SortOrder::Idle)
+                .sessions,
             vec![
                 session3.clone(),
                 session4.clone(),
@@ -217,7 +571,8 @@ mod test {
         session5.idle_time = 1;
         sessions[4].idle_time = 1;
         assert_eq!(
-            SessionList::new(sessions.clone(), size.clone()).sessions,
+            SessionList::new(sessions.clone(), size.clone(), SortOrder::Idle)
+                .sessions,
             vec![
                 session5.clone(),
                 session3.clone(),
@@ -244,7 +599,8 @@ mod test {
             session("doy", 9),
             session("doy", 10),
         ];
-        let mut list = SessionList::new(sessions.clone(), size);
+        let mut list =
+            SessionList::new(sessions.clone(), size, SortOrder::Idle);
         assert_eq!(list.limit(), 5);
         assert_eq!(list.total_pages(), 3);
         assert_eq!(list.current_page(), 1);
@@ -321,7 +677,7 @@ mod test {
             session("doy", 20),
             session("doy", 21),
         ];
-        let list = SessionList::new(sessions.clone(), size);
+        let list = SessionList::new(sessions.clone(), size, SortOrder::Idle);
         assert_eq!(list.limit(), 18);
         assert_eq!(list.total_pages(), 2);
         assert_eq!(list.current_page(), 1);
@@ -334,9 +690,276 @@ mod test {
         assert!(id.is_none());
         let id = list.id_for('r').unwrap();
         assert_eq!(id, sessions[16].id);
-        let id = list.id_for('s').unwrap();
+        let id = list.id_for('s');
+        assert!(id.is_none());
+        let id = list.id_for('t').unwrap();
         assert_eq!(id, sessions[17].id);
-        let id = list.id_for('t');
+        let id = list.id_for('u');
         assert!(id.is_none());
     }
+
+    #[test]
+    fn test_session_list_sort_orders() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+
+        let session1 = session_with("doy", 35, 1, 600);
+        let session2 = session_with("toft", 3, 0, 60);
+        let session3 = session_with("sartak", 12, 4, 6000);
+        let session4 = session_with("doy", 100, 4, 30);
+        let sessions = vec![
+            session1.clone(),
+            session2.clone(),
+            session3.clone(),
+            session4.clone(),
+        ];
+
+        let mut list =
+            SessionList::new(sessions.clone(), size, SortOrder::Username);
+        assert_eq!(
+            list.sessions,
+            vec![
+                session1.clone(),
+                session4.clone(),
+                session3.clone(),
+                session2.clone(),
+            ]
+        );
+
+        list.set_sort(list.sort().next());
+        assert_eq!(list.sort(), SortOrder::Watchers);
+        assert_eq!(
+            list.sessions,
+            vec![
+                session3.clone(),
+                session4.clone(),
+                session1.clone(),
+                session2.clone(),
+            ]
+        );
+
+        list.set_sort(list.sort().next());
+        assert_eq!(list.sort(), SortOrder::StartTime);
+        assert_eq!(
+            list.sessions,
+            vec![
+                session4.clone(),
+                session2.clone(),
+                session1.clone(),
+                session3.clone(),
+            ]
+        );
+
+        list.set_sort(list.sort().next());
+        assert_eq!(list.sort(), SortOrder::Idle);
+        assert_eq!(
+            list.sessions,
+            vec![
+                session2.clone(),
+                session3.clone(),
+                session1.clone(),
+                session4.clone(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_session_list_update() {
+        let size = crate::term::Size { rows: 8, cols: 80 };
+
+        let mut session1 = session("doy", 3);
+        let session2 = session("sartak", 12);
+        let session3 = session("toft", 20);
+        let mut list = SessionList::new(
+            vec![session1.clone(), session2.clone(), session3.clone()],
+            size,
+            SortOrder::Idle,
+        );
+        assert_eq!(list.limit(), 2);
+        list.next_page();
+        assert_eq!(list.current_page(), 2);
+
+        // existing streams stay where they were even if they would sort
+        // differently now, and new ones go at the end
+        session1.idle_time = 40;
+        let session4 = session("sartak", 2);
+        let session5 = session("alice", 1);
+        list.update(vec![
+            session4.clone(),
+            session3.clone(),
+            session5.clone(),
+            session1.clone(),
+        ]);
+        assert_eq!(
+            list.sessions,
+            vec![
+                session1.clone(),
+                session3.clone(),
+                session5.clone(),
+                session4.clone(),
+            ]
+        );
+        assert_eq!(list.current_page(), 2);
+        assert_eq!(list.id_for('a').unwrap(), session5.id);
+
+        // and the current page is kept in range
+        list.update(vec![session1.clone()]);
+        assert_eq!(list.sessions, vec![session1.clone()]);
+        assert_eq!(list.current_page(), 1);
+        assert_eq!(list.id_for('a').unwrap(), session1.id);
+    }
+
+    #[test]
+    fn test_session_list_changes() {
+        let size = crate::term::Size { rows: 8, cols: 80 };
+
+        let session1 = session("doy", 3);
+        let mut session2 = session("sartak", 12);
+        let session3 = session("toft", 20);
+        let mut list = SessionList::new(
+            vec![session1.clone(), session2.clone()],
+            size,
+            SortOrder::Idle,
+        );
+
+        list.add(session3.clone());
+        assert_eq!(
+            list.sessions,
+            vec![session1.clone(), session2.clone(), session3.clone()]
+        );
+
+        session2.title = "new title".to_string();
+        list.replace(session2.clone());
+        assert_eq!(
+            list.sessions,
+            vec![session1.clone(), session2.clone(), session3.clone()]
+        );
+
+        list.next_page();
+        assert_eq!(list.current_page(), 2);
+        list.remove(&session3.id);
+        assert_eq!(list.sessions, vec![session1.clone(), session2.clone()]);
+        assert_eq!(list.current_page(), 1);
+    }
+
+    #[test]
+    fn test_session_list_tick() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+
+        let session1 = session_with("doy", 3, 0, 60);
+        let session2 = session_with("sartak", std::u32::MAX - 1, 0, 10);
+        let mut list = SessionList::new(
+            vec![session1.clone(), session2.clone()],
+            size,
+            SortOrder::Username,
+        );
+        list.tick(2);
+        assert_eq!(list.session(&session1.id).unwrap().idle_time, 5);
+        assert_eq!(list.session(&session1.id).unwrap().uptime, 62);
+        assert_eq!(
+            list.session(&session2.id).unwrap().idle_time,
+            std::u32::MAX
+        );
+        assert_eq!(list.session(&session2.id).unwrap().uptime, 12);
+    }
+
+    #[test]
+    fn test_session_list_marks() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let session1 = session("doy", 3);
+        let session2 = session("sartak", 12);
+        let session3 = session("toft", 30);
+        let mut list = SessionList::new(
+            vec![session1.clone(), session2.clone(), session3.clone()],
+            size,
+            SortOrder::Username,
+        );
+        assert!(list.marked().is_empty());
+
+        assert!(list.toggle_mark('c'));
+        assert!(list.toggle_mark('a'));
+        assert!(!list.toggle_mark('z'));
+        assert!(list.is_marked(&session1.id));
+        assert!(!list.is_marked(&session2.id));
+        assert_eq!(
+            list.marked()
+                .iter()
+                .map(|session| &session.id)
+                .collect::<Vec<_>>(),
+            vec![&session3.id, &session1.id]
+        );
+
+        assert!(list.toggle_mark('c'));
+        assert_eq!(list.marked().len(), 1);
+
+        list.remove(&session1.id);
+        assert!(list.marked().is_empty());
+        assert_eq!(list.marked_ids(), &[session1.id.clone()]);
+    }
+
+    #[test]
+    fn test_session_list_rooms() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let session1 = session_with("doy", 3, 2, 0);
+        let mut session2 = session_with("sartak", 12, 1, 0);
+        session2.room = "rustconf".to_string();
+        let mut session3 = session_with("toft", 30, 4, 0);
+        session3.room = "rustconf".to_string();
+        let mut session4 = session_with("alice", 1, 0, 0);
+        session4.room = "bugs".to_string();
+
+        // servers which don't use rooms just list everything
+        let mut list = SessionList::new(
+            vec![session1.clone()],
+            size,
+            SortOrder::Username,
+        );
+        assert!(!list.browsing_rooms());
+        assert_eq!(list.id_for('a').unwrap(), session1.id);
+
+        list.update(vec![
+            session1.clone(),
+            session2.clone(),
+            session3.clone(),
+            session4.clone(),
+        ]);
+        assert!(list.browsing_rooms());
+        assert_eq!(
+            list.rooms(),
+            vec![
+                Room {
+                    name: "bugs".to_string(),
+                    sessions: 1,
+                    watchers: 0,
+                },
+                Room {
+                    name: "rustconf".to_string(),
+                    sessions: 2,
+                    watchers: 5,
+                },
+                Room {
+                    name: "".to_string(),
+                    sessions: 1,
+                    watchers: 2,
+                },
+            ]
+        );
+        assert_eq!(list.total_pages(), 1);
+        assert_eq!(list.room_for('b').unwrap().name, "rustconf");
+        assert!(list.room_for('d').is_none());
+
+        list.set_room(Some("rustconf".to_string()));
+        assert!(!list.browsing_rooms());
+        assert_eq!(list.room(), Some("rustconf"));
+        assert_eq!(list.visible_sessions().len(), 2);
+        assert_eq!(list.id_for('a').unwrap(), session2.id);
+        assert_eq!(list.id_for('b').unwrap(), session3.id);
+        assert!(list.id_for('c').is_none());
+
+        list.set_room(Some("".to_string()));
+        assert_eq!(list.id_for('a').unwrap(), session1.id);
+        assert!(list.id_for('b').is_none());
+
+        list.set_room(None);
+        assert!(list.browsing_rooms());
+    }
 }
@@ -0,0 +1,139 @@
+use crate::prelude::*;
+
+const FLASH_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+
+// a single line of text drawn in reverse video over the top row of the
+// terminal, without disturbing the cursor position
+pub struct StatusLine {
+    text: Option<String>,
+    timer: Option<tokio::timer::Delay>,
+}
+
+impl StatusLine {
+    pub fn new() -> Self {
+        Self {
+            text: None,
+            timer: None,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.text.is_some()
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_ref().map(std::string::String::as_str)
+    }
+
+    pub fn expires(&self) -> bool {
+        self.timer.is_some()
+    }
+
+    // displays text until it is explicitly hidden
+    pub fn set(&mut self, text: &str) {
+        self.text = Some(text.to_string());
+        self.timer = None;
+    }
+
+    // displays text for a few seconds
+    pub fn flash(&mut self, text: &str) {
+        self.text = Some(text.to_string());
+        self.timer = Some(tokio::timer::Delay::new(
+            std::time::Instant::now() + FLASH_DURATION,
+        ));
+    }
+
+    pub fn hide(&mut self) {
+        self.text = None;
+        self.timer = None;
+    }
+
+    pub fn draw(&self, cols: u16) -> Vec<u8> {
+        if let Some(text) = &self.text {
+            // the text may have come from another user, so don't let it
+            // send arbitrary escape sequences to our terminal. wide
+            // characters take up two columns, so the line has to be cut
+            // off by width rather than by number of characters.
+            let cols = usize::from(cols);
+            let mut line = String::new();
+            let mut width = 0;
+            for c in text.chars().filter(|c| !c.is_control()) {
+                let c_width =
+                    unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+                if width + c_width > cols {
+                    break;
+                }
+                line.push(c);
+                width += c_width;
+            }
+            let padding = cols - width;
+            format!(
+                "\x1b7\x1b[1;1H\x1b[7m{}{}\x1b[m\x1b8",
+                line,
+                " ".repeat(padding)
+            )
+            .into_bytes()
+        } else {
+            vec![]
+        }
+    }
+
+    // resolves when a flashed message should be removed from the screen
+    pub fn poll_expire(&mut self) -> futures::Poll<(), Error> {
+        if let Some(timer) = &mut self.timer {
+            futures::try_ready!(timer
+                .poll()
+                .context(crate::error::TimerStatusLine));
+            self.hide();
+        }
+        Ok(futures::Async::Ready(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_draw() {
+        let mut status_line = StatusLine::new();
+        assert_eq!(status_line.draw(10), b"");
+
+        status_line.set("hello");
+        assert_eq!(
+            status_line.draw(10),
+            &b"\x1b7\x1b[1;1H\x1b[7mhello     \x1b[m\x1b8"[..]
+        );
+
+        status_line.set("hello world");
+        assert_eq!(
+            status_line.draw(5),
+            &b"\x1b7\x1b[1;1H\x1b[7mhello\x1b[m\x1b8"[..]
+        );
+
+        status_line.set("a\x1b[2Jb\r\n");
+        assert_eq!(
+            status_line.draw(6),
+            &b"\x1b7\x1b[1;1H\x1b[7ma[2Jb \x1b[m\x1b8"[..]
+        );
+
+        status_line.set("\u{4f60}\u{597d}\u{4e16}\u{754c}");
+        assert_eq!(
+            status_line.draw(5),
+            "\x1b7\x1b[1;1H\x1b[7m\u{4f60}\u{597d} \x1b[m\x1b8".as_bytes()
+        );
+
+        status_line.set("hi \u{1f600}!");
+        assert_eq!(
+            status_line.draw(8),
+            "\x1b7\x1b[1;1H\x1b[7mhi \u{1f600}!  \x1b[m\x1b8".as_bytes()
+        );
+        assert_eq!(
+            status_line.draw(4),
+            "\x1b7\x1b[1;1H\x1b[7mhi  \x1b[m\x1b8".as_bytes()
+        );
+
+        status_line.hide();
+        assert_eq!(status_line.draw(10), b"");
+    }
+}
@@ -0,0 +1,455 @@
+use crate::prelude::*;
+use std::io::{Read as _, Seek as _};
+use std::os::unix::fs::MetadataExt as _;
+use tokio::io::AsyncRead as _;
+
+const READ_BUFFER_SIZE: usize = 4 * 1024;
+
+// like tail(1), start by showing the last few lines of the file
+const INITIAL_LINES: usize = 10;
+const INITIAL_BYTES: u64 = 8 * 1024;
+
+// inotify lets us see new data as soon as it is written, but we also check
+// the file periodically, both as a fallback for when inotify isn't available
+// and to notice when the file is replaced (by log rotation, for instance)
+const POLL_INTERVAL: std::time::Duration =
+    std::time::Duration::from_millis(500);
+
+struct Inotify {
+    fd: std::os::unix::io::RawFd,
+}
+
+impl Inotify {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn new() -> std::io::Result<Self> {
+        let fd = unsafe {
+            libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC)
+        };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self { fd })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn new() -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "inotify is not supported on this platform",
+        ))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn watch(&self, filename: &str) -> std::io::Result<()> {
+        let filename = std::ffi::CString::new(filename).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+        })?;
+        let wd = unsafe {
+            libc::inotify_add_watch(
+                self.fd,
+                filename.as_ptr(),
+                libc::IN_MODIFY
+                    | libc::IN_ATTRIB
+                    | libc::IN_MOVE_SELF
+                    | libc::IN_DELETE_SELF,
+            )
+        };
+        if wd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn watch(&self, _filename: &str) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+impl std::io::Read for Inotify {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = unsafe {
+            libc::read(
+                self.fd,
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+            )
+        };
+        if n == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // n is nonnegative and at most buf.len()
+        #[allow(clippy::cast_sign_loss)]
+        let n = n as usize;
+        Ok(n)
+    }
+}
+
+impl mio::Evented for Inotify {
+    fn register(
+        &self,
+        poll: &mio::Poll,
+        token: mio::Token,
+        interest: mio::Ready,
+        opts: mio::PollOpt,
+    ) -> std::io::Result<()> {
+        mio::unix::EventedFd(&self.fd).register(poll, token, interest, opts)
+    }
+
+    fn reregister(
+        &self,
+        poll: &mio::Poll,
+        token: mio::Token,
+        interest: mio::Ready,
+        opts: mio::PollOpt,
+    ) -> std::io::Result<()> {
+        mio::unix::EventedFd(&self.fd).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &mio::Poll) -> std::io::Result<()> {
+        mio::unix::EventedFd(&self.fd).deregister(poll)
+    }
+}
+
+// this produces the same events as tokio_pty_process_stream::Process, so
+// that a file can be used as a drop-in replacement for the process input
+// source in tt stream. data appended to the file is emitted as it shows up,
+// with lines matching any of the configured patterns colorized.
+pub struct Tail {
+    filename: String,
+    colors: Vec<crate::config::TailColor>,
+    file: Option<std::fs::File>,
+    ino: u64,
+
+    notify: Option<tokio::reactor::PollEvented2<Inotify>>,
+    timer: tokio::timer::Interval,
+    needs_read: bool,
+
+    resizer: Box<
+        dyn futures::Stream<Item = (u16, u16), Error = crate::error::Error>
+            + Send,
+    >,
+
+    read_buf: [u8; READ_BUFFER_SIZE],
+    line: Vec<u8>,
+}
+
+impl Tail {
+    pub fn new(filename: &str, colors: &[crate::config::TailColor]) -> Self {
+        let notify = match Inotify::new() {
+            Ok(notify) => Some(tokio::reactor::PollEvented2::new(notify)),
+            Err(e) => {
+                log::debug!(
+                    "couldn't initialize inotify ({}), polling {} instead",
+                    e,
+                    filename
+                );
+                None
+            }
+        };
+
+        Self {
+            filename: filename.to_string(),
+            colors: colors.to_vec(),
+            file: None,
+            ino: 0,
+
+            notify,
+            timer: tokio::timer::Interval::new_interval(POLL_INTERVAL),
+            needs_read: true,
+
+            resizer: Box::new(
+                tokio_terminal_resize::resizes()
+                    .flatten_stream()
+                    .context(crate::error::Resize),
+            ),
+
+            read_buf: [0; READ_BUFFER_SIZE],
+            line: vec![],
+        }
+    }
+
+    fn open(&mut self) -> Result<std::fs::File> {
+        let file = std::fs::File::open(&self.filename).context(
+            crate::error::OpenFileSync {
+                filename: self.filename.clone(),
+            },
+        )?;
+        self.ino = file.metadata().context(crate::error::ReadFileSync)?.ino();
+        if let Some(notify) = &self.notify {
+            if let Err(e) = notify.get_ref().watch(&self.filename) {
+                log::warn!("couldn't watch {}: {}", self.filename, e);
+            }
+        }
+        Ok(file)
+    }
+
+    // returns true if the file we have open is no longer the file at
+    // self.filename, as long as there is a new file there to switch to
+    fn rotated(&self) -> bool {
+        std::fs::metadata(&self.filename)
+            .map(|metadata| metadata.ino() != self.ino)
+            .unwrap_or(false)
+    }
+
+    // log files use bare newlines, which need to be translated for the
+    // terminal. when there are colorization rules, output is buffered until
+    // a full line is available, since the rules match against whole lines.
+    fn format(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        for &c in data {
+            if c == b'\n' {
+                let line = std::mem::replace(&mut self.line, vec![]);
+                out.extend(colorize(&self.colors, &line));
+                out.extend(b"\r\n");
+            } else if self.colors.is_empty() {
+                out.push(c);
+            } else {
+                self.line.push(c);
+            }
+        }
+        out
+    }
+}
+
+impl Tail {
+    const POLL_FNS:
+        &'static [&'static dyn for<'a> Fn(
+            &'a mut Self,
+        )
+            -> component_future::Poll<
+            Option<tokio_pty_process_stream::Event>,
+            Error,
+        >] = &[
+        &Self::poll_open,
+        &Self::poll_resize,
+        &Self::poll_notify,
+        &Self::poll_timer,
+        &Self::poll_read_file,
+    ];
+
+    fn poll_open(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        if self.file.is_some() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        let mut file = self.open()?;
+        let len = file.metadata().context(crate::error::ReadFileSync)?.len();
+        let start = len.saturating_sub(INITIAL_BYTES);
+        file.seek(std::io::SeekFrom::Start(start))
+            .context(crate::error::SeekFile)?;
+        let mut buf = vec![];
+        file.read_to_end(&mut buf)
+            .context(crate::error::ReadFileSync)?;
+        let offset = if let Some(offset) = last_lines(&buf, INITIAL_LINES) {
+            offset
+        } else if start == 0 {
+            0
+        } else {
+            // don't start in the middle of a line
+            buf.iter().position(|&c| c == b'\n').map_or(0, |i| i + 1)
+        };
+        file.seek(std::io::SeekFrom::Start(start + offset as u64))
+            .context(crate::error::SeekFile)?;
+        self.file = Some(file);
+
+        Ok(component_future::Async::Ready(Some(
+            tokio_pty_process_stream::Event::CommandStart {
+                cmd: self.filename.clone(),
+                args: vec![],
+            },
+        )))
+    }
+
+    fn poll_resize(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        let (rows, cols) =
+            component_future::try_ready!(self.resizer.poll()).unwrap();
+        Ok(component_future::Async::Ready(Some(
+            tokio_pty_process_stream::Event::Resize { size: (rows, cols) },
+        )))
+    }
+
+    fn poll_notify(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        let notify = if let Some(notify) = &mut self.notify {
+            notify
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        // we don't care what the events actually are, since we check the
+        // state of the file directly whenever anything happens to it
+        let mut buf = [0; READ_BUFFER_SIZE];
+        match notify.poll_read(&mut buf) {
+            Ok(futures::Async::Ready(_)) => {
+                self.needs_read = true;
+                Ok(component_future::Async::DidWork)
+            }
+            Ok(futures::Async::NotReady) => {
+                Ok(component_future::Async::NotReady)
+            }
+            Err(e) => {
+                log::warn!(
+                    "couldn't read inotify events ({}), polling {} instead",
+                    e,
+                    self.filename
+                );
+                self.notify = None;
+                Ok(component_future::Async::DidWork)
+            }
+        }
+    }
+
+    fn poll_timer(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        component_future::try_ready!(self
+            .timer
+            .poll()
+            .context(crate::error::TimerTail));
+        self.needs_read = true;
+        Ok(component_future::Async::DidWork)
+    }
+
+    fn poll_read_file(
+        &mut self,
+    ) -> component_future::Poll<Option<tokio_pty_process_stream::Event>, Error>
+    {
+        if !self.needs_read {
+            return Ok(component_future::Async::NothingToDo);
+        }
+        let file = if let Some(file) = &mut self.file {
+            file
+        } else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+
+        let n = file
+            .read(&mut self.read_buf)
+            .context(crate::error::ReadFileSync)?;
+        if n > 0 {
+            let data = self.read_buf[..n].to_vec();
+            let out = self.format(&data);
+            if out.is_empty() {
+                return Ok(component_future::Async::DidWork);
+            }
+            return Ok(component_future::Async::Ready(Some(
+                tokio_pty_process_stream::Event::Output { data: out },
+            )));
+        }
+
+        // we only check for rotation once we've read everything from the
+        // old file, so that nothing written to it before it was rotated is
+        // lost
+        let pos = file
+            .seek(std::io::SeekFrom::Current(0))
+            .context(crate::error::SeekFile)?;
+        let len = file.metadata().context(crate::error::ReadFileSync)?.len();
+        if len < pos {
+            log::info!(
+                "{} was truncated, reading from the start",
+                self.filename
+            );
+            file.seek(std::io::SeekFrom::Start(0))
+                .context(crate::error::SeekFile)?;
+        } else if self.rotated() {
+            log::info!("{} was replaced, reopening", self.filename);
+            self.file = Some(self.open()?);
+        } else {
+            self.needs_read = false;
+        }
+        Ok(component_future::Async::DidWork)
+    }
+}
+
+#[must_use = "streams do nothing unless polled"]
+impl futures::Stream for Tail {
+    type Item = tokio_pty_process_stream::Event;
+    type Error = Error;
+
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        component_future::poll_stream(self, Self::POLL_FNS)
+    }
+}
+
+fn colorize(colors: &[crate::config::TailColor], line: &[u8]) -> Vec<u8> {
+    if let Some(color) = colors.iter().find(|c| c.pattern.is_match(line)) {
+        let mut out = format!("\x1b[{}m", color.color).into_bytes();
+        out.extend(line);
+        out.extend(b"\x1b[m");
+        out
+    } else {
+        line.to_vec()
+    }
+}
+
+// returns the offset into buf of the start of the last `lines` lines, or
+// None if buf doesn't contain that many lines
+fn last_lines(buf: &[u8], lines: usize) -> Option<usize> {
+    let buf = if buf.last() == Some(&b'\n') {
+        &buf[..buf.len() - 1]
+    } else {
+        buf
+    };
+    buf.iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, c)| **c == b'\n')
+        .nth(lines - 1)
+        .map(|(i, _)| i + 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_last_lines() {
+        assert_eq!(last_lines(b"", 2), None);
+        assert_eq!(last_lines(b"a\nb\n", 2), None);
+        assert_eq!(last_lines(b"a\nb\nc\n", 2), Some(2));
+        assert_eq!(last_lines(b"a\nb\nc", 2), Some(2));
+        assert_eq!(last_lines(b"a\nb\nc\nd", 2), Some(4));
+        assert_eq!(last_lines(b"a\nb\nc\n", 1), Some(4));
+    }
+
+    #[test]
+    fn test_colorize() {
+        let colors = vec![
+            crate::config::TailColor {
+                pattern: regex::bytes::Regex::new("ERROR").unwrap(),
+                color: 31,
+            },
+            crate::config::TailColor {
+                pattern: regex::bytes::Regex::new("WARN|ERROR").unwrap(),
+                color: 33,
+            },
+        ];
+        assert_eq!(colorize(&colors, b"INFO: ok"), b"INFO: ok".to_vec());
+        assert_eq!(
+            colorize(&colors, b"WARN: hmm"),
+            b"\x1b[33mWARN: hmm\x1b[m".to_vec()
+        );
+        assert_eq!(
+            colorize(&colors, b"ERROR: bad"),
+            b"\x1b[31mERROR: bad\x1b[m".to_vec()
+        );
+    }
+}
@@ -23,3 +23,174 @@ impl std::fmt::Display for Size {
         std::fmt::Display::fmt(&format!("{}x{}", self.cols, self.rows), f)
     }
 }
+
+// what the local terminal is able to display, so that the parts of the ui
+// which we draw ourselves can fall back to plain ascii and no colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Caps {
+    pub unicode: bool,
+    pub color: bool,
+}
+
+impl Caps {
+    pub fn detect() -> Self {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|val| !val.is_empty());
+        Self::from_env(
+            std::env::var("TERM")
+                .ok()
+                .as_ref()
+                .map(std::string::String::as_str),
+            locale.as_ref().map(std::string::String::as_str),
+            // see https://no-color.org/
+            std::env::var_os("NO_COLOR").is_some(),
+        )
+    }
+
+    fn from_env(
+        term: Option<&str>,
+        locale: Option<&str>,
+        no_color: bool,
+    ) -> Self {
+        let dumb =
+            term.map_or(true, |term| term.is_empty() || term == "dumb");
+        let utf8 = locale.map_or(false, |locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        });
+        Self {
+            unicode: !dumb && utf8,
+            color: !dumb && !no_color,
+        }
+    }
+
+    pub fn border(self) -> Border {
+        if self.unicode {
+            Border {
+                top_left: "╭",
+                top_right: "╮",
+                bottom_left: "╰",
+                bottom_right: "╯",
+                horizontal: "─",
+                vertical: "│",
+            }
+        } else {
+            Border {
+                top_left: "+",
+                top_right: "+",
+                bottom_left: "+",
+                bottom_right: "+",
+                horizontal: "-",
+                vertical: "|",
+            }
+        }
+    }
+
+    // reverse video stands out without needing any colors
+    pub fn overlay_style(self) -> &'static str {
+        if self.color {
+            "\x1b[37;44m"
+        } else {
+            "\x1b[7m"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Border {
+    pub top_left: &'static str,
+    pub top_right: &'static str,
+    pub bottom_left: &'static str,
+    pub bottom_right: &'static str,
+    pub horizontal: &'static str,
+    pub vertical: &'static str,
+}
+
+impl Border {
+    pub fn top(self, width: usize) -> String {
+        format!(
+            "{}{}{}",
+            self.top_left,
+            self.horizontal.repeat(width),
+            self.top_right
+        )
+    }
+
+    pub fn bottom(self, width: usize) -> String {
+        format!(
+            "{}{}{}",
+            self.bottom_left,
+            self.horizontal.repeat(width),
+            self.bottom_right
+        )
+    }
+
+    // pads text to fill the inside of a box which is width + 4 columns wide
+    pub fn line(self, text: &str, width: usize) -> String {
+        format!("{0} {1:2$} {0}", self.vertical, text, width)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_caps() {
+        let full = Caps {
+            unicode: true,
+            color: true,
+        };
+        assert_eq!(
+            Caps::from_env(Some("xterm"), Some("en_US.UTF-8"), false),
+            full
+        );
+        assert_eq!(
+            Caps::from_env(Some("screen"), Some("en_US.utf8"), false),
+            full
+        );
+        assert_eq!(
+            Caps::from_env(Some("xterm"), Some("C"), false),
+            Caps {
+                unicode: false,
+                color: true,
+            }
+        );
+        assert_eq!(
+            Caps::from_env(Some("xterm"), None, true),
+            Caps {
+                unicode: false,
+                color: false,
+            }
+        );
+        assert_eq!(
+            Caps::from_env(Some("dumb"), Some("en_US.UTF-8"), false),
+            Caps {
+                unicode: false,
+                color: false,
+            }
+        );
+        assert_eq!(
+            Caps::from_env(None, Some("en_US.UTF-8"), false),
+            Caps {
+                unicode: false,
+                color: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_border() {
+        let border = Caps {
+            unicode: false,
+            color: false,
+        }
+        .border();
+        assert_eq!(border.top(4), "+----+");
+        assert_eq!(border.line("ab", 2), "| ab |");
+        assert_eq!(border.line("ab", 4), "| ab   |");
+        assert_eq!(border.bottom(4), "+----+");
+    }
+}
@@ -0,0 +1,169 @@
+use std::convert::TryFrom as _;
+
+// number of events to remember for the session being watched
+const MAX_EVENTS: usize = 200;
+
+const WIDTH: u16 = 40;
+
+// a list of notable things that happened while watching a session, drawn as
+// a pane along the right side of the terminal (below the status line) so
+// that watchers can see what they might have missed
+pub struct Timeline {
+    started: std::time::Instant,
+    events: std::collections::VecDeque<(u32, String)>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self {
+            started: std::time::Instant::now(),
+            events: std::collections::VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, text: &str) {
+        let secs = u32::try_from(self.started.elapsed().as_secs())
+            .unwrap_or(std::u32::MAX);
+        self.events.push_back((secs, text.to_string()));
+        while self.events.len() > MAX_EVENTS {
+            self.events.pop_front();
+        }
+    }
+
+    pub fn record_message(&mut self, msg: &crate::protocol::Message) {
+        let text = match msg {
+            crate::protocol::Message::Resize { size } => {
+                format!("resized to {}x{}", size.cols, size.rows)
+            }
+            crate::protocol::Message::Chat { from, text } => {
+                format!("{}: {}", from, text)
+            }
+            crate::protocol::Message::GrantControl { .. } => {
+                "you were given control".to_string()
+            }
+            crate::protocol::Message::RevokeControl { .. } => {
+                "control was revoked".to_string()
+            }
+            crate::protocol::Message::SessionChanged {
+                title,
+                tags,
+                paused,
+            } => crate::ui::session_changed(
+                title.as_ref().map(std::string::String::as_str),
+                tags.as_ref().map(std::vec::Vec::as_slice),
+                *paused,
+            ),
+            _ => return,
+        };
+        self.record(&text);
+    }
+
+    pub fn draw(&self, size: crate::term::Size) -> Vec<u8> {
+        let width = WIDTH.min(size.cols);
+        // leave the top row for the status line
+        let height = usize::from(size.rows.saturating_sub(1));
+        if width == 0 || height == 0 {
+            return vec![];
+        }
+        let col = size.cols - width + 1;
+
+        let shown = self.events.len().min(height - 1);
+        let lines = std::iter::once("timeline (t to close)".to_string())
+            .chain(self.events.iter().skip(self.events.len() - shown).map(
+                |(secs, text)| {
+                    format!("{} {}", format_timestamp(*secs), text)
+                },
+            ));
+
+        let mut out = "\x1b7".to_string();
+        for (i, line) in lines.enumerate() {
+            // chat messages come from other users, so don't let them send
+            // arbitrary escape sequences to our terminal
+            let line: String = line
+                .chars()
+                .filter(|c| !c.is_control())
+                .take(width as usize)
+                .collect();
+            let padding = width as usize - line.chars().count();
+            out.push_str(&format!(
+                "\x1b[{};{}H\x1b[7m{}{}\x1b[m",
+                i + 2,
+                col,
+                line,
+                " ".repeat(padding)
+            ));
+        }
+        out.push_str("\x1b8");
+        out.into_bytes()
+    }
+}
+
+fn format_timestamp(secs: u32) -> String {
+    let hours = secs / 3600;
+    let mins = secs / 60 % 60;
+    let secs = secs % 60;
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, mins, secs)
+    } else {
+        format!("{:02}:{:02}", mins, secs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_draw() {
+        let mut timeline = Timeline::new();
+        let size = crate::term::Size { rows: 4, cols: 30 };
+        assert_eq!(
+            String::from_utf8(timeline.draw(size)).unwrap(),
+            concat!(
+                "\x1b7",
+                "\x1b[2;1H\x1b[7mtimeline (t to close)         \x1b[m",
+                "\x1b8",
+            )
+        );
+
+        timeline.record("started watching");
+        timeline.record_message(&crate::protocol::Message::resize(
+            crate::term::Size { rows: 24, cols: 80 },
+        ));
+        timeline.record_message(&crate::protocol::Message::chat(
+            "doy",
+            "hi\x1b[2J",
+        ));
+        timeline.record_message(&crate::protocol::Message::heartbeat());
+        assert_eq!(
+            String::from_utf8(timeline.draw(size)).unwrap(),
+            concat!(
+                "\x1b7",
+                "\x1b[2;1H\x1b[7mtimeline (t to close)         \x1b[m",
+                "\x1b[3;1H\x1b[7m00:00 resized to 80x24        \x1b[m",
+                "\x1b[4;1H\x1b[7m00:00 doy: hi[2J              \x1b[m",
+                "\x1b8",
+            )
+        );
+
+        let size = crate::term::Size { rows: 2, cols: 60 };
+        assert_eq!(
+            String::from_utf8(timeline.draw(size)).unwrap(),
+            concat!(
+                "\x1b7",
+                "\x1b[2;21H\x1b[7mtimeline (t to close)                   ",
+                "\x1b[m\x1b8",
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "00:00");
+        assert_eq!(format_timestamp(59), "00:59");
+        assert_eq!(format_timestamp(61), "01:01");
+        assert_eq!(format_timestamp(3599), "59:59");
+        assert_eq!(format_timestamp(3600), "1:00:00");
+        assert_eq!(format_timestamp(36061), "10:01:01");
+    }
+}
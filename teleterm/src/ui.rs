@@ -0,0 +1,1547 @@
+// rendering for the screens shown by `tt watch`. everything in here is a pure
+// function of the state being displayed, so that changes to the layout can be
+// tested without a terminal or a server. screens are returned as a list of
+// lines (which may contain color codes), the last of which is a prompt that
+// the cursor should be left at the end of.
+
+use std::convert::TryFrom as _;
+
+pub fn loading(last_error: Option<&str>) -> Vec<String> {
+    let mut lines = vec!["loading...".to_string()];
+    if let Some(err) = last_error {
+        lines.push(format!("error: {}", err));
+    }
+    lines.push("q: quit --> ".to_string());
+    lines
+}
+
+// streams which have produced output more recently than this (in seconds)
+// are highlighted in the menu
+const ACTIVE_IDLE_TIME: u32 = 5;
+
+pub fn choosing(
+    sessions: &crate::session_list::SessionList,
+    columns: &[crate::session_list::Column],
+    color: bool,
+) -> Vec<String> {
+    let char_width = 2;
+
+    let widths: Vec<_> = columns
+        .iter()
+        .map(|column| column_width(*column, sessions))
+        .collect();
+
+    let max_title_width = (sessions.size().cols as usize)
+        .saturating_sub(char_width + 3)
+        .saturating_sub(widths.iter().map(|width| width + 3).sum());
+
+    let welcome = match sessions.limits() {
+        Some(crate::session_list::Limits {
+            connections,
+            max_connections: Some(max_connections),
+            ..
+        }) => format!(
+            "welcome to teleterm ({}/{} connections)",
+            connections, max_connections
+        ),
+        _ => "welcome to teleterm".to_string(),
+    };
+    let max_watchers =
+        sessions.limits().and_then(|limits| limits.max_watchers);
+
+    // the notice takes the place of the blank line, so that it doesn't
+    // change how many sessions fit on the screen
+    let notice_line = sessions.notice().map_or_else(String::new, |text| {
+        truncate(&notice(text), sessions.size().cols as usize)
+    });
+
+    if sessions.browsing_rooms() {
+        return choosing_room(sessions, welcome, notice_line);
+    }
+
+    let heading = if let Some(room) = sessions.room() {
+        format!(
+            "sessions in {} (sorted by {}):",
+            room_name(room),
+            sessions.sort().description()
+        )
+    } else {
+        format!(
+            "available sessions (sorted by {}):",
+            sessions.sort().description()
+        )
+    };
+    let mut lines = vec![welcome, heading, notice_line];
+
+    let mut header = format!("{:1$} | ", "", char_width);
+    let mut separator = "-".repeat(char_width + 1);
+    for (column, width) in columns.iter().zip(&widths) {
+        header.push_str(&format!("{:1$} | ", column.header(), width));
+        separator.push_str(&format!("+{}", "-".repeat(width + 2)));
+    }
+    header.push_str("title");
+    separator.push_str(&format!("+{}", "-".repeat(max_title_width + 1)));
+    lines.push(header);
+    lines.push(separator);
+
+    let mut prev_name: Option<&str> = None;
+    for (c, session) in sessions.visible_sessions_with_chars() {
+        let first = if let Some(name) = prev_name {
+            name != session.username
+        } else {
+            true
+        };
+
+        // marked sessions will be watched side by side
+        let label = if sessions.is_marked(&session.id) {
+            format!("{}*", c)
+        } else {
+            format!("{})", c)
+        };
+        let mut row = format!("{:1$} | ", label, char_width);
+        for (column, width) in columns.iter().zip(&widths) {
+            let (cell, cell_width) = column_cell(
+                *column,
+                session,
+                first,
+                sessions.size(),
+                max_watchers,
+                *width,
+                color,
+            );
+            row.push_str(&cell);
+            row.push_str(&" ".repeat(width.saturating_sub(cell_width)));
+            row.push_str(" | ");
+        }
+        let mut title = if session.tags.is_empty() {
+            session.title.clone()
+        } else {
+            format!("{} [{}]", session.title, session.tags.join(", "))
+        };
+        if session.paused {
+            title = format!("(paused) {}", title);
+        }
+        row.push_str(&truncate(&title, max_title_width));
+        lines.push(row);
+
+        prev_name = Some(&session.username);
+    }
+
+    let marked = sessions.marked().len();
+    let watch_marked = if marked > 0 {
+        format!(", enter: watch {} marked", marked)
+    } else {
+        "".to_string()
+    };
+    let back = if sessions.room().is_some() {
+        ", esc: rooms"
+    } else {
+        ""
+    };
+    lines.push(format!(
+        "({}/{}) space: refresh, s: sort, q: quit, <: prev page, >: next page{}{} --> ",
+        sessions.current_page(),
+        sessions.total_pages(),
+        watch_marked,
+        back,
+    ));
+
+    lines
+}
+
+// servers where streamers have joined rooms list the rooms first, and then
+// the sessions in whichever room is chosen
+fn choosing_room(
+    sessions: &crate::session_list::SessionList,
+    welcome: String,
+    notice_line: String,
+) -> Vec<String> {
+    let char_width = 2;
+    let max_name_width =
+        (sessions.size().cols as usize).saturating_sub(char_width + 24);
+
+    let mut lines = vec![
+        welcome,
+        "available rooms:".to_string(),
+        notice_line,
+        format!("{:1$} | streams | watch | room", "", char_width),
+        format!(
+            "{}+---------+-------+{}",
+            "-".repeat(char_width + 1),
+            "-".repeat(max_name_width + 1)
+        ),
+    ];
+    for (c, room) in sessions.visible_rooms_with_chars() {
+        let label = format!("{})", c);
+        lines.push(format!(
+            "{:1$} | {2:7} | {3:5} | {4}",
+            label,
+            char_width,
+            room.sessions,
+            room.watchers,
+            truncate(room_name(&room.name), max_name_width),
+        ));
+    }
+    lines.push(format!(
+        "({}/{}) space: refresh, q: quit, <: prev page, >: next page --> ",
+        sessions.current_page(),
+        sessions.total_pages(),
+    ));
+
+    lines
+}
+
+// the key for the session (or room) shown on the given line of the menu
+// (counting from 1, as mouse events do), if there is one there. both menus
+// have the same five lines above the list.
+pub fn choice_at(
+    sessions: &crate::session_list::SessionList,
+    row: u16,
+) -> Option<char> {
+    let idx = (row as usize).checked_sub(6)?;
+    if sessions.browsing_rooms() {
+        sessions
+            .visible_rooms_with_chars()
+            .get(idx)
+            .map(|(c, _)| *c)
+    } else {
+        sessions
+            .visible_sessions_with_chars()
+            .nth(idx)
+            .map(|(c, _)| c)
+    }
+}
+
+fn room_name(name: &str) -> &str {
+    if name.is_empty() {
+        "(no room)"
+    } else {
+        name
+    }
+}
+
+// where each of count streams goes when watching them side by side, as
+// (row, col, rows, cols). the tiles are laid out in a grid which is as
+// close to square as possible, with a blank column between neighbors.
+pub fn tile_layout(
+    count: usize,
+    size: crate::term::Size,
+) -> Vec<(u16, u16, u16, u16)> {
+    if count == 0 {
+        return vec![];
+    }
+    let mut grid_cols = 1;
+    while grid_cols * grid_cols < count {
+        grid_cols += 1;
+    }
+    let grid_rows = (count + grid_cols - 1) / grid_cols;
+
+    let grid_cols = u16::try_from(grid_cols).unwrap_or(std::u16::MAX);
+    let grid_rows = u16::try_from(grid_rows).unwrap_or(std::u16::MAX);
+    let cols = size.cols.saturating_sub(grid_cols - 1) / grid_cols;
+    let rows = size.rows / grid_rows;
+
+    (0..count)
+        .map(|i| {
+            let i = u16::try_from(i).unwrap_or(std::u16::MAX);
+            let row = (i / grid_cols) * rows;
+            let col = (i % grid_cols) * (cols + 1);
+            (row, col, rows, cols)
+        })
+        .collect()
+}
+
+// draws one of the streams being watched side by side, with a label on its
+// first row (highlighted for the focused stream) and as much of the top
+// left of the stream's screen as fits below it. colors and other
+// attributes are dropped, since they can't be cropped reliably.
+pub fn tile(
+    label: &str,
+    screen: &vt100::Screen,
+    (row, col, rows, cols): (u16, u16, u16, u16),
+    focused: bool,
+) -> Vec<u8> {
+    let width = usize::from(cols);
+    let fit = |line: &str| -> String {
+        // labels come from other users, and so can contain anything
+        let line: String = line
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(width)
+            .collect();
+        let padding = width.saturating_sub(line.chars().count());
+        format!("{}{}", line, " ".repeat(padding))
+    };
+
+    let mut out = format!(
+        "\x1b[{};{}H{}{}\x1b[m",
+        row + 1,
+        col + 1,
+        if focused { "\x1b[7m" } else { "\x1b[4m" },
+        fit(label)
+    );
+    let mut lines = screen.rows(0, cols);
+    for i in 1..rows {
+        let line = lines.next().unwrap_or_default();
+        out.push_str(&format!(
+            "\x1b[{};{}H{}",
+            row + i + 1,
+            col + 1,
+            fit(&line)
+        ));
+    }
+    out.into_bytes()
+}
+
+// now is in seconds since the unix epoch
+pub fn history(
+    sessions: &[crate::protocol::PastSession],
+    now: u64,
+) -> Vec<String> {
+    if sessions.is_empty() {
+        return vec!["no streams have ended recently".to_string()];
+    }
+
+    let ended: Vec<_> = sessions
+        .iter()
+        .map(|s| {
+            let ago = now.saturating_sub(u64::from(s.end_time));
+            #[allow(clippy::cast_possible_truncation)]
+            let ago = ago.min(u64::from(std::u32::MAX)) as u32;
+            format!("{} ago", format_time(ago))
+        })
+        .collect();
+
+    let name_width = sessions
+        .iter()
+        .map(|s| s.username.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let started_width = 16;
+    let ended_width = ended.iter().map(String::len).max().unwrap_or(0).max(5);
+    let duration_width = sessions
+        .iter()
+        .map(|s| format_time(s.duration()).len())
+        .max()
+        .unwrap_or(0)
+        .max(8);
+    let bytes_width = sessions
+        .iter()
+        .map(|s| format_bytes(s.bytes).len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+
+    let mut lines = vec![
+        "recently ended streams:".to_string(),
+        "".to_string(),
+        format!(
+            "{:5$} | {:6$} | {:7$} | {:8$} | {:9$} | title",
+            "name",
+            "started",
+            "ended",
+            "duration",
+            "bytes",
+            name_width,
+            started_width,
+            ended_width,
+            duration_width,
+            bytes_width,
+        ),
+        format!(
+            "{}+{}+{}+{}+{}+{}",
+            "-".repeat(name_width + 1),
+            "-".repeat(started_width + 2),
+            "-".repeat(ended_width + 2),
+            "-".repeat(duration_width + 2),
+            "-".repeat(bytes_width + 2),
+            "-".repeat(7),
+        ),
+    ];
+    for (session, ended) in sessions.iter().zip(ended.iter()) {
+        let title = if session.description.is_empty() {
+            session.title.clone()
+        } else if session.title.is_empty() {
+            session.description.clone()
+        } else {
+            format!("{} ({})", session.title, session.description)
+        };
+        lines.push(format!(
+            "{:6$} | {:7$} | {:8$} | {:>9$} | {:>10$} | {}",
+            session.username,
+            format_local_time(session.start_time),
+            ended,
+            format_time(session.duration()),
+            format_bytes(session.bytes),
+            title,
+            name_width,
+            started_width,
+            ended_width,
+            duration_width,
+            bytes_width,
+        ));
+    }
+    lines
+}
+
+// the output of `tt admin list`
+pub fn admin_connections(
+    connections: &[crate::protocol::AdminConnection],
+) -> Vec<String> {
+    let id_width = connections
+        .iter()
+        .map(|c| c.id.len())
+        .max()
+        .unwrap_or(0)
+        .max(2);
+    let address_width = connections
+        .iter()
+        .map(|c| c.address.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+    let name_width = connections
+        .iter()
+        .map(|c| c.username.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let state_width = connections
+        .iter()
+        .map(|c| c.state.len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let connected_width = connections
+        .iter()
+        .map(|c| format_time(c.connected).len())
+        .max()
+        .unwrap_or(0)
+        .max(9);
+
+    let mut lines = vec![
+        format!(
+            "{:5$} | {:6$} | {:7$} | {:8$} | {:9$} | watching",
+            "id",
+            "address",
+            "name",
+            "state",
+            "connected",
+            id_width,
+            address_width,
+            name_width,
+            state_width,
+            connected_width,
+        ),
+        format!(
+            "{}+{}+{}+{}+{}+{}",
+            "-".repeat(id_width + 1),
+            "-".repeat(address_width + 2),
+            "-".repeat(name_width + 2),
+            "-".repeat(state_width + 2),
+            "-".repeat(connected_width + 2),
+            "-".repeat(10),
+        ),
+    ];
+    for c in connections {
+        lines.push(format!(
+            "{:6$} | {:7$} | {:8$} | {:9$} | {:>10$} | {}",
+            c.id,
+            c.address,
+            c.username,
+            c.state,
+            format_time(c.connected),
+            c.watch_id,
+            id_width,
+            address_width,
+            name_width,
+            state_width,
+            connected_width,
+        ));
+    }
+    lines
+}
+
+// the output of `tt admin stats`
+pub fn admin_stats(stats: &crate::protocol::ServerStats) -> Vec<String> {
+    vec![
+        format!("uptime:            {}", format_time(stats.uptime)),
+        format!("connections:       {}", stats.connections),
+        format!("streams:           {}", stats.streams),
+        format!("watchers:          {}", stats.watchers),
+        format!("total connections: {}", stats.total_connections),
+        format!("total streams:     {}", stats.total_streams),
+    ]
+}
+
+// redraws everything on the screen while watching a stream. the overlays
+// are drawn on top of the watched terminal, and restore the cursor
+// afterwards.
+// screen is the watched terminal, as drawn by the renderer
+pub fn watching(
+    screen: &[u8],
+    status_line: &crate::status_line::StatusLine,
+    timeline: Option<&crate::timeline::Timeline>,
+    size: crate::term::Size,
+) -> Vec<u8> {
+    let mut data = screen.to_vec();
+    if let Some(timeline) = timeline {
+        data.extend(timeline.draw(size));
+    }
+    data.extend(status_line.draw(size.cols));
+    data
+}
+
+// a box showing details about the stream and listing what each key does,
+// drawn over the top left corner of the watched terminal (below the status
+// line)
+pub fn help(
+    info: &[String],
+    bindings: &[(String, &str)],
+    size: crate::term::Size,
+) -> Vec<u8> {
+    let key_width = bindings
+        .iter()
+        .map(|(key, _)| key.chars().count())
+        .max()
+        .unwrap_or(0);
+    let title = "press any key to close".to_string();
+    let separator = if info.is_empty() {
+        None
+    } else {
+        Some("".to_string())
+    };
+    let lines: Vec<String> = std::iter::once(title)
+        .chain(info.iter().cloned())
+        .chain(separator)
+        .chain(bindings.iter().map(|(key, action)| {
+            format!("{:1$}  {2}", key, key_width, action)
+        }))
+        // titles come from other users, so don't let them send arbitrary
+        // escape sequences to our terminal
+        .map(|line| line.chars().filter(|c| !c.is_control()).collect())
+        .collect();
+
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(usize::from(size.cols).saturating_sub(2));
+    let height = usize::from(size.rows.saturating_sub(1));
+
+    let mut out = "\x1b7".to_string();
+    for (i, line) in lines.iter().take(height).enumerate() {
+        let line: String = line.chars().take(width).collect();
+        let padding = width - line.chars().count();
+        out.push_str(&format!(
+            "\x1b[{};1H\x1b[7m {}{} \x1b[m",
+            i + 2,
+            line,
+            " ".repeat(padding)
+        ));
+    }
+    out.push_str("\x1b8");
+    out.into_bytes()
+}
+
+// the details about the watched stream shown in the help overlay. the
+// session is only as up to date as the last time the server told us about
+// it, so the current uptime is passed separately.
+pub fn session_info(
+    session: Option<&crate::protocol::Session>,
+    uptime: u32,
+    latency: Option<std::time::Duration>,
+) -> Vec<String> {
+    let mut lines = vec![];
+    if let Some(session) = session {
+        lines.push(format!("streamer: {}", session.username));
+        lines.push(format!("title: {}", session.title));
+        lines.push(format!(
+            "size: {}x{}",
+            session.size.cols, session.size.rows
+        ));
+        lines.push(format!("uptime: {}", format_time(uptime)));
+        lines.push(format!("watchers: {}", session.watchers));
+    }
+    lines.push(format!(
+        "latency: {}",
+        latency.map_or_else(
+            || "unknown".to_string(),
+            |latency| format!("{}ms", latency.as_millis())
+        )
+    ));
+    lines
+}
+
+// describes the fields of a SessionChanged message which were included
+pub fn session_changed(
+    title: Option<&str>,
+    tags: Option<&[String]>,
+    paused: Option<bool>,
+) -> String {
+    let mut changes = vec![];
+    match paused {
+        Some(true) => changes.push("stream paused".to_string()),
+        Some(false) => changes.push("stream resumed".to_string()),
+        None => {}
+    }
+    if let Some(title) = title {
+        changes.push(format!("title changed to \"{}\"", title));
+    }
+    if let Some(tags) = tags {
+        if tags.is_empty() {
+            changes.push("tags removed".to_string());
+        } else {
+            changes.push(format!("tags changed to {}", tags.join(", ")));
+        }
+    }
+    changes.join(", ")
+}
+
+// shown in the streamer's terminal title. watchers is none until at least
+// one of the servers is connected.
+pub fn streaming_title(servers: &[&str], watchers: Option<u32>) -> String {
+    let servers = servers.join(", ");
+    match watchers {
+        Some(1) => format!("teleterm: streaming to {} (1 watcher)", servers),
+        Some(n) => {
+            format!("teleterm: streaming to {} ({} watchers)", servers, n)
+        }
+        None => format!("teleterm: connecting to {}", servers),
+    }
+}
+
+// how much a stream is costing the streamer's connection to one server.
+// rate is in bytes per second.
+pub fn stream_stats(
+    sent: u64,
+    rate: u64,
+    queued: usize,
+    queue_size: usize,
+) -> String {
+    format!(
+        "sent {} ({}/s), send queue {}/{}",
+        format_bytes(sent),
+        format_bytes(rate),
+        queued,
+        queue_size
+    )
+}
+
+// notices come from the server itself rather than from another user
+pub fn notice(text: &str) -> String {
+    format!("server notice: {}", text)
+}
+
+pub fn scrolled_back(lines: usize) -> String {
+    format!(
+        "scrolled back {} lines, paused (pgdn to go forward, ] to go live)",
+        lines
+    )
+}
+
+pub fn chat_input(text: &str) -> String {
+    format!("chat (enter to send): {}", text)
+}
+
+pub fn format_time(dur: u32) -> String {
+    let secs = dur % 60;
+    let dur = dur / 60;
+    if dur == 0 {
+        return format!("{}s", secs);
+    }
+
+    let mins = dur % 60;
+    let dur = dur / 60;
+    if dur == 0 {
+        return format!("{}m{:02}s", mins, secs);
+    }
+
+    let hours = dur % 24;
+    let dur = dur / 24;
+    if dur == 0 {
+        return format!("{}h{:02}m{:02}s", hours, mins, secs);
+    }
+
+    let days = dur;
+    format!("{}d{:02}h{:02}m{:02}s", days, hours, mins, secs)
+}
+
+// takes seconds since the unix epoch, and formats it in the local time zone
+// (which can be overridden by setting TZ)
+pub fn format_local_time(time: u32) -> String {
+    let time = libc::time_t::from(time);
+    // this is plain old data, so all zeros is a valid value
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // localtime_r isn't required to notice changes to TZ on its own
+    unsafe { libc::tzset() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return "?".to_string();
+    }
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min
+    )
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "K", "M", "G", "T"];
+    let mut size = bytes;
+    let mut unit = 0;
+    while size >= 1024 && unit < units.len() - 1 {
+        size /= 1024;
+        unit += 1;
+    }
+    format!("{}{}", size, units[unit])
+}
+
+fn truncate(s: &str, len: usize) -> String {
+    if s.len() <= len {
+        s.to_string()
+    } else {
+        format!("{}...", &s[..(len - 3)])
+    }
+}
+
+fn column_width(
+    column: crate::session_list::Column,
+    sessions: &crate::session_list::SessionList,
+) -> usize {
+    let min_width = column.header().len();
+    match column {
+        crate::session_list::Column::Name => {
+            let max_name_width = (sessions.size().cols / 3) as usize;
+            let name_width = sessions
+                .visible_sessions()
+                .iter()
+                .map(|s| s.username.len())
+                .max()
+                .unwrap_or(min_width);
+            // XXX unstable
+            // name_width.clamp(min_width, max_name_width)
+            if name_width < min_width {
+                min_width
+            } else if name_width > max_name_width {
+                max_name_width
+            } else {
+                name_width
+            }
+        }
+        crate::session_list::Column::Size => 7,
+        crate::session_list::Column::Idle => {
+            let max_idle_time = sessions
+                .visible_sessions()
+                .iter()
+                .map(|s| s.idle_time)
+                .max()
+                .unwrap_or(0);
+            format_time(max_idle_time).len().max(min_width)
+        }
+        crate::session_list::Column::Watchers => {
+            if let Some(max) =
+                sessions.limits().and_then(|limits| limits.max_watchers)
+            {
+                let max_watchers = sessions
+                    .visible_sessions()
+                    .iter()
+                    .map(|s| s.watchers)
+                    .max()
+                    .unwrap_or(0);
+                format!("{}/{}", max_watchers, max).len().max(min_width)
+            } else {
+                5
+            }
+        }
+        crate::session_list::Column::Uptime => {
+            let max_uptime = sessions
+                .visible_sessions()
+                .iter()
+                .map(|s| s.uptime)
+                .max()
+                .unwrap_or(0);
+            format_time(max_uptime).len().max(min_width)
+        }
+        crate::session_list::Column::Started => 16,
+    }
+}
+
+// returns the text of the cell, along with how many columns it takes up on
+// the screen (which can differ from its length because of color codes)
+fn column_cell(
+    column: crate::session_list::Column,
+    session: &crate::protocol::Session,
+    first: bool,
+    size: crate::term::Size,
+    max_watchers: Option<u32>,
+    width: usize,
+    color: bool,
+) -> (String, usize) {
+    let cell = match column {
+        crate::session_list::Column::Name => {
+            // only show the name for the first of each user's sessions
+            if first {
+                truncate(&session.username, width)
+            } else {
+                "".to_string()
+            }
+        }
+        crate::session_list::Column::Size => {
+            let plain = format!("{}", &session.size);
+            if !color {
+                // without colors, only point out the streams which won't
+                // fit on our screen
+                let cell = if session.size.fits_in(size) {
+                    plain
+                } else {
+                    format!("{}!", plain)
+                };
+                let cell_width = cell.len();
+                return (cell, cell_width);
+            }
+            let plain_width = plain.len();
+            let full = if session.size == size {
+                // XXX i should be able to use crossterm::style here, but
+                // it has bugs
+                format!("\x1b[32m{}\x1b[m", plain)
+            } else if session.size.fits_in(size) {
+                plain
+            } else {
+                // XXX i should be able to use crossterm::style here, but
+                // it has bugs
+                format!("\x1b[31m{}\x1b[m", plain)
+            };
+            return (full, plain_width);
+        }
+        crate::session_list::Column::Idle => {
+            let plain = format_time(session.idle_time);
+            if !color || session.idle_time >= ACTIVE_IDLE_TIME {
+                plain
+            } else {
+                let plain_width = plain.len();
+                // XXX i should be able to use crossterm::style here, but
+                // it has bugs
+                return (format!("\x1b[1m{}\x1b[m", plain), plain_width);
+            }
+        }
+        crate::session_list::Column::Watchers => {
+            match max_watchers {
+                Some(max) => {
+                    let plain = format!(
+                        "{:>1$}",
+                        format!("{}/{}", session.watchers, max),
+                        width
+                    );
+                    if !color || session.watchers < max {
+                        plain
+                    } else {
+                        // full streams will refuse new watchers
+                        let plain_width = plain.len();
+                        // XXX i should be able to use crossterm::style here,
+                        // but it has bugs
+                        return (
+                            format!("\x1b[31m{}\x1b[m", plain),
+                            plain_width,
+                        );
+                    }
+                }
+                None => format!("{:1$}", session.watchers, width),
+            }
+        }
+        crate::session_list::Column::Uptime => format_time(session.uptime),
+        crate::session_list::Column::Started => {
+            format_local_time(session.start_time)
+        }
+    };
+    let cell_width = cell.len();
+    (cell, cell_width)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session(
+        username: &str,
+        size: crate::term::Size,
+        idle_time: u32,
+        title: &str,
+        tags: &[&str],
+        watchers: u32,
+        uptime: u32,
+    ) -> crate::protocol::Session {
+        crate::protocol::Session {
+            id: format!("{}", uuid::Uuid::new_v4()),
+            username: username.to_string(),
+            term_type: "screen".to_string(),
+            size,
+            idle_time,
+            title: title.to_string(),
+            description: "".to_string(),
+            tags: tags.iter().map(std::string::ToString::to_string).collect(),
+            room: "".to_string(),
+            paused: false,
+            watchers,
+            uptime,
+            start_time: 1_573_000_000 - uptime,
+        }
+    }
+
+    fn session_list() -> crate::session_list::SessionList {
+        crate::session_list::SessionList::new(
+            vec![
+                session(
+                    "doy",
+                    crate::term::Size {
+                        rows: 30,
+                        cols: 100,
+                    },
+                    35,
+                    "htop",
+                    &[],
+                    0,
+                    30,
+                ),
+                session(
+                    "sartak",
+                    crate::term::Size { rows: 20, cols: 70 },
+                    12,
+                    "nethack",
+                    &["games"],
+                    4,
+                    6000,
+                ),
+                session(
+                    "doy",
+                    crate::term::Size { rows: 24, cols: 80 },
+                    3,
+                    "vim",
+                    &[],
+                    1,
+                    600,
+                ),
+            ],
+            crate::term::Size { rows: 24, cols: 80 },
+            crate::session_list::SortOrder::Idle,
+        )
+    }
+
+    #[test]
+    fn test_loading() {
+        assert_eq!(loading(None), vec!["loading...", "q: quit --> "]);
+        assert_eq!(
+            loading(Some("connection refused")),
+            vec!["loading...", "error: connection refused", "q: quit --> "]
+        );
+    }
+
+    #[test]
+    fn test_choosing() {
+        let columns = [
+            crate::session_list::Column::Name,
+            crate::session_list::Column::Size,
+            crate::session_list::Column::Idle,
+            crate::session_list::Column::Watchers,
+            crate::session_list::Column::Uptime,
+        ];
+        assert_eq!(
+            choosing(&session_list(), &columns, true),
+            vec![
+                "welcome to teleterm",
+                "available sessions (sorted by idle time):",
+                "",
+                "   | name   | size    | idle | watch | uptime   | title",
+                "---+--------+---------+------+-------+----------+-------------------------------",
+                "a) | doy    | \x1b[32m80x24\x1b[m   | \x1b[1m3s\x1b[m   |     1 | 10m00s   | vim",
+                "b) |        | \x1b[31m100x30\x1b[m  | 35s  |     0 | 30s      | htop",
+                "c) | sartak | 70x20   | 12s  |     4 | 1h40m00s | nethack [games]",
+                "(1/1) space: refresh, s: sort, q: quit, <: prev page, >: next page --> ",
+            ]
+        );
+        assert_eq!(
+            &choosing(&session_list(), &columns, false)[5..8],
+            &[
+                "a) | doy    | 80x24   | 3s   |     1 | 10m00s   | vim",
+                "b) |        | 100x30! | 35s  |     0 | 30s      | htop",
+                "c) | sartak | 70x20   | 12s  |     4 | 1h40m00s | nethack [games]",
+            ]
+        );
+
+        let columns = [
+            crate::session_list::Column::Name,
+            crate::session_list::Column::Uptime,
+        ];
+        let mut sessions = session_list();
+        sessions.set_sort(crate::session_list::SortOrder::StartTime);
+        assert_eq!(
+            choosing(&sessions, &columns, true),
+            vec![
+                "welcome to teleterm",
+                "available sessions (sorted by start time):",
+                "",
+                "   | name   | uptime   | title",
+                "---+--------+----------+--------------------------------------------------------",
+                "a) | doy    | 30s      | htop",
+                "b) |        | 10m00s   | vim",
+                "c) | sartak | 1h40m00s | nethack [games]",
+                "(1/1) space: refresh, s: sort, q: quit, <: prev page, >: next page --> ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_choosing_limits() {
+        let columns = [
+            crate::session_list::Column::Name,
+            crate::session_list::Column::Watchers,
+        ];
+        let mut sessions = session_list();
+        sessions.set_limits(Some(crate::session_list::Limits {
+            connections: 12,
+            max_connections: Some(100),
+            max_watchers: Some(4),
+        }));
+        let lines = choosing(&sessions, &columns, true);
+        assert_eq!(lines[0], "welcome to teleterm (12/100 connections)");
+        assert_eq!(
+            &lines[5..8],
+            &[
+                "a) | doy    |   1/4 | vim",
+                "b) |        |   0/4 | htop",
+                "c) | sartak | \x1b[31m  4/4\x1b[m | nethack [games]",
+            ]
+        );
+
+        sessions.set_limits(Some(crate::session_list::Limits {
+            connections: 12,
+            max_connections: None,
+            max_watchers: None,
+        }));
+        let lines = choosing(&sessions, &columns, false);
+        assert_eq!(lines[0], "welcome to teleterm");
+        assert_eq!(lines[7], "c) | sartak |     4 | nethack [games]");
+    }
+
+    #[test]
+    fn test_choosing_rooms() {
+        let columns = [crate::session_list::Column::Name];
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let mut htop = session("doy", size, 35, "htop", &[], 0, 30);
+        htop.room = "rustconf".to_string();
+        let mut nethack =
+            session("sartak", size, 12, "nethack", &["games"], 4, 6000);
+        nethack.room = "rustconf".to_string();
+        let vim = session("doy", size, 3, "vim", &[], 1, 600);
+        let mut sessions = crate::session_list::SessionList::new(
+            vec![htop, nethack, vim],
+            size,
+            crate::session_list::SortOrder::Idle,
+        );
+
+        assert_eq!(
+            choosing(&sessions, &columns, false),
+            vec![
+                "welcome to teleterm",
+                "available rooms:",
+                "",
+                "   | streams | watch | room",
+                "---+---------+-------+-------------------------------------------------------",
+                "a) |       2 |     4 | rustconf",
+                "b) |       1 |     1 | (no room)",
+                "(1/1) space: refresh, q: quit, <: prev page, >: next page --> ",
+            ]
+        );
+
+        sessions.set_room(Some("rustconf".to_string()));
+        let lines = choosing(&sessions, &columns, false);
+        assert_eq!(lines[1], "sessions in rustconf (sorted by idle time):");
+        assert_eq!(
+            &lines[5..],
+            &[
+                "a) | doy    | htop",
+                "b) | sartak | nethack [games]",
+                "(1/1) space: refresh, s: sort, q: quit, <: prev page, >: next page, esc: rooms --> ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_choice_at() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let mut htop = session("doy", size, 35, "htop", &[], 0, 30);
+        htop.room = "rustconf".to_string();
+        let vim = session("doy", size, 3, "vim", &[], 1, 600);
+        let mut sessions = crate::session_list::SessionList::new(
+            vec![htop, vim],
+            size,
+            crate::session_list::SortOrder::Idle,
+        );
+
+        assert_eq!(choice_at(&sessions, 1), None);
+        assert_eq!(choice_at(&sessions, 5), None);
+        assert_eq!(choice_at(&sessions, 6), Some('a'));
+        assert_eq!(choice_at(&sessions, 7), Some('b'));
+        assert_eq!(choice_at(&sessions, 8), None);
+
+        sessions.set_room(Some("rustconf".to_string()));
+        assert_eq!(choice_at(&sessions, 6), Some('a'));
+        assert_eq!(choice_at(&sessions, 7), None);
+    }
+
+    #[test]
+    fn test_choosing_notice() {
+        let columns = [crate::session_list::Column::Name];
+        let mut sessions = session_list();
+        let lines = choosing(&sessions, &columns, false);
+        assert_eq!(lines[2], "");
+
+        sessions.set_notice(Some("restarting in 5 minutes".to_string()));
+        let lines2 = choosing(&sessions, &columns, false);
+        assert_eq!(lines2[2], "server notice: restarting in 5 minutes");
+        assert_eq!(lines.len(), lines2.len());
+        assert_eq!(&lines[3..], &lines2[3..]);
+    }
+
+    #[test]
+    fn test_choosing_marked() {
+        let columns = [crate::session_list::Column::Name];
+        let mut sessions = session_list();
+        assert!(sessions.toggle_mark('b'));
+        let lines = choosing(&sessions, &columns, false);
+        assert!(lines[5].starts_with("a) | "));
+        assert!(lines[6].starts_with("b* | "));
+        assert!(lines[7].starts_with("c) | "));
+        assert!(lines
+            .last()
+            .unwrap()
+            .ends_with(", enter: watch 1 marked --> "));
+    }
+
+    #[test]
+    fn test_tile_layout() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        assert_eq!(tile_layout(0, size), vec![]);
+        assert_eq!(tile_layout(1, size), vec![(0, 0, 24, 80)]);
+        assert_eq!(
+            tile_layout(2, size),
+            vec![(0, 0, 24, 39), (0, 40, 24, 39)]
+        );
+        assert_eq!(
+            tile_layout(3, size),
+            vec![(0, 0, 12, 39), (0, 40, 12, 39), (12, 0, 12, 39)]
+        );
+        assert_eq!(tile_layout(4, size).len(), 4);
+        assert_eq!(tile_layout(4, size)[3], (12, 40, 12, 39));
+        assert_eq!(
+            tile_layout(5, size),
+            vec![
+                (0, 0, 12, 26),
+                (0, 27, 12, 26),
+                (0, 54, 12, 26),
+                (12, 0, 12, 26),
+                (12, 27, 12, 26),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tile() {
+        let size = crate::term::Size { rows: 6, cols: 21 };
+        let rows = |term: &vt100::Parser| -> Vec<String> {
+            let (_, cols) = term.screen().size();
+            term.screen()
+                .rows(0, cols)
+                .map(|row| row.trim_end().to_string())
+                .collect()
+        };
+
+        let mut stream1 = vt100::Parser::new(24, 80, 0);
+        stream1.process(b"\x1b[31mhello world\x1b[m\r\nfoo");
+        let mut stream2 = vt100::Parser::new(24, 80, 0);
+        stream2.process(b"bar");
+
+        let layout = tile_layout(2, size);
+        let mut term = vt100::Parser::new(size.rows, size.cols, 0);
+        term.process(&tile("doy: vim", stream1.screen(), layout[0], true));
+        term.process(&tile(
+            "sartak: \x1b[2Jhtop",
+            stream2.screen(),
+            layout[1],
+            false,
+        ));
+        assert_eq!(
+            rows(&term),
+            vec![
+                "doy: vim   sartak: [2",
+                "hello worl bar",
+                "foo",
+                "",
+                "",
+                "",
+            ]
+        );
+        assert!(term.screen().cell(0, 0).unwrap().inverse());
+        assert!(!term.screen().cell(0, 11).unwrap().inverse());
+        assert!(term.screen().cell(0, 11).unwrap().underline());
+        assert_eq!(
+            term.screen().cell(1, 0).unwrap().fgcolor(),
+            vt100::Color::Default
+        );
+    }
+
+    #[test]
+    fn test_choosing_pagination() {
+        let sessions: Vec<_> = (0..12)
+            .map(|i| {
+                session(
+                    &format!("user{}", i),
+                    crate::term::Size { rows: 24, cols: 80 },
+                    i,
+                    "title",
+                    &[],
+                    0,
+                    0,
+                )
+            })
+            .collect();
+        let mut sessions = crate::session_list::SessionList::new(
+            sessions,
+            crate::term::Size { rows: 11, cols: 40 },
+            crate::session_list::SortOrder::Idle,
+        );
+        let columns = [crate::session_list::Column::Name];
+
+        let lines = choosing(&sessions, &columns, true);
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[5], "a) | user0 | title");
+        assert_eq!(lines[9], "e) | user4 | title");
+        assert!(lines[10].starts_with("(1/3) "));
+
+        sessions.next_page();
+        sessions.next_page();
+        let lines = choosing(&sessions, &columns, true);
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[5], "a) | user10 | title");
+        assert_eq!(lines[6], "b) | user11 | title");
+        assert!(lines[7].starts_with("(3/3) "));
+    }
+
+    #[test]
+    fn test_history() {
+        // every test which formats local times sets it to the same thing,
+        // so it doesn't matter that they run in parallel
+        std::env::set_var("TZ", "UTC");
+
+        assert_eq!(history(&[], 0), vec!["no streams have ended recently"]);
+
+        let sessions = [
+            crate::protocol::PastSession {
+                username: "doy".to_string(),
+                title: "vim".to_string(),
+                description: "".to_string(),
+                start_time: 1000,
+                end_time: 1600,
+                bytes: 2048,
+            },
+            crate::protocol::PastSession {
+                username: "sartak".to_string(),
+                title: "".to_string(),
+                description: "debugging".to_string(),
+                start_time: 10,
+                end_time: 20,
+                bytes: 12,
+            },
+        ];
+        assert_eq!(
+            history(&sessions, 1660),
+            vec![
+                "recently ended streams:",
+                "",
+                "name   | started          | ended      | duration | bytes | title",
+                "-------+------------------+------------+----------+-------+-------",
+                "doy    | 1970-01-01 00:16 | 1m00s ago  |   10m00s |    2K | vim",
+                "sartak | 1970-01-01 00:00 | 27m20s ago |      10s |   12B | debugging",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_admin_connections() {
+        let connections = [
+            crate::protocol::AdminConnection {
+                id: "stream-id".to_string(),
+                address: "127.0.0.1:54321".to_string(),
+                username: "doy".to_string(),
+                state: "streaming".to_string(),
+                watch_id: "".to_string(),
+                connected: 3600,
+            },
+            crate::protocol::AdminConnection {
+                id: "watch-id".to_string(),
+                address: "10.0.0.2:4321".to_string(),
+                username: "sartak".to_string(),
+                state: "watching".to_string(),
+                watch_id: "stream-id".to_string(),
+                connected: 30,
+            },
+            crate::protocol::AdminConnection {
+                id: "new-id".to_string(),
+                address: "10.0.0.3:1234".to_string(),
+                username: "".to_string(),
+                state: "accepted".to_string(),
+                watch_id: "".to_string(),
+                connected: 0,
+            },
+        ];
+        assert_eq!(
+            admin_connections(&connections),
+            vec![
+                "id        | address         | name   | state     | connected | watching",
+                "----------+-----------------+--------+-----------+-----------+----------",
+                "stream-id | 127.0.0.1:54321 | doy    | streaming |  1h00m00s | ",
+                "watch-id  | 10.0.0.2:4321   | sartak | watching  |       30s | stream-id",
+                "new-id    | 10.0.0.3:1234   |        | accepted  |        0s | ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_admin_stats() {
+        let stats = crate::protocol::ServerStats {
+            uptime: 90000,
+            connections: 12,
+            streams: 3,
+            watchers: 8,
+            total_connections: 1234,
+            total_streams: 56,
+        };
+        assert_eq!(
+            admin_stats(&stats),
+            vec![
+                "uptime:            1d01h00m00s",
+                "connections:       12",
+                "streams:           3",
+                "watchers:          8",
+                "total connections: 1234",
+                "total streams:     56",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watching() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let mut term = vt100::Parser::new(size.rows, size.cols, 0);
+        term.process(b"\r\n\r\nhello");
+        let mut status_line = crate::status_line::StatusLine::new();
+
+        let mut redrawn = vt100::Parser::new(size.rows, size.cols, 0);
+        redrawn.process(&watching(
+            &term.screen().contents_formatted(),
+            &status_line,
+            None,
+            size,
+        ));
+        assert_eq!(redrawn.screen().contents(), term.screen().contents());
+
+        status_line.set("watching");
+        let mut redrawn = vt100::Parser::new(size.rows, size.cols, 0);
+        redrawn.process(&watching(
+            &term.screen().contents_formatted(),
+            &status_line,
+            None,
+            size,
+        ));
+        assert!(redrawn.screen().contents().starts_with("watching"));
+        assert!(redrawn.screen().contents().contains("hello"));
+    }
+
+    #[test]
+    fn test_help() {
+        let size = crate::term::Size { rows: 24, cols: 80 };
+        let bindings = vec![
+            ("^\\".to_string(), "go back to the list of streams"),
+            ("c".to_string(), "send a chat message"),
+        ];
+        let rows = |term: &vt100::Parser| -> Vec<String> {
+            let (_, cols) = term.screen().size();
+            term.screen()
+                .rows(0, cols)
+                .map(|row| row.trim_end().to_string())
+                .collect()
+        };
+
+        let mut term = vt100::Parser::new(size.rows, size.cols, 0);
+        term.process(b"hello");
+        term.process(&help(&[], &bindings, size));
+        let lines = rows(&term);
+        assert_eq!(lines[0], "hello");
+        assert_eq!(lines[1], " press any key to close");
+        assert_eq!(lines[2], " ^\\  go back to the list of streams");
+        assert_eq!(lines[3], " c   send a chat message");
+        assert_eq!(lines[4], "");
+        assert_eq!(term.screen().cursor_position(), (0, 5));
+
+        let info = vec!["title: \x1b[2Jvim".to_string()];
+        let mut term = vt100::Parser::new(size.rows, size.cols, 0);
+        term.process(&help(&info, &bindings, size));
+        let lines = rows(&term);
+        assert_eq!(lines[2], " title: [2Jvim");
+        assert_eq!(lines[3], "");
+        assert_eq!(lines[4], " ^\\  go back to the list of streams");
+
+        // never draws past the edges of the terminal
+        let size = crate::term::Size { rows: 3, cols: 10 };
+        let mut term = vt100::Parser::new(size.rows, size.cols, 0);
+        term.process(&help(&[], &bindings, size));
+        assert_eq!(rows(&term), vec!["", " press an", " ^\\  go b"]);
+    }
+
+    #[test]
+    fn test_session_info() {
+        assert_eq!(session_info(None, 0, None), vec!["latency: unknown"]);
+        let session = crate::protocol::Session {
+            id: "some-session-id".to_string(),
+            username: "doy".to_string(),
+            term_type: "screen".to_string(),
+            size: crate::term::Size { rows: 24, cols: 80 },
+            idle_time: 0,
+            title: "vim".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            watchers: 2,
+            uptime: 60,
+            start_time: 0,
+        };
+        assert_eq!(
+            session_info(
+                Some(&session),
+                90,
+                Some(std::time::Duration::from_millis(42))
+            ),
+            vec![
+                "streamer: doy",
+                "title: vim",
+                "size: 80x24",
+                "uptime: 1m30s",
+                "watchers: 2",
+                "latency: 42ms",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_session_changed() {
+        assert_eq!(
+            session_changed(Some("vim"), None, None),
+            "title changed to \"vim\""
+        );
+        assert_eq!(
+            session_changed(None, Some(&["rust".to_string()]), None),
+            "tags changed to rust"
+        );
+        assert_eq!(
+            session_changed(Some(""), Some(&[]), None),
+            "title changed to \"\", tags removed"
+        );
+        assert_eq!(session_changed(None, None, Some(true)), "stream paused");
+        assert_eq!(
+            session_changed(None, None, Some(false)),
+            "stream resumed"
+        );
+    }
+
+    #[test]
+    fn test_stream_stats() {
+        assert_eq!(
+            stream_stats(0, 0, 0, 1000),
+            "sent 0B (0B/s), send queue 0/1000"
+        );
+        assert_eq!(
+            stream_stats(5 * 1024 * 1024, 2048, 12, 1000),
+            "sent 5M (2K/s), send queue 12/1000"
+        );
+    }
+
+    #[test]
+    fn test_streaming_title() {
+        assert_eq!(
+            streaming_title(&["example.com"], None),
+            "teleterm: connecting to example.com"
+        );
+        assert_eq!(
+            streaming_title(&["example.com"], Some(1)),
+            "teleterm: streaming to example.com (1 watcher)"
+        );
+        assert_eq!(
+            streaming_title(&["a.com", "b.com"], Some(0)),
+            "teleterm: streaming to a.com, b.com (0 watchers)"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abcdefghij", 12), "abcdefghij");
+        assert_eq!(truncate("abcdefghij", 11), "abcdefghij");
+        assert_eq!(truncate("abcdefghij", 10), "abcdefghij");
+        assert_eq!(truncate("abcdefghij", 9), "abcdef...");
+        assert_eq!(truncate("abcdefghij", 8), "abcde...");
+        assert_eq!(truncate("abcdefghij", 7), "abcd...");
+
+        assert_eq!(truncate("", 7), "");
+        assert_eq!(truncate("a", 7), "a");
+        assert_eq!(truncate("ab", 7), "ab");
+        assert_eq!(truncate("abc", 7), "abc");
+        assert_eq!(truncate("abcd", 7), "abcd");
+        assert_eq!(truncate("abcde", 7), "abcde");
+        assert_eq!(truncate("abcdef", 7), "abcdef");
+        assert_eq!(truncate("abcdefg", 7), "abcdefg");
+        assert_eq!(truncate("abcdefgh", 7), "abcd...");
+        assert_eq!(truncate("abcdefghi", 7), "abcd...");
+        assert_eq!(truncate("abcdefghij", 7), "abcd...");
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "0s");
+        assert_eq!(format_time(5), "5s");
+        assert_eq!(format_time(10), "10s");
+        assert_eq!(format_time(60), "1m00s");
+        assert_eq!(format_time(61), "1m01s");
+        assert_eq!(format_time(601), "10m01s");
+        assert_eq!(format_time(610), "10m10s");
+        assert_eq!(format_time(3599), "59m59s");
+        assert_eq!(format_time(3600), "1h00m00s");
+        assert_eq!(format_time(3601), "1h00m01s");
+        assert_eq!(format_time(3610), "1h00m10s");
+        assert_eq!(format_time(3660), "1h01m00s");
+        assert_eq!(format_time(3661), "1h01m01s");
+        assert_eq!(format_time(3670), "1h01m10s");
+        assert_eq!(format_time(4200), "1h10m00s");
+        assert_eq!(format_time(4201), "1h10m01s");
+        assert_eq!(format_time(4210), "1h10m10s");
+        assert_eq!(format_time(36000), "10h00m00s");
+        assert_eq!(format_time(86399), "23h59m59s");
+        assert_eq!(format_time(86400), "1d00h00m00s");
+        assert_eq!(format_time(86401), "1d00h00m01s");
+        assert_eq!(format_time(864_000), "10d00h00m00s");
+        assert_eq!(format_time(8_640_000), "100d00h00m00s");
+        assert_eq!(format_time(86_400_000), "1000d00h00m00s");
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0B");
+        assert_eq!(format_bytes(1023), "1023B");
+        assert_eq!(format_bytes(1024), "1K");
+        assert_eq!(format_bytes(1_048_575), "1023K");
+        assert_eq!(format_bytes(1_048_576), "1M");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5G");
+        assert_eq!(format_bytes(std::u64::MAX), "16777215T");
+    }
+}
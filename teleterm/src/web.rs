@@ -1,8 +1,11 @@
 mod disk_session;
+mod embed;
+mod filtered;
 mod list;
 mod login;
 mod logout;
 mod oauth;
+mod recordings;
 mod view;
 mod watch;
 mod ws;
@@ -15,13 +18,15 @@ use gotham::state::FromState as _;
 #[derive(Clone, gotham_derive::StateData)]
 struct Config {
     server_address: (String, std::net::SocketAddr),
-    public_address: String,
     allowed_login_methods:
         std::collections::HashSet<crate::protocol::AuthType>,
     oauth_configs: std::collections::HashMap<
         crate::protocol::AuthType,
         crate::oauth::Config,
     >,
+    jitter_buffer: std::time::Duration,
+    web_root: Option<std::path::PathBuf>,
+    recordings_dir: Option<std::path::PathBuf>,
 }
 
 impl Config {
@@ -39,25 +44,47 @@ impl Config {
 pub(crate) struct LoginState {
     auth: crate::protocol::Auth,
     username: String,
+    // oauth logins are checked by the server again on every connection we
+    // make for them, using this
+    #[serde(default)]
+    access_token: Option<String>,
 }
 
 #[derive(Default, serde::Deserialize, serde::Serialize)]
 struct SessionData {
     login: Option<LoginState>,
+    // the state parameter handed out in the oauth login urls, which the
+    // oauth callback has to match
+    #[serde(default)]
+    oauth_state: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize)]
 struct WebConfig<'a> {
     username: Option<&'a str>,
-    public_address: &'a str,
     allowed_login_methods:
         &'a std::collections::HashSet<crate::protocol::AuthType>,
     oauth_login_urls:
         std::collections::HashMap<crate::protocol::AuthType, String>,
+    jitter_buffer_ms: f64,
+    recordings: bool,
+    // the stream to show, for pages which only show a single stream
+    embed: Option<&'a str>,
+    // set for pages which only list the streams of a single user or room
+    list_username: Option<&'a str>,
+    list_room: Option<&'a str>,
+    // how to get back to the root from the page's url, since the page
+    // refers to everything else relatively
+    base: &'static str,
 }
 
 impl<'a> WebConfig<'a> {
-    fn new(config: &'a Config, session: &'a SessionData) -> Result<Self> {
+    fn new(
+        config: &'a Config,
+        session: &'a SessionData,
+        page: Page<'a>,
+    ) -> Result<Self> {
+        let oauth_state = session.oauth_state.as_ref();
         let mut oauth_login_urls = std::collections::HashMap::new();
         for ty in config.allowed_oauth_login_methods() {
             let oauth_config = config
@@ -65,20 +92,43 @@ impl<'a> WebConfig<'a> {
                 .get(&ty)
                 .context(crate::error::AuthTypeMissingOauthConfig { ty })?;
             let client = ty.oauth_client(oauth_config, None).unwrap();
-            oauth_login_urls.insert(ty, client.generate_authorize_url());
+            let state = oauth2::CsrfToken::new(
+                oauth_state.cloned().unwrap_or_default(),
+            );
+            oauth_login_urls.insert(ty, client.generate_authorize_url(state));
         }
+        let (embed, list_username, list_room, base) = match page {
+            Page::Index => (None, None, None, ""),
+            Page::Embed(id) => (Some(id), None, None, "../"),
+            Page::User(username) => (None, Some(username), None, "../"),
+            Page::Room(room) => (None, None, Some(room), "../"),
+        };
         Ok(Self {
             username: session
                 .login
                 .as_ref()
                 .map(|login| login.username.as_str()),
-            public_address: &config.public_address,
             allowed_login_methods: &config.allowed_login_methods,
             oauth_login_urls,
+            jitter_buffer_ms: config.jitter_buffer.as_secs_f64() * 1000.0,
+            recordings: config.recordings_dir.is_some(),
+            embed,
+            list_username,
+            list_room,
+            base,
         })
     }
 }
 
+// which page of the web interface is being served
+#[derive(Clone, Copy)]
+enum Page<'a> {
+    Index,
+    Embed(&'a str),
+    User(&'a str),
+    Room(&'a str),
+}
+
 pub struct Server {
     server: Box<dyn futures::Future<Item = (), Error = ()> + Send>,
 }
@@ -86,7 +136,6 @@ pub struct Server {
 impl Server {
     pub fn new(
         listen_address: std::net::SocketAddr,
-        public_address: String,
         server_address: (String, std::net::SocketAddr),
         allowed_login_methods: std::collections::HashSet<
             crate::protocol::AuthType,
@@ -95,12 +144,17 @@ impl Server {
             crate::protocol::AuthType,
             crate::oauth::Config,
         >,
+        jitter_buffer: std::time::Duration,
+        web_root: Option<std::path::PathBuf>,
+        recordings_dir: Option<std::path::PathBuf>,
     ) -> Self {
         let data = Config {
             server_address,
-            public_address,
             allowed_login_methods,
             oauth_configs,
+            jitter_buffer,
+            web_root,
+            recordings_dir,
         };
         Self {
             server: Box::new(gotham::init_server(
@@ -137,20 +191,30 @@ fn router(data: &Config) -> impl gotham::handler::NewHandler {
             .build(),
     );
     gotham::router::builder::build_router(chain, pipeline, |route| {
-        route
-            .get("/")
-            .to(serve_template("text/html", view::INDEX_HTML_TMPL_NAME));
+        route.get("/").to(serve_template(
+            "text/html",
+            view::INDEX_HTML_TMPL_NAME,
+            "index.html.tmpl",
+        ));
         route.get("/teleterm_web.js").to(serve_static(
             "application/javascript",
+            "teleterm_web.js",
             &view::TELETERM_WEB_JS,
         ));
+        route.get("/teleterm_web_bg.wasm").to(serve_static(
+            "application/wasm",
+            "teleterm_web_bg.wasm",
+            &view::TELETERM_WEB_WASM,
+        ));
+        route.get("/teleterm.css").to(serve_static(
+            "text/css",
+            "teleterm.css",
+            &view::TELETERM_CSS,
+        ));
         route
-            .get("/teleterm_web_bg.wasm")
-            .to(serve_static("application/wasm", &view::TELETERM_WEB_WASM));
-        route
-            .get("/teleterm.css")
-            .to(serve_static("text/css", &view::TELETERM_CSS));
-        route.get("/list").to(list::run);
+            .get("/list")
+            .with_query_string_extractor::<list::QueryParams>()
+            .to(list::run);
         route
             .get("/watch")
             .with_query_string_extractor::<watch::QueryParams>()
@@ -164,18 +228,69 @@ fn router(data: &Config) -> impl gotham::handler::NewHandler {
             .with_path_extractor::<oauth::PathParts>()
             .with_query_string_extractor::<oauth::QueryParams>()
             .to(oauth::run);
+        route
+            .get("/embed/:id")
+            .with_path_extractor::<embed::PathParts>()
+            .to(embed::run);
+        route
+            .get("/u/:username")
+            .with_path_extractor::<filtered::UserPathParts>()
+            .to(filtered::user);
+        route
+            .get("/r/:room")
+            .with_path_extractor::<filtered::RoomPathParts>()
+            .to(filtered::room);
         route.get("/logout").to(logout::run);
+        route.get("/recordings").to(recordings::list);
+        route
+            .get("/recordings/:id")
+            .with_path_extractor::<recordings::PathParts>()
+            .to(recordings::run);
     })
 }
 
+// the files are built into the binary, but can be overridden by a directory
+// of files to serve instead, which is read on every request so that changes
+// show up without a restart
+fn read_web_root(
+    config: &Config,
+    filename: &str,
+) -> Option<std::io::Result<Vec<u8>>> {
+    config
+        .web_root
+        .as_ref()
+        .map(|web_root| std::fs::read(web_root.join(filename)))
+}
+
+fn not_found(
+    filename: &str,
+    e: &std::io::Error,
+) -> hyper::Response<hyper::Body> {
+    log::warn!("failed to read {}: {}", filename, e);
+    hyper::Response::builder()
+        .status(hyper::StatusCode::NOT_FOUND)
+        .body(hyper::Body::empty())
+        .unwrap()
+}
+
 fn serve_static(
     content_type: &'static str,
+    filename: &'static str,
     s: &'static [u8],
 ) -> impl gotham::handler::Handler + Copy {
     move |state| {
+        let data = read_web_root(Config::borrow_from(&state), filename);
+        let body = match data {
+            Some(Ok(data)) => hyper::Body::from(data),
+            Some(Err(e)) => {
+                let response = not_found(filename, &e);
+                return (state, response);
+            }
+            None => hyper::Body::from(s),
+        };
         let response = hyper::Response::builder()
             .header("Content-Type", content_type)
-            .body(hyper::Body::from(s))
+            .body(body)
             .unwrap();
         (state, response)
     }
@@ -184,31 +299,79 @@ fn serve_static(
 fn serve_template(
     content_type: &'static str,
     name: &'static str,
+    filename: &'static str,
 ) -> impl gotham::handler::Handler + Copy {
-    move |state| {
-        let config = Config::borrow_from(&state);
+    move |mut state| {
+        let response = template_response(
+            &mut state,
+            content_type,
+            name,
+            filename,
+            Page::Index,
+        );
+        (state, response)
+    }
+}
+
+fn template_response(
+    state: &mut gotham::state::State,
+    content_type: &str,
+    name: &str,
+    filename: &str,
+    page: Page<'_>,
+) -> hyper::Response<hyper::Body> {
+    // oauth callbacks are only accepted with the state from the login urls
+    // we hand out here, so that other sites can't send people back to us
+    // logged in to an account of their choosing. this is kept for the rest
+    // of the session so that pages open in other tabs keep working.
+    let uses_oauth = Config::borrow_from(state)
+        .allowed_oauth_login_methods()
+        .next()
+        .is_some();
+    if uses_oauth {
         let session = gotham::middleware::session::SessionData::<
             crate::web::SessionData,
-        >::borrow_from(&state);
-        let web_config = match WebConfig::new(config, session) {
-            Ok(config) => config,
-            Err(e) => {
-                // this means that the server configuration is incorrect, and
-                // there's nothing the client can do about it
-                return (
-                    state,
-                    hyper::Response::builder()
-                        .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
-                        .body(hyper::Body::from(format!("{}", e)))
-                        .unwrap(),
-                );
-            }
-        };
-        let rendered = view::HANDLEBARS.render(name, &web_config).unwrap();
-        let response = hyper::Response::builder()
-            .header("Content-Type", content_type)
-            .body(hyper::Body::from(rendered))
-            .unwrap();
-        (state, response)
+        >::borrow_mut_from(state);
+        if session.oauth_state.is_none() {
+            session.oauth_state =
+                Some(oauth2::CsrfToken::new_random().secret().clone());
+        }
     }
+    let config = Config::borrow_from(state);
+    let session = gotham::middleware::session::SessionData::<
+        crate::web::SessionData,
+    >::borrow_from(state);
+    let web_config = match WebConfig::new(config, session, page) {
+        Ok(config) => config,
+        Err(e) => {
+            // this means that the server configuration is incorrect, and
+            // there's nothing the client can do about it
+            return hyper::Response::builder()
+                .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+                .body(hyper::Body::from(format!("{}", e)))
+                .unwrap();
+        }
+    };
+    let rendered = match read_web_root(config, filename) {
+        Some(Ok(template)) => view::HANDLEBARS
+            .render_template(&String::from_utf8_lossy(&template), &web_config)
+            .map_err(|e| e.to_string()),
+        Some(Err(e)) => return not_found(filename, &e),
+        None => view::HANDLEBARS
+            .render(name, &web_config)
+            .map_err(|e| e.to_string()),
+    };
+    let rendered = match rendered {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            return hyper::Response::builder()
+                .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+                .body(hyper::Body::from(e))
+                .unwrap();
+        }
+    };
+    hyper::Response::builder()
+        .header("Content-Type", content_type)
+        .body(hyper::Body::from(rendered))
+        .unwrap()
 }
@@ -0,0 +1,58 @@
+use gotham::state::FromState as _;
+
+// viewers of embedded streams usually haven't logged into the web interface
+// (or can't, since browsers often refuse to send cookies to iframes), so if
+// plain logins are allowed, they watch as this user instead
+const ANONYMOUS_USERNAME: &str = "(embedded viewer)";
+
+#[derive(
+    serde::Deserialize,
+    gotham_derive::StateData,
+    gotham_derive::StaticResponseExtender,
+)]
+pub struct PathParts {
+    id: String,
+}
+
+// serves a page showing nothing but the terminal of a single stream, for
+// putting in an iframe. the page then connects back to this same url over a
+// websocket to watch the stream.
+pub fn run(
+    mut state: gotham::state::State,
+) -> (gotham::state::State, hyper::Response<hyper::Body>) {
+    let id = PathParts::borrow_from(&state).id.clone();
+
+    if !crate::web::ws::requested(hyper::HeaderMap::borrow_from(&state)) {
+        let response = crate::web::template_response(
+            &mut state,
+            "text/html",
+            crate::web::view::EMBED_HTML_TMPL_NAME,
+            "embed.html.tmpl",
+            crate::web::Page::Embed(&id),
+        );
+        return (state, response);
+    }
+
+    let session = gotham::middleware::session::SessionData::<
+        crate::web::SessionData,
+    >::borrow_from(&state);
+    let config = crate::web::Config::borrow_from(&state);
+    let (auth, access_token) = if let Some(login) = &session.login {
+        (login.auth.clone(), login.access_token.clone())
+    } else if config
+        .allowed_login_methods
+        .contains(&crate::protocol::AuthType::Plain)
+    {
+        (crate::protocol::Auth::plain(ANONYMOUS_USERNAME), None)
+    } else {
+        return (
+            state,
+            hyper::Response::builder()
+                .status(hyper::StatusCode::UNAUTHORIZED)
+                .body(hyper::Body::empty())
+                .unwrap(),
+        );
+    };
+
+    crate::web::watch::watch(state, &auth, access_token, &id)
+}
@@ -0,0 +1,50 @@
+use gotham::state::FromState as _;
+
+#[derive(
+    serde::Deserialize,
+    gotham_derive::StateData,
+    gotham_derive::StaticResponseExtender,
+)]
+pub struct UserPathParts {
+    username: String,
+}
+
+#[derive(
+    serde::Deserialize,
+    gotham_derive::StateData,
+    gotham_derive::StaticResponseExtender,
+)]
+pub struct RoomPathParts {
+    room: String,
+}
+
+// these serve the normal page, but with the list limited to the live
+// streams of a single user or room, so that there is a stable url which
+// always points at whatever they are currently streaming
+pub fn user(
+    mut state: gotham::state::State,
+) -> (gotham::state::State, hyper::Response<hyper::Body>) {
+    let username = UserPathParts::borrow_from(&state).username.clone();
+    let response = crate::web::template_response(
+        &mut state,
+        "text/html",
+        crate::web::view::INDEX_HTML_TMPL_NAME,
+        "index.html.tmpl",
+        crate::web::Page::User(&username),
+    );
+    (state, response)
+}
+
+pub fn room(
+    mut state: gotham::state::State,
+) -> (gotham::state::State, hyper::Response<hyper::Body>) {
+    let room = RoomPathParts::borrow_from(&state).room.clone();
+    let response = crate::web::template_response(
+        &mut state,
+        "text/html",
+        crate::web::view::INDEX_HTML_TMPL_NAME,
+        "index.html.tmpl",
+        crate::web::Page::Room(&room),
+    );
+    (state, response)
+}
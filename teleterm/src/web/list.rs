@@ -2,44 +2,63 @@ use crate::prelude::*;
 
 use gotham::state::FromState as _;
 
+#[derive(
+    serde::Deserialize,
+    gotham_derive::StateData,
+    gotham_derive::StaticResponseExtender,
+)]
+pub struct QueryParams {
+    room: Option<String>,
+    username: Option<String>,
+}
+
 pub fn run(
     state: gotham::state::State,
 ) -> (gotham::state::State, hyper::Response<hyper::Body>) {
     let session = gotham::middleware::session::SessionData::<
         crate::web::SessionData,
     >::borrow_from(&state);
-    let auth = if let Some(login) = &session.login {
-        &login.auth
+    let login = if let Some(login) = &session.login {
+        login
     } else {
         return (
             state,
             hyper::Response::builder()
-                .status(hyper::StatusCode::FORBIDDEN)
+                .status(hyper::StatusCode::UNAUTHORIZED)
                 .body(hyper::Body::empty())
                 .unwrap(),
         );
     };
 
+    let query = QueryParams::borrow_from(&state);
+    let room = query.room.clone();
+    let username = query.username.clone();
     let config = crate::web::Config::borrow_from(&state);
 
-    let (_, address) = config.server_address;
-    let connector: crate::client::Connector<_> = Box::new(move || {
-        Box::new(
-            tokio::net::tcp::TcpStream::connect(&address)
-                .context(crate::error::Connect { address }),
-        )
-    });
-    let client = crate::client::Client::raw(
+    let (host, address) = &config.server_address;
+    let connector = crate::connect::Connector::new(
+        host,
+        *address,
+        crate::connect::Source::Any,
+        None,
+        None,
+    );
+    let connector: crate::client::Connector<_> =
+        Box::new(move || connector.connect());
+    let mut client = crate::client::Client::raw(
         "teleterm-web",
         connector,
-        auth,
+        &login.auth,
         crate::protocol::AuthClient::Web,
     );
+    if let Some(access_token) = &login.access_token {
+        client.set_oauth_access_token(access_token);
+    }
 
     let (w_sessions, r_sessions) = tokio::sync::oneshot::channel();
 
     tokio::spawn(
-        Client::new(client, w_sessions)
+        Client::new(client, room, username, w_sessions)
             .map_err(|e| log::warn!("error listing: {}", e)),
     );
 
@@ -65,6 +84,10 @@ struct Client<
     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
 > {
     client: crate::client::Client<S>,
+    // only the streams in this room are listed, if given
+    room: Option<String>,
+    // only the streams by this user are listed, if given
+    username: Option<String>,
     w_sessions: Option<
         tokio::sync::oneshot::Sender<Result<Vec<crate::protocol::Session>>>,
     >,
@@ -75,12 +98,16 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
 {
     fn new(
         client: crate::client::Client<S>,
+        room: Option<String>,
+        username: Option<String>,
         w_sessions: tokio::sync::oneshot::Sender<
             Result<Vec<crate::protocol::Session>>,
         >,
     ) -> Self {
         Self {
             client,
+            room,
+            username,
             w_sessions: Some(w_sessions),
         }
     }
@@ -91,17 +118,33 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     ) -> Option<Result<Vec<crate::protocol::Session>>> {
         match msg {
             crate::protocol::Message::Sessions { sessions } => {
-                Some(Ok(sessions))
+                if let Some(username) = &self.username {
+                    Some(Ok(sessions
+                        .into_iter()
+                        .filter(|session| &session.username == username)
+                        .collect()))
+                } else {
+                    Some(Ok(sessions))
+                }
             }
             crate::protocol::Message::Disconnected => {
                 Some(Err(Error::ServerDisconnected))
             }
+            crate::protocol::Message::ServerShutdown { reason } => {
+                Some(Err(Error::ServerShutdown { reason }))
+            }
             crate::protocol::Message::Error { msg } => {
                 Some(Err(Error::Server { message: msg }))
             }
             crate::protocol::Message::LoggedIn { .. } => {
-                self.client
-                    .send_message(crate::protocol::Message::list_sessions());
+                let msg = match &self.room {
+                    Some(room) if self.client.has_capability("rooms") => {
+                        crate::protocol::Message::list_room_sessions(room)
+                    }
+                    // servers without rooms just get the full list
+                    _ => crate::protocol::Message::list_sessions(),
+                };
+                self.client.send_message(msg);
                 None
             }
             msg => Some(Err(crate::error::Error::UnexpectedMessage {
@@ -124,7 +167,19 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         >] = &[&Self::poll_client];
 
     fn poll_client(&mut self) -> component_future::Poll<(), Error> {
-        match component_future::try_ready!(self.client.poll()).unwrap() {
+        let event = match self.client.poll() {
+            Ok(futures::Async::Ready(event)) => event.unwrap(),
+            Ok(futures::Async::NotReady) => {
+                return Ok(component_future::Async::NotReady)
+            }
+            Err(e) => {
+                // the request is waiting on us, so it needs to hear about
+                // errors (like the server timing out) too
+                self.w_sessions.take().unwrap().send(Err(e)).unwrap();
+                return Ok(component_future::Async::Ready(()));
+            }
+        };
+        match event {
             crate::client::Event::ServerMessage(msg) => {
                 if let Some(res) = self.server_message(msg) {
                     self.w_sessions.take().unwrap().send(res).unwrap();
@@ -18,7 +18,7 @@ struct Response {
 }
 
 pub fn run(
-    mut state: gotham::state::State,
+    state: gotham::state::State,
 ) -> Box<
     dyn futures::Future<
             Item = (gotham::state::State, hyper::Response<hyper::Body>),
@@ -31,76 +31,95 @@ pub fn run(
     };
 
     let config = crate::web::Config::borrow_from(&state);
-
-    let (_, address) = config.server_address;
-    let connector: crate::client::Connector<_> = Box::new(move || {
-        Box::new(
-            tokio::net::tcp::TcpStream::connect(&address)
-                .context(crate::error::Connect { address }),
-        )
-    });
     let auth = crate::protocol::Auth::plain(&username);
-    let client = crate::client::Client::raw(
+
+    Box::new(login(config, auth, None).then(move |res| {
+        finish(state, res, |_| {
+            hyper::Response::new(hyper::Body::from(
+                serde_json::to_string(&Response { username }).unwrap(),
+            ))
+        })
+    }))
+}
+
+// logs into the server with the given credentials, sending back the
+// state to store in the session if the server accepted them
+pub(crate) fn login(
+    config: &crate::web::Config,
+    auth: crate::protocol::Auth,
+    access_token: Option<String>,
+) -> tokio::sync::oneshot::Receiver<Result<super::LoginState>> {
+    let (host, address) = &config.server_address;
+    let connector = crate::connect::Connector::new(
+        host,
+        *address,
+        crate::connect::Source::Any,
+        None,
+        None,
+    );
+    let connector: crate::client::Connector<_> =
+        Box::new(move || connector.connect());
+    let mut client = crate::client::Client::raw(
         "teleterm-web",
         connector,
         &auth,
         crate::protocol::AuthClient::Web,
     );
+    if let Some(access_token) = &access_token {
+        client.set_oauth_access_token(access_token);
+    }
 
     let (w_login, r_login) = tokio::sync::oneshot::channel();
 
     tokio::spawn(
-        Client::new(client, auth, w_login)
-            // XXX if this happens, we might not have sent anything on the
-            // channel, and so the wait might block forever
+        Client::new(client, auth, access_token, w_login)
             .map_err(|e| log::error!("error logging in: {}", e)),
     );
 
-    Box::new(r_login.then(|res| {
-        let session = gotham::middleware::session::SessionData::<
-            crate::web::SessionData,
-        >::borrow_mut_from(&mut state);
-        match res {
-            Ok(login) => {
-                let session = gotham::middleware::session::SessionData::<
-                    crate::web::SessionData,
-                >::borrow_mut_from(&mut state);
-
-                match login {
-                    Ok(login) => {
-                        session.login = Some(login);
-                        futures::future::ok((
-                            state,
-                            hyper::Response::new(hyper::Body::from(
-                                serde_json::to_string(&Response { username })
-                                    .unwrap(),
-                            )),
-                        ))
-                    }
-                    Err(e) => {
-                        session.login = None;
-                        log::error!("error logging in: {}", e);
-                        futures::future::err((
-                            state,
-                            e.into_handler_error().with_status(
-                                hyper::StatusCode::INTERNAL_SERVER_ERROR,
-                            ),
-                        ))
-                    }
-                }
-            }
-            Err(e) => {
-                session.login = None;
-                log::error!("error logging in: {}", e);
-                futures::future::err((
-                    state,
-                    e.into_handler_error().with_status(
-                        hyper::StatusCode::INTERNAL_SERVER_ERROR,
-                    ),
-                ))
-            }
+    r_login
+}
+
+// stores the result of logging in in the session, and responds with
+// `response` if it succeeded
+pub(crate) fn finish(
+    mut state: gotham::state::State,
+    res: std::result::Result<
+        Result<super::LoginState>,
+        tokio::sync::oneshot::error::RecvError,
+    >,
+    response: impl FnOnce(&super::LoginState) -> hyper::Response<hyper::Body>,
+) -> futures::future::FutureResult<
+    (gotham::state::State, hyper::Response<hyper::Body>),
+    (gotham::state::State, gotham::handler::HandlerError),
+> {
+    let session = gotham::middleware::session::SessionData::<
+        crate::web::SessionData,
+    >::borrow_mut_from(&mut state);
+    match res {
+        Ok(Ok(login)) => {
+            let response = response(&login);
+            session.login = Some(login);
+            futures::future::ok((state, response))
         }
-    }))
+        Ok(Err(e)) => {
+            session.login = None;
+            log::error!("error logging in: {}", e);
+            futures::future::err((
+                state,
+                e.into_handler_error()
+                    .with_status(hyper::StatusCode::INTERNAL_SERVER_ERROR),
+            ))
+        }
+        Err(e) => {
+            session.login = None;
+            log::error!("error logging in: {}", e);
+            futures::future::err((
+                state,
+                e.into_handler_error()
+                    .with_status(hyper::StatusCode::INTERNAL_SERVER_ERROR),
+            ))
+        }
+    }
 }
 
 pub(crate) struct Client<
@@ -108,6 +127,7 @@ pub(crate) struct Client<
 > {
     client: crate::client::Client<S>,
     auth: crate::protocol::Auth,
+    access_token: Option<String>,
     w_login: Option<tokio::sync::oneshot::Sender<Result<super::LoginState>>>,
 }
 
@@ -117,11 +137,13 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
     pub(crate) fn new(
         client: crate::client::Client<S>,
         auth: crate::protocol::Auth,
+        access_token: Option<String>,
         w_login: tokio::sync::oneshot::Sender<Result<super::LoginState>>,
     ) -> Self {
         Self {
             client,
             auth,
+            access_token,
             w_login: Some(w_login),
         }
     }
@@ -140,27 +162,42 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         >] = &[&Self::poll_client];
 
     fn poll_client(&mut self) -> component_future::Poll<(), Error> {
-        let res =
-            match component_future::try_ready!(self.client.poll()).unwrap() {
-                crate::client::Event::ServerMessage(msg) => match msg {
-                    crate::protocol::Message::Disconnected => {
-                        Err(Error::ServerDisconnected)
-                    }
-                    crate::protocol::Message::Error { msg } => {
-                        Err(Error::Server { message: msg })
-                    }
-                    crate::protocol::Message::LoggedIn { username } => {
-                        Ok(super::LoginState {
-                            auth: self.auth.clone(),
-                            username,
-                        })
-                    }
-                    _ => {
-                        return Ok(component_future::Async::DidWork);
-                    }
-                },
-                _ => unreachable!(),
-            };
+        let event = match self.client.poll() {
+            Ok(futures::Async::Ready(event)) => event.unwrap(),
+            Ok(futures::Async::NotReady) => {
+                return Ok(component_future::Async::NotReady)
+            }
+            Err(e) => {
+                // the request is waiting on us, so it needs to hear about
+                // errors (like the server timing out) too
+                self.w_login.take().unwrap().send(Err(e)).unwrap();
+                return Ok(component_future::Async::Ready(()));
+            }
+        };
+        let res = match event {
+            crate::client::Event::ServerMessage(msg) => match msg {
+                crate::protocol::Message::Disconnected => {
+                    Err(Error::ServerDisconnected)
+                }
+                crate::protocol::Message::ServerShutdown { reason } => {
+                    Err(Error::ServerShutdown { reason })
+                }
+                crate::protocol::Message::Error { msg } => {
+                    Err(Error::Server { message: msg })
+                }
+                crate::protocol::Message::LoggedIn { username } => {
+                    Ok(super::LoginState {
+                        auth: self.auth.clone(),
+                        username,
+                        access_token: self.access_token.clone(),
+                    })
+                }
+                _ => {
+                    return Ok(component_future::Async::DidWork);
+                }
+            },
+            _ => unreachable!(),
+        };
         self.w_login.take().unwrap().send(res).unwrap();
         Ok(component_future::Async::Ready(()))
     }
@@ -1,3 +1,6 @@
+use crate::prelude::*;
+
+use gotham::handler::IntoHandlerError as _;
 use gotham::state::FromState as _;
 use std::convert::TryFrom as _;
 
@@ -17,11 +20,17 @@ pub struct PathParts {
 )]
 pub struct QueryParams {
     code: String,
+    state: String,
 }
 
 pub fn run(
-    mut state: gotham::state::State,
-) -> (gotham::state::State, hyper::Response<hyper::Body>) {
+    state: gotham::state::State,
+) -> Box<
+    dyn futures::Future<
+            Item = (gotham::state::State, hyper::Response<hyper::Body>),
+            Error = (gotham::state::State, gotham::handler::HandlerError),
+        > + Send,
+> {
     let auth_type = {
         let path_parts = PathParts::borrow_from(&state);
         crate::protocol::AuthType::try_from(path_parts.method.as_str())
@@ -29,28 +38,88 @@ pub fn run(
     let auth_type = match auth_type {
         Ok(auth_type) => auth_type,
         Err(e) => {
-            return (
-                state,
-                hyper::Response::builder()
-                    .status(hyper::StatusCode::BAD_REQUEST)
-                    .body(hyper::Body::from(format!("{}", e)))
-                    .unwrap(),
-            );
+            return Box::new(futures::future::ok(bad_request(state, &e)));
         }
     };
-    let code = {
+    let (code, oauth_state) = {
         let query_params = QueryParams::borrow_from(&state);
-        query_params.code.clone()
+        (query_params.code.clone(), query_params.state.clone())
     };
+    let expected_oauth_state = gotham::middleware::session::SessionData::<
+        crate::web::SessionData,
+    >::borrow_from(&state)
+    .oauth_state
+    .clone();
+    if expected_oauth_state.as_ref() != Some(&oauth_state) {
+        let e = Error::InvalidOauthState;
+        return Box::new(futures::future::ok(bad_request(state, &e)));
+    }
 
-    // TODO
+    let config = crate::web::Config::borrow_from(&state).clone();
+    let oauth_config = config
+        .allowed_oauth_login_methods()
+        .find(|ty| *ty == auth_type)
+        .and_then(|ty| config.oauth_configs.get(&ty));
+    let client = if let Some(oauth_config) = oauth_config {
+        auth_type.oauth_client(oauth_config, None).unwrap()
+    } else {
+        let e = Error::AuthTypeNotAllowed { ty: auth_type };
+        return Box::new(futures::future::ok(bad_request(state, &e)));
+    };
+    let id = Some(client.user_id());
+    let auth = match auth_type {
+        crate::protocol::AuthType::RecurseCenter => {
+            crate::protocol::Auth::recurse_center(id)
+        }
+        crate::protocol::AuthType::Github => {
+            crate::protocol::Auth::github(id)
+        }
+        crate::protocol::AuthType::Google => {
+            crate::protocol::Auth::google(id)
+        }
+        crate::protocol::AuthType::Plain => unreachable!(),
+    };
 
+    Box::new(
+        client
+            .get_access_token_from_auth_code(&code)
+            .then(move |res| match res {
+                Ok(access_token) => futures::future::Either::A(
+                    super::login::login(&config, auth, Some(access_token))
+                        .then(move |res| {
+                            super::login::finish(state, res, |_| {
+                                hyper::Response::builder()
+                                    .status(hyper::StatusCode::FOUND)
+                                    // relative, so that this still works when we
+                                    // are served under a path prefix
+                                    .header(hyper::header::LOCATION, "../")
+                                    .body(hyper::Body::empty())
+                                    .unwrap()
+                            })
+                        }),
+                ),
+                Err(e) => {
+                    log::error!("error logging in: {}", e);
+                    futures::future::Either::B(futures::future::err((
+                        state,
+                        e.into_handler_error().with_status(
+                            hyper::StatusCode::INTERNAL_SERVER_ERROR,
+                        ),
+                    )))
+                }
+            }),
+    )
+}
+
+fn bad_request(
+    state: gotham::state::State,
+    e: &Error,
+) -> (gotham::state::State, hyper::Response<hyper::Body>) {
     (
         state,
         hyper::Response::builder()
-            .status(hyper::StatusCode::FOUND)
-            .header(hyper::header::LOCATION, "/")
-            .body(hyper::Body::empty())
+            .status(hyper::StatusCode::BAD_REQUEST)
+            .body(hyper::Body::from(format!("{}", e)))
             .unwrap(),
     )
 }
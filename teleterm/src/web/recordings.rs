@@ -0,0 +1,216 @@
+use crate::prelude::*;
+
+use gotham::state::FromState as _;
+use tokio_tungstenite::tungstenite;
+
+#[derive(
+    serde::Deserialize,
+    gotham_derive::StateData,
+    gotham_derive::StaticResponseExtender,
+)]
+pub struct PathParts {
+    id: String,
+}
+
+#[derive(serde::Serialize)]
+struct Recording {
+    id: String,
+    size: u64,
+    modified: u32,
+}
+
+pub fn list(
+    state: gotham::state::State,
+) -> (gotham::state::State, hyper::Response<hyper::Body>) {
+    let dir = match recordings_dir(&state) {
+        Ok(dir) => dir,
+        Err(response) => return (state, response),
+    };
+
+    match read_recordings(&dir) {
+        Ok(recordings) => {
+            let body = serde_json::to_string(&recordings).unwrap();
+            (state, hyper::Response::new(hyper::Body::from(body)))
+        }
+        Err(e) => {
+            log::warn!("error listing recordings: {}", e);
+            (
+                state,
+                empty_response(hyper::StatusCode::INTERNAL_SERVER_ERROR),
+            )
+        }
+    }
+}
+
+pub fn run(
+    mut state: gotham::state::State,
+) -> (gotham::state::State, hyper::Response<hyper::Body>) {
+    let dir = match recordings_dir(&state) {
+        Ok(dir) => dir,
+        Err(response) => return (state, response),
+    };
+    let id = PathParts::borrow_from(&state).id.clone();
+    let filename = dir.join(&id);
+    if !valid_id(&id) || !filename.is_file() {
+        return (state, empty_response(hyper::StatusCode::NOT_FOUND));
+    }
+
+    let body = hyper::Body::take_from(&mut state);
+    let headers = hyper::HeaderMap::take_from(&mut state);
+
+    if crate::web::ws::requested(&headers) {
+        let (response, stream) = match crate::web::ws::accept(&headers, body)
+        {
+            Ok(res) => res,
+            Err(_) => {
+                log::error!("failed to accept websocket request");
+                return (
+                    state,
+                    empty_response(hyper::StatusCode::BAD_REQUEST),
+                );
+            }
+        };
+
+        tokio::spawn(
+            play(&filename, stream.context(crate::error::WebSocketAccept))
+                .map_err(|e| log::warn!("error playing recording: {}", e)),
+        );
+
+        (state, response)
+    } else {
+        (
+            state,
+            hyper::Response::builder()
+                .header(
+                    hyper::header::CONTENT_TYPE,
+                    "application/octet-stream",
+                )
+                .header(
+                    hyper::header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", id),
+                )
+                .body(hyper::Body::wrap_stream(read_file(filename)))
+                .unwrap(),
+        )
+    }
+}
+
+// recordings can be large, so they are sent a chunk at a time as they are
+// read, and the reads happen on the blocking pool rather than on the
+// reactor. the response has already started by the time a read can fail,
+// so failures just cut it short.
+fn read_file(
+    filename: std::path::PathBuf,
+) -> impl futures::Stream<Item = bytes::Bytes, Error = std::io::Error> + Send
+{
+    let display = filename.display().to_string();
+    tokio::fs::File::open(filename)
+        .map(|file| {
+            tokio::codec::FramedRead::new(
+                file,
+                tokio::codec::BytesCodec::new(),
+            )
+        })
+        .flatten_stream()
+        .map(bytes::BytesMut::freeze)
+        .map_err(move |e| {
+            log::warn!("failed to read {}: {}", display, e);
+            e
+        })
+}
+
+// recordings are only available when a directory for them was configured,
+// and only to logged in users, since they can contain anything that was
+// streamed
+fn recordings_dir(
+    state: &gotham::state::State,
+) -> std::result::Result<std::path::PathBuf, hyper::Response<hyper::Body>> {
+    let session = gotham::middleware::session::SessionData::<
+        crate::web::SessionData,
+    >::borrow_from(state);
+    if session.login.is_none() {
+        return Err(empty_response(hyper::StatusCode::UNAUTHORIZED));
+    }
+
+    let config = crate::web::Config::borrow_from(state);
+    config
+        .recordings_dir
+        .clone()
+        .ok_or_else(|| empty_response(hyper::StatusCode::NOT_FOUND))
+}
+
+fn read_recordings(dir: &std::path::Path) -> std::io::Result<Vec<Recording>> {
+    let mut recordings = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let id = entry.file_name().to_string_lossy().to_string();
+        if !metadata.is_file() || !valid_id(&id) {
+            continue;
+        }
+        let modified = metadata.modified()?;
+        recordings.push(Recording {
+            id,
+            size: metadata.len(),
+            modified: modified
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as u32),
+        });
+    }
+    recordings.sort_by_key(|recording| std::cmp::Reverse(recording.modified));
+    Ok(recordings)
+}
+
+// ids are used directly as filenames (and in urls), so only allow names
+// which can't escape the recordings directory or need escaping
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && !id.starts_with('.')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+// sends the recording to the browser a frame at a time, with the same
+// timing that it was recorded with
+fn play(
+    filename: &std::path::Path,
+    stream: impl futures::Future<
+            Item = tokio_tungstenite::WebSocketStream<
+                hyper::upgrade::Upgraded,
+            >,
+            Error = Error,
+        > + Send,
+) -> impl futures::Future<Item = (), Error = Error> + Send {
+    let mut replay =
+        crate::replay::Replay::new(&filename.to_string_lossy(), 1.0);
+    replay.ignore_resizes();
+    let messages = replay
+        .filter_map(|event| {
+            if let tokio_pty_process_stream::Event::Output { data } = event {
+                Some(crate::protocol::Message::terminal_output(&data))
+            } else {
+                None
+            }
+        })
+        .and_then(|msg| {
+            serde_json::to_string(&msg)
+                .context(crate::error::SerializeMessage)
+                .map(tungstenite::Message::Text)
+        });
+
+    stream
+        .and_then(|stream| {
+            let (sink, _) = stream.split();
+            sink.sink_map_err(|e| Error::WebSocket { source: e })
+                .send_all(messages)
+        })
+        .map(|_| ())
+}
+
+fn empty_response(status: hyper::StatusCode) -> hyper::Response<hyper::Body> {
+    hyper::Response::builder()
+        .status(status)
+        .body(hyper::Body::empty())
+        .unwrap()
+}
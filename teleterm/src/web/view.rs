@@ -6,6 +6,10 @@ lazy_static_include::lazy_static_include_bytes!(
     pub INDEX_HTML_TMPL,
     "static/index.html.tmpl"
 );
+lazy_static_include::lazy_static_include_bytes!(
+    pub EMBED_HTML_TMPL,
+    "static/embed.html.tmpl"
+);
 lazy_static_include::lazy_static_include_bytes!(
     pub TELETERM_WEB_JS,
     "static/teleterm_web.js"
@@ -22,6 +26,7 @@ lazy_static_include::lazy_static_include_bytes!(
 handlebars_helper!(json: |x: object| serde_json::to_string(x).unwrap());
 
 pub const INDEX_HTML_TMPL_NAME: &str = "index";
+pub const EMBED_HTML_TMPL_NAME: &str = "embed";
 lazy_static::lazy_static! {
     pub static ref HANDLEBARS: handlebars::Handlebars = {
         let mut handlebars = handlebars::Handlebars::new();
@@ -33,5 +38,11 @@ lazy_static::lazy_static! {
             )
             .unwrap();
         handlebars
+            .register_template_string(
+                EMBED_HTML_TMPL_NAME,
+                String::from_utf8(EMBED_HTML_TMPL.to_vec()).unwrap(),
+            )
+            .unwrap();
+        handlebars
     };
 }
@@ -13,23 +13,35 @@ pub struct QueryParams {
 }
 
 pub fn run(
-    mut state: gotham::state::State,
+    state: gotham::state::State,
 ) -> (gotham::state::State, hyper::Response<hyper::Body>) {
     let session = gotham::middleware::session::SessionData::<
         crate::web::SessionData,
     >::borrow_from(&state);
-    let auth = if let Some(login) = &session.login {
-        login.auth.clone()
+    let (auth, access_token) = if let Some(login) = &session.login {
+        (login.auth.clone(), login.access_token.clone())
     } else {
         return (
             state,
             hyper::Response::builder()
-                .status(hyper::StatusCode::FORBIDDEN)
+                .status(hyper::StatusCode::UNAUTHORIZED)
                 .body(hyper::Body::empty())
                 .unwrap(),
         );
     };
+    let id = QueryParams::borrow_from(&state).id.clone();
+
+    watch(state, &auth, access_token, &id)
+}
 
+// connects the websocket request in `state` to the stream `id` on the
+// server, logged in with `auth`
+pub(crate) fn watch(
+    mut state: gotham::state::State,
+    auth: &crate::protocol::Auth,
+    access_token: Option<String>,
+    id: &str,
+) -> (gotham::state::State, hyper::Response<hyper::Body>) {
     let body = hyper::Body::take_from(&mut state);
     let headers = hyper::HeaderMap::take_from(&mut state);
     let config = crate::web::Config::borrow_from(&state);
@@ -50,27 +62,31 @@ pub fn run(
             }
         };
 
-        let query_params = QueryParams::borrow_from(&state);
-
-        let (_, address) = config.server_address;
-        let connector: crate::client::Connector<_> = Box::new(move || {
-            Box::new(
-                tokio::net::tcp::TcpStream::connect(&address)
-                    .context(crate::error::Connect { address }),
-            )
-        });
-        let client = crate::client::Client::raw(
+        let (host, address) = &config.server_address;
+        let connector = crate::connect::Connector::new(
+            host,
+            *address,
+            crate::connect::Source::Any,
+            None,
+            None,
+        );
+        let connector: crate::client::Connector<_> =
+            Box::new(move || connector.connect());
+        let mut client = crate::client::Client::raw(
             "teleterm-web",
             connector,
-            &auth,
+            auth,
             crate::protocol::AuthClient::Web,
         );
+        if let Some(access_token) = &access_token {
+            client.set_oauth_access_token(access_token);
+        }
 
         tokio::spawn(
             Connection::new(
                 gotham::state::request_id(&state),
                 client,
-                &query_params.id,
+                id,
                 ConnectionState::Connecting(Box::new(
                     stream.context(crate::error::WebSocketAccept),
                 )),
@@ -180,14 +196,32 @@ impl<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static>
         match msg {
             crate::protocol::Message::TerminalOutput { .. }
             | crate::protocol::Message::Disconnected
-            | crate::protocol::Message::Resize { .. } => {
+            | crate::protocol::Message::Resize { .. }
+            | crate::protocol::Message::Notice { .. } => {
                 let json = serde_json::to_string(msg)
                     .context(crate::error::SerializeMessage)?;
                 Ok(Some(tungstenite::Message::Text(json)))
             }
+            // the browser doesn't need to know why, just that the stream
+            // is gone
+            crate::protocol::Message::ServerShutdown { .. } => {
+                let json = serde_json::to_string(
+                    &crate::protocol::Message::disconnected(),
+                )
+                .context(crate::error::SerializeMessage)?;
+                Ok(Some(tungstenite::Message::Text(json)))
+            }
             crate::protocol::Message::LoggedIn { .. } => {
+                if self.client.has_capability("notice") {
+                    self.client.send_message(
+                        crate::protocol::Message::subscribe_notices(),
+                    );
+                }
                 self.client.send_message(
-                    crate::protocol::Message::start_watching(&self.watch_id),
+                    crate::protocol::Message::start_watching(
+                        &self.watch_id,
+                        0,
+                    ),
                 );
                 Ok(None)
             }